@@ -9,6 +9,7 @@ import {
 	LanguageClient,
 	LanguageClientOptions,
 	ServerOptions,
+	StaticFeature,
 } from "vscode-languageclient/node";
 
 import { getExtensionContext } from "./extension";
@@ -20,6 +21,24 @@ import util from "./util";
 let client: LanguageClient | undefined;
 let outputChannel: vscode.OutputChannel;
 
+/**
+	Lets the server know that this extension runs package managers itself,
+	using the `runPackageManager` command, which the server never runs.
+*/
+const packageManagerCommandsFeature: StaticFeature = {
+	fillClientCapabilities(capabilities) {
+		capabilities.experimental = {
+			...capabilities.experimental,
+			packageManagerCommands: true,
+		};
+	},
+	initialize() {},
+	getState() {
+		return { kind: "static" };
+	},
+	clear() {},
+};
+
 /**
 	Starts the language server.
 
@@ -108,6 +127,8 @@ export const startServer = async () => {
 		documentSelector: [
 			{ scheme: "file", language: "toml" },
 			{ scheme: "file", language: "json" },
+			{ scheme: "untitled", language: "toml" },
+			{ scheme: "untitled", language: "json" },
 		],
 		diagnosticCollectionName: "Tooling Language Server",
 		outputChannel,
@@ -128,6 +149,7 @@ export const startServer = async () => {
 		clientOptions
 	);
 
+	client.registerFeature(packageManagerCommandsFeature);
 	client.start();
 
 	// Listen for custom requests from server
@@ -1,9 +1,11 @@
 import * as vscode from "vscode";
 
 import authCommands from "./auth";
+import packageManagerCommands from "./packageManager";
 
 const ALL_COMMANDS = {
 	...authCommands,
+	...packageManagerCommands,
 };
 
 // https://stackoverflow.com/questions/51851677/how-to-get-argument-types-from-function-in-typescript
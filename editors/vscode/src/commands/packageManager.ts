@@ -0,0 +1,100 @@
+import * as path from "path";
+import * as vscode from "vscode";
+
+type PackageManager = "cargo" | "npm" | "wally";
+
+type PackageManagerAction =
+	| { kind: "update"; package: string }
+	| { kind: "install" };
+
+/**
+	The manifest file that each package manager may be run for.
+*/
+const MANIFEST_NAMES: Record<PackageManager, string> = {
+	cargo: "Cargo.toml",
+	npm: "package.json",
+	wally: "wally.toml",
+};
+
+// NOTE: Package names are passed as separate process arguments, never
+// through a shell, but are still limited to characters that names use
+const PACKAGE_NAME_PATTERN = /^[A-Za-z0-9@][A-Za-z0-9_.@/-]*$/;
+
+/**
+	Builds the command line for a package manager and action from a fixed
+	allowlist, or returns `undefined` if the combination is not allowed.
+*/
+const buildCommandLine = (
+	tool: PackageManager,
+	action: PackageManagerAction
+): [string, string[]] | undefined => {
+	if (tool === "cargo" && action.kind === "update") {
+		if (!PACKAGE_NAME_PATTERN.test(action.package)) {
+			return undefined;
+		}
+		return ["cargo", ["update", "--package", action.package]];
+	} else if (tool === "cargo" && action.kind === "install") {
+		return ["cargo", ["fetch"]];
+	} else if (
+		(tool === "npm" || tool === "wally") &&
+		action.kind === "install"
+	) {
+		return [tool, ["install"]];
+	}
+	return undefined;
+};
+
+export const runPackageManager = async (args: {
+	tool: PackageManager;
+	action: PackageManagerAction;
+	manifest: string;
+}) => {
+	const tool = args?.tool;
+	const built =
+		Object.prototype.hasOwnProperty.call(MANIFEST_NAMES, tool) && args.action
+			? buildCommandLine(tool, args.action)
+			: undefined;
+	if (built === undefined) {
+		vscode.window.showErrorMessage(
+			"Refusing to run an unknown package manager command."
+		);
+		return;
+	}
+
+	// The command only runs next to a manifest for the same tool,
+	// and only if that manifest is inside of an open workspace folder
+	let manifest: vscode.Uri;
+	try {
+		manifest = vscode.Uri.parse(args.manifest, true);
+	} catch {
+		return;
+	}
+	const folder = vscode.workspace.getWorkspaceFolder(manifest);
+	if (
+		manifest.scheme !== "file" ||
+		folder === undefined ||
+		path.basename(manifest.fsPath) !== MANIFEST_NAMES[tool]
+	) {
+		vscode.window.showErrorMessage(
+			"Package managers can only be run for manifests in an open workspace folder."
+		);
+		return;
+	}
+
+	const [program, programArgs] = built;
+	const execution = new vscode.ProcessExecution(program, programArgs, {
+		cwd: path.dirname(manifest.fsPath),
+	});
+	const task = new vscode.Task(
+		{ type: "process" },
+		folder,
+		[program, ...programArgs].join(" "),
+		"Tooling Language Server",
+		execution
+	);
+	await vscode.tasks.executeTask(task);
+};
+
+export default {
+	runPackageManager,
+};
@@ -0,0 +1,97 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+
+use crate::clients::{Clients, SystemCredentials};
+use crate::parser::TreeSitterLanguage;
+use crate::server::{load_project_config, Config, PROJECT_CONFIG_FILE_NAME};
+
+/**
+    Checks the setup of the language server, and prints a report to include in
+    bug reports. Tokens are never printed, only where they were found, if anywhere.
+
+    Exits with an error if crates.io, GitHub, or the npm registry can not be reached.
+*/
+#[derive(Debug, Clone, Parser)]
+pub struct DoctorCommand {
+    /** The directory to read project config from, defaults to the current directory */
+    pub dir: Option<PathBuf>,
+    #[arg(long, env)]
+    pub github_token: Option<String>,
+}
+
+impl DoctorCommand {
+    pub async fn run(self) -> Result<()> {
+        let dir = match self.dir {
+            Some(dir) => dir,
+            None => std::env::current_dir().context("Failed to get the current directory")?,
+        };
+
+        println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        println!("tree-sitter ABI {}", tree_sitter::LANGUAGE_VERSION);
+        for language in [TreeSitterLanguage::Json, TreeSitterLanguage::Toml] {
+            println!(
+                "- {language:?} grammar ABI {}",
+                language.language().version()
+            );
+        }
+
+        // Config is read the same way as the server reads it, but
+        // without any options, since those are sent by the client
+        println!();
+        let project_config = load_project_config(&[&dir]).await;
+        match &project_config {
+            Some(_) => println!(
+                "Project config: {}",
+                dir.join(PROJECT_CONFIG_FILE_NAME).display()
+            ),
+            None => println!("Project config: none in {}", dir.display()),
+        }
+        let config = Config::from_sources_lossy(project_config, None);
+        println!("{config:#?}");
+
+        let clients = Clients::new();
+        let credentials = if config.use_system_credentials {
+            SystemCredentials::load().await
+        } else {
+            SystemCredentials::default()
+        };
+        let token_source = if let Some(token) = &self.github_token {
+            clients.github.set_auth_token(token);
+            Some("--github-token or GITHUB_TOKEN")
+        } else {
+            clients.set_system_credentials(credentials);
+            clients
+                .github
+                .has_auth_token()
+                .then_some("system credentials")
+        };
+
+        println!();
+        match token_source {
+            Some(source) => println!("GitHub token: configured, from {source}"),
+            None => println!("GitHub token: not configured, requests to GitHub are rate limited"),
+        }
+        println!("Caches: in memory only, cleared whenever the server exits");
+
+        println!();
+        let mut unreachable = Vec::new();
+        for contact in clients.check_reachability().await {
+            if contact.reached {
+                println!("Connected to {}", contact.source);
+            } else {
+                println!("Failed to connect to {}", contact.source);
+                unreachable.push(contact.source.to_string());
+            }
+        }
+
+        if !unreachable.is_empty() {
+            bail!(
+                "Failed to connect to {} - check your network and proxy settings",
+                unreachable.join(", ")
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,354 @@
+use std::collections::{HashSet, VecDeque};
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, ValueEnum};
+use serde_json::json;
+use tokio::fs;
+use url::Url;
+
+use crate::parser::{
+    query_cargo_toml_dependencies, query_package_json_dependencies, query_rokit_toml_dependencies,
+    query_wally_toml_dependencies, SimpleDependency, TreeSitterDocument,
+};
+use crate::tools::{Lockfile, ToolName};
+use crate::Versioned;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GraphFormat {
+    #[default]
+    Json,
+    Dot,
+}
+
+/**
+    Outputs the dependencies of a manifest, as JSON or as a Graphviz DOT graph.
+*/
+#[derive(Debug, Clone, Parser)]
+pub struct GraphCommand {
+    /** The manifest to output dependencies for, such as `Cargo.toml` */
+    pub manifest: PathBuf,
+    /** The format to output the dependencies in */
+    #[arg(long, value_enum, default_value_t)]
+    pub format: GraphFormat,
+    /** Also output transitive dependencies, using the lockfile of the manifest */
+    #[arg(long)]
+    pub transitive: bool,
+}
+
+impl GraphCommand {
+    pub async fn run(self) -> Result<()> {
+        let path = fs::canonicalize(&self.manifest)
+            .await
+            .with_context(|| format!("Failed to find '{}'", self.manifest.display()))?;
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        let Ok(tool) = file_name.parse::<ToolName>() else {
+            bail!("Unsupported manifest '{}'", self.manifest.display());
+        };
+
+        let contents = fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("Failed to read '{}'", self.manifest.display()))?;
+        let Some(doc) = TreeSitterDocument::new_file(&path, contents) else {
+            bail!("Unsupported manifest '{}'", self.manifest.display());
+        };
+
+        // Lockfiles are optional for direct dependencies, and only
+        // give them exact versions, but required for transitive ones
+        let lockfile_uri = match Url::from_file_path(&path) {
+            Ok(uri) => tool.lockfile_uri(&uri).await,
+            Err(()) => None,
+        };
+        let lockfile_path = lockfile_uri.and_then(|uri| uri.to_file_path().ok());
+        let lockfile = match &lockfile_path {
+            Some(lockfile_path) => {
+                let contents = fs::read_to_string(lockfile_path)
+                    .await
+                    .with_context(|| format!("Failed to read '{}'", lockfile_path.display()))?;
+                Some(Lockfile::parse(tool, &contents)?)
+            }
+            None if self.transitive => {
+                bail!("Missing lockfile for '{}'", self.manifest.display());
+            }
+            None => None,
+        };
+
+        let root = self.manifest.display().to_string();
+        let graph = DependencyGraph::build(
+            root,
+            direct_dependencies(tool, &doc),
+            lockfile.as_ref(),
+            self.transitive,
+        );
+        match self.format {
+            GraphFormat::Json => println!("{}", graph.to_json()),
+            GraphFormat::Dot => println!("{}", graph.to_dot()),
+        }
+
+        Ok(())
+    }
+}
+
+/**
+    Gets the name and version text of all direct dependencies in a manifest.
+*/
+fn direct_dependencies(tool: ToolName, doc: &TreeSitterDocument) -> Vec<(String, String)> {
+    let simple = |deps: Vec<SimpleDependency>| {
+        deps.into_iter()
+            .filter_map(|dep| {
+                let spec = dep.parsed_spec();
+                let name = format!(
+                    "{}/{}",
+                    spec.author.unquoted(),
+                    spec.name.as_ref()?.unquoted()
+                );
+                Some((name, spec.raw_version_string()))
+            })
+            .collect()
+    };
+    match tool {
+        ToolName::Aftman | ToolName::Rokit => simple(query_rokit_toml_dependencies(doc)),
+        ToolName::Wally => simple(query_wally_toml_dependencies(doc)),
+        ToolName::Cargo => query_cargo_toml_dependencies(doc)
+            .into_iter()
+            .map(|dep| {
+                (
+                    dep.package_name().unquoted().to_string(),
+                    dep.raw_version_string(),
+                )
+            })
+            .collect(),
+        ToolName::Npm => query_package_json_dependencies(doc)
+            .into_iter()
+            .map(|dep| {
+                (
+                    dep.package_name().unquoted().to_string(),
+                    dep.raw_version_string(),
+                )
+            })
+            .collect(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct GraphNode {
+    name: String,
+    version: String,
+}
+
+impl GraphNode {
+    fn new(name: impl Into<String>, version: impl Into<String>) -> Self {
+        let version = version.into();
+        Self {
+            name: name.into(),
+            version: if version.trim().is_empty() {
+                String::from("*")
+            } else {
+                version
+            },
+        }
+    }
+
+    fn label(&self) -> String {
+        format!("{}@{}", self.name, self.version)
+    }
+}
+
+/**
+    A graph of dependencies, with the manifest itself as its root.
+
+    Nodes are unique by name and version, and edges only
+    ever point from the root or a node, to another node.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DependencyGraph {
+    root: String,
+    nodes: Vec<GraphNode>,
+    edges: Vec<(Option<usize>, usize)>,
+}
+
+impl DependencyGraph {
+    fn build(
+        root: String,
+        direct: Vec<(String, String)>,
+        lockfile: Option<&Lockfile>,
+        transitive: bool,
+    ) -> Self {
+        let mut graph = Self {
+            root,
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        };
+
+        let mut queue = VecDeque::new();
+        for (name, version) in direct {
+            let locked = lockfile.and_then(|l| l.find(&name, Some(version.as_str())));
+            let node = match locked {
+                Some(locked) => GraphNode::new(&locked.name, &locked.version),
+                None => GraphNode::new(name, version),
+            };
+            let index = graph.add_node(node);
+            graph.add_edge(None, index);
+            if let Some(locked) = locked {
+                queue.push_back((index, locked));
+            }
+        }
+
+        let Some(lockfile) = lockfile.filter(|_| transitive) else {
+            return graph;
+        };
+        let mut visited = HashSet::new();
+        while let Some((index, package)) = queue.pop_front() {
+            if !visited.insert(index) {
+                continue;
+            }
+            for (name, version) in &package.dependencies {
+                let Some(dep) = lockfile.find(name, version.as_deref()) else {
+                    continue;
+                };
+                let dep_index = graph.add_node(GraphNode::new(&dep.name, &dep.version));
+                graph.add_edge(Some(index), dep_index);
+                queue.push_back((dep_index, dep));
+            }
+        }
+
+        graph
+    }
+
+    fn add_node(&mut self, node: GraphNode) -> usize {
+        match self.nodes.iter().position(|n| *n == node) {
+            Some(index) => index,
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn add_edge(&mut self, from: Option<usize>, to: usize) {
+        if !self.edges.contains(&(from, to)) {
+            self.edges.push((from, to));
+        }
+    }
+
+    fn label(&self, index: Option<usize>) -> String {
+        match index {
+            Some(index) => self.nodes[index].label(),
+            None => self.root.clone(),
+        }
+    }
+
+    fn to_json(&self) -> String {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| {
+                json!({
+                    "label": node.label(),
+                    "name": node.name,
+                    "version": node.version,
+                })
+            })
+            .collect::<Vec<_>>();
+        let edges = self
+            .edges
+            .iter()
+            .map(|(from, to)| {
+                json!({
+                    "from": self.label(*from),
+                    "to": self.label(Some(*to)),
+                })
+            })
+            .collect::<Vec<_>>();
+        let value = json!({
+            "root": self.root,
+            "nodes": nodes,
+            "edges": edges,
+        });
+        serde_json::to_string_pretty(&value).expect("graph is always serializable")
+    }
+
+    fn to_dot(&self) -> String {
+        let quote =
+            |label: String| format!("\"{}\"", label.replace('\\', "\\\\").replace('"', "\\\""));
+
+        let mut lines = vec![String::from("digraph dependencies {")];
+        lines.push(format!("    {} [shape=box];", quote(self.root.clone())));
+        for node in &self.nodes {
+            lines.push(format!("    {};", quote(node.label())));
+        }
+        for (from, to) in &self.edges {
+            lines.push(format!(
+                "    {} -> {};",
+                quote(self.label(*from)),
+                quote(self.label(Some(*to)))
+            ));
+        }
+        lines.push(String::from("}"));
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lockfile() -> Lockfile {
+        let contents = r#"
+        [[package]]
+        name = "app"
+        version = "0.1.0"
+        dependencies = ["serde", "tokio"]
+
+        [[package]]
+        name = "serde"
+        version = "1.0.200"
+
+        [[package]]
+        name = "tokio"
+        version = "1.37.0"
+        dependencies = ["serde"]
+        "#;
+        Lockfile::parse(ToolName::Cargo, contents).unwrap()
+    }
+
+    fn direct() -> Vec<(String, String)> {
+        vec![
+            (String::from("serde"), String::from("1.0")),
+            (String::from("tokio"), String::from("1")),
+            (String::from("local"), String::new()),
+        ]
+    }
+
+    #[test]
+    fn test_direct_only() {
+        let lockfile = lockfile();
+        let graph =
+            DependencyGraph::build(String::from("Cargo.toml"), direct(), Some(&lockfile), false);
+        let labels = graph.nodes.iter().map(GraphNode::label).collect::<Vec<_>>();
+        assert_eq!(labels, vec!["serde@1.0.200", "tokio@1.37.0", "local@*"]);
+        assert_eq!(graph.edges.len(), 3);
+    }
+
+    #[test]
+    fn test_transitive() {
+        let lockfile = lockfile();
+        let graph =
+            DependencyGraph::build(String::from("Cargo.toml"), direct(), Some(&lockfile), true);
+        assert_eq!(graph.nodes.len(), 3);
+        assert!(graph.edges.contains(&(Some(1), 0)));
+        assert_eq!(graph.edges.len(), 4);
+    }
+
+    #[test]
+    fn test_dot() {
+        let graph = DependencyGraph::build(String::from("Cargo.toml"), direct(), None, false);
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph dependencies {"));
+        assert!(dot.contains("    \"Cargo.toml\" -> \"serde@1.0\";"));
+        assert!(dot.contains("    \"Cargo.toml\" -> \"local@*\";"));
+    }
+}
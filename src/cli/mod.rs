@@ -1,14 +1,20 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 
+mod doctor;
+mod graph;
 mod serve;
 mod tracing;
 
+use doctor::*;
+use graph::*;
 use serve::*;
 use tracing::*;
 
 #[derive(Debug, Clone, Subcommand)]
 pub enum CliSubcommand {
+    Doctor(DoctorCommand),
+    Graph(GraphCommand),
     Serve(ServeCommand),
 }
 
@@ -19,15 +25,27 @@ pub struct Cli {
     subcommand: CliSubcommand,
 }
 
+#[allow(clippy::new_without_default)]
 impl Cli {
     pub fn new() -> Self {
         Self::parse()
     }
 
-    pub async fn run(self) -> Result<()> {
-        setup_tracing();
+    /**
+        Runs the subcommand, and returns the code that the process should exit
+        with, which is only non-zero when a client exits the server without
+        having shut it down first.
+    */
+    pub async fn run(self) -> Result<i32> {
+        let log_format = match &self.subcommand {
+            CliSubcommand::Serve(cmd) => cmd.log_format,
+            CliSubcommand::Doctor(_) | CliSubcommand::Graph(_) => LogFormat::default(),
+        };
+        setup_tracing(log_format);
 
         match self.subcommand {
+            CliSubcommand::Doctor(cmd) => cmd.run().await.map(|()| 0),
+            CliSubcommand::Graph(cmd) => cmd.run().await.map(|()| 0),
             CliSubcommand::Serve(cmd) => cmd.run().await,
         }
     }
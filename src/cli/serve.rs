@@ -3,6 +3,9 @@ use clap::Parser;
 use tracing::debug;
 
 use crate::server::{Server, ServerArguments, Transport};
+use crate::tools::ToolName;
+
+use super::LogFormat;
 
 #[derive(Debug, Clone, Parser)]
 pub struct ServeCommand {
@@ -12,10 +15,20 @@ pub struct ServeCommand {
     pub stdio: bool,
     #[arg(long, env)]
     pub github_token: Option<String>,
+    #[arg(long)]
+    pub metrics: bool,
+    #[arg(long, value_enum, default_value_t)]
+    pub log_format: LogFormat,
+    /**
+        The tools to enable, separated by commas, such as `rokit,wally`.
+        All tools are enabled if none are given.
+    */
+    #[arg(long, value_delimiter = ',', value_parser = parse_tool_name)]
+    pub tools: Vec<ToolName>,
 }
 
 impl ServeCommand {
-    pub async fn run(self) -> Result<()> {
+    pub async fn run(self) -> Result<i32> {
         let transport = if let Some(port) = self.socket {
             Some(Transport::Socket(port))
         } else if self.stdio {
@@ -27,18 +40,30 @@ impl ServeCommand {
         let args = ServerArguments {
             transport: transport.unwrap_or_default(),
             github_token: self.github_token,
+            metrics: self.metrics,
+            tools: if self.tools.is_empty() {
+                ToolName::all()
+            } else {
+                self.tools
+            },
         };
 
         debug!(
-            "Parsed arguments\n\ttransport: {}\n\tgithub_token: {}",
+            "Parsed arguments\n\ttransport: {}\n\tgithub_token: {}\n\tmetrics: {}\n\ttools: {:?}",
             args.transport,
             if args.github_token.is_some() {
                 "Some(_)"
             } else {
                 "None"
             },
+            args.metrics,
+            args.tools,
         );
 
         Server::new(args).serve().await
     }
 }
+
+fn parse_tool_name(s: &str) -> Result<ToolName, String> {
+    s.parse().map_err(|e| format!("{e} '{s}'"))
+}
@@ -1,5 +1,6 @@
 use std::io::{stderr, IsTerminal};
 
+use clap::ValueEnum;
 use tracing_subscriber::filter::{EnvFilter, LevelFilter};
 
 #[cfg(debug_assertions)]
@@ -7,7 +8,22 @@ const IS_DEBUG: bool = true;
 #[cfg(not(debug_assertions))]
 const IS_DEBUG: bool = false;
 
-pub fn setup_tracing() {
+/**
+    The format that logs are written to stderr in.
+
+    Secrets such as tokens are never logged in the first place, so
+    they are kept out of logs in every format, not only the default one.
+*/
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /** Compact lines that are easy for humans to read */
+    #[default]
+    Human,
+    /** One JSON object per line, for log aggregation */
+    Json,
+}
+
+pub fn setup_tracing(format: LogFormat) {
     let tracing_filter = EnvFilter::builder()
         .with_default_directive(LevelFilter::INFO.into())
         .from_env_lossy()
@@ -19,15 +35,26 @@ pub fn setup_tracing() {
         .add_directive("rustls=info".parse().unwrap())
         .add_directive("reqwest=info".parse().unwrap());
 
-    tracing_subscriber::fmt()
-        .compact()
+    // Stdio transport takes up stdout, so emit output to stderr
+    let builder = tracing_subscriber::fmt()
         .with_env_filter(tracing_filter)
-        .without_time()
-        .with_target(IS_DEBUG)
-        .with_level(true)
-        .with_ansi(stderr().is_terminal())
-        .with_writer(stderr) // Stdio transport takes up stdout, so emit output to stderr
-        .init();
+        .with_writer(stderr);
+    match format {
+        LogFormat::Human => builder
+            .compact()
+            .without_time()
+            .with_target(IS_DEBUG)
+            .with_level(true)
+            .with_ansi(stderr().is_terminal())
+            .init(),
+        LogFormat::Json => builder
+            .json()
+            .flatten_event(true)
+            .with_target(true)
+            .with_level(true)
+            .with_ansi(false)
+            .init(),
+    }
 
     std::panic::set_hook(Box::new(tracing_panic::panic_hook));
 }
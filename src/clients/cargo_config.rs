@@ -0,0 +1,185 @@
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use tokio::fs;
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::util::cargo_home_dir;
+
+/**
+    A single Cargo config file that was read and parsed.
+*/
+#[derive(Debug, Clone)]
+pub struct CargoConfigFile {
+    pub path: PathBuf,
+    pub table: toml::Table,
+}
+
+impl CargoConfigFile {
+    /**
+        Returns the directory that relative paths in the config file are
+        relative to, which is the directory that contains its `.cargo` directory.
+    */
+    pub fn base_dir(&self) -> PathBuf {
+        self.path
+            .parent()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .unwrap_or_default()
+    }
+}
+
+/**
+    Parsed Cargo config files that apply to manifests, cached by the directory of
+    each manifest, since they are needed for every single dependency in it.
+
+    Config files are searched for the same way Cargo does it, meaning
+    in `.cargo` directories of the manifest directory and any of its
+    ancestors, as well as in the Cargo home directory, with the
+    config files that are closest to the manifest coming first.
+
+    The vendor directory of each manifest is also kept here, since it is
+    usually found through the config files, and needed just as often.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct CargoConfigs {
+    inner: Arc<DashMap<PathBuf, Arc<Vec<CargoConfigFile>>>>,
+    vendor_dirs: Arc<DashMap<PathBuf, Option<PathBuf>>>,
+}
+
+impl CargoConfigs {
+    pub async fn for_manifest(&self, manifest_uri: &Url) -> Arc<Vec<CargoConfigFile>> {
+        let Ok(manifest_path) = manifest_uri.to_file_path() else {
+            return Arc::default();
+        };
+        let Some(manifest_dir) = manifest_path.parent() else {
+            return Arc::default();
+        };
+
+        if let Some(configs) = self.inner.get(manifest_dir) {
+            return Arc::clone(&configs);
+        }
+
+        let configs = Arc::new(read_config_files(&manifest_path).await);
+        self.inner
+            .insert(manifest_dir.to_path_buf(), Arc::clone(&configs));
+        configs
+    }
+
+    /**
+        Gets the vendor directory of the manifest at the given uri, using
+        `find` to look for it if it has not been looked for yet, and
+        remembering if there is none, which is the most common case.
+    */
+    pub async fn vendor_dir<F>(&self, manifest_uri: &Url, find: F) -> Option<PathBuf>
+    where
+        F: Future<Output = Option<PathBuf>>,
+    {
+        let manifest_path = manifest_uri.to_file_path().ok()?;
+        let manifest_dir = manifest_path.parent()?;
+        if let Some(found) = self.vendor_dirs.get(manifest_dir) {
+            return found.clone();
+        }
+
+        let found = find.await;
+        self.vendor_dirs
+            .insert(manifest_dir.to_path_buf(), found.clone());
+        found
+    }
+
+    /**
+        Clears all cached config files if the given uri is a Cargo config file,
+        and all cached vendor directories if it is a config file or a lockfile,
+        since a `vendor` directory is only used if it is next to a lockfile.
+
+        Config files in the Cargo home directory apply to every manifest,
+        so there is no point in trying to only clear some of them.
+    */
+    pub fn invalidate(&self, uri: &Url) {
+        if is_config_file(uri) {
+            debug!("Cargo config changed, clearing cached Cargo configs");
+            self.clear();
+        } else if uri
+            .to_file_path()
+            .is_ok_and(|path| path.file_name().is_some_and(|name| name == "Cargo.lock"))
+        {
+            self.vendor_dirs.clear();
+        }
+    }
+
+    pub fn clear(&self) {
+        self.inner.clear();
+        self.vendor_dirs.clear();
+    }
+}
+
+/**
+    Returns the paths that Cargo looks for config files at, for
+    the manifest at the given path, with the closest ones first.
+*/
+pub fn config_file_paths(manifest_path: &Path) -> Vec<PathBuf> {
+    let mut dirs = manifest_path
+        .ancestors()
+        .skip(1)
+        .map(|dir| dir.join(".cargo"))
+        .collect::<Vec<_>>();
+
+    if let Some(cargo_home) = cargo_home_dir() {
+        if !dirs.contains(&cargo_home) {
+            dirs.push(cargo_home);
+        }
+    }
+
+    dirs.into_iter()
+        .flat_map(|dir| [dir.join("config.toml"), dir.join("config")])
+        .collect()
+}
+
+async fn read_config_files(manifest_path: &Path) -> Vec<CargoConfigFile> {
+    let mut configs = Vec::new();
+    for path in config_file_paths(manifest_path) {
+        let Ok(contents) = fs::read_to_string(&path).await else {
+            continue;
+        };
+        match contents.parse::<toml::Table>() {
+            Ok(table) => configs.push(CargoConfigFile { path, table }),
+            Err(e) => {
+                warn!("Failed to parse Cargo config at '{}' - {e}", path.display());
+            }
+        }
+    }
+    configs
+}
+
+fn is_config_file(uri: &Url) -> bool {
+    let Ok(path) = uri.to_file_path() else {
+        return false;
+    };
+    let in_cargo_dir = path
+        .parent()
+        .and_then(Path::file_name)
+        .is_some_and(|dir| dir == ".cargo")
+        || path.parent() == cargo_home_dir().as_deref();
+    let named_config = path
+        .file_name()
+        .is_some_and(|name| name == "config.toml" || name == "config");
+    in_cargo_dir && named_config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_config_file() {
+        let uri = |path: &str| Url::parse(&format!("file://{path}")).unwrap();
+        assert!(is_config_file(&uri("/project/.cargo/config.toml")));
+        assert!(is_config_file(&uri("/project/.cargo/config")));
+        assert!(!is_config_file(&uri("/project/config.toml")));
+        assert!(!is_config_file(&uri("/project/.cargo/credentials.toml")));
+        assert!(!is_config_file(&uri("/project/Cargo.lock")));
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use async_channel::{unbounded, Receiver, Sender};
+use tracing::debug;
+
+use crate::util::{RequestError, RequestResult};
+
+/**
+    A remote source that the clients fetch data from.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContactSource {
+    Crates,
+    Github,
+    Npm,
+}
+
+impl fmt::Display for ContactSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Crates => write!(f, "crates.io"),
+            Self::Github => write!(f, "GitHub"),
+            Self::Npm => write!(f, "the npm registry"),
+        }
+    }
+}
+
+/**
+    The outcome of the first request made to a source.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Contact {
+    pub source: ContactSource,
+    pub reached: bool,
+}
+
+/**
+    The last known outcome for each source, to only let users know about
+    a source when it changes between being reachable and unreachable.
+
+    A source may be contacted more than once per session, such as when
+    the client for Rokit tool sources is created again for a new API url.
+*/
+#[derive(Debug, Default)]
+pub struct ContactStates {
+    reached: HashMap<ContactSource, bool>,
+}
+
+impl ContactStates {
+    /**
+        Stores the outcome of the given contact, returning
+        `true` if it differs from the last known outcome.
+    */
+    pub fn update(&mut self, contact: Contact) -> bool {
+        self.reached.insert(contact.source, contact.reached) != Some(contact.reached)
+    }
+}
+
+/**
+    Receives the outcome of the first request made to each
+    source, which happens at most once per source per session.
+*/
+#[derive(Debug, Clone)]
+pub struct Contacts {
+    tx: Sender<Contact>,
+    rx: Receiver<Contact>,
+}
+
+impl Contacts {
+    pub fn new() -> Self {
+        let (tx, rx) = unbounded();
+        Self { tx, rx }
+    }
+
+    pub fn tracker(&self, source: ContactSource) -> ContactTracker {
+        ContactTracker {
+            source,
+            contacted: Arc::new(AtomicBool::new(false)),
+            tx: self.tx.clone(),
+        }
+    }
+
+    pub fn receiver(&self) -> Receiver<Contact> {
+        self.rx.clone()
+    }
+}
+
+impl Default for Contacts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/**
+    Checks if the source of a request could be reached, using the same rules
+    as `ContactTracker::report`, or returns `None` if it is not known.
+*/
+pub(super) fn request_reached<T>(result: &RequestResult<T>) -> Option<bool> {
+    match result {
+        Ok(_) => Some(true),
+        Err(e) if e.is_unreachable_error() => Some(false),
+        Err(RequestError::Response(_)) => Some(true),
+        Err(_) => None,
+    }
+}
+
+/**
+    Tracks if a request has been made to a single source yet.
+
+    This tracker is cheaply cloneable, and all clones share the same state.
+*/
+#[derive(Debug, Clone)]
+pub struct ContactTracker {
+    source: ContactSource,
+    contacted: Arc<AtomicBool>,
+    tx: Sender<Contact>,
+}
+
+impl ContactTracker {
+    /**
+        Reports the result of a request, if it is the first request to the source.
+
+        Any response at all, even an error status such as not found, means
+        that the source could be reached - only errors from the client
+        itself, or from the server being unavailable, mean that it could not.
+    */
+    pub fn report<T>(&self, result: &RequestResult<T>) {
+        let Some(reached) = request_reached(result) else {
+            return;
+        };
+        if !self.contacted.swap(true, Ordering::SeqCst) {
+            debug!("First contact with {} - reached: {reached}", self.source);
+            let contact = Contact {
+                source: self.source,
+                reached,
+            };
+            self.tx.try_send(contact).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contact_states() {
+        let contacts = Contacts::new();
+        let receiver = contacts.receiver();
+        let mut states = ContactStates::default();
+
+        // Trackers that are created again report their first contact again
+        let github = contacts.tracker(ContactSource::Github);
+        github.report(&Ok(()));
+        github.report(&Ok(()));
+        contacts.tracker(ContactSource::Github).report(&Ok(()));
+        contacts
+            .tracker(ContactSource::Crates)
+            .report::<()>(&Err(RequestError::Client(String::new())));
+
+        let mut changed = Vec::new();
+        while let Ok(contact) = receiver.try_recv() {
+            changed.push(states.update(contact));
+        }
+        assert_eq!(changed, [true, false, true]);
+
+        let reached_again = Contact {
+            source: ContactSource::Crates,
+            reached: true,
+        };
+        assert!(states.update(reached_again));
+    }
+}
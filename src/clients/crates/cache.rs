@@ -1,3 +1,8 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use dashmap::DashMap;
+
 use crate::util::*;
 
 use super::models::*;
@@ -5,16 +10,40 @@ use super::models::*;
 #[derive(Debug, Clone)]
 pub(super) struct CratesCache {
     pub index_metadatas: RequestCacheMap<RequestResult<Vec<IndexMetadata>>>,
+    pub index_lines: RequestCacheMap<RequestResult<IndexLines>>,
     pub crate_datas: RequestCacheMap<RequestResult<CrateDataSingle>>,
     pub crate_search: RequestCacheMap<RequestResult<CrateDataMulti>>,
+    pub vendored_dirs: Arc<DashMap<PathBuf, Arc<Vec<(String, PathBuf)>>>>,
 }
 
 impl CratesCache {
-    pub fn new() -> Self {
+    pub fn new(metrics: &Metrics) -> Self {
         Self {
-            index_metadatas: RequestCacheMap::new(60, 15),
-            crate_datas: RequestCacheMap::new(240, 120),
-            crate_search: RequestCacheMap::new(480, 240),
+            // NOTE: Versions are shown directly in completions, so they are
+            // kept for the session and only refreshed when invalidated
+            index_metadatas: RequestCacheMap::for_session().with_metrics(metrics),
+            // NOTE: Raw index files can be large, and are only kept
+            // around for a short while, in a cache with a bounded size
+            index_lines: RequestCacheMap::new(10, 5).with_metrics(metrics),
+            crate_datas: RequestCacheMap::new(240, 120).with_metrics(metrics),
+            crate_search: RequestCacheMap::new(480, 240).with_metrics(metrics),
+            // NOTE: Vendored crates are listed once per vendor directory, and
+            // change together with the lockfile, same as any other versions
+            vendored_dirs: Arc::default(),
         }
     }
+
+    pub fn invalidate(&self) {
+        self.index_metadatas.invalidate();
+        self.index_lines.invalidate();
+        self.crate_datas.invalidate();
+        self.crate_search.invalidate();
+        self.vendored_dirs.clear();
+    }
+
+    pub fn invalidate_versions(&self) {
+        self.index_metadatas.invalidate();
+        self.index_lines.invalidate();
+        self.vendored_dirs.clear();
+    }
 }
@@ -12,11 +12,14 @@ use tracing::error;
 
 use crate::util::*;
 
+use super::contact::ContactTracker;
+
 mod cache;
 use cache::*;
 
 mod consts;
 mod requests;
+mod vendor;
 
 pub mod models;
 
@@ -26,21 +29,47 @@ pub struct CratesClient {
     crawl_limit_tx: Sender<()>,
     crawl_limit_rx: Receiver<()>,
     crawl_limited: Arc<AtomicBool>,
+    limiter: RequestLimiter,
+    fetcher: Fetcher,
+    contact: ContactTracker,
 }
 
 impl CratesClient {
-    pub fn new() -> Self {
+    pub fn new(
+        limiter: RequestLimiter,
+        fetcher: Fetcher,
+        contact: ContactTracker,
+        metrics: &Metrics,
+    ) -> Self {
         let (crawl_limit_tx, crawl_limit_rx) = unbounded();
         Self {
-            cache: CratesCache::new(),
+            cache: CratesCache::new(metrics),
             crawl_limit_tx,
             crawl_limit_rx,
             crawl_limited: Arc::new(AtomicBool::new(false)),
+            limiter,
+            fetcher,
+            contact,
         }
     }
 
     async fn request_get(&self, url: impl Into<String>) -> RequestResult<Vec<u8>> {
-        Request::get(url).send().await
+        let _permit = self.limiter.acquire().await;
+        let result = self.fetcher.send(Request::get(url)).await;
+        self.contact.report(&result);
+        result
+    }
+
+    pub fn invalidate_cache(&self) {
+        self.cache.invalidate();
+    }
+
+    /**
+        Clears only cached versions, which are otherwise kept for the
+        whole session, so that newly published versions are picked up.
+    */
+    pub fn invalidate_versions(&self) {
+        self.cache.invalidate_versions();
     }
 
     fn emit_result<T>(&self, result: &RequestResult<T>) {
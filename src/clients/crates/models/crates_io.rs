@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 
 use serde::Deserialize;
+use serde_json::Value as JsonValue;
 
 use crate::util::Versioned;
 
@@ -28,6 +29,72 @@ pub struct CrateData {
     pub links: CrateDataLinks,
     #[serde(flatten)]
     pub downloads: CrateDataDownloads,
+    #[serde(default)]
+    pub badges: Vec<CrateDataBadge>,
+}
+
+impl CrateData {
+    /**
+        Checks if the crate is deprecated, either using its maintenance
+        badge, or a description that starts with something like `DEPRECATED:`.
+
+        This is best-effort, since crates.io has no proper way to deprecate
+        crates, and the successor is only found in the description, if at all.
+    */
+    pub fn deprecation(&self) -> Option<CrateDeprecation> {
+        let badge = self.badges.iter().any(|badge| {
+            badge.badge_type == "maintenance"
+                && badge
+                    .attributes
+                    .get("status")
+                    .and_then(JsonValue::as_str)
+                    .is_some_and(|status| status == "deprecated")
+        });
+        let description = self.description.trim().to_ascii_lowercase();
+        let described = description.starts_with("deprecated")
+            || description.starts_with("[deprecated]")
+            || description.starts_with("(deprecated)");
+        if !badge && !described {
+            return None;
+        }
+        Some(CrateDeprecation {
+            successor: find_successor(&self.description),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CrateDataBadge {
+    pub badge_type: String,
+    #[serde(default)]
+    pub attributes: HashMap<String, JsonValue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrateDeprecation {
+    pub successor: Option<String>,
+}
+
+/**
+    Finds the name of a suggested replacement in a crate description,
+    such as `bar` in "Deprecated in favor of `bar`" or "Deprecated, use bar instead".
+*/
+fn find_successor(description: &str) -> Option<String> {
+    const PREFIXES: [&str; 4] = ["in favor of ", "in favour of ", "replaced by ", "use "];
+
+    let lower = description.to_ascii_lowercase();
+    PREFIXES.iter().find_map(|prefix| {
+        let start = lower.find(prefix)? + prefix.len();
+        let name = description[start..]
+            .split_whitespace()
+            .next()?
+            .trim_matches(|c: char| !c.is_ascii_alphanumeric() && c != '-' && c != '_');
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        valid.then(|| name.to_string())
+    })
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -56,6 +123,8 @@ pub struct CrateDataVersion {
     pub updated_at: String,
     pub downloads: u64,
     pub features: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    pub yanked: bool,
 }
 
 impl Versioned for CrateDataVersion {
@@ -63,3 +132,57 @@ impl Versioned for CrateDataVersion {
         self.version.to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crate_data(description: &str, badges: JsonValue) -> CrateData {
+        serde_json::from_value(serde_json::json!({
+            "name": "foo",
+            "description": description,
+            "created_at": "",
+            "updated_at": "",
+            "documentation": null,
+            "repository": null,
+            "homepage": null,
+            "downloads": 0,
+            "recent_downloads": 0,
+            "badges": badges,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_not_deprecated() {
+        let data = crate_data(
+            "A crate that is not deprecated, use it",
+            serde_json::json!([]),
+        );
+        assert_eq!(data.deprecation(), None);
+    }
+
+    #[test]
+    fn test_deprecated_description() {
+        let data = crate_data("DEPRECATED: use `bar` instead", serde_json::json!([]));
+        let successor = data.deprecation().and_then(|d| d.successor);
+        assert_eq!(successor.as_deref(), Some("bar"));
+
+        let data = crate_data("[deprecated] in favour of baz.", serde_json::json!([]));
+        let successor = data.deprecation().and_then(|d| d.successor);
+        assert_eq!(successor.as_deref(), Some("baz"));
+    }
+
+    #[test]
+    fn test_deprecated_badge() {
+        let badges = serde_json::json!([{
+            "badge_type": "maintenance",
+            "attributes": { "status": "deprecated" },
+        }]);
+        let data = crate_data("Does things", badges);
+        assert_eq!(
+            data.deprecation(),
+            Some(CrateDeprecation { successor: None })
+        );
+    }
+}
@@ -1,8 +1,9 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use serde::Deserialize;
 
-use crate::util::Versioned;
+use crate::util::{cmp_completion_versions, Versioned};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct IndexMetadata {
@@ -13,6 +14,10 @@ pub struct IndexMetadata {
     pub dependencies: Vec<IndexMetadataDependency>,
     #[serde(default, alias = "feats")]
     pub features: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    pub links: Option<String>,
+    #[serde(default)]
+    pub rust_version: Option<String>,
 }
 
 impl Versioned for IndexMetadata {
@@ -29,9 +34,26 @@ pub struct IndexMetadataDependency {
     pub features: Vec<String>,
     pub optional: bool,
     pub default_features: bool,
+    #[serde(default)]
+    pub kind: Option<String>,
 }
 
 impl IndexMetadata {
+    /**
+        Checks if this version of the crate has a build script.
+
+        The index does not say this directly, so this is best-effort - crates
+        that link to a native library, or that have any build dependencies,
+        always have a build script, but other build scripts are not detected.
+    */
+    pub fn has_build_script(&self) -> bool {
+        self.links.is_some()
+            || self
+                .dependencies
+                .iter()
+                .any(|dep| dep.kind.as_deref() == Some("build"))
+    }
+
     pub fn try_from_lines(lines: Vec<&'_ str>) -> Result<Vec<Self>, serde_json::Error> {
         let mut packages = Vec::new();
         for line in lines {
@@ -43,3 +65,92 @@ impl IndexMetadata {
         Ok(packages)
     }
 }
+
+/**
+    Only the version of a single line in the index, which is much
+    cheaper to parse than the full metadata, since all else is skipped.
+*/
+#[derive(Debug, Deserialize)]
+struct IndexLineVersion {
+    #[serde(alias = "vers")]
+    version: String,
+}
+
+/**
+    The raw lines of an index file, one line for each version of the crate, in
+    the order that they were published, which are only parsed when needed.
+
+    Crates such as `libc` have thousands of versions, each with all of their
+    dependencies and features, and parsing only the versions that will actually
+    be used is much faster than parsing and holding on to all of them.
+*/
+#[derive(Debug, Clone)]
+pub struct IndexLines {
+    text: Arc<str>,
+}
+
+impl IndexLines {
+    pub fn new(text: impl Into<Arc<str>>) -> Self {
+        Self { text: text.into() }
+    }
+
+    /**
+        Parses metadatas for the highest versions that `filter` returns true for,
+        at most `limit` of them, with the highest version first.
+
+        Only the version is parsed for each line to pick out the highest versions,
+        so any lines for versions that are not picked are never fully parsed.
+    */
+    pub fn parse_highest(
+        &self,
+        limit: usize,
+        filter: impl Fn(&str) -> bool,
+    ) -> Result<Vec<IndexMetadata>, serde_json::Error> {
+        let mut candidates = Vec::new();
+        for line in self.text.lines() {
+            let IndexLineVersion { version } = serde_json::from_str(line)?;
+            if filter(&version) {
+                candidates.push((version, line));
+            }
+        }
+        highest_first(candidates, limit)
+            .map(serde_json::from_str)
+            .collect()
+    }
+}
+
+/**
+    Picks at most `limit` of the highest versions out of metadatas that have
+    already been parsed, in the same order as `IndexLines::parse_highest`.
+*/
+pub fn pick_highest(
+    metas: Vec<IndexMetadata>,
+    limit: usize,
+    filter: impl Fn(&str) -> bool,
+) -> Vec<IndexMetadata> {
+    let candidates = metas
+        .into_iter()
+        .filter(|meta| filter(&meta.version))
+        .map(|meta| (meta.version.clone(), meta))
+        .collect();
+    highest_first(candidates, limit).collect()
+}
+
+fn highest_first<T>(candidates: Vec<(String, T)>, limit: usize) -> impl Iterator<Item = T> {
+    let mut candidates = candidates
+        .into_iter()
+        .map(|(raw, item)| (raw.parse_version().ok(), raw, item))
+        .collect::<Vec<_>>();
+
+    // NOTE: This must be the same order as completions are sorted in,
+    // so that the versions that end up being shown are the same ones
+    candidates.sort_by(|(a, a_raw, _), (b, b_raw, _)| {
+        cmp_completion_versions(a.as_ref(), a_raw, b.as_ref(), b_raw)
+    });
+
+    candidates
+        .into_iter()
+        .rev()
+        .take(limit)
+        .map(|(_, _, item)| item)
+}
@@ -1,5 +1,6 @@
 use tracing::debug;
 
+use super::super::contact::request_reached;
 use super::consts::*;
 use super::models::*;
 use super::*;
@@ -9,18 +10,7 @@ impl CratesClient {
         &self,
         name: &str,
     ) -> RequestResult<Vec<IndexMetadata>> {
-        let name_low = name.to_ascii_lowercase();
-        let index_url = if name_low.len() <= 2 {
-            format!("{BASE_URL_INDEX}/{}/{name_low}", name_low.len())
-        } else if name_low.len() == 3 {
-            format!("{BASE_URL_INDEX}/3/{}/{name_low}", &name_low[..1])
-        } else {
-            format!(
-                "{BASE_URL_INDEX}/{}/{}/{name_low}",
-                &name_low[..2],
-                &name_low[2..4]
-            )
-        };
+        let index_url = sparse_index_url(BASE_URL_INDEX, name);
 
         let fut = async {
             debug!("Fetching crates index metadatas for '{name}'");
@@ -29,7 +19,7 @@ impl CratesClient {
             // we can catch and emit all errors at once
             let mut inner = async {
                 let bytes = self.request_get(&index_url).await?;
-                let text = String::from_utf8(bytes.to_vec())?;
+                let text = String::from_utf8(bytes)?;
                 Ok(IndexMetadata::try_from_lines(text.lines().collect())?)
             }
             .await;
@@ -55,6 +45,119 @@ impl CratesClient {
             .await
     }
 
+    /**
+        Fetches index metadatas for only the highest versions of a crate that
+        `filter` returns true for, at most `limit` of them, highest first.
+
+        Meant for completions, hovers, and diagnostics, which only ever look at a few
+        of the newest versions, even for crates with thousands of them - unlike with
+        `get_sparse_index_crate_metadatas`, versions that are not picked are never
+        fully parsed, and only the raw index file is kept around, for a short while.
+    */
+    pub async fn get_sparse_index_highest_metadatas(
+        &self,
+        name: &str,
+        limit: usize,
+        filter: impl Fn(&str) -> bool,
+    ) -> RequestResult<Vec<IndexMetadata>> {
+        // NOTE: Versions that have already been parsed are kept for the whole
+        // session, and must be used if they exist, so that completions do not
+        // change while editing, even if the raw index file has since expired
+        let index_url = sparse_index_url(BASE_URL_INDEX, name);
+        if let Some(Ok(metas)) = self.cache.index_metadatas.get_cached(&index_url).await {
+            return Ok(pick_highest(metas, limit, filter));
+        }
+
+        let inner = async {
+            let lines = self.get_sparse_index_lines(name).await?;
+            Ok(lines.parse_highest(limit, filter)?)
+        }
+        .await;
+
+        if inner
+            .as_ref()
+            .is_err_and(|e: &RequestError| !e.is_not_found_error())
+        {
+            self.emit_result(&inner);
+        }
+
+        inner
+    }
+
+    /**
+        Fetches the raw index file for a crate from the sparse index.
+
+        ### Caching
+
+        This method caches its result for the given `name` for a few minutes,
+        and only for a limited number of crates, since index files can be large.
+    */
+    async fn get_sparse_index_lines(&self, name: &str) -> RequestResult<IndexLines> {
+        let index_url = sparse_index_url(BASE_URL_INDEX, name);
+
+        let fut = async {
+            let bytes = self.request_get(&index_url).await?;
+            let text = String::from_utf8(bytes)?;
+            Ok(IndexLines::new(text))
+        };
+
+        self.cache
+            .index_lines
+            .with_caching(index_url.clone(), fut)
+            .await
+    }
+
+    /**
+        Fetches index metadatas for a crate from the sparse index of an alternate
+        registry, such as a private registry, at the given url without `sparse+`.
+
+        The token is sent as-is in the `Authorization` header, the same way
+        that Cargo sends it, and only ever to the host of the index. Registries
+        that need authentication respond with `401` or `403` without a valid token.
+    */
+    pub async fn get_registry_index_metadatas(
+        &self,
+        index_url: &str,
+        name: &str,
+        token: Option<&str>,
+    ) -> RequestResult<Vec<IndexMetadata>> {
+        let crate_url = sparse_index_url(index_url, name);
+
+        let fut = async {
+            debug!("Fetching registry index metadatas for '{name}' from '{index_url}'");
+
+            // NOTE: We make this inner scope so that
+            // we can catch and emit all errors at once
+            let mut inner = async {
+                let _permit = self.limiter.acquire().await;
+                let request = Request::get(&crate_url).with_header_opt("Authorization", token);
+                let bytes = self.fetcher.send(request).await?;
+                let text = String::from_utf8(bytes.to_vec())?;
+                Ok(IndexMetadata::try_from_lines(text.lines().collect())?)
+            }
+            .await;
+
+            // NOTE: We should sort by most recent version first
+            if let Ok(vec) = &mut inner {
+                vec.reverse();
+            }
+
+            if inner
+                .as_ref()
+                .is_err_and(|e: &RequestError| !e.is_not_found_error())
+            {
+                self.emit_result(&inner);
+            }
+
+            inner
+        };
+
+        self.cache
+            .index_metadatas
+            .with_caching(crate_url.clone(), fut)
+            .await
+    }
+
     /**
         Fetches crate data using the crates.io API directly.
 
@@ -75,8 +178,7 @@ impl CratesClient {
         [crates.io data access policy](https://crates.io/data-access).
     */
     pub async fn get_crate_data(&self, name: &str) -> RequestResult<CrateDataSingle> {
-        let crates_name = name.trim().to_ascii_lowercase();
-        let crates_url = format!("{BASE_URL_CRATES}/{crates_name}{QUERY_STRING_CRATE_SINGLE}");
+        let crates_url = crate_data_url(name);
 
         let fut = async {
             self.wait_for_crawl_limit().await;
@@ -153,4 +255,62 @@ impl CratesClient {
             .with_caching(crates_url.clone(), fut)
             .await
     }
+
+    /**
+        Clears cached versions and crate data for a single crate,
+        so that both are fetched again the next time they are used.
+    */
+    pub async fn invalidate_crate(&self, name: &str) {
+        debug!("Clearing cached crate data for '{name}'");
+        let index_url = sparse_index_url(BASE_URL_INDEX, name);
+        self.cache.index_metadatas.invalidate_key(&index_url).await;
+        self.cache.index_lines.invalidate_key(&index_url).await;
+        self.cache
+            .crate_datas
+            .invalidate_key(&crate_data_url(name))
+            .await;
+    }
+
+    /**
+        Clears cached versions for a single crate from an alternate registry,
+        which only has the index, and none of the crate data that crates.io has.
+    */
+    pub async fn invalidate_registry_crate(&self, index_url: &str, name: &str) {
+        debug!("Clearing cached registry index metadatas for '{name}' from '{index_url}'");
+        self.cache
+            .index_metadatas
+            .invalidate_key(&sparse_index_url(index_url, name))
+            .await;
+    }
+
+    /**
+        Sends a single uncached request to the sparse index, to check that it can be reached.
+    */
+    pub async fn check_reachable(&self) -> bool {
+        let result = self
+            .request_get(format!("{BASE_URL_INDEX}/config.json"))
+            .await;
+        request_reached(&result).unwrap_or(false)
+    }
+}
+
+fn sparse_index_url(base_url: &str, name: &str) -> String {
+    let base_url = base_url.trim_end_matches('/');
+    let name_low = name.to_ascii_lowercase();
+    if name_low.len() <= 2 {
+        format!("{base_url}/{}/{name_low}", name_low.len())
+    } else if name_low.len() == 3 {
+        format!("{base_url}/3/{}/{name_low}", &name_low[..1])
+    } else {
+        format!(
+            "{base_url}/{}/{}/{name_low}",
+            &name_low[..2],
+            &name_low[2..4]
+        )
+    }
+}
+
+fn crate_data_url(name: &str) -> String {
+    let crates_name = name.trim().to_ascii_lowercase();
+    format!("{BASE_URL_CRATES}/{crates_name}{QUERY_STRING_CRATE_SINGLE}")
 }
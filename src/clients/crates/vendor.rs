@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use reqwest::StatusCode;
+use semver::Version;
+use tokio::fs;
+use tracing::debug;
+
+use super::models::*;
+use super::*;
+
+/**
+    The file that `cargo vendor` writes into every vendored crate,
+    used to tell vendored crates apart from any other directories.
+*/
+const VENDOR_CHECKSUM_FILE_NAME: &str = ".cargo-checksum.json";
+
+impl CratesClient {
+    /**
+        Gets metadatas for all versions of a crate, from the given vendor
+        directory if the crate has been vendored there, and otherwise
+        from the sparse index, sorted by most recent version first.
+    */
+    pub async fn get_index_metadatas(
+        &self,
+        vendor_dir: Option<&Path>,
+        name: &str,
+    ) -> RequestResult<Vec<IndexMetadata>> {
+        if let Some(vendor_dir) = vendor_dir {
+            match self.get_vendored_crate_metadatas(vendor_dir, name).await {
+                Ok(metas) => return Ok(metas),
+                Err(e) if e.is_not_found_error() => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.get_sparse_index_crate_metadatas(name).await
+    }
+
+    /**
+        Reads metadatas for all vendored versions of a crate, from the
+        manifests in a directory created using `cargo vendor`.
+
+        Crates are vendored either as `name` or as `name-version`,
+        and vendored manifests are always normalized, meaning that
+        they never inherit anything from a workspace.
+
+        ### Caching
+
+        The crates in each vendor directory are only listed once, until
+        versions are invalidated, but the manifests of the matching crates
+        are always read again, since they are small and few in number.
+    */
+    pub async fn get_vendored_crate_metadatas(
+        &self,
+        vendor_dir: &Path,
+        name: &str,
+    ) -> RequestResult<Vec<IndexMetadata>> {
+        debug!(
+            "Reading vendored metadatas for '{name}' from '{}'",
+            vendor_dir.display()
+        );
+
+        let mut metas = Vec::new();
+        for (dir_name, dir) in self.vendored_crate_dirs(vendor_dir).await.iter() {
+            let is_versioned = dir_name
+                .strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix('-'))
+                .is_some_and(|version| Version::parse(version).is_ok());
+            if dir_name != name && !is_versioned {
+                continue;
+            }
+
+            let Ok(contents) = fs::read_to_string(dir.join("Cargo.toml")).await else {
+                continue;
+            };
+            if let Some(meta) = IndexMetadata::from_vendored_manifest(&contents) {
+                if meta.name == name {
+                    metas.push(meta);
+                }
+            }
+        }
+
+        if metas.is_empty() {
+            return Err(ResponseError::from_status_and_string(
+                StatusCode::NOT_FOUND,
+                format!("No vendored crate '{name}'"),
+            )
+            .into());
+        }
+
+        metas.sort_by_cached_key(|meta| std::cmp::Reverse(Version::parse(&meta.version).ok()));
+        Ok(metas)
+    }
+
+    /**
+        Lists the names and paths of all vendored crates in a vendor
+        directory, meaning directories that have a checksum file.
+    */
+    async fn vendored_crate_dirs(&self, vendor_dir: &Path) -> Arc<Vec<(String, PathBuf)>> {
+        if let Some(dirs) = self.cache.vendored_dirs.get(vendor_dir) {
+            return Arc::clone(&dirs);
+        }
+
+        let mut dirs = Vec::new();
+        if let Ok(mut entries) = fs::read_dir(vendor_dir).await {
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                let Ok(dir_name) = entry.file_name().into_string() else {
+                    continue;
+                };
+                let dir = entry.path();
+                if fs::try_exists(dir.join(VENDOR_CHECKSUM_FILE_NAME))
+                    .await
+                    .unwrap_or_default()
+                {
+                    dirs.push((dir_name, dir));
+                }
+            }
+        }
+
+        let dirs = Arc::new(dirs);
+        self.cache
+            .vendored_dirs
+            .insert(vendor_dir.to_path_buf(), Arc::clone(&dirs));
+        dirs
+    }
+}
+
+impl IndexMetadata {
+    /**
+        Creates metadata from the normalized manifest of a vendored crate,
+        returning `None` if it is missing the package name or version.
+    */
+    pub fn from_vendored_manifest(contents: &str) -> Option<Self> {
+        let table = contents.parse::<toml::Table>().ok()?;
+        let package = table.get("package")?.as_table()?;
+        let name = package.get("name")?.as_str()?.to_string();
+        let version = package.get("version")?.as_str()?.to_string();
+        let links = package
+            .get("links")
+            .and_then(|l| l.as_str())
+            .map(ToString::to_string);
+        let rust_version = package
+            .get("rust-version")
+            .and_then(|r| r.as_str())
+            .map(ToString::to_string);
+
+        let features = table
+            .get("features")
+            .and_then(|f| f.as_table())
+            .map(|features| {
+                features
+                    .iter()
+                    .map(|(feature, values)| {
+                        let values = values
+                            .as_array()
+                            .map(|values| {
+                                values
+                                    .iter()
+                                    .filter_map(|v| v.as_str().map(ToString::to_string))
+                                    .collect()
+                            })
+                            .unwrap_or_default();
+                        (feature.to_string(), values)
+                    })
+                    .collect::<HashMap<_, _>>()
+            })
+            .unwrap_or_default();
+
+        let mut dependencies = Vec::new();
+        for (key, kind) in [
+            ("dependencies", None),
+            ("dev-dependencies", Some("dev")),
+            ("build-dependencies", Some("build")),
+        ] {
+            let Some(deps) = table.get(key).and_then(|d| d.as_table()) else {
+                continue;
+            };
+            for (dep_name, dep) in deps {
+                dependencies.push(vendored_dependency(dep_name, dep, kind));
+            }
+        }
+
+        Some(Self {
+            name,
+            version,
+            dependencies,
+            features,
+            links,
+            rust_version,
+        })
+    }
+}
+
+fn vendored_dependency(
+    dep_name: &str,
+    dep: &toml::Value,
+    kind: Option<&str>,
+) -> IndexMetadataDependency {
+    let get_bool = |key: &str| dep.get(key).and_then(|v| v.as_bool());
+    IndexMetadataDependency {
+        name: dep
+            .get("package")
+            .and_then(|p| p.as_str())
+            .unwrap_or(dep_name)
+            .to_string(),
+        version_requirement: dep
+            .as_str()
+            .or_else(|| dep.get("version").and_then(|v| v.as_str()))
+            .unwrap_or("*")
+            .to_string(),
+        features: dep
+            .get("features")
+            .and_then(|f| f.as_array())
+            .map(|features| {
+                features
+                    .iter()
+                    .filter_map(|f| f.as_str().map(ToString::to_string))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        optional: get_bool("optional").unwrap_or_default(),
+        default_features: get_bool("default-features").unwrap_or(true),
+        kind: kind.map(ToString::to_string),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::FixtureDir;
+
+    use super::*;
+
+    #[test]
+    fn test_vendored_manifest() {
+        let contents = r#"
+        [package]
+        edition = "2021"
+        name = "serde"
+        version = "1.0.200"
+
+        [features]
+        default = ["std"]
+        derive = ["serde_derive"]
+        std = []
+
+        [dependencies.serde_derive]
+        version = "=1.0.200"
+        optional = true
+
+        [dev-dependencies.serde_derive]
+        version = "1.0"
+        "#;
+
+        let meta = IndexMetadata::from_vendored_manifest(contents).unwrap();
+        assert_eq!(meta.name, "serde");
+        assert_eq!(meta.version, "1.0.200");
+        assert_eq!(meta.features.len(), 3);
+        assert_eq!(meta.features["derive"], vec![String::from("serde_derive")]);
+        assert_eq!(meta.dependencies.len(), 2);
+        assert!(meta.dependencies[0].optional);
+        assert_eq!(meta.dependencies[0].version_requirement, "=1.0.200");
+        assert_eq!(meta.dependencies[1].kind.as_deref(), Some("dev"));
+    }
+
+    #[test]
+    fn test_vendored_manifest_missing_version() {
+        let contents = r#"
+        [package]
+        name = "serde"
+        "#;
+        assert!(IndexMetadata::from_vendored_manifest(contents).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_vendored_crate_metadatas() {
+        let vendor = FixtureDir::new("vendored-crate-metadatas");
+        let write_crate = |dir: &str, name: &str, version: &str| {
+            vendor.write(format!("{dir}/{VENDOR_CHECKSUM_FILE_NAME}"), "{}");
+            vendor.write(
+                format!("{dir}/Cargo.toml"),
+                format!("[package]\nname = \"{name}\"\nversion = \"{version}\"\n"),
+            );
+        };
+        write_crate("serde", "serde", "1.0.200");
+        write_crate("serde-0.9.15", "serde", "0.9.15");
+
+        let client = crate::clients::Clients::new().crates;
+        let found = client.get_vendored_crate_metadatas(&vendor, "serde").await;
+        let missing = client.get_vendored_crate_metadatas(&vendor, "tokio").await;
+
+        // Vendored crates are listed once, until versions are invalidated
+        write_crate("tokio", "tokio", "1.0.0");
+        let still_missing = client.get_vendored_crate_metadatas(&vendor, "tokio").await;
+        client.invalidate_versions();
+        let added = client.get_vendored_crate_metadatas(&vendor, "tokio").await;
+
+        let versions = found
+            .unwrap()
+            .into_iter()
+            .map(|meta| meta.version)
+            .collect::<Vec<_>>();
+        assert_eq!(versions, vec!["1.0.200", "0.9.15"]);
+        assert!(missing.unwrap_err().is_not_found_error());
+        assert!(still_missing.unwrap_err().is_not_found_error());
+        assert_eq!(added.unwrap().len(), 1);
+    }
+}
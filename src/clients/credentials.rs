@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use tokio::fs;
+use tracing::{debug, warn};
+
+use crate::util::{cargo_home_dir, home_dir};
+
+/**
+    Credentials discovered in standard locations on the system:
+
+    - `~/.netrc` (or `~/_netrc` on Windows), matched by host
+    - Cargo's `credentials.toml`, matched by registry name
+
+    Note that the `Debug` implementation never includes any secrets,
+    only the hosts and registries that credentials were found for.
+*/
+#[derive(Clone, Default)]
+pub struct SystemCredentials {
+    hosts: HashMap<String, String>,
+    registries: HashMap<String, String>,
+}
+
+impl SystemCredentials {
+    pub async fn load() -> Self {
+        let mut credentials = Self::default();
+
+        for path in netrc_paths() {
+            if let Ok(contents) = fs::read_to_string(&path).await {
+                debug!("Reading credentials from '{}'", path.display());
+                credentials.hosts.extend(parse_netrc(&contents));
+                break;
+            }
+        }
+
+        for path in cargo_credentials_paths() {
+            if let Ok(contents) = fs::read_to_string(&path).await {
+                debug!("Reading credentials from '{}'", path.display());
+                match parse_cargo_credentials(&contents) {
+                    Ok(registries) => credentials.registries.extend(registries),
+                    Err(e) => warn!("Failed to parse '{}' - {e}", path.display()),
+                }
+                break;
+            }
+        }
+
+        credentials
+    }
+
+    /**
+        Returns the token or password for the given host, if any.
+    */
+    pub fn token_for_host(&self, host: &str) -> Option<&str> {
+        self.hosts
+            .get(&host.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    /**
+        Returns the token for the given Cargo registry, if any.
+
+        The default registry is named `crates-io`.
+    */
+    pub fn token_for_registry(&self, registry: &str) -> Option<&str> {
+        self.registries.get(registry).map(String::as_str)
+    }
+}
+
+impl fmt::Debug for SystemCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut hosts = self.hosts.keys().collect::<Vec<_>>();
+        let mut registries = self.registries.keys().collect::<Vec<_>>();
+        hosts.sort_unstable();
+        registries.sort_unstable();
+        f.debug_struct("SystemCredentials")
+            .field("hosts", &hosts)
+            .field("registries", &registries)
+            .finish()
+    }
+}
+
+fn netrc_paths() -> Vec<PathBuf> {
+    if let Some(path) = std::env::var_os("NETRC").filter(|p| !p.is_empty()) {
+        return vec![PathBuf::from(path)];
+    }
+    home_dir()
+        .map(|home| vec![home.join(".netrc"), home.join("_netrc")])
+        .unwrap_or_default()
+}
+
+fn cargo_credentials_paths() -> Vec<PathBuf> {
+    cargo_home_dir()
+        .map(|home| vec![home.join("credentials.toml"), home.join("credentials")])
+        .unwrap_or_default()
+}
+
+/**
+    Parses the contents of a `.netrc` file, returning
+    a map of lowercase host names to their passwords.
+
+    Macro definitions and the `default` entry are ignored,
+    since we only ever want to send credentials to known hosts.
+*/
+fn parse_netrc(contents: &str) -> HashMap<String, String> {
+    let mut hosts = HashMap::new();
+    let mut machine = None::<String>;
+
+    let mut tokens = NetrcTokens { rest: contents };
+    while let Some(token) = tokens.next() {
+        match token.as_str() {
+            "machine" => machine = tokens.next().map(|t| t.to_ascii_lowercase()),
+            "default" => machine = None,
+            "password" => {
+                if let (Some(host), Some(password)) = (machine.as_ref(), tokens.next()) {
+                    hosts.insert(host.clone(), password);
+                }
+            }
+            "login" | "account" => {
+                tokens.next();
+            }
+            "macdef" => {
+                tokens.next();
+                tokens.skip_macro();
+            }
+            _ => {}
+        }
+    }
+
+    hosts
+}
+
+/**
+    The tokens in a `.netrc` file, which are separated by any whitespace,
+    and may be quoted to contain whitespace, such as `"pass word"`.
+*/
+struct NetrcTokens<'a> {
+    rest: &'a str,
+}
+
+impl NetrcTokens<'_> {
+    /**
+        Skips the body of a macro definition, which is everything
+        after its name, until the next empty line or the end of the file.
+    */
+    fn skip_macro(&mut self) {
+        let mut lines = self.rest.split_inclusive('\n');
+        let mut skipped = lines.next().map_or(0, str::len);
+        for line in lines {
+            skipped += line.len();
+            if line.trim().is_empty() {
+                break;
+            }
+        }
+        self.rest = &self.rest[skipped..];
+    }
+}
+
+impl Iterator for NetrcTokens<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rest = self.rest.trim_start();
+        let Some(quoted) = self.rest.strip_prefix('"') else {
+            let end = self
+                .rest
+                .find(char::is_whitespace)
+                .unwrap_or(self.rest.len());
+            let (token, rest) = self.rest.split_at(end);
+            self.rest = rest;
+            return (!token.is_empty()).then(|| token.to_string());
+        };
+
+        // Quoted tokens end at the next unescaped quote, or at the end of the file
+        let mut token = String::new();
+        let mut chars = quoted.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = &quoted[i + 1..];
+                    return Some(token);
+                }
+                '\\' => token.extend(chars.next().map(|(_, c)| c)),
+                c => token.push(c),
+            }
+        }
+        self.rest = "";
+        Some(token)
+    }
+}
+
+/**
+    Parses the contents of a Cargo `credentials.toml` file,
+    returning a map of registry names to their tokens.
+*/
+fn parse_cargo_credentials(contents: &str) -> Result<HashMap<String, String>, toml::de::Error> {
+    let table = contents.parse::<toml::Table>()?;
+
+    let mut registries = HashMap::new();
+    if let Some(token) = table
+        .get("registry")
+        .and_then(|r| r.get("token"))
+        .and_then(|t| t.as_str())
+    {
+        registries.insert(String::from("crates-io"), token.to_string());
+    }
+    if let Some(named) = table.get("registries").and_then(|r| r.as_table()) {
+        for (name, registry) in named {
+            if let Some(token) = registry.get("token").and_then(|t| t.as_str()) {
+                registries.insert(name.clone(), token.to_string());
+            }
+        }
+    }
+
+    Ok(registries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_netrc() {
+        let hosts = parse_netrc(
+            "machine GitHub.com login user password secret\n\
+            machine\n  gitlab.com\n  login other\n  password other-secret\n",
+        );
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts["github.com"], "secret");
+        assert_eq!(hosts["gitlab.com"], "other-secret");
+    }
+
+    #[test]
+    fn test_parse_netrc_default() {
+        // Credentials of the default entry are never sent anywhere
+        let hosts = parse_netrc(
+            "default login anyone password everywhere\n\
+            machine example.com password secret\n",
+        );
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts["example.com"], "secret");
+    }
+
+    #[test]
+    fn test_parse_netrc_macdef() {
+        let hosts = parse_netrc(
+            "machine a.com password first\n\
+            macdef init\n\
+            machine b.com password in-macro\n\
+            \n\
+            machine c.com password after-macro\n",
+        );
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts["a.com"], "first");
+        assert_eq!(hosts["c.com"], "after-macro");
+    }
+
+    #[test]
+    fn test_parse_netrc_quoted() {
+        let hosts = parse_netrc(
+            "machine example.com login \"some user\" password \"pass \\\"word\\\"\"\n\
+            machine other.com password \"unterminated",
+        );
+        assert_eq!(hosts["example.com"], "pass \"word\"");
+        assert_eq!(hosts["other.com"], "unterminated");
+    }
+}
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+
+/**
+    Identifies a single dependency by where its data is fetched from, to
+    be able to clear cached data for only that dependency, and nothing else.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "camelCase")]
+pub enum DependencyId {
+    Crates {
+        name: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    CratesRegistry {
+        index_url: String,
+        name: String,
+    },
+    Npm {
+        name: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    Github {
+        owner: String,
+        repository: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    Wally {
+        index_url: String,
+        scope: String,
+        name: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_dependency_id_json() {
+        let id = DependencyId::Wally {
+            index_url: String::from("https://github.com/UpliftGames/wally-index"),
+            scope: String::from("roblox"),
+            name: String::from("roact"),
+        };
+        let value = serde_json::to_value(&id).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "source": "wally",
+                "indexUrl": "https://github.com/UpliftGames/wally-index",
+                "scope": "roblox",
+                "name": "roact",
+            })
+        );
+        assert_eq!(serde_json::from_value::<DependencyId>(value).unwrap(), id);
+    }
+}
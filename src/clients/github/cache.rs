@@ -8,15 +8,19 @@ pub(super) struct GithubCache {
     pub repository_releases: RequestCacheMap<RequestResult<Vec<RepositoryRelease>>>,
     pub repository_trees: RequestCacheMap<RequestResult<GitTreeRoot>>,
     pub repository_files: RequestCacheMap<RequestResult<Vec<u8>>>,
+    pub owner_repositories: RequestCacheMap<RequestResult<Vec<Repository>>>,
 }
 
 impl GithubCache {
-    pub fn new() -> Self {
+    pub fn new(metrics: &Metrics) -> Self {
         Self {
-            repository_metrics: RequestCacheMap::new(60, 15),
-            repository_releases: RequestCacheMap::new(30, 5),
-            repository_trees: RequestCacheMap::new(45, 10),
-            repository_files: RequestCacheMap::new(10, 5),
+            repository_metrics: RequestCacheMap::new(60, 15).with_metrics(metrics),
+            // NOTE: Unlike versions of other tools, tools have no lockfile that
+            // invalidates their releases, so those must expire on their own
+            repository_releases: RequestCacheMap::new(30, 5).with_metrics(metrics),
+            repository_trees: RequestCacheMap::new(45, 10).with_metrics(metrics),
+            repository_files: RequestCacheMap::new(10, 5).with_metrics(metrics),
+            owner_repositories: RequestCacheMap::new(60, 15).with_metrics(metrics),
         }
     }
 
@@ -25,5 +29,10 @@ impl GithubCache {
         self.repository_releases.invalidate();
         self.repository_trees.invalidate();
         self.repository_files.invalidate();
+        self.owner_repositories.invalidate();
+    }
+
+    pub fn invalidate_versions(&self) {
+        self.repository_releases.invalidate();
     }
 }
@@ -5,3 +5,6 @@ pub const GITHUB_API_VERSION_VALUE: &str = "2022-11-28";
 
 pub const GITHUB_API_CONTENT_TYPE: &str = "application/vnd.github.v3+json";
 pub const GITHUB_API_CONTENT_TYPE_RAW: &str = "application/vnd.github.raw";
+
+pub const OWNER_REPOSITORIES_PER_PAGE: usize = 100;
+pub const OWNER_REPOSITORIES_MAX_PAGES: usize = 3;
@@ -1,12 +1,15 @@
 use std::sync::{
     atomic::{AtomicBool, Ordering},
-    Arc, Mutex,
+    Arc, Mutex, RwLock,
 };
 
-use tracing::error;
+use tracing::{debug, error};
+use url::Url;
 
 use crate::util::*;
 
+use super::contact::ContactTracker;
+
 mod cache;
 use cache::*;
 
@@ -19,29 +22,101 @@ pub mod models;
 
 #[derive(Debug, Clone)]
 pub struct GithubClient {
+    api_url: Arc<RwLock<String>>,
     auth_token: Arc<Mutex<Option<String>>>,
     cache: GithubCache,
     rate_limited: Arc<AtomicBool>,
+    limiter: RequestLimiter,
+    fetcher: Fetcher,
+    contact: ContactTracker,
 }
 
 impl GithubClient {
-    pub fn new() -> Self {
+    pub fn new(
+        limiter: RequestLimiter,
+        fetcher: Fetcher,
+        contact: ContactTracker,
+        metrics: &Metrics,
+    ) -> Self {
         Self {
+            api_url: Arc::new(RwLock::new(GITHUB_API_BASE_URL.to_string())),
             auth_token: Arc::new(Mutex::new(None)),
-            cache: GithubCache::new(),
+            cache: GithubCache::new(metrics),
             rate_limited: Arc::new(AtomicBool::new(false)),
+            limiter,
+            fetcher,
+            contact,
         }
     }
 
     async fn request_get(&self, url: impl Into<String>) -> RequestResult<Vec<u8>> {
         let auth_token = self.auth_token.lock().unwrap().clone();
 
-        Request::get(url)
+        let _permit = self.limiter.acquire().await;
+        let request = Request::get(url)
             .with_header("Content-Type", consts::GITHUB_API_CONTENT_TYPE)
             .with_header(GITHUB_API_VERSION_NAME, GITHUB_API_VERSION_VALUE)
-            .with_header_opt("Authorization", auth_token)
-            .send()
-            .await
+            .with_header_opt("Authorization", auth_token);
+        let result = self.fetcher.send(request).await;
+        self.contact.report(&result);
+        result
+    }
+
+    pub fn invalidate_cache(&self) {
+        self.cache.invalidate();
+    }
+
+    /**
+        Clears only cached versions, which are otherwise kept for the
+        whole session, so that newly published versions are picked up.
+    */
+    pub fn invalidate_versions(&self) {
+        self.cache.invalidate_versions();
+    }
+
+    fn api_url(&self) -> String {
+        self.api_url
+            .read()
+            .expect("GitHub api url lock was poisoned")
+            .clone()
+    }
+
+    /**
+        Sets the base url of the API, such as for a GitHub Enterprise instance.
+
+        Returns an error, and keeps using the current url, if
+        the given url is not a valid `http` or `https` url.
+    */
+    pub fn set_api_url(&self, url: &str) -> RequestResult<()> {
+        let parsed = Url::parse(url)?;
+        if !matches!(parsed.scheme(), "http" | "https") || parsed.host().is_none() {
+            return Err(RequestError::Client(format!(
+                "invalid GitHub api url - expected an http or https url, got `{url}`"
+            )));
+        }
+
+        let url = url.trim_end_matches('/').to_string();
+        debug!("Using GitHub api url {url}");
+        *self
+            .api_url
+            .write()
+            .expect("GitHub api url lock was poisoned") = url;
+        self.cache.invalidate();
+        Ok(())
+    }
+
+    /**
+        Returns the base url for web pages, such as repositories and releases,
+        which is derived from the API url - `https://api.github.com` becomes
+        `https://github.com`, and `https://host/api/v3` becomes `https://host`.
+    */
+    pub fn web_url(&self) -> String {
+        let api_url = self.api_url();
+        if let Some(web_url) = api_url.strip_suffix("/api/v3") {
+            web_url.to_string()
+        } else {
+            api_url.replacen("://api.", "://", 1)
+        }
     }
 
     fn emit_result<T>(&self, result: &RequestResult<T>) {
@@ -58,6 +133,10 @@ impl GithubClient {
         self.rate_limited.load(Ordering::SeqCst)
     }
 
+    pub fn has_auth_token(&self) -> bool {
+        self.auth_token.lock().unwrap().is_some()
+    }
+
     pub fn set_auth_token(&self, token: impl AsRef<str>) {
         let mut auth_token = self
             .auth_token
@@ -8,6 +8,15 @@ pub struct RepositoryMetrics {
     pub documentation: Option<String>,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct Repository {
+    pub name: String,
+    pub full_name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub stargazers_count: u64,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct RepositoryRelease {
     pub tag_name: String,
@@ -1,5 +1,6 @@
 use tracing::debug;
 
+use super::super::contact::request_reached;
 use super::consts::*;
 use super::models::*;
 use super::*;
@@ -13,8 +14,10 @@ impl GithubClient {
         let owner_low = owner.to_ascii_lowercase();
         let repository_low = repository.to_ascii_lowercase();
 
-        let metrics_url =
-            format!("{GITHUB_API_BASE_URL}/repos/{owner_low}/{repository_low}/community/profile");
+        let metrics_url = format!(
+            "{}/repos/{owner_low}/{repository_low}/community/profile",
+            self.api_url()
+        );
 
         let fut = async move {
             debug!("Fetching GitHub metrics for {owner}/{repository}");
@@ -46,8 +49,10 @@ impl GithubClient {
         let owner_low = owner.to_ascii_lowercase();
         let repository_low = repository.to_ascii_lowercase();
 
-        let releases_url =
-            format!("{GITHUB_API_BASE_URL}/repos/{owner_low}/{repository_low}/releases");
+        let releases_url = format!(
+            "{}/repos/{owner_low}/{repository_low}/releases",
+            self.api_url()
+        );
 
         let fut = async move {
             debug!("Fetching GitHub releases for {owner}/{repository}");
@@ -71,6 +76,33 @@ impl GithubClient {
             .await
     }
 
+    /**
+        Clears cached releases and metrics for a single repository,
+        so that both are fetched again the next time they are used.
+    */
+    pub async fn invalidate_repository(&self, owner: &str, repository: &str) {
+        debug!("Clearing cached GitHub data for {owner}/{repository}");
+        let key = format!(
+            "{}/{}",
+            owner.to_ascii_lowercase(),
+            repository.to_ascii_lowercase()
+        );
+        self.cache.repository_releases.invalidate_key(&key).await;
+        self.cache.repository_metrics.invalidate_key(&key).await;
+    }
+
+    /**
+        Clears a single cached file from a repository, at its default branch.
+    */
+    pub async fn invalidate_repository_file(&self, owner: &str, repository: &str, path: &str) {
+        let key = format!(
+            "{}/{}/{path}",
+            owner.to_ascii_lowercase(),
+            repository.to_ascii_lowercase()
+        );
+        self.cache.repository_files.invalidate_key(&key).await;
+    }
+
     pub async fn get_repository_tree(
         &self,
         owner: &str,
@@ -81,8 +113,10 @@ impl GithubClient {
         let repository_low = repository.to_ascii_lowercase();
         let sha_low = sha.to_ascii_lowercase();
 
-        let git_tree_url =
-            format!("{GITHUB_API_BASE_URL}/repos/{owner_low}/{repository_low}/git/trees/{sha_low}");
+        let git_tree_url = format!(
+            "{}/repos/{owner_low}/{repository_low}/git/trees/{sha_low}",
+            self.api_url()
+        );
 
         let fut = async move {
             debug!("Fetching GitHub tree for {owner}/{repository}/{sha}");
@@ -111,24 +145,62 @@ impl GithubClient {
         owner: &str,
         repository: &str,
         path: &str,
+    ) -> RequestResult<Vec<u8>> {
+        self.fetch_repository_file(owner, repository, path, None)
+            .await
+    }
+
+    /**
+        Fetches a file from a repository at the given git reference,
+        which may be a commit hash, a tag, or the name of a branch.
+    */
+    pub async fn get_repository_file_at(
+        &self,
+        owner: &str,
+        repository: &str,
+        path: &str,
+        reference: &str,
+    ) -> RequestResult<Vec<u8>> {
+        self.fetch_repository_file(owner, repository, path, Some(reference))
+            .await
+    }
+
+    async fn fetch_repository_file(
+        &self,
+        owner: &str,
+        repository: &str,
+        path: &str,
+        reference: Option<&str>,
     ) -> RequestResult<Vec<u8>> {
         let owner_low = owner.to_ascii_lowercase();
         let repository_low = repository.to_ascii_lowercase();
 
-        let git_file_url =
-            format!("{GITHUB_API_BASE_URL}/repos/{owner_low}/{repository_low}/contents/{path}");
+        // NOTE: References are case sensitive, unlike owners and
+        // repositories, and branch names may contain slashes
+        let mut git_file_url = format!(
+            "{}/repos/{owner_low}/{repository_low}/contents/{path}",
+            self.api_url()
+        );
+        let mut cache_key = format!("{owner_low}/{repository_low}/{path}");
+        if let Some(reference) = reference {
+            let encoded =
+                url::form_urlencoded::byte_serialize(reference.as_bytes()).collect::<String>();
+            git_file_url.push_str(&format!("?ref={encoded}"));
+            cache_key.push_str(&format!("@{reference}"));
+        }
 
         let agent_auth = self.auth_token.lock().unwrap().clone();
         let fut = async move {
             debug!("Fetching GitHub file for {owner}/{repository} at {path}");
 
-            let result = Request::get(git_file_url)
+            let _permit = self.limiter.acquire().await;
+            let request = Request::get(git_file_url)
                 .with_header("Accept", consts::GITHUB_API_CONTENT_TYPE_RAW)
                 .with_header(GITHUB_API_VERSION_NAME, GITHUB_API_VERSION_VALUE)
-                .with_header_opt("Authorization", agent_auth)
-                .send()
-                .await;
+                .with_header_opt("Authorization", agent_auth);
+            let result = self.fetcher.send(request).await;
 
+            self.contact.report(&result);
             self.emit_result(&result);
 
             result
@@ -136,7 +208,64 @@ impl GithubClient {
 
         self.cache
             .repository_files
-            .with_caching(format!("{owner_low}/{repository_low}/{path}"), fut)
+            .with_caching(cache_key, fut)
+            .await
+    }
+
+    /**
+        Fetches the public repositories of the given user or organization.
+
+        Repositories are sorted by most recently pushed to, and only the first
+        few pages are fetched, since owners with more repositories than that
+        are rare, and the most recently pushed ones are the most relevant.
+    */
+    pub async fn get_owner_repositories(&self, owner: &str) -> RequestResult<Vec<Repository>> {
+        let owner_low = owner.to_ascii_lowercase();
+
+        let repos_url = format!(
+            "{}/users/{owner_low}/repos?sort=pushed&per_page={OWNER_REPOSITORIES_PER_PAGE}",
+            self.api_url()
+        );
+
+        let fut = async move {
+            debug!("Fetching GitHub repositories for {owner}");
+
+            // NOTE: We make this inner scope so that
+            // we can catch and emit all errors at once
+            let inner = async {
+                let mut repos = Vec::new();
+                for page in 1..=OWNER_REPOSITORIES_MAX_PAGES {
+                    let bytes = self.request_get(format!("{repos_url}&page={page}")).await?;
+                    let page_repos = serde_json::from_slice::<Vec<Repository>>(&bytes)?;
+                    // A page that is not full is always the last one
+                    let is_last = page_repos.len() < OWNER_REPOSITORIES_PER_PAGE;
+                    repos.extend(page_repos);
+                    if is_last {
+                        break;
+                    }
+                }
+                Ok(repos)
+            }
+            .await;
+
+            self.emit_result(&inner);
+
+            inner
+        };
+
+        self.cache
+            .owner_repositories
+            .with_caching(owner_low, fut)
             .await
     }
+
+    /**
+        Sends a single uncached request to the API, to check that it can be reached.
+    */
+    pub async fn check_reachable(&self) -> bool {
+        let result = self
+            .request_get(format!("{}/rate_limit", self.api_url()))
+            .await;
+        request_reached(&result).unwrap_or(false)
+    }
 }
@@ -1,5 +1,17 @@
 #![allow(dead_code)]
 
+use std::sync::{Arc, RwLock};
+
+use async_channel::Receiver;
+use futures::join;
+use tracing::debug;
+use url::Url;
+
+mod cargo_config;
+mod contact;
+mod credentials;
+mod dependency;
+
 pub mod crates;
 pub mod github;
 pub mod npm;
@@ -10,26 +22,263 @@ use github::GithubClient;
 use npm::NpmClient;
 use wally::WallyClient;
 
+use crate::util::{Fetcher, Metrics, RequestLimiter, RequestResult};
+
+pub use cargo_config::{config_file_paths, CargoConfigFile, CargoConfigs};
+pub use contact::{Contact, ContactSource, ContactStates, Contacts};
+pub use credentials::SystemCredentials;
+pub use dependency::DependencyId;
+
 #[derive(Debug, Clone)]
 pub struct Clients {
     pub crates: CratesClient,
     pub github: GithubClient,
     pub npm: NpmClient,
     pub wally: WallyClient,
+    pub cargo_configs: CargoConfigs,
+    limiter: RequestLimiter,
+    fetcher: Fetcher,
+    contacts: Contacts,
+    metrics: Metrics,
+    rokit: Arc<RwLock<Option<GithubClient>>>,
+    credentials: Arc<RwLock<SystemCredentials>>,
+}
+
+impl Default for Clients {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Clients {
+    /**
+        Creates a new set of clients, sharing a single request limiter.
+    */
     pub fn new() -> Self {
-        let crates = CratesClient::new();
-        let github = GithubClient::new();
-        let npm = NpmClient::new();
-        let wally = WallyClient::new(github.clone());
+        Self::with_fetcher(Fetcher::default())
+    }
+
+    /**
+        Creates a new set of clients, sharing a single request limiter,
+        which send all of their requests using the given fetcher.
+    */
+    pub fn with_fetcher(fetcher: Fetcher) -> Self {
+        let limiter = RequestLimiter::default();
+        let contacts = Contacts::new();
+        let metrics = Metrics::default();
+
+        let crates = CratesClient::new(
+            limiter.clone(),
+            fetcher.clone(),
+            contacts.tracker(ContactSource::Crates),
+            &metrics,
+        );
+        let github = GithubClient::new(
+            limiter.clone(),
+            fetcher.clone(),
+            contacts.tracker(ContactSource::Github),
+            &metrics,
+        );
+        let npm = NpmClient::new(
+            limiter.clone(),
+            fetcher.clone(),
+            contacts.tracker(ContactSource::Npm),
+            &metrics,
+        );
+        let wally = WallyClient::new(github.clone(), &metrics);
 
         Self {
             crates,
             github,
             npm,
             wally,
+            cargo_configs: CargoConfigs::default(),
+            limiter,
+            fetcher,
+            contacts,
+            metrics,
+            rokit: Arc::new(RwLock::new(None)),
+            credentials: Arc::new(RwLock::new(SystemCredentials::default())),
         }
     }
+
+    /**
+        Sets the maximum number of concurrent web requests, shared by all clients.
+    */
+    pub fn set_max_concurrent_requests(&self, limit: usize) {
+        self.limiter.set_limit(limit);
+    }
+
+    /**
+        Clears all in-memory caches for all clients, forcing fresh data to
+        be fetched on the next request. There are no on-disk caches to clear.
+    */
+    pub fn invalidate_caches(&self) {
+        debug!("Clearing all client caches");
+        self.crates.invalidate_cache();
+        self.github.invalidate_cache();
+        self.npm.invalidate_cache();
+        self.wally.invalidate_cache();
+        self.cargo_configs.clear();
+        if let Some(rokit) = self
+            .rokit
+            .read()
+            .expect("rokit client lock was poisoned")
+            .as_ref()
+        {
+            rokit.invalidate_cache();
+        }
+    }
+
+    /**
+        Clears cached data for a single dependency, such as right after
+        publishing a new version of it, while keeping everything else cached.
+    */
+    pub async fn invalidate_dependency(&self, dependency: &DependencyId) {
+        match dependency {
+            DependencyId::Crates { name } => self.crates.invalidate_crate(name).await,
+            DependencyId::CratesRegistry { index_url, name } => {
+                self.crates.invalidate_registry_crate(index_url, name).await
+            }
+            DependencyId::Npm { name } => self.npm.invalidate_package(name).await,
+            DependencyId::Github { owner, repository } => {
+                self.rokit().invalidate_repository(owner, repository).await
+            }
+            DependencyId::Wally {
+                index_url,
+                scope,
+                name,
+            } => self.wally.invalidate_package(index_url, scope, name).await,
+        }
+    }
+
+    /**
+        Returns the client to use for Rokit tool sources, which is the
+        regular GitHub client unless a different API url was configured.
+    */
+    pub fn rokit(&self) -> GithubClient {
+        self.rokit
+            .read()
+            .expect("rokit client lock was poisoned")
+            .clone()
+            .unwrap_or_else(|| self.github.clone())
+    }
+
+    /**
+        Returns the client for Rokit tool sources only if it is separate from
+        the regular GitHub client, meaning that a different API url was configured.
+    */
+    pub fn custom_rokit(&self) -> Option<GithubClient> {
+        self.rokit
+            .read()
+            .expect("rokit client lock was poisoned")
+            .clone()
+    }
+
+    /**
+        Sets a different GitHub API url for Rokit tool sources, such as
+        for a GitHub Enterprise instance, or goes back to using the regular
+        GitHub client if no url is given. A different url gets its own client,
+        and uses any system credentials for its host instead of the token
+        for `github.com`, since those tokens are never valid for both.
+    */
+    pub fn set_rokit_api_url(&self, url: Option<&str>) -> RequestResult<()> {
+        let Some(url) = url else {
+            *self.rokit.write().expect("rokit client lock was poisoned") = None;
+            return Ok(());
+        };
+
+        let client = GithubClient::new(
+            self.limiter.clone(),
+            self.fetcher.clone(),
+            self.contacts.tracker(ContactSource::Github),
+            &self.metrics,
+        );
+        client.set_api_url(url)?;
+
+        let host = Url::parse(url)?.host_str().map(ToString::to_string);
+        let token = host.and_then(|host| {
+            self.credentials
+                .read()
+                .expect("credentials lock was poisoned")
+                .token_for_host(&host)
+                .map(ToString::to_string)
+        });
+        if let Some(token) = token {
+            debug!("Using token from system credentials for Rokit tool sources");
+            client.set_auth_token(token);
+        }
+
+        *self.rokit.write().expect("rokit client lock was poisoned") = Some(client);
+        Ok(())
+    }
+
+    /**
+        Returns the local usage metrics of these clients, including
+        hits and misses of every cache, which are disabled by default.
+    */
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /**
+        Returns a receiver for the outcome of the first request made
+        to each source, to let users know if networking works at all.
+    */
+    pub fn contacts(&self) -> Receiver<Contact> {
+        self.contacts.receiver()
+    }
+
+    /**
+        Checks if every source can be reached, by sending a single
+        uncached request to each of them, all at the same time.
+    */
+    pub async fn check_reachability(&self) -> Vec<Contact> {
+        let (crates, github, npm) = join!(
+            self.crates.check_reachable(),
+            self.github.check_reachable(),
+            self.npm.check_reachable(),
+        );
+        [
+            (ContactSource::Crates, crates),
+            (ContactSource::Github, github),
+            (ContactSource::Npm, npm),
+        ]
+        .into_iter()
+        .map(|(source, reached)| Contact { source, reached })
+        .collect()
+    }
+
+    /**
+        Sets discovered system credentials, to be used as a fallback.
+
+        Any explicitly configured credentials, such as a GitHub
+        token passed as an argument, are always preferred.
+    */
+    pub fn set_system_credentials(&self, credentials: SystemCredentials) {
+        if !self.github.has_auth_token() {
+            let token = credentials
+                .token_for_host("api.github.com")
+                .or_else(|| credentials.token_for_host("github.com"));
+            if let Some(token) = token {
+                debug!("Using GitHub token from system credentials");
+                self.github.set_auth_token(token);
+            }
+        }
+        *self
+            .credentials
+            .write()
+            .expect("credentials lock was poisoned") = credentials;
+    }
+
+    /**
+        Returns the discovered token for the given Cargo registry, if any.
+    */
+    pub fn registry_token(&self, registry: &str) -> Option<String> {
+        self.credentials
+            .read()
+            .expect("credentials lock was poisoned")
+            .token_for_registry(registry)
+            .map(ToString::to_string)
+    }
 }
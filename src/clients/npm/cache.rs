@@ -8,9 +8,19 @@ pub(super) struct NpmCache {
 }
 
 impl NpmCache {
-    pub fn new() -> Self {
+    pub fn new(metrics: &Metrics) -> Self {
         Self {
-            registry_metadatas: RequestCacheMap::new(60, 15),
+            // NOTE: Versions are shown directly in completions, so they are
+            // kept for the session and only refreshed when invalidated
+            registry_metadatas: RequestCacheMap::for_session().with_metrics(metrics),
         }
     }
+
+    pub fn invalidate(&self) {
+        self.registry_metadatas.invalidate();
+    }
+
+    pub fn invalidate_versions(&self) {
+        self.registry_metadatas.invalidate();
+    }
 }
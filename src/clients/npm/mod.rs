@@ -2,6 +2,8 @@ use tracing::error;
 
 use crate::util::*;
 
+use super::contact::ContactTracker;
+
 mod cache;
 use cache::*;
 
@@ -13,17 +15,43 @@ pub mod models;
 #[derive(Debug, Clone)]
 pub struct NpmClient {
     cache: NpmCache,
+    limiter: RequestLimiter,
+    fetcher: Fetcher,
+    contact: ContactTracker,
 }
 
 impl NpmClient {
-    pub fn new() -> Self {
+    pub fn new(
+        limiter: RequestLimiter,
+        fetcher: Fetcher,
+        contact: ContactTracker,
+        metrics: &Metrics,
+    ) -> Self {
         Self {
-            cache: NpmCache::new(),
+            cache: NpmCache::new(metrics),
+            limiter,
+            fetcher,
+            contact,
         }
     }
 
     async fn request_get(&self, url: impl Into<String>) -> RequestResult<Vec<u8>> {
-        Request::get(url).send().await
+        let _permit = self.limiter.acquire().await;
+        let result = self.fetcher.send(Request::get(url)).await;
+        self.contact.report(&result);
+        result
+    }
+
+    pub fn invalidate_cache(&self) {
+        self.cache.invalidate();
+    }
+
+    /**
+        Clears only cached versions, which are otherwise kept for the
+        whole session, so that newly published versions are picked up.
+    */
+    pub fn invalidate_versions(&self) {
+        self.cache.invalidate_versions();
     }
 
     fn emit_result<T>(&self, result: &RequestResult<T>) {
@@ -1,5 +1,6 @@
 use tracing::debug;
 
+use super::super::contact::request_reached;
 use super::consts::*;
 use super::models::*;
 use super::*;
@@ -37,4 +38,25 @@ impl NpmClient {
             .with_caching(registry_url.clone(), fut)
             .await
     }
+
+    /**
+        Clears cached metadata for a single package, so
+        that it is fetched again the next time it is used.
+    */
+    pub async fn invalidate_package(&self, name: &str) {
+        debug!("Clearing cached npm package metadata for '{name}'");
+        let name_low = name.to_ascii_lowercase();
+        self.cache
+            .registry_metadatas
+            .invalidate_key(&format!("{BASE_URL_REGISTRY}/{name_low}"))
+            .await;
+    }
+
+    /**
+        Sends a single uncached request to the registry, to check that it can be reached.
+    */
+    pub async fn check_reachable(&self) -> bool {
+        let result = self.request_get(BASE_URL_REGISTRY).await;
+        request_reached(&result).unwrap_or(false)
+    }
 }
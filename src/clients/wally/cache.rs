@@ -5,10 +5,11 @@ use super::models::*;
 #[derive(Debug, Clone)]
 pub(super) struct WallyCache {
     pub index_configs: RequestCacheMap<RequestResult<IndexConfig>>,
+    pub index_metadatas: RequestCacheMap<RequestResult<Vec<Metadata>>>,
 }
 
 impl WallyCache {
-    pub fn new() -> Self {
+    pub fn new(metrics: &Metrics) -> Self {
         Self {
             // NOTE: Registry configs should never really change,
             // so we can use a really long cache here. If a user
@@ -16,7 +17,20 @@ impl WallyCache {
             index_configs: RequestCacheMap::new(
                 60 * 24 * 30, // One month
                 60 * 24 * 7,  // One week
-            ),
+            )
+            .with_metrics(metrics),
+            // NOTE: Versions are shown directly in completions, so they are
+            // kept for the session and only refreshed when invalidated
+            index_metadatas: RequestCacheMap::for_session().with_metrics(metrics),
         }
     }
+
+    pub fn invalidate(&self) {
+        self.index_configs.invalidate();
+        self.index_metadatas.invalidate();
+    }
+
+    pub fn invalidate_versions(&self) {
+        self.index_metadatas.invalidate();
+    }
 }
@@ -10,15 +10,27 @@ pub mod models;
 
 #[derive(Debug, Clone)]
 pub struct WallyClient {
-    _cache: WallyCache,
+    cache: WallyCache,
     github: GithubClient,
 }
 
 impl WallyClient {
-    pub fn new(github: GithubClient) -> Self {
+    pub fn new(github: GithubClient, metrics: &Metrics) -> Self {
         Self {
-            _cache: WallyCache::new(),
+            cache: WallyCache::new(metrics),
             github,
         }
     }
+
+    pub fn invalidate_cache(&self) {
+        self.cache.invalidate();
+    }
+
+    /**
+        Clears only cached versions, which are otherwise kept for the
+        whole session, so that newly published versions are picked up.
+    */
+    pub fn invalidate_versions(&self) {
+        self.cache.invalidate_versions();
+    }
 }
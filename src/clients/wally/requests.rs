@@ -25,7 +25,7 @@ impl WallyClient {
             Ok(config)
         };
 
-        self._cache
+        self.cache
             .index_configs
             .with_caching(format!("{owner}/{repo}"), fut)
             .await
@@ -136,7 +136,75 @@ impl WallyClient {
     ) -> RequestResult<Vec<Metadata>> {
         let scope_low = scope.to_ascii_lowercase();
         let name_low = name.to_ascii_lowercase();
+        let index_url_low = index_url.to_ascii_lowercase();
 
+        let fut = async {
+            self.get_index_metadatas_uncached(index_url, &scope_low, &name_low)
+                .await
+        };
+
+        self.cache
+            .index_metadatas
+            .with_caching(format!("{index_url_low}/{scope_low}/{name_low}"), fut)
+            .await
+    }
+
+    /**
+        Returns metadatas for a package only if they have already been fetched,
+        for places where making a request for every single package is too slow.
+    */
+    pub async fn get_cached_index_metadatas(
+        &self,
+        index_url: &str,
+        scope: &str,
+        name: &str,
+    ) -> Option<Vec<Metadata>> {
+        let scope_low = scope.to_ascii_lowercase();
+        let name_low = name.to_ascii_lowercase();
+        let index_url_low = index_url.to_ascii_lowercase();
+
+        self.cache
+            .index_metadatas
+            .get_cached(&format!("{index_url_low}/{scope_low}/{name_low}"))
+            .await?
+            .ok()
+    }
+
+    /**
+        Clears cached metadatas for a single package, including the index
+        files they were read from, in the given index and all of its fallbacks.
+    */
+    pub async fn invalidate_package(&self, index_url: &str, scope: &str, name: &str) {
+        let scope_low = scope.to_ascii_lowercase();
+        let name_low = name.to_ascii_lowercase();
+        let index_url_low = index_url.to_ascii_lowercase();
+
+        // NOTE: Index configs are cached for much longer than this,
+        // so looking up the fallbacks here should never fetch anything
+        let index_urls = self
+            .get_index_urls_following_fallbacks(index_url)
+            .await
+            .unwrap_or_default();
+        for index_url in index_urls {
+            if let Ok((owner, repo)) = parse_index_url(&index_url) {
+                self.github
+                    .invalidate_repository_file(&owner, &repo, &format!("{scope_low}/{name_low}"))
+                    .await;
+            }
+        }
+
+        self.cache
+            .index_metadatas
+            .invalidate_key(&format!("{index_url_low}/{scope_low}/{name_low}"))
+            .await;
+    }
+
+    async fn get_index_metadatas_uncached(
+        &self,
+        index_url: &str,
+        scope_low: &str,
+        name_low: &str,
+    ) -> RequestResult<Vec<Metadata>> {
         for index_url in self.get_index_urls_following_fallbacks(index_url).await? {
             let (owner, repo) = parse_index_url(&index_url)?;
 
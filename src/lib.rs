@@ -0,0 +1,49 @@
+//! Parsing and fetching for tooling manifests such as `Cargo.toml`,
+//! `package.json`, `rokit.toml`, and `wally.toml`.
+//!
+//! The language server itself is one consumer of this library, and other
+//! tools may use the same building blocks directly, without any of the LSP:
+//!
+//! - [`parser`] - parses manifests and queries them for dependencies, features, ...
+//! - [`clients`] - fetches data from registries and GitHub, with caching
+//! - [`util`] - versioning helpers shared by the above, such as [`Versioned`]
+//!
+//! ```no_run
+//! use tooling_language_server::clients::Clients;
+//! use tooling_language_server::parser::{query_cargo_toml_dependencies, TreeSitterDocument};
+//! use tooling_language_server::Versioned;
+//!
+//! # async fn example() {
+//! let contents = "[dependencies]\ntokio = \"1.25\"";
+//! let doc = TreeSitterDocument::new_file("Cargo.toml", contents).unwrap();
+//!
+//! let clients = Clients::new();
+//! for dep in query_cargo_toml_dependencies(&doc) {
+//!     let name = dep.package_name().unquoted();
+//!     let versions = clients.crates.get_sparse_index_crate_metadatas(name).await;
+//!     println!("{name} {:?} - {} versions", dep.parse_version(), versions.map_or(0, |v| v.len()));
+//! }
+//! # }
+//! ```
+
+/**
+    Clients for fetching data from registries and GitHub.
+*/
+pub mod clients;
+
+/**
+    Parsing and querying of manifest files, using tree-sitter.
+*/
+pub mod parser;
+
+/**
+    Versioning and request utilities, shared by parsers and clients.
+*/
+pub mod util;
+
+mod cli;
+mod server;
+mod tools;
+
+pub use cli::Cli;
+pub use util::{VersionReqExt, Versioned};
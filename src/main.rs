@@ -1,11 +1,11 @@
-mod cli;
-mod clients;
-mod parser;
-mod server;
-mod tools;
-mod util;
+use tooling_language_server::Cli;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    cli::Cli::new().run().await
+    let code = Cli::new().run().await?;
+
+    // Exit right away instead of returning, since reading from stdin
+    // blocks the runtime from shutting down until the next read
+    // completes, which may never happen once the client is gone
+    std::process::exit(code)
 }
@@ -19,24 +19,45 @@ pub struct TreeSitterDocument {
 }
 
 impl TreeSitterDocument {
+    /**
+        Creates a new document from a file uri and its contents.
+
+        Returns `None` if the file is not a known manifest, such as `Cargo.toml`.
+    */
     pub fn new(file_uri: impl Into<Arc<Url>>, contents: impl Into<Arc<str>>) -> Option<Self> {
         let uri: Arc<Url> = file_uri.into();
         let contents: Arc<str> = contents.into();
 
         let language = TreeSitterLanguage::from_file_uri(&uri)?;
-        let tree = language
-            .parser()
-            .parse(contents.as_bytes(), None)
-            .expect("no fallible flags set");
+        Some(Self::new_with_language(uri, contents, language))
+    }
 
-        Some(Self {
+    /**
+        Creates a new document from a file uri and its contents, using the
+        given language, regardless of what the file name of the uri is.
+    */
+    pub fn new_with_language(
+        file_uri: impl Into<Arc<Url>>,
+        contents: impl Into<Arc<str>>,
+        language: TreeSitterLanguage,
+    ) -> Self {
+        let uri: Arc<Url> = file_uri.into();
+        let contents: Arc<str> = contents.into();
+        let tree = language.parse(contents.as_bytes());
+
+        Self {
             uri,
             contents,
             language,
             tree,
-        })
+        }
     }
 
+    /**
+        Creates a new document from a file path and its contents.
+
+        Relative paths are resolved using the current directory.
+    */
     pub fn new_file(file_path: impl AsRef<Path>, contents: impl Into<Arc<str>>) -> Option<Self> {
         let file_path: &Path = file_path.as_ref();
         let contents: Arc<str> = contents.into();
@@ -59,11 +80,7 @@ impl TreeSitterDocument {
     pub fn set_contents(&mut self, new_contents: impl Into<Arc<str>>) {
         let contents: Arc<str> = new_contents.into();
         self.contents = contents;
-        self.tree = self
-            .language
-            .parser()
-            .parse(self.contents.as_bytes(), None)
-            .expect("no fallible flags set")
+        self.tree = self.language.parse(self.contents.as_bytes());
     }
 
     pub fn uri(&self) -> &Url {
@@ -74,7 +91,7 @@ impl TreeSitterDocument {
         &self.contents
     }
 
-    pub fn query(&self, query: &str) -> Option<Query> {
+    pub fn query(&self, query: &'static str) -> Option<Arc<Query>> {
         self.language.query(query)
     }
 }
@@ -1,6 +1,9 @@
+use std::cell::RefCell;
 use std::path::Path;
+use std::sync::{Arc, OnceLock};
 
-use tree_sitter::{Parser, Query};
+use dashmap::DashMap;
+use tree_sitter::{Language, Parser, Query, Tree};
 use tree_sitter_language::LanguageFn;
 use url::Url;
 
@@ -14,10 +17,30 @@ pub const TOML_FILE_NAMES: &[&str] = &[
     "rokit.toml",
 ];
 
+static JSON_LANGUAGE: OnceLock<Language> = OnceLock::new();
+static TOML_LANGUAGE: OnceLock<Language> = OnceLock::new();
+
+// NOTE: Query strings are all constants, so this never grows past
+// one entry per query, and compiling them is much slower than lookups
+type CompiledQueries = DashMap<(TreeSitterLanguage, &'static str), Option<Arc<Query>>>;
+static QUERIES: OnceLock<CompiledQueries> = OnceLock::new();
+
+thread_local! {
+    /*
+        Parsers are reused for every parse on the same thread, instead of
+        being created and configured again for every single document change.
+
+        NOTE: Parsers can't be shared between threads, but parsing never
+        awaits anything, so a parser is never used by two requests at once.
+    */
+    static JSON_PARSER: RefCell<Option<Parser>> = const { RefCell::new(None) };
+    static TOML_PARSER: RefCell<Option<Parser>> = const { RefCell::new(None) };
+}
+
 /**
     Tree-sitter language for a given file.
 */
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TreeSitterLanguage {
     Json,
     Toml,
@@ -56,16 +79,53 @@ impl TreeSitterLanguage {
         }
     }
 
-    pub fn parser(&self) -> Parser {
-        let f = self.language_fn();
-        let mut parser = Parser::new();
-        parser.set_language(&f.into()).unwrap();
-        parser
+    /**
+        Gets the tree-sitter language, which is only created once and then shared.
+    */
+    pub fn language(&self) -> &'static Language {
+        let lock = match self {
+            Self::Json => &JSON_LANGUAGE,
+            Self::Toml => &TOML_LANGUAGE,
+        };
+        lock.get_or_init(|| self.language_fn().into())
     }
 
-    pub fn query(&self, query: &str) -> Option<Query> {
-        let f = self.language_fn();
-        Query::new(&f.into(), query).ok()
+    /**
+        Parses the given contents into a new tree, using a parser
+        for this language that is reused for the current thread.
+    */
+    pub fn parse(&self, contents: &[u8]) -> Tree {
+        let parser = match self {
+            Self::Json => &JSON_PARSER,
+            Self::Toml => &TOML_PARSER,
+        };
+        parser.with_borrow_mut(|parser| {
+            parser
+                .get_or_insert_with(|| {
+                    let mut parser = Parser::new();
+                    parser.set_language(self.language()).unwrap();
+                    parser
+                })
+                .parse(contents, None)
+                .expect("no fallible flags set")
+        })
+    }
+
+    /**
+        Gets the given query compiled for this language, which is only
+        compiled the first time it is used and then shared by all threads.
+
+        Returns `None` if the query is not valid for this language.
+    */
+    pub fn query(&self, query: &'static str) -> Option<Arc<Query>> {
+        let queries = QUERIES.get_or_init(DashMap::new);
+        if let Some(compiled) = queries.get(&(*self, query)) {
+            return compiled.clone();
+        }
+        queries
+            .entry((*self, query))
+            .or_insert_with(|| Query::new(self.language(), query).ok().map(Arc::new))
+            .clone()
     }
 }
 
@@ -105,4 +165,27 @@ mod tests {
         test("package.txt", None);
         test("package.json.txt", None);
     }
+
+    #[test]
+    fn test_parse_reuses_parser() {
+        let first = TreeSitterLanguage::Toml.parse(b"[dependencies]\nserde = \"1\"\n");
+        let second = TreeSitterLanguage::Toml.parse(b"[package]\nname = \"app\"\n");
+        let json = TreeSitterLanguage::Json.parse(b"{}");
+        assert!(!first.root_node().has_error());
+        assert!(!second.root_node().has_error());
+        assert!(!json.root_node().has_error());
+        assert!(TOML_PARSER.with_borrow(Option::is_some));
+    }
+
+    #[test]
+    fn test_query_compiled_once() {
+        const QUERY: &str = "(table (bare_key) @name)";
+        let first = TreeSitterLanguage::Toml.query(QUERY).unwrap();
+        let second = TreeSitterLanguage::Toml.query(QUERY).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        // The same query is compiled separately for each language
+        assert!(TreeSitterLanguage::Json.query(QUERY).is_none());
+        assert!(TreeSitterLanguage::Toml.query(QUERY).is_some());
+    }
 }
@@ -11,9 +11,16 @@ mod query_utils;
 pub use self::document::TreeSitterDocument;
 pub use self::language::TreeSitterLanguage;
 pub use self::query_fns::{
-    query_cargo_toml_dependencies, query_package_json_dependencies, query_rokit_toml_dependencies,
-    query_wally_toml_dependencies,
+    query_cargo_toml_dependencies, query_cargo_toml_duplicate_tables, query_cargo_toml_features,
+    query_cargo_toml_inherited_package_fields, query_cargo_toml_lints,
+    query_cargo_toml_package_fields, query_cargo_toml_table_headers, query_cargo_toml_targets,
+    query_cargo_toml_workspace_dependencies, query_cargo_toml_workspace_fields,
+    query_cargo_toml_workspace_package_fields, query_package_json_dependencies,
+    query_rokit_toml_dependencies, query_rokit_toml_has_tools_table,
+    query_rokit_toml_misplaced_tools, query_wally_toml_dependencies, query_wally_toml_sections,
 };
 pub use self::query_structs::{
-    Dependency, DependencyKind, DependencySource, DependencySpec, Node, SimpleDependency,
+    Dependency, DependencyKind, DependencySource, DependencySpec, DuplicateTable, Feature,
+    LintTable, MisplacedTool, Node, ParsedSpecFull, SimpleDependency, TableField, Target,
+    TargetKind,
 };
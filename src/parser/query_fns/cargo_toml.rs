@@ -5,10 +5,30 @@ use tree_sitter::QueryCursor;
 use crate::parser::query_utils::{range_extend, range_from_node};
 
 use super::super::document::TreeSitterDocument;
-use super::super::query_strings::CARGO_TOML_DEPENDENCIES_QUERY;
+use super::super::query_strings::{
+    CARGO_TOML_DEPENDENCIES_QUERY, CARGO_TOML_FEATURES_QUERY, CARGO_TOML_PACKAGE_QUERY,
+    CARGO_TOML_WORKSPACE_QUERY,
+};
 use super::super::query_structs::{
-    Dependency, DependencyKind, DependencySource, DependencySpec, Node,
+    Dependency, DependencyKind, DependencySource, DependencySpec, DuplicateTable, Feature, Node,
+    TableField,
+};
+
+use super::{table_keys, table_pairs};
+
+mod fallback;
+mod inheritance;
+mod lints;
+mod targets;
+
+pub use inheritance::{
+    query_cargo_toml_inherited_package_fields, query_cargo_toml_workspace_dependencies,
+    query_cargo_toml_workspace_package_fields,
 };
+pub use lints::query_cargo_toml_lints;
+pub use targets::query_cargo_toml_targets;
+
+use fallback::fallback_cargo_toml_dependencies;
 
 pub fn query_cargo_toml_dependencies(doc: &TreeSitterDocument) -> Vec<Dependency> {
     let Some(query) = doc.query(CARGO_TOML_DEPENDENCIES_QUERY) else {
@@ -21,11 +41,19 @@ pub fn query_cargo_toml_dependencies(doc: &TreeSitterDocument) -> Vec<Dependency
     let mut it = cursor.matches(&query, doc.tree.root_node(), doc.contents.as_bytes());
     while let Some(m) = it.next() {
         let mut dep_kind = None;
+        let mut dep_target = None;
         let mut dep_range = None;
         let mut dep_name_node = None;
         let mut version_node = None;
         let mut features = Vec::new();
         let mut features_range = None;
+        let mut package_node = None;
+        let mut optional_node = None;
+        let mut registry_node = None;
+        let mut path_node = None;
+        let mut git_node = None;
+        let mut git_reference = None;
+        let mut keys_node = None;
         let mut spec_range = None::<Range>;
 
         for capture in m.captures {
@@ -43,12 +71,55 @@ pub fn query_cargo_toml_dependencies(doc: &TreeSitterDocument) -> Vec<Dependency
                         _ => continue,
                     });
                 }
+                "target_spec" => {
+                    dep_target = Some(node_text.trim_matches(['"', '\'']).to_string());
+                }
                 "dependency_name" | "incomplete_dependency_name" => {
                     dep_name_node = Some(Node::string(&capture.node, node_text));
                 }
                 "version" => {
                     version_node = Some(Node::string(&capture.node, node_text));
                 }
+                "dependency_table" | "dependency_full_capture" => {
+                    let keys = table_keys(&capture.node, &doc.contents);
+                    keys_node = Some(Node::new(&capture.node, keys));
+                    for (key, value) in table_pairs(&capture.node, &doc.contents) {
+                        let Ok(value_text) = value.utf8_text(doc.contents.as_bytes()) else {
+                            continue;
+                        };
+                        match (key, value.kind()) {
+                            ("git", "string") => {
+                                git_node = Some(Node::string(&value, value_text));
+                            }
+                            ("rev" | "tag" | "branch", "string") => {
+                                let Some(key_node) = value.prev_named_sibling() else {
+                                    continue;
+                                };
+                                let Ok(key_text) = key_node.utf8_text(doc.contents.as_bytes())
+                                else {
+                                    continue;
+                                };
+                                git_reference = Some(TableField {
+                                    name: Node::string(&key_node, key_text),
+                                    value: Node::string(&value, value_text),
+                                });
+                            }
+                            ("package", "string") => {
+                                package_node = Some(Node::string(&value, value_text));
+                            }
+                            ("optional", "boolean") => {
+                                optional_node = Some(Node::new(&value, value_text == "true"));
+                            }
+                            ("registry", "string") => {
+                                registry_node = Some(Node::string(&value, value_text));
+                            }
+                            ("path", "string") => {
+                                path_node = Some(Node::string(&value, value_text));
+                            }
+                            _ => {}
+                        }
+                    }
+                }
                 "features_array" => {
                     if features_range.is_none() {
                         features_range = Some(&capture.node);
@@ -94,8 +165,22 @@ pub fn query_cargo_toml_dependencies(doc: &TreeSitterDocument) -> Vec<Dependency
             }
         }
 
+        // A renamed dependency may not have any version or features, but the
+        // package name, registry, path, git url, and any other keys should still be a part of its spec
+        let keys = keys_node.iter().flat_map(|k| k.contents.iter());
+        for node in [&package_node, &registry_node, &path_node, &git_node]
+            .into_iter()
+            .flatten()
+            .chain(keys)
+        {
+            spec_range = Some(match spec_range {
+                Some(srange) => range_extend(node.range, srange),
+                None => node.range,
+            });
+        }
+
         if let (Some(dep_kind), Some(range), Some(name)) = (dep_kind, dep_range, dep_name_node) {
-            dependencies.push(Dependency::new_opt(
+            let dependency = Dependency::new_opt(
                 dep_kind,
                 range,
                 name,
@@ -103,23 +188,230 @@ pub fn query_cargo_toml_dependencies(doc: &TreeSitterDocument) -> Vec<Dependency
                     Node::new_raw(
                         r,
                         DependencySpec {
-                            source: DependencySource::Registry,
+                            source: match (path_node, git_node) {
+                                (Some(path), _) => DependencySource::Path { path },
+                                (None, Some(url)) => DependencySource::Git {
+                                    url,
+                                    reference: git_reference,
+                                },
+                                (None, None) => DependencySource::Registry,
+                            },
                             version: version_node,
                             features: features_range.map(|r| Node::new(r, features)),
+                            package: package_node,
+                            optional: optional_node,
+                            registry: registry_node,
+                            keys: keys_node,
                         },
                     )
                 }),
-            ));
+            );
+            dependencies.push(dependency.with_target(dep_target));
         }
     }
 
+    // Dependencies may be gathered from several tables, and even from duplicate
+    // tables with the same header, so make sure they are always in document order
+    dependencies.sort_by_key(|dep| (dep.range().start, dep.range().end));
+
+    // Finding nothing at all in a manifest that failed to parse most likely means
+    // that the grammar gave up on it, so try to recover what we can without it
+    if dependencies.is_empty() && doc.tree.root_node().has_error() {
+        dependencies = fallback_cargo_toml_dependencies(&doc.contents);
+    }
+
     dependencies
 }
 
+/**
+    Finds all tables with a header that was already used by a previous
+    table, such as a second `[dependencies]` table, which TOML forbids.
+
+    Note that this does not use a query, since tables are always
+    top-level and can be found by simply walking the root node.
+*/
+pub fn query_cargo_toml_duplicate_tables(doc: &TreeSitterDocument) -> Vec<DuplicateTable> {
+    let mut seen = Vec::<(String, Node<String>)>::new();
+    let mut duplicates = Vec::new();
+
+    let root = doc.tree.root_node();
+    for child in root.named_children(&mut root.walk()) {
+        if child.kind() != "table" {
+            continue;
+        }
+        let Some(key) = child.named_child(0) else {
+            continue;
+        };
+        let Ok(key_text) = key.utf8_text(doc.contents.as_bytes()) else {
+            continue;
+        };
+
+        // Headers such as [ dependencies ] and [dependencies] are the same table
+        let normalized = key_text
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>();
+        let node = Node::string(&key, key_text);
+        match seen.iter().find(|(header, _)| *header == normalized) {
+            Some((_, first)) => duplicates.push(DuplicateTable {
+                first: first.clone(),
+                duplicate: node,
+            }),
+            None => seen.push((normalized, node)),
+        }
+    }
+
+    duplicates
+}
+
+/**
+    Finds the headers of all tables, without any whitespace, such as
+    `package` and `workspace.dependencies`, in the order they appear in.
+
+    Tables without any keys are also included, which matters for tables
+    like `[workspace]`, where the table existing at all is meaningful.
+*/
+pub fn query_cargo_toml_table_headers(doc: &TreeSitterDocument) -> Vec<String> {
+    let root = doc.tree.root_node();
+    root.named_children(&mut root.walk())
+        .filter(|child| child.kind() == "table")
+        .filter_map(|child| child.named_child(0))
+        .filter_map(|key| key.utf8_text(doc.contents.as_bytes()).ok())
+        .map(|key| key.chars().filter(|c| !c.is_whitespace()).collect())
+        .collect()
+}
+
+pub fn query_cargo_toml_features(doc: &TreeSitterDocument) -> Vec<Feature> {
+    let Some(query) = doc.query(CARGO_TOML_FEATURES_QUERY) else {
+        return Vec::new();
+    };
+
+    let mut cursor = QueryCursor::new();
+    let mut features = Vec::new();
+
+    let mut it = cursor.matches(&query, doc.tree.root_node(), doc.contents.as_bytes());
+    while let Some(m) = it.next() {
+        let mut feature_name_node = None;
+        let mut feature_values_node = None;
+
+        for capture in m.captures {
+            let capture_name = query.capture_names()[capture.index as usize];
+            let Ok(node_text) = capture.node.utf8_text(doc.contents.as_bytes()) else {
+                continue;
+            };
+
+            match capture_name {
+                "feature_name" => {
+                    feature_name_node = Some(Node::string(&capture.node, node_text));
+                }
+                "feature_values" => {
+                    let mut values = Vec::new();
+                    for child in capture.node.named_children(&mut capture.node.walk()) {
+                        if child.kind() == "string" {
+                            if let Ok(child_text) = child.utf8_text(doc.contents.as_bytes()) {
+                                values.push(Node::string(&child, child_text));
+                            };
+                        }
+                    }
+                    feature_values_node = Some(Node::new(&capture.node, values));
+                }
+                _ => {}
+            }
+        }
+
+        if let (Some(name), Some(values)) = (feature_name_node, feature_values_node) {
+            features.push(Feature { name, values });
+        }
+    }
+
+    features
+}
+
+pub fn query_cargo_toml_package_fields(doc: &TreeSitterDocument) -> Vec<TableField> {
+    let Some(query) = doc.query(CARGO_TOML_PACKAGE_QUERY) else {
+        return Vec::new();
+    };
+
+    let mut cursor = QueryCursor::new();
+    let mut fields = Vec::new();
+
+    let mut it = cursor.matches(&query, doc.tree.root_node(), doc.contents.as_bytes());
+    while let Some(m) = it.next() {
+        let mut field_name_node = None;
+        let mut field_value_node = None;
+
+        for capture in m.captures {
+            let capture_name = query.capture_names()[capture.index as usize];
+            let Ok(node_text) = capture.node.utf8_text(doc.contents.as_bytes()) else {
+                continue;
+            };
+
+            match capture_name {
+                "field_name" => {
+                    field_name_node = Some(Node::string(&capture.node, node_text));
+                }
+                "field_value" => {
+                    field_value_node = Some(Node::string(&capture.node, node_text));
+                }
+                _ => {}
+            }
+        }
+
+        if let (Some(name), Some(value)) = (field_name_node, field_value_node) {
+            fields.push(TableField { name, value });
+        }
+    }
+
+    fields
+}
+
+/**
+    Finds all entries in the array fields of the `[workspace]` table, such as
+    `members` and `exclude`, with one field for each entry in each array.
+*/
+pub fn query_cargo_toml_workspace_fields(doc: &TreeSitterDocument) -> Vec<TableField> {
+    let Some(query) = doc.query(CARGO_TOML_WORKSPACE_QUERY) else {
+        return Vec::new();
+    };
+
+    let mut cursor = QueryCursor::new();
+    let mut fields = Vec::new();
+
+    let mut it = cursor.matches(&query, doc.tree.root_node(), doc.contents.as_bytes());
+    while let Some(m) = it.next() {
+        let mut field_name_node = None;
+        let mut field_value_node = None;
+
+        for capture in m.captures {
+            let capture_name = query.capture_names()[capture.index as usize];
+            let Ok(node_text) = capture.node.utf8_text(doc.contents.as_bytes()) else {
+                continue;
+            };
+
+            match capture_name {
+                "field_name" => {
+                    field_name_node = Some(Node::string(&capture.node, node_text));
+                }
+                "field_value" => {
+                    field_value_node = Some(Node::string(&capture.node, node_text));
+                }
+                _ => {}
+            }
+        }
+
+        if let (Some(name), Some(value)) = (field_name_node, field_value_node) {
+            fields.push(TableField { name, value });
+        }
+    }
+
+    fields
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::Path;
 
+    use tower_lsp::lsp_types::Position;
     use url::Url;
 
     use super::*;
@@ -255,6 +547,165 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_target_dependencies() {
+        test_dependencies(
+            r#"
+            [target.'cfg(unix)'.dependencies]
+            libc = "0.2"
+
+            [target."cfg(windows)".dev-dependencies]
+            windows-sys = { version = "0.52", features = ["Win32_Foundation"] }
+
+            [target.x86_64-pc-windows-msvc.build-dependencies]
+            cc = "1.0"
+
+            [target.'cfg(unix)'.dependencies.nix]
+            version = "0.28"
+            features = ["fs"]
+            "#,
+            vec![
+                (DependencyKind::Default, "libc", "0.2", vec![]),
+                (
+                    DependencyKind::Dev,
+                    "windows-sys",
+                    "0.52",
+                    vec!["Win32_Foundation"],
+                ),
+                (DependencyKind::Build, "cc", "1.0", vec![]),
+                (DependencyKind::Default, "nix", "0.28", vec!["fs"]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_target_dependencies_platforms() {
+        let contents = r#"
+            [dependencies]
+            serde = "1.0"
+
+            [target.'cfg(unix)'.dependencies]
+            libc = "0.2"
+
+            [target."cfg(unix)".dependencies.nix]
+            version = "0.28"
+
+            [target.x86_64-pc-windows-msvc.build-dependencies]
+            cc = "1.0"
+            "#;
+        let file = TreeSitterDocument::new_file(Path::new("Cargo.toml"), contents).unwrap();
+        let targets = query_cargo_toml_dependencies(&file)
+            .iter()
+            .map(|dep| {
+                (
+                    dep.name().unquoted().to_string(),
+                    dep.target().map(String::from),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            targets,
+            vec![
+                (String::from("serde"), None),
+                (String::from("libc"), Some(String::from("cfg(unix)"))),
+                (String::from("nix"), Some(String::from("cfg(unix)"))),
+                (
+                    String::from("cc"),
+                    Some(String::from("x86_64-pc-windows-msvc"))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_workspace_dependencies() {
+        test_dependencies(
+            r#"
+            [workspace.dependencies]
+            serde = "1.0"
+            "#,
+            vec![(DependencyKind::Default, "serde", "1.0", vec![])],
+        );
+    }
+
+    #[test]
+    fn test_empty_documents() {
+        let path = Path::new("Cargo.toml");
+        for contents in ["", "  \n\t\n", "[dependencies]", "[dependencies]\n"] {
+            let file = TreeSitterDocument::new_file(path, contents).unwrap();
+            assert!(query_cargo_toml_dependencies(&file).is_empty());
+            assert!(query_cargo_toml_features(&file).is_empty());
+            assert!(query_cargo_toml_package_fields(&file).is_empty());
+            assert!(query_cargo_toml_workspace_fields(&file).is_empty());
+            assert!(query_cargo_toml_duplicate_tables(&file).is_empty());
+            assert!(query_cargo_toml_targets(&file).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_position_past_last_dependency() {
+        let path = Path::new("Cargo.toml");
+        let contents = "[dependencies]\nserde = \"1.0\"\n";
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        let deps = query_cargo_toml_dependencies(&file);
+
+        assert!(Dependency::find_at_pos(&deps, Position::new(1, 13)).is_some());
+        assert!(Dependency::find_at_pos(&deps, Position::new(2, 0)).is_none());
+        assert!(Dependency::find_at_pos(&deps, Position::new(3, 5)).is_none());
+        assert!(Dependency::find_at_pos(&deps, Position::new(u32::MAX, u32::MAX)).is_none());
+    }
+
+    #[test]
+    fn test_lints_and_profiles() {
+        test_dependencies(
+            r#"
+            [package]
+            name = "my-crate"
+
+            [lints]
+            workspace = true
+
+            [lints.rust]
+            unsafe_code = "forbid"
+            unexpected_cfgs = { level = "warn", check-cfg = ["cfg(foo)"] }
+
+            [dependencies]
+            tokio = "1.25.0"
+            serde = { version = "1.0.160", features = ["derive"] }
+
+            [profile.release]
+            lto = "thin"
+            opt-level = 3
+
+            [profile.dev.package."*"]
+            opt-level = 2
+
+            [profile.release.build-override]
+            debug = true
+
+            [dev-dependencies]
+            pretty_assertions = "1.3.0"
+
+            [workspace.lints.clippy]
+            pedantic = { level = "warn", priority = -1 }
+            version = "1.0"
+
+            [workspace.dependencies]
+            anyhow = "1.0"
+
+            [dependencies.cc]
+            version = "1.0"
+            "#,
+            vec![
+                (DependencyKind::Default, "tokio", "1.25.0", vec![]),
+                (DependencyKind::Default, "serde", "1.0.160", vec!["derive"]),
+                (DependencyKind::Dev, "pretty_assertions", "1.3.0", vec![]),
+                (DependencyKind::Default, "anyhow", "1.0", vec![]),
+                (DependencyKind::Default, "cc", "1.0", vec![]),
+            ],
+        );
+    }
+
     #[test]
     fn test_features() {
         test_dependencies(
@@ -271,6 +722,341 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_renamed_dependency() {
+        let contents = r#"
+        [dependencies]
+        foo = { package = "real-crate", version = "1.0" }
+
+        [dependencies.bar]
+        package = "other-crate"
+        version = "2.0"
+        "#;
+
+        let path = Path::new("Cargo.toml");
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        let deps = query_cargo_toml_dependencies(&file);
+
+        assert_eq!(deps.len(), 2, "mismatched number of dependencies");
+
+        let foo = deps.first().unwrap();
+        assert_eq!(foo.name().contents, "foo");
+        assert_eq!(foo.package_name().unquoted(), "real-crate");
+        assert!(foo.is_renamed());
+
+        let bar = deps.get(1).unwrap();
+        assert_eq!(bar.name().contents, "bar");
+        assert_eq!(bar.package_name().unquoted(), "other-crate");
+        assert!(bar.is_renamed());
+    }
+
+    #[test]
+    fn test_not_renamed_dependency() {
+        let contents = r#"
+        [dependencies]
+        tokio = { version = "1.0" }
+        "#;
+
+        let path = Path::new("Cargo.toml");
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        let deps = query_cargo_toml_dependencies(&file);
+
+        let dep = deps.first().unwrap();
+        assert_eq!(dep.package_name().unquoted(), "tokio");
+        assert!(!dep.is_renamed());
+    }
+
+    #[test]
+    fn test_optional_dependency() {
+        let contents = r#"
+        [dependencies]
+        serde = { version = "1.0", optional = true }
+        tokio = { version = "1.0", optional = false }
+        "#;
+
+        let path = Path::new("Cargo.toml");
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        let deps = query_cargo_toml_dependencies(&file);
+
+        assert_eq!(deps.len(), 2, "mismatched number of dependencies");
+        assert!(deps[0].spec().unwrap().contents.is_optional());
+        assert!(!deps[1].spec().unwrap().contents.is_optional());
+    }
+
+    #[test]
+    fn test_registry_dependency() {
+        let contents = r#"
+        [dependencies]
+        foo = { version = "1.0", registry = "my-registry" }
+        bar = { registry = "" }
+        baz = "1.0"
+        "#;
+
+        let path = Path::new("Cargo.toml");
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        let deps = query_cargo_toml_dependencies(&file);
+
+        assert_eq!(deps.len(), 3, "mismatched number of dependencies");
+        let registry = |dep: &Dependency| {
+            dep.spec()
+                .and_then(|s| s.contents.registry.as_ref())
+                .map(|r| r.unquoted().to_string())
+        };
+        assert_eq!(registry(&deps[0]).as_deref(), Some("my-registry"));
+        assert_eq!(registry(&deps[1]).as_deref(), Some(""));
+        assert_eq!(registry(&deps[2]), None);
+    }
+
+    #[test]
+    fn test_path_dependency() {
+        let contents = r#"
+        [dependencies]
+        foo = { path = "../foo" }
+        bar = { version = "1.0", path = "crates/bar" }
+        baz = "1.0"
+
+        [dependencies.qux]
+        path = "qux"
+        "#;
+
+        let path = Path::new("Cargo.toml");
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        let deps = query_cargo_toml_dependencies(&file);
+
+        assert_eq!(deps.len(), 4, "mismatched number of dependencies");
+        let path = |dep: &Dependency| match dep.spec().map(|s| &s.contents.source) {
+            Some(DependencySource::Path { path }) => Some(path.unquoted().to_string()),
+            _ => None,
+        };
+        assert_eq!(path(&deps[0]).as_deref(), Some("../foo"));
+        assert_eq!(path(&deps[1]).as_deref(), Some("crates/bar"));
+        assert_eq!(path(&deps[2]), None);
+        assert_eq!(path(&deps[3]).as_deref(), Some("qux"));
+    }
+
+    #[test]
+    fn test_git_dependency() {
+        let contents = r#"
+        [dependencies]
+        foo = { git = "https://github.com/foo/foo" }
+        bar = { git = "https://github.com/foo/bar", rev = "abc123" }
+
+        [dependencies.baz]
+        git = "https://github.com/foo/baz"
+        branch = "main"
+        "#;
+
+        let path = Path::new("Cargo.toml");
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        let deps = query_cargo_toml_dependencies(&file);
+
+        assert_eq!(deps.len(), 3, "mismatched number of dependencies");
+        let git = |dep: &Dependency| match dep.spec().map(|s| &s.contents.source) {
+            Some(DependencySource::Git { url, reference }) => Some((
+                url.unquoted().to_string(),
+                reference
+                    .as_ref()
+                    .map(|r| format!("{}={}", r.name.unquoted(), r.value.unquoted())),
+            )),
+            _ => None,
+        };
+        assert_eq!(
+            git(&deps[0]),
+            Some((String::from("https://github.com/foo/foo"), None))
+        );
+        assert_eq!(
+            git(&deps[1]),
+            Some((
+                String::from("https://github.com/foo/bar"),
+                Some(String::from("rev=abc123"))
+            ))
+        );
+        assert_eq!(
+            git(&deps[2]),
+            Some((
+                String::from("https://github.com/foo/baz"),
+                Some(String::from("branch=main"))
+            ))
+        );
+    }
+
+    #[test]
+    fn test_dependency_keys() {
+        let contents = r#"
+        [dependencies]
+        foo = { version = "1.0", "default-features" = false, features = [] }
+        bar = { }
+        baz = "1.0"
+
+        [dependencies.qux]
+        git = "https://github.com/owner/qux"
+        branch = "main"
+        "#;
+
+        let path = Path::new("Cargo.toml");
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        let deps = query_cargo_toml_dependencies(&file);
+
+        assert_eq!(deps.len(), 4, "mismatched number of dependencies");
+        let keys = |dep: &Dependency| {
+            dep.spec().and_then(|s| s.contents.keys.as_ref()).map(|k| {
+                k.contents
+                    .iter()
+                    .map(|k| k.unquoted().to_string())
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(
+            keys(&deps[0]),
+            Some(vec![
+                String::from("version"),
+                String::from("default-features"),
+                String::from("features")
+            ])
+        );
+        assert_eq!(keys(&deps[1]), Some(vec![]));
+        assert_eq!(keys(&deps[2]), None);
+        assert_eq!(
+            keys(&deps[3]),
+            Some(vec![String::from("git"), String::from("branch")])
+        );
+    }
+
+    #[test]
+    fn test_features_table() {
+        let contents = r#"
+        [features]
+        default = ["std"]
+        std = ["dep:serde", "tokio/full"]
+        "#;
+
+        let path = Path::new("Cargo.toml");
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        let features = query_cargo_toml_features(&file);
+
+        assert_eq!(features.len(), 2, "mismatched number of features");
+        assert_eq!(features[0].name.unquoted(), "default");
+        assert_eq!(features[1].name.unquoted(), "std");
+        assert_eq!(
+            features[1]
+                .values
+                .contents
+                .iter()
+                .map(|v| v.unquoted())
+                .collect::<Vec<_>>(),
+            vec!["dep:serde", "tokio/full"]
+        );
+    }
+
+    #[test]
+    fn test_package_fields() {
+        let contents = r#"
+        [package]
+        name = "my-crate"
+        edition = "2024"
+        "#;
+
+        let path = Path::new("Cargo.toml");
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        let fields = query_cargo_toml_package_fields(&file);
+
+        let edition = TableField::find_by_name(&fields, "edition").unwrap();
+        assert_eq!(edition.value.unquoted(), "2024");
+    }
+
+    #[test]
+    fn test_workspace_fields() {
+        let contents = r#"
+        [workspace]
+        members = ["crates/*", "tools/cli"]
+        exclude = ["crates/old"]
+        resolver = "2"
+        "#;
+
+        let path = Path::new("Cargo.toml");
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        let fields = query_cargo_toml_workspace_fields(&file);
+
+        let entries = fields
+            .iter()
+            .map(|f| (f.name.unquoted(), f.value.unquoted()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![
+                ("members", "crates/*"),
+                ("members", "tools/cli"),
+                ("exclude", "crates/old"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_table_headers() {
+        let contents = r#"
+        [workspace]
+
+        [ workspace.dependencies ]
+        serde = "1.0"
+        "#;
+
+        let path = Path::new("Cargo.toml");
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        assert_eq!(
+            query_cargo_toml_table_headers(&file),
+            vec!["workspace", "workspace.dependencies"]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_dependencies_tables() {
+        let contents = r#"
+        [dependencies]
+        tokio = "1.25.0"
+
+        [package]
+        name = "foo"
+
+        [dependencies]
+        serde = "1.0"
+        "#;
+
+        test_dependencies(
+            contents,
+            vec![
+                (DependencyKind::Default, "tokio", "1.25.0", vec![]),
+                (DependencyKind::Default, "serde", "1.0", vec![]),
+            ],
+        );
+
+        let path = Path::new("Cargo.toml");
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        let duplicates = query_cargo_toml_duplicate_tables(&file);
+
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].first.unquoted(), "dependencies");
+        assert_eq!(duplicates[0].first.range.start.line, 1);
+        assert_eq!(duplicates[0].duplicate.range.start.line, 7);
+    }
+
+    #[test]
+    fn test_no_duplicate_tables() {
+        let contents = r#"
+        [dependencies]
+        tokio = "1.25.0"
+
+        [dependencies.serde]
+        version = "1.0"
+
+        [dev-dependencies]
+        tokio = "1.25.0"
+        "#;
+
+        let path = Path::new("Cargo.toml");
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        assert!(query_cargo_toml_duplicate_tables(&file).is_empty());
+    }
+
     #[test]
     fn test_incomplete_dependency_first() {
         let contents = r#"
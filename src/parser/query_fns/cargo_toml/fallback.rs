@@ -0,0 +1,382 @@
+use tower_lsp::lsp_types::{Position, Range};
+
+use super::super::super::query_structs::{
+    Dependency, DependencyKind, DependencySource, DependencySpec, Node,
+};
+
+/**
+    Recovers dependencies from a manifest that tree-sitter failed to make
+    sense of, by parsing every line using the `toml` crate on its own.
+
+    Each line is parsed separately, so that a single broken line can't
+    hide any other dependencies, and ranges are found by searching for
+    values in the text of their line, meaning they are only approximate.
+*/
+pub(super) fn fallback_cargo_toml_dependencies(contents: &str) -> Vec<Dependency> {
+    let mut dependencies = Vec::new();
+    let mut section = None::<Section>;
+
+    for (row, line) in contents.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            if let Some(section) = section.take() {
+                dependencies.extend(section.into_dependency());
+            }
+            section = Section::from_header(row, line);
+            continue;
+        }
+
+        let Some(section) = section.as_mut() else {
+            continue;
+        };
+        let Some((key, value)) = parse_line(line) else {
+            continue;
+        };
+        match &mut section.table {
+            // A table of dependencies, such as [dependencies], with one per line
+            None => {
+                let name = located(row, line, key.start, &key.raw);
+                let spec = spec_from_value(row, line, key.end, &value);
+                let dependency = Dependency::new_full(
+                    section.kind,
+                    Range::new(name.range.start, line_end(row, line)),
+                    name,
+                    spec,
+                );
+                dependencies.push(dependency.with_target(section.target.clone()));
+            }
+            // A table for a single dependency, such as [dependencies.serde]
+            Some(table) => {
+                table.end = line_end(row, line);
+                add_to_spec(&mut table.spec, row, line, key.start, &key.name, &value);
+                table.keys.push(located(row, line, key.start, &key.raw));
+            }
+        }
+    }
+
+    if let Some(section) = section {
+        dependencies.extend(section.into_dependency());
+    }
+    dependencies
+}
+
+struct Section {
+    kind: DependencyKind,
+    target: Option<String>,
+    table: Option<SectionTable>,
+}
+
+struct SectionTable {
+    name: Node<String>,
+    end: Position,
+    spec: DependencySpec,
+    keys: Vec<Node<String>>,
+}
+
+impl Section {
+    fn from_header(row: usize, line: &str) -> Option<Self> {
+        let header = line.trim().strip_prefix('[')?.split(']').next()?;
+        let parts = header.split('.').map(str::trim).collect::<Vec<_>>();
+
+        // Target tables such as [target.'cfg(unix)'.dependencies] are
+        // approximated by their last parts, since the spec may contain dots
+        let position = parts
+            .iter()
+            .rposition(|part| dependency_kind(part).is_some())?;
+        let kind = dependency_kind(parts[position])?;
+        let target = match &parts[..position] {
+            ["target", spec @ ..] if !spec.is_empty() => {
+                Some(spec.join(".").trim_matches(['"', '\'']).to_string())
+            }
+            _ => None,
+        };
+        let table = match &parts[position + 1..] {
+            [] => None,
+            [name] => {
+                let name = located(row, line, line.rfind(*name)?, name);
+                Some(SectionTable {
+                    end: name.range.end,
+                    name,
+                    spec: DependencySpec::default(),
+                    keys: Vec::new(),
+                })
+            }
+            _ => return None,
+        };
+        Some(Self {
+            kind,
+            target,
+            table,
+        })
+    }
+
+    fn into_dependency(self) -> Option<Dependency> {
+        let table = self.table?;
+        let start = table.name.range.start;
+        let spec = DependencySpec {
+            keys: table.keys.first().map(|first| {
+                Node::new_raw(Range::new(first.range.start, table.end), table.keys.clone())
+            }),
+            ..table.spec
+        };
+        let spec_start = table
+            .keys
+            .first()
+            .map_or(table.name.range.end, |k| k.range.start);
+        let dependency = Dependency::new_full(
+            self.kind,
+            Range::new(start, table.end),
+            table.name,
+            Node::new_raw(Range::new(spec_start, table.end), spec),
+        );
+        Some(dependency.with_target(self.target))
+    }
+}
+
+fn dependency_kind(name: &str) -> Option<DependencyKind> {
+    match name {
+        "dependencies" => Some(DependencyKind::Default),
+        "dev-dependencies" | "dev_dependencies" => Some(DependencyKind::Dev),
+        "build-dependencies" | "build_dependencies" => Some(DependencyKind::Build),
+        _ => None,
+    }
+}
+
+struct LineKey {
+    name: String,
+    raw: String,
+    start: usize,
+    end: usize,
+}
+
+/**
+    Parses a single `key = value` line, returning the first key, exactly
+    as it was written, and its value, which is a table for dotted keys.
+*/
+fn parse_line(line: &str) -> Option<(LineKey, toml::Value)> {
+    let table = line.parse::<toml::Table>().ok()?;
+    let (name, value) = table.into_iter().next()?;
+
+    let leading = line.len() - line.trim_start().len();
+    let raw_len = if line[leading..].starts_with(['"', '\'']) {
+        line[leading + 1..].find(['"', '\''])? + 2
+    } else {
+        line[leading..].find(|c: char| c == '=' || c == '.' || c.is_whitespace())?
+    };
+    let raw = line[leading..leading + raw_len].to_string();
+    Some((
+        LineKey {
+            name,
+            raw,
+            start: leading,
+            end: leading + raw_len,
+        },
+        value,
+    ))
+}
+
+fn spec_from_value(
+    row: usize,
+    line: &str,
+    after: usize,
+    value: &toml::Value,
+) -> Node<DependencySpec> {
+    let mut spec = DependencySpec::default();
+    let start = position(
+        row,
+        line.len() - line[after..].trim_start_matches([' ', '.', '=']).len(),
+    );
+    match value {
+        toml::Value::String(version) => {
+            spec.version = Some(located_string(row, line, after, version));
+        }
+        toml::Value::Table(table) => {
+            let mut keys = Vec::new();
+            for (key, value) in table {
+                add_to_spec(&mut spec, row, line, after, key, value);
+                let offset = line[after..]
+                    .find(key.as_str())
+                    .map_or(after, |o| after + o);
+                keys.push(located(row, line, offset, key));
+            }
+            // Tables are not parsed in document order, but keys should be
+            keys.sort_by_key(|k| k.range.start);
+            spec.keys = Some(Node::new_raw(Range::new(start, line_end(row, line)), keys));
+        }
+        _ => {}
+    }
+    Node::new_raw(Range::new(start, line_end(row, line)), spec)
+}
+
+fn add_to_spec(
+    spec: &mut DependencySpec,
+    row: usize,
+    line: &str,
+    after: usize,
+    key: &str,
+    value: &toml::Value,
+) {
+    // Values are searched for after their key, so that they
+    // are not confused with other values that look the same
+    let after = line[after..]
+        .find(key)
+        .map_or(after, |o| after + o + key.len());
+    match (key, value) {
+        ("version", toml::Value::String(s)) => {
+            spec.version = Some(located_string(row, line, after, s));
+        }
+        ("package", toml::Value::String(s)) => {
+            spec.package = Some(located_string(row, line, after, s));
+        }
+        ("registry", toml::Value::String(s)) => {
+            spec.registry = Some(located_string(row, line, after, s));
+        }
+        ("path", toml::Value::String(s)) => {
+            spec.source = DependencySource::Path {
+                path: located_string(row, line, after, s),
+            };
+        }
+        ("git", toml::Value::String(s))
+            if !matches!(spec.source, DependencySource::Path { .. }) =>
+        {
+            spec.source = DependencySource::Git {
+                url: located_string(row, line, after, s),
+                reference: None,
+            };
+        }
+        ("optional", toml::Value::Boolean(b)) => {
+            let text = b.to_string();
+            let offset = line[after..].find(&text).map_or(after, |o| after + o);
+            spec.optional = Some(Node::new_raw(located(row, line, offset, &text).range, *b));
+        }
+        ("features", toml::Value::Array(features)) => {
+            let mut after = after;
+            let nodes = features
+                .iter()
+                .filter_map(|f| f.as_str())
+                .map(|f| {
+                    let node = located_string(row, line, after, f);
+                    after = line.len().min(offset_of(line, node.range.end));
+                    node
+                })
+                .collect::<Vec<_>>();
+            let range = match (nodes.first(), nodes.last()) {
+                (Some(first), Some(last)) => Range::new(first.range.start, last.range.end),
+                _ => located(row, line, after, "").range,
+            };
+            spec.features = Some(Node::new_raw(range, nodes));
+        }
+        _ => {}
+    }
+}
+
+/**
+    Finds a string value in a line, after the given offset, keeping its quotes
+    if they can be found, and otherwise adding them, since ranges are approximate.
+*/
+fn located_string(row: usize, line: &str, after: usize, value: &str) -> Node<String> {
+    for quote in ['"', '\''] {
+        let quoted = format!("{quote}{value}{quote}");
+        if let Some(offset) = line[after..].find(&quoted) {
+            return located(row, line, after + offset, &quoted);
+        }
+    }
+    let node = located(row, line, after, "");
+    Node::new_raw(node.range, format!("\"{value}\""))
+}
+
+fn located(row: usize, line: &str, offset: usize, text: &str) -> Node<String> {
+    let offset = offset.min(line.len());
+    let end = (offset + text.len()).min(line.len());
+    Node::new_raw(
+        Range::new(position(row, offset), position(row, end)),
+        text.to_string(),
+    )
+}
+
+fn offset_of(line: &str, position: Position) -> usize {
+    (position.character as usize).min(line.len())
+}
+
+fn line_end(row: usize, line: &str) -> Position {
+    position(row, line.trim_end().len())
+}
+
+fn position(row: usize, column: usize) -> Position {
+    Position::new(row as u32, column as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fallback_dependencies() {
+        let contents = r#"
+[package]
+name = "broken"
+version = =
+
+[dependencies]
+serde = { version = "1.0", features = ["derive"], optional = true }
+this line is broken
+tokio = "1"
+local = { path = "../local" }
+
+[dev-dependencies.insta]
+version = '1.40'
+"#;
+        let deps = fallback_cargo_toml_dependencies(contents);
+        let names = deps
+            .iter()
+            .map(|d| d.name().unquoted().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["serde", "tokio", "local", "insta"]);
+
+        let serde = deps[0].spec().unwrap();
+        let version = serde.contents.version.as_ref().unwrap();
+        assert_eq!(version.unquoted(), "1.0");
+        assert_eq!(version.range.start, Position::new(6, 20));
+        assert_eq!(version.range.end, Position::new(6, 25));
+        assert!(serde.contents.is_optional());
+        assert_eq!(serde.contents.features.as_ref().unwrap().contents.len(), 1);
+
+        let tokio = deps[1].spec().unwrap();
+        assert_eq!(tokio.contents.version.as_ref().unwrap().quoted(), "\"1\"");
+
+        let local = deps[2].spec().unwrap();
+        assert!(matches!(
+            local.contents.source,
+            DependencySource::Path { .. }
+        ));
+
+        assert_eq!(deps[3].kind(), DependencyKind::Dev);
+        let insta = deps[3].spec().unwrap();
+        assert_eq!(insta.contents.version.as_ref().unwrap().quoted(), "'1.40'");
+    }
+
+    #[test]
+    fn test_fallback_lints_and_profiles() {
+        let contents = "[lints.rust]\nunsafe_code = \"forbid\"\n\
+            [dependencies]\ntokio = \"1\"\nbroken = =\n\
+            [profile.release]\nlto = \"thin\"\n\
+            [workspace.lints.clippy]\nversion = \"1.0\"\n";
+        let deps = fallback_cargo_toml_dependencies(contents);
+        assert_eq!(deps.len(), 1);
+        assert_eq!(deps[0].name().unquoted(), "tokio");
+    }
+
+    #[test]
+    fn test_fallback_target_dependencies() {
+        let contents = "[target.'cfg(unix)'.build-dependencies]\ncc = \"1\"\n";
+        let deps = fallback_cargo_toml_dependencies(contents);
+        assert_eq!(deps.len(), 1);
+        assert_eq!(deps[0].kind(), DependencyKind::Build);
+        assert_eq!(deps[0].target(), Some("cfg(unix)"));
+
+        let contents = "[target.x86_64-pc-windows-msvc.dependencies.winapi]\nversion = \"0.3\"\n";
+        let deps = fallback_cargo_toml_dependencies(contents);
+        assert_eq!(deps.len(), 1);
+        assert_eq!(deps[0].target(), Some("x86_64-pc-windows-msvc"));
+    }
+}
@@ -0,0 +1,233 @@
+use crate::parser::query_utils::{range_contains, range_from_node};
+
+use super::super::super::document::TreeSitterDocument;
+use super::super::super::query_structs::{Dependency, Node, TableField};
+use super::{query_cargo_toml_dependencies, table_pairs};
+
+/**
+    Finds all fields in the `[package]` table that are inherited
+    from the workspace, using either of these equivalent forms:
+
+    ```toml
+    [package]
+    edition.workspace = true
+    license = { workspace = true }
+    ```
+
+    The name of each field is the name of the inherited field,
+    and the value is the `true` that makes it inherited.
+*/
+pub fn query_cargo_toml_inherited_package_fields(doc: &TreeSitterDocument) -> Vec<TableField> {
+    let Some(table) = find_table(doc, &["package"]) else {
+        return Vec::new();
+    };
+
+    let mut fields = Vec::new();
+    for pair in table.named_children(&mut table.walk()) {
+        if pair.kind() != "pair" {
+            continue;
+        }
+        let (Some(key), Some(value)) = (pair.named_child(0), pair.named_child(1)) else {
+            continue;
+        };
+
+        // Either a dotted key ending in `workspace` with a `true` value,
+        // or a plain key with an inline table containing `workspace = true`
+        let (name_node, name, inherits) = if key.kind() == "dotted_key" {
+            match key_path(&key, &doc.contents).as_slice() {
+                [(node, name), (_, "workspace")] => (*node, *name, Some(value)),
+                _ => continue,
+            }
+        } else if value.kind() == "inline_table" {
+            let inherits = table_pairs(&value, &doc.contents)
+                .into_iter()
+                .find(|(key, _)| *key == "workspace")
+                .map(|(_, value)| value);
+            (key, key_text(&key, &doc.contents), inherits)
+        } else {
+            continue;
+        };
+
+        let Some(inherits) = inherits.filter(|v| v.kind() == "boolean") else {
+            continue;
+        };
+        let Ok(inherits_text) = inherits.utf8_text(doc.contents.as_bytes()) else {
+            continue;
+        };
+        if inherits_text != "true" {
+            continue;
+        }
+
+        fields.push(TableField {
+            name: Node::string(&name_node, name),
+            value: Node::string(&inherits, inherits_text),
+        });
+    }
+
+    fields
+}
+
+/**
+    Finds all fields in the `[workspace.package]` table, which members of the
+    workspace may inherit - unlike the fields in `[package]`, values may be of
+    any kind, such as arrays for `authors`, and are given as their source text.
+*/
+pub fn query_cargo_toml_workspace_package_fields(doc: &TreeSitterDocument) -> Vec<TableField> {
+    let Some(table) = find_table(doc, &["workspace", "package"]) else {
+        return Vec::new();
+    };
+
+    let mut fields = Vec::new();
+    for pair in table.named_children(&mut table.walk()) {
+        if pair.kind() != "pair" {
+            continue;
+        }
+        let (Some(key), Some(value)) = (pair.named_child(0), pair.named_child(1)) else {
+            continue;
+        };
+        if !matches!(key.kind(), "bare_key" | "quoted_key") {
+            continue;
+        }
+        let Ok(value_text) = value.utf8_text(doc.contents.as_bytes()) else {
+            continue;
+        };
+        fields.push(TableField {
+            name: Node::string(&key, key_text(&key, &doc.contents)),
+            value: Node::string(&value, value_text),
+        });
+    }
+
+    fields
+}
+
+/**
+    Finds all dependencies in the `[workspace.dependencies]` table, which members
+    of the workspace may inherit using `foo = { workspace = true }`, and not
+    the dependencies of the root package itself, if there is one.
+*/
+pub fn query_cargo_toml_workspace_dependencies(doc: &TreeSitterDocument) -> Vec<Dependency> {
+    let Some(table) = find_table(doc, &["workspace", "dependencies"]) else {
+        return Vec::new();
+    };
+
+    let range = range_from_node(&table);
+    query_cargo_toml_dependencies(doc)
+        .into_iter()
+        .filter(|dep| range_contains(range, dep.range().start))
+        .collect()
+}
+
+/**
+    Finds the top-level table with the given header, such as
+    `["workspace", "package"]` for `[workspace.package]`.
+*/
+fn find_table<'a>(doc: &'a TreeSitterDocument, header: &[&str]) -> Option<tree_sitter::Node<'a>> {
+    let root = doc.tree.root_node();
+    let mut cursor = root.walk();
+    let found = root.named_children(&mut cursor).find(|child| {
+        child.kind() == "table"
+            && child.named_child(0).is_some_and(|key| {
+                let path = key_path(&key, &doc.contents);
+                path.len() == header.len() && path.iter().zip(header).all(|((_, a), b)| a == b)
+            })
+    });
+    found
+}
+
+/**
+    Flattens a key, which may be a nested dotted key, into
+    its parts, along with the node for each of the parts.
+*/
+pub(super) fn key_path<'a>(
+    key: &tree_sitter::Node<'a>,
+    contents: &'a str,
+) -> Vec<(tree_sitter::Node<'a>, &'a str)> {
+    match key.kind() {
+        "dotted_key" => key
+            .named_children(&mut key.walk())
+            .flat_map(|child| key_path(&child, contents))
+            .collect(),
+        "bare_key" | "quoted_key" => vec![(*key, key_text(key, contents))],
+        _ => Vec::new(),
+    }
+}
+
+pub(super) fn key_text<'a>(key: &tree_sitter::Node<'a>, contents: &'a str) -> &'a str {
+    key.utf8_text(contents.as_bytes())
+        .unwrap_or_default()
+        .trim_matches(['"', '\''])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_inherited_package_fields() {
+        let contents = r#"
+        [package]
+        name = "my-crate"
+        version.workspace = true
+        edition = { workspace = true }
+        license.workspace = false
+        authors = ["someone"]
+        "#;
+
+        let path = Path::new("Cargo.toml");
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        let fields = query_cargo_toml_inherited_package_fields(&file);
+
+        let names = fields.iter().map(|f| f.name.unquoted()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["version", "edition"]);
+    }
+
+    #[test]
+    fn test_workspace_dependencies() {
+        let contents = r#"
+        [workspace.dependencies]
+        serde = { version = "1.0", features = ["derive"] }
+        tokio = "1.25"
+
+        [dependencies]
+        serde.workspace = true
+        anyhow = "1.0"
+        "#;
+
+        let path = Path::new("Cargo.toml");
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        let deps = query_cargo_toml_workspace_dependencies(&file);
+
+        let names = deps.iter().map(|d| d.name().unquoted()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["serde", "tokio"]);
+    }
+
+    #[test]
+    fn test_workspace_package_fields() {
+        let contents = r#"
+        [workspace]
+        members = ["crates/*"]
+
+        [workspace.package]
+        edition = "2021"
+        authors = ["someone"]
+
+        [package]
+        edition = "2018"
+        "#;
+
+        let path = Path::new("Cargo.toml");
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        let fields = query_cargo_toml_workspace_package_fields(&file);
+
+        let entries = fields
+            .iter()
+            .map(|f| (f.name.unquoted(), f.value.unquoted()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![("edition", "2021"), ("authors", "[\"someone\"]")]
+        );
+    }
+}
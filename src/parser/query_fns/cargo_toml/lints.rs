@@ -0,0 +1,146 @@
+use crate::parser::query_utils::range_from_node;
+
+use super::super::super::document::TreeSitterDocument;
+use super::super::super::query_structs::{LintTable, Node, TableField};
+use super::inheritance::{key_path, key_text};
+
+/**
+    Finds all tables of lints for a tool, for the package or the workspace:
+
+    ```toml
+    [lints.rust]
+    unsafe_code = "forbid"
+
+    [workspace.lints.clippy]
+    pedantic = { level = "warn", priority = -1 }
+    ```
+
+    The value of each lint is its level, which is either the lint value
+    itself, or the `level` in an inline table. Lints without any level,
+    and the `workspace = true` key in a plain `[lints]` table, are skipped.
+
+    Note that this does not use a query, since lint tables are always
+    top-level tables and can be found by simply walking the root node.
+*/
+pub fn query_cargo_toml_lints(doc: &TreeSitterDocument) -> Vec<LintTable> {
+    let mut tables = Vec::new();
+
+    let root = doc.tree.root_node();
+    for child in root.named_children(&mut root.walk()) {
+        if child.kind() != "table" {
+            continue;
+        }
+        let Some(key) = child.named_child(0) else {
+            continue;
+        };
+        let path = key_path(&key, &doc.contents);
+        let (workspace, tool) = match path.as_slice() {
+            [(_, "lints"), (node, name)] => (false, Node::string(node, *name)),
+            [(_, "workspace"), (_, "lints"), (node, name)] => (true, Node::string(node, *name)),
+            _ => continue,
+        };
+
+        let mut lints = Vec::new();
+        for pair in child.named_children(&mut child.walk()) {
+            if pair.kind() != "pair" {
+                continue;
+            }
+            let (Some(name), Some(value)) = (pair.named_child(0), pair.named_child(1)) else {
+                continue;
+            };
+            if !matches!(name.kind(), "bare_key" | "quoted_key") {
+                continue;
+            }
+
+            // Lints are either `name = "level"` or `name = { level = "level" }`
+            let level = match value.kind() {
+                "string" => Some(value),
+                "inline_table" => value
+                    .named_children(&mut value.walk())
+                    .filter(|p| p.kind() == "pair")
+                    .find(|p| {
+                        p.named_child(0)
+                            .is_some_and(|k| key_text(&k, &doc.contents) == "level")
+                    })
+                    .and_then(|p| p.named_child(1))
+                    .filter(|v| v.kind() == "string"),
+                _ => None,
+            };
+            let Some(level) = level else {
+                continue;
+            };
+            let Ok(level_text) = level.utf8_text(doc.contents.as_bytes()) else {
+                continue;
+            };
+            lints.push(TableField {
+                name: Node::string(&name, key_text(&name, &doc.contents)),
+                value: Node::string(&level, level_text),
+            });
+        }
+
+        tables.push(LintTable {
+            tool,
+            workspace,
+            lints,
+            range: range_from_node(&child),
+        });
+    }
+
+    tables
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_lints() {
+        let contents = r#"
+        [lints]
+        workspace = true
+
+        [lints.rust]
+        unsafe_code = "forbid"
+        unexpected_cfgs = { level = "warn", check-cfg = ["cfg(foo)"] }
+
+        [workspace.lints.clippy]
+        pedantic = { priority = -1, level = "warn" }
+        no_level = { priority = 1 }
+
+        [profile.release]
+        lto = "thin"
+        "#;
+
+        let path = Path::new("Cargo.toml");
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        let tables = query_cargo_toml_lints(&file);
+
+        let entries = tables
+            .iter()
+            .map(|table| {
+                let lints = table
+                    .lints
+                    .iter()
+                    .map(|l| format!("{}={}", l.name.unquoted(), l.value.unquoted()))
+                    .collect::<Vec<_>>();
+                (table.tool.unquoted(), table.workspace, lints)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![
+                (
+                    "rust",
+                    false,
+                    vec![
+                        String::from("unsafe_code=forbid"),
+                        String::from("unexpected_cfgs=warn")
+                    ]
+                ),
+                ("clippy", true, vec![String::from("pedantic=warn")]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use crate::parser::query_utils::range_from_node;
+
+use super::super::super::document::TreeSitterDocument;
+use super::super::super::query_structs::{Node, Target, TargetKind};
+use super::table_pairs;
+
+/**
+    Finds all build targets declared using arrays of tables, such as:
+
+    ```toml
+    [[bin]]
+    name = "my-binary"
+    path = "src/bin/main.rs"
+    ```
+
+    Note that this does not use a query, since targets are always
+    top-level tables and can be found by simply walking the root node.
+*/
+pub fn query_cargo_toml_targets(doc: &TreeSitterDocument) -> Vec<Target> {
+    let mut targets = Vec::new();
+
+    let root = doc.tree.root_node();
+    for child in root.named_children(&mut root.walk()) {
+        if child.kind() != "table_array_element" {
+            continue;
+        }
+
+        let Some(key) = child.named_child(0).filter(|k| k.kind() == "bare_key") else {
+            continue;
+        };
+        let Ok(Ok(kind)) = key
+            .utf8_text(doc.contents.as_bytes())
+            .map(str::parse::<TargetKind>)
+        else {
+            continue;
+        };
+
+        let mut name_node = None;
+        let mut path_node = None;
+        for (field, value) in table_pairs(&child, &doc.contents) {
+            if value.kind() != "string" {
+                continue;
+            }
+            let Ok(value_text) = value.utf8_text(doc.contents.as_bytes()) else {
+                continue;
+            };
+            match field {
+                "name" => name_node = Some(Node::string(&value, value_text)),
+                "path" => path_node = Some(Node::string(&value, value_text)),
+                _ => {}
+            }
+        }
+
+        targets.push(Target {
+            kind: Node::new(&key, kind),
+            name: name_node,
+            path: path_node,
+            range: range_from_node(&child),
+        });
+    }
+
+    targets
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn test_targets(contents: &str, expected: Vec<(TargetKind, Option<&str>, Option<&str>)>) {
+        let path = Path::new("Cargo.toml");
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        let targets = query_cargo_toml_targets(&file);
+
+        assert_eq!(
+            targets.len(),
+            expected.len(),
+            "mismatched number of targets!\ntargets: {targets:#?}\nexpected: {expected:?}"
+        );
+
+        for (target, (kind, name, path)) in targets.into_iter().zip(expected.into_iter()) {
+            assert_eq!(target.kind.contents, kind);
+            assert_eq!(target.name.as_ref().map(|n| n.unquoted()), name);
+            assert_eq!(target.path.as_ref().map(|p| p.unquoted()), path);
+        }
+    }
+
+    #[test]
+    fn test_no_targets() {
+        test_targets(
+            r#"
+            [package]
+            name = "foo"
+
+            [dependencies]
+            tokio = "1.25.0"
+            "#,
+            vec![],
+        );
+    }
+
+    #[test]
+    fn test_mixed_targets() {
+        test_targets(
+            r#"
+            [[bin]]
+            name = "foo-cli"
+            path = "src/bin/cli.rs"
+
+            [[example]]
+            name = "basic"
+
+            [[bench]]
+            name = "parsing"
+            harness = false
+
+            [[other]]
+            name = "ignored"
+            "#,
+            vec![
+                (TargetKind::Bin, Some("foo-cli"), Some("src/bin/cli.rs")),
+                (TargetKind::Example, Some("basic"), None),
+                (TargetKind::Bench, Some("parsing"), None),
+            ],
+        );
+    }
+}
@@ -3,7 +3,67 @@ mod package_json;
 mod rokit_toml;
 mod wally_toml;
 
-pub use cargo_toml::query_cargo_toml_dependencies;
+pub use cargo_toml::{
+    query_cargo_toml_dependencies, query_cargo_toml_duplicate_tables, query_cargo_toml_features,
+    query_cargo_toml_inherited_package_fields, query_cargo_toml_lints,
+    query_cargo_toml_package_fields, query_cargo_toml_table_headers, query_cargo_toml_targets,
+    query_cargo_toml_workspace_dependencies, query_cargo_toml_workspace_fields,
+    query_cargo_toml_workspace_package_fields,
+};
 pub use package_json::query_package_json_dependencies;
-pub use rokit_toml::query_rokit_toml_dependencies;
-pub use wally_toml::query_wally_toml_dependencies;
+pub use rokit_toml::{
+    query_rokit_toml_dependencies, query_rokit_toml_has_tools_table,
+    query_rokit_toml_misplaced_tools,
+};
+pub use wally_toml::{query_wally_toml_dependencies, query_wally_toml_sections};
+
+use super::query_structs::Node;
+
+/**
+    Collects all key-value pairs directly inside of a table or
+    inline table node, as pairs of unquoted key and value node.
+*/
+pub(super) fn table_pairs<'a>(
+    node: &tree_sitter::Node<'a>,
+    contents: &'a str,
+) -> Vec<(&'a str, tree_sitter::Node<'a>)> {
+    let mut pairs = Vec::new();
+    for child in node.named_children(&mut node.walk()) {
+        if child.kind() != "pair" {
+            continue;
+        }
+        let (Some(key), Some(value)) = (child.named_child(0), child.named_child(1)) else {
+            continue;
+        };
+        if !matches!(key.kind(), "bare_key" | "quoted_key") {
+            continue;
+        }
+        if let Ok(key_text) = key.utf8_text(contents.as_bytes()) {
+            pairs.push((key_text.trim_matches(['"', '\'']), value));
+        }
+    }
+    pairs
+}
+
+/**
+    Finds the keys of all pairs in a table, same as `table_pairs`,
+    but as nodes, so that the range of each key is also known.
+*/
+pub(super) fn table_keys(node: &tree_sitter::Node, contents: &str) -> Vec<Node<String>> {
+    let mut keys = Vec::new();
+    for child in node.named_children(&mut node.walk()) {
+        if child.kind() != "pair" {
+            continue;
+        }
+        let Some(key) = child.named_child(0) else {
+            continue;
+        };
+        if !matches!(key.kind(), "bare_key" | "quoted_key") {
+            continue;
+        }
+        if let Ok(key_text) = key.utf8_text(contents.as_bytes()) {
+            keys.push(Node::string(&key, key_text));
+        }
+    }
+    keys
+}
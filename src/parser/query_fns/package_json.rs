@@ -84,7 +84,10 @@ pub fn query_package_json_dependencies(doc: &TreeSitterDocument) -> Vec<Dependen
             (dep_kind, dep_range, dep_name_node, spec_range)
         {
             let source = if let Some(url) = git_url {
-                DependencySource::Git { url }
+                DependencySource::Git {
+                    url,
+                    reference: None,
+                }
             } else if let Some(path) = path {
                 DependencySource::Path { path }
             } else {
@@ -101,6 +104,10 @@ pub fn query_package_json_dependencies(doc: &TreeSitterDocument) -> Vec<Dependen
                         source,
                         version: version_node,
                         features: None, // NPM doesn't have features
+                        package: None,  // NPM doesn't have renames
+                        optional: None, // NPM has a separate optional dependency kind
+                        registry: None,
+                        keys: None,
                     },
                 ),
             ));
@@ -170,6 +177,7 @@ mod tests {
                     end: Position::new(0, 0),
                 },
             },
+            reference: None,
         }
     }
 
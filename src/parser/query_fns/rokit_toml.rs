@@ -5,7 +5,10 @@ use crate::parser::DependencyKind;
 
 use super::super::document::TreeSitterDocument;
 use super::super::query_strings::ROKIT_TOML_DEPENDENCIES_QUERY;
-use super::super::query_structs::{Node, SimpleDependency};
+use super::super::query_structs::{MisplacedTool, Node, SimpleDependency};
+use super::table_pairs;
+
+const TOOLS_TABLE_NAME: &str = "tools";
 
 pub fn query_rokit_toml_dependencies(doc: &TreeSitterDocument) -> Vec<SimpleDependency> {
     let Some(query) = doc.query(ROKIT_TOML_DEPENDENCIES_QUERY) else {
@@ -51,6 +54,87 @@ pub fn query_rokit_toml_dependencies(doc: &TreeSitterDocument) -> Vec<SimpleDepe
     tools
 }
 
+/**
+    Finds all tool-looking entries that are not inside of the `[tools]` table,
+    such as tools at the top level, or under a misspelled table like `[tool]`.
+
+    An entry looks like a tool if its value is a string in the `author/name@version`
+    format. Note that this does not use a query, since we need to look at every
+    top-level table, and those can be found by simply walking the root node.
+*/
+pub fn query_rokit_toml_misplaced_tools(doc: &TreeSitterDocument) -> Vec<MisplacedTool> {
+    let mut tools = Vec::new();
+
+    let root = doc.tree.root_node();
+    let mut push_pairs = |node: &tree_sitter::Node, table: Option<Node<String>>| {
+        for (_, value) in table_pairs(node, &doc.contents) {
+            if value.kind() != "string" {
+                continue;
+            }
+            let (Some(key), Ok(value_text)) = (
+                value.prev_named_sibling(),
+                value.utf8_text(doc.contents.as_bytes()),
+            ) else {
+                continue;
+            };
+            let Ok(key_text) = key.utf8_text(doc.contents.as_bytes()) else {
+                continue;
+            };
+            let spec = Node::string(&value, value_text);
+            if looks_like_tool_spec(spec.unquoted()) {
+                tools.push(MisplacedTool {
+                    table: table.clone(),
+                    name: Node::string(&key, key_text),
+                    spec,
+                });
+            }
+        }
+    };
+
+    // Top-level pairs are direct children of the root node
+    push_pairs(&root, None);
+
+    for child in root.named_children(&mut root.walk()) {
+        if child.kind() != "table" {
+            continue;
+        }
+        let Some(key) = child.named_child(0) else {
+            continue;
+        };
+        let Ok(key_text) = key.utf8_text(doc.contents.as_bytes()) else {
+            continue;
+        };
+        if key_text.trim_matches(['"', '\'']) == TOOLS_TABLE_NAME {
+            continue;
+        }
+        push_pairs(&child, Some(Node::string(&key, key_text)));
+    }
+
+    tools
+}
+
+/**
+    Checks if there is a `[tools]` table, even an empty one, using the parsed
+    header, so that any comments or whitespace around the header don't matter.
+*/
+pub fn query_rokit_toml_has_tools_table(doc: &TreeSitterDocument) -> bool {
+    let root = doc.tree.root_node();
+    root.named_children(&mut root.walk())
+        .filter(|child| child.kind() == "table")
+        .filter_map(|child| child.named_child(0))
+        .filter_map(|key| key.utf8_text(doc.contents.as_bytes()).ok())
+        .any(|key| key.trim().trim_matches(['"', '\'']) == TOOLS_TABLE_NAME)
+}
+
+fn looks_like_tool_spec(spec: &str) -> bool {
+    spec.split_once('/').is_some_and(|(author, rest)| {
+        !author.is_empty()
+            && rest
+                .split_once('@')
+                .is_some_and(|(name, version)| !name.is_empty() && !version.is_empty())
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::Path;
@@ -102,4 +186,114 @@ mod tests {
             ],
         );
     }
+
+    #[test]
+    fn test_aftman_formats() {
+        let contents = r#"
+        [tools]
+        rojo = "rojo-rbx/rojo@7.4.4"
+        selene = "Kampfkarren/selene"
+        stylua = 'JohnnyMorganz/StyLua@v0.20.0'
+        wally = "UpliftGames/wally@"
+        "#;
+
+        let path = Path::new("aftman.toml");
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        let tools = query_rokit_toml_dependencies(&file);
+
+        assert_eq!(tools.len(), 4, "mismatched number of tools");
+        let parsed = tools
+            .iter()
+            .map(|tool| {
+                let spec = tool.parsed_spec();
+                (
+                    spec.author.unquoted().to_string(),
+                    spec.name.map(|n| n.unquoted().to_string()),
+                    spec.version.map(|v| v.unquoted().to_string()),
+                )
+            })
+            .collect::<Vec<_>>();
+        let owned = |author: &str, name: &str, version: Option<&str>| {
+            (
+                author.to_string(),
+                Some(name.to_string()),
+                version.map(str::to_string),
+            )
+        };
+        assert_eq!(
+            parsed,
+            vec![
+                owned("rojo-rbx", "rojo", Some("7.4.4")),
+                owned("Kampfkarren", "selene", None),
+                owned("JohnnyMorganz", "StyLua", Some("v0.20.0")),
+                owned("UpliftGames", "wally", Some("")),
+            ]
+        );
+    }
+
+    fn test_misplaced(contents: &str, expected: Vec<(Option<&'static str>, &'static str)>) {
+        let path = Path::new("rokit.toml");
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        let tools = query_rokit_toml_misplaced_tools(&file);
+
+        assert_eq!(tools.len(), expected.len(), "mismatched number of tools");
+
+        for (tool, (table, name)) in tools.into_iter().zip(expected.into_iter()) {
+            assert_eq!(tool.table.as_ref().map(|t| t.contents.as_str()), table);
+            assert_eq!(tool.name.contents, name);
+        }
+    }
+
+    #[test]
+    fn test_misplaced_none() {
+        test_misplaced(
+            r#"
+            [tools]
+            stylua = "JohnnyMorganz/StyLua@2.0.2"
+
+            [other]
+            key = "value"
+            "#,
+            vec![],
+        );
+    }
+
+    #[test]
+    fn test_misplaced_table() {
+        test_misplaced(
+            r#"
+            [tool]
+            stylua = "JohnnyMorganz/StyLua@2.0.2"
+            "#,
+            vec![(Some("tool"), "stylua")],
+        );
+    }
+
+    #[test]
+    fn test_misplaced_top_level() {
+        test_misplaced(
+            r#"
+            wally = "UpliftGames/wally@0.3.2"
+
+            [tools]
+            stylua = "JohnnyMorganz/StyLua@2.0.2"
+            "#,
+            vec![(None, "wally")],
+        );
+    }
+
+    #[test]
+    fn test_has_tools_table() {
+        let has_table = |contents: &str| {
+            let file = TreeSitterDocument::new_file(Path::new("rokit.toml"), contents).unwrap();
+            query_rokit_toml_has_tools_table(&file)
+        };
+        assert!(has_table("[tools]"));
+        assert!(has_table(
+            "[tools] # comment\nrojo = \"rojo-rbx/rojo@7.4.1\""
+        ));
+        assert!(has_table("[ \"tools\" ]"));
+        assert!(!has_table("rojo = \"rojo-rbx/rojo@7.4.1\""));
+        assert!(!has_table("[tool]\n# [tools]"));
+    }
 }
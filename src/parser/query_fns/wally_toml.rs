@@ -1,6 +1,9 @@
 use streaming_iterator::StreamingIterator;
+use tower_lsp::lsp_types::Range;
 use tree_sitter::QueryCursor;
 
+use crate::parser::query_utils::range_from_node;
+
 use super::super::document::TreeSitterDocument;
 use super::super::query_strings::WALLY_TOML_DEPENDENCIES_QUERY;
 use super::super::query_structs::{DependencyKind, Node, SimpleDependency};
@@ -62,6 +65,46 @@ pub fn query_wally_toml_dependencies(doc: &TreeSitterDocument) -> Vec<SimpleDepe
     dependencies
 }
 
+/**
+    Finds the headers of all dependency sections, such as `[server-dependencies]`,
+    with the range of each node being the range of the whole header.
+
+    Note that this does not use a query, since sections are always
+    top-level tables, and can be found by simply walking the root node.
+*/
+pub fn query_wally_toml_sections(doc: &TreeSitterDocument) -> Vec<Node<DependencyKind>> {
+    let mut sections = Vec::new();
+
+    let root = doc.tree.root_node();
+    for child in root.named_children(&mut root.walk()) {
+        if child.kind() != "table" {
+            continue;
+        }
+        let Some(key) = child.named_child(0).filter(|k| k.kind() == "bare_key") else {
+            continue;
+        };
+        let Ok(key_text) = key.utf8_text(doc.contents.as_bytes()) else {
+            continue;
+        };
+        let kind = match key_text {
+            "dependencies" => DependencyKind::Default,
+            "dev-dependencies" => DependencyKind::Dev,
+            "server-dependencies" => DependencyKind::Server,
+            _ => continue,
+        };
+
+        // The header ends at the closing bracket, right after the key
+        let mut cursor = child.walk();
+        let Some(end) = child.children(&mut cursor).find(|c| c.kind() == "]") else {
+            continue;
+        };
+        let range = Range::new(range_from_node(&child).start, range_from_node(&end).end);
+        sections.push(Node::new_raw(range, kind));
+    }
+
+    sections
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::Path;
@@ -168,4 +211,35 @@ mod tests {
             ],
         );
     }
+
+    #[test]
+    fn test_sections() {
+        let contents = r#"
+        [package]
+        name = "user/repo"
+
+        [dependencies]
+        Fusion = "elttob/fusion@0.3.0"
+
+        [server-dependencies]
+
+        [dev-dependencies]
+        TestEZ = "roblox/testez@0.4.1"
+        "#;
+
+        let path = Path::new("wally.toml");
+        let file = TreeSitterDocument::new_file(path, contents).unwrap();
+        let sections = query_wally_toml_sections(&file);
+
+        let kinds = sections.iter().map(|s| s.contents).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                DependencyKind::Default,
+                DependencyKind::Server,
+                DependencyKind::Dev
+            ]
+        );
+        assert_eq!(sections[1].range.start.line, sections[1].range.end.line);
+    }
 }
@@ -0,0 +1,8 @@
+(table
+    (bare_key) @root_name
+    (pair
+        (bare_key) @feature_name
+        (array) @feature_values
+    ) @feature_pair
+    (#eq? @root_name "features")
+)
@@ -0,0 +1,8 @@
+(table
+    (bare_key) @root_name
+    (pair
+        (bare_key) @field_name
+        (string) @field_value
+    ) @field_pair
+    (#eq? @root_name "package")
+)
@@ -0,0 +1,10 @@
+(table
+    (bare_key) @root_name
+    (pair
+        (bare_key) @field_name
+        (array
+            (string) @field_value
+        )
+    )
+    (#eq? @root_name "workspace")
+)
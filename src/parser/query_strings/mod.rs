@@ -1,4 +1,7 @@
 pub const CARGO_TOML_DEPENDENCIES_QUERY: &str = include_str!("./cargo_toml.scm");
+pub const CARGO_TOML_FEATURES_QUERY: &str = include_str!("./cargo_toml_features.scm");
+pub const CARGO_TOML_PACKAGE_QUERY: &str = include_str!("./cargo_toml_package.scm");
+pub const CARGO_TOML_WORKSPACE_QUERY: &str = include_str!("./cargo_toml_workspace.scm");
 pub const PACKAGE_JSON_DEPENDENCIES_QUERY: &str = include_str!("./package_json.scm");
 pub const ROKIT_TOML_DEPENDENCIES_QUERY: &str = include_str!("./rokit_toml.scm");
 pub const WALLY_TOML_DEPENDENCIES_QUERY: &str = include_str!("./wally_toml.scm");
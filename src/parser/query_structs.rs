@@ -4,7 +4,9 @@ use tower_lsp::lsp_types::{Position, Range};
 
 use crate::util::Versioned;
 
-use super::query_utils::{range_contains, range_extend, range_for_substring, range_from_node};
+use super::query_utils::{
+    find_at_pos, range_contains, range_extend, range_for_offset, range_from_node,
+};
 
 /**
     A node in the tree-sitter parse tree.
@@ -45,10 +47,15 @@ where
         s
     }
 
+    /**
+        Returns the contents of this node without surrounding quotes,
+        which may be either double quotes, or the single quotes of
+        TOML literal strings, often used in older aftman manifests.
+    */
     pub fn unquoted(&self) -> &str {
         let s = self.quoted();
-        if let Some(s) = s.strip_prefix('"') {
-            if let Some(s) = s.strip_suffix('"') {
+        for quote in ['"', '\''] {
+            if let Some(s) = s.strip_prefix(quote).and_then(|s| s.strip_suffix(quote)) {
                 return s;
             }
         }
@@ -58,12 +65,47 @@ where
     pub fn parse<T: FromStr>(&self) -> Result<T, <T as FromStr>::Err> {
         self.unquoted().parse()
     }
+
+    /**
+        Returns the range of the unquoted contents of this node.
+
+        Assumes that the contents are on a single line, which
+        is always the case for strings in JSON and TOML files.
+    */
+    pub fn unquoted_range(&self) -> Range {
+        let quoted_len = self.quoted().len();
+        let unquoted_len = self.unquoted().len();
+        if unquoted_len < quoted_len && self.range.start.line == self.range.end.line {
+            Range {
+                start: Position {
+                    line: self.range.start.line,
+                    character: self.range.start.character + 1,
+                },
+                end: Position {
+                    line: self.range.end.line,
+                    character: self.range.end.character.saturating_sub(1),
+                },
+            }
+        } else {
+            self.range
+        }
+    }
+
+    /**
+        Returns the range of a substring of the unquoted contents of this
+        node, at the given byte offset and with the given byte length.
+
+        Assumes that the contents are on a single line, same as `unquoted_range`.
+    */
+    pub fn unquoted_subrange(&self, offset: usize, len: usize) -> Range {
+        range_for_offset(self.unquoted_range(), offset, len)
+    }
 }
 
 /**
     The kind of dependency.
 */
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum DependencyKind {
     #[default]
     Default,
@@ -87,6 +129,8 @@ pub enum DependencySource {
     },
     Git {
         url: Node<String>,
+        /** The `rev`, `tag`, or `branch` key of the dependency, if any */
+        reference: Option<TableField>,
     },
 }
 
@@ -95,7 +139,7 @@ impl DependencySource {
         match self {
             Self::Registry => None,
             Self::Path { path } => Some(path.contents.as_ref()),
-            Self::Git { url } => Some(url.contents.as_ref()),
+            Self::Git { url, .. } => Some(url.contents.as_ref()),
         }
     }
 }
@@ -106,12 +150,26 @@ impl DependencySource {
     - The source of the dependency
     - The version of the dependency (may be `None` if the dependency is not versioned)
     - The features of the dependency (may also be `None` if the dependency has no features specified)
+    - The real package name of the dependency (`None` unless the dependency has been renamed)
+    - The optional flag of the dependency (`None` if the dependency has no optional flag specified)
+    - The alternate registry of the dependency (`None` if the dependency uses the default registry)
+    - The keys of the dependency, with the range of its table (`None` unless the dependency is a table)
 */
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct DependencySpec {
     pub source: DependencySource,
     pub version: Option<Node<String>>,
     pub features: Option<Node<Vec<Node<String>>>>,
+    pub package: Option<Node<String>>,
+    pub optional: Option<Node<bool>>,
+    pub registry: Option<Node<String>>,
+    pub keys: Option<Node<Vec<Node<String>>>>,
+}
+
+impl DependencySpec {
+    pub fn is_optional(&self) -> bool {
+        self.optional.as_ref().is_some_and(|o| o.contents)
+    }
 }
 
 impl Versioned for DependencySpec {
@@ -128,7 +186,8 @@ impl Versioned for DependencySpec {
     A partial *or* fully parsed dependency.
 
     Contains the kind of dependency, the name of the dependency,
-    and the full version specification of the dependency.
+    and the full version specification of the dependency, as well as
+    the platform of the `[target.*]` table it is in, if any.
 */
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Dependency {
@@ -136,18 +195,25 @@ pub enum Dependency {
         kind: DependencyKind,
         range: Range,
         name: Node<String>,
+        target: Option<String>,
     },
     Full {
         kind: DependencyKind,
         range: Range,
         name: Node<String>,
         spec: Node<DependencySpec>,
+        target: Option<String>,
     },
 }
 
 impl Dependency {
     pub fn new_partial(kind: DependencyKind, range: Range, name: Node<String>) -> Self {
-        Self::Partial { kind, range, name }
+        Self::Partial {
+            kind,
+            range,
+            name,
+            target: None,
+        }
     }
 
     pub fn new_full(
@@ -161,7 +227,19 @@ impl Dependency {
             range,
             name,
             spec,
+            target: None,
+        }
+    }
+
+    /**
+        Sets the platform of the `[target.*]` table that the dependency is in,
+        such as `cfg(unix)`, without any quotes around it.
+    */
+    pub fn with_target(mut self, new_target: Option<String>) -> Self {
+        match &mut self {
+            Self::Partial { target, .. } | Self::Full { target, .. } => *target = new_target,
         }
+        self
     }
 
     pub fn new_opt(
@@ -190,6 +268,12 @@ impl Dependency {
         }
     }
 
+    pub fn target(&self) -> Option<&str> {
+        match self {
+            Self::Partial { target, .. } | Self::Full { target, .. } => target.as_deref(),
+        }
+    }
+
     pub fn name(&self) -> &Node<String> {
         match self {
             Self::Partial { name, .. } => name,
@@ -204,6 +288,25 @@ impl Dependency {
         }
     }
 
+    /**
+        Returns the node containing the name of the package that this dependency refers to.
+
+        This is the `package` field of the spec if the dependency has been renamed,
+        otherwise it is the same as the name of the dependency.
+    */
+    pub fn package_name(&self) -> &Node<String> {
+        self.spec()
+            .and_then(|s| s.contents.package.as_ref())
+            .unwrap_or_else(|| self.name())
+    }
+
+    /**
+        Returns `true` if this dependency has been renamed using the `package` field.
+    */
+    pub fn is_renamed(&self) -> bool {
+        self.spec().is_some_and(|s| s.contents.package.is_some())
+    }
+
     pub fn sort_vec(vec: &mut [Self]) {
         vec.sort_by(|a, b| match (a.spec(), b.spec()) {
             (Some(a), Some(b)) => {
@@ -221,7 +324,7 @@ impl Dependency {
     }
 
     pub fn find_at_pos(vec: &[Self], pos: Position) -> Option<&Self> {
-        vec.iter().find(|dep| range_contains(dep.range(), pos))
+        find_at_pos(vec, pos, Self::range)
     }
 }
 
@@ -261,7 +364,7 @@ impl SimpleDependency {
     }
 
     pub fn find_at_pos(vec: &[Self], pos: Position) -> Option<&Self> {
-        vec.iter().find(|dep| range_contains(dep.range(), pos))
+        find_at_pos(vec, pos, Self::range)
     }
 
     pub fn range(&self) -> Range {
@@ -283,6 +386,155 @@ impl Versioned for SimpleDependency {
     }
 }
 
+/**
+    A simple key-value field in a table, such as `edition = "2021"` in `[package]`.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableField {
+    pub name: Node<String>,
+    pub value: Node<String>,
+}
+
+impl TableField {
+    pub fn find_by_name<'a>(vec: &'a [Self], name: &str) -> Option<&'a Self> {
+        vec.iter().find(|field| field.name.unquoted() == name)
+    }
+
+    /**
+        Finds the field with a value at the given position - note that this
+        only checks the value, since several fields may share the same name,
+        such as all of the entries in an array of the `[workspace]` table.
+    */
+    pub fn find_value_at_pos(vec: &[Self], pos: Position) -> Option<&Self> {
+        find_at_pos(vec, pos, |field| field.value.range)
+    }
+
+    pub fn range(&self) -> Range {
+        range_extend(self.name.range, self.value.range)
+    }
+}
+
+/**
+    A feature definition in a `[features]` table, containing:
+
+    - The name of the feature
+    - The values that the feature activates - other features, optional dependencies, ...
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Feature {
+    pub name: Node<String>,
+    pub values: Node<Vec<Node<String>>>,
+}
+
+impl Feature {
+    pub fn find_at_pos(vec: &[Self], pos: Position) -> Option<&Self> {
+        find_at_pos(vec, pos, Self::range)
+    }
+
+    pub fn range(&self) -> Range {
+        range_extend(self.name.range, self.values.range)
+    }
+}
+
+/**
+    The kind of a build target, declared using an array of tables such as `[[bin]]`.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetKind {
+    Bin,
+    Example,
+    Bench,
+    Test,
+}
+
+impl TargetKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Bin => "bin",
+            Self::Example => "example",
+            Self::Bench => "bench",
+            Self::Test => "test",
+        }
+    }
+}
+
+impl FromStr for TargetKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bin" => Ok(Self::Bin),
+            "example" => Ok(Self::Example),
+            "bench" => Ok(Self::Bench),
+            "test" => Ok(Self::Test),
+            _ => Err(format!("unknown target kind '{s}'")),
+        }
+    }
+}
+
+/**
+    A build target, containing:
+
+    - The kind of the target, with the range of its table header key
+    - The name of the target (may be `None` if the target has no name specified)
+    - The path of the target (may also be `None`, to use the default path)
+    - The range of the entire target table, including the header
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub kind: Node<TargetKind>,
+    pub name: Option<Node<String>>,
+    pub path: Option<Node<String>>,
+    pub range: Range,
+}
+
+impl Target {
+    pub fn find_at_pos(vec: &[Self], pos: Position) -> Option<&Self> {
+        find_at_pos(vec, pos, |target| target.range)
+    }
+}
+
+/**
+    A table of lints for a single tool, such as `[lints.clippy]`, containing:
+
+    - The name of the tool, with the range of its part of the table header
+    - If the table is for the workspace, as in `[workspace.lints.clippy]`
+    - The lints in the table, with the level of each lint as its value
+    - The range of the entire lints table, including the header
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintTable {
+    pub tool: Node<String>,
+    pub workspace: bool,
+    pub lints: Vec<TableField>,
+    pub range: Range,
+}
+
+/**
+    A table header that appears more than once, containing:
+
+    - The key of the first header for the table
+    - The key of the duplicate header, which is not allowed in TOML
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateTable {
+    pub first: Node<String>,
+    pub duplicate: Node<String>,
+}
+
+/**
+    A tool that was found outside of the `[tools]` table, containing:
+
+    - The table the tool was found in (`None` if found at the top level)
+    - The name of the tool
+    - The spec of the tool
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MisplacedTool {
+    pub table: Option<Node<String>>,
+    pub name: Node<String>,
+    pub spec: Node<String>,
+}
+
 /**
     A parsed tool specification, in the format:
 
@@ -333,51 +585,26 @@ impl From<Node<String>> for ParsedSpec {
             None => (raw, None, None),
         };
 
-        // NOTE: range_for_substring will not work for empty substrings
-        // but in this case we can just grab whatever the last position
-        // is and use that as our full zero-length range
-        let end_pos = Position {
-            line: node.range.end.line,
-            character: if node.unquoted().len() < node.quoted().len() {
-                node.range.end.character.saturating_sub(1)
-            } else {
-                node.range.end.character
-            },
-        };
-        let end_range = Range {
-            start: end_pos,
-            end: end_pos,
+        // NOTE: Segments are located using their known offsets, and not
+        // by searching, since the same substring may appear several times,
+        // for example in "wally/wally@0.3.2" - the name is not the author.
+        // Empty segments get a zero-length range right where they would be,
+        // so that completing them inserts text there and nowhere else.
+        let quote_offset = usize::from(raw.len() < node.quoted().len());
+        let segment = |offset: usize, segment: &str| {
+            Node::new_raw(
+                range_for_offset(node.range, quote_offset + offset, segment.len()),
+                segment.to_string(),
+            )
         };
 
+        let name_offset = author.len() + 1;
+        let version_offset = name_offset + name.map(str::len).unwrap_or_default() + 1;
+
         ParsedSpec {
-            author: if author.is_empty() {
-                Node::new_raw(end_range, String::new())
-            } else {
-                Node::new_raw(
-                    range_for_substring(node.range, node.quoted(), author),
-                    author.to_string(),
-                )
-            },
-            name: name.map(|name| {
-                if name.is_empty() {
-                    Node::new_raw(end_range, String::new())
-                } else {
-                    Node::new_raw(
-                        range_for_substring(node.range, node.quoted(), name),
-                        name.to_string(),
-                    )
-                }
-            }),
-            version: version.map(|version| {
-                if version.is_empty() {
-                    Node::new_raw(end_range, String::new())
-                } else {
-                    Node::new_raw(
-                        range_for_substring(node.range, node.quoted(), version),
-                        version.to_string(),
-                    )
-                }
-            }),
+            author: segment(0, author),
+            name: name.map(|name| segment(name_offset, name)),
+            version: version.map(|version| segment(version_offset, version)),
         }
     }
 }
@@ -409,3 +636,134 @@ impl Versioned for ParsedSpecFull {
         self.version.unquoted().to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec_node(spec: &str) -> Node<String> {
+        let quoted = format!("\"{spec}\"");
+        let range = Range {
+            start: Position {
+                line: 0,
+                character: 0,
+            },
+            end: Position {
+                line: 0,
+                character: quoted.len() as u32,
+            },
+        };
+        Node::new_raw(range, quoted)
+    }
+
+    fn pos(character: u32) -> Position {
+        Position { line: 0, character }
+    }
+
+    #[test]
+    fn test_parsed_spec_ranges() {
+        let parsed = ParsedSpec::from(spec_node("wally/wally@0.3.2"));
+        let name = parsed.name.unwrap();
+        let version = parsed.version.unwrap();
+
+        assert_eq!(parsed.author.range.start, pos(1));
+        assert_eq!(parsed.author.range.end, pos(6));
+        assert_eq!(name.range.start, pos(7));
+        assert_eq!(name.range.end, pos(12));
+        assert_eq!(version.range.start, pos(13));
+        assert_eq!(version.range.end, pos(18));
+    }
+
+    #[test]
+    fn test_parsed_spec_boundaries() {
+        // "owner/repo@1.0.0"
+        //  ^    ^    ^
+        //  1    7    12
+        let parsed = ParsedSpec::from(spec_node("owner/repo@1.0.0"));
+        let name = parsed.name.unwrap();
+        let version = parsed.version.unwrap();
+
+        // Right before the slash belongs to the owner, right after to the repo
+        assert!(parsed.author.contains(pos(6)));
+        assert!(!name.contains(pos(6)));
+        assert!(name.contains(pos(7)));
+        assert!(!parsed.author.contains(pos(7)));
+
+        // Right before the at sign belongs to the repo, right after to the version
+        assert!(name.contains(pos(11)));
+        assert!(!version.contains(pos(11)));
+        assert!(version.contains(pos(12)));
+        assert!(!name.contains(pos(12)));
+    }
+
+    #[test]
+    fn test_parsed_spec_multi_byte() {
+        // Segment columns are bytes, same as the node, "ö" is two bytes and "🦀" is four
+        let parsed = ParsedSpec::from(spec_node("öwner/🦀@1.0.0"));
+        let name = parsed.name.unwrap();
+        let version = parsed.version.unwrap();
+
+        assert_eq!(parsed.author.unquoted(), "öwner");
+        assert_eq!(parsed.author.range.start, pos(1));
+        assert_eq!(parsed.author.range.end, pos(7));
+        assert_eq!(name.unquoted(), "🦀");
+        assert_eq!(name.range.start, pos(8));
+        assert_eq!(name.range.end, pos(12));
+        assert_eq!(version.unquoted(), "1.0.0");
+        assert_eq!(version.range.start, pos(13));
+        assert_eq!(version.range.end, pos(18));
+    }
+
+    #[test]
+    fn test_parsed_spec_empty_segments() {
+        let parsed = ParsedSpec::from(spec_node("owner/"));
+        let name = parsed.name.unwrap();
+        assert!(name.unquoted().is_empty());
+        assert_eq!(name.range.start, pos(7));
+        assert!(!parsed.author.contains(pos(7)));
+    }
+
+    #[test]
+    fn test_parsed_spec_empty_name_with_version() {
+        // "owner/@1.0.0"
+        //        ^
+        //        7
+        let parsed = ParsedSpec::from(spec_node("owner/@1.0.0"));
+        let name = parsed.name.unwrap();
+        let version = parsed.version.unwrap();
+        assert!(name.unquoted().is_empty());
+        assert_eq!(name.range.start, pos(7));
+        assert_eq!(name.range.end, pos(7));
+        assert_eq!(version.range.start, pos(8));
+    }
+
+    #[test]
+    fn test_parsed_spec_empty() {
+        let parsed = ParsedSpec::from(spec_node(""));
+        assert_eq!(parsed.author.range.start, pos(1));
+        assert_eq!(parsed.author.range.end, pos(1));
+    }
+
+    #[test]
+    fn test_parsed_spec_without_version() {
+        // Older aftman manifests may leave out the version entirely
+        let parsed = ParsedSpec::from(spec_node("owner/repo"));
+        let name = parsed.name.clone().unwrap();
+        assert_eq!(parsed.author.unquoted(), "owner");
+        assert_eq!(name.unquoted(), "repo");
+        assert_eq!(name.range.start, pos(7));
+        assert_eq!(name.range.end, pos(11));
+        assert!(parsed.version.is_none());
+        assert!(parsed.into_full().is_none());
+    }
+
+    #[test]
+    fn test_parsed_spec_empty_version() {
+        let parsed = ParsedSpec::from(spec_node("owner/repo@"));
+        let version = parsed.version.clone().unwrap();
+        assert!(version.unquoted().is_empty());
+        assert_eq!(version.range.start, pos(12));
+        assert_eq!(version.range.end, pos(12));
+        assert!(parsed.into_full().is_some());
+    }
+}
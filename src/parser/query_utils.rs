@@ -17,10 +17,46 @@ pub fn range_from_node(node: &tree_sitter::Node) -> Range {
     }
 }
 
+/**
+    Checks if the given range contains the given position.
+
+    Both the start and the end of the range are inclusive, meaning a position
+    placed right at the end of a node, which is where the cursor usually is
+    while typing, is considered to be inside of that node.
+
+    Since this makes adjacent ranges overlap at their boundary, prefer
+    using [`find_at_pos`] when searching through multiple ranges.
+
+    Ranges that are separated by a delimiter never overlap, and can be checked
+    one by one in any order, such as the `author/name@version` segments of a
+    spec - a position right before a delimiter belongs to the segment before
+    it, and a position right after a delimiter belongs to the one after it.
+*/
 pub fn range_contains(range: Range, pos: Position) -> bool {
     range.start <= pos && pos <= range.end
 }
 
+/**
+    Finds the first item with a range that contains the given position.
+
+    If the position is exactly at the boundary of two adjacent items, where one
+    range ends and the next one starts, the item *starting* at the position is
+    preferred - the end of a range is only inclusive when nothing starts there.
+*/
+pub fn find_at_pos<T>(items: &[T], pos: Position, range: impl Fn(&T) -> Range) -> Option<&T> {
+    let mut found = None;
+    for item in items {
+        let item_range = range(item);
+        if range_contains(item_range, pos) {
+            if item_range.start == pos {
+                return Some(item);
+            }
+            found = found.or(Some(item));
+        }
+    }
+    found
+}
+
 pub fn range_extend(range: Range, other: Range) -> Range {
     Range {
         start: pos_min(range.start, other.start),
@@ -29,15 +65,26 @@ pub fn range_extend(range: Range, other: Range) -> Range {
 }
 
 pub fn range_for_substring(original_range: Range, original_string: &str, substring: &str) -> Range {
-    let offset = original_string.find(substring).unwrap() as u32;
+    let offset = original_string.find(substring).unwrap();
+    range_for_offset(original_range, offset, substring.len())
+}
+
+/**
+    Creates a range for a substring at a known byte offset and byte length
+    within the string of the original range, which must be on a single line.
+
+    Columns of the returned range are byte offsets within the line, same as
+    the ranges of nodes from tree-sitter, and same as all ranges used by tools.
+*/
+pub fn range_for_offset(original_range: Range, offset: usize, len: usize) -> Range {
     Range {
         start: Position {
             line: original_range.start.line,
-            character: original_range.start.character + offset,
+            character: original_range.start.character + offset as u32,
         },
         end: Position {
             line: original_range.start.line,
-            character: original_range.start.character + offset + substring.len() as u32,
+            character: original_range.start.character + (offset + len) as u32,
         },
     }
 }
@@ -63,3 +110,57 @@ pub fn pos_max(pos: Position, other: Position) -> Position {
         Ordering::Greater => pos,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(character: u32) -> Position {
+        Position { line: 0, character }
+    }
+
+    fn range(start: u32, end: u32) -> Range {
+        Range {
+            start: pos(start),
+            end: pos(end),
+        }
+    }
+
+    #[test]
+    fn test_range_contains_bounds() {
+        assert!(!range_contains(range(2, 5), pos(1)));
+        assert!(range_contains(range(2, 5), pos(2)));
+        assert!(range_contains(range(2, 5), pos(5)));
+        assert!(!range_contains(range(2, 5), pos(6)));
+    }
+
+    #[test]
+    fn test_find_at_pos_prefers_start() {
+        let items = vec![range(0, 5), range(5, 9)];
+        assert_eq!(find_at_pos(&items, pos(4), |r| *r), Some(&items[0]));
+        assert_eq!(find_at_pos(&items, pos(5), |r| *r), Some(&items[1]));
+        assert_eq!(find_at_pos(&items, pos(9), |r| *r), Some(&items[1]));
+        assert_eq!(find_at_pos(&items, pos(10), |r| *r), None);
+    }
+
+    #[test]
+    fn test_range_for_offset_ascii() {
+        let original = range(10, 28);
+        assert_eq!(range_for_offset(original, 7, 4), range(17, 21));
+        assert_eq!(
+            range_for_substring(original, "\"owner/repo@1.0.0\"", "1.0.0"),
+            range(22, 27)
+        );
+    }
+
+    #[test]
+    fn test_range_for_offset_multi_byte() {
+        // Columns stay in bytes, like the range of the node itself,
+        // "ö" is two bytes and "🦀" is four
+        let spec = "\"öwner/🦀@1.0.0\"";
+        let original = range(10, 10 + spec.len() as u32);
+        assert_eq!(range_for_offset(original, 1, 6), range(11, 17));
+        assert_eq!(range_for_substring(original, spec, "🦀"), range(18, 22));
+        assert_eq!(range_for_substring(original, spec, "1.0.0"), range(23, 28));
+    }
+}
@@ -0,0 +1,884 @@
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use tokio::fs;
+use tower_lsp::lsp_types::{ClientCapabilities, DiagnosticSeverity, MarkupKind};
+use tracing::{debug, warn};
+
+use crate::util::DEFAULT_MAX_CONCURRENT_REQUESTS;
+
+const DEFAULT_MAX_DOCUMENT_SIZE: usize = 1024 * 1024;
+const DEFAULT_MAX_DEPENDENCIES: usize = 500;
+
+pub const PROJECT_CONFIG_FILE_NAME: &str = ".tooling-lsp.toml";
+
+/**
+    Configuration for the language server.
+
+    Parsed from the `initializationOptions` sent by the client, where all
+    keys are in camel case, and any missing keys use their default values.
+
+    May also be read from a project config file at the root of a workspace,
+    using the same keys, for config that should be shared across a team.
+    Any options sent by the client take precedence over the project config.
+
+    - `denied_dependencies` - dependencies that are not permitted, such as for
+      license reasons, which are flagged as errors wherever they are declared
+    - `allowed_dependencies` - the only dependencies that are permitted for each
+      ecosystem that has a list, where any other dependency is flagged as an error
+    - `sort_on_save` - if tools should be sorted alphabetically right before
+      a manifest is saved, the same way as the sort code action sorts them
+    - `use_system_credentials` - if credentials should be loaded from `~/.netrc`
+      and Cargo's `credentials.toml` when not explicitly configured
+*/
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Config {
+    pub allowed_dependencies: AllowedDependencies,
+    pub completion: CompletionConfig,
+    pub denied_dependencies: Vec<DependencyRule>,
+    pub diagnostics: DiagnosticsConfig,
+    pub features: FeaturesConfig,
+    pub hover: HoverConfig,
+    pub inlay_hints: InlayHintsConfig,
+    pub limits: LimitsConfig,
+    pub network: NetworkConfig,
+    pub rokit: RokitConfig,
+    pub sort_on_save: bool,
+    pub use_system_credentials: bool,
+}
+
+impl Config {
+    /**
+        Parses a config from a json value, falling back
+        to the default config if the value is invalid.
+    */
+    pub fn from_json_lossy(value: Option<&JsonValue>) -> Self {
+        match value {
+            None | Some(JsonValue::Null) => Self::default(),
+            Some(value) => match serde_json::from_value(value.clone()) {
+                Ok(config) => config,
+                Err(e) => {
+                    warn!("Failed to parse config, using defaults - {e}");
+                    Self::default()
+                }
+            },
+        }
+    }
+
+    /**
+        Parses a config from the project config and the options sent by
+        the client, with the options sent by the client taking precedence,
+        falling back to the default config if the merged value is invalid.
+    */
+    pub fn from_sources_lossy(project: Option<JsonValue>, options: Option<&JsonValue>) -> Self {
+        match (project, options) {
+            (Some(mut project), Some(options)) => {
+                merge_json(&mut project, options.clone());
+                Self::from_json_lossy(Some(&project))
+            }
+            (Some(project), None) => Self::from_json_lossy(Some(&project)),
+            (None, options) => Self::from_json_lossy(options),
+        }
+    }
+}
+
+/**
+    Reads project config files from the given workspace roots, merging
+    them together, with roots that come first taking precedence.
+
+    Returns `None` if no roots contain a valid project config file.
+*/
+pub async fn load_project_config(roots: &[impl AsRef<Path>]) -> Option<JsonValue> {
+    let mut merged = None;
+    for root in roots.iter().rev() {
+        let path = root.as_ref().join(PROJECT_CONFIG_FILE_NAME);
+        let Ok(contents) = fs::read_to_string(&path).await else {
+            continue;
+        };
+        let value = match toml::from_str::<toml::Value>(&contents) {
+            Ok(value) => value,
+            Err(e) => {
+                warn!(
+                    "Failed to parse project config at '{}' - {e}",
+                    path.display()
+                );
+                continue;
+            }
+        };
+        let Ok(value) = serde_json::to_value(value) else {
+            continue;
+        };
+        debug!("Read project config from '{}'", path.display());
+        match merged.as_mut() {
+            None => merged = Some(value),
+            Some(merged) => merge_json(merged, value),
+        }
+    }
+    merged
+}
+
+/**
+    Merges the overrides into the base value, recursively for objects,
+    and otherwise replacing values in the base value entirely.
+*/
+pub(super) fn merge_json(base: &mut JsonValue, overrides: JsonValue) {
+    match (base, overrides) {
+        (JsonValue::Object(base), JsonValue::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+/**
+    A dependency in `denied_dependencies` or `allowed_dependencies`, which is
+    either a name, or a table with a `name` and a `message` that is shown
+    in the diagnostics for any dependencies that it does not permit.
+
+    Names may use the `*` and `?` wildcards, such as `openssl*` or `*-sys`, and
+    are matched against crate names, and the `scope/name` of packages and tools.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum DependencyRule {
+    Name(String),
+    WithMessage {
+        name: String,
+        #[serde(default)]
+        message: Option<String>,
+    },
+}
+
+impl DependencyRule {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Name(name) | Self::WithMessage { name, .. } => name,
+        }
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        match self {
+            Self::Name(_) => None,
+            Self::WithMessage { message, .. } => message.as_deref(),
+        }
+    }
+}
+
+/**
+    The dependencies that are permitted, with separate lists for each
+    ecosystem, since names mean different things in each of them.
+
+    Ecosystems without a list permit any dependency that is not denied,
+    and Aftman manifests use the same list as Rokit manifests.
+*/
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AllowedDependencies {
+    pub cargo: Option<Vec<DependencyRule>>,
+    pub rokit: Option<Vec<DependencyRule>>,
+    pub wally: Option<Vec<DependencyRule>>,
+}
+
+/**
+    Configuration for completions.
+
+    - `sort_by_popularity` - if completions for names should be ranked by
+      popularity (downloads, stars, ...) instead of alphabetically
+    - `filter_by_realm` - if completions for Wally package names should
+      leave out packages for other realms, instead of ranking them last
+    - `filter_declared` - if completions for Cargo crate names should leave out
+      crates that are already declared, instead of marking them and ranking them last
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CompletionConfig {
+    pub sort_by_popularity: bool,
+    pub filter_by_realm: bool,
+    pub filter_declared: bool,
+}
+
+impl Default for CompletionConfig {
+    fn default() -> Self {
+        Self {
+            sort_by_popularity: true,
+            filter_by_realm: false,
+            filter_declared: false,
+        }
+    }
+}
+
+/**
+    Configuration for diagnostics.
+
+    - `partial_versions` - if partial version requirements such as `"1"`
+      or `"1.2"` should be flagged, to enforce fully specified versions
+    - `moving_tags` - if tools pinned to tags that are not versions, such
+      as `latest` or `main`, should be flagged, since they are not reproducible
+    - `deprecated` - how dependencies on deprecated crates should be flagged, if
+      at all, which is off by default, since it needs an extra request for each
+      dependency to the crates.io API, which is rate limited to one per second
+    - `merge` - if diagnostics for the same dependency should be merged when on
+      the same range, and ordered by severity, instead of all being shown as-is
+    - `workspace_versions` - how workspace members that use a different version
+      than the one in `[workspace.dependencies]` should be flagged, if at all
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DiagnosticsConfig {
+    pub partial_versions: bool,
+    pub moving_tags: bool,
+    pub deprecated: DiagnosticLevel,
+    pub merge: bool,
+    pub workspace_versions: DiagnosticLevel,
+}
+
+impl Default for DiagnosticsConfig {
+    fn default() -> Self {
+        Self {
+            partial_versions: false,
+            moving_tags: true,
+            deprecated: DiagnosticLevel::Off,
+            merge: true,
+            workspace_versions: DiagnosticLevel::Hint,
+        }
+    }
+}
+
+/**
+    The level of a diagnostic that can be configured, which
+    is either `"off"`, `"hint"`, or `"warning"` in the config.
+*/
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiagnosticLevel {
+    Off,
+    #[default]
+    Hint,
+    Warning,
+}
+
+impl DiagnosticLevel {
+    pub fn severity(self) -> Option<DiagnosticSeverity> {
+        match self {
+            Self::Off => None,
+            Self::Hint => Some(DiagnosticSeverity::HINT),
+            Self::Warning => Some(DiagnosticSeverity::WARNING),
+        }
+    }
+}
+
+/**
+    Configuration for which language server features are enabled.
+
+    Disabled features are not advertised to the client, and
+    any requests for them that still arrive do no work.
+
+    - `hover` - hover information for dependencies and more
+    - `definition` - going to the manifests of local dependencies
+    - `completion` - completions for names, versions, features, ...
+    - `diagnostics` - diagnostics for dependencies and manifests
+    - `code_action` - quick fixes for diagnostics
+    - `document_highlight` - highlights for dependencies and features
+    - `rename` - renames for features, in the manifest that defines them
+    - `document_symbol` - document symbols for build targets
+    - `inlay_hint` - inlay hints, such as the realms of Wally sections
+    - `inline_value` - inline values with the versions that dependencies are locked to
+    - `linked_editing_range` - editing Rokit tool aliases together with their repository
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FeaturesConfig {
+    pub hover: bool,
+    pub definition: bool,
+    pub completion: bool,
+    pub diagnostics: bool,
+    pub code_action: bool,
+    pub document_highlight: bool,
+    pub rename: bool,
+    pub document_symbol: bool,
+    pub inlay_hint: bool,
+    pub inline_value: bool,
+    pub linked_editing_range: bool,
+}
+
+impl Default for FeaturesConfig {
+    fn default() -> Self {
+        Self {
+            hover: true,
+            definition: true,
+            completion: true,
+            diagnostics: true,
+            code_action: true,
+            document_highlight: true,
+            rename: true,
+            document_symbol: true,
+            inlay_hint: true,
+            inline_value: true,
+            linked_editing_range: true,
+        }
+    }
+}
+
+/**
+    Configuration for hovers.
+
+    - `version_source` - where the versions shown for a dependency come from,
+      which is one of `"lockfile"`, `"registry"`, or `"both"`
+*/
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HoverConfig {
+    pub version_source: VersionSource,
+}
+
+/**
+    Where the versions shown in hovers come from - the version that is locked
+    in the lockfile, the newest published versions in the registry, or both.
+*/
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VersionSource {
+    Lockfile,
+    Registry,
+    #[default]
+    Both,
+}
+
+impl VersionSource {
+    pub fn uses_lockfile(self) -> bool {
+        matches!(self, Self::Lockfile | Self::Both)
+    }
+
+    pub fn uses_registry(self) -> bool {
+        matches!(self, Self::Registry | Self::Both)
+    }
+}
+
+/**
+    Configuration for individual kinds of inlay hints, which
+    are all disabled if the `inlay_hint` feature is disabled.
+
+    - `wally_realms` - the realm that each dependency section in `wally.toml` targets
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct InlayHintsConfig {
+    pub wally_realms: bool,
+}
+
+impl Default for InlayHintsConfig {
+    fn default() -> Self {
+        Self { wally_realms: true }
+    }
+}
+
+/**
+    Configuration for limits on how large manifests may be.
+
+    Manifests above any of these limits are usually generated or
+    vendored, and still get features that only need parsing, but
+    nothing is fetched for their dependencies, to stay responsive.
+
+    - `max_document_size` - the maximum size of a manifest, in bytes
+    - `max_dependencies` - the maximum number of dependencies in a manifest
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LimitsConfig {
+    pub max_document_size: usize,
+    pub max_dependencies: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_document_size: DEFAULT_MAX_DOCUMENT_SIZE,
+            max_dependencies: DEFAULT_MAX_DEPENDENCIES,
+        }
+    }
+}
+
+/**
+    Configuration for network requests.
+
+    - `max_concurrent_requests` - how many registry and GitHub
+      requests may run at the same time, across all tools
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct NetworkConfig {
+    pub max_concurrent_requests: usize,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_requests: DEFAULT_MAX_CONCURRENT_REQUESTS,
+        }
+    }
+}
+
+/**
+    Configuration for Rokit and Aftman tool sources.
+
+    - `github_api_url` - the base url of the GitHub API used for tools,
+      such as `https://github.example.com/api/v3` for GitHub Enterprise
+    - `include_prereleases` - if releases marked as prereleases on GitHub should
+      be completed and suggested as newer versions, even if their tags look stable
+*/
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RokitConfig {
+    pub github_api_url: Option<String>,
+    pub include_prereleases: bool,
+}
+
+/**
+    Which completion item fields the client is able to resolve
+    lazily, negotiated using `completionItem.resolveSupport`.
+
+    Fields that are expensive to compute, usually because they need
+    an extra request, are only populated in `completionItem/resolve`
+    when the client supports resolving them, and are otherwise omitted.
+
+    No completions currently need additional text edits, but support
+    for resolving them is still tracked for any future completions.
+*/
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CompletionResolveSupport {
+    pub detail: bool,
+    pub documentation: bool,
+    pub additional_text_edits: bool,
+}
+
+impl CompletionResolveSupport {
+    pub fn from_capabilities(capabilities: &ClientCapabilities) -> Self {
+        let properties = capabilities
+            .text_document
+            .as_ref()
+            .and_then(|t| t.completion.as_ref())
+            .and_then(|c| c.completion_item.as_ref())
+            .and_then(|i| i.resolve_support.as_ref())
+            .map(|r| r.properties.as_slice())
+            .unwrap_or_default();
+        let supports = |property: &str| properties.iter().any(|p| p == property);
+        Self {
+            detail: supports("detail"),
+            documentation: supports("documentation"),
+            additional_text_edits: supports("additionalTextEdits"),
+        }
+    }
+
+    pub fn any(&self) -> bool {
+        self.detail || self.documentation || self.additional_text_edits
+    }
+}
+
+/**
+    Checks if the client supports snippets in completion items,
+    negotiated using `completionItem.snippetSupport`.
+*/
+pub fn completion_snippet_support(capabilities: &ClientCapabilities) -> bool {
+    capabilities
+        .text_document
+        .as_ref()
+        .and_then(|t| t.completion.as_ref())
+        .and_then(|c| c.completion_item.as_ref())
+        .and_then(|i| i.snippet_support)
+        .unwrap_or_default()
+}
+
+/**
+    Checks if the client prefers plain text over markdown in hovers, negotiated
+    using `hover.contentFormat`, which lists formats in order of preference.
+
+    Clients that do not list any formats are sent markdown, as before.
+*/
+pub fn hover_plaintext_preferred(capabilities: &ClientCapabilities) -> bool {
+    let formats = capabilities
+        .text_document
+        .as_ref()
+        .and_then(|t| t.hover.as_ref())
+        .and_then(|h| h.content_format.as_deref())
+        .unwrap_or_default();
+    matches!(formats.first(), Some(MarkupKind::PlainText))
+}
+
+/**
+    Checks if the client supports inline values at all, which only some
+    clients do, and mostly use for showing values during debugging sessions.
+*/
+pub fn inline_value_support(capabilities: &ClientCapabilities) -> bool {
+    capabilities
+        .text_document
+        .as_ref()
+        .is_some_and(|t| t.inline_value.is_some())
+}
+
+/**
+    Checks if the client supports resolving edits of code actions lazily,
+    negotiated using `codeAction.resolveSupport.properties`.
+*/
+pub fn code_action_edit_resolve_support(capabilities: &ClientCapabilities) -> bool {
+    capabilities
+        .text_document
+        .as_ref()
+        .and_then(|t| t.code_action.as_ref())
+        .and_then(|c| c.resolve_support.as_ref())
+        .is_some_and(|r| r.properties.iter().any(|p| p == "edit"))
+}
+
+/**
+    Checks if the client runs package managers itself, using the
+    `tooling-language-server.runPackageManager` command, which it
+    opts into by setting the `packageManagerCommands` experimental
+    capability, since the server never runs the command on its own.
+*/
+pub fn package_manager_command_support(capabilities: &ClientCapabilities) -> bool {
+    capabilities
+        .experimental
+        .as_ref()
+        .and_then(|e| e.get("packageManagerCommands"))
+        .and_then(JsonValue::as_bool)
+        .unwrap_or_default()
+}
+
+/**
+    Checks if the client supports the server pulling settings,
+    negotiated using `workspace.configuration`.
+*/
+pub fn configuration_pull_support(capabilities: &ClientCapabilities) -> bool {
+    capabilities
+        .workspace
+        .as_ref()
+        .and_then(|w| w.configuration)
+        .unwrap_or_default()
+}
+
+/**
+    Checks if the client supports registering for settings changes,
+    negotiated using `workspace.didChangeConfiguration.dynamicRegistration`.
+*/
+pub fn configuration_change_registration_support(capabilities: &ClientCapabilities) -> bool {
+    capabilities
+        .workspace
+        .as_ref()
+        .and_then(|w| w.did_change_configuration.as_ref())
+        .and_then(|d| d.dynamic_registration)
+        .unwrap_or_default()
+}
+
+/**
+    Checks if the client supports registering for changes to watched files,
+    negotiated using `workspace.didChangeWatchedFiles.dynamicRegistration`.
+*/
+pub fn watched_files_registration_support(capabilities: &ClientCapabilities) -> bool {
+    capabilities
+        .workspace
+        .as_ref()
+        .and_then(|w| w.did_change_watched_files.as_ref())
+        .and_then(|d| d.dynamic_registration)
+        .unwrap_or_default()
+}
+
+/**
+    Checks if the client supports registering for pulled diagnostics at runtime,
+    negotiated using `textDocument.diagnostic.dynamicRegistration`.
+*/
+pub fn diagnostic_registration_support(capabilities: &ClientCapabilities) -> bool {
+    capabilities
+        .text_document
+        .as_ref()
+        .and_then(|t| t.diagnostic.as_ref())
+        .and_then(|d| d.dynamic_registration)
+        .unwrap_or_default()
+}
+
+/**
+    Checks if the client supports watching files relative to a workspace folder,
+    negotiated using `workspace.didChangeWatchedFiles.relativePatternSupport`.
+*/
+pub fn watched_files_relative_pattern_support(capabilities: &ClientCapabilities) -> bool {
+    capabilities
+        .workspace
+        .as_ref()
+        .and_then(|w| w.did_change_watched_files.as_ref())
+        .and_then(|d| d.relative_pattern_support)
+        .unwrap_or_default()
+}
+
+/**
+    A cheaply cloneable handle to the current config.
+
+    The config may change while the server is running, so
+    make sure to not hold onto values from `get` for too long.
+
+    Also contains any negotiated client capabilities that affect
+    how tools behave, which never change after initialization.
+*/
+#[derive(Debug, Default, Clone)]
+pub struct SharedConfig {
+    inner: Arc<RwLock<Config>>,
+    completion_resolve: Arc<RwLock<CompletionResolveSupport>>,
+    completion_snippets: Arc<RwLock<bool>>,
+    hover_plaintext: Arc<RwLock<bool>>,
+    code_action_edit_resolve: Arc<RwLock<bool>>,
+    package_manager_commands: Arc<RwLock<bool>>,
+    watched_files_registration: Arc<RwLock<bool>>,
+    watched_files_relative_patterns: Arc<RwLock<bool>>,
+    diagnostic_registration: Arc<RwLock<bool>>,
+}
+
+impl SharedConfig {
+    pub fn get(&self) -> Config {
+        self.inner.read().expect("config lock was poisoned").clone()
+    }
+
+    pub fn set(&self, config: Config) {
+        *self.inner.write().expect("config lock was poisoned") = config;
+    }
+
+    pub fn completion_resolve(&self) -> CompletionResolveSupport {
+        *self
+            .completion_resolve
+            .read()
+            .expect("config lock was poisoned")
+    }
+
+    pub fn set_completion_resolve(&self, support: CompletionResolveSupport) {
+        *self
+            .completion_resolve
+            .write()
+            .expect("config lock was poisoned") = support;
+    }
+
+    pub fn completion_snippets(&self) -> bool {
+        *self
+            .completion_snippets
+            .read()
+            .expect("config lock was poisoned")
+    }
+
+    pub fn set_completion_snippets(&self, supported: bool) {
+        *self
+            .completion_snippets
+            .write()
+            .expect("config lock was poisoned") = supported;
+    }
+
+    pub fn hover_plaintext(&self) -> bool {
+        *self
+            .hover_plaintext
+            .read()
+            .expect("config lock was poisoned")
+    }
+
+    pub fn set_hover_plaintext(&self, plaintext: bool) {
+        *self
+            .hover_plaintext
+            .write()
+            .expect("config lock was poisoned") = plaintext;
+    }
+
+    pub fn code_action_edit_resolve(&self) -> bool {
+        *self
+            .code_action_edit_resolve
+            .read()
+            .expect("config lock was poisoned")
+    }
+
+    pub fn set_code_action_edit_resolve(&self, supported: bool) {
+        *self
+            .code_action_edit_resolve
+            .write()
+            .expect("config lock was poisoned") = supported;
+    }
+
+    pub fn package_manager_commands(&self) -> bool {
+        *self
+            .package_manager_commands
+            .read()
+            .expect("config lock was poisoned")
+    }
+
+    pub fn set_package_manager_commands(&self, supported: bool) {
+        *self
+            .package_manager_commands
+            .write()
+            .expect("config lock was poisoned") = supported;
+    }
+
+    pub fn watched_files_registration(&self) -> bool {
+        *self
+            .watched_files_registration
+            .read()
+            .expect("config lock was poisoned")
+    }
+
+    pub fn set_watched_files_registration(&self, supported: bool) {
+        *self
+            .watched_files_registration
+            .write()
+            .expect("config lock was poisoned") = supported;
+    }
+
+    pub fn diagnostic_registration(&self) -> bool {
+        *self
+            .diagnostic_registration
+            .read()
+            .expect("config lock was poisoned")
+    }
+
+    pub fn set_diagnostic_registration(&self, supported: bool) {
+        *self
+            .diagnostic_registration
+            .write()
+            .expect("config lock was poisoned") = supported;
+    }
+
+    pub fn watched_files_relative_patterns(&self) -> bool {
+        *self
+            .watched_files_relative_patterns
+            .read()
+            .expect("config lock was poisoned")
+    }
+
+    pub fn set_watched_files_relative_patterns(&self, supported: bool) {
+        *self
+            .watched_files_relative_patterns
+            .write()
+            .expect("config lock was poisoned") = supported;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_merge_nested() {
+        let mut base = json!({
+            "limits": { "maxDocumentSize": 1, "maxDependencies": 2 },
+            "useSystemCredentials": true,
+        });
+        merge_json(
+            &mut base,
+            json!({
+                "limits": { "maxDependencies": 3 },
+                "features": { "hover": false },
+            }),
+        );
+        assert_eq!(
+            base,
+            json!({
+                "limits": { "maxDocumentSize": 1, "maxDependencies": 3 },
+                "features": { "hover": false },
+                "useSystemCredentials": true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_options_take_precedence() {
+        let project: toml::Value = toml::from_str(
+            r#"
+            useSystemCredentials = true
+
+            [completion]
+            sortByPopularity = false
+
+            [network]
+            maxConcurrentRequests = 2
+            "#,
+        )
+        .unwrap();
+        let project = serde_json::to_value(project).unwrap();
+        let options = json!({ "network": { "maxConcurrentRequests": 4 } });
+
+        let config = Config::from_sources_lossy(Some(project), Some(&options));
+        assert!(config.use_system_credentials);
+        assert!(!config.completion.sort_by_popularity);
+        assert_eq!(config.network.max_concurrent_requests, 4);
+    }
+
+    #[test]
+    fn test_diagnostic_level() {
+        let options = json!({ "diagnostics": { "workspaceVersions": "warning" } });
+        let config = Config::from_json_lossy(Some(&options));
+        assert_eq!(
+            config.diagnostics.workspace_versions,
+            DiagnosticLevel::Warning
+        );
+        assert_eq!(
+            Config::default().diagnostics.workspace_versions.severity(),
+            Some(DiagnosticSeverity::HINT)
+        );
+    }
+
+    #[test]
+    fn test_package_manager_command_support() {
+        let capabilities = |experimental| ClientCapabilities {
+            experimental,
+            ..Default::default()
+        };
+        assert!(!package_manager_command_support(&capabilities(None)));
+        assert!(!package_manager_command_support(&capabilities(Some(
+            json!({ "packageManagerCommands": "yes" })
+        ))));
+        assert!(package_manager_command_support(&capabilities(Some(
+            json!({ "packageManagerCommands": true })
+        ))));
+    }
+
+    #[test]
+    fn test_hover_version_source() {
+        let options = json!({ "hover": { "versionSource": "lockfile" } });
+        let config = Config::from_json_lossy(Some(&options));
+        assert_eq!(config.hover.version_source, VersionSource::Lockfile);
+        assert!(!config.hover.version_source.uses_registry());
+        assert_eq!(Config::default().hover.version_source, VersionSource::Both);
+    }
+
+    #[test]
+    fn test_dependency_rules() {
+        let options = json!({
+            "deniedDependencies": [
+                "openssl*",
+                { "name": "git2", "message": "Use gix instead" }
+            ]
+        });
+        let config = Config::from_json_lossy(Some(&options));
+        assert_eq!(
+            config.denied_dependencies,
+            vec![
+                DependencyRule::Name(String::from("openssl*")),
+                DependencyRule::WithMessage {
+                    name: String::from("git2"),
+                    message: Some(String::from("Use gix instead")),
+                },
+            ]
+        );
+        assert_eq!(config.denied_dependencies[1].name(), "git2");
+        assert_eq!(config.allowed_dependencies, AllowedDependencies::default());
+
+        let options = json!({ "allowedDependencies": { "cargo": ["serde*"] } });
+        let config = Config::from_json_lossy(Some(&options));
+        assert_eq!(
+            config.allowed_dependencies.cargo,
+            Some(vec![DependencyRule::Name(String::from("serde*"))])
+        );
+        assert_eq!(config.allowed_dependencies.wally, None);
+    }
+}
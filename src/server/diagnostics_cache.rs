@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tower_lsp::lsp_types::*;
+
+use crate::parser::DependencyKind;
+
+use super::Document;
+
+/*
+    Cached diagnostics may depend on registry data that changes over time,
+    so they expire after a while, even if the dependency never changes
+*/
+const MINUTES_TO_LIVE: u64 = 10;
+
+/**
+    A stable identity for a single dependency in a document.
+
+    Two dependencies with the same key are guaranteed to produce the
+    same diagnostics, apart from where those diagnostics are located.
+
+    - `kind` - the kind of dependency, such as a dev dependency
+    - `text` - the full source text of the dependency, name and spec
+    - `context` - any other document contents that the
+      diagnostics depend on, such as a custom index url
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DependencyKey {
+    kind: DependencyKind,
+    text: String,
+    context: String,
+}
+
+impl DependencyKey {
+    pub fn new(doc: &Document, kind: DependencyKind, range: Range) -> Self {
+        let text = doc.as_str()[doc.range_to_span(range)].to_string();
+        Self {
+            kind,
+            text,
+            context: String::new(),
+        }
+    }
+
+    pub fn with_context(self, context: impl Into<String>) -> Self {
+        Self {
+            context: context.into(),
+            ..self
+        }
+    }
+}
+
+/**
+    Diagnostics that were computed for a dependency
+    while it was located at the given range.
+*/
+#[derive(Debug, Clone)]
+pub struct CachedDiagnostics {
+    pub range: Range,
+    pub diagnostics: Vec<Diagnostic>,
+    created: Instant,
+}
+
+impl CachedDiagnostics {
+    pub fn new(range: Range, diagnostics: Vec<Diagnostic>) -> Self {
+        Self {
+            range,
+            diagnostics,
+            created: Instant::now(),
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.created.elapsed() > Duration::from_secs(60 * MINUTES_TO_LIVE)
+    }
+}
+
+/**
+    A cache of diagnostics for individual dependencies in a document.
+
+    This is cheaply cloneable, and all clones of a document share
+    the same cache, so that diagnostics computed for the document
+    can be reused the next time that the document changes.
+
+    Also remembers if the diagnostics that were last computed for the
+    document were incomplete, since those are never cached themselves.
+*/
+#[derive(Debug, Default, Clone)]
+pub struct DiagnosticsCache {
+    inner: Arc<Mutex<HashMap<DependencyKey, CachedDiagnostics>>>,
+    incomplete: Arc<AtomicBool>,
+}
+
+impl DiagnosticsCache {
+    pub fn get(&self, key: &DependencyKey) -> Option<CachedDiagnostics> {
+        let inner = self
+            .inner
+            .lock()
+            .expect("diagnostics cache lock was poisoned");
+        inner
+            .get(key)
+            .filter(|cached| !cached.is_expired())
+            .cloned()
+    }
+
+    /**
+        Replaces the entire contents of the cache, making sure that
+        dependencies which were removed from the document don't linger.
+    */
+    pub fn replace(
+        &self,
+        entries: impl IntoIterator<Item = (DependencyKey, CachedDiagnostics)>,
+        complete: bool,
+    ) {
+        let mut inner = self
+            .inner
+            .lock()
+            .expect("diagnostics cache lock was poisoned");
+        *inner = entries.into_iter().collect();
+        self.incomplete.store(!complete, Ordering::Relaxed);
+    }
+
+    /**
+        Checks if all of the diagnostics that were last computed for the
+        document were complete, meaning that no data was missing for them.
+    */
+    pub fn is_complete(&self) -> bool {
+        !self.incomplete.load(Ordering::Relaxed)
+    }
+
+    pub fn clear(&self) {
+        let mut inner = self
+            .inner
+            .lock()
+            .expect("diagnostics cache lock was poisoned");
+        inner.clear();
+        self.incomplete.store(false, Ordering::Relaxed);
+    }
+}
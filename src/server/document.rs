@@ -7,7 +7,13 @@ use lsp_document::{IndexedText, TextAdapter, TextMap};
 
 use tower_lsp::lsp_types::*;
 
-use crate::{parser::TreeSitterDocument, util::*};
+use crate::{
+    parser::{TreeSitterDocument, TreeSitterLanguage},
+    tools::ToolName,
+    util::*,
+};
+
+use super::DiagnosticsCache;
 
 type Span = std::ops::Range<usize>;
 
@@ -21,6 +27,8 @@ pub struct Document {
     opened: bool,
     text: IndexedText<String>,
     inner: TreeSitterDocument,
+    diagnostics: DiagnosticsCache,
+    tool_override: Option<ToolName>,
 }
 
 impl Document {
@@ -32,6 +40,10 @@ impl Document {
         &self.name
     }
 
+    pub fn is_opened(&self) -> bool {
+        self.opened
+    }
+
     pub fn version(&self) -> i32 {
         self.version
     }
@@ -44,12 +56,61 @@ impl Document {
         &self.inner
     }
 
+    pub fn diagnostics_cache(&self) -> &DiagnosticsCache {
+        &self.diagnostics
+    }
+
+    /**
+        Gets the tool forced by a modeline on the first line of the document,
+        if any, which takes precedence over the file name of the document.
+    */
+    pub fn tool_override(&self) -> Option<ToolName> {
+        self.tool_override
+    }
+
+    /**
+        Checks if the document belongs to the given tool, either because of
+        a modeline, or otherwise because its file name matches one of the
+        given file names, such as `Cargo.toml`, ignoring case.
+    */
+    pub fn is_for_tool(&self, tool: ToolName, file_names: &[&str]) -> bool {
+        match self.tool_override {
+            Some(tool_override) => tool_override == tool,
+            None => self
+                .uri
+                .file_name()
+                .is_some_and(|f| file_names.iter().any(|n| f.eq_ignore_ascii_case(n))),
+        }
+    }
+
+    /**
+        Clamps a position sent by a client to the contents of the document - clients
+        may send positions past the end of a line, or past the last line, such as
+        for a cursor on a trailing empty line, or in an empty document.
+    */
+    fn clamp_lsp_position(&self, position: Position) -> Position {
+        let text = self.as_str();
+        let Some(line) = text.split('\n').nth(position.line as usize) else {
+            return self.lsp_position_from_offset(text.len());
+        };
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let line_len = line.encode_utf16().count() as u32;
+        Position {
+            line: position.line,
+            character: position.character.min(line_len),
+        }
+    }
+
     pub fn lsp_position_to_offset(&self, position: Position) -> usize {
-        let pos = self.text.lsp_pos_to_pos(&position).unwrap();
-        self.text.pos_to_offset(&pos).unwrap()
+        let position = self.clamp_lsp_position(position);
+        self.text
+            .lsp_pos_to_pos(&position)
+            .and_then(|pos| self.text.pos_to_offset(&pos))
+            .unwrap_or(self.as_str().len())
     }
 
     pub fn lsp_position_from_offset(&self, offset: usize) -> Position {
+        let offset = offset.min(self.as_str().len());
         let pos = self.text.offset_to_pos(offset).unwrap();
         self.text.pos_to_lsp_pos(&pos).unwrap()
     }
@@ -66,11 +127,84 @@ impl Document {
         Span { start, end }
     }
 
+    /**
+        Gets the offset of a position from the parser, where columns are byte
+        offsets within their line, the same as in ranges of tree-sitter nodes.
+
+        Positions past the end of their line are clamped to it, and positions
+        past the last line to the end of the document, just like for clients.
+    */
+    pub fn position_to_offset(&self, position: Position) -> usize {
+        let text = self.as_str();
+        let Some(line_start) = line_starts(text).nth(position.line as usize) else {
+            return text.len();
+        };
+        let line = &text[line_start..];
+        let line = line.split('\n').next().unwrap_or(line);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let mut column = (position.character as usize).min(line.len());
+        while !line.is_char_boundary(column) {
+            column -= 1;
+        }
+        line_start + column
+    }
+
+    pub fn position_from_offset(&self, offset: usize) -> Position {
+        let text = self.as_str();
+        let mut offset = offset.min(text.len());
+        while !text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let before = &text[..offset];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        Position {
+            line: before.matches('\n').count() as u32,
+            character: (offset - line_start) as u32,
+        }
+    }
+
+    pub fn range_from_span(&self, span: Span) -> Range {
+        let start = self.position_from_offset(span.start);
+        let end = self.position_from_offset(span.end);
+        Range::new(start, end)
+    }
+
+    pub fn range_to_span(&self, range: Range) -> Span {
+        let start = self.position_to_offset(range.start);
+        let end = self.position_to_offset(range.end);
+        Span { start, end }
+    }
+
+    /**
+        Clamps a position from the parser to the contents of the document.
+    */
+    pub fn clamp_position(&self, position: Position) -> Position {
+        self.position_from_offset(self.position_to_offset(position))
+    }
+
+    /**
+        Converts a position from the parser into a position for clients.
+
+        Parser positions come from tree-sitter, where columns are byte offsets within
+        their line, while clients use UTF-16 code units, which differ as soon as
+        there are multi-byte characters on the line before the position.
+    */
+    pub fn lsp_position_from_parser(&self, position: Position) -> Position {
+        self.lsp_position_from_offset(self.position_to_offset(position))
+    }
+
+    /**
+        Converts a position sent by a client into a position for the parser.
+    */
+    pub fn lsp_position_to_parser(&self, position: Position) -> Position {
+        self.position_from_offset(self.lsp_position_to_offset(position))
+    }
+
     pub fn create_edit(&self, range: Range, new_text: impl Into<String>) -> TextEdit {
         let new_text = new_text.into();
         tracing::trace!(
             "Created edit: '{}' at range '{}:{} -> {}:{}' becomes '{}'",
-            &self.as_str()[self.lsp_range_to_span(range)],
+            &self.as_str()[self.range_to_span(range)],
             range.start.line,
             range.start.character,
             range.end.line,
@@ -80,42 +214,6 @@ impl Document {
         TextEdit { range, new_text }
     }
 
-    pub fn create_substring_edit(
-        &self,
-        line: u32,
-        substring: impl AsRef<str>,
-        replacement: impl Into<String>,
-    ) -> TextEdit {
-        let substring = substring.as_ref();
-        let replacement = replacement.into();
-
-        let range = self.text.line_range(line).expect("invalid line");
-        let slice = &self.as_str()[self.lsp_range_to_span(Range {
-            start: Position {
-                line: range.start.line,
-                character: 0,
-            },
-            end: Position {
-                line: range.end.line,
-                character: range.end.col,
-            },
-        })];
-
-        let offset = slice.find(substring).expect("invalid source text");
-        let edit_range = Range {
-            start: Position {
-                line: range.start.line,
-                character: offset as u32,
-            },
-            end: Position {
-                line: range.end.line,
-                character: (offset + substring.len()) as u32,
-            },
-        };
-
-        self.create_edit(edit_range, replacement)
-    }
-
     pub fn set_version(&mut self, version: impl Into<i32>) {
         self.version = version.into();
     }
@@ -127,14 +225,52 @@ impl Document {
     pub fn set_text(&mut self, new_text: impl Into<String>) {
         let text = new_text.into();
         self.text = IndexedText::new(text.clone());
-        self.inner.set_contents(text);
+        self.update_contents(text);
     }
 
-    pub fn apply_change(&mut self, change: TextDocumentContentChangeEvent) {
-        let change = self.text.lsp_change_to_change(change).unwrap();
+    pub fn apply_change(&mut self, mut change: TextDocumentContentChangeEvent) {
+        change.range = change.range.map(|range| Range {
+            start: self.clamp_lsp_position(range.start),
+            end: self.clamp_lsp_position(range.end),
+        });
+        let Some(change) = self.text.lsp_change_to_change(change) else {
+            return;
+        };
         let replaced = lsp_document::apply_change(&self.text, change);
         self.text = IndexedText::new(replaced.clone());
-        self.inner.set_contents(replaced);
+        self.update_contents(replaced);
+    }
+
+    fn update_contents(&mut self, text: String) {
+        // A modeline that was added or removed may change the language,
+        // which needs a new tree, and not just new contents for the old one
+        let tool_override = ToolName::from_modeline(&text);
+        if tool_override == self.tool_override {
+            self.inner.set_contents(text);
+        } else {
+            self.tool_override = tool_override;
+            self.inner = parse_document(&self.uri, text, tool_override);
+        }
+    }
+}
+
+fn line_starts(text: &str) -> impl Iterator<Item = usize> + '_ {
+    let newlines = text.match_indices('\n').map(|(index, _)| index + 1);
+    std::iter::once(0).chain(newlines)
+}
+
+fn parse_document(uri: &Url, text: String, tool_override: Option<ToolName>) -> TreeSitterDocument {
+    // Modelines are only ever recognized for tools with TOML manifests,
+    // and documents that lost their modeline can only have been TOML
+    match tool_override {
+        Some(_) => TreeSitterDocument::new_with_language(
+            uri.clone(),
+            text.as_str(),
+            TreeSitterLanguage::Toml,
+        ),
+        None => TreeSitterDocument::new(uri.clone(), text.as_str()).unwrap_or_else(|| {
+            TreeSitterDocument::new_with_language(uri.clone(), text, TreeSitterLanguage::Toml)
+        }),
     }
 }
 
@@ -195,8 +331,9 @@ impl DocumentBuilder {
         });
 
         let text = IndexedText::new(self.text.clone().unwrap_or_default());
-        let inner = TreeSitterDocument::new(uri.clone(), self.text.unwrap_or_default())
-            .expect("encountered unexpected file name with no corresponding language");
+        let tool_override = ToolName::from_modeline(&text.text);
+
+        let inner = parse_document(&uri, self.text.unwrap_or_default(), tool_override);
 
         Document {
             uri,
@@ -205,6 +342,94 @@ impl DocumentBuilder {
             opened: self.opened.unwrap_or(false),
             text,
             inner,
+            diagnostics: DiagnosticsCache::default(),
+            tool_override,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(text: &str) -> Document {
+        DocumentBuilder::new()
+            .with_uri(Url::parse("file:///project/Cargo.toml").unwrap())
+            .with_text(text)
+            .build()
+    }
+
+    #[test]
+    fn test_positions_past_end() {
+        let doc = document("[dependencies]\nserde = \"1\"\n");
+        assert_eq!(doc.lsp_position_to_offset(Position::new(1, 99)), 26);
+        assert_eq!(doc.lsp_position_to_offset(Position::new(2, 0)), 27);
+        assert_eq!(doc.lsp_position_to_offset(Position::new(9, 9)), 27);
+        assert_eq!(doc.clamp_position(Position::new(9, 9)), Position::new(2, 0));
+        assert_eq!(doc.lsp_position_from_offset(99), Position::new(2, 0));
+    }
+
+    #[test]
+    fn test_positions_from_parser() {
+        // "ö" is two bytes but one UTF-16 unit, and "🦀" is four bytes but two units
+        let doc = document("a = \"b\"\nö = \"🦀x\"\r\n");
+        let from_parser =
+            |line, character| doc.lsp_position_from_parser(Position::new(line, character));
+        assert_eq!(from_parser(0, 5), Position::new(0, 5));
+        assert_eq!(from_parser(1, 6), Position::new(1, 5));
+        assert_eq!(from_parser(1, 10), Position::new(1, 7));
+        assert_eq!(from_parser(1, 11), Position::new(1, 8));
+        // Columns inside of a character are moved to its start, and
+        // columns past the end of the line are clamped to it
+        assert_eq!(from_parser(1, 8), Position::new(1, 5));
+        assert_eq!(from_parser(1, 99), Position::new(1, 9));
+
+        let to_parser =
+            |line, character| doc.lsp_position_to_parser(Position::new(line, character));
+        assert_eq!(to_parser(1, 5), Position::new(1, 6));
+        assert_eq!(to_parser(1, 7), Position::new(1, 10));
+        assert_eq!(to_parser(1, 99), Position::new(1, 12));
+        assert_eq!(to_parser(9, 0), Position::new(2, 0));
+
+        assert_eq!(doc.position_to_offset(Position::new(1, 10)), 18);
+        assert_eq!(doc.position_from_offset(18), Position::new(1, 10));
+        assert_eq!(
+            doc.range_to_span(Range::new(Position::new(1, 0), Position::new(1, 3))),
+            8..11
+        );
+    }
+
+    #[test]
+    fn test_positions_empty() {
+        let doc = document("");
+        assert_eq!(doc.lsp_position_to_offset(Position::new(3, 3)), 0);
+        assert_eq!(doc.clamp_position(Position::new(3, 3)), Position::new(0, 0));
+    }
+
+    #[test]
+    fn test_change_past_end() {
+        let mut doc = document("[dependencies]\n");
+        doc.apply_change(TextDocumentContentChangeEvent {
+            range: Some(Range::new(Position::new(5, 0), Position::new(5, 0))),
+            range_length: None,
+            text: String::from("serde = \"1\"\n"),
+        });
+        assert_eq!(doc.as_str(), "[dependencies]\nserde = \"1\"\n");
+    }
+
+    #[test]
+    fn test_change_modeline_language() {
+        let mut doc = DocumentBuilder::new()
+            .with_uri(Url::parse("file:///project/package.json").unwrap())
+            .with_text("{}")
+            .build();
+        doc.apply_change(TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: String::from("# tooling-lsp: tool=cargo\n[dependencies]\nserde = \"1\"\n"),
+        });
+        assert_eq!(doc.tool_override(), Some(ToolName::Cargo));
+        let deps = crate::parser::query_cargo_toml_dependencies(doc.inner());
+        assert_eq!(deps.len(), 1);
+    }
+}
@@ -0,0 +1,216 @@
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use tower_lsp::lsp_types::*;
+
+/**
+    The workspace folders that are open in the client, which is cheaply
+    cloneable, with all clones sharing the same folders.
+
+    Kept up to date using `workspace/didChangeWorkspaceFolders`,
+    since folders may be added, removed, or renamed at any time.
+*/
+#[derive(Debug, Default, Clone)]
+pub struct WorkspaceFolders {
+    inner: Arc<RwLock<Vec<WorkspaceFolder>>>,
+}
+
+impl WorkspaceFolders {
+    pub fn get(&self) -> Vec<WorkspaceFolder> {
+        self.inner
+            .read()
+            .expect("workspace folders lock was poisoned")
+            .clone()
+    }
+
+    /**
+        Returns the paths of all folders that are on disk, which
+        are the roots that project config files are read from.
+    */
+    pub fn roots(&self) -> Vec<PathBuf> {
+        self.get()
+            .iter()
+            .filter_map(|folder| folder.uri.to_file_path().ok())
+            .collect()
+    }
+
+    pub fn set(&self, folders: Vec<WorkspaceFolder>) {
+        *self
+            .inner
+            .write()
+            .expect("workspace folders lock was poisoned") = folders;
+    }
+
+    /**
+        Applies a change from `workspace/didChangeWorkspaceFolders`,
+        where a renamed folder is both removed and added.
+    */
+    pub fn apply(&self, event: WorkspaceFoldersChangeEvent) {
+        let mut folders = self
+            .inner
+            .write()
+            .expect("workspace folders lock was poisoned");
+        folders.retain(|folder| event.removed.iter().all(|r| r.uri != folder.uri));
+        for added in event.added {
+            if folders.iter().all(|f| f.uri != added.uri) {
+                folders.push(added);
+            }
+        }
+    }
+}
+
+/**
+    Creates watchers for the given globs, relative to each workspace folder if
+    the client supports relative patterns, so that watching follows folders when
+    they change, or as plain globs which match anywhere, as a fallback.
+*/
+pub fn file_system_watchers(
+    globs: &[String],
+    folders: &[WorkspaceFolder],
+    relative_patterns: bool,
+) -> Vec<FileSystemWatcher> {
+    if !relative_patterns || folders.is_empty() {
+        return globs
+            .iter()
+            .map(|glob| FileSystemWatcher {
+                glob_pattern: GlobPattern::String(glob.clone()),
+                kind: None,
+            })
+            .collect();
+    }
+    folders
+        .iter()
+        .flat_map(|folder| {
+            globs.iter().map(|glob| FileSystemWatcher {
+                glob_pattern: GlobPattern::Relative(RelativePattern {
+                    base_uri: OneOf::Left(folder.clone()),
+                    pattern: glob.clone(),
+                }),
+                kind: None,
+            })
+        })
+        .collect()
+}
+
+/**
+    Creates a document filter that matches only the document at the given
+    uri, for documents that tools recognize by contents and not by file name.
+
+    Returns `None` if the path of the document can not be matched exactly,
+    since characters such as `*` and `{` would be read as part of a glob.
+*/
+pub fn exact_document_filter(uri: &Url) -> Option<DocumentFilter> {
+    let path = match uri.scheme() {
+        "file" => uri.to_file_path().ok()?.to_str()?.replace('\\', "/"),
+        _ => uri.path().to_string(),
+    };
+    if path.is_empty() || path.contains(['*', '?', '[', ']', '{', '}']) {
+        return None;
+    }
+    Some(DocumentFilter {
+        language: None,
+        scheme: Some(uri.scheme().to_string()),
+        pattern: Some(path),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::LanguageServer;
+
+    use crate::server::testing::{MockFetcher, TestServer};
+    use crate::util::FixtureDir;
+
+    use super::*;
+
+    fn folder(name: &str) -> WorkspaceFolder {
+        WorkspaceFolder {
+            uri: Url::parse(&format!("file:///projects/{name}")).unwrap(),
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_apply_changes() {
+        let folders = WorkspaceFolders::default();
+        folders.set(vec![folder("app"), folder("lib")]);
+        folders.apply(WorkspaceFoldersChangeEvent {
+            added: vec![folder("renamed"), folder("app")],
+            removed: vec![folder("lib")],
+        });
+        assert_eq!(folders.get(), vec![folder("app"), folder("renamed")]);
+    }
+
+    #[test]
+    fn test_watchers_relative() {
+        let globs = [String::from("**/Cargo.lock"), String::from("**/wally.lock")];
+        let watchers = file_system_watchers(&globs, &[folder("app"), folder("lib")], true);
+        assert_eq!(watchers.len(), 4);
+        assert_eq!(
+            watchers[1].glob_pattern,
+            GlobPattern::Relative(RelativePattern {
+                base_uri: OneOf::Left(folder("app")),
+                pattern: String::from("**/wally.lock"),
+            })
+        );
+    }
+
+    #[test]
+    fn test_watchers_fallback() {
+        let globs = [String::from("**/Cargo.lock")];
+        let plain = vec![FileSystemWatcher {
+            glob_pattern: GlobPattern::String(String::from("**/Cargo.lock")),
+            kind: None,
+        }];
+        assert_eq!(file_system_watchers(&globs, &[folder("app")], false), plain);
+        assert_eq!(file_system_watchers(&globs, &[], true), plain);
+    }
+
+    #[test]
+    fn test_exact_document_filter() {
+        let uri = Url::parse("file:///projects/app/deps.toml").unwrap();
+        let filter = exact_document_filter(&uri).unwrap();
+        assert_eq!(filter.scheme.as_deref(), Some("file"));
+        assert_eq!(filter.pattern.as_deref(), Some("/projects/app/deps.toml"));
+
+        let uri = Url::parse("untitled:Untitled-1").unwrap();
+        let filter = exact_document_filter(&uri).unwrap();
+        assert_eq!(filter.scheme.as_deref(), Some("untitled"));
+        assert_eq!(filter.pattern.as_deref(), Some("Untitled-1"));
+
+        let uri = Url::parse("file:///projects/%7Bapp%7D/deps.toml").unwrap();
+        assert_eq!(exact_document_filter(&uri), None);
+    }
+
+    #[tokio::test]
+    async fn test_workspace_folders_project_config() {
+        let server = TestServer::new(MockFetcher::new()).await;
+        let default_limits = server.config().get().limits;
+
+        let base = FixtureDir::new("folders-project-config");
+        base.write(".tooling-lsp.toml", "[limits]\nmaxDependencies = 1\n");
+        let folder = WorkspaceFolder {
+            uri: Url::from_file_path(&*base).unwrap(),
+            name: String::from("project"),
+        };
+        let change = |added: Vec<WorkspaceFolder>, removed: Vec<WorkspaceFolder>| {
+            DidChangeWorkspaceFoldersParams {
+                event: WorkspaceFoldersChangeEvent { added, removed },
+            }
+        };
+
+        // Folders that are added after initialization get their project config
+        server
+            .server()
+            .did_change_workspace_folders(change(vec![folder.clone()], Vec::new()))
+            .await;
+        assert_eq!(server.config().get().limits.max_dependencies, 1);
+
+        // ... which stops applying once the folder is removed again
+        server
+            .server()
+            .did_change_workspace_folders(change(Vec::new(), vec![folder]))
+            .await;
+        assert_eq!(server.config().get().limits, default_limits);
+    }
+}
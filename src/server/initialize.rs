@@ -1,4 +1,4 @@
-use tracing::{info, trace};
+use tracing::{debug, info, trace};
 
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
@@ -26,9 +26,47 @@ impl Server {
 
         log_client_info(&params);
 
+        // Parse any config that the client sent us, on top of any project config,
+        // and remember where it came from so that it can be reloaded later on
+        self.folders.set(workspace_folders(&params));
+        let roots = self.folders.roots();
+        let project_config = load_project_config(&roots).await;
+        let config =
+            Config::from_sources_lossy(project_config, params.initialization_options.as_ref());
+        self.settings.set_sources(
+            roots,
+            params.initialization_options.clone(),
+            &params.capabilities,
+        );
+        let features = config.features;
+        self.apply_config(config).await;
+
+        // Check which completion item fields the client can resolve lazily
+        let completion_resolve = CompletionResolveSupport::from_capabilities(&params.capabilities);
+        debug!("Using completion resolve support: {completion_resolve:?}");
+        self.config.set_completion_resolve(completion_resolve);
+        self.config
+            .set_completion_snippets(completion_snippet_support(&params.capabilities));
+        self.config
+            .set_hover_plaintext(hover_plaintext_preferred(&params.capabilities));
+        self.config
+            .set_code_action_edit_resolve(code_action_edit_resolve_support(&params.capabilities));
+        self.config
+            .set_package_manager_commands(package_manager_command_support(&params.capabilities));
+        self.config
+            .set_watched_files_registration(watched_files_registration_support(
+                &params.capabilities,
+            ));
+        self.config
+            .set_watched_files_relative_patterns(watched_files_relative_pattern_support(
+                &params.capabilities,
+            ));
+        self.config
+            .set_diagnostic_registration(diagnostic_registration_support(&params.capabilities));
+
         // Create completion provider parameters
         let completion_options = CompletionOptions {
-            resolve_provider: Some(false),
+            resolve_provider: Some(completion_resolve.any()),
             trigger_characters: Some(completion_trigger_characters()),
             ..Default::default()
         };
@@ -36,28 +74,31 @@ impl Server {
         // Create diagnostic registration parameters combined for all known tools
         let diagnostic_registration_options = DiagnosticRegistrationOptions {
             text_document_registration_options: TextDocumentRegistrationOptions {
+                // NOTE: Unsaved documents are also included, so that
+                // manifests get diagnostics before they are saved
                 document_selector: Some(
-                    Tools::file_globs()
+                    self.tools
+                        .file_globs()
                         .iter()
-                        .map(|&glob| DocumentFilter {
-                            scheme: Some(String::from("file")),
-                            pattern: Some(String::from(glob)),
-                            language: None,
+                        .flat_map(|&glob| {
+                            ["file", "untitled"].map(|scheme| DocumentFilter {
+                                scheme: Some(String::from(scheme)),
+                                pattern: Some(String::from(glob)),
+                                language: None,
+                            })
                         })
                         .collect(),
                 ),
             },
-            diagnostic_options: DiagnosticOptions {
-                inter_file_dependencies: true,
-                workspace_diagnostics: false,
-                ..Default::default()
-            },
+            diagnostic_options: diagnostic_options(),
             ..Default::default()
         };
 
         // Create similar options but for file operation notifications
         let file_operation_options = FileOperationRegistrationOptions {
-            filters: Tools::file_globs()
+            filters: self
+                .tools
+                .file_globs()
                 .iter()
                 .map(|&glob| FileOperationFilter {
                     scheme: Some(String::from("file")),
@@ -85,16 +126,55 @@ impl Server {
                     TextDocumentSyncOptions {
                         change: Some(TextDocumentSyncKind::INCREMENTAL),
                         open_close: Some(true),
+                        // NOTE: Always advertised, since `sortOnSave`
+                        // may be turned on after initialization
+                        will_save_wait_until: Some(true),
                         ..Default::default()
                     },
                 )),
-                hover_provider: Some(HoverProviderCapability::Simple(true)),
-                completion_provider: Some(completion_options),
-                code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
-                diagnostic_provider: Some(DiagnosticServerCapabilities::RegistrationOptions(
-                    diagnostic_registration_options,
-                )),
+                hover_provider: features
+                    .hover
+                    .then_some(HoverProviderCapability::Simple(true)),
+                definition_provider: features.definition.then_some(OneOf::Left(true)),
+                completion_provider: features.completion.then_some(completion_options),
+                code_action_provider: features.code_action.then_some(
+                    CodeActionProviderCapability::Options(CodeActionOptions {
+                        resolve_provider: Some(true),
+                        ..Default::default()
+                    }),
+                ),
+                document_highlight_provider: features
+                    .document_highlight
+                    .then_some(OneOf::Left(true)),
+                rename_provider: features.rename.then_some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: WorkDoneProgressOptions::default(),
+                })),
+                document_symbol_provider: features.document_symbol.then_some(OneOf::Left(true)),
+                inlay_hint_provider: features.inlay_hint.then_some(OneOf::Left(true)),
+                inline_value_provider: (features.inline_value
+                    && inline_value_support(&params.capabilities))
+                .then_some(OneOf::Left(true)),
+                linked_editing_range_provider: features
+                    .linked_editing_range
+                    .then_some(LinkedEditingRangeServerCapabilities::Simple(true)),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: vec![
+                        CLEAR_CACHE_COMMAND.to_string(),
+                        REFRESH_DEPENDENCY_COMMAND.to_string(),
+                    ],
+                    ..Default::default()
+                }),
+                diagnostic_provider: features.diagnostics.then_some(
+                    DiagnosticServerCapabilities::RegistrationOptions(
+                        diagnostic_registration_options,
+                    ),
+                ),
                 workspace: Some(WorkspaceServerCapabilities {
+                    workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                        supported: Some(true),
+                        change_notifications: Some(OneOf::Left(true)),
+                    }),
                     file_operations: Some(WorkspaceFileOperationsServerCapabilities {
                         did_create: Some(file_operation_options.clone()),
                         did_rename: Some(file_operation_options.clone()),
@@ -109,6 +189,34 @@ impl Server {
     }
 }
 
+pub(super) fn diagnostic_options() -> DiagnosticOptions {
+    DiagnosticOptions {
+        inter_file_dependencies: true,
+        workspace_diagnostics: false,
+        ..Default::default()
+    }
+}
+
+fn workspace_folders(params: &InitializeParams) -> Vec<WorkspaceFolder> {
+    if let Some(folders) = &params.workspace_folders {
+        return folders.clone();
+    }
+    // NOTE: Clients that only send a root get a single folder, named
+    // like the directory, the same way that editors name folders
+    #[allow(deprecated)]
+    let root = params.root_uri.as_ref();
+    root.map(|uri| WorkspaceFolder {
+        name: uri
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .unwrap_or_default()
+            .to_string(),
+        uri: uri.clone(),
+    })
+    .into_iter()
+    .collect()
+}
+
 fn log_client_info(params: &InitializeParams) {
     let num_folders = params
         .workspace_folders
@@ -3,16 +3,24 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use futures::future::join_all;
+use serde_json::Value as JsonValue;
 use tokio::fs;
 use tokio::time::timeout;
 use tower_lsp::jsonrpc::{Error, ErrorCode, Result};
 use tower_lsp::lsp_types::*;
 use tower_lsp::LanguageServer;
-use tracing::{trace, warn};
+use tracing::{debug, info, trace, warn};
 
 use crate::server::conversion::convert_to_utf8;
+use crate::server::folders::{exact_document_filter, file_system_watchers};
+use crate::server::initialize::diagnostic_options;
+use crate::server::requests::CLEAR_CACHE_COMMAND;
 use crate::server::{DocumentBuilder, Server};
-use crate::tools::{Tool, Tools};
+use crate::tools::{RefreshDependencyParams, Tool, ToolName, Tools, REFRESH_DEPENDENCY_COMMAND};
+use crate::util::Metric;
+
+const WATCHED_FILES_REGISTRATION_ID: &str = "tooling-language-server/didChangeWatchedFiles";
+const DIAGNOSTIC_REGISTRATION_ID: &str = "tooling-language-server/diagnostic";
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Server {
@@ -20,7 +28,24 @@ impl LanguageServer for Server {
         self.respond_to_initalize(params).await
     }
 
+    async fn initialized(&self, _: InitializedParams) {
+        self.watch_configuration().await;
+        self.watch_lockfiles().await;
+    }
+
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        self.reload_config(Some(params.settings)).await;
+    }
+
     async fn shutdown(&self) -> Result<()> {
+        // Cancel any in-flight requests and background tasks, and drop all
+        // documents - the client should not send us anything but an exit now
+        self.lifecycle.shut_down();
+        self.waiting.clear();
+        self.documents.clear();
+        if let Some(metrics) = self.clients.metrics().snapshot() {
+            info!("Usage metrics - {metrics}");
+        }
         Ok(())
     }
 
@@ -29,6 +54,17 @@ impl LanguageServer for Server {
         let version = params.text_document.version;
         let text = params.text_document.text.clone();
 
+        // Documents that no tool recognizes, such as an `untitled:Untitled-1`
+        // buffer that has no file name yet, are not tracked at all, since
+        // there is no way to know which language or tool they belong to,
+        // unless the first line of the document has a modeline for a tool.
+        // Documents of disabled tools are also ignored, just like these.
+        let tool = ToolName::from_modeline(&text).map_or_else(|| ToolName::from_uri(&uri), Ok);
+        if !tool.is_ok_and(|t| self.tools.is_enabled(t)) {
+            trace!("Ignoring unrecognized document: {uri}");
+            return;
+        }
+
         let documents = Arc::clone(&self.documents);
         let waiting = self.waiting.clone();
 
@@ -49,9 +85,19 @@ impl LanguageServer for Server {
                     .build()
             });
         waiting.trigger(uri.clone());
+        self.file_changed(&uri).await;
+
+        // Documents with a modeline are not covered by the file name
+        // patterns that diagnostics were registered for when initializing
+        if ToolName::from_uri(&uri).is_err() {
+            self.register_document_diagnostics(&uri).await;
+        }
 
         // If we have any relevant files, try to read those too right away
-        let relevant_uris = Tools::relevant_file_uris(&uri)
+        let relevant_uris = self
+            .tools
+            .relevant_file_uris(&uri)
+            .await
             .into_iter()
             .filter(|u| !documents.contains_key(u))
             .collect::<Vec<_>>();
@@ -100,10 +146,13 @@ impl LanguageServer for Server {
         let uri = params.text_document.uri.clone();
 
         let documents = Arc::clone(&self.documents);
-        let mut document = documents
-            .get_mut(&uri)
-            .expect("Got close event for nonexistent document");
+        let Some(mut document) = documents.get_mut(&uri) else {
+            trace!("Ignoring close event for untracked document: {uri}");
+            return;
+        };
         document.set_opened(false);
+        drop(document);
+        self.unregister_document_diagnostics(&uri).await;
 
         trace!("File closed: {uri}");
     }
@@ -113,14 +162,17 @@ impl LanguageServer for Server {
         let version = params.text_document.version;
 
         let documents = Arc::clone(&self.documents);
-        let mut document = documents
-            .get_mut(&uri)
-            .expect("Got change event for nonexistent document");
+        let Some(mut document) = documents.get_mut(&uri) else {
+            trace!("Ignoring change event for untracked document: {uri}");
+            return;
+        };
 
         document.set_version(version);
         for change in params.content_changes {
             document.apply_change(change);
         }
+        drop(document);
+        self.file_changed(&uri).await;
 
         trace!("File changed: {uri}");
     }
@@ -132,6 +184,7 @@ impl LanguageServer for Server {
             // NOTE: We intentionally don't read and insert a document here,
             // it is not provided directly in the create files params, and
             // we might as well do it lazily when a file is opened instead
+            self.file_changed(&new).await;
             trace!("File created: {new}");
         }
     }
@@ -143,6 +196,8 @@ impl LanguageServer for Server {
                 .expect("Got invalid file path in rename notification");
             let new = Url::parse(rename.new_uri.as_str())
                 .expect("Got invalid file path in rename notification");
+            self.file_changed(&old).await;
+            self.file_changed(&new).await;
             if let Some((_, old_doc)) = documents.remove(&old) {
                 trace!("File renamed: {old} -> {new}");
                 documents.insert(new, old_doc);
@@ -158,27 +213,76 @@ impl LanguageServer for Server {
             let old = Url::parse(delete.uri.as_str())
                 .expect("Got invalid file path in delete notification");
             documents.remove(&old);
+            self.file_changed(&old).await;
             trace!("File deleted: {old}");
         }
     }
 
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+        self.folders.apply(params.event);
+        trace!("Workspace folders changed: {:?}", self.folders.get());
+
+        // Added folders may have a project config of their own,
+        // and the project configs of removed folders no longer apply
+        self.settings.set_roots(self.folders.roots());
+        self.reload_config(None).await;
+
+        // Plain globs match in every folder, so only relative patterns
+        // need to be registered again, to follow the new folders
+        if self.config.watched_files_relative_patterns() {
+            self.unwatch_lockfiles().await;
+            self.watch_lockfiles().await;
+        }
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        for change in params.changes {
+            // NOTE: Only lockfiles and tool configs are watched, and changes to
+            // them are usually made by tools, not by users, so documents are not
+            // read again here, they get updated when they are next opened
+            self.file_changed(&change.uri).await;
+            trace!("Watched file changed: {} ({:?})", change.uri, change.typ);
+        }
+    }
+
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
         let uri = &params.text_document_position_params.text_document.uri;
         self.wait_if_nonexistent_or_timeout(uri).await?;
-        self.tools.hover(params).await
+        self.clients.metrics().record(Metric::Hover);
+        self.lifecycle
+            .until_shut_down(self.tools.hover(params))
+            .await
+    }
+
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        self.wait_if_nonexistent_or_timeout(uri).await?;
+        self.lifecycle
+            .until_shut_down(self.tools.goto_definition(params))
+            .await
     }
 
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
         let uri = &params.text_document_position.text_document.uri;
         self.wait_if_nonexistent_or_timeout(uri).await?;
-        match self.tools.completion(params).await {
+        self.clients.metrics().record(Metric::Completion);
+        match self
+            .lifecycle
+            .until_shut_down(self.tools.completion(params))
+            .await
+        {
             Err(e) => Err(e),
             Ok(r) => Ok(Some(r)),
         }
     }
 
     async fn completion_resolve(&self, item: CompletionItem) -> Result<CompletionItem> {
-        self.tools.completion_resolve(item).await
+        self.lifecycle
+            .until_shut_down(self.tools.completion_resolve(item))
+            .await
     }
 
     async fn diagnostic(
@@ -187,7 +291,12 @@ impl LanguageServer for Server {
     ) -> Result<DocumentDiagnosticReportResult> {
         let uri = &params.text_document.uri;
         self.wait_if_nonexistent_or_timeout(uri).await?;
-        match self.tools.diagnostics(params).await {
+        self.clients.metrics().record(Metric::Diagnostics);
+        match self
+            .lifecycle
+            .until_shut_down(self.tools.diagnostics(params))
+            .await
+        {
             Err(e) => Err(e),
             Ok(v) => Ok(DocumentDiagnosticReportResult::Report(
                 DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport {
@@ -201,11 +310,118 @@ impl LanguageServer for Server {
         }
     }
 
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Option<Vec<DocumentHighlight>>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        self.wait_if_nonexistent_or_timeout(uri).await?;
+        let highlights = self
+            .lifecycle
+            .until_shut_down(self.tools.document_highlight(params))
+            .await?;
+        if highlights.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(highlights))
+        }
+    }
+
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        self.wait_if_nonexistent_or_timeout(&params.text_document.uri)
+            .await?;
+        self.lifecycle
+            .until_shut_down(self.tools.prepare_rename(params))
+            .await
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let uri = &params.text_document_position.text_document.uri;
+        self.wait_if_nonexistent_or_timeout(uri).await?;
+        self.lifecycle
+            .until_shut_down(self.tools.rename(params))
+            .await
+    }
+
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        let uri = &params.text_document.uri;
+        self.wait_if_nonexistent_or_timeout(uri).await?;
+        let symbols = self
+            .lifecycle
+            .until_shut_down(self.tools.document_symbols(params))
+            .await?;
+        if symbols.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(DocumentSymbolResponse::Nested(symbols)))
+        }
+    }
+
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        let uri = &params.text_document.uri;
+        self.wait_if_nonexistent_or_timeout(uri).await?;
+        let hints = self
+            .lifecycle
+            .until_shut_down(self.tools.inlay_hints(params))
+            .await?;
+        if hints.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(hints))
+        }
+    }
+
+    async fn inline_value(&self, params: InlineValueParams) -> Result<Option<Vec<InlineValue>>> {
+        let uri = &params.text_document.uri;
+        self.wait_if_nonexistent_or_timeout(uri).await?;
+        let values = self
+            .lifecycle
+            .until_shut_down(self.tools.inline_values(params))
+            .await?;
+        if values.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(values))
+        }
+    }
+
+    async fn linked_editing_range(
+        &self,
+        params: LinkedEditingRangeParams,
+    ) -> Result<Option<LinkedEditingRanges>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        self.wait_if_nonexistent_or_timeout(uri).await?;
+        self.lifecycle
+            .until_shut_down(self.tools.linked_editing_range(params))
+            .await
+    }
+
+    async fn will_save_wait_until(
+        &self,
+        params: WillSaveTextDocumentParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        // NOTE: The editor is blocked until this returns, so
+        // never wait for a document that is not open yet
+        self.lifecycle
+            .until_shut_down(self.tools.will_save_wait_until(params))
+            .await
+    }
+
     async fn code_action(
         &self,
         params: CodeActionParams,
     ) -> Result<Option<Vec<CodeActionOrCommand>>> {
-        match self.tools.code_action(params).await {
+        match self
+            .lifecycle
+            .until_shut_down(self.tools.code_action(params))
+            .await
+        {
             Err(e) => Err(e),
             Ok(v) => {
                 if v.is_empty() {
@@ -218,12 +434,162 @@ impl LanguageServer for Server {
     }
 
     async fn code_action_resolve(&self, action: CodeAction) -> Result<CodeAction> {
-        self.tools.code_action_resolve(action).await
+        self.lifecycle
+            .until_shut_down(self.tools.code_action_resolve(action))
+            .await
+    }
+
+    async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<JsonValue>> {
+        match params.command.as_str() {
+            CLEAR_CACHE_COMMAND => {
+                self.clear_cache().await?;
+                Ok(None)
+            }
+            REFRESH_DEPENDENCY_COMMAND => {
+                let params = params
+                    .arguments
+                    .into_iter()
+                    .next()
+                    .map(serde_json::from_value::<RefreshDependencyParams>)
+                    .and_then(|result| result.ok())
+                    .ok_or_else(|| {
+                        Error::invalid_params(format!(
+                            "Command '{REFRESH_DEPENDENCY_COMMAND}' needs a dependency to refresh"
+                        ))
+                    })?;
+                self.refresh_dependency(params).await?;
+                Ok(None)
+            }
+            command => Err(Error::invalid_params(format!(
+                "Unknown command '{command}'"
+            ))),
+        }
     }
 }
 
 impl Server {
-    async fn wait_if_nonexistent_or_timeout(&self, uri: &Url) -> Result<()> {
+    /**
+        Lets tools know that a file has changed, and clears cached diagnostics for
+        any documents that depend on it, such as a manifest whose lockfile changed.
+    */
+    async fn file_changed(&self, uri: &Url) {
+        self.tools.file_changed(uri);
+        // Documents are not kept locked while looking for relevant files
+        let doc_uris = self
+            .documents
+            .iter()
+            .map(|doc| doc.uri().clone())
+            .collect::<Vec<_>>();
+        for doc_uri in doc_uris {
+            if !self.tools.relevant_file_uris(&doc_uri).await.contains(uri) {
+                continue;
+            }
+            if let Some(doc) = self.documents.get(&doc_uri) {
+                doc.diagnostics_cache().clear();
+            }
+        }
+    }
+
+    /**
+        Registers for changes to lockfiles that happen outside of the client,
+        such as when updating dependencies, so that cached versions can be
+        refreshed right when they are expected to have changed. Config files
+        of tools, such as Cargo's `.cargo/config.toml`, are watched as well.
+
+        Lockfiles are watched relative to each workspace folder if the client
+        supports it, which keeps working when folders are added or renamed.
+    */
+    async fn watch_lockfiles(&self) {
+        if !self.config.watched_files_registration() {
+            return;
+        }
+        let watchers = file_system_watchers(
+            &self.tools.watched_file_globs(),
+            &self.folders.get(),
+            self.config.watched_files_relative_patterns(),
+        );
+        let registration = Registration {
+            id: String::from(WATCHED_FILES_REGISTRATION_ID),
+            method: String::from("workspace/didChangeWatchedFiles"),
+            register_options: serde_json::to_value(DidChangeWatchedFilesRegistrationOptions {
+                watchers,
+            })
+            .ok(),
+        };
+        if let Err(e) = self.client.register_capability(vec![registration]).await {
+            warn!("Failed to register for lockfile changes - {e}");
+        }
+    }
+
+    async fn unwatch_lockfiles(&self) {
+        if !self.config.watched_files_registration() {
+            return;
+        }
+        let unregistration = Unregistration {
+            id: String::from(WATCHED_FILES_REGISTRATION_ID),
+            method: String::from("workspace/didChangeWatchedFiles"),
+        };
+        if let Err(e) = self
+            .client
+            .unregister_capability(vec![unregistration])
+            .await
+        {
+            warn!("Failed to unregister for lockfile changes - {e}");
+        }
+    }
+
+    /**
+        Registers for diagnostics of a single document that is only recognized
+        by its modeline, since clients only pull diagnostics for documents that
+        match the document selector, which is made from file names of manifests.
+    */
+    async fn register_document_diagnostics(&self, uri: &Url) {
+        if !self.config.diagnostic_registration() || !self.config.get().features.diagnostics {
+            return;
+        }
+        let Some(filter) = exact_document_filter(uri) else {
+            debug!("Document can not be matched exactly, skipping diagnostics: {uri}");
+            return;
+        };
+        if !self.diagnostic_registrations.insert(uri.clone()) {
+            return;
+        }
+        let registration = Registration {
+            id: format!("{DIAGNOSTIC_REGISTRATION_ID}:{uri}"),
+            method: String::from("textDocument/diagnostic"),
+            register_options: serde_json::to_value(DiagnosticRegistrationOptions {
+                text_document_registration_options: TextDocumentRegistrationOptions {
+                    document_selector: Some(vec![filter]),
+                },
+                diagnostic_options: diagnostic_options(),
+                ..Default::default()
+            })
+            .ok(),
+        };
+        if let Err(e) = self.client.register_capability(vec![registration]).await {
+            warn!("Failed to register for diagnostics of '{uri}' - {e}");
+            self.diagnostic_registrations.remove(uri);
+        }
+    }
+
+    async fn unregister_document_diagnostics(&self, uri: &Url) {
+        if self.diagnostic_registrations.remove(uri).is_none() {
+            return;
+        }
+        let unregistration = Unregistration {
+            id: format!("{DIAGNOSTIC_REGISTRATION_ID}:{uri}"),
+            method: String::from("textDocument/diagnostic"),
+        };
+        if let Err(e) = self
+            .client
+            .unregister_capability(vec![unregistration])
+            .await
+        {
+            warn!("Failed to unregister for diagnostics of '{uri}' - {e}");
+        }
+    }
+
+    pub(super) async fn wait_if_nonexistent_or_timeout(&self, uri: &Url) -> Result<()> {
         // HACK: Sometimes we receive a notification or request for diagnostics
         // or something similar before the file has been opened, so we need to
         // first wait for it to open and register with the language server
@@ -245,3 +611,36 @@ impl Server {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::LanguageServer;
+
+    use crate::server::testing::{MockFetcher, TestServer};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_untitled_without_file_name() {
+        let server = TestServer::new(MockFetcher::new()).await;
+
+        // Unrecognized documents are ignored, and so are any changes to them
+        let uri = Url::parse("untitled:Untitled-1").unwrap();
+        let uri = server.open_uri(uri, "[dependencies]\n").await;
+        server
+            .server()
+            .did_change(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier {
+                    uri: uri.clone(),
+                    version: 2,
+                },
+                content_changes: vec![TextDocumentContentChangeEvent {
+                    range: None,
+                    range_length: None,
+                    text: String::from("[dependencies]\nserde = \"1\"\n"),
+                }],
+            })
+            .await;
+        assert!(!server.documents().contains_key(&uri));
+    }
+}
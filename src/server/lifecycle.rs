@@ -0,0 +1,130 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use futures::Future;
+use tokio::select;
+use tokio::sync::Notify;
+use tower_lsp::jsonrpc::{Error, ErrorCode, Result};
+
+/**
+    Tracks if the client has requested the server to shut down, so that
+    any in-flight work can be cancelled, and so that the server exits
+    with the conventional exit code once the client asks it to exit.
+
+    This is cheaply cloneable, and all clones share the same state.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Lifecycle {
+    shut_down: Arc<AtomicBool>,
+    notify: Arc<Notify>,
+}
+
+impl Lifecycle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+        Marks the server as shut down, and cancels any in-flight work.
+    */
+    pub fn shut_down(&self) {
+        self.shut_down.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.shut_down.load(Ordering::SeqCst)
+    }
+
+    /**
+        The exit code for when the client asks the server to exit,
+        which is `0` if it asked to shut down first, and `1` otherwise.
+    */
+    pub fn exit_code(&self) -> i32 {
+        if self.is_shut_down() {
+            0
+        } else {
+            1
+        }
+    }
+
+    /**
+        Waits until the server has been shut down.
+    */
+    pub async fn cancelled(&self) {
+        // Waiters are registered as soon as the future is created,
+        // so there is no race between the check and notification
+        let notified = self.notify.notified();
+        if self.is_shut_down() {
+            return;
+        }
+        notified.await;
+    }
+
+    /**
+        Runs the given request future, unless the server shuts down before it
+        completes, in which case the future is dropped and the request cancelled.
+    */
+    pub async fn until_shut_down<T>(&self, fut: impl Future<Output = Result<T>>) -> Result<T> {
+        select! {
+            result = fut => result,
+            () = self.cancelled() => Err(Error::new(ErrorCode::RequestCancelled)),
+        }
+    }
+
+    /**
+        Spawns a background task that gets dropped once the server shuts down.
+    */
+    pub fn spawn(&self, fut: impl Future<Output = ()> + Send + 'static) {
+        let lifecycle = self.clone();
+        tokio::spawn(async move {
+            select! {
+                () = fut => {},
+                () = lifecycle.cancelled() => {},
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::future::pending;
+    use tokio::time::timeout;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_exit_without_shutdown() {
+        let lifecycle = Lifecycle::new();
+        assert!(!lifecycle.is_shut_down());
+        assert_eq!(lifecycle.exit_code(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_then_exit() {
+        let lifecycle = Lifecycle::new();
+
+        // In-flight requests are cancelled as soon as shutdown is requested
+        let in_flight = tokio::spawn({
+            let lifecycle = lifecycle.clone();
+            async move { lifecycle.until_shut_down(pending::<Result<()>>()).await }
+        });
+        tokio::task::yield_now().await;
+        lifecycle.shut_down();
+
+        let result = timeout(Duration::from_secs(1), in_flight)
+            .await
+            .expect("in-flight request should be cancelled")
+            .unwrap();
+        assert_eq!(result.unwrap_err().code, ErrorCode::RequestCancelled);
+
+        // Requests after shutdown never run at all, and then exit succeeds
+        let late = lifecycle.until_shut_down(pending::<Result<()>>());
+        assert!(timeout(Duration::from_secs(1), late).await.is_ok());
+        assert_eq!(lifecycle.exit_code(), 0);
+    }
+}
@@ -3,42 +3,63 @@ use std::ops::DerefMut;
 use std::sync::Arc;
 
 use anyhow::Result;
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
+use tracing::debug;
 
-use tower_lsp::{Client, LspService, Server as LspServer};
+use tower_lsp::{lsp_types::Url, Client, LspService, Server as LspServer};
 
 use crate::clients::*;
 use crate::tools::*;
 
+mod config;
 mod conversion;
+mod diagnostics_cache;
 mod document;
+mod folders;
 mod initialize;
 mod language_server;
+mod lifecycle;
 mod requests;
+mod settings;
+#[cfg(test)]
+pub(crate) mod testing;
 mod transport;
 mod waiting;
 
+use folders::*;
+use lifecycle::*;
+use requests::CLEAR_CACHE_COMMAND;
 use waiting::*;
 
+pub use config::*;
+pub use diagnostics_cache::*;
 pub use document::*;
+pub use settings::*;
 pub use transport::*;
 
 #[derive(Debug, Clone)]
 pub struct ServerArguments {
     pub transport: Transport,
     pub github_token: Option<String>,
+    pub metrics: bool,
+    pub tools: Vec<ToolName>,
 }
 
 pub struct ServerInner {
     client: Client,
     clients: Clients,
+    config: SharedConfig,
     documents: Documents,
+    diagnostic_registrations: DashSet<Url>,
+    folders: WorkspaceFolders,
+    settings: SettingsSources,
     tools: Tools,
     waiting: Waiting,
 }
 
 pub struct Server {
     args: ServerArguments,
+    lifecycle: Lifecycle,
     inner: Option<ServerInner>,
 }
 
@@ -57,32 +78,61 @@ impl DerefMut for Server {
 
 impl Server {
     pub fn new(args: ServerArguments) -> Self {
-        Self { args, inner: None }
+        Self {
+            args,
+            lifecycle: Lifecycle::new(),
+            inner: None,
+        }
+    }
+
+    fn with_client(self, client: Client) -> Self {
+        self.with_clients(client, Clients::new())
     }
 
-    fn with_client(mut self, client: Client) -> Self {
-        let clients = Clients::new();
+    fn with_clients(mut self, client: Client, clients: Clients) -> Self {
+        let config = SharedConfig::default();
         let documents = Arc::new(DashMap::new());
 
         if let Some(token) = &self.args.github_token {
             clients.github.set_auth_token(token);
         }
 
+        if self.args.metrics {
+            clients.metrics().enable();
+        }
+
         self.inner.replace(ServerInner {
             client: client.clone(),
             clients: clients.clone(),
+            config: config.clone(),
             documents: Arc::clone(&documents),
-            tools: Tools::new(client, clients, documents),
+            diagnostic_registrations: DashSet::new(),
+            folders: WorkspaceFolders::default(),
+            settings: SettingsSources::default(),
+            tools: Tools::new(client, clients, config, documents, self.args.tools.clone()),
             waiting: Waiting::new(),
         });
 
         self.watch_rate_limit();
+        self.watch_contacts();
         self
     }
 
-    pub async fn serve(self) -> Result<()> {
-        // FUTURE: Add custom notifications here by calling custom_method
-        let (service, socket) = LspService::build(|client| self.with_client(client)).finish();
+    /**
+        Serves the language server until the client asks it to exit, and
+        returns the exit code that the process should then exit with.
+    */
+    pub async fn serve(self) -> Result<i32> {
+        let lifecycle = self.lifecycle.clone();
+
+        // FUTURE: Add more custom notifications here by calling custom_method
+        let (service, socket) = LspService::build(|client| self.with_client(client))
+            .custom_method("tooling/analyze", Server::analyze)
+            .custom_method("tooling/manifestInfo", Server::manifest_info)
+            .custom_method("tooling/status", Server::status)
+            .custom_method("tooling/version", Server::version)
+            .custom_method(CLEAR_CACHE_COMMAND, Server::clear_cache)
+            .finish();
 
         match service.inner().args.transport {
             Transport::Socket(port) => {
@@ -95,6 +145,93 @@ impl Server {
             }
         }
 
-        Ok(())
+        let code = lifecycle.exit_code();
+        debug!("Exiting with code {code}");
+        Ok(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::server::testing::MockFetcher;
+    use crate::util::Fetcher;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_shutdown_then_exit() {
+        use std::time::Duration;
+
+        use serde_json::Value;
+        use tokio::io::{duplex, split, AsyncReadExt, AsyncWriteExt};
+        use tokio::time::timeout;
+
+        let args = ServerArguments {
+            transport: Transport::Stdio,
+            github_token: None,
+            metrics: false,
+            tools: ToolName::all(),
+        };
+        let server = Server::new(args);
+        let lifecycle = server.lifecycle.clone();
+        let clients = Clients::with_fetcher(Fetcher::new(MockFetcher::new()));
+        let (service, socket) = LspService::new(move |client| server.with_clients(client, clients));
+
+        // Serve over an in-memory stream, the same way that stdio is served
+        let (client_io, server_io) = duplex(1 << 16);
+        let (server_read, server_write) = split(server_io);
+        let serving =
+            tokio::spawn(LspServer::new(server_read, server_write, socket).serve(service));
+        let (mut client_read, mut client_write) = split(client_io);
+        let output = tokio::spawn(async move {
+            let mut output = String::new();
+            client_read.read_to_string(&mut output).await.unwrap();
+            output
+        });
+
+        let messages = [
+            json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": {
+                    "capabilities": {},
+                    "initializationOptions": { "useSystemCredentials": false },
+                },
+            }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "shutdown" }),
+            json!({ "jsonrpc": "2.0", "method": "exit" }),
+        ];
+        for message in messages {
+            let body = message.to_string();
+            let frame = format!("Content-Length: {}\r\n\r\n{body}", body.len());
+            client_write.write_all(frame.as_bytes()).await.unwrap();
+        }
+
+        // Clients close the stream once they have sent the exit notification
+        client_write.shutdown().await.unwrap();
+        timeout(Duration::from_secs(5), serving)
+            .await
+            .expect("server should stop serving after exit")
+            .unwrap();
+
+        let output = output.await.unwrap();
+        let responses = output
+            .split("Content-Length: ")
+            .filter_map(|frame| frame.split_once("\r\n\r\n"))
+            .map(|(_, body)| serde_json::from_str::<Value>(body).unwrap())
+            .collect::<Vec<_>>();
+        let shutdown = responses
+            .iter()
+            .find(|response| response["id"] == 2)
+            .expect("missing shutdown response");
+        assert_eq!(shutdown.get("error"), None);
+        assert_eq!(shutdown["result"], Value::Null);
+
+        // Exiting after a shutdown is the only way to exit successfully
+        assert!(lifecycle.is_shut_down());
+        assert_eq!(lifecycle.exit_code(), 0);
     }
 }
@@ -3,9 +3,18 @@ use std::time::Duration;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use tokio::time::sleep;
-use tracing::{debug, trace};
+use tracing::{debug, trace, warn};
 
+use tower_lsp::jsonrpc::Result as LspResult;
 use tower_lsp::lsp_types::request::Request;
+use tower_lsp::lsp_types::MessageType;
+
+use crate::clients::github::GithubClient;
+use crate::clients::ContactStates;
+use crate::tools::{
+    AnalyzeParams, AnalyzeResult, ManifestInfo, ManifestInfoParams, RefreshDependencyParams, Tool,
+};
+use crate::util::MetricsSnapshot;
 
 use super::Server;
 
@@ -51,20 +60,135 @@ impl RateLimitResponse {
     }
 }
 
+/**
+    Response for the custom `tooling/status` request.
+
+    Metrics are only included if the server was started with `--metrics`.
+*/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusResponse {
+    pub version: String,
+    pub open_documents: usize,
+    pub metrics: Option<MetricsSnapshot>,
+}
+
+/**
+    Response for the custom `tooling/version` request.
+
+    Lets extensions check that they are compatible with the running language server.
+*/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionResponse {
+    pub name: String,
+    pub version: String,
+}
+
+pub const CLEAR_CACHE_COMMAND: &str = "tooling/clearCache";
+
 impl Server {
+    /**
+        Clears all caches and asks the client to refresh diagnostics for open documents.
+
+        Available both as the custom `tooling/clearCache` request,
+        and as a command using `workspace/executeCommand`.
+    */
+    pub async fn clear_cache(&self) -> LspResult<()> {
+        self.clients.invalidate_caches();
+        for doc in self.documents.iter() {
+            doc.diagnostics_cache().clear();
+        }
+        if let Err(e) = self.client.workspace_diagnostic_refresh().await {
+            warn!("Failed to refresh diagnostics after clearing cache - {e}");
+        }
+        Ok(())
+    }
+
+    /**
+        Clears cached data for a single dependency, and asks the client to refresh
+        diagnostics, which are fetched again only for the document of the dependency.
+
+        Available as a command using `workspace/executeCommand`, which
+        is what the "Refresh version info" code action runs.
+    */
+    pub async fn refresh_dependency(&self, params: RefreshDependencyParams) -> LspResult<()> {
+        debug!("Refreshing dependency: {:?}", params.dependency);
+        self.clients.invalidate_dependency(&params.dependency).await;
+        if let Some(doc) = self.documents.get(&params.text_document.uri) {
+            doc.diagnostics_cache().clear();
+        }
+        if let Err(e) = self.client.workspace_diagnostic_refresh().await {
+            warn!("Failed to refresh diagnostics after refreshing dependency - {e}");
+        }
+        Ok(())
+    }
+
+    pub async fn version(&self) -> LspResult<VersionResponse> {
+        Ok(VersionResponse {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        })
+    }
+
+    pub async fn status(&self) -> LspResult<StatusResponse> {
+        Ok(StatusResponse {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            open_documents: self.documents.iter().filter(|d| d.is_opened()).count(),
+            metrics: self.clients.metrics().snapshot(),
+        })
+    }
+
+    pub async fn manifest_info(
+        &self,
+        params: ManifestInfoParams,
+    ) -> LspResult<Option<ManifestInfo>> {
+        let uri = &params.text_document.uri;
+        self.wait_if_nonexistent_or_timeout(uri).await?;
+        self.tools.manifest_info(params).await
+    }
+
+    pub async fn analyze(&self, params: AnalyzeParams) -> LspResult<Option<AnalyzeResult>> {
+        let uri = &params.text_document.uri;
+        self.wait_if_nonexistent_or_timeout(uri).await?;
+        self.tools.analyze(params).await
+    }
+
     pub fn watch_rate_limit(&self) {
         let client = self.client.clone();
-        let github = self.clients.github.clone();
-        tokio::spawn(async move {
+        let clients = self.clients.clone();
+        let documents = self.documents.clone();
+        self.lifecycle.spawn(async move {
+            let github = clients.github.clone();
+            let mut rokit_limited = false;
             loop {
                 sleep(Duration::from_secs(2)).await;
                 trace!("Checking rate limits");
+
+                // NOTE: Tokens for github.com are never valid for a custom API url,
+                // so users can only be told about limits of the Rokit client,
+                // and only once until it is created again or the limit is gone
+                let rokit = clients.custom_rokit();
+                let limited = rokit.as_ref().is_some_and(GithubClient::is_rate_limited);
+                if let Some(rokit) = rokit.filter(|_| limited && !rokit_limited) {
+                    let message = format!(
+                        "Rate limited by {}; Rokit tool versions will be limited",
+                        rokit.web_url()
+                    );
+                    client.show_message(MessageType::WARNING, message).await;
+                }
+                rokit_limited = limited;
+
                 if github.is_rate_limited() {
                     let notif = RateLimitRequest::github();
                     let response = client.send_request::<RateLimitRequest>(notif).await;
                     if let Ok(res) = response {
                         if let Some(token) = res.value_string() {
                             github.set_auth_token(token);
+                            // Diagnostics computed while rate limited are incomplete
+                            for doc in documents.iter() {
+                                doc.diagnostics_cache().clear();
+                            }
                             client
                                 .workspace_diagnostic_refresh()
                                 .await
@@ -79,4 +203,64 @@ impl Server {
             }
         });
     }
+
+    /**
+        Lets the user know if the first request to each source succeeded,
+        since networking failures would otherwise go entirely unnoticed.
+    */
+    pub fn watch_contacts(&self) {
+        let client = self.client.clone();
+        let contacts = self.clients.contacts();
+        self.lifecycle.spawn(async move {
+            let mut states = ContactStates::default();
+            while let Ok(contact) = contacts.recv().await {
+                if !states.update(contact) {
+                    continue;
+                }
+                if contact.reached {
+                    let message = format!("Connected to {}", contact.source);
+                    client.show_message(MessageType::INFO, message).await;
+                } else {
+                    let message = format!(
+                        "Could not reach {}; completions will be limited",
+                        contact.source
+                    );
+                    client.show_message(MessageType::WARNING, message).await;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::testing::{cursor_fixture, MockFetcher, TestServer, SERDE_INDEX};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_metrics_per_server() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::new(fetcher.clone()).await;
+        let other = TestServer::new(fetcher).await;
+        server.server().clients.metrics().enable();
+
+        let (contents, pos) = cursor_fixture("[dependencies]\nser$0de = \"1.0\"\n");
+        let uri = server.open("Cargo.toml", &contents).await;
+        server.hover(&uri, pos).await.expect("missing hover");
+        server.hover(&uri, pos).await.expect("missing hover");
+        let other_uri = other.open("Cargo.toml", &contents).await;
+        other.hover(&other_uri, pos).await.expect("missing hover");
+
+        // The second hover is served from the cache that the first one filled
+        let status = server.server().status().await.unwrap();
+        let metrics = status.metrics.expect("metrics should be enabled");
+        assert_eq!(metrics.hovers, 2);
+        assert!(metrics.cache_misses >= 1);
+        assert!(metrics.cache_hits >= 1);
+        assert!(metrics.cache_hit_ratio.is_some_and(|ratio| ratio > 0.0));
+
+        let other_status = other.server().status().await.unwrap();
+        assert_eq!(other_status.metrics, None);
+    }
 }
@@ -0,0 +1,241 @@
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use serde_json::Value as JsonValue;
+use tower_lsp::lsp_types::*;
+use tracing::{debug, warn};
+
+use crate::clients::SystemCredentials;
+
+use super::config::merge_json;
+use super::{
+    configuration_change_registration_support, configuration_pull_support, load_project_config,
+    Config, Server,
+};
+
+/**
+    The section of the client settings that contains our config,
+    such as `"tooling-language-server": { ... }` in VSCode settings.
+*/
+pub const SETTINGS_SECTION: &str = "tooling-language-server";
+
+/**
+    Everything needed to compute the config again after initialization,
+    which is cheaply cloneable, with all clones sharing the same state.
+
+    The config is computed from each of these, in order of precedence:
+
+    1. Settings sent by the client using `workspace/didChangeConfiguration`,
+       or pulled from the client using `workspace/configuration`
+    2. Initialization options sent by the client using `initialize`
+    3. Project config files at the root of each workspace folder
+
+    Workspace folders may change after initialization, and the roots
+    are then updated, so that project config files follow the folders.
+*/
+#[derive(Debug, Default, Clone)]
+pub struct SettingsSources {
+    inner: Arc<RwLock<SettingsSourcesInner>>,
+}
+
+#[derive(Debug, Default)]
+struct SettingsSourcesInner {
+    roots: Vec<PathBuf>,
+    initialization_options: Option<JsonValue>,
+    settings: Option<JsonValue>,
+    pull_support: bool,
+    registration_support: bool,
+}
+
+impl SettingsSources {
+    pub fn set_sources(
+        &self,
+        roots: Vec<PathBuf>,
+        initialization_options: Option<JsonValue>,
+        capabilities: &ClientCapabilities,
+    ) {
+        *self.inner.write().expect("settings lock was poisoned") = SettingsSourcesInner {
+            roots,
+            initialization_options,
+            settings: None,
+            pull_support: configuration_pull_support(capabilities),
+            registration_support: configuration_change_registration_support(capabilities),
+        };
+    }
+
+    pub fn set_roots(&self, roots: Vec<PathBuf>) {
+        self.inner
+            .write()
+            .expect("settings lock was poisoned")
+            .roots = roots;
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        self.inner
+            .read()
+            .expect("settings lock was poisoned")
+            .roots
+            .clone()
+    }
+
+    fn initialization_options(&self) -> Option<JsonValue> {
+        self.inner
+            .read()
+            .expect("settings lock was poisoned")
+            .initialization_options
+            .clone()
+    }
+
+    /**
+        The settings that the client last sent, used when the config is
+        computed again for any other reason, for clients that can't be
+        asked for their settings using `workspace/configuration`.
+    */
+    fn settings(&self) -> Option<JsonValue> {
+        self.inner
+            .read()
+            .expect("settings lock was poisoned")
+            .settings
+            .clone()
+    }
+
+    fn set_settings(&self, settings: JsonValue) {
+        self.inner
+            .write()
+            .expect("settings lock was poisoned")
+            .settings = Some(settings);
+    }
+
+    fn pull_support(&self) -> bool {
+        self.inner
+            .read()
+            .expect("settings lock was poisoned")
+            .pull_support
+    }
+
+    fn registration_support(&self) -> bool {
+        self.inner
+            .read()
+            .expect("settings lock was poisoned")
+            .registration_support
+    }
+}
+
+impl Server {
+    /**
+        Applies a config, updating clients for anything that they depend on.
+
+        Note that features are only advertised to the client during
+        initialization, so enabling a feature that was disabled before
+        only takes effect after a restart - disabling one always works.
+    */
+    pub(super) async fn apply_config(&self, config: Config) {
+        debug!("Using config: {config:#?}");
+        self.clients
+            .set_max_concurrent_requests(config.network.max_concurrent_requests);
+        if config.use_system_credentials {
+            let credentials = SystemCredentials::load().await;
+            debug!("Loaded system credentials: {credentials:?}");
+            self.clients.set_system_credentials(credentials);
+        }
+        if let Err(e) = self
+            .clients
+            .set_rokit_api_url(config.rokit.github_api_url.as_deref())
+        {
+            warn!("Invalid GitHub api url for Rokit, using github.com - {e}");
+            self.clients.set_rokit_api_url(None).ok();
+            self.client
+                .show_message(
+                    MessageType::WARNING,
+                    format!("Invalid `rokit.githubApiUrl` option, using github.com - {e}"),
+                )
+                .await;
+        }
+        self.config.set(config);
+    }
+
+    /**
+        Computes the config again, using the given settings from the client,
+        or pulling them from the client if none were given and it supports
+        `workspace/configuration`, or else the settings that it last sent,
+        and then applies it if it has changed.
+
+        Diagnostics may depend on the config, so any cached diagnostics
+        are cleared, and the client is asked to refresh its diagnostics.
+    */
+    pub(super) async fn reload_config(&self, settings: Option<JsonValue>) {
+        let settings = match settings.filter(|s| !s.is_null()) {
+            Some(settings) => {
+                self.settings.set_settings(settings.clone());
+                Some(settings)
+            }
+            None if self.settings.pull_support() => self.pull_settings().await,
+            None => self.settings.settings(),
+        };
+        let settings = settings.map(|settings| match settings {
+            JsonValue::Object(mut map) if map.contains_key(SETTINGS_SECTION) => {
+                map.remove(SETTINGS_SECTION).unwrap_or_default()
+            }
+            other => other,
+        });
+
+        let mut options = self.settings.initialization_options();
+        if let Some(settings) = settings.filter(|s| !s.is_null()) {
+            match options.as_mut() {
+                Some(options) => merge_json(options, settings),
+                None => options = Some(settings),
+            }
+        }
+        let project_config = load_project_config(&self.settings.roots()).await;
+        let config = Config::from_sources_lossy(project_config, options.as_ref());
+        if config == self.config.get() {
+            debug!("Config did not change");
+            return;
+        }
+
+        self.apply_config(config).await;
+        for doc in self.documents.iter() {
+            doc.diagnostics_cache().clear();
+        }
+        if let Err(e) = self.client.workspace_diagnostic_refresh().await {
+            warn!("Failed to refresh diagnostics after changing config - {e}");
+        }
+    }
+
+    async fn pull_settings(&self) -> Option<JsonValue> {
+        let items = vec![ConfigurationItem {
+            scope_uri: None,
+            section: Some(SETTINGS_SECTION.to_string()),
+        }];
+        match self.client.configuration(items).await {
+            Ok(mut values) if !values.is_empty() => Some(values.swap_remove(0)),
+            Ok(_) => None,
+            Err(e) => {
+                warn!("Failed to pull settings from client - {e}");
+                None
+            }
+        }
+    }
+
+    /**
+        Registers for `workspace/didChangeConfiguration` notifications,
+        which some clients only send after the server has registered,
+        and pulls settings from the client once, if it supports it.
+    */
+    pub(super) async fn watch_configuration(&self) {
+        if self.settings.pull_support() {
+            self.reload_config(None).await;
+        }
+        if !self.settings.registration_support() {
+            return;
+        }
+        let registration = Registration {
+            id: String::from("tooling-language-server/didChangeConfiguration"),
+            method: String::from("workspace/didChangeConfiguration"),
+            register_options: None,
+        };
+        if let Err(e) = self.client.register_capability(vec![registration]).await {
+            warn!("Failed to register for configuration changes - {e}");
+        }
+    }
+}
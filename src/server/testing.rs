@@ -0,0 +1,475 @@
+/*
+    Utilities for testing the server end to end, without a real
+    client or any network requests, which looks roughly like this:
+
+    ```rust
+    let fetcher = MockFetcher::new().with_response("/se/rd/serde", index_lines);
+    let server = TestServer::new(fetcher).await;
+    let (contents, pos) = cursor_fixture(r#"[dependencies]
+    ser$0de = "1.0""#);
+    let uri = server.open("Cargo.toml", &contents).await;
+    let hover = server.hover(&uri, pos).await;
+    ```
+
+    This is only the harness, the tests themselves live
+    next to the tools and server modules that they exercise.
+*/
+
+use std::env::temp_dir;
+use std::sync::{Arc, Mutex};
+
+use futures::future::BoxFuture;
+use reqwest::StatusCode;
+use serde_json::json;
+use tower_lsp::lsp_types::*;
+use tower_lsp::{ClientSocket, LanguageServer, LspService};
+
+use crate::clients::Clients;
+use crate::tools::{ToolName, Tools};
+use crate::util::{Fetch, Fetcher, Request, RequestResult, ResponseError};
+
+use super::{Documents, Server, ServerArguments, SharedConfig, Transport};
+
+/**
+    The marker for the cursor position in fixtures given to `cursor_fixture`.
+*/
+pub const CURSOR: &str = "$0";
+
+/**
+    A sparse index for `serde`, with an old major version, and
+    a newer patch version that has features and links a library.
+*/
+pub const SERDE_INDEX: &str = concat!(
+    r#"{"name":"serde","vers":"0.9.0","deps":[],"features":{}}"#,
+    "\n",
+    r#"{"name":"serde","vers":"1.0.100","deps":[],"features":{}}"#,
+    "\n",
+    r#"{"name":"serde","vers":"1.0.150","deps":[],"features":{"derive":[]},"links":"foo"}"#,
+);
+
+/**
+    A sparse index for `foo`, with versions that only sort
+    correctly when compared as versions, not as strings.
+*/
+pub const FOO_INDEX: &str = concat!(
+    r#"{"name":"foo","vers":"0.9.0","deps":[],"features":{}}"#,
+    "\n",
+    r#"{"name":"foo","vers":"1.0.0","deps":[],"features":{}}"#,
+    "\n",
+    r#"{"name":"foo","vers":"1.2.3","deps":[],"features":{}}"#,
+    "\n",
+    r#"{"name":"foo","vers":"1.2.10","deps":[],"features":{}}"#,
+    "\n",
+    r#"{"name":"foo","vers":"1.20.0","deps":[],"features":{}}"#,
+    "\n",
+    r#"{"name":"foo","vers":"10.0.0","deps":[],"features":{}}"#,
+);
+
+/**
+    Responds to requests with canned responses, matching any request with
+    a url that contains the given pattern, and with `404 Not Found` otherwise.
+    Error responses with other statuses can also be given for a pattern.
+
+    All requested urls are recorded, to be able to assert on them later.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct MockFetcher {
+    responses: Arc<Mutex<Vec<(String, Vec<u8>)>>>,
+    statuses: Arc<Mutex<Vec<(String, StatusCode)>>>,
+    requested: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockFetcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_response(self, pattern: impl Into<String>, body: impl Into<Vec<u8>>) -> Self {
+        self.responses
+            .lock()
+            .expect("responses lock was poisoned")
+            .push((pattern.into(), body.into()));
+        self
+    }
+
+    pub fn with_status(self, pattern: impl Into<String>, status: StatusCode) -> Self {
+        self.statuses
+            .lock()
+            .expect("statuses lock was poisoned")
+            .push((pattern.into(), status));
+        self
+    }
+
+    pub fn requested(&self) -> Vec<String> {
+        self.requested
+            .lock()
+            .expect("requested lock was poisoned")
+            .clone()
+    }
+}
+
+impl Fetch for MockFetcher {
+    fn fetch(&self, request: Request) -> BoxFuture<'static, RequestResult<Vec<u8>>> {
+        let url = request.url().to_string();
+        self.requested
+            .lock()
+            .expect("requested lock was poisoned")
+            .push(url.clone());
+        let body = self
+            .responses
+            .lock()
+            .expect("responses lock was poisoned")
+            .iter()
+            .find(|(pattern, _)| url.contains(pattern.as_str()))
+            .map(|(_, body)| body.clone());
+        let status = self
+            .statuses
+            .lock()
+            .expect("statuses lock was poisoned")
+            .iter()
+            .find(|(pattern, _)| url.contains(pattern.as_str()))
+            .map(|(_, status)| *status);
+        Box::pin(async move {
+            match (body, status) {
+                (Some(body), _) => Ok(body),
+                (None, Some(status)) => Err(ResponseError::from_status_and_string(
+                    status,
+                    format!("Mock status for '{url}'"),
+                )
+                .into()),
+                (None, None) => Err(ResponseError::from_status_and_string(
+                    StatusCode::NOT_FOUND,
+                    format!("No mock response for '{url}'"),
+                )
+                .into()),
+            }
+        })
+    }
+}
+
+/**
+    Removes the cursor marker from a fixture, returning
+    the remaining contents and the position of the marker.
+*/
+pub fn cursor_fixture(fixture: &str) -> (String, Position) {
+    let offset = fixture
+        .find(CURSOR)
+        .expect("fixture should contain a cursor marker");
+    let before = &fixture[..offset];
+    let line = before.matches('\n').count();
+    let character = before
+        .rsplit('\n')
+        .next()
+        .unwrap_or_default()
+        .encode_utf16()
+        .count();
+    let contents = fixture.replacen(CURSOR, "", 1);
+    let position = Position::new(line as u32, character as u32);
+    (contents, position)
+}
+
+/**
+    Gets the plain text edit of a completion item, panicking for any other kind of edit.
+*/
+pub fn edit_of(item: &CompletionItem) -> &TextEdit {
+    match item.text_edit.as_ref() {
+        Some(CompletionTextEdit::Edit(edit)) => edit,
+        other => panic!("unexpected text edit: {other:?}"),
+    }
+}
+
+/**
+    A server that is initialized without a client connection, where
+    requests are made by calling the language server methods directly.
+*/
+pub struct TestServer {
+    service: LspService<Server>,
+    _socket: ClientSocket,
+}
+
+impl TestServer {
+    pub async fn new(fetcher: MockFetcher) -> Self {
+        Self::with_capabilities(fetcher, ClientCapabilities::default()).await
+    }
+
+    /**
+        Creates a server for a client with the given capabilities,
+        for testing any behavior that depends on what the client supports.
+    */
+    pub async fn with_capabilities(fetcher: MockFetcher, capabilities: ClientCapabilities) -> Self {
+        Self::build(fetcher, capabilities, ToolName::all()).await
+    }
+
+    /**
+        Creates a server where only the given tools are enabled.
+    */
+    pub async fn with_tools(fetcher: MockFetcher, tools: Vec<ToolName>) -> Self {
+        Self::build(fetcher, ClientCapabilities::default(), tools).await
+    }
+
+    async fn build(
+        fetcher: MockFetcher,
+        capabilities: ClientCapabilities,
+        tools: Vec<ToolName>,
+    ) -> Self {
+        let args = ServerArguments {
+            transport: Transport::Stdio,
+            github_token: None,
+            metrics: false,
+            tools,
+        };
+        let clients = Clients::with_fetcher(Fetcher::new(fetcher));
+        let (service, socket) =
+            LspService::new(move |client| Server::new(args).with_clients(client, clients));
+
+        let params = InitializeParams {
+            initialization_options: Some(json!({ "useSystemCredentials": false })),
+            capabilities,
+            ..Default::default()
+        };
+        service
+            .inner()
+            .initialize(params)
+            .await
+            .expect("server should initialize");
+
+        Self {
+            service,
+            _socket: socket,
+        }
+    }
+
+    pub fn server(&self) -> &Server {
+        self.service.inner()
+    }
+
+    /**
+        The config of the server, which tests may change at any time.
+    */
+    pub fn config(&self) -> &SharedConfig {
+        &self.server().config
+    }
+
+    pub fn documents(&self) -> &Documents {
+        &self.server().documents
+    }
+
+    pub fn tools(&self) -> &Tools {
+        &self.server().tools
+    }
+
+    /**
+        Opens a document with the given file name, such as `Cargo.toml`,
+        in a directory that does not exist, so that no other files are
+        ever read from disk, and returns the uri of the document.
+    */
+    pub async fn open(&self, file_name: &str, contents: &str) -> Url {
+        let path = temp_dir()
+            .join("tooling-language-server-fixtures")
+            .join(file_name);
+        let uri = Url::from_file_path(path).expect("fixture path should be absolute");
+        self.open_uri(uri, contents).await
+    }
+
+    /**
+        Opens a document at the given uri, which does not need to be a file
+        path, such as `untitled:/Cargo.toml` for a document that was never saved.
+    */
+    pub async fn open_uri(&self, uri: Url, contents: &str) -> Url {
+        self.server()
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem {
+                    uri: uri.clone(),
+                    language_id: String::from("toml"),
+                    version: 1,
+                    text: contents.to_string(),
+                },
+            })
+            .await;
+        uri
+    }
+
+    /**
+        Hovers at the given position, and returns the contents of the hover.
+    */
+    pub async fn hover(&self, uri: &Url, position: Position) -> Option<String> {
+        let hover = self.hover_response(uri, position).await?;
+        match hover.contents {
+            HoverContents::Markup(markup) => Some(markup.value),
+            HoverContents::Scalar(MarkedString::String(s)) => Some(s),
+            other => panic!("unexpected hover contents: {other:?}"),
+        }
+    }
+
+    /**
+        Hovers at the given position, and returns the range that the hover describes.
+    */
+    pub async fn hover_range(&self, uri: &Url, position: Position) -> Option<Range> {
+        self.hover_response(uri, position).await?.range
+    }
+
+    /**
+        Goes to the definition at the given position, and returns its single location.
+    */
+    pub async fn definition(&self, uri: &Url, position: Position) -> Option<Location> {
+        let params = GotoDefinitionParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let response = self.server().goto_definition(params).await;
+        match response.expect("goto definition failed")? {
+            GotoDefinitionResponse::Scalar(location) => Some(location),
+            other => panic!("unexpected definition response: {other:?}"),
+        }
+    }
+
+    /**
+        Hovers at the given position, and returns the full hover response.
+    */
+    pub async fn hover_response(&self, uri: &Url, position: Position) -> Option<Hover> {
+        let params = HoverParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        self.server().hover(params).await.expect("hover failed")
+    }
+
+    /**
+        Completes at the given position, and returns the completion items.
+    */
+    pub async fn completion(&self, uri: &Url, position: Position) -> Vec<CompletionItem> {
+        let params = CompletionParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+            context: None,
+        };
+        match self.server().completion(params).await {
+            Ok(Some(CompletionResponse::Array(items))) => items,
+            Ok(Some(CompletionResponse::List(list))) => list.items,
+            Ok(None) => Vec::new(),
+            Err(e) => panic!("completion failed: {e}"),
+        }
+    }
+
+    /**
+        Notifies the server that a document is about to be saved for the given
+        reason, and returns the edits that it wants applied before saving.
+    */
+    pub async fn will_save(&self, uri: &Url, reason: TextDocumentSaveReason) -> Vec<TextEdit> {
+        let params = WillSaveTextDocumentParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            reason,
+        };
+        match self.server().will_save_wait_until(params).await {
+            Ok(edits) => edits.unwrap_or_default(),
+            Err(e) => panic!("will save failed: {e}"),
+        }
+    }
+
+    /**
+        Requests code actions at the given position, and returns them.
+    */
+    pub async fn code_actions(&self, uri: &Url, position: Position) -> Vec<CodeAction> {
+        let params = CodeActionParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            range: Range::new(position, position),
+            context: CodeActionContext::default(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        match self.server().code_action(params).await {
+            Ok(actions) => actions
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|action| match action {
+                    CodeActionOrCommand::CodeAction(action) => Some(action),
+                    CodeActionOrCommand::Command(_) => None,
+                })
+                .collect(),
+            Err(e) => panic!("code actions failed: {e}"),
+        }
+    }
+
+    /**
+        Prepares a rename at the given position, and returns the
+        range and placeholder of the token that would be renamed.
+    */
+    pub async fn prepare_rename(&self, uri: &Url, position: Position) -> Option<(Range, String)> {
+        let params = TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            position,
+        };
+        match self.server().prepare_rename(params).await {
+            Ok(Some(PrepareRenameResponse::RangeWithPlaceholder { range, placeholder })) => {
+                Some((range, placeholder))
+            }
+            Ok(None) => None,
+            Ok(other) => panic!("unexpected prepare rename response: {other:?}"),
+            Err(e) => panic!("prepare rename failed: {e}"),
+        }
+    }
+
+    /**
+        Renames the token at the given position, and returns the edits for the document.
+    */
+    pub async fn rename(&self, uri: &Url, position: Position, new_name: &str) -> Vec<TextEdit> {
+        let params = RenameParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+                position,
+            },
+            new_name: new_name.to_string(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        match self.server().rename(params).await {
+            Ok(edit) => edit
+                .and_then(|edit| edit.changes)
+                .and_then(|mut changes| changes.remove(uri))
+                .unwrap_or_default(),
+            Err(e) => panic!("rename failed: {e}"),
+        }
+    }
+
+    /**
+        Pulls diagnostics for the given document, and returns them.
+    */
+    pub async fn diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+        let params = DocumentDiagnosticParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            identifier: None,
+            previous_result_id: None,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        match self.server().diagnostic(params).await {
+            Ok(DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(report))) => {
+                report.full_document_diagnostic_report.items
+            }
+            Ok(other) => panic!("unexpected diagnostic report: {other:?}"),
+            Err(e) => panic!("diagnostics failed: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_fixture() {
+        let (contents, pos) = cursor_fixture("[dependencies]\nser$0de = \"1.0\"");
+        assert_eq!(contents, "[dependencies]\nserde = \"1.0\"");
+        assert_eq!(pos, Position::new(1, 3));
+    }
+}
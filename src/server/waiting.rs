@@ -30,6 +30,13 @@ impl Waiting {
         self.senders.remove(uri);
     }
 
+    /**
+        Removes all waiters, which makes them stop waiting right away.
+    */
+    pub fn clear(&self) {
+        self.senders.clear();
+    }
+
     pub fn trigger(&self, uri: Url) {
         if let Some((_, senders)) = self.senders.remove(&uri) {
             for sender in senders {
@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{Diagnostic, NumberOrString, Range, TextDocumentIdentifier};
+
+use super::DiagnosticCode;
+
+/**
+    Parameters for the custom `tooling/analyze` request.
+*/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyzeParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+/**
+    A summary of the dependencies in a manifest, returned from the custom `tooling/analyze` request.
+
+    Computed from the same diagnostics that are shown in the editor, so the
+    counts always match what users see. If `partial` is set, some dependencies
+    were not checked at all, such as when the manifest exceeds the limits,
+    or when data for them could not be fetched, such as when offline.
+*/
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyzeResult {
+    pub total: usize,
+    pub outdated: Vec<AnalyzedDependency>,
+    pub deprecated: Vec<AnalyzedDependency>,
+    pub unknown: Vec<AnalyzedDependency>,
+    pub partial: bool,
+}
+
+/**
+    A single dependency with a problem, along with the message of its diagnostic.
+*/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyzedDependency {
+    pub name: String,
+    pub range: Range,
+    pub message: String,
+}
+
+impl AnalyzeResult {
+    /**
+        Creates a summary from the names and ranges of all dependencies in a
+        manifest, and the diagnostics for it, where each diagnostic belongs to the
+        dependency that contains its range. Diagnostics outside of any dependency,
+        or with codes that are not summarized, are not included.
+
+        The diagnostics are not `complete` if data for some of them failed to
+        be fetched, and the summary is then partial, just like for the limits.
+    */
+    pub fn from_diagnostics<'a>(
+        dependencies: impl IntoIterator<Item = (&'a str, Range)>,
+        diagnostics: &[Diagnostic],
+        complete: bool,
+    ) -> Self {
+        let dependencies = dependencies.into_iter().collect::<Vec<_>>();
+        let mut result = Self {
+            total: dependencies.len(),
+            partial: !complete,
+            ..Default::default()
+        };
+
+        for diag in diagnostics {
+            let Some(NumberOrString::String(code)) = &diag.code else {
+                continue;
+            };
+            if code == DiagnosticCode::ExceedsLimits.as_str() {
+                result.partial = true;
+                continue;
+            }
+            let list = match code.as_str() {
+                c if c == DiagnosticCode::Outdated.as_str() => &mut result.outdated,
+                c if c == DiagnosticCode::Deprecated.as_str() => &mut result.deprecated,
+                c if c == DiagnosticCode::UnknownPackage.as_str()
+                    || c == DiagnosticCode::UnknownVersion.as_str() =>
+                {
+                    &mut result.unknown
+                }
+                _ => continue,
+            };
+            let Some((name, range)) = dependencies
+                .iter()
+                .find(|(_, range)| contains(*range, diag.range))
+            else {
+                continue;
+            };
+            // The same dependency may have several diagnostics with the same code
+            if list.iter().any(|d| d.range == *range) {
+                continue;
+            }
+            list.push(AnalyzedDependency {
+                name: name.to_string(),
+                range: *range,
+                message: diag.message.clone(),
+            });
+        }
+
+        result
+    }
+}
+
+fn contains(outer: Range, inner: Range) -> bool {
+    outer.start <= inner.start && inner.end <= outer.end
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Position;
+
+    use super::*;
+
+    fn range(line: u32, start: u32, end: u32) -> Range {
+        Range::new(Position::new(line, start), Position::new(line, end))
+    }
+
+    fn diagnostic(code: DiagnosticCode, range: Range) -> Diagnostic {
+        Diagnostic {
+            range,
+            message: code.to_string(),
+            ..code.diagnostic()
+        }
+    }
+
+    #[test]
+    fn test_from_diagnostics() {
+        let dependencies = [("serde", range(1, 0, 15)), ("tokio", range(2, 0, 11))];
+        let diagnostics = [
+            diagnostic(DiagnosticCode::Outdated, range(1, 8, 15)),
+            diagnostic(DiagnosticCode::Deprecated, range(1, 0, 5)),
+            diagnostic(DiagnosticCode::UnknownVersion, range(2, 8, 11)),
+            diagnostic(DiagnosticCode::DuplicateTable, range(0, 0, 14)),
+        ];
+        let result = AnalyzeResult::from_diagnostics(dependencies, &diagnostics, true);
+
+        assert_eq!(result.total, 2);
+        assert_eq!(result.outdated.len(), 1);
+        assert_eq!(result.outdated[0].name, "serde");
+        assert_eq!(result.deprecated.len(), 1);
+        assert_eq!(result.unknown[0].name, "tokio");
+        assert!(!result.partial);
+    }
+
+    #[test]
+    fn test_from_diagnostics_partial() {
+        let limits = diagnostic(DiagnosticCode::ExceedsLimits, range(0, 0, 0));
+        let result = AnalyzeResult::from_diagnostics([("serde", range(1, 0, 15))], &[limits], true);
+        assert_eq!(result.total, 1);
+        assert!(result.partial);
+
+        // Failed fetches leave out diagnostics, so there is nothing to look for
+        let result = AnalyzeResult::from_diagnostics([("serde", range(1, 0, 15))], &[], false);
+        assert!(result.partial);
+        assert!(result.outdated.is_empty());
+    }
+}
@@ -0,0 +1,264 @@
+use tower_lsp::lsp_types::*;
+
+use crate::parser::{Dependency, DependencySource};
+
+use super::super::shared::CodeActionMetadata;
+use super::workspace::WorkspaceDependencies;
+use super::Document;
+
+/**
+    Keys of a dependency that are replaced by inheriting it from the workspace,
+    and which therefore don't prevent it from being switched to `workspace = true`.
+*/
+const INHERITABLE_KEYS: [&str; 4] = ["version", "features", "optional", "package"];
+
+/**
+    Creates refactors for a dependency, which are available
+    whether or not the dependency has any diagnostics.
+
+    Refactors that need the latest version of the dependency are only
+    created if the client can resolve their edits lazily, since fetching
+    it for every code action request would be far too slow.
+*/
+pub fn get_cargo_refactors(
+    doc: &Document,
+    dep: &Dependency,
+    workspace: Option<&WorkspaceDependencies>,
+    resolve_edits: bool,
+) -> Vec<CodeActionOrCommand> {
+    let mut actions = Vec::new();
+
+    // Only crates.io dependencies can be looked up in the index
+    let spec = dep.spec().map(|s| &s.contents);
+    let from_index =
+        spec.is_some_and(|s| s.registry.is_none() && s.source == DependencySource::Registry);
+    let version = spec
+        .and_then(|s| s.version.as_ref())
+        .filter(|v| !v.unquoted().trim().is_empty());
+    if let Some(version) = version.filter(|_| resolve_edits && from_index) {
+        let metadata = CodeActionMetadata::PinLatestVersion {
+            edit_range: version.range,
+            source_uri: doc.uri().clone(),
+            source_text: version.quoted().to_string(),
+            version_current: version.unquoted().to_string(),
+            package: dep.package_name().unquoted().to_string(),
+        };
+        actions.extend(metadata.into_code_actions(None));
+    }
+
+    // A shorthand dependency such as `foo = "1"` has no keys, only a version
+    let spec = dep.spec().filter(|spec| spec.contents.keys.is_none());
+    if let Some(version) = spec.and_then(|spec| spec.contents.version.as_ref()) {
+        let metadata = CodeActionMetadata::ConvertToTable {
+            edit_range: version.range,
+            source_uri: doc.uri().clone(),
+            version: version.quoted().to_string(),
+        };
+        actions.extend(metadata.into_code_actions(None));
+    }
+
+    // An inline table such as `foo = { version = "1" }` with only a version
+    // can be simplified back to a shorthand, but tables under their own
+    // header such as `[dependencies.foo]` are left alone
+    let table = dep.spec().and_then(|spec| {
+        let keys = spec.contents.keys.as_ref()?;
+        let version = spec.contents.version.as_ref()?;
+        Some((keys, version))
+    });
+    if let Some((keys, version)) = table.filter(|(keys, _)| {
+        keys.contents.len() == 1
+            && keys.contents[0].unquoted() == "version"
+            && doc.as_str()[doc.range_to_span(keys.range)].starts_with('{')
+    }) {
+        let metadata = CodeActionMetadata::ConvertToShorthand {
+            edit_range: keys.range,
+            source_uri: doc.uri().clone(),
+            version: version.quoted().to_string(),
+        };
+        actions.extend(metadata.into_code_actions(None));
+    }
+
+    // A dependency that is also in `[workspace.dependencies]` may inherit it instead
+    if let Some(inherited) = workspace.and_then(|w| w.find_for(doc, dep)) {
+        if let Some(metadata) = get_cargo_workspace_version_metadata(doc, dep, inherited) {
+            actions.extend(metadata.into_code_actions(None));
+        }
+    }
+
+    actions
+}
+
+/**
+    Creates the metadata for switching a dependency to `workspace = true`,
+    keeping any features that the workspace dependency does not already
+    enable, since features of members are added to those of the workspace.
+
+    Returns `None` if the dependency already inherits from the workspace, is
+    not from the registry, has keys that would be lost, or is in a table
+    under its own header such as `[dependencies.foo]`, which is left alone.
+*/
+pub fn get_cargo_workspace_version_metadata(
+    doc: &Document,
+    dep: &Dependency,
+    inherited: &Dependency,
+) -> Option<CodeActionMetadata> {
+    let spec = &dep.spec()?.contents;
+    if spec.source != DependencySource::Registry || spec.registry.is_some() {
+        return None;
+    }
+    let version = spec.version.as_ref()?;
+
+    let edit_range = match &spec.keys {
+        None => version.range,
+        Some(keys) => {
+            let inline = doc.as_str()[doc.range_to_span(keys.range)].starts_with('{');
+            let inheritable = keys
+                .contents
+                .iter()
+                .all(|k| INHERITABLE_KEYS.contains(&k.unquoted()));
+            if !inline || !inheritable {
+                return None;
+            }
+            keys.range
+        }
+    };
+
+    let inherited_features = inherited
+        .spec()
+        .and_then(|s| s.contents.features.as_ref())
+        .map(|f| f.contents.as_slice())
+        .unwrap_or_default();
+    let features = spec
+        .features
+        .iter()
+        .flat_map(|f| f.contents.iter())
+        .filter(|f| {
+            !inherited_features
+                .iter()
+                .any(|i| i.unquoted() == f.unquoted())
+        })
+        .map(|f| f.quoted().to_string())
+        .collect();
+
+    Some(CodeActionMetadata::UseWorkspaceVersion {
+        edit_range,
+        source_uri: doc.uri().clone(),
+        features,
+        optional: spec.is_optional(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::LanguageServer;
+
+    use crate::server::testing::{cursor_fixture, MockFetcher, TestServer, FOO_INDEX, SERDE_INDEX};
+
+    use super::*;
+
+    fn code_action_resolve_capabilities() -> ClientCapabilities {
+        ClientCapabilities {
+            text_document: Some(TextDocumentClientCapabilities {
+                code_action: Some(CodeActionClientCapabilities {
+                    resolve_support: Some(CodeActionCapabilityResolveSupport {
+                        properties: vec![String::from("edit")],
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cargo_code_action_pin_latest_resolve() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let capabilities = code_action_resolve_capabilities();
+        let server = TestServer::with_capabilities(fetcher.clone(), capabilities).await;
+
+        let (contents, pos) = cursor_fixture("[dependencies]\nserde = \"1.0$0\"\n");
+        let uri = server.open("Cargo.toml", &contents).await;
+        let actions = server.code_actions(&uri, pos).await;
+
+        // The action is offered without an edit, and without fetching anything
+        let action = actions
+            .into_iter()
+            .find(|a| a.title == "Pin to latest version")
+            .expect("missing pin action");
+        assert!(action.edit.is_none());
+        assert!(!fetcher
+            .requested()
+            .iter()
+            .any(|u| u.contains("/se/rd/serde")));
+
+        let resolved = server
+            .server()
+            .code_action_resolve(action)
+            .await
+            .expect("resolve failed");
+        let changes = resolved.edit.and_then(|e| e.changes).expect("missing edit");
+        assert_eq!(changes[&uri][0].new_text, "\"=1.0.150\"");
+    }
+
+    #[tokio::test]
+    async fn test_cargo_code_action_pin_latest_unsupported() {
+        let server = TestServer::new(MockFetcher::new()).await;
+
+        let (contents, pos) = cursor_fixture("[dependencies]\nserde = \"1.0$0\"\n");
+        let uri = server.open("Cargo.toml", &contents).await;
+        let actions = server.code_actions(&uri, pos).await;
+        assert!(actions.iter().all(|a| a.title != "Pin to latest version"));
+    }
+
+    #[tokio::test]
+    async fn test_cargo_code_action_refresh_dependency() {
+        let fetcher = MockFetcher::new()
+            .with_response("/se/rd/serde", SERDE_INDEX)
+            .with_response("/3/f/foo", FOO_INDEX);
+        let server = TestServer::new(fetcher.clone()).await;
+        let index_requests = |pattern: &str| {
+            fetcher
+                .requested()
+                .iter()
+                .filter(|url| url.contains(pattern))
+                .count()
+        };
+
+        let contents = "[dependencies]\nserde = \"1.0.1\"\nfoo = \"1\"\n";
+        let uri = server.open("Cargo.toml", contents).await;
+        server.completion(&uri, Position::new(1, 13)).await;
+        server.completion(&uri, Position::new(2, 8)).await;
+        assert_eq!(index_requests("/se/rd/serde"), 1);
+        assert_eq!(index_requests("/3/f/foo"), 1);
+
+        let actions = server.code_actions(&uri, Position::new(1, 2)).await;
+        let refresh = actions
+            .into_iter()
+            .find(|action| action.title == "Refresh version info")
+            .expect("refresh action should be offered for any dependency");
+        let command = refresh
+            .command
+            .expect("refresh action should run a command");
+        assert_eq!(
+            command.arguments.as_ref().unwrap()[0]["dependency"]["name"],
+            "serde"
+        );
+
+        server
+            .server()
+            .execute_command(ExecuteCommandParams {
+                command: command.command,
+                arguments: command.arguments.unwrap_or_default(),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            })
+            .await
+            .expect("refresh command should succeed");
+
+        // Only the refreshed dependency is fetched again
+        server.completion(&uri, Position::new(1, 13)).await;
+        server.completion(&uri, Position::new(2, 8)).await;
+        assert_eq!(index_requests("/se/rd/serde"), 2);
+        assert_eq!(index_requests("/3/f/foo"), 1);
+    }
+}
@@ -3,23 +3,38 @@ use tower_lsp::lsp_types::*;
 use tracing::debug;
 
 use crate::clients::*;
-use crate::parser::{Dependency, Node};
+use crate::parser::{Dependency, Feature, Node, TableField};
 use crate::server::*;
 use crate::tools::cargo::constants::CratesIoPackage;
-use crate::tools::cargo::util::get_features;
+use crate::tools::cargo::util::{get_dependency_metadatas_highest, get_edition, get_features};
 
-use super::constants::top_crates_io_packages_prefixed;
+use super::super::shared::{
+    format_count, sort_alphabetically, sort_by_relevance, version_segment_range, with_sort_texts,
+    with_version_variants, workspace_dependency_text, CodeActionMetadata, CompletionMetadata,
+    MarkdownBuilder, PlainVersion,
+};
+use super::constants::{top_crates_io_packages_prefixed, CARGO_DEPENDENCY_KEYS};
+use super::registries::{get_registry_names, DEFAULT_REGISTRY_NAME};
 use super::Versioned;
 
 const MAXIMUM_PACKAGES_SHOWN: usize = 64;
 const MINIMUM_PACKAGES_BEFORE_FETCH: usize = 16; // Less than 16 packages found statically = fetch dynamically
 
+/*
+    Editions before 2021 are usually built by toolchains that
+    predate the `dep:` syntax for optional dependency features
+*/
+const EDITION_WITH_DEP_FEATURES: u32 = 2021;
+
 pub async fn get_cargo_completions_name(
     clients: &Clients,
+    config: &Config,
+    resolve: CompletionResolveSupport,
     document: &Document,
-    dep: &Dependency,
+    name: &Node<String>,
+    declared: &[&str],
 ) -> Result<CompletionResponse> {
-    let dname = dep.name().unquoted();
+    let dname = name.unquoted();
 
     let mut packages = top_crates_io_packages_prefixed(dname, MAXIMUM_PACKAGES_SHOWN)
         .into_iter()
@@ -30,6 +45,9 @@ pub async fn get_cargo_completions_name(
         if let Ok(crates) = clients.crates.search_crates(dname).await {
             let count_prev = packages.len();
 
+            // NOTE: The bundled crates have all-time download counts, so the same
+            // kind of count is used for fetched crates, and the bundled entries come
+            // first to win the dedup below, giving each crate one consistent count
             packages.extend(crates.inner.into_iter().map(|m| CratesIoPackage {
                 name: m.name.to_string().into(),
                 downloads: m.downloads.total_count,
@@ -38,6 +56,9 @@ pub async fn get_cargo_completions_name(
 
             packages.sort_by_key(|package| package.name.to_ascii_lowercase());
             packages.dedup_by_key(|p| p.name.to_ascii_lowercase());
+            if config.completion.sort_by_popularity {
+                packages.sort_by_key(|package| u64::MAX - package.downloads);
+            }
             packages.truncate(MINIMUM_PACKAGES_BEFORE_FETCH);
 
             let count_after = packages.len();
@@ -50,32 +71,124 @@ pub async fn get_cargo_completions_name(
         }
     }
 
-    let items = packages
-        .into_iter()
-        .map(|package| CompletionItem {
+    // Crates that are already declared are either left out, or marked and ranked
+    // last, so that they are not accidentally added again as duplicates
+    let items = packages.into_iter().filter_map(|package| {
+        let is_declared = declared.contains(&package.name.as_ref());
+        if is_declared && config.completion.filter_declared {
+            return None;
+        }
+        let relevance = match (is_declared, config.completion.sort_by_popularity) {
+            (true, _) => 0,
+            (false, true) => package.downloads.saturating_add(1),
+            (false, false) => 1,
+        };
+        let description = if is_declared {
+            format!("Already a dependency - {}", package.description)
+        } else {
+            package.description.to_string()
+        };
+        let item = CompletionItem {
             label: package.name.to_string(),
             kind: Some(CompletionItemKind::VALUE),
             text_edit: Some(CompletionTextEdit::Edit(
-                document.create_edit(dep.name().range, package.name.to_string()),
+                document.create_edit(name.unquoted_range(), package.name.to_string()),
             )),
-            detail: Some(package.description.to_string()),
+            detail: Some(format!(
+                "{} downloads - {}",
+                format_count(package.downloads),
+                description
+            )),
+            data: resolve.documentation.then(|| {
+                CompletionMetadata::CargoCrate {
+                    name: package.name.to_string(),
+                }
+                .into_data(document.uri())
+            }),
             ..Default::default()
-        })
-        .collect::<Vec<_>>();
-    Ok(CompletionResponse::Array(items))
+        };
+        Some((relevance, item))
+    });
+    Ok(CompletionResponse::Array(sort_by_relevance(items)))
+}
+
+/**
+    Gets the names of the crates declared by all other dependencies in the same
+    section as the given one, both as written and as renamed using `package`.
+
+    Sections are the same only if both their kind and their target are, since
+    the same crate may be declared once for each platform in `[target.*]` tables.
+*/
+pub fn get_declared_crate_names<'a>(
+    dependencies: &'a [Dependency],
+    dep: &Dependency,
+) -> Vec<&'a str> {
+    dependencies
+        .iter()
+        .filter(|other| other.kind() == dep.kind() && other.target() == dep.target())
+        .filter(|other| other.range() != dep.range())
+        .flat_map(|other| [other.name().unquoted(), other.package_name().unquoted()])
+        .collect()
+}
+
+/**
+    Resolves the documentation for a crate name completion item,
+    which requires fetching the full crate data from crates.io.
+*/
+pub async fn get_cargo_completion_resolve(
+    clients: &Clients,
+    mut item: CompletionItem,
+    name: &str,
+) -> Result<CompletionItem> {
+    let Ok(crate_data_single) = clients.crates.get_crate_data(name).await else {
+        return Ok(item);
+    };
+
+    let crate_data = crate_data_single.inner;
+    let mut md = MarkdownBuilder::new();
+    md.p(crate_data.description);
+
+    md.br();
+    md.h3("Links");
+    md.a(
+        "Documentation",
+        format!("https://docs.rs/{}", crate_data.name),
+    );
+    if let Some(repo) = crate_data.links.repository.as_deref() {
+        md.a("Repository", repo);
+    }
+    if let Some(page) = crate_data.links.homepage.as_deref() {
+        md.a("Homepage", page);
+    }
+
+    item.documentation = Some(Documentation::MarkupContent(MarkupContent {
+        kind: MarkupKind::Markdown,
+        value: md.build(),
+    }));
+    Ok(item)
 }
 
 pub async fn get_cargo_completions_version(
     clients: &Clients,
     document: &Document,
     dep: &Dependency,
+    workspace: Option<CodeActionMetadata>,
 ) -> Result<CompletionResponse> {
-    let name = dep.name().unquoted();
     let Some(version) = dep.spec().and_then(|s| s.contents.version.as_ref()) else {
         return Ok(CompletionResponse::Array(Vec::new()));
     };
 
-    let metadatas = match clients.crates.get_sparse_index_crate_metadatas(name).await {
+    // Only the versions that can be shown are needed, which
+    // is far from all of them for crates with long histories
+    let metadatas = match get_dependency_metadatas_highest(
+        clients,
+        document,
+        dep,
+        MAXIMUM_PACKAGES_SHOWN,
+        |v| dep.matches_completion_version(v),
+    )
+    .await
+    {
         Err(_) => return Ok(CompletionResponse::Array(Vec::new())),
         Ok(m) => m,
     };
@@ -84,21 +197,53 @@ pub async fn get_cargo_completions_version(
         .extract_completion_versions(metadatas.into_iter())
         .into_iter()
         .take(MAXIMUM_PACKAGES_SHOWN)
-        .enumerate()
-        .map(|(index, potential_version)| CompletionItem {
+        .map(|potential_version| CompletionItem {
             label: potential_version.item_version_raw.to_string(),
             kind: Some(CompletionItemKind::VALUE),
-            sort_text: Some(format!("{:0>5}", index)),
-            text_edit: Some(CompletionTextEdit::Edit(document.create_substring_edit(
-                version.range.start.line,
-                potential_version.this_version_raw,
+            text_edit: Some(CompletionTextEdit::Edit(document.create_edit(
+                version_segment_range(version),
                 potential_version.item_version_raw,
             ))),
             ..Default::default()
         })
         .collect::<Vec<_>>();
 
-    Ok(CompletionResponse::Array(valid_vec))
+    let valid_vec = with_version_variants(
+        document,
+        version.unquoted_range(),
+        version.unquoted(),
+        PlainVersion::Caret,
+        valid_vec,
+    );
+
+    // The same edit as the "Use workspace version" code action, listed last
+    // so that it never gets in the way of picking a version as usual
+    let workspace_item = workspace.and_then(|metadata| {
+        let CodeActionMetadata::UseWorkspaceVersion {
+            edit_range,
+            features,
+            optional,
+            ..
+        } = metadata
+        else {
+            return None;
+        };
+        Some(CompletionItem {
+            label: String::from("workspace = true"),
+            kind: Some(CompletionItemKind::VALUE),
+            detail: Some(String::from("Use workspace version")),
+            filter_text: Some(document.as_str()[document.range_to_span(edit_range)].to_string()),
+            text_edit: Some(CompletionTextEdit::Edit(document.create_edit(
+                edit_range,
+                workspace_dependency_text(&features, optional),
+            ))),
+            ..Default::default()
+        })
+    });
+
+    Ok(CompletionResponse::Array(with_sort_texts(
+        valid_vec.into_iter().chain(workspace_item),
+    )))
 }
 
 pub async fn get_cargo_completions_features(
@@ -107,25 +252,502 @@ pub async fn get_cargo_completions_features(
     dep: &Dependency,
     feat: &Node<String>,
 ) -> Result<CompletionResponse> {
-    let known_features = get_features(clients, dep).await;
+    let known_features = get_features(clients, document, dep).await;
     tracing::debug!("Known features: {known_features:?}");
 
     let valid_features = known_features
         .into_iter()
         .filter(|f| f.starts_with(feat.unquoted()))
-        .enumerate()
-        .map(|(index, known_feat)| CompletionItem {
+        .map(|known_feat| CompletionItem {
             label: known_feat.to_string(),
             kind: Some(CompletionItemKind::VALUE),
-            sort_text: Some(format!("{:0>5}", index)),
-            text_edit: Some(CompletionTextEdit::Edit(document.create_substring_edit(
-                feat.range.start.line,
-                feat.unquoted(),
-                known_feat.to_string(),
-            ))),
+            text_edit: Some(CompletionTextEdit::Edit(
+                document.create_edit(feat.unquoted_range(), known_feat.to_string()),
+            )),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+
+    Ok(CompletionResponse::Array(sort_alphabetically(
+        valid_features,
+    )))
+}
+
+/**
+    Completes a value in a `[features]` table entry, which may be:
+
+    - Another feature, such as `std`
+    - An optional dependency, such as `dep:serde`, if the edition supports it
+    - A feature of a dependency, such as `serde/derive` or `serde?/derive`
+*/
+pub async fn get_cargo_completions_feature_value(
+    clients: &Clients,
+    document: &Document,
+    features: &[Feature],
+    dependencies: &[Dependency],
+    package_fields: &[TableField],
+    feature: &Feature,
+    value: &Node<String>,
+) -> Result<CompletionResponse> {
+    let current = value.unquoted();
+    let range = value.unquoted_range();
+    let item = |label: String, detail: String| CompletionItem {
+        label: label.clone(),
+        kind: Some(CompletionItemKind::VALUE),
+        detail: Some(detail),
+        text_edit: Some(CompletionTextEdit::Edit(document.create_edit(range, label))),
+        ..Default::default()
+    };
+
+    // Features of dependencies need to be fetched, but only once a dependency has been chosen
+    if let Some((prefix, feat)) = current.split_once('/') {
+        let name = prefix.strip_suffix('?').unwrap_or(prefix);
+        let Some(dep) = dependencies.iter().find(|d| d.name().unquoted() == name) else {
+            return Ok(CompletionResponse::Array(Vec::new()));
+        };
+        let items = get_features(clients, document, dep)
+            .await
+            .into_iter()
+            .filter(|known| known.starts_with(feat))
+            .map(|known| {
+                let detail = format!("Feature `{known}` of dependency `{name}`");
+                item(format!("{prefix}/{known}"), detail)
+            });
+        return Ok(CompletionResponse::Array(sort_alphabetically(items)));
+    }
+
+    let other_features = features
+        .iter()
+        .map(|f| f.name.unquoted())
+        .filter(|name| *name != feature.name.unquoted())
+        .map(|name| item(name.to_string(), format!("Feature `{name}`")));
+
+    // Optional dependencies may also be referenced using their implicit
+    // features, but `dep:` is preferred wherever it is supported
+    let dep_prefix = if get_edition(package_fields) >= EDITION_WITH_DEP_FEATURES {
+        "dep:"
+    } else {
+        ""
+    };
+    let optional_dependencies = dependencies
+        .iter()
+        .filter(|d| d.spec().is_some_and(|s| s.contents.is_optional()))
+        .map(|d| d.name().unquoted())
+        .map(|name| {
+            let detail = format!("Optional dependency `{name}`");
+            item(format!("{dep_prefix}{name}"), detail)
+        });
+
+    // Implicit features have the same name as their dependency, so there may be duplicates
+    let mut items =
+        sort_alphabetically(other_features.chain(optional_dependencies).filter(|item| {
+            let name = item.label.strip_prefix("dep:").unwrap_or(&item.label);
+            item.label.starts_with(current) || name.starts_with(current)
+        }));
+    items.dedup_by(|a, b| a.label == b.label);
+    Ok(CompletionResponse::Array(items))
+}
+
+pub async fn get_cargo_completions_registry(
+    clients: &Clients,
+    document: &Document,
+    registry: &Node<String>,
+) -> Result<CompletionResponse> {
+    let mut names = get_registry_names(&clients.cargo_configs, document.uri()).await;
+    if names.is_empty() {
+        names.push(DEFAULT_REGISTRY_NAME.to_string());
+    }
+
+    let items = names
+        .into_iter()
+        .filter(|name| name.starts_with(registry.unquoted()))
+        .map(|name| CompletionItem {
+            label: name.clone(),
+            kind: Some(CompletionItemKind::VALUE),
+            text_edit: Some(CompletionTextEdit::Edit(
+                document.create_edit(registry.unquoted_range(), name),
+            )),
             ..Default::default()
         })
         .collect::<Vec<_>>();
+    Ok(CompletionResponse::Array(sort_alphabetically(items)))
+}
+
+pub async fn get_cargo_completions_keys(
+    document: &Document,
+    keys: &Node<Vec<Node<String>>>,
+    pos: Position,
+    snippets: bool,
+) -> Result<CompletionResponse> {
+    // Find any partially typed key around the cursor - the table may not parse
+    // properly while a key is being typed, so we look at the text directly
+    let text = document.as_str();
+    let offset = document.position_to_offset(pos);
+    let is_key_char = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    let start = text[..offset].trim_end_matches(is_key_char).len();
+    let end = text.len() - text[offset..].trim_start_matches(is_key_char).len();
+    let current = &text[start..end];
 
-    Ok(CompletionResponse::Array(valid_features))
+    // Keys may only come right after the opening brace or a comma,
+    // anything else means that the cursor is at a value instead
+    if !text[..start].trim_end().ends_with(['{', ',']) {
+        return Ok(CompletionResponse::Array(Vec::new()));
+    }
+
+    // A key that is being renamed already has a value, so leave it alone
+    let has_value = text[end..].trim_start().starts_with('=');
+    let range = document.range_from_span(start..end);
+
+    let items = CARGO_DEPENDENCY_KEYS
+        .iter()
+        .filter(|key| key.name.starts_with(current))
+        .filter(|key| {
+            key.name == current || !keys.contents.iter().any(|k| k.unquoted() == key.name)
+        })
+        .map(|key| {
+            let value = if snippets {
+                key.value_snippet
+            } else {
+                key.value_plain
+            };
+            let new_text = if has_value {
+                key.name.to_string()
+            } else {
+                format!("{} = {value}", key.name)
+            };
+            CompletionItem {
+                label: key.name.to_string(),
+                kind: Some(CompletionItemKind::PROPERTY),
+                detail: Some(key.description.to_string()),
+                insert_text_format: Some(if snippets && !has_value {
+                    InsertTextFormat::SNIPPET
+                } else {
+                    InsertTextFormat::PLAIN_TEXT
+                }),
+                text_edit: Some(CompletionTextEdit::Edit(
+                    document.create_edit(range, new_text),
+                )),
+                ..Default::default()
+            }
+        });
+    Ok(CompletionResponse::Array(with_sort_texts(items)))
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::LanguageServer;
+
+    use crate::server::testing::{
+        cursor_fixture, edit_of, MockFetcher, TestServer, FOO_INDEX, SERDE_INDEX,
+    };
+    use crate::server::{CompletionConfig, Config};
+    use crate::util::LspUriExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cargo_completion_version() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::new(fetcher).await;
+
+        let (contents, pos) = cursor_fixture("[dependencies]\nserde = \"1.0.1$0\"\n");
+        let uri = server.open("Cargo.toml", &contents).await;
+        let items = server.completion(&uri, pos).await;
+
+        let labels = items.iter().map(|i| i.label.as_str()).collect::<Vec<_>>();
+        assert_eq!(labels, vec!["1.0.150", "=1.0.150", "1.0.100", "=1.0.100"]);
+        assert_eq!(items[0].detail.as_deref(), Some("caret"));
+        assert_eq!(items[1].detail.as_deref(), Some("exact"));
+    }
+
+    #[tokio::test]
+    async fn test_cargo_completion_version_operator() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::new(fetcher).await;
+
+        let (contents, pos) = cursor_fixture("[dependencies]\nserde = \"^1.0.1$0\"\n");
+        let uri = server.open("Cargo.toml", &contents).await;
+        let items = server.completion(&uri, pos).await;
+
+        // An operator was already picked, so no exact pins are offered
+        let labels = items.iter().map(|i| i.label.as_str()).collect::<Vec<_>>();
+        assert_eq!(labels, vec!["1.0.150", "1.0.100"]);
+        assert!(items.iter().all(|i| i.detail.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_cargo_completion_versions_cached_until_lockfile_changes() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::new(fetcher.clone()).await;
+        let index_requests = || {
+            fetcher
+                .requested()
+                .iter()
+                .filter(|url| url.contains("/se/rd/serde"))
+                .count()
+        };
+
+        let (contents, pos) = cursor_fixture("[dependencies]\nserde = \"1.0.1$0\"\n");
+        let uri = server.open("Cargo.toml", &contents).await;
+        server.completion(&uri, pos).await;
+        server.completion(&uri, pos).await;
+        assert_eq!(index_requests(), 1);
+
+        // Unrelated lockfiles changing should not refresh versions
+        let changed = |file_name: &str| DidChangeWatchedFilesParams {
+            changes: vec![FileEvent {
+                uri: uri.with_file_name(file_name).unwrap(),
+                typ: FileChangeType::CHANGED,
+            }],
+        };
+        server
+            .server()
+            .did_change_watched_files(changed("package-lock.json"))
+            .await;
+        server.completion(&uri, pos).await;
+        assert_eq!(index_requests(), 1);
+
+        server
+            .server()
+            .did_change_watched_files(changed("Cargo.lock"))
+            .await;
+        let items = server.completion(&uri, pos).await;
+        assert_eq!(index_requests(), 2);
+        assert_eq!(items.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_untitled_cargo_completion_version() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::new(fetcher).await;
+
+        let (contents, pos) = cursor_fixture("[dependencies]\nserde = \"1.0.1$0\"\n");
+        let uri = Url::parse("untitled:/drafts/Cargo.toml").unwrap();
+        let uri = server.open_uri(uri, &contents).await;
+        let items = server.completion(&uri, pos).await;
+
+        let labels = items.iter().map(|i| i.label.as_str()).collect::<Vec<_>>();
+        assert_eq!(labels, vec!["1.0.150", "=1.0.150", "1.0.100", "=1.0.100"]);
+        let diagnostics = server.diagnostics(&uri).await;
+        assert!(diagnostics
+            .iter()
+            .all(|d| d.severity != Some(DiagnosticSeverity::ERROR)));
+    }
+
+    #[tokio::test]
+    async fn test_cargo_completion_version_mid_word() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::new(fetcher).await;
+
+        let (contents, pos) = cursor_fixture("[dependencies]\nserde = \"1.0$0.1\"\n");
+        let uri = server.open("Cargo.toml", &contents).await;
+        let items = server.completion(&uri, pos).await;
+
+        // The whole version is replaced, not only the text before the cursor
+        let edit = edit_of(&items[0]);
+        assert_eq!(edit.new_text, "1.0.150");
+        assert_eq!(
+            edit.range,
+            Range::new(Position::new(1, 9), Position::new(1, 14))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cargo_completion_feature_mid_word() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::new(fetcher).await;
+
+        // The typed "d" is also a part of "serde", which must be left alone
+        let (contents, pos) = cursor_fixture(
+            "[dependencies]\nserde = { version = \"1.0\", features = [\"d$0e\"] }\n",
+        );
+        let uri = server.open("Cargo.toml", &contents).await;
+        let items = server.completion(&uri, pos).await;
+
+        let edit = edit_of(&items[0]);
+        assert_eq!(items[0].label, "derive");
+        assert_eq!(
+            edit.range,
+            Range::new(Position::new(1, 40), Position::new(1, 42))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cargo_completion_name_declared() {
+        let server = TestServer::new(MockFetcher::new()).await;
+
+        let (contents, pos) =
+            cursor_fixture("[dependencies]\nserde_json = \"1\"\nserde_j$0 = \"1\"\n");
+        let uri = server.open("Cargo.toml", &contents).await;
+        let items = server.completion(&uri, pos).await;
+
+        // Crates that are already declared are marked and ranked last
+        let last = items.last().unwrap();
+        assert_eq!(last.label, "serde_json");
+        assert!(last
+            .detail
+            .as_ref()
+            .unwrap()
+            .contains("Already a dependency"));
+        assert!(items.len() > 1);
+
+        let config = server.config().get();
+        server.config().set(Config {
+            completion: CompletionConfig {
+                filter_declared: true,
+                ..config.completion.clone()
+            },
+            ..config
+        });
+        let items = server.completion(&uri, pos).await;
+        assert!(items.iter().all(|i| i.label != "serde_json"));
+    }
+
+    #[tokio::test]
+    async fn test_cargo_completion_name_declared_other_target() {
+        let server = TestServer::new(MockFetcher::new()).await;
+
+        // Declaring a crate for one platform does not declare it for the others
+        let (contents, pos) = cursor_fixture(
+            "[target.'cfg(unix)'.dependencies]\nserde_json = \"1\"\n[dependencies]\nserde_j$0 = \"1\"\n",
+        );
+        let uri = server.open("Cargo.toml", &contents).await;
+        let items = server.completion(&uri, pos).await;
+        let item = items.iter().find(|i| i.label == "serde_json").unwrap();
+        assert!(!item
+            .detail
+            .as_ref()
+            .is_some_and(|detail| detail.contains("Already a dependency")));
+    }
+
+    #[tokio::test]
+    async fn test_target_dependency_completion_version() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::new(fetcher).await;
+
+        let (contents, pos) = cursor_fixture(
+            "[target.x86_64-pc-windows-msvc.dependencies]\nserde = { version = \"1.0.1$0\" }\n",
+        );
+        let uri = server.open("Cargo.toml", &contents).await;
+        let items = server.completion(&uri, pos).await;
+
+        let labels = items.iter().map(|i| i.label.as_str()).collect::<Vec<_>>();
+        assert_eq!(labels, vec!["1.0.150", "=1.0.150", "1.0.100", "=1.0.100"]);
+    }
+
+    async fn foo_version_labels(version: &str) -> Vec<String> {
+        let fetcher = MockFetcher::new().with_response("/3/f/foo", FOO_INDEX);
+        let server = TestServer::new(fetcher).await;
+
+        let fixture = format!("[dependencies]\nfoo = \"{version}$0\"\n");
+        let (contents, pos) = cursor_fixture(&fixture);
+        let uri = server.open("Cargo.toml", &contents).await;
+        let items = server.completion(&uri, pos).await;
+        items
+            .into_iter()
+            .filter(|i| !i.label.starts_with('='))
+            .map(|i| i.label)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_cargo_completion_version_partial() {
+        assert_eq!(
+            foo_version_labels("1.").await,
+            vec!["1.20.0", "1.2.10", "1.2.3", "1.0.0"]
+        );
+        assert_eq!(foo_version_labels("1.2.").await, vec!["1.2.10", "1.2.3"]);
+        assert_eq!(
+            foo_version_labels("^1.").await,
+            vec!["1.20.0", "1.2.10", "1.2.3", "1.0.0"]
+        );
+    }
+
+    const BAR_INDEX: &str = concat!(
+        r#"{"name":"bar","vers":"1.0.0","deps":[],"features":{"old":[],"shared":[]}}"#,
+        "\n",
+        r#"{"name":"bar","vers":"2.0.0","deps":[],"features":{"new":[],"shared":[]}}"#,
+    );
+
+    #[tokio::test]
+    async fn test_cargo_completion_features_renamed_version() {
+        let fetcher = MockFetcher::new().with_response("/3/b/bar", BAR_INDEX);
+        let server = TestServer::new(fetcher).await;
+
+        // Features come from the renamed package, and only from versions matching the requirement
+        let complete = |version: &'static str| {
+            let server = &server;
+            async move {
+                let (contents, pos) = cursor_fixture(&format!(
+                    "[dependencies]\nrenamed = {{ package = \"bar\", version = \"{version}\", features = [\"$0\"] }}\n"
+                ));
+                let uri = server.open("Cargo.toml", &contents).await;
+                let items = server.completion(&uri, pos).await;
+                items.into_iter().map(|i| i.label).collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(complete("1").await, vec!["old", "shared"]);
+        assert_eq!(complete("2.0").await, vec!["new", "shared"]);
+
+        // No matching versions, such as while typing, falls back to all features
+        assert_eq!(complete("3").await, vec!["new", "old", "shared"]);
+    }
+
+    #[tokio::test]
+    async fn test_cargo_completion_version_large_history() {
+        // Versions are not published in order, with patches for old minor versions last
+        let index = (0..100)
+            .flat_map(|patch| (0..30).map(move |minor| format!("0.{minor}.{patch}")))
+            .map(|v| format!(r#"{{"name":"libc","vers":"{v}","deps":[],"features":{{}}}}"#))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let fetcher = MockFetcher::new().with_response("/li/bc/libc", index);
+        let server = TestServer::new(fetcher).await;
+
+        let plain_labels = |items: Vec<CompletionItem>| {
+            items
+                .into_iter()
+                .map(|i| i.label)
+                .filter(|l| !l.starts_with('='))
+                .collect::<Vec<_>>()
+        };
+
+        // Only the highest matching versions are shown, no matter when they were published
+        let (contents, pos) = cursor_fixture("[dependencies]\nlibc = \"0.$0\"\n");
+        let uri = server.open("Cargo.toml", &contents).await;
+        let labels = plain_labels(server.completion(&uri, pos).await);
+        assert_eq!(labels.len(), 64);
+        assert_eq!(labels[0], "0.29.99");
+        assert_eq!(labels[63], "0.29.36");
+
+        let (contents, pos) = cursor_fixture("[dependencies]\nlibc = \"0.2.1$0\"\n");
+        let uri = server.open("partial/Cargo.toml", &contents).await;
+        let labels = plain_labels(server.completion(&uri, pos).await);
+        let mut expected = (10..20)
+            .rev()
+            .map(|p| format!("0.2.{p}"))
+            .collect::<Vec<_>>();
+        expected.push(String::from("0.2.1"));
+        assert_eq!(labels, expected);
+    }
+
+    #[tokio::test]
+    async fn test_cargo_completion_version_variants_multi_byte() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::new(fetcher).await;
+
+        // The variants replace the whole version, which starts after the "é"
+        let (contents, pos) = cursor_fixture(
+            "[dependencies]\n\"sérde\" = { package = \"serde\", version = \"1.0.1$0\" }\n",
+        );
+        let uri = server.open("Cargo.toml", &contents).await;
+        let items = server.completion(&uri, pos).await;
+        assert_eq!(items[1].label, "=1.0.150");
+        let edit = edit_of(&items[1]);
+        assert_eq!(edit.new_text, "=1.0.150");
+        assert_eq!(
+            edit.range,
+            Range::new(Position::new(1, 42), Position::new(1, 47))
+        );
+    }
 }
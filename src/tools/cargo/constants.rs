@@ -69,3 +69,187 @@ pub fn top_crates_io_packages_prefixed(prefix: &str, limit: usize) -> Vec<&Crate
 
     top.iter(prefix).take(limit).collect()
 }
+
+/**
+    A key that may be used in the table of a Cargo dependency,
+    along with a snippet and plain text for its default value.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct CargoDependencyKey {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub value_snippet: &'static str,
+    pub value_plain: &'static str,
+}
+
+impl CargoDependencyKey {
+    const fn new(
+        name: &'static str,
+        description: &'static str,
+        value_snippet: &'static str,
+        value_plain: &'static str,
+    ) -> Self {
+        Self {
+            name,
+            description,
+            value_snippet,
+            value_plain,
+        }
+    }
+}
+
+pub const CARGO_DEPENDENCY_KEYS: &[CargoDependencyKey] = &[
+    CargoDependencyKey::new("version", "The version requirement", "\"$1\"", "\"\""),
+    CargoDependencyKey::new("features", "Features to enable", "[$0]", "[]"),
+    CargoDependencyKey::new(
+        "default-features",
+        "If default features should be enabled",
+        "${1|false,true|}",
+        "false",
+    ),
+    CargoDependencyKey::new("optional", "If the dependency is optional", "true", "true"),
+    CargoDependencyKey::new("path", "A local path to the crate", "\"$1\"", "\"\""),
+    CargoDependencyKey::new("git", "A git repository url", "\"$1\"", "\"\""),
+    CargoDependencyKey::new("branch", "The git branch to use", "\"$1\"", "\"\""),
+    CargoDependencyKey::new("tag", "The git tag to use", "\"$1\"", "\"\""),
+    CargoDependencyKey::new("rev", "The git revision to use", "\"$1\"", "\"\""),
+    CargoDependencyKey::new(
+        "package",
+        "The real name of a renamed crate",
+        "\"$1\"",
+        "\"\"",
+    ),
+    CargoDependencyKey::new("registry", "An alternate registry", "\"$1\"", "\"\""),
+    CargoDependencyKey::new(
+        "workspace",
+        "Inherit the dependency from the workspace",
+        "true",
+        "true",
+    ),
+];
+
+/**
+    A combination of keys in the table of a Cargo dependency that is either
+    rejected by Cargo, or almost certainly not what the user intended.
+
+    The conflict applies when all of `keys` are present, and none of
+    `unless` are present - every one of `keys` is then flagged.
+
+    These rules are intentionally conservative, and only
+    cover the combinations that are clearly problematic.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct CargoKeyConflict {
+    pub keys: &'static [&'static str],
+    pub unless: &'static [&'static str],
+    pub message: &'static str,
+    pub is_error: bool,
+}
+
+impl CargoKeyConflict {
+    const fn error(keys: &'static [&'static str], message: &'static str) -> Self {
+        Self {
+            keys,
+            unless: &[],
+            message,
+            is_error: true,
+        }
+    }
+
+    const fn warning(
+        keys: &'static [&'static str],
+        unless: &'static [&'static str],
+        message: &'static str,
+    ) -> Self {
+        Self {
+            keys,
+            unless,
+            message,
+            is_error: false,
+        }
+    }
+}
+
+pub const CARGO_KEY_CONFLICTS: &[CargoKeyConflict] = &[
+    // A dependency can only have a single source
+    CargoKeyConflict::error(
+        &["path", "git"],
+        "Only one of `path` or `git` may be specified",
+    ),
+    CargoKeyConflict::error(
+        &["git", "registry"],
+        "Only one of `git` or `registry` may be specified",
+    ),
+    // A git dependency can only point at a single reference
+    CargoKeyConflict::error(
+        &["branch", "tag"],
+        "Only one of `branch`, `tag`, or `rev` may be specified",
+    ),
+    CargoKeyConflict::error(
+        &["branch", "rev"],
+        "Only one of `branch`, `tag`, or `rev` may be specified",
+    ),
+    CargoKeyConflict::error(
+        &["tag", "rev"],
+        "Only one of `branch`, `tag`, or `rev` may be specified",
+    ),
+    // Git references are silently ignored by Cargo without a git source
+    CargoKeyConflict::warning(&["branch"], &["git"], "`branch` is ignored without `git`"),
+    CargoKeyConflict::warning(&["tag"], &["git"], "`tag` is ignored without `git`"),
+    CargoKeyConflict::warning(&["rev"], &["git"], "`rev` is ignored without `git`"),
+    // A version for a moving git reference may stop matching at any time
+    CargoKeyConflict::warning(
+        &["version", "git"],
+        &["rev", "tag"],
+        "`version` together with `git` may stop matching when the repository changes, \
+        consider pinning a `rev` or `tag`",
+    ),
+    // Inherited dependencies get their source and version from the workspace
+    CargoKeyConflict::error(
+        &["workspace", "version"],
+        "`version` can not be used together with `workspace`",
+    ),
+    CargoKeyConflict::error(
+        &["workspace", "path"],
+        "`path` can not be used together with `workspace`",
+    ),
+    CargoKeyConflict::error(
+        &["workspace", "git"],
+        "`git` can not be used together with `workspace`",
+    ),
+];
+
+/**
+    Fields of the `[package]` table, which are never crate names, even
+    though a bare key such as `edition` looks just like the start of one.
+*/
+pub const CARGO_PACKAGE_FIELDS: &[&str] = &[
+    "name",
+    "version",
+    "authors",
+    "edition",
+    "rust-version",
+    "description",
+    "documentation",
+    "readme",
+    "homepage",
+    "repository",
+    "license",
+    "license-file",
+    "keywords",
+    "categories",
+    "workspace",
+    "build",
+    "links",
+    "exclude",
+    "include",
+    "publish",
+    "metadata",
+    "default-run",
+    "autolib",
+    "autobins",
+    "autoexamples",
+    "autotests",
+    "autobenches",
+    "resolver",
+];
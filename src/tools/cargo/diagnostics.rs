@@ -1,46 +1,59 @@
-use semver::VersionReq;
+use semver::{Op, VersionReq};
 
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tracing::debug;
 
-use crate::parser::Dependency;
+use crate::parser::{
+    Dependency, DependencyKind, DependencySource, DuplicateTable, Feature, TableField,
+};
+use crate::server::Config;
 use crate::util::{VersionReqExt, Versioned};
 
 use super::super::shared::*;
+use super::super::ToolName;
+use super::constants::CARGO_KEY_CONFLICTS;
 use super::crates::models::IndexMetadata;
-use super::util::get_features;
+use super::util::{get_dependency_metadatas_newest, get_edition, get_features};
 use super::{Clients, Document};
 
+/*
+    Editions before 2024 create an implicit feature for every
+    optional dependency that is not referenced using `dep:`
+*/
+const EDITION_WITHOUT_IMPLICIT_FEATURES: u32 = 2024;
+
 // TODO: Enable feature diagnostics when we have a way to
 // actually fetch *all* features from the index or the api
 const SHOW_FEATURE_DIAGNOSTICS: bool = false;
 
 pub async fn get_cargo_diagnostics(
     clients: &Clients,
+    config: &Config,
     doc: &Document,
     dep: &Dependency,
-) -> Result<Vec<Diagnostic>> {
-    let metas = match clients
-        .crates
-        .get_sparse_index_crate_metadatas(dep.name().unquoted())
-        .await
-    {
+) -> Result<DependencyDiagnostics> {
+    // Local crates and git dependencies without a version are never looked up
+    // in the index, and whether or not local crates exist is checked without the network
+    let spec = dep.spec().map(|s| &s.contents);
+    if spec.is_some_and(|s| s.source != DependencySource::Registry && s.version.is_none()) {
+        return Ok(Vec::new().into());
+    }
+
+    // NOTE: None of the checks below need more than the newest few versions
+    let metas = match get_dependency_metadatas_newest(clients, doc, dep).await {
         Ok(v) => v,
         Err(e) => {
             if e.is_not_found_error() {
-                return Ok(vec![Diagnostic {
-                    source: Some(String::from("Cargo")),
-                    range: dep.name().range,
-                    message: format!(
-                        "No package exists with the name `{}`",
-                        dep.name().unquoted()
-                    ),
-                    severity: Some(DiagnosticSeverity::ERROR),
-                    ..Default::default()
-                }]);
+                return Ok(vec![get_cargo_diagnostic_not_found(clients, doc, dep).await].into());
+            } else if e.is_auth_error() {
+                // NOTE: A token may be added at any time, so this is checked again
+                let auth = get_cargo_diagnostic_registry_auth(dep)
+                    .into_iter()
+                    .collect();
+                return Ok(DependencyDiagnostics::incomplete(auth));
             } else {
-                return Ok(Vec::new());
+                return Ok(DependencyDiagnostics::incomplete(Vec::new()));
             }
         }
     };
@@ -48,7 +61,110 @@ pub async fn get_cargo_diagnostics(
     let mut diagnostics = Vec::new();
     diagnostics.extend(get_cargo_diagnostics_version(clients, doc, dep, &metas).await?);
     diagnostics.extend(get_cargo_diagnostics_features(clients, doc, dep, &metas).await?);
-    Ok(diagnostics)
+    if config.diagnostics.partial_versions {
+        diagnostics.extend(get_cargo_diagnostics_partial_version(doc, dep, &metas));
+    }
+    if let Some(severity) = config.diagnostics.deprecated.severity() {
+        diagnostics.extend(get_cargo_diagnostic_deprecated(clients, dep, severity).await);
+    }
+    Ok(diagnostics.into())
+}
+
+/**
+    Creates a diagnostic for a package that is deprecated on crates.io,
+    which is tagged so that editors can render it as struck through.
+*/
+async fn get_cargo_diagnostic_deprecated(
+    clients: &Clients,
+    dep: &Dependency,
+    severity: DiagnosticSeverity,
+) -> Option<Diagnostic> {
+    // Only crates.io knows about deprecations, other sources don't
+    let spec = dep.spec().map(|s| &s.contents);
+    if spec.is_some_and(|s| s.registry.is_some() || s.source != DependencySource::Registry) {
+        return None;
+    }
+
+    let package = dep.package_name();
+    let crate_data_single = clients
+        .crates
+        .get_crate_data(package.unquoted())
+        .await
+        .ok()?;
+    let deprecation = crate_data_single.inner.deprecation()?;
+
+    let message = match deprecation.successor {
+        Some(successor) => format!(
+            "The package `{}` is deprecated, consider using `{successor}` instead",
+            package.unquoted()
+        ),
+        None => format!("The package `{}` is deprecated", package.unquoted()),
+    };
+    Some(Diagnostic {
+        source: Some(String::from("Cargo")),
+        range: package.range,
+        message,
+        severity: Some(severity),
+        tags: Some(vec![DiagnosticTag::DEPRECATED]),
+        ..DiagnosticCode::Deprecated.diagnostic()
+    })
+}
+
+/**
+    Creates a diagnostic for a dependency from an alternate registry that
+    responded that its index can not be read without a valid token, which
+    would otherwise look like the registry has no versions at all.
+*/
+fn get_cargo_diagnostic_registry_auth(dep: &Dependency) -> Option<Diagnostic> {
+    let registry = dep.spec()?.contents.registry.as_ref()?;
+    Some(Diagnostic {
+        source: Some(String::from("Cargo")),
+        range: registry.range,
+        message: format!(
+            "Authentication required for registry `{}`.\
+            \nAdd a token for it to Cargo's `credentials.toml`",
+            registry.unquoted()
+        ),
+        severity: Some(DiagnosticSeverity::WARNING),
+        ..DiagnosticCode::RegistryAuth.diagnostic()
+    })
+}
+
+/**
+    Creates a diagnostic for a package that does not exist in the index,
+    with suggestions for close matches found using the crates.io search.
+*/
+async fn get_cargo_diagnostic_not_found(
+    clients: &Clients,
+    doc: &Document,
+    dep: &Dependency,
+) -> Diagnostic {
+    let package = dep.package_name();
+    let diag = Diagnostic {
+        source: Some(String::from("Cargo")),
+        range: package.range,
+        message: format!("No package exists with the name `{}`", package.unquoted()),
+        severity: Some(DiagnosticSeverity::ERROR),
+        ..DiagnosticCode::UnknownPackage.diagnostic()
+    };
+
+    // The crates.io search knows nothing about packages in other registries
+    if dep.spec().is_some_and(|s| s.contents.registry.is_some()) {
+        return diag;
+    }
+
+    let Ok(crates) = clients.crates.search_crates(package.unquoted()).await else {
+        return diag;
+    };
+
+    let names = crates.inner.into_iter().map(|m| m.name.to_string());
+    with_suggestions(
+        diag,
+        doc.uri(),
+        package.unquoted_range(),
+        package.unquoted(),
+        names,
+    )
 }
 
 async fn get_cargo_diagnostics_version(
@@ -80,12 +196,12 @@ async fn get_cargo_diagnostics_version(
                 spec_version.unquoted()
             ),
             severity: Some(DiagnosticSeverity::ERROR),
-            ..Default::default()
+            ..DiagnosticCode::UnknownVersion.diagnostic()
         }]);
     }
 
     // Try to find the latest non-prerelease version
-    let latest_name = dep.name().unquoted().to_string();
+    let latest_name = dep.package_name().unquoted().to_string();
     let Some(latest_version) = version_min.extract_latest_version(metas.iter().cloned()) else {
         debug!("Failed to get latest crates.io version for '{latest_name}'");
         return Ok(Vec::new());
@@ -100,6 +216,11 @@ async fn get_cargo_diagnostics_version(
             source_text: spec_version.quoted().to_string(),
             version_current: version_min.to_string(),
             version_latest: latest_version_string.to_string(),
+            package_manager: PackageManagerCommand::for_package(
+                ToolName::Cargo,
+                doc.uri(),
+                &latest_name,
+            ),
         };
 
         return Ok(vec![Diagnostic {
@@ -117,16 +238,67 @@ async fn get_cargo_diagnostics_version(
                 }
                 .into(),
             ),
-            ..Default::default()
+            ..DiagnosticCode::Outdated.diagnostic()
         }]);
     }
 
     Ok(Vec::new())
 }
 
+fn get_cargo_diagnostics_partial_version(
+    doc: &Document,
+    dep: &Dependency,
+    metas: &[IndexMetadata],
+) -> Option<Diagnostic> {
+    let spec_version = dep.spec().and_then(|s| s.contents.version.as_ref())?;
+    let version_req = VersionReq::parse(spec_version.unquoted()).ok()?;
+
+    // Only plain caret requirements with a missing minor or patch
+    // version are considered partial, others have special meanings
+    let [comparator] = version_req.comparators.as_slice() else {
+        return None;
+    };
+    if comparator.op != Op::Caret || comparator.patch.is_some() {
+        return None;
+    }
+
+    // Expand using the latest published version that still matches
+    let version_expanded = metas
+        .iter()
+        .filter_map(|meta| meta.parse_version().ok())
+        .filter(|version| version_req.matches(version))
+        .max()?;
+
+    let metadata = CodeActionMetadata::ExpandVersion {
+        edit_range: spec_version.range,
+        source_uri: doc.uri().clone(),
+        source_text: spec_version.quoted().to_string(),
+        version_current: spec_version.unquoted().to_string(),
+        version_expanded: version_expanded.to_string(),
+    };
+
+    Some(Diagnostic {
+        source: Some(String::from("Cargo")),
+        range: spec_version.range,
+        message: format!(
+            "Version requirement `{}` is not fully specified",
+            spec_version.unquoted()
+        ),
+        severity: Some(DiagnosticSeverity::HINT),
+        data: Some(
+            ResolveContext {
+                uri: doc.uri().clone(),
+                value: metadata,
+            }
+            .into(),
+        ),
+        ..DiagnosticCode::PartialVersion.diagnostic()
+    })
+}
+
 async fn get_cargo_diagnostics_features(
     clients: &Clients,
-    _doc: &Document,
+    doc: &Document,
     dep: &Dependency,
     _metas: &[IndexMetadata],
 ) -> Result<Vec<Diagnostic>> {
@@ -138,7 +310,7 @@ async fn get_cargo_diagnostics_features(
         return Ok(Vec::new());
     }
 
-    let known_features = get_features(clients, dep).await;
+    let known_features = get_features(clients, doc, dep).await;
 
     let mut diagnostics = Vec::new();
     for feat in features.contents.iter() {
@@ -156,10 +328,295 @@ async fn get_cargo_diagnostics_features(
                     None => format!("Unknown feature `{}`", feat.unquoted()),
                 },
                 severity: Some(DiagnosticSeverity::ERROR),
-                ..Default::default()
+                ..DiagnosticCode::UnknownFeature.diagnostic()
             });
         }
     }
 
     Ok(diagnostics)
 }
+
+/**
+    Creates diagnostics for optional dependencies that are not activated by any feature.
+
+    Note that in editions where Cargo creates an implicit feature for optional
+    dependencies, these dependencies can always be activated, and no diagnostics
+    will be created - see `EDITION_WITHOUT_IMPLICIT_FEATURES` for more details.
+*/
+pub fn get_cargo_diagnostics_optional(
+    dependencies: &[Dependency],
+    features: &[Feature],
+    package_fields: &[TableField],
+) -> Vec<Diagnostic> {
+    let edition = get_edition(package_fields);
+    if edition < EDITION_WITHOUT_IMPLICIT_FEATURES {
+        return Vec::new();
+    }
+
+    let is_activated = |name: &str| {
+        features.iter().any(|feat| {
+            feat.values.contents.iter().any(|value| {
+                // NOTE: Weak dependency features such as `dep?/feat`
+                // do not activate the dependency, only `dep/feat` does
+                let value = value.unquoted();
+                if let Some(dep) = value.strip_prefix("dep:") {
+                    dep == name
+                } else if let Some((dep, _)) = value.split_once('/') {
+                    dep == name
+                } else {
+                    false
+                }
+            })
+        })
+    };
+
+    dependencies
+        .iter()
+        .filter(|dep| matches!(dep.kind(), DependencyKind::Default | DependencyKind::Build))
+        .filter_map(|dep| {
+            let optional = dep.spec()?.contents.optional.as_ref()?;
+            if !optional.contents || is_activated(dep.name().unquoted()) {
+                return None;
+            }
+            Some(Diagnostic {
+                source: Some(String::from("Cargo")),
+                range: optional.range,
+                message: format!(
+                    "Optional dependency `{0}` is not activated by any feature.\
+                    \nAdd `\"dep:{0}\"` to a feature, or remove `optional = true`",
+                    dep.name().unquoted()
+                ),
+                severity: Some(DiagnosticSeverity::HINT),
+                ..DiagnosticCode::UnusedOptional.diagnostic()
+            })
+        })
+        .collect()
+}
+
+pub fn get_cargo_diagnostics_duplicate_tables(
+    doc: &Document,
+    duplicates: &[DuplicateTable],
+) -> Vec<Diagnostic> {
+    duplicates
+        .iter()
+        .map(|table| Diagnostic {
+            source: Some(String::from("Cargo")),
+            range: table.duplicate.range,
+            message: format!(
+                "Duplicate table `[{}]`, which is not allowed in TOML.\
+                \nMove its contents into the first `[{}]` table",
+                table.duplicate.quoted(),
+                table.first.quoted(),
+            ),
+            severity: Some(DiagnosticSeverity::WARNING),
+            related_information: Some(vec![DiagnosticRelatedInformation {
+                location: Location {
+                    uri: doc.uri().clone(),
+                    range: table.first.range,
+                },
+                message: String::from("First defined here"),
+            }]),
+            ..DiagnosticCode::DuplicateTable.diagnostic()
+        })
+        .collect()
+}
+
+pub fn get_cargo_diagnostics_keys(dependencies: &[Dependency]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for keys in dependencies
+        .iter()
+        .filter_map(|dep| dep.spec()?.contents.keys.as_ref())
+    {
+        let find = |name: &str| keys.contents.iter().find(|k| k.unquoted() == name);
+        for conflict in CARGO_KEY_CONFLICTS {
+            if conflict.unless.iter().any(|key| find(key).is_some()) {
+                continue;
+            }
+            let Some(offending) = conflict
+                .keys
+                .iter()
+                .map(|key| find(key))
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            diagnostics.extend(offending.into_iter().map(|key| Diagnostic {
+                source: Some(String::from("Cargo")),
+                range: key.range,
+                message: conflict.message.to_string(),
+                severity: Some(if conflict.is_error {
+                    DiagnosticSeverity::ERROR
+                } else {
+                    DiagnosticSeverity::WARNING
+                }),
+                ..DiagnosticCode::ConflictingKeys.diagnostic()
+            }));
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::parser::{query_cargo_toml_dependencies, TreeSitterDocument};
+    use crate::server::testing::{MockFetcher, TestServer, SERDE_INDEX};
+    use crate::server::{Config, DiagnosticLevel, DiagnosticsConfig};
+    use crate::util::FixtureDir;
+
+    use super::*;
+
+    fn test_keys(dependency: &str, expected: Vec<(&str, DiagnosticSeverity)>) {
+        let contents = format!("[dependencies]\n{dependency}\n");
+        let file =
+            TreeSitterDocument::new_file(Path::new("Cargo.toml"), contents.as_str()).unwrap();
+        let dependencies = query_cargo_toml_dependencies(&file);
+        let diagnostics = get_cargo_diagnostics_keys(&dependencies);
+
+        let flagged = diagnostics
+            .iter()
+            .map(|diag| {
+                let start = diag.range.start.character as usize;
+                let end = diag.range.end.character as usize;
+                (&dependency[start..end], diag.severity.unwrap())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(flagged, expected);
+    }
+
+    #[test]
+    fn test_keys_valid() {
+        test_keys(r#"foo = { version = "1.0", features = ["bar"] }"#, vec![]);
+        test_keys(
+            r#"foo = { git = "https://example.com", rev = "abc" }"#,
+            vec![],
+        );
+        test_keys(r#"foo = { path = "../foo", version = "1.0" }"#, vec![]);
+        test_keys(r#"foo = { workspace = true, optional = true }"#, vec![]);
+    }
+
+    #[test]
+    fn test_keys_path_and_git() {
+        test_keys(
+            r#"foo = { path = "../foo", git = "https://example.com" }"#,
+            vec![
+                ("path", DiagnosticSeverity::ERROR),
+                ("git", DiagnosticSeverity::ERROR),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_keys_version_and_git() {
+        test_keys(
+            r#"foo = { version = "1.0", git = "https://example.com" }"#,
+            vec![
+                ("version", DiagnosticSeverity::WARNING),
+                ("git", DiagnosticSeverity::WARNING),
+            ],
+        );
+        test_keys(
+            r#"foo = { version = "1.0", git = "https://example.com", tag = "v1.0" }"#,
+            vec![],
+        );
+    }
+
+    #[test]
+    fn test_keys_reference_without_git() {
+        test_keys(
+            r#"foo = { version = "1.0", branch = "main" }"#,
+            vec![("branch", DiagnosticSeverity::WARNING)],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cargo_path_dependency_diagnostics() {
+        let base = FixtureDir::new("path-dependency-diagnostics");
+        base.write("core/Cargo.toml", "");
+        let server = TestServer::new(MockFetcher::new()).await;
+
+        let contents = "[dependencies]\n\
+            core = { path = \"../core\" }\n\
+            utils = { path = \"./../utils\" }\n";
+        let uri = Url::from_file_path(base.join("app").join("Cargo.toml")).unwrap();
+        let uri = server.open_uri(uri, contents).await;
+        let diagnostics = server.diagnostics(&uri).await;
+
+        // Only the missing crate is reported, using its normalized path
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start.line, 2);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+        let missing = format!("`{}`", base.join("utils").display());
+        assert!(diagnostics[0].message.contains(&missing));
+    }
+
+    #[tokio::test]
+    async fn test_untitled_path_dependency_diagnostics() {
+        let server = TestServer::new(MockFetcher::new()).await;
+
+        let contents = "[dependencies]\nutils = { path = \"../utils\" }\n";
+        let uri = Url::parse("untitled:/drafts/Cargo.toml").unwrap();
+        let uri = server.open_uri(uri, contents).await;
+        assert!(server.diagnostics(&uri).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_target_dependency_diagnostics() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::new(fetcher).await;
+
+        let contents = "[target.'cfg(unix)'.dependencies.serde]\nversion = \"0.9.0\"\n";
+        let uri = server.open("Cargo.toml", contents).await;
+        let diagnostics = server.diagnostics(&uri).await;
+
+        assert_eq!(diagnostics.len(), 1, "diagnostics: {diagnostics:#?}");
+        assert!(diagnostics[0]
+            .message
+            .starts_with("A newer version of `serde` is available."));
+        assert_eq!(diagnostics[0].range.start, Position::new(1, 10));
+        assert!(diagnostics[0].data.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_deprecated_dependency_diagnostics() {
+        let crate_data = r#"{"crate":{
+            "name":"serde","description":"DEPRECATED: use `serde2` instead",
+            "created_at":"","updated_at":"","downloads":0,"recent_downloads":0
+        }}"#;
+        let fetcher = MockFetcher::new()
+            .with_response("/se/rd/serde", SERDE_INDEX)
+            .with_response("/api/v1/crates/serde?", crate_data);
+        let server = TestServer::new(fetcher.clone()).await;
+
+        // Deprecations are checked with one request per crate, so only when enabled
+        let uri = server
+            .open("Cargo.toml", "[dependencies]\nserde = \"1.0.150\"\n")
+            .await;
+        assert!(server.diagnostics(&uri).await.is_empty());
+        assert!(fetcher
+            .requested()
+            .iter()
+            .all(|url| !url.contains("/api/v1/crates/serde")));
+
+        let config = server.config().get();
+        server.config().set(Config {
+            diagnostics: DiagnosticsConfig {
+                deprecated: DiagnosticLevel::Warning,
+                ..config.diagnostics.clone()
+            },
+            ..config
+        });
+        let doc = server.documents().get(&uri).map(|d| d.clone());
+        doc.expect("missing document").diagnostics_cache().clear();
+        let diagnostics = server.diagnostics(&uri).await;
+
+        assert_eq!(diagnostics.len(), 1, "diagnostics: {diagnostics:#?}");
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(
+            diagnostics[0].message,
+            "The package `serde` is deprecated, consider using `serde2` instead"
+        );
+        assert_eq!(diagnostics[0].tags, Some(vec![DiagnosticTag::DEPRECATED]));
+    }
+}
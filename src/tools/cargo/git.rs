@@ -0,0 +1,236 @@
+use std::path::Path;
+
+use tokio::fs;
+use tower_lsp::jsonrpc::Result;
+use tower_lsp::lsp_types::*;
+use tracing::trace;
+
+use crate::parser::{Dependency, Node, TableField};
+use crate::tools::MarkdownBuilder;
+
+use super::{Clients, Document};
+
+/**
+    How many characters of a locked commit hash to show, same as `cargo tree`.
+*/
+const SHORT_COMMIT_LENGTH: usize = 8;
+
+pub async fn get_cargo_hover_git(
+    clients: &Clients,
+    doc: &Document,
+    dep: &Dependency,
+    url: &Node<String>,
+    reference: Option<&TableField>,
+) -> Result<Option<Hover>> {
+    let package = dep.package_name().unquoted();
+
+    // The lockfile knows exactly which commit is used, which is
+    // always preferred over a branch or tag that may have moved
+    let locked = match doc.uri().to_file_path() {
+        Ok(path) => read_locked_commit(&path, package, url.unquoted()).await,
+        Err(_) => None,
+    };
+    let rev = locked
+        .as_deref()
+        .or_else(|| reference.map(|r| r.value.unquoted()));
+
+    trace!("Hovering: {package} from git at {}", url.unquoted());
+    let mut version = None;
+    if let Some((owner, repository)) = parse_github_url(url.unquoted()) {
+        let file = match rev {
+            Some(rev) => {
+                clients
+                    .github
+                    .get_repository_file_at(&owner, &repository, "Cargo.toml", rev)
+                    .await
+            }
+            None => {
+                clients
+                    .github
+                    .get_repository_file(&owner, &repository, "Cargo.toml")
+                    .await
+            }
+        };
+        if let Ok(bytes) = file {
+            version = manifest_version(&String::from_utf8_lossy(&bytes), package);
+        }
+    }
+
+    let mut md = MarkdownBuilder::new();
+    md.h2(dep.name().unquoted());
+    if dep.is_renamed() {
+        md.p(format!("Aliased from `{package}`"));
+        md.br();
+    }
+    if let Some(version) = version {
+        md.version(version);
+        md.br();
+    }
+    md.p(format!("Git repository at `{}`", url.unquoted()));
+    if let Some(reference) = reference {
+        let kind = match reference.name.unquoted() {
+            "rev" => "revision",
+            other => other,
+        };
+        md.p(format!("Using {kind} `{}`", reference.value.unquoted()));
+    }
+    if let Some(locked) = &locked {
+        let short = locked.get(..SHORT_COMMIT_LENGTH).unwrap_or(locked);
+        md.p(format!("Locked to commit `{short}`"));
+    }
+
+    Ok(Some(Hover {
+        range: Some(dep.range()),
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: md.build(),
+        }),
+    }))
+}
+
+/**
+    Parses the owner and repository out of a GitHub url, such as
+    `https://github.com/owner/repo.git` or `ssh://git@github.com/owner/repo`.
+
+    Returns `None` for any other git host, since files can't be fetched from them.
+*/
+fn parse_github_url(url: &str) -> Option<(String, String)> {
+    let (_, rest) = url.split_once("://")?;
+    let rest = rest.split(['?', '#']).next()?;
+    let rest = rest.rsplit_once('@').map_or(rest, |(_, host)| host);
+    let mut parts = rest.trim_end_matches('/').split('/');
+    let host = parts.next()?.to_ascii_lowercase();
+    if host != "github.com" && host != "www.github.com" {
+        return None;
+    }
+    let owner = parts.next().filter(|o| !o.is_empty())?;
+    let repository = parts.next()?.trim_end_matches(".git");
+    if repository.is_empty() || parts.next().is_some() {
+        return None;
+    }
+    Some((owner.to_string(), repository.to_string()))
+}
+
+/**
+    Normalizes a git url for comparisons, since a trailing slash or `.git`
+    suffix does not change the repository, and neither does case on GitHub.
+*/
+fn normalize_git_url(url: &str) -> String {
+    url.trim_end_matches('/')
+        .trim_end_matches(".git")
+        .to_ascii_lowercase()
+}
+
+/**
+    Reads the commit that a git dependency is locked to, from the
+    closest `Cargo.lock` in or above the directory of the manifest,
+    which is where Cargo places it for both packages and workspaces.
+*/
+async fn read_locked_commit(manifest_path: &Path, package: &str, url: &str) -> Option<String> {
+    for dir in manifest_path.ancestors().skip(1) {
+        if let Ok(contents) = fs::read_to_string(dir.join("Cargo.lock")).await {
+            return locked_commit(&contents, package, url);
+        }
+    }
+    None
+}
+
+/**
+    Finds the commit that a git dependency is locked to in the contents of a
+    lockfile, where sources look like `git+https://host/repo?rev=main#<commit>`.
+*/
+fn locked_commit(lockfile: &str, package: &str, url: &str) -> Option<String> {
+    let lockfile = lockfile.parse::<toml::Table>().ok()?;
+    let url = normalize_git_url(url);
+    lockfile
+        .get("package")?
+        .as_array()?
+        .iter()
+        .filter_map(|p| p.as_table())
+        .filter(|p| p.get("name").and_then(|n| n.as_str()) == Some(package))
+        .filter_map(|p| p.get("source")?.as_str()?.strip_prefix("git+"))
+        .find_map(|source| {
+            let (source_url, commit) = source.split_once('#')?;
+            let source_url = source_url.split('?').next()?;
+            (normalize_git_url(source_url) == url).then(|| commit.to_string())
+        })
+}
+
+/**
+    Gets the version of the given package from the contents of its manifest,
+    following `version.workspace = true` to the workspace of the manifest.
+
+    Returns `None` if the manifest is for a different package, such as
+    for the root of a workspace that contains the package somewhere else.
+*/
+fn manifest_version(manifest: &str, package: &str) -> Option<String> {
+    let manifest = manifest.parse::<toml::Table>().ok()?;
+    let fields = manifest.get("package")?.as_table()?;
+    if fields.get("name")?.as_str()? != package {
+        return None;
+    }
+    match fields.get("version")? {
+        toml::Value::String(version) => Some(version.clone()),
+        toml::Value::Table(inherited) if inherited.get("workspace")?.as_bool()? => manifest
+            .get("workspace")?
+            .get("package")?
+            .get("version")?
+            .as_str()
+            .map(ToString::to_string),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_github_url() {
+        let parsed = parse_github_url;
+        let expected = Some((String::from("foo"), String::from("bar")));
+        assert_eq!(parsed("https://github.com/foo/bar"), expected);
+        assert_eq!(parsed("https://github.com/foo/bar.git/"), expected);
+        assert_eq!(parsed("ssh://git@github.com/foo/bar.git"), expected);
+        assert_eq!(parsed("https://gitlab.com/foo/bar"), None);
+        assert_eq!(parsed("https://github.com/foo"), None);
+    }
+
+    #[test]
+    fn test_locked_commit() {
+        let lockfile = r#"
+[[package]]
+name = "bar"
+version = "0.1.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "bar"
+version = "0.2.0"
+source = "git+https://github.com/foo/bar?branch=main#0123456789abcdef"
+"#;
+        assert_eq!(
+            locked_commit(lockfile, "bar", "https://github.com/foo/bar.git").as_deref(),
+            Some("0123456789abcdef")
+        );
+        assert_eq!(
+            locked_commit(lockfile, "bar", "https://github.com/other/bar"),
+            None
+        );
+        assert_eq!(
+            locked_commit(lockfile, "baz", "https://github.com/foo/bar"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_manifest_version() {
+        let manifest = "[package]\nname = \"bar\"\nversion = \"0.2.0\"\n";
+        assert_eq!(manifest_version(manifest, "bar").as_deref(), Some("0.2.0"));
+        assert_eq!(manifest_version(manifest, "baz"), None);
+
+        let inherited = "[workspace.package]\nversion = \"1.2.3\"\n\n\
+            [package]\nname = \"bar\"\nversion.workspace = true\n";
+        assert_eq!(manifest_version(inherited, "bar").as_deref(), Some("1.2.3"));
+    }
+}
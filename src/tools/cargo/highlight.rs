@@ -0,0 +1,50 @@
+use tower_lsp::lsp_types::*;
+
+use crate::parser::{Dependency, Feature};
+
+use super::occurrences::{
+    find_dependency_occurrences, find_feature_occurrences, FeatureValue, Occurrence,
+};
+
+pub fn get_cargo_document_highlights(
+    features: &[Feature],
+    dependencies: &[Dependency],
+    pos: Position,
+) -> Vec<DocumentHighlight> {
+    let occurrences = if let Some(dep) =
+        Dependency::find_at_pos(dependencies, pos).filter(|dep| dep.name().contains(pos))
+    {
+        find_dependency_occurrences(features, dependencies, dep.name().unquoted())
+    } else if let Some(feature) = Feature::find_at_pos(features, pos) {
+        if feature.name.contains(pos) {
+            find_feature_occurrences(features, dependencies, feature.name.unquoted())
+        } else if let Some(value) = feature.values.contents.iter().find(|v| v.contains(pos)) {
+            match FeatureValue::classify(value.unquoted(), features, dependencies) {
+                FeatureValue::Feature(name) => {
+                    find_feature_occurrences(features, dependencies, name)
+                }
+                FeatureValue::Dependency(name)
+                | FeatureValue::DependencyFeature {
+                    dependency: name, ..
+                } => find_dependency_occurrences(features, dependencies, name),
+            }
+        } else {
+            Vec::new()
+        }
+    } else {
+        Vec::new()
+    };
+
+    occurrences.into_iter().map(into_highlight).collect()
+}
+
+fn into_highlight(occurrence: Occurrence) -> DocumentHighlight {
+    DocumentHighlight {
+        range: occurrence.range,
+        kind: Some(if occurrence.is_declaration {
+            DocumentHighlightKind::WRITE
+        } else {
+            DocumentHighlightKind::READ
+        }),
+    }
+}
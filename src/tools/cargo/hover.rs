@@ -1,80 +1,515 @@
+use semver::VersionReq;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tracing::trace;
 
-use crate::{parser::Dependency, tools::MarkdownBuilder};
+use crate::{
+    parser::{Dependency, Feature, Node},
+    server::VersionSource,
+    tools::{HoverSections, LockfileCache, MarkdownBuilder, ToolName},
+    util::Versioned,
+};
 
+use super::crates::models::{CrateData, CrateDataSingle, IndexMetadata};
+use super::occurrences::FeatureValue;
+use super::util::{alternate_registry, get_dependency_metadatas_newest};
 use super::{Clients, Document};
 
 pub async fn get_cargo_hover(
     clients: &Clients,
-    _doc: &Document,
+    lockfiles: &LockfileCache,
+    doc: &Document,
     dep: &Dependency,
+    source: VersionSource,
 ) -> Result<Option<Hover>> {
     let Some(version) = dep.spec().and_then(|s| s.contents.version.as_ref()) else {
         return Ok(None);
     };
 
-    let dependency_name = dep.name().unquoted();
+    let dependency_name = dep.package_name().unquoted();
     let dependency_version = version.unquoted();
-
-    // Add basic hover information with version and name
     trace!("Hovering: {dependency_name} version {dependency_version}");
-    let mut md = MarkdownBuilder::new();
-    md.h2(dependency_name);
-    md.version(dependency_version);
-
-    // Try to fetch additional information from the index - description, links
-    trace!("Fetching crate data from crates.io");
-    if let Ok(crate_data) = clients
-        .crates
-        .get_crate_data(dependency_name)
+
+    let metas = get_dependency_metadatas_newest(clients, doc, dep)
         .await
-        .map(|c| c.inner)
-    {
-        md.br();
-        md.p(crate_data.description);
-
-        // Ignore homepage or docs if it's the same as the repo
-        let mut docs = crate_data.links.documentation.as_deref();
-        let mut page = crate_data.links.homepage.as_deref();
-        let repo = crate_data.links.repository.as_deref();
-        if page == repo {
-            page = None;
-        }
-        if docs == repo {
-            docs = None;
+        .unwrap_or_default();
+    let newest = newest_matching_metadata(&metas, version);
+    let lockfile = match source.uses_lockfile() {
+        true => lockfiles.for_manifest(ToolName::Cargo, doc.uri()).await,
+        false => None,
+    };
+    let locked = lockfile.and_then(|lockfile| {
+        let package = lockfile.find(dependency_name, Some(dependency_version))?;
+        Some(package.version.clone())
+    });
+
+    // Try to fetch additional information from crates.io - description, links - which
+    // knows nothing about crates in alternate registries, even if they share a name
+    let crate_data = match alternate_registry(dep) {
+        Some(_) => None,
+        None => {
+            trace!("Fetching crate data from crates.io");
+            clients.crates.get_crate_data(dependency_name).await.ok()
         }
+    };
 
-        // Add links to documentation, repo, and homepage
-        let docs_rs = format!("https://docs.rs/{}", crate_data.name);
-        md.br();
-        md.h3("Links");
-        if let Some(docs) = docs {
-            md.a("Documentation", docs);
-            if !docs.contains("docs.rs") {
-                // docs.rs is the standard for autogenerated documentation
-                // for any rust crate, so we always provide it at the end,
-                // will be formatted like "- Documentation (docs.rs)" with
-                // both of those links being clickable by the user
-                md.extend_last(format!(" ([docs.rs]({docs_rs}))"));
+    let mut hover = HoverSections::new();
+    hover.section(|md| {
+        md.h2(dep.name().unquoted());
+        if dep.is_renamed() {
+            md.p(format!("Aliased from `{dependency_name}`"));
+        }
+    });
+    hover.push(version_section(
+        dependency_version,
+        &metas,
+        newest,
+        locked.as_deref(),
+        source,
+    ));
+    if let Some(crate_data) = &crate_data {
+        hover.push(deprecation_section(&crate_data.inner));
+    }
+    hover.push(rust_version_section(newest));
+    if let Some(crate_data) = &crate_data {
+        hover.section(|md| {
+            if !crate_data.inner.description.trim().is_empty() {
+                md.p(&crate_data.inner.description);
             }
-        } else {
-            md.a("Documentation", docs_rs);
+        });
+        hover.push(timeline_section(crate_data));
+        hover.push(links_section(&crate_data.inner));
+    }
+
+    Ok(Some(hover.into_hover(dep.range())))
+}
+
+/**
+    The version requirement, the version locked in the lockfile, what the
+    requirement resolves to in the registry, and if a newer version is available,
+    depending on the configured version source. Build scripts can add a lot to
+    build times, so they are also mentioned if the newest matching version has one.
+*/
+fn version_section(
+    version: &str,
+    metas: &[IndexMetadata],
+    newest: Option<&IndexMetadata>,
+    locked: Option<&str>,
+    source: VersionSource,
+) -> MarkdownBuilder {
+    let mut md = MarkdownBuilder::new();
+    md.version(version);
+    if let Some(meta) = newest {
+        if let Some(links) = meta.links.as_deref() {
+            md.p(format!("Has a build script, linking to `{links}`"));
+        } else if meta.has_build_script() {
+            md.p("Has a build script");
         }
-        if let Some(repo) = repo {
-            md.a("Repository", repo);
+    }
+
+    md.br();
+    if let Some(locked) = locked.filter(|_| source.uses_lockfile()) {
+        md.locked(locked);
+    }
+    if !source.uses_registry() {
+        md.requirement(version, None);
+        return md;
+    }
+
+    let resolved = newest.and_then(|meta| meta.parse_version().ok());
+    md.requirement(version, resolved.as_ref());
+
+    let latest = metas
+        .iter()
+        .filter_map(|meta| meta.parse_version().ok())
+        .filter(|v| v.pre.is_empty())
+        .max();
+    if let (Ok(req), Some(latest)) = (VersionReq::parse(version), latest) {
+        if !req.matches(&latest) && resolved.as_ref().is_some_and(|r| *r < latest) {
+            md.p(format!("A newer version `{latest}` is available"));
         }
-        if let Some(page) = page {
-            md.a("Homepage", page);
+    }
+
+    md
+}
+
+fn deprecation_section(crate_data: &CrateData) -> MarkdownBuilder {
+    let mut md = MarkdownBuilder::new();
+    if let Some(deprecation) = crate_data.deprecation() {
+        match deprecation.successor {
+            Some(successor) => md.p(format!("**Deprecated**, in favor of `{successor}`")),
+            None => md.p("**Deprecated**"),
         }
     }
+    md
+}
+
+fn rust_version_section(newest: Option<&IndexMetadata>) -> MarkdownBuilder {
+    let mut md = MarkdownBuilder::new();
+    if let Some(rust_version) = newest.and_then(|meta| meta.rust_version.as_deref()) {
+        md.p(format!("Needs Rust `{rust_version}` or newer"));
+    }
+    md
+}
+
+/**
+    A short timeline of the most recent releases, not including yanked ones.
+*/
+fn timeline_section(crate_data: &CrateDataSingle) -> MarkdownBuilder {
+    let mut versions = crate_data
+        .versions
+        .iter()
+        .filter(|v| !v.yanked)
+        .collect::<Vec<_>>();
+    versions.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+
+    let mut md = MarkdownBuilder::new();
+    md.timeline(versions.iter().map(|v| (&v.version, &v.created_at)));
+    md
+}
+
+fn links_section(crate_data: &CrateData) -> MarkdownBuilder {
+    // Ignore homepage or docs if it's the same as the repo
+    let mut docs = crate_data.links.documentation.as_deref();
+    let mut page = crate_data.links.homepage.as_deref();
+    let repo = crate_data.links.repository.as_deref();
+    if page == repo {
+        page = None;
+    }
+    if docs == repo {
+        docs = None;
+    }
+
+    // Add links to documentation, repo, and homepage
+    let docs_rs = format!("https://docs.rs/{}", crate_data.name);
+    let mut md = MarkdownBuilder::new();
+    md.h3("Links");
+    if let Some(docs) = docs {
+        md.a("Documentation", docs);
+        if !docs.contains("docs.rs") {
+            // docs.rs is the standard for autogenerated documentation
+            // for any rust crate, so we always provide it at the end,
+            // will be formatted like "- Documentation (docs.rs)" with
+            // both of those links being clickable by the user
+            md.extend_last(format!(" ([docs.rs]({docs_rs}))"));
+        }
+    } else {
+        md.a("Documentation", docs_rs);
+    }
+    if let Some(repo) = repo {
+        md.a("Repository", repo);
+    }
+    if let Some(page) = page {
+        md.a("Homepage", page);
+    }
+    md
+}
+
+fn newest_matching_metadata<'a>(
+    metas: &'a [IndexMetadata],
+    version: &Node<String>,
+) -> Option<&'a IndexMetadata> {
+    let req = VersionReq::parse(version.unquoted()).ok()?;
+    metas
+        .iter()
+        .filter_map(|meta| Some((meta, meta.parse_version().ok()?)))
+        .filter(|(_, v)| req.matches(v))
+        .max_by(|(_, a), (_, b)| a.cmp(b))
+        .map(|(meta, _)| meta)
+}
+
+pub fn get_cargo_hover_feature(
+    _doc: &Document,
+    features: &[Feature],
+    dependencies: &[Dependency],
+    feature: &Feature,
+    pos: Position,
+) -> Result<Option<Hover>> {
+    let mut md = MarkdownBuilder::new();
+
+    // Hovering a single value explains only that value, hovering
+    // anywhere else explains everything the feature activates
+    let range = if let Some(value) = feature.values.contents.iter().find(|v| v.contains(pos)) {
+        md.p(FeatureValue::classify(value.unquoted(), features, dependencies).describe());
+        value.range
+    } else {
+        md.h2(format!("Feature `{}`", feature.name.unquoted()));
+        md.br();
+        describe_feature_values(&mut md, &feature.values, features, dependencies);
+        feature.range()
+    };
 
     Ok(Some(Hover {
-        range: Some(dep.range()),
+        range: Some(range),
         contents: HoverContents::Markup(MarkupContent {
             kind: MarkupKind::Markdown,
             value: md.build(),
         }),
     }))
 }
+
+fn describe_feature_values(
+    md: &mut MarkdownBuilder,
+    values: &Node<Vec<Node<String>>>,
+    features: &[Feature],
+    dependencies: &[Dependency],
+) {
+    if values.contents.is_empty() {
+        md.p("Does not activate anything");
+        return;
+    }
+
+    md.p("Activates:");
+    md.br();
+    for value in &values.contents {
+        let value = FeatureValue::classify(value.unquoted(), features, dependencies);
+        md.p(format!("- {}", value.describe()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::testing::{cursor_fixture, MockFetcher, TestServer, SERDE_INDEX};
+    use crate::server::{Config, HoverConfig, VersionSource};
+    use crate::util::FixtureDir;
+
+    use super::*;
+
+    fn metas() -> Vec<IndexMetadata> {
+        IndexMetadata::try_from_lines(vec![
+            r#"{"name":"serde","vers":"0.9.0","deps":[],"features":{}}"#,
+            r#"{"name":"serde","vers":"1.0.150","deps":[],"features":{},"rust_version":"1.31"}"#,
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_version_section_outdated() {
+        let metas = metas();
+        let section =
+            version_section("0.9", &metas, Some(&metas[0]), None, VersionSource::Both).build();
+        assert!(section.starts_with("Version **0.9**\n"));
+        assert!(section.contains("latest matching: `0.9.0` · requirement: `^0.9`"));
+        assert!(section.contains("A newer version `1.0.150` is available"));
+
+        let section =
+            version_section("1.0", &metas, Some(&metas[1]), None, VersionSource::Both).build();
+        assert!(!section.contains("A newer version"));
+    }
+
+    #[test]
+    fn test_version_section_sources() {
+        let metas = metas();
+        let section =
+            |source| version_section("0.9", &metas, Some(&metas[0]), Some("0.9.0"), source).build();
+
+        let lockfile = section(VersionSource::Lockfile);
+        assert!(lockfile.contains("locked: `0.9.0`\nrequirement: `^0.9`"));
+        assert!(!lockfile.contains("latest matching:"));
+        assert!(!lockfile.contains("A newer version"));
+
+        let registry = section(VersionSource::Registry);
+        assert!(!registry.contains("locked:"));
+        assert!(registry.contains("A newer version `1.0.150` is available"));
+
+        let both = section(VersionSource::Both);
+        assert!(both.contains("locked: `0.9.0`\nlatest matching: `0.9.0`"));
+    }
+
+    #[test]
+    fn test_rust_version_section() {
+        let metas = metas();
+        assert!(rust_version_section(Some(&metas[0])).is_empty());
+        assert_eq!(
+            rust_version_section(Some(&metas[1])).build(),
+            "Needs Rust `1.31` or newer\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cargo_hover() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::new(fetcher.clone()).await;
+
+        let (contents, pos) = cursor_fixture("[dependencies]\nser$0de = \"1.0\"\n");
+        let uri = server.open("Cargo.toml", &contents).await;
+        let hover = server.hover(&uri, pos).await.expect("missing hover");
+
+        assert!(hover.contains("## serde"));
+        assert!(hover.contains("Version **1.0**"));
+        assert!(hover.contains("linking to `foo`"));
+        assert!(fetcher
+            .requested()
+            .iter()
+            .any(|url| url.ends_with("/se/rd/serde")));
+    }
+
+    #[tokio::test]
+    async fn test_cargo_hover_git() {
+        let manifest = "[package]\nname = \"bar\"\nversion = \"0.3.0\"\n";
+        let fetcher = MockFetcher::new()
+            .with_response("/repos/foo/bar/contents/Cargo.toml?ref=v0.3.0", manifest);
+        let server = TestServer::new(fetcher.clone()).await;
+
+        let (contents, pos) = cursor_fixture(
+            "[dependencies]\nb$0ar = { git = \"https://github.com/foo/bar\", tag = \"v0.3.0\" }\n",
+        );
+        let uri = server.open("Cargo.toml", &contents).await;
+        let hover = server.hover(&uri, pos).await.expect("missing hover");
+
+        assert!(hover.contains("## bar"));
+        assert!(hover.contains("Version **0.3.0**"));
+        assert!(hover.contains("Using tag `v0.3.0`"));
+        assert!(!fetcher
+            .requested()
+            .iter()
+            .any(|url| url.ends_with("/3/b/bar")));
+    }
+
+    #[tokio::test]
+    async fn test_cargo_hover_git_unavailable() {
+        let server = TestServer::new(MockFetcher::new()).await;
+
+        let (contents, pos) = cursor_fixture(
+            "[dependencies]\nb$0ar = { git = \"https://example.com/bar.git\", rev = \"abc123\" }\n",
+        );
+        let uri = server.open("Cargo.toml", &contents).await;
+        let hover = server.hover(&uri, pos).await.expect("missing hover");
+
+        assert!(!hover.contains("Version"));
+        assert!(hover.contains("Git repository at `https://example.com/bar.git`"));
+        assert!(hover.contains("Using revision `abc123`"));
+    }
+
+    #[tokio::test]
+    async fn test_cargo_hover_ranges() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::new(fetcher).await;
+
+        let contents = "[dependencies]\n\
+            serde = \"1.0\"\n\
+            local = { path = \"../local\" }\n\
+            \n\
+            [features]\n\
+            std = [\"serde/std\"]\n";
+        let uri = server.open("Cargo.toml", contents).await;
+        let range = |start: (u32, u32), end: (u32, u32)| {
+            Some(Range::new(
+                Position::new(start.0, start.1),
+                Position::new(end.0, end.1),
+            ))
+        };
+
+        // Dependencies are described as a whole, and so are local ones
+        let hovered = server.hover_range(&uri, Position::new(1, 2)).await;
+        assert_eq!(hovered, range((1, 0), (1, 13)));
+        let hovered = server.hover_range(&uri, Position::new(2, 2)).await;
+        assert_eq!(hovered, range((2, 0), (2, 29)));
+
+        // Feature values are described one by one
+        let hovered = server.hover_range(&uri, Position::new(5, 10)).await;
+        assert_eq!(hovered, range((5, 7), (5, 18)));
+    }
+
+    #[tokio::test]
+    async fn test_hover_outside_dependencies() {
+        let server = TestServer::new(MockFetcher::new()).await;
+
+        let (contents, pos) = cursor_fixture("[pack$0age]\nname = \"app\"\n");
+        let uri = server.open("Cargo.toml", &contents).await;
+        assert_eq!(server.hover(&uri, pos).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_target_dependency_hover() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::new(fetcher).await;
+
+        let (contents, pos) =
+            cursor_fixture("[target.'cfg(unix)'.dependencies]\nser$0de = \"1.0\"\n");
+        let uri = server.open("Cargo.toml", &contents).await;
+        let hover = server.hover(&uri, pos).await.expect("missing hover");
+
+        assert!(hover.contains("## serde"));
+        assert!(hover.contains("Version **1.0**"));
+    }
+
+    #[tokio::test]
+    async fn test_cargo_hover_requirement() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::new(fetcher).await;
+
+        let (contents, pos) = cursor_fixture("[dependencies]\nser$0de = \"1.0\"\n");
+        let uri = server.open("Cargo.toml", &contents).await;
+        let hover = server.hover(&uri, pos).await.expect("missing hover");
+        assert!(hover.contains("latest matching: `1.0.150` · requirement: `^1.0`"));
+
+        let (contents, pos) = cursor_fixture("[dependencies]\nser$0de = \"one\"\n");
+        let uri = server.open("Cargo.toml", &contents).await;
+        let hover = server.hover(&uri, pos).await.expect("missing hover");
+        assert!(hover.contains("unparsed: `one`"));
+        assert!(!hover.contains("latest matching:"));
+    }
+
+    #[tokio::test]
+    async fn test_cargo_hover_outdated_and_deprecated() {
+        let crate_data = r#"{"crate":{
+            "name":"serde","description":"DEPRECATED: use `serde2` instead",
+            "created_at":"","updated_at":"","downloads":0,"recent_downloads":0
+        }}"#;
+        let fetcher = MockFetcher::new()
+            .with_response("/se/rd/serde", SERDE_INDEX)
+            .with_response("/api/v1/crates/serde?", crate_data);
+        let server = TestServer::new(fetcher).await;
+
+        let (contents, pos) = cursor_fixture("[dependencies]\nser$0de = \"0.9\"\n");
+        let uri = server.open("Cargo.toml", &contents).await;
+        let hover = server.hover(&uri, pos).await.expect("missing hover");
+
+        // Both pieces of information are in their own section, in order
+        let outdated = hover.find("A newer version `1.0.150` is available");
+        let deprecated = hover.find("**Deprecated**, in favor of `serde2`");
+        assert!(outdated.is_some(), "hover: {hover}");
+        assert!(outdated < deprecated, "hover: {hover}");
+        assert!(hover.contains("is available\n\n**Deprecated**"));
+    }
+
+    #[tokio::test]
+    async fn test_cargo_hover_version_source() {
+        let base = FixtureDir::new("hover-version-source");
+        let lockfile = "[[package]]\nname = \"serde\"\nversion = \"1.0.100\"\n";
+        base.write("Cargo.lock", lockfile);
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::new(fetcher).await;
+
+        let (contents, pos) = cursor_fixture("[dependencies]\nser$0de = \"1.0\"\n");
+        let uri = Url::from_file_path(base.join("Cargo.toml")).unwrap();
+        let uri = &server.open_uri(uri, &contents).await;
+        let server = &server;
+        let hover = move |source| {
+            let config = server.config().get();
+            server.config().set(Config {
+                hover: HoverConfig {
+                    version_source: source,
+                },
+                ..config
+            });
+            server.hover(uri, pos)
+        };
+
+        // Both the locked version and the newest matching version by default
+        let both = hover(VersionSource::Both).await.expect("missing hover");
+        assert!(
+            both.contains("locked: `1.0.100`\nlatest matching: `1.0.150`"),
+            "{both}"
+        );
+
+        let lockfile = hover(VersionSource::Lockfile).await.expect("missing hover");
+        assert!(lockfile.contains("locked: `1.0.100`\nrequirement: `^1.0`"));
+        assert!(!lockfile.contains("latest matching:"));
+
+        let registry = hover(VersionSource::Registry).await.expect("missing hover");
+        assert!(!registry.contains("locked:"));
+        assert!(registry.contains("latest matching: `1.0.150`"));
+    }
+}
@@ -0,0 +1,43 @@
+use tower_lsp::lsp_types::*;
+
+use crate::parser::LintTable;
+
+pub fn get_cargo_lint_symbols(tables: &[LintTable]) -> Vec<DocumentSymbol> {
+    tables
+        .iter()
+        .map(|table| {
+            let tool = table.tool.unquoted();
+            let name = if table.workspace {
+                format!("workspace.lints.{tool}")
+            } else {
+                format!("lints.{tool}")
+            };
+            #[allow(deprecated)]
+            let children = table
+                .lints
+                .iter()
+                .map(|lint| DocumentSymbol {
+                    name: lint.name.unquoted().to_string(),
+                    detail: Some(lint.value.unquoted().to_string()),
+                    kind: SymbolKind::PROPERTY,
+                    tags: None,
+                    deprecated: None,
+                    range: lint.range(),
+                    selection_range: lint.name.range,
+                    children: None,
+                })
+                .collect();
+            #[allow(deprecated)]
+            DocumentSymbol {
+                name,
+                detail: Some(String::from("lints")),
+                kind: SymbolKind::NAMESPACE,
+                tags: None,
+                deprecated: None,
+                range: table.range,
+                selection_range: table.tool.range,
+                children: Some(children),
+            }
+        })
+        .collect()
+}
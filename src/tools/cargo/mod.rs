@@ -1,52 +1,104 @@
-use futures::future::try_join_all;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::Client;
 use tracing::debug;
 
-use crate::parser::query_cargo_toml_dependencies;
-use crate::parser::Dependency;
+use crate::parser::{
+    query_cargo_toml_dependencies, query_cargo_toml_duplicate_tables, query_cargo_toml_features,
+    query_cargo_toml_inherited_package_fields, query_cargo_toml_lints,
+    query_cargo_toml_package_fields, query_cargo_toml_table_headers, query_cargo_toml_targets,
+    query_cargo_toml_workspace_fields,
+};
+use crate::parser::{Dependency, DependencySource, Feature, TableField};
 use crate::server::*;
 use crate::util::*;
 
 use super::*;
 
+mod actions;
 mod completion;
 mod constants;
 mod diagnostics;
+mod git;
+mod highlight;
 mod hover;
+mod lints;
+mod occurrences;
+mod registries;
+mod rename;
+mod rust_version;
+mod targets;
 mod util;
+mod vendor;
+mod workspace;
 
+use actions::*;
 use completion::*;
+use constants::CARGO_PACKAGE_FIELDS;
 use diagnostics::*;
+use git::*;
+use highlight::*;
 use hover::*;
+use lints::*;
+use rename::*;
+use rust_version::*;
+use targets::*;
+use workspace::*;
 
 #[derive(Debug, Clone)]
 pub struct Cargo {
     _client: Client,
     clients: Clients,
+    config: SharedConfig,
     documents: Documents,
+    limited: LimitedDocuments,
+    workspaces: WorkspaceCache,
+    lockfiles: LockfileCache,
 }
 
 impl Cargo {
-    pub(super) fn new(client: Client, clients: Clients, documents: Documents) -> Self {
+    pub(super) fn new(
+        client: Client,
+        clients: Clients,
+        config: SharedConfig,
+        documents: Documents,
+        limited: LimitedDocuments,
+    ) -> Self {
         Self {
             _client: client,
             clients,
+            config,
             documents,
+            limited,
+            workspaces: WorkspaceCache::default(),
+            lockfiles: LockfileCache::default(),
         }
     }
 
     fn get_document(&self, uri: &Url) -> Option<Document> {
-        if uri
-            .file_name()
-            .as_deref()
-            .is_some_and(|f| f.eq_ignore_ascii_case("Cargo.toml"))
-        {
-            self.documents.get(uri).map(|r| r.clone())
-        } else {
-            None
-        }
+        self.documents
+            .get(uri)
+            .filter(|doc| doc.is_for_tool(ToolName::Cargo, &["Cargo.toml"]))
+            .map(|r| r.clone())
+    }
+
+    /**
+        Identifies a dependency from a registry by where its versions are fetched
+        from, which for alternate registries is the sparse index of the registry.
+    */
+    async fn refreshable_dependency(
+        &self,
+        doc: &Document,
+        dep: &Dependency,
+    ) -> Option<DependencyId> {
+        let name = dep.package_name().unquoted().to_string();
+        let Some(registry) = util::alternate_registry(dep) else {
+            return Some(DependencyId::Crates { name });
+        };
+        let index_url = util::get_alternate_registry_index_url(&self.clients, doc, registry)
+            .await
+            .ok()?;
+        Some(DependencyId::CratesRegistry { index_url, name })
     }
 }
 
@@ -62,12 +114,101 @@ impl Tool for Cargo {
         // Find the dependency that is hovered over
         let dependencies = query_cargo_toml_dependencies(doc.inner());
         let Some(found) = Dependency::find_at_pos(&dependencies, pos) else {
+            // Not hovering a dependency, but we may be hovering a feature
+            let features = query_cargo_toml_features(doc.inner());
+            if let Some(feature) = Feature::find_at_pos(&features, pos) {
+                debug!("Hovering feature: {feature:?}");
+                return get_cargo_hover_feature(&doc, &features, &dependencies, feature, pos);
+            }
+            // ... or a workspace member
+            let workspace = query_cargo_toml_workspace_fields(doc.inner());
+            if let Some(field) = TableField::find_value_at_pos(&workspace, pos) {
+                debug!("Hovering workspace field: {field:?}");
+                return get_cargo_hover_workspace(&doc, &workspace, field).await;
+            }
+            // ... or a package field inherited from the workspace
+            let inherited = query_cargo_toml_inherited_package_fields(doc.inner());
+            if let Some(field) = inherited
+                .iter()
+                .find(|f| f.name.contains(pos) || f.value.contains(pos))
+            {
+                debug!("Hovering inherited package field: {field:?}");
+                let resolved = resolve_inherited_fields(
+                    &doc,
+                    &self.documents,
+                    &self.workspaces,
+                    std::slice::from_ref(field),
+                )
+                .await;
+                return match resolved.first() {
+                    Some(inherited) => get_cargo_hover_inherited(inherited),
+                    None => Ok(None),
+                };
+            }
+            // ... or the Rust version of the package
+            let package_fields = query_cargo_toml_package_fields(doc.inner());
+            if let Some(field) = TableField::find_by_name(&package_fields, "rust-version")
+                .filter(|f| f.name.contains(pos) || f.value.contains(pos))
+            {
+                debug!("Hovering rust version: {field:?}");
+                let conflicts = match parse_rust_version(field.value.unquoted()) {
+                    Some(declared) if !self.limited.contains(&uri) => {
+                        find_rust_version_conflicts(&self.clients, &doc, &dependencies, &declared)
+                            .await
+                    }
+                    _ => Vec::new(),
+                };
+                return get_cargo_hover_rust_version(field, &conflicts);
+            }
             return Ok(None);
         };
 
+        // Local crates are not in the registry, but other workspace members may use them
+        if let Some(DependencySource::Path { path }) = found.spec().map(|s| &s.contents.source) {
+            debug!("Hovering path dependency: {found:?}");
+            return get_cargo_hover_path(&doc, &self.documents, &self.workspaces, found, path)
+                .await;
+        }
+
+        if self.limited.contains(&uri) {
+            return Ok(None);
+        }
+
+        // Git dependencies are not in the registry, but their own manifest may be fetched
+        if let Some(DependencySource::Git { url, reference }) =
+            found.spec().map(|s| &s.contents.source)
+        {
+            debug!("Hovering git dependency: {found:?}");
+            return get_cargo_hover_git(&self.clients, &doc, found, url, reference.as_ref()).await;
+        }
+
         // Fetch some extra info and return the hover
         debug!("Hovering: {found:?}");
-        get_cargo_hover(&self.clients, &doc, found).await
+        let source = self.config.get().hover.version_source;
+        get_cargo_hover(&self.clients, &self.lockfiles, &doc, found, source).await
+    }
+
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let pos = params.text_document_position_params.position;
+        let Some(doc) = self.get_document(&uri) else {
+            return Ok(None);
+        };
+
+        // Only local crates have a manifest that we can point to
+        let dependencies = query_cargo_toml_dependencies(doc.inner());
+        let Some(found) = Dependency::find_at_pos(&dependencies, pos) else {
+            return Ok(None);
+        };
+        let Some(DependencySource::Path { path }) = found.spec().map(|s| &s.contents.source) else {
+            return Ok(None);
+        };
+        Ok(get_cargo_definition_path(&doc, path)
+            .await
+            .map(GotoDefinitionResponse::Scalar))
     }
 
     async fn completion(&self, params: CompletionParams) -> Result<CompletionResponse> {
@@ -80,51 +221,412 @@ impl Tool for Cargo {
         // Find the dependency that is being completed
         let dependencies = query_cargo_toml_dependencies(doc.inner());
         let Some(found) = Dependency::find_at_pos(&dependencies, pos) else {
-            return Ok(CompletionResponse::Array(Vec::new()));
+            // Not completing a dependency, but we may be completing the name of the
+            // first one, in a manifest that has no [dependencies] table to put it in
+            let config = self.config.get();
+            let headers = query_cargo_toml_table_headers(doc.inner());
+            if let Some(entry) = find_unsectioned_entry(&doc, pos).filter(|e| {
+                e.value.is_none()
+                    && e.key.contains(pos)
+                    && e.table.as_deref().is_none_or(|t| t == "package")
+                    && !CARGO_PACKAGE_FIELDS.contains(&e.key.unquoted())
+                    && !headers.iter().any(|h| h == "dependencies")
+                    && !self.limited.contains(&uri)
+            }) {
+                debug!("Completing name without a section: {entry:?}");
+                let resolve = self.config.completion_resolve();
+                let response = get_cargo_completions_name(
+                    &self.clients,
+                    &config,
+                    resolve,
+                    &doc,
+                    &entry.key,
+                    &[],
+                )
+                .await?;
+                let edit = section_header_edit(&doc, entry.key.range.start.line, "dependencies");
+                return Ok(with_additional_edit(response, edit));
+            }
+            // ... or the Rust version
+            let package_fields = query_cargo_toml_package_fields(doc.inner());
+            if let Some(field) = TableField::find_by_name(&package_fields, "rust-version")
+                .filter(|f| f.value.contains(pos))
+            {
+                debug!("Completing rust version: {field:?}");
+                return get_cargo_completions_rust_version(&doc, field);
+            }
+            // ... or a feature value
+            let features = query_cargo_toml_features(doc.inner());
+            let Some(feature) = Feature::find_at_pos(&features, pos) else {
+                return Ok(CompletionResponse::Array(Vec::new()));
+            };
+            let Some(value) = feature.values.contents.iter().find(|v| v.contains(pos)) else {
+                return Ok(CompletionResponse::Array(Vec::new()));
+            };
+            debug!("Completing feature value: {value:?}");
+            let mut package_fields = query_cargo_toml_package_fields(doc.inner());
+            let inherited = resolve_inherited_fields(
+                &doc,
+                &self.documents,
+                &self.workspaces,
+                &query_cargo_toml_inherited_package_fields(doc.inner()),
+            )
+            .await;
+            extend_with_inherited(&mut package_fields, inherited);
+            return get_cargo_completions_feature_value(
+                &self.clients,
+                &doc,
+                &features,
+                &dependencies,
+                &package_fields,
+                feature,
+                value,
+            )
+            .await;
         };
 
-        // Check what we're completing - name or version
+        // Check what we're completing - name, package, version, registry, features, or keys,
+        // where only registries and keys can be completed for documents over the limits
+        let limited = self.limited.contains(&uri);
+        let nothing = || Ok(CompletionResponse::Array(Vec::new()));
         if found.name().contains(pos) {
+            if limited {
+                return nothing();
+            }
             debug!("Completing name: {found:?}");
-            return get_cargo_completions_name(&self.clients, &doc, found).await;
+            let config = self.config.get();
+            let resolve = self.config.completion_resolve();
+            let declared = get_declared_crate_names(&dependencies, found);
+            return get_cargo_completions_name(
+                &self.clients,
+                &config,
+                resolve,
+                &doc,
+                found.name(),
+                &declared,
+            )
+            .await;
         } else if let Some(s) = found.spec().filter(|s| s.contains(pos)) {
-            if s.contents.version.as_ref().is_some_and(|v| v.contains(pos)) {
+            if let Some(p) = s.contents.package.as_ref().filter(|p| p.contains(pos)) {
+                if limited {
+                    return nothing();
+                }
+                debug!("Completing package: {found:?}");
+                let config = self.config.get();
+                let resolve = self.config.completion_resolve();
+                let declared = get_declared_crate_names(&dependencies, found);
+                return get_cargo_completions_name(
+                    &self.clients,
+                    &config,
+                    resolve,
+                    &doc,
+                    p,
+                    &declared,
+                )
+                .await;
+            } else if s.contents.version.as_ref().is_some_and(|v| v.contains(pos)) {
+                if limited {
+                    return nothing();
+                }
                 debug!("Completing version: {found:?}");
-                return get_cargo_completions_version(&self.clients, &doc, found).await;
+                let workspace =
+                    resolve_workspace_dependencies(&doc, &self.documents, &self.workspaces).await;
+                let inherit = workspace
+                    .as_ref()
+                    .and_then(|w| w.find_for(&doc, found))
+                    .and_then(|inherited| {
+                        get_cargo_workspace_version_metadata(&doc, found, inherited)
+                    });
+                return get_cargo_completions_version(&self.clients, &doc, found, inherit).await;
+            } else if let Some(r) = s.contents.registry.as_ref().filter(|r| r.contains(pos)) {
+                debug!("Completing registry: {found:?}");
+                return get_cargo_completions_registry(&self.clients, &doc, r).await;
             } else if let Some(f) = s.contents.features.as_ref().filter(|f| f.contains(pos)) {
+                if limited {
+                    return nothing();
+                }
                 debug!("Completing features: {found:?}");
                 if let Some(f) = f.contents.iter().find(|f| f.contains(pos)) {
                     debug!("Completing feature: {f:?}");
                     return get_cargo_completions_features(&self.clients, &doc, found, f).await;
                 }
+            } else if let Some(k) = s
+                .contents
+                .keys
+                .as_ref()
+                .filter(|k| k.range.start < pos && pos < k.range.end)
+            {
+                debug!("Completing keys: {found:?}");
+                let snippets = self.config.completion_snippets();
+                return get_cargo_completions_keys(&doc, k, pos, snippets).await;
             }
         }
 
         Ok(CompletionResponse::Array(Vec::new()))
     }
 
+    async fn completion_resolve(&self, item: CompletionItem) -> Result<CompletionItem> {
+        match CompletionMetadata::from_item(&item) {
+            Some(CompletionMetadata::CargoCrate { name }) => {
+                debug!("Resolving completion: {name}");
+                get_cargo_completion_resolve(&self.clients, item, &name).await
+            }
+            _ => Ok(item),
+        }
+    }
+
     async fn diagnostics(&self, params: DocumentDiagnosticParams) -> Result<Vec<Diagnostic>> {
         let uri = params.text_document.uri;
         let Some(doc) = self.get_document(&uri) else {
             return Ok(Vec::new());
         };
 
+        // Check for any problems that need no network requests - workspace
+        // members that match nothing, tables such as [dependencies] twice,
+        // and package fields inherited from a workspace that doesn't have them
+        let workspace = query_cargo_toml_workspace_fields(doc.inner());
+        let duplicates = query_cargo_toml_duplicate_tables(doc.inner());
+        let inherited = resolve_inherited_fields(
+            &doc,
+            &self.documents,
+            &self.workspaces,
+            &query_cargo_toml_inherited_package_fields(doc.inner()),
+        )
+        .await;
+        let mut local = get_cargo_diagnostics_workspace(&doc, &workspace).await;
+        local.extend(get_cargo_diagnostics_duplicate_tables(&doc, &duplicates));
+        local.extend(get_cargo_diagnostics_inherited(&inherited));
+
         // Find all dependencies
         let dependencies = query_cargo_toml_dependencies(doc.inner());
         if dependencies.is_empty() {
-            return Ok(Vec::new());
+            return Ok(local);
+        }
+
+        // Local crates are also checked without the network, and should always exist
+        let config = self.config.get();
+        local.extend(get_cargo_diagnostics_paths(&doc, &dependencies).await);
+
+        // ... and so are crates that are not permitted by policy,
+        // using the name of the crate itself, even if it is renamed
+        local.extend(dependencies.iter().filter_map(|dep| {
+            let name = dep.package_name();
+            get_policy_diagnostic(
+                &config,
+                ToolName::Cargo,
+                name.unquoted(),
+                name.range,
+                "Cargo",
+            )
+        }));
+
+        // ... and versions that differ from the ones in the workspace
+        if let Some(severity) = config.diagnostics.workspace_versions.severity() {
+            if let Some(workspace) =
+                resolve_workspace_dependencies(&doc, &self.documents, &self.workspaces).await
+            {
+                local.extend(get_cargo_diagnostics_workspace_versions(
+                    &doc,
+                    &workspace,
+                    &dependencies,
+                    severity,
+                ));
+            }
+        }
+
+        // Also check for any optional dependencies that can never be activated
+        let features = query_cargo_toml_features(doc.inner());
+        let mut package_fields = query_cargo_toml_package_fields(doc.inner());
+        extend_with_inherited(&mut package_fields, inherited);
+        let optional = get_cargo_diagnostics_optional(&dependencies, &features, &package_fields);
+
+        // ... and for any keys that conflict with each other, such as `path` and `git`
+        let keys = get_cargo_diagnostics_keys(&dependencies);
+
+        if self.limited.contains(&uri) {
+            let limited = exceeds_limits_diagnostic(&config.limits, "Cargo");
+            return Ok(optional
+                .into_iter()
+                .chain(keys)
+                .chain(local)
+                .chain([limited])
+                .collect());
         }
 
         // Fetch all diagnostics concurrently
         debug!("Fetching cargo diagnostics for dependencies");
-        let results = try_join_all(
-            dependencies
-                .iter()
-                .map(|dep| get_cargo_diagnostics(&self.clients, &doc, dep)),
+        let results = cached_dependency_diagnostics(
+            &doc,
+            config.diagnostics.merge,
+            dependencies.iter().map(|dep| {
+                let key = DependencyKey::new(&doc, dep.kind(), dep.range());
+                let fut = get_cargo_diagnostics(&self.clients, &config, &doc, dep);
+                (key, dep.range(), fut)
+            }),
         )
         .await?;
 
-        Ok(results.into_iter().flatten().collect())
+        // Dependencies may also need a newer Rust version than the package declares,
+        // which is not cached per dependency, since it depends on the package too
+        let mut rust_version = Vec::new();
+        if let Some(field) = TableField::find_by_name(&package_fields, "rust-version") {
+            if let Some(declared) = parse_rust_version(field.value.unquoted()) {
+                let conflicts =
+                    find_rust_version_conflicts(&self.clients, &doc, &dependencies, &declared)
+                        .await;
+                rust_version = get_cargo_diagnostics_rust_version(field, &conflicts);
+            }
+        }
+
+        Ok(results
+            .into_iter()
+            .chain(rust_version)
+            .chain(optional)
+            .chain(keys)
+            .chain(local)
+            .collect())
+    }
+
+    fn dependency_count(&self, uri: &Url) -> usize {
+        self.get_document(uri)
+            .map_or(0, |doc| query_cargo_toml_dependencies(doc.inner()).len())
+    }
+
+    fn file_changed(&self, uri: &Url) {
+        self.workspaces.invalidate(uri);
+        self.clients.cargo_configs.invalidate(uri);
+        self.lockfiles.invalidate(uri);
+
+        // A changed lockfile usually means that dependencies were just
+        // updated, so this is a good time to pick up any new versions
+        if uri
+            .file_name()
+            .as_deref()
+            .is_some_and(|f| f.eq_ignore_ascii_case("Cargo.lock"))
+        {
+            debug!("Lockfile changed, invalidating cached Cargo versions");
+            self.clients.crates.invalidate_versions();
+        }
+    }
+
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Vec<DocumentHighlight>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let pos = params.text_document_position_params.position;
+        let Some(doc) = self.get_document(&uri) else {
+            return Ok(Vec::new());
+        };
+
+        let dependencies = query_cargo_toml_dependencies(doc.inner());
+        let features = query_cargo_toml_features(doc.inner());
+        Ok(get_cargo_document_highlights(&features, &dependencies, pos))
+    }
+
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        let Some(doc) = self.get_document(&params.text_document.uri) else {
+            return Ok(None);
+        };
+
+        let dependencies = query_cargo_toml_dependencies(doc.inner());
+        let features = query_cargo_toml_features(doc.inner());
+        Ok(get_cargo_prepare_rename(
+            &features,
+            &dependencies,
+            params.position,
+        ))
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let uri = params.text_document_position.text_document.uri;
+        let pos = params.text_document_position.position;
+        let Some(doc) = self.get_document(&uri) else {
+            return Ok(None);
+        };
+
+        let dependencies = query_cargo_toml_dependencies(doc.inner());
+        let features = query_cargo_toml_features(doc.inner());
+        get_cargo_rename(&doc, &features, &dependencies, pos, &params.new_name)
+    }
+
+    async fn document_symbols(&self, params: DocumentSymbolParams) -> Result<Vec<DocumentSymbol>> {
+        let Some(doc) = self.get_document(&params.text_document.uri) else {
+            return Ok(Vec::new());
+        };
+
+        let targets = query_cargo_toml_targets(doc.inner());
+        let lints = query_cargo_toml_lints(doc.inner());
+
+        let mut symbols = get_cargo_document_symbols(&targets);
+        symbols.extend(get_cargo_lint_symbols(&lints));
+        Ok(symbols)
+    }
+
+    async fn manifest_info(&self, params: ManifestInfoParams) -> Result<Option<ManifestInfo>> {
+        let Some(doc) = self.get_document(&params.text_document.uri) else {
+            return Ok(None);
+        };
+
+        let targets = query_cargo_toml_targets(doc.inner());
+        Ok(Some(get_cargo_manifest_info(&targets)))
+    }
+
+    async fn analyze(&self, params: AnalyzeParams) -> Result<Option<AnalyzeResult>> {
+        let Some(doc) = self.get_document(&params.text_document.uri) else {
+            return Ok(None);
+        };
+
+        let diagnostics = self
+            .diagnostics(DocumentDiagnosticParams {
+                text_document: params.text_document,
+                identifier: None,
+                previous_result_id: None,
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+            })
+            .await?;
+        let dependencies = query_cargo_toml_dependencies(doc.inner());
+        Ok(Some(AnalyzeResult::from_diagnostics(
+            dependencies
+                .iter()
+                .map(|dep| (dep.name().unquoted(), dep.range())),
+            &diagnostics,
+            doc.diagnostics_cache().is_complete(),
+        )))
+    }
+
+    async fn code_action_resolve(&self, action: CodeAction) -> Result<CodeAction> {
+        let Some(Ok(context)) = action
+            .data
+            .as_ref()
+            .map(ResolveContext::<CodeActionMetadata>::try_from)
+        else {
+            return Ok(action);
+        };
+        let CodeActionMetadata::PinLatestVersion { package, .. } = &context.value else {
+            return Ok(action);
+        };
+        let Some(doc) = self.get_document(&context.uri) else {
+            return Ok(action);
+        };
+
+        // Only the edit is missing, which needs the latest version
+        let latest = match util::get_metadatas(&self.clients, &doc, package).await {
+            Err(_) => None,
+            Ok(metas) => metas
+                .iter()
+                .filter_map(|meta| meta.parse_version().ok())
+                .filter(|version| version.pre.is_empty())
+                .max(),
+        };
+        let latest = latest.map(|version| version.to_string());
+        Ok(context
+            .into_inner()
+            .into_resolved_code_action(action, latest.as_deref()))
     }
 
     async fn code_action(&self, params: CodeActionParams) -> Result<Vec<CodeActionOrCommand>> {
@@ -135,9 +637,313 @@ impl Tool for Cargo {
                 .as_ref()
                 .map(ResolveContext::<CodeActionMetadata>::try_from)
             {
-                actions.push(action.into_inner().into_code_action(diag.clone()))
+                actions.extend(action.into_inner().into_code_actions(Some(diag.clone())))
+            }
+        }
+
+        // Refactors are available for any dependency, not just ones with diagnostics,
+        // unless the client only asked for other kinds of code actions, like quick fixes
+        let wants_refactors = params.context.only.as_ref().is_none_or(|only| {
+            only.iter().any(|kind| {
+                CodeActionKind::REFACTOR_REWRITE
+                    .as_str()
+                    .starts_with(kind.as_str())
+            })
+        });
+        if wants_refactors {
+            if let Some(doc) = self.get_document(&params.text_document.uri) {
+                let dependencies = query_cargo_toml_dependencies(doc.inner());
+                if let Some(found) = Dependency::find_at_pos(&dependencies, params.range.start) {
+                    let resolve_edits = self.config.code_action_edit_resolve();
+                    let workspace =
+                        resolve_workspace_dependencies(&doc, &self.documents, &self.workspaces)
+                            .await;
+                    let refactors =
+                        get_cargo_refactors(&doc, found, workspace.as_ref(), resolve_edits);
+
+                    // Some refactors are also quick fixes for diagnostics, such as
+                    // using the workspace version, and should only be shown once
+                    let title = |action: &CodeActionOrCommand| match action {
+                        CodeActionOrCommand::CodeAction(action) => action.title.clone(),
+                        CodeActionOrCommand::Command(command) => command.title.clone(),
+                    };
+                    let fixes = actions.iter().map(title).collect::<Vec<_>>();
+                    actions.extend(refactors.into_iter().filter(|r| !fixes.contains(&title(r))));
+                }
+            }
+        }
+
+        // Refreshing has no kind, so it is only offered when no specific
+        // kinds were asked for, and only for crates fetched from a registry
+        if params.context.only.is_none() {
+            if let Some(doc) = self.get_document(&params.text_document.uri) {
+                let dependencies = query_cargo_toml_dependencies(doc.inner());
+                if let Some(found) = Dependency::find_at_pos(&dependencies, params.range.start) {
+                    let from_registry = found
+                        .spec()
+                        .is_none_or(|spec| spec.contents.source == DependencySource::Registry);
+                    if from_registry {
+                        if let Some(dependency) = self.refreshable_dependency(&doc, found).await {
+                            actions.push(
+                                RefreshDependencyParams::new(doc.uri(), dependency)
+                                    .into_code_action(),
+                            );
+                        }
+                    }
+                }
             }
         }
+
         Ok(actions)
     }
+
+    async fn inline_values(&self, params: InlineValueParams) -> Result<Vec<InlineValue>> {
+        let Some(doc) = self.get_document(&params.text_document.uri) else {
+            return Ok(Vec::new());
+        };
+        let Some(lockfile) = self
+            .lockfiles
+            .for_manifest(ToolName::Cargo, doc.uri())
+            .await
+        else {
+            return Ok(Vec::new());
+        };
+
+        // Renamed dependencies are locked under the name of their package
+        let dependencies = query_cargo_toml_dependencies(doc.inner());
+        let values = dependencies.iter().map(|dep| LockedInlineValue {
+            range: dep.range(),
+            name: dep.package_name().unquoted(),
+            version: dep
+                .spec()
+                .and_then(|s| s.contents.version.as_ref())
+                .map(|v| v.unquoted()),
+        });
+        Ok(get_locked_inline_values(&lockfile, values, params.range))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::StatusCode;
+    use tower_lsp::LanguageServer;
+
+    use crate::server::testing::{cursor_fixture, MockFetcher, TestServer, SERDE_INDEX};
+    use crate::server::{Config, FeaturesConfig, LimitsConfig};
+    use crate::util::FixtureDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cargo_analyze() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::new(fetcher).await;
+
+        let contents = "[dependencies]\nserde = \"0.9.0\"\nlocal = { path = \"../local\" }\n";
+        let uri = server.open("Cargo.toml", contents).await;
+        let params = crate::tools::AnalyzeParams {
+            text_document: TextDocumentIdentifier { uri },
+        };
+        let result = server
+            .server()
+            .analyze(params)
+            .await
+            .expect("analyze failed")
+            .expect("missing analysis");
+
+        assert_eq!(result.total, 2);
+        assert_eq!(result.outdated.len(), 1);
+        assert_eq!(result.outdated[0].name, "serde");
+        assert!(result.deprecated.is_empty());
+        assert!(result.unknown.is_empty());
+        assert!(!result.partial);
+    }
+
+    #[tokio::test]
+    async fn test_cargo_analyze_fetch_failure() {
+        let fetcher =
+            MockFetcher::new().with_status("/se/rd/serde", StatusCode::INTERNAL_SERVER_ERROR);
+        let server = TestServer::new(fetcher).await;
+
+        let uri = server
+            .open("Cargo.toml", "[dependencies]\nserde = \"0.9.0\"\n")
+            .await;
+        let params = crate::tools::AnalyzeParams {
+            text_document: TextDocumentIdentifier { uri },
+        };
+        let result = server
+            .server()
+            .analyze(params)
+            .await
+            .expect("analyze failed")
+            .expect("missing analysis");
+
+        // Nothing could be checked, which is not the same as nothing being wrong
+        assert_eq!(result.total, 1);
+        assert!(result.outdated.is_empty());
+        assert!(result.partial);
+    }
+
+    #[tokio::test]
+    async fn test_cargo_requests_at_end_of_file() {
+        let server = TestServer::new(MockFetcher::new()).await;
+
+        for contents in ["", "[dependencies]", "[dependencies]\nserde = \"1.0\"\n"] {
+            let uri = server.open("Cargo.toml", contents).await;
+            for pos in [
+                Position::new(2, 0),
+                Position::new(2, 40),
+                Position::new(9, 9),
+            ] {
+                assert_eq!(server.hover(&uri, pos).await, None);
+                assert!(server.completion(&uri, pos).await.is_empty());
+                assert!(server.code_actions(&uri, pos).await.is_empty());
+            }
+        }
+
+        // Empty manifests and lone headers have nothing to report
+        for contents in ["", "[dependencies]"] {
+            let uri = server.open("Cargo.toml", contents).await;
+            assert!(server.diagnostics(&uri).await.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cargo_path_dependency_definition() {
+        let base = FixtureDir::new("path-dependency-definition");
+        base.write("core/Cargo.toml", "");
+        let server = TestServer::new(MockFetcher::new()).await;
+
+        // Paths must point at the directory of a crate, never at its manifest
+        let contents = "[dependencies]\n\
+            core = { path = \"../core\" }\n\
+            direct = { path = \"../core/Cargo.toml\" }\n\
+            utils = { path = \"../utils\" }\n";
+        let uri = Url::from_file_path(base.join("app").join("Cargo.toml")).unwrap();
+        let uri = server.open_uri(uri, contents).await;
+        let dir = server.definition(&uri, Position::new(1, 2)).await;
+        let file = server.definition(&uri, Position::new(2, 2)).await;
+        let missing = server.definition(&uri, Position::new(3, 2)).await;
+        let diagnostics = server.diagnostics(&uri).await;
+
+        let manifest = Url::from_file_path(base.join("core").join("Cargo.toml")).unwrap();
+        assert_eq!(dir.map(|l| l.uri), Some(manifest));
+        assert_eq!(file, None);
+        assert_eq!(missing, None);
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].range.start.line, 2);
+        assert!(diagnostics[0]
+            .message
+            .starts_with("`path` must point at the directory of a crate"));
+        assert_eq!(diagnostics[1].range.start.line, 3);
+        assert!(diagnostics[1]
+            .message
+            .starts_with("No `Cargo.toml` was found"));
+    }
+
+    #[tokio::test]
+    async fn test_cargo_inline_values_locked() {
+        let base = FixtureDir::new("inline-values-locked");
+        let lockfile = "[[package]]\nname = \"serde\"\nversion = \"1.0.100\"\n";
+        base.write("Cargo.lock", lockfile);
+        let server = TestServer::new(MockFetcher::new()).await;
+
+        let contents = "[dependencies]\nserde = \"1.0\"\ntokio = \"1\"\n";
+        let uri = Url::from_file_path(base.join("Cargo.toml")).unwrap();
+        let uri = server.open_uri(uri, contents).await;
+        let range = Range::new(Position::new(0, 0), Position::new(3, 0));
+        let params = InlineValueParams {
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            text_document: TextDocumentIdentifier { uri },
+            range,
+            context: InlineValueContext {
+                frame_id: 0,
+                stopped_location: range,
+            },
+        };
+
+        // Only dependencies that are in the lockfile get a value
+        let values = server.server().inline_value(params.clone()).await.unwrap();
+        let Some([InlineValue::Text(value)]) = values.as_deref() else {
+            panic!("expected a single inline value, got {values:?}");
+        };
+        assert_eq!(value.text, "locked: 1.0.100");
+        assert_eq!(value.range.start, Position::new(1, 0));
+
+        // The parsed lockfile is kept until the server is told that it changed
+        let lockfile = "[[package]]\nname = \"serde\"\nversion = \"1.0.200\"\n";
+        base.write("Cargo.lock", lockfile);
+        let locked_text = |values: Option<Vec<InlineValue>>| match values.as_deref() {
+            Some([InlineValue::Text(value)]) => value.text.clone(),
+            _ => panic!("expected a single inline value, got {values:?}"),
+        };
+        let values = server.server().inline_value(params.clone()).await.unwrap();
+        assert_eq!(locked_text(values), "locked: 1.0.100");
+        server
+            .server()
+            .did_change_watched_files(DidChangeWatchedFilesParams {
+                changes: vec![FileEvent {
+                    uri: Url::from_file_path(base.join("Cargo.lock")).unwrap(),
+                    typ: FileChangeType::CHANGED,
+                }],
+            })
+            .await;
+        let values = server.server().inline_value(params.clone()).await.unwrap();
+        assert_eq!(locked_text(values), "locked: 1.0.200");
+
+        let config = server.config().get();
+        server.config().set(Config {
+            features: FeaturesConfig {
+                inline_value: false,
+                ..config.features
+            },
+            ..config
+        });
+        let values = server.server().inline_value(params).await.unwrap();
+        assert_eq!(values, None);
+    }
+
+    #[tokio::test]
+    async fn test_cargo_limits_local_features() {
+        let server = TestServer::new(MockFetcher::new()).await;
+        let config = server.config().get();
+        server.config().set(Config {
+            limits: LimitsConfig {
+                max_dependencies: 1,
+                ..config.limits
+            },
+            ..config
+        });
+
+        let codes = |diags: &[Diagnostic]| {
+            diags
+                .iter()
+                .filter_map(|d| match &d.code {
+                    Some(NumberOrString::String(code)) => Some(code.clone()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Nothing is fetched for the dependencies of a manifest over the limits
+        let (contents, pos) = cursor_fixture(
+            "[dependencies]\nserde = { version = \"1\", registry = \"$0\" }\ntokio = \"1\"\n",
+        );
+        let uri = server.open("Cargo.toml", &contents).await;
+        let diagnostics = server.diagnostics(&uri).await;
+        assert_eq!(codes(&diagnostics), ["exceeds-limits"]);
+
+        // ... but local completions, such as registry names, still work
+        let items = server.completion(&uri, pos).await;
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].label, "crates-io");
+
+        // The same manifest is fetched for as usual once it is under the limits
+        let uri = server
+            .open("small/Cargo.toml", "[dependencies]\ntokio = \"1\"\n")
+            .await;
+        let diagnostics = server.diagnostics(&uri).await;
+        assert_eq!(codes(&diagnostics), ["unknown-package"]);
+    }
 }
@@ -0,0 +1,158 @@
+use tower_lsp::lsp_types::*;
+
+use crate::parser::{Dependency, Feature, Node};
+
+/**
+    Classification of a single value in a `[features]` table entry.
+*/
+pub enum FeatureValue<'a> {
+    Feature(&'a str),
+    Dependency(&'a str),
+    DependencyFeature {
+        dependency: &'a str,
+        feature: &'a str,
+        weak: bool,
+    },
+}
+
+impl<'a> FeatureValue<'a> {
+    pub fn classify(value: &'a str, features: &[Feature], dependencies: &[Dependency]) -> Self {
+        if let Some(dependency) = value.strip_prefix("dep:") {
+            Self::Dependency(dependency)
+        } else if let Some((dependency, feature)) = value.split_once('/') {
+            match dependency.strip_suffix('?') {
+                Some(dependency) => Self::DependencyFeature {
+                    dependency,
+                    feature,
+                    weak: true,
+                },
+                None => Self::DependencyFeature {
+                    dependency,
+                    feature,
+                    weak: false,
+                },
+            }
+        } else if features.iter().any(|f| f.name.unquoted() == value) {
+            Self::Feature(value)
+        } else if dependencies.iter().any(|d| {
+            d.name().unquoted() == value && d.spec().is_some_and(|s| s.contents.is_optional())
+        }) {
+            // Implicit feature for an optional dependency
+            Self::Dependency(value)
+        } else {
+            Self::Feature(value)
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Feature(feature) => format!("Feature `{feature}`"),
+            Self::Dependency(dependency) => format!("Optional dependency `{dependency}`"),
+            Self::DependencyFeature {
+                dependency,
+                feature,
+                weak: false,
+            } => format!("Feature `{feature}` of dependency `{dependency}`"),
+            Self::DependencyFeature {
+                dependency,
+                feature,
+                weak: true,
+            } => format!(
+                "Feature `{feature}` of dependency `{dependency}`, \
+                only if `{dependency}` is activated elsewhere"
+            ),
+        }
+    }
+}
+
+/**
+    A single occurrence of a dependency or feature name in a manifest.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Occurrence {
+    pub range: Range,
+    pub is_declaration: bool,
+}
+
+/**
+    Finds all occurrences of the dependency with the given name - the
+    dependency declarations themselves, and any mentions in `[features]`,
+    such as `dep:name`, `name/feature`, `name?/feature`, or implicit features.
+*/
+pub fn find_dependency_occurrences(
+    features: &[Feature],
+    dependencies: &[Dependency],
+    name: &str,
+) -> Vec<Occurrence> {
+    let mut occurrences = dependencies
+        .iter()
+        .filter(|dep| dep.name().unquoted() == name)
+        .map(|dep| Occurrence {
+            range: dep.name().unquoted_range(),
+            is_declaration: true,
+        })
+        .collect::<Vec<_>>();
+
+    for value in features.iter().flat_map(|f| f.values.contents.iter()) {
+        let mention = match FeatureValue::classify(value.unquoted(), features, dependencies) {
+            FeatureValue::Dependency(dependency) => dependency,
+            FeatureValue::DependencyFeature { dependency, .. } => dependency,
+            FeatureValue::Feature(_) => continue,
+        };
+        if mention == name {
+            occurrences.push(Occurrence {
+                range: dependency_mention_range(value, mention),
+                is_declaration: false,
+            });
+        }
+    }
+
+    occurrences
+}
+
+/**
+    Finds all occurrences of the feature with the given name - the
+    feature definition itself, and any other features that activate it.
+*/
+pub fn find_feature_occurrences(
+    features: &[Feature],
+    dependencies: &[Dependency],
+    name: &str,
+) -> Vec<Occurrence> {
+    let mut occurrences = features
+        .iter()
+        .filter(|f| f.name.unquoted() == name)
+        .map(|f| Occurrence {
+            range: f.name.unquoted_range(),
+            is_declaration: true,
+        })
+        .collect::<Vec<_>>();
+
+    for value in features.iter().flat_map(|f| f.values.contents.iter()) {
+        if let FeatureValue::Feature(feature) =
+            FeatureValue::classify(value.unquoted(), features, dependencies)
+        {
+            if feature == name {
+                occurrences.push(Occurrence {
+                    range: value.unquoted_range(),
+                    is_declaration: false,
+                });
+            }
+        }
+    }
+
+    occurrences
+}
+
+/**
+    Gets the range of a dependency name mentioned in a feature value - the
+    name is always at the start of the value, unless prefixed with `dep:`.
+*/
+fn dependency_mention_range(value: &Node<String>, dependency: &str) -> Range {
+    let offset = if value.unquoted().starts_with("dep:") {
+        "dep:".len()
+    } else {
+        0
+    };
+    value.unquoted_subrange(offset, dependency.len())
+}
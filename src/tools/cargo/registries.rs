@@ -0,0 +1,192 @@
+use tower_lsp::lsp_types::Url;
+use tracing::debug;
+
+use crate::clients::CargoConfigs;
+
+pub const DEFAULT_REGISTRY_NAME: &str = "crates-io";
+
+/**
+    Finds the names of all alternate registries configured in the
+    `[registries]` table of any Cargo config file that applies to
+    the manifest at the given uri, sorted alphabetically.
+*/
+pub async fn get_registry_names(configs: &CargoConfigs, manifest_uri: &Url) -> Vec<String> {
+    let mut names = Vec::new();
+    for config in configs.for_manifest(manifest_uri).await.iter() {
+        if let Some(registries) = config.table.get("registries").and_then(|r| r.as_table()) {
+            debug!(
+                "Found {} registries in Cargo config at '{}'",
+                registries.len(),
+                config.path.display()
+            );
+            names.extend(registries.keys().cloned());
+        }
+    }
+
+    names.sort_unstable();
+    names.dedup();
+    names
+}
+
+/**
+    Finds the index url of an alternate registry, the same way Cargo does it,
+    meaning from the `CARGO_REGISTRIES_<NAME>_INDEX` environment variable,
+    or from the `[registries]` table of the closest Cargo config file with it.
+*/
+pub async fn get_registry_index_url(
+    configs: &CargoConfigs,
+    manifest_uri: &Url,
+    registry: &str,
+) -> Option<String> {
+    if let Some(index) = registry_env_var(registry, "INDEX") {
+        return Some(index);
+    }
+
+    let configs = configs.for_manifest(manifest_uri).await;
+    configs.iter().find_map(|config| {
+        let index = config
+            .table
+            .get("registries")
+            .and_then(|r| r.get(registry))
+            .and_then(|r| r.get("index"))
+            .and_then(|i| i.as_str())?;
+        debug!(
+            "Found index '{index}' for registry '{registry}' in Cargo config at '{}'",
+            config.path.display()
+        );
+        Some(index.to_string())
+    })
+}
+
+/**
+    Gets the token for an alternate registry from the
+    `CARGO_REGISTRIES_<NAME>_TOKEN` environment variable, if set.
+*/
+pub fn get_registry_env_token(registry: &str) -> Option<String> {
+    registry_env_var(registry, "TOKEN")
+}
+
+fn registry_env_var(registry: &str, key: &str) -> Option<String> {
+    let name = registry.to_ascii_uppercase().replace('-', "_");
+    std::env::var(format!("CARGO_REGISTRIES_{name}_{key}"))
+        .ok()
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::StatusCode;
+    use tower_lsp::lsp_types::*;
+    use tower_lsp::LanguageServer;
+
+    use crate::server::testing::{MockFetcher, TestServer, SERDE_INDEX};
+    use crate::util::FixtureDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cargo_diagnostics_registry_auth() {
+        let base = FixtureDir::new("registry-auth");
+        base.write(
+            ".cargo/config.toml",
+            "[registries.private]\nindex = \"sparse+https://registry.example.com/index/\"\n",
+        );
+
+        let fetcher = MockFetcher::new()
+            .with_response("/se/rd/serde", SERDE_INDEX)
+            .with_status("registry.example.com", StatusCode::UNAUTHORIZED);
+        let server = TestServer::new(fetcher.clone()).await;
+        let contents = "[dependencies]\n\
+            serde = \"1.0.100\"\n\
+            secret = { version = \"1.0\", registry = \"private\" }\n";
+        let uri = Url::from_file_path(base.join("Cargo.toml")).unwrap();
+        let uri = server.open_uri(uri, contents).await;
+        let diagnostics = server.diagnostics(&uri).await;
+
+        // The index of the alternate registry is requested, never crates.io
+        let requested = fetcher.requested();
+        assert!(requested
+            .iter()
+            .any(|url| url == "https://registry.example.com/index/se/cr/secret"));
+        assert!(!requested
+            .iter()
+            .any(|url| url.contains("/se/cr/secret")
+                && !url.starts_with("https://registry.example.com")));
+
+        let auth = diagnostics
+            .iter()
+            .find(|d| d.code == Some(NumberOrString::String(String::from("registry-auth"))))
+            .expect("missing authentication diagnostic");
+        assert!(auth.message.contains("registry `private`"));
+        assert_eq!(auth.range.start, Position::new(2, 39));
+        assert_eq!(auth.severity, Some(DiagnosticSeverity::WARNING));
+
+        // Versions are not reported as missing when the registry could not be read
+        assert!(diagnostics
+            .iter()
+            .all(|d| d.code != Some(NumberOrString::String(String::from("unknown-version")))));
+    }
+
+    #[tokio::test]
+    async fn test_cargo_alternate_registry_hover_and_refresh() {
+        let base = FixtureDir::new("alternate-registry");
+        let write_config = |index: &str| {
+            let registry = format!("[registries.private]\nindex = \"sparse+{index}\"\n");
+            base.write(".cargo/config.toml", registry)
+        };
+        let config = write_config("https://one.example.com/index/");
+
+        let index = r#"{"name":"secret","vers":"1.0.0","deps":[],"features":{}}"#;
+        let fetcher = MockFetcher::new().with_response("/se/cr/secret", index);
+        let server = TestServer::new(fetcher.clone()).await;
+        let contents = "[dependencies]\nsecret = { version = \"1.0\", registry = \"private\" }\n";
+        let uri = Url::from_file_path(base.join("Cargo.toml")).unwrap();
+        let uri = server.open_uri(uri, contents).await;
+
+        // Crates in alternate registries are never looked up on crates.io
+        let hover = server.hover(&uri, Position::new(1, 2)).await;
+        assert!(hover.is_some_and(|h| h.contains("1.0")));
+        assert!(fetcher
+            .requested()
+            .iter()
+            .all(|url| !url.contains("crates.io")));
+
+        let refresh_index_url = |actions: Vec<CodeAction>| {
+            let action = actions
+                .into_iter()
+                .find(|a| a.title == "Refresh version info")
+                .expect("missing refresh action");
+            let arguments = action.command.and_then(|c| c.arguments).unwrap();
+            arguments[0]["dependency"]["indexUrl"]
+                .as_str()
+                .map(ToString::to_string)
+        };
+        let actions = server.code_actions(&uri, Position::new(1, 2)).await;
+        assert_eq!(
+            refresh_index_url(actions).as_deref(),
+            Some("https://one.example.com/index/")
+        );
+
+        // Cargo configs are cached until they are changed
+        write_config("https://two.example.com/index/");
+        let actions = server.code_actions(&uri, Position::new(1, 2)).await;
+        assert_eq!(
+            refresh_index_url(actions).as_deref(),
+            Some("https://one.example.com/index/")
+        );
+        server
+            .server()
+            .did_change_watched_files(DidChangeWatchedFilesParams {
+                changes: vec![FileEvent {
+                    uri: Url::from_file_path(&config).unwrap(),
+                    typ: FileChangeType::CHANGED,
+                }],
+            })
+            .await;
+        let actions = server.code_actions(&uri, Position::new(1, 2)).await;
+        assert_eq!(
+            refresh_index_url(actions).as_deref(),
+            Some("https://two.example.com/index/")
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use tower_lsp::jsonrpc::{Error, Result};
+use tower_lsp::lsp_types::*;
+
+use crate::parser::{Dependency, Feature, Node};
+use crate::server::Document;
+
+use super::occurrences::{find_feature_occurrences, FeatureValue};
+
+/**
+    The feature that is enabled by default, which has a special
+    meaning to Cargo, and which can therefore never be renamed.
+*/
+const DEFAULT_FEATURE_NAME: &str = "default";
+
+/**
+    Finds the feature name at the given position that can be renamed, which is
+    either the name of a feature in the `[features]` table, or a mention of such
+    a feature in the values of another one.
+
+    Dependencies, versions, and features of other crates are never renamed.
+*/
+fn find_renameable_feature<'a>(
+    features: &'a [Feature],
+    dependencies: &[Dependency],
+    pos: Position,
+) -> Option<&'a Node<String>> {
+    let feature = Feature::find_at_pos(features, pos)?;
+    let node = if feature.name.contains(pos) {
+        &feature.name
+    } else {
+        let value = feature.values.contents.iter().find(|v| v.contains(pos))?;
+        match FeatureValue::classify(value.unquoted(), features, dependencies) {
+            FeatureValue::Feature(_) => value,
+            _ => return None,
+        }
+    };
+
+    // Only features that are actually defined can be renamed everywhere
+    let name = node.unquoted();
+    let is_defined = features.iter().any(|f| f.name.unquoted() == name);
+    (is_defined && name != DEFAULT_FEATURE_NAME).then_some(node)
+}
+
+pub fn get_cargo_prepare_rename(
+    features: &[Feature],
+    dependencies: &[Dependency],
+    pos: Position,
+) -> Option<PrepareRenameResponse> {
+    let node = find_renameable_feature(features, dependencies, pos)?;
+    Some(PrepareRenameResponse::RangeWithPlaceholder {
+        range: node.unquoted_range(),
+        placeholder: node.unquoted().to_string(),
+    })
+}
+
+pub fn get_cargo_rename(
+    doc: &Document,
+    features: &[Feature],
+    dependencies: &[Dependency],
+    pos: Position,
+    new_name: &str,
+) -> Result<Option<WorkspaceEdit>> {
+    let Some(node) = find_renameable_feature(features, dependencies, pos) else {
+        return Ok(None);
+    };
+    if !is_valid_feature_name(new_name) {
+        return Err(Error::invalid_params(format!(
+            "`{new_name}` is not a valid feature name"
+        )));
+    }
+    if node.unquoted() == new_name {
+        return Ok(None);
+    }
+    if features.iter().any(|f| f.name.unquoted() == new_name) {
+        return Err(Error::invalid_params(format!(
+            "A feature named `{new_name}` already exists"
+        )));
+    }
+
+    let edits = find_feature_occurrences(features, dependencies, node.unquoted())
+        .into_iter()
+        .map(|occurrence| doc.create_edit(occurrence.range, new_name))
+        .collect();
+    Ok(Some(WorkspaceEdit {
+        changes: Some(HashMap::from([(doc.uri().clone(), edits)])),
+        ..Default::default()
+    }))
+}
+
+/**
+    Checks if a name is allowed as a feature name by Cargo, meaning that it
+    starts with a letter, digit, or underscore, and otherwise only contains
+    letters, digits, and the characters `_`, `-`, `+`, and `.`.
+
+    The `default` feature is also rejected, since renaming any other
+    feature to it would change which features are enabled by default.
+*/
+fn is_valid_feature_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_alphanumeric() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '+' | '.'))
+        && name != DEFAULT_FEATURE_NAME
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::testing::{MockFetcher, TestServer};
+
+    use super::*;
+
+    #[test]
+    fn test_valid_feature_names() {
+        assert!(is_valid_feature_name("serde"));
+        assert!(is_valid_feature_name("_internal-1.0+extra"));
+        assert!(!is_valid_feature_name(""));
+        assert!(!is_valid_feature_name("-leading"));
+        assert!(!is_valid_feature_name("dep:serde"));
+        assert!(!is_valid_feature_name("serde/std"));
+        assert!(!is_valid_feature_name("default"));
+    }
+
+    #[tokio::test]
+    async fn test_cargo_prepare_rename() {
+        let server = TestServer::new(MockFetcher::new()).await;
+
+        let contents = "[dependencies]\n\
+            serde = { version = \"1.0\", optional = true }\n\
+            [features]\n\
+            default = [\"std\"]\n\
+            std = [\"dep:serde\", \"serde/std\"]\n";
+        let uri = server.open("Cargo.toml", contents).await;
+
+        // Feature names can be renamed, both where defined and where mentioned
+        let prepared = server.prepare_rename(&uri, Position::new(4, 1)).await;
+        assert_eq!(
+            prepared,
+            Some((
+                Range::new(Position::new(4, 0), Position::new(4, 3)),
+                String::from("std")
+            ))
+        );
+        let prepared = server.prepare_rename(&uri, Position::new(3, 13)).await;
+        assert_eq!(prepared.map(|(_, p)| p).as_deref(), Some("std"));
+
+        // Crate names, versions, dependency mentions, and the default feature can not
+        assert_eq!(server.prepare_rename(&uri, Position::new(1, 2)).await, None);
+        assert_eq!(
+            server.prepare_rename(&uri, Position::new(1, 21)).await,
+            None
+        );
+        assert_eq!(
+            server.prepare_rename(&uri, Position::new(4, 12)).await,
+            None
+        );
+        assert_eq!(server.prepare_rename(&uri, Position::new(3, 2)).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_cargo_rename_feature() {
+        let server = TestServer::new(MockFetcher::new()).await;
+
+        let contents = "[features]\n\
+            default = [\"std\"]\n\
+            std = []\n\
+            full = [\"std\"]\n";
+        let uri = server.open("Cargo.toml", contents).await;
+
+        let mut edits = server.rename(&uri, Position::new(2, 1), "alloc").await;
+        edits.sort_by_key(|edit| edit.range.start);
+        let lines = edits
+            .iter()
+            .map(|edit| (edit.range.start.line, edit.new_text.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![(1, "alloc"), (2, "alloc"), (3, "alloc")]);
+    }
+}
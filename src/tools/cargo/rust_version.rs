@@ -0,0 +1,287 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures::future::join_all;
+use semver::{Version, VersionReq};
+use tower_lsp::jsonrpc::Result;
+use tower_lsp::lsp_types::*;
+
+use crate::parser::{Dependency, DependencySource, TableField};
+use crate::tools::{with_sort_texts, DiagnosticCode, MarkdownBuilder};
+
+use super::crates::models::IndexMetadata;
+use super::util::get_metadatas;
+use super::{Clients, Document};
+
+/**
+    The start of the day before Rust 1.0 was released, as a unix timestamp.
+
+    Every stable release since has been on a Thursday, six weeks after the
+    one before it, so the latest stable version is known without the network.
+*/
+const RUST_1_0_TIMESTAMP: u64 = 1_431_561_600;
+const RELEASE_CYCLE_SECONDS: u64 = 42 * 86_400;
+
+/**
+    How many of the most recent stable versions to offer as completions.
+*/
+const RECENT_VERSIONS_SHOWN: u64 = 10;
+
+/**
+    A dependency where every version matching its requirement
+    needs a newer Rust version than the package declares.
+*/
+pub struct RustVersionConflict<'a> {
+    pub dependency: &'a Dependency,
+    pub required: Version,
+}
+
+/**
+    Gets the minor version of the latest stable Rust release, such as `83` for `1.83`.
+*/
+pub fn latest_stable_minor() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    stable_minor_at(now)
+}
+
+fn stable_minor_at(timestamp: u64) -> u64 {
+    timestamp.saturating_sub(RUST_1_0_TIMESTAMP) / RELEASE_CYCLE_SECONDS
+}
+
+/**
+    Parses a Rust version as written in `rust-version`, such as `1.70` or
+    `1.70.1`, where any missing components are zero. Pre-releases are not allowed.
+*/
+pub fn parse_rust_version(version: &str) -> Option<Version> {
+    let mut parts = version.trim().split('.');
+    let mut next = || parts.next().map(|part| part.parse::<u64>().ok());
+    let major = next()??;
+    let minor = next().unwrap_or(Some(0))?;
+    let patch = next().unwrap_or(Some(0))?;
+    if next().is_some() {
+        return None;
+    }
+    Some(Version::new(major, minor, patch))
+}
+
+/**
+    Gets the lowest Rust version that any version matching the requirement needs,
+    or `None` if any matching version does not declare a Rust version at all.
+*/
+fn minimum_rust_version(metas: &[IndexMetadata], req: &VersionReq) -> Option<Version> {
+    let mut minimum = None::<Version>;
+    for meta in metas {
+        if !meta.parse_version().is_ok_and(|v| req.matches(&v)) {
+            continue;
+        }
+        let required = parse_rust_version(meta.rust_version.as_deref()?)?;
+        if minimum.as_ref().is_none_or(|m| required < *m) {
+            minimum = Some(required);
+        }
+    }
+    minimum
+}
+
+/**
+    Finds all dependencies from the registry that need a newer
+    Rust version than the declared one, fetching them concurrently.
+*/
+pub async fn find_rust_version_conflicts<'a>(
+    clients: &Clients,
+    doc: &Document,
+    dependencies: &'a [Dependency],
+    declared: &Version,
+) -> Vec<RustVersionConflict<'a>> {
+    let futs = dependencies.iter().map(|dep| async move {
+        let spec = &dep.spec()?.contents;
+        if spec.source != DependencySource::Registry || spec.registry.is_some() {
+            return None;
+        }
+        let req = VersionReq::parse(spec.version.as_ref()?.unquoted()).ok()?;
+        let metas = get_metadatas(clients, doc, dep.package_name().unquoted())
+            .await
+            .ok()?;
+        let required = minimum_rust_version(&metas, &req)?;
+        (required > *declared).then_some(RustVersionConflict {
+            dependency: dep,
+            required,
+        })
+    });
+    join_all(futs).await.into_iter().flatten().collect()
+}
+
+pub fn get_cargo_diagnostics_rust_version(
+    field: &TableField,
+    conflicts: &[RustVersionConflict],
+) -> Vec<Diagnostic> {
+    conflicts
+        .iter()
+        .map(|conflict| {
+            let dep = conflict.dependency;
+            let version = dep.spec().and_then(|s| s.contents.version.as_ref());
+            let message = format!(
+                "Every version of `{}` matching `{}` needs Rust {} or newer, \
+                but the package declares `rust-version = \"{}\"`",
+                dep.package_name().unquoted(),
+                version.map_or("*", |v| v.unquoted()),
+                conflict.required,
+                field.value.unquoted(),
+            );
+            Diagnostic {
+                source: Some(String::from("Cargo")),
+                range: version.map_or(dep.name().range, |v| v.range),
+                message,
+                severity: Some(DiagnosticSeverity::WARNING),
+                ..DiagnosticCode::RustVersion.diagnostic()
+            }
+        })
+        .collect()
+}
+
+pub fn get_cargo_completions_rust_version(
+    doc: &Document,
+    field: &TableField,
+) -> Result<CompletionResponse> {
+    let latest = latest_stable_minor();
+    let range = field.value.unquoted_range();
+    let items = (latest.saturating_sub(RECENT_VERSIONS_SHOWN - 1)..=latest)
+        .rev()
+        .map(|minor| {
+            let label = format!("1.{minor}");
+            CompletionItem {
+                label: label.clone(),
+                kind: Some(CompletionItemKind::VALUE),
+                detail: (minor == latest).then(|| String::from("Latest stable")),
+                text_edit: Some(CompletionTextEdit::Edit(doc.create_edit(range, label))),
+                ..Default::default()
+            }
+        });
+    Ok(CompletionResponse::Array(with_sort_texts(items)))
+}
+
+pub fn get_cargo_hover_rust_version(
+    field: &TableField,
+    conflicts: &[RustVersionConflict],
+) -> Result<Option<Hover>> {
+    let latest = latest_stable_minor();
+    let declared = field.value.unquoted();
+
+    let mut md = MarkdownBuilder::new();
+    md.h2(format!("Rust {declared}"));
+    match parse_rust_version(declared) {
+        None => md.p("Not a valid Rust version, expected a version such as `1.70`"),
+        Some(version) if version.major == 1 && version.minor > latest => {
+            md.p("This version of Rust has not been released yet");
+        }
+        Some(version) if version.major == 1 && version.minor == latest => {
+            md.p("The latest stable version of Rust");
+        }
+        Some(_) => md.p(format!("The latest stable version of Rust is 1.{latest}")),
+    }
+
+    if !conflicts.is_empty() {
+        md.br();
+        md.h3("Dependencies that need a newer Rust version");
+        for conflict in conflicts {
+            md.p(format!(
+                "`{}` needs Rust {}",
+                conflict.dependency.name().unquoted(),
+                conflict.required
+            ));
+        }
+    }
+
+    Ok(Some(Hover {
+        range: Some(field.range()),
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: md.build(),
+        }),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::testing::{cursor_fixture, MockFetcher, TestServer};
+
+    use super::*;
+
+    #[test]
+    fn test_stable_minor() {
+        // Rust 1.83 was released on 2024-11-28, and 1.84 six weeks later
+        assert_eq!(stable_minor_at(RUST_1_0_TIMESTAMP), 0);
+        assert_eq!(stable_minor_at(1_732_752_000), 83);
+        assert_eq!(
+            stable_minor_at(1_732_752_000 + RELEASE_CYCLE_SECONDS - 1),
+            83
+        );
+        assert_eq!(stable_minor_at(1_732_752_000 + RELEASE_CYCLE_SECONDS), 84);
+    }
+
+    #[test]
+    fn test_parse_rust_version() {
+        assert_eq!(parse_rust_version("1.70"), Some(Version::new(1, 70, 0)));
+        assert_eq!(parse_rust_version("1.70.1"), Some(Version::new(1, 70, 1)));
+        assert_eq!(parse_rust_version("1"), Some(Version::new(1, 0, 0)));
+        assert_eq!(parse_rust_version("1.70.0-beta"), None);
+        assert_eq!(parse_rust_version("1.70.0.1"), None);
+        assert_eq!(parse_rust_version(""), None);
+    }
+
+    #[test]
+    fn test_minimum_rust_version() {
+        let meta = |version: &str, rust_version: Option<&str>| IndexMetadata {
+            name: String::from("foo"),
+            version: version.to_string(),
+            dependencies: Vec::new(),
+            features: Default::default(),
+            links: None,
+            rust_version: rust_version.map(ToString::to_string),
+        };
+        let metas = [
+            meta("0.9.0", None),
+            meta("1.0.0", Some("1.60")),
+            meta("1.1.0", Some("1.70")),
+        ];
+        let req = |req: &str| VersionReq::parse(req).unwrap();
+        assert_eq!(
+            minimum_rust_version(&metas, &req("1.0")),
+            Some(Version::new(1, 60, 0))
+        );
+        assert_eq!(
+            minimum_rust_version(&metas, &req("1.1")),
+            Some(Version::new(1, 70, 0))
+        );
+        assert_eq!(minimum_rust_version(&metas, &req("0.9")), None);
+    }
+
+    #[tokio::test]
+    async fn test_cargo_rust_version() {
+        let index =
+            r#"{"name":"serde","vers":"1.0.100","deps":[],"features":{},"rust_version":"1.75"}"#;
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", index);
+        let server = TestServer::new(fetcher).await;
+
+        let (contents, pos) = cursor_fixture(
+            "[package]\nname = \"foo\"\nrust-version = \"1.7$00\"\n\n[dependencies]\nserde = \"1.0\"\n",
+        );
+        let uri = server.open("Cargo.toml", &contents).await;
+
+        let items = server.completion(&uri, pos).await;
+        assert_eq!(items.len(), 10);
+        assert_eq!(items[0].detail.as_deref(), Some("Latest stable"));
+        assert!(items.iter().all(|item| item.label.starts_with("1.")));
+
+        let hover = server.hover(&uri, pos).await.expect("missing hover");
+        assert!(hover.contains("## Rust 1.70"));
+        assert!(hover.contains("`serde` needs Rust 1.75.0"));
+
+        let diagnostics = server.diagnostics(&uri).await;
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:#?}");
+        assert!(diagnostics[0]
+            .message
+            .contains("needs Rust 1.75.0 or newer"));
+        assert_eq!(diagnostics[0].range.start, Position::new(5, 8));
+    }
+}
@@ -0,0 +1,42 @@
+use tower_lsp::lsp_types::*;
+
+use crate::parser::Target;
+use crate::tools::{ManifestInfo, ManifestTarget};
+
+pub fn get_cargo_document_symbols(targets: &[Target]) -> Vec<DocumentSymbol> {
+    targets
+        .iter()
+        .map(|target| {
+            let kind = target.kind.contents.as_str();
+            let name = match &target.name {
+                Some(name) => name.unquoted().to_string(),
+                None => format!("[[{kind}]]"),
+            };
+            let detail = match &target.path {
+                Some(path) => format!("{kind} - {}", path.unquoted()),
+                None => kind.to_string(),
+            };
+            #[allow(deprecated)]
+            DocumentSymbol {
+                name,
+                detail: Some(detail),
+                kind: SymbolKind::MODULE,
+                tags: None,
+                deprecated: None,
+                range: target.range,
+                selection_range: target
+                    .name
+                    .as_ref()
+                    .map(|n| n.range)
+                    .unwrap_or(target.kind.range),
+                children: None,
+            }
+        })
+        .collect()
+}
+
+pub fn get_cargo_manifest_info(targets: &[Target]) -> ManifestInfo {
+    ManifestInfo {
+        targets: targets.iter().map(ManifestTarget::from).collect(),
+    }
+}
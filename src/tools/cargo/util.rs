@@ -1,14 +1,198 @@
-use crate::parser::Dependency;
+use semver::VersionReq;
 
-use super::Clients;
+use crate::parser::{Dependency, TableField};
+use crate::util::{RequestError, RequestResult, VersionReqExt, Versioned};
 
-pub async fn get_features(clients: &Clients, dep: &Dependency) -> Vec<String> {
-    let dname = dep.name().unquoted();
+use super::crates::models::{pick_highest, IndexMetadata};
+use super::registries::{get_registry_env_token, get_registry_index_url, DEFAULT_REGISTRY_NAME};
+use super::vendor::find_vendor_dir;
+use super::{Clients, Document};
 
-    let Ok(metas) = clients.crates.get_sparse_index_crate_metadatas(dname).await else {
+/**
+    Gets metadatas for all versions of a crate, reading them from
+    vendored crates instead of the index if the project vendors them.
+*/
+pub async fn get_metadatas(
+    clients: &Clients,
+    doc: &Document,
+    name: &str,
+) -> RequestResult<Vec<IndexMetadata>> {
+    let vendor_dir = find_vendor_dir(&clients.cargo_configs, doc.uri()).await;
+    clients
+        .crates
+        .get_index_metadatas(vendor_dir.as_deref(), name)
+        .await
+}
+
+/**
+    Gets metadatas for all versions of a dependency, from the sparse index of
+    its alternate registry if it has one, and otherwise using `get_metadatas`.
+
+    Tokens for alternate registries are read from Cargo's `credentials.toml`,
+    or from the `CARGO_REGISTRIES_<NAME>_TOKEN` environment variable.
+*/
+pub async fn get_dependency_metadatas(
+    clients: &Clients,
+    doc: &Document,
+    dep: &Dependency,
+) -> RequestResult<Vec<IndexMetadata>> {
+    let name = dep.package_name().unquoted();
+    let Some(registry) = alternate_registry(dep) else {
+        return get_metadatas(clients, doc, name).await;
+    };
+
+    let index_url = get_alternate_registry_index_url(clients, doc, registry).await?;
+    let token = clients
+        .registry_token(registry)
+        .or_else(|| get_registry_env_token(registry));
+    clients
+        .crates
+        .get_registry_index_metadatas(&index_url, name, token.as_deref())
+        .await
+}
+
+/**
+    Returns the alternate registry of a dependency, if it has one that is not crates.io.
+*/
+pub fn alternate_registry(dep: &Dependency) -> Option<&str> {
+    dep.spec()
+        .and_then(|s| s.contents.registry.as_ref())
+        .map(|r| r.unquoted())
+        .filter(|r| *r != DEFAULT_REGISTRY_NAME)
+}
+
+/**
+    Gets the sparse index url of the alternate registry of a dependency, without
+    `sparse+`, failing if the registry is not configured or uses a git index.
+*/
+pub async fn get_alternate_registry_index_url(
+    clients: &Clients,
+    doc: &Document,
+    registry: &str,
+) -> RequestResult<String> {
+    let Some(index_url) = get_registry_index_url(&clients.cargo_configs, doc.uri(), registry).await
+    else {
+        return Err(RequestError::Client(format!(
+            "Registry `{registry}` is not configured"
+        )));
+    };
+    // NOTE: Git indexes would need to be cloned, which we never do
+    match index_url.strip_prefix("sparse+") {
+        Some(index_url) => Ok(index_url.to_string()),
+        None => Err(RequestError::Client(format!(
+            "Registry `{registry}` does not use a sparse index"
+        ))),
+    }
+}
+
+/**
+    Gets metadatas for at most `limit` of the highest versions of a dependency
+    that `filter` returns true for, meant for completions, which only show a few.
+
+    Crates from crates.io that are not vendored only get the picked versions parsed,
+    which matters for crates with thousands of versions, while any other crates have
+    all of their versions read as usual, before picking the highest ones out of them.
+*/
+pub async fn get_dependency_metadatas_highest(
+    clients: &Clients,
+    doc: &Document,
+    dep: &Dependency,
+    limit: usize,
+    filter: impl Fn(&str) -> bool,
+) -> RequestResult<Vec<IndexMetadata>> {
+    let uses_default_registry = alternate_registry(dep).is_none();
+    if uses_default_registry
+        && find_vendor_dir(&clients.cargo_configs, doc.uri())
+            .await
+            .is_none()
+    {
+        let name = dep.package_name().unquoted();
+        return clients
+            .crates
+            .get_sparse_index_highest_metadatas(name, limit, filter)
+            .await;
+    }
+    let metas = get_dependency_metadatas(clients, doc, dep).await?;
+    Ok(pick_highest(metas, limit, filter))
+}
+
+/**
+    Gets metadatas for only the newest versions of a dependency that hovers and
+    diagnostics look at, instead of all of its versions, which are the newest one
+    that matches its requirement, the newest stable one, and the newest prerelease
+    of the version that the requirement starts at, such as `1.0.0-rc.2` for `1.0.0-rc.1`.
+
+    Any of them may be missing, such as if no version matches the requirement.
+*/
+pub async fn get_dependency_metadatas_newest(
+    clients: &Clients,
+    doc: &Document,
+    dep: &Dependency,
+) -> RequestResult<Vec<IndexMetadata>> {
+    let req = dep
+        .spec()
+        .and_then(|s| s.contents.version.as_ref())
+        .and_then(|v| VersionReq::parse(v.unquoted()).ok());
+    let min = req.as_ref().map(VersionReqExt::minimum_version);
+
+    let matching = |v: &str| {
+        req.as_ref()
+            .is_some_and(|req| v.parse_version().is_ok_and(|v| req.matches(&v)))
+    };
+    let stable = |v: &str| v.parse_version().is_ok_and(|v| v.pre.is_empty());
+    let same_prerelease = |v: &str| {
+        v.parse_version().is_ok_and(|v| {
+            min.as_ref()
+                .is_some_and(|m| (m.major, m.minor, m.patch) == (v.major, v.minor, v.patch))
+        })
+    };
+
+    let mut newest = Vec::<IndexMetadata>::new();
+    for filter in [
+        &matching as &dyn Fn(&str) -> bool,
+        &stable,
+        &same_prerelease,
+    ] {
+        let picked = get_dependency_metadatas_highest(clients, doc, dep, 1, filter).await?;
+        for meta in picked {
+            if !newest.iter().any(|m| m.version == meta.version) {
+                newest.push(meta);
+            }
+        }
+    }
+    Ok(newest)
+}
+
+/**
+    Gets the known features of a dependency, only using the versions that match
+    its version requirement, since features may be added or removed between versions.
+    Both completions and diagnostics use this, so that they agree on which features exist.
+
+    Only the matching versions are parsed from the index, and the features of all
+    versions are only used if there is no requirement, or if no versions match it,
+    such as while the version is still being typed.
+*/
+pub async fn get_features(clients: &Clients, doc: &Document, dep: &Dependency) -> Vec<String> {
+    let req = dep
+        .spec()
+        .and_then(|s| s.contents.version.as_ref())
+        .and_then(|v| VersionReq::parse(v.unquoted()).ok());
+    if let Some(req) = req {
+        let matching = |v: &str| v.parse_version().is_ok_and(|v| req.matches(&v));
+        match get_dependency_metadatas_highest(clients, doc, dep, usize::MAX, matching).await {
+            Ok(metas) if !metas.is_empty() => return collect_features(&metas),
+            Ok(_) => {}
+            Err(_) => return Vec::new(),
+        }
+    }
+
+    let Ok(metas) = get_dependency_metadatas(clients, doc, dep).await else {
         return Vec::new();
     };
+    collect_features(&metas)
+}
 
+fn collect_features(metas: &[IndexMetadata]) -> Vec<String> {
     let mut known_features = metas
         .iter()
         .flat_map(|meta| meta.features.keys().cloned())
@@ -18,3 +202,13 @@ pub async fn get_features(clients: &Clients, dep: &Dependency) -> Vec<String> {
     known_features.dedup();
     known_features
 }
+
+/**
+    Gets the edition of the package from its `[package]` fields,
+    which defaults to `2015` if no edition has been specified.
+*/
+pub fn get_edition(package_fields: &[TableField]) -> u32 {
+    TableField::find_by_name(package_fields, "edition")
+        .and_then(|field| field.value.unquoted().parse::<u32>().ok())
+        .unwrap_or(2015)
+}
@@ -0,0 +1,122 @@
+use std::path::PathBuf;
+
+use tokio::fs;
+use tower_lsp::lsp_types::Url;
+use tracing::debug;
+
+use crate::clients::CargoConfigs;
+
+use super::registries::DEFAULT_REGISTRY_NAME;
+
+/**
+    The directory that `cargo vendor` writes vendored crates to by default.
+*/
+const DEFAULT_VENDOR_DIR_NAME: &str = "vendor";
+
+/**
+    Finds the directory that crates are vendored into, for the manifest at
+    the given uri, so that their info can be read without the network.
+
+    Prefers a directory source that replaces `crates-io` in any Cargo config
+    file, which is what `cargo vendor` asks to be added, and otherwise falls
+    back to a `vendor` directory next to the lockfile of the manifest.
+
+    This is needed for every dependency, so the directory that was found is
+    cached together with the config files, until either a config or lockfile changes.
+*/
+pub async fn find_vendor_dir(configs: &CargoConfigs, manifest_uri: &Url) -> Option<PathBuf> {
+    configs
+        .vendor_dir(manifest_uri, search_vendor_dir(configs, manifest_uri))
+        .await
+}
+
+async fn search_vendor_dir(configs: &CargoConfigs, manifest_uri: &Url) -> Option<PathBuf> {
+    let manifest_path = manifest_uri.to_file_path().ok()?;
+
+    // Config files that come first take precedence, and paths in
+    // them are relative to the directory that contains `.cargo`
+    let configs = configs.for_manifest(manifest_uri).await;
+
+    let source = |table: &toml::Table, name: &str| {
+        table
+            .get("source")
+            .and_then(|s| s.get(name))
+            .and_then(|s| s.as_table())
+            .cloned()
+    };
+    let replacement = configs.iter().find_map(|config| {
+        source(&config.table, DEFAULT_REGISTRY_NAME)?
+            .get("replace-with")?
+            .as_str()
+            .map(ToString::to_string)
+    });
+    if let Some(replacement) = replacement {
+        let found = configs.iter().find_map(|config| {
+            let directory = source(&config.table, &replacement)?
+                .get("directory")?
+                .as_str()
+                .map(|d| config.base_dir().join(d))?;
+            Some((&config.path, directory))
+        });
+        if let Some((config_path, directory)) = found {
+            debug!(
+                "Found vendored source '{replacement}' at '{}' in Cargo config at '{}'",
+                directory.display(),
+                config_path.display()
+            );
+            return Some(directory);
+        }
+    }
+
+    for dir in manifest_path.ancestors().skip(1) {
+        let vendor_dir = dir.join(DEFAULT_VENDOR_DIR_NAME);
+        let has_lockfile = fs::try_exists(dir.join("Cargo.lock"))
+            .await
+            .unwrap_or_default();
+        if has_lockfile && fs::metadata(&vendor_dir).await.is_ok_and(|m| m.is_dir()) {
+            debug!("Found vendor directory at '{}'", vendor_dir.display());
+            return Some(vendor_dir);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::FixtureDir;
+
+    use super::*;
+
+    fn member_uri(root: &FixtureDir) -> Url {
+        Url::from_file_path(root.join("crates").join("member").join("Cargo.toml")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_vendor_dir_from_config() {
+        let root = FixtureDir::new("vendor-config");
+        root.write(
+            ".cargo/config.toml",
+            r#"
+            [source.crates-io]
+            replace-with = "vendored-sources"
+
+            [source.vendored-sources]
+            directory = "third-party/crates"
+            "#,
+        );
+
+        let found = find_vendor_dir(&CargoConfigs::default(), &member_uri(&root)).await;
+        assert_eq!(found, Some(root.join("third-party").join("crates")));
+    }
+
+    #[tokio::test]
+    async fn test_vendor_dir_next_to_lockfile() {
+        let root = FixtureDir::new("vendor-default");
+        root.write("Cargo.lock", "");
+        root.write("vendor/serde/Cargo.toml", "");
+
+        let found = find_vendor_dir(&CargoConfigs::default(), &member_uri(&root)).await;
+        assert_eq!(found, Some(root.join("vendor")));
+    }
+}
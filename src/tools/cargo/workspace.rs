@@ -0,0 +1,1141 @@
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use semver::VersionReq;
+use tokio::fs;
+use tower_lsp::jsonrpc::Result;
+use tower_lsp::lsp_types::*;
+use tracing::trace;
+
+use crate::parser::{
+    query_cargo_toml_dependencies, query_cargo_toml_package_fields, query_cargo_toml_table_headers,
+    query_cargo_toml_workspace_dependencies, query_cargo_toml_workspace_fields,
+    query_cargo_toml_workspace_package_fields, Dependency, DependencySource, Node, TableField,
+    TreeSitterDocument,
+};
+use crate::server::Documents;
+use crate::tools::{
+    normalize_path, resolve_manifest_path, wildcard_matches, DiagnosticCode, MarkdownBuilder,
+    ResolveContext,
+};
+
+use super::actions::get_cargo_workspace_version_metadata;
+use super::Document;
+
+const MAXIMUM_MEMBERS_SHOWN: usize = 16;
+const CARGO_MANIFEST: &str = "Cargo.toml";
+
+/**
+    A single crate in a workspace, along with the directories
+    of all the crates that it depends on using `path`.
+*/
+#[derive(Debug, Clone)]
+pub struct WorkspaceMember {
+    pub name: String,
+    pub dir: PathBuf,
+    pub path_dependencies: Vec<PathBuf>,
+}
+
+/**
+    A cache of workspace members, keyed by the directory of their workspace manifest.
+
+    Reading and parsing every member manifest is too slow to do on each
+    hover, so members are discovered once per workspace, and then kept
+    around until any manifest inside of that workspace is changed.
+
+    The workspace that each manifest directory belongs to is also kept here,
+    since finding it reads every manifest above the directory, and is needed
+    for diagnostics, completions, and hovers of every member.
+*/
+#[derive(Debug, Default, Clone)]
+pub struct WorkspaceCache {
+    members: Arc<DashMap<PathBuf, Arc<Vec<WorkspaceMember>>>>,
+    roots: Arc<DashMap<PathBuf, Option<PathBuf>>>,
+}
+
+impl WorkspaceCache {
+    pub async fn members(
+        &self,
+        documents: &Documents,
+        workspace_dir: &Path,
+    ) -> Arc<Vec<WorkspaceMember>> {
+        if let Some(members) = self.members.get(workspace_dir) {
+            return Arc::clone(&members);
+        }
+
+        trace!(
+            "Discovering workspace members in {}",
+            workspace_dir.display()
+        );
+        let members = Arc::new(discover_members(documents, workspace_dir).await);
+        self.members
+            .insert(workspace_dir.to_path_buf(), Arc::clone(&members));
+        members
+    }
+
+    /**
+        Gets the directory of the workspace that the manifest in the
+        given directory belongs to, if any, see `find_workspace_dir`.
+    */
+    pub async fn workspace_dir(
+        &self,
+        documents: &Documents,
+        manifest_dir: &Path,
+    ) -> Option<PathBuf> {
+        if let Some(found) = self.roots.get(manifest_dir) {
+            return found.clone();
+        }
+
+        let found = find_workspace_dir(documents, manifest_dir).await;
+        self.roots.insert(manifest_dir.to_path_buf(), found.clone());
+        found
+    }
+
+    pub fn invalidate(&self, uri: &Url) {
+        let Ok(path) = uri.to_file_path() else {
+            return;
+        };
+        self.members
+            .retain(|workspace_dir, _| !path.starts_with(workspace_dir));
+
+        // Any manifest may start or stop being the workspace of the ones below it
+        if path.file_name().is_some_and(|name| name == CARGO_MANIFEST) {
+            if let Some(dir) = path.parent() {
+                self.roots
+                    .retain(|manifest_dir, _| !manifest_dir.starts_with(dir));
+            }
+        }
+    }
+}
+
+pub async fn get_cargo_hover_path(
+    doc: &Document,
+    documents: &Documents,
+    workspaces: &WorkspaceCache,
+    dep: &Dependency,
+    path: &Node<String>,
+) -> Result<Option<Hover>> {
+    let Some(manifest_dir) = manifest_dir(doc) else {
+        return Ok(None);
+    };
+    let target = crate_dir(&manifest_dir, path.unquoted());
+
+    let mut md = MarkdownBuilder::new();
+    md.h2(dep.name().unquoted());
+    if dep.is_renamed() {
+        md.p(format!("Aliased from `{}`", dep.package_name().unquoted()));
+        md.br();
+    }
+    if let Some(version) = dep.spec().and_then(|s| s.contents.version.as_ref()) {
+        md.version(version.unquoted());
+        md.br();
+    }
+    md.p(format!("Local crate at `{}`", path.unquoted()));
+
+    // List any other workspace members that depend on the same local crate
+    if let Some(workspace_dir) = workspaces.workspace_dir(documents, &manifest_dir).await {
+        let members = workspaces.members(documents, &workspace_dir).await;
+        let users = members
+            .iter()
+            .filter(|member| member.dir != manifest_dir)
+            .filter(|member| member.path_dependencies.contains(&target))
+            .collect::<Vec<_>>();
+        if !users.is_empty() {
+            md.br();
+            md.h3("Also used by");
+            for member in users.iter().take(MAXIMUM_MEMBERS_SHOWN) {
+                let Ok(manifest_uri) = Url::from_file_path(member.dir.join("Cargo.toml")) else {
+                    continue;
+                };
+                md.a(&member.name, manifest_uri.to_string());
+            }
+            if users.len() > MAXIMUM_MEMBERS_SHOWN {
+                md.p(format!(
+                    "... and {} more",
+                    users.len() - MAXIMUM_MEMBERS_SHOWN
+                ));
+            }
+        }
+    }
+
+    Ok(Some(Hover {
+        range: Some(dep.range()),
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: md.build(),
+        }),
+    }))
+}
+
+pub async fn get_cargo_hover_workspace(
+    doc: &Document,
+    fields: &[TableField],
+    field: &TableField,
+) -> Result<Option<Hover>> {
+    let Some(manifest_dir) = manifest_dir(doc) else {
+        return Ok(None);
+    };
+
+    let pattern = field.value.unquoted();
+    let (title, members) = match field.name.unquoted() {
+        "members" => {
+            let members = expand_member_glob(&manifest_dir, pattern)
+                .await
+                .into_iter()
+                .filter(|path| !is_excluded(&manifest_dir, fields, &normalize_path(path)))
+                .collect::<Vec<_>>();
+            ("Matches", members)
+        }
+        "default-members" => {
+            let members = member_dirs(&manifest_dir, fields, is_package(doc.inner())).await;
+            let defaults = expand_member_glob(&manifest_dir, pattern)
+                .await
+                .into_iter()
+                .filter(|path| members.contains(&normalize_path(path)))
+                .collect::<Vec<_>>();
+            ("Builds by default", defaults)
+        }
+        "exclude" => {
+            let excluded = manifest_dir.join(pattern);
+            let mut members = Vec::new();
+            for field in fields.iter().filter(|f| f.name.unquoted() == "members") {
+                let matched = expand_member_glob(&manifest_dir, field.value.unquoted()).await;
+                members.extend(
+                    matched
+                        .into_iter()
+                        .filter(|path| path.starts_with(&excluded)),
+                );
+            }
+            ("Excludes", members)
+        }
+        _ => return Ok(None),
+    };
+
+    let mut md = MarkdownBuilder::new();
+    md.h2(format!("`{pattern}`"));
+    md.p(format!(
+        "{title} {} workspace {}",
+        members.len(),
+        if members.len() == 1 {
+            "crate"
+        } else {
+            "crates"
+        }
+    ));
+
+    if !members.is_empty() {
+        md.br();
+        for member in members.iter().take(MAXIMUM_MEMBERS_SHOWN) {
+            let manifest = member.join("Cargo.toml");
+            let Ok(manifest_uri) = Url::from_file_path(&manifest) else {
+                continue;
+            };
+            let display = member
+                .strip_prefix(&manifest_dir)
+                .unwrap_or(member)
+                .to_string_lossy()
+                .replace('\\', "/");
+            md.a(display, manifest_uri.to_string());
+        }
+        if members.len() > MAXIMUM_MEMBERS_SHOWN {
+            md.p(format!(
+                "... and {} more",
+                members.len() - MAXIMUM_MEMBERS_SHOWN
+            ));
+        }
+    }
+
+    Ok(Some(Hover {
+        range: Some(field.value.range),
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: md.build(),
+        }),
+    }))
+}
+
+pub async fn get_cargo_diagnostics_workspace(
+    doc: &Document,
+    fields: &[TableField],
+) -> Vec<Diagnostic> {
+    let Some(manifest_dir) = manifest_dir(doc) else {
+        return Vec::new();
+    };
+
+    let mut diagnostics = Vec::new();
+    for field in fields.iter().filter(|f| f.name.unquoted() == "members") {
+        if expand_member_glob(&manifest_dir, field.value.unquoted())
+            .await
+            .is_empty()
+        {
+            diagnostics.push(unmatched_member_diagnostic(
+                field,
+                format!(
+                    "Workspace member `{}` does not match any crates",
+                    field.value.unquoted()
+                ),
+            ));
+        }
+    }
+
+    // Default members must also be members, which Cargo refuses to build otherwise
+    let members = member_dirs(&manifest_dir, fields, is_package(doc.inner())).await;
+    for field in fields
+        .iter()
+        .filter(|field| field.name.unquoted() == "default-members")
+    {
+        let pattern = field.value.unquoted();
+        let defaults = expand_member_glob(&manifest_dir, pattern).await;
+        let message = if defaults.is_empty() {
+            format!("Default member `{pattern}` does not match any crates")
+        } else if defaults
+            .iter()
+            .any(|dir| !members.contains(&normalize_path(dir)))
+        {
+            format!("Default member `{pattern}` is not a member of the workspace")
+        } else {
+            continue;
+        };
+        diagnostics.push(unmatched_member_diagnostic(field, message));
+    }
+
+    diagnostics
+}
+
+fn unmatched_member_diagnostic(field: &TableField, message: String) -> Diagnostic {
+    Diagnostic {
+        source: Some(String::from("Cargo")),
+        range: field.value.range,
+        message,
+        severity: Some(DiagnosticSeverity::WARNING),
+        ..DiagnosticCode::UnmatchedMember.diagnostic()
+    }
+}
+
+/**
+    A field in `[package]` that is inherited from the workspace, along with
+    the version of the field in `[workspace.package]` that it resolves to.
+*/
+#[derive(Debug, Clone)]
+pub struct InheritedField {
+    pub field: TableField,
+    pub resolved: Option<InheritedValue>,
+}
+
+#[derive(Debug, Clone)]
+pub struct InheritedValue {
+    pub value: TableField,
+    pub workspace_manifest: PathBuf,
+}
+
+/**
+    Resolves inherited `[package]` fields by finding the workspace root
+    and the matching fields in its `[workspace.package]` table.
+
+    Returns an empty list if the manifest is not a file on disk, since
+    there is then no way to know which workspace it would belong to.
+*/
+pub async fn resolve_inherited_fields(
+    doc: &Document,
+    documents: &Documents,
+    workspaces: &WorkspaceCache,
+    fields: &[TableField],
+) -> Vec<InheritedField> {
+    if fields.is_empty() {
+        return Vec::new();
+    }
+    let Some(manifest_dir) = manifest_dir(doc) else {
+        return Vec::new();
+    };
+
+    let workspace_manifest = workspaces
+        .workspace_dir(documents, &manifest_dir)
+        .await
+        .map(|dir| dir.join(CARGO_MANIFEST));
+    let workspace_fields = match &workspace_manifest {
+        Some(path) => read_manifest(documents, path)
+            .await
+            .map(|manifest| query_cargo_toml_workspace_package_fields(&manifest))
+            .unwrap_or_default(),
+        None => Vec::new(),
+    };
+
+    fields
+        .iter()
+        .map(|field| InheritedField {
+            field: field.clone(),
+            resolved: workspace_manifest.as_ref().and_then(|path| {
+                let value = TableField::find_by_name(&workspace_fields, field.name.unquoted())?;
+                Some(InheritedValue {
+                    value: value.clone(),
+                    workspace_manifest: path.clone(),
+                })
+            }),
+        })
+        .collect()
+}
+
+/**
+    Adds the resolved values of inherited fields to the given `[package]`
+    fields, so that fields such as `edition` can be used the same way
+    no matter if they are inherited from the workspace or not.
+*/
+pub fn extend_with_inherited(fields: &mut Vec<TableField>, inherited: Vec<InheritedField>) {
+    fields.extend(inherited.into_iter().filter_map(|inherited| {
+        let resolved = inherited.resolved?;
+        Some(TableField {
+            name: inherited.field.name,
+            value: resolved.value.value,
+        })
+    }));
+}
+
+/**
+    The dependencies in `[workspace.dependencies]` of the workspace
+    that a manifest belongs to, which its dependencies may inherit.
+*/
+#[derive(Debug, Clone)]
+pub struct WorkspaceDependencies {
+    pub workspace_manifest: PathBuf,
+    pub dependencies: Vec<Dependency>,
+}
+
+impl WorkspaceDependencies {
+    /**
+        Finds the workspace dependency that the given dependency could inherit,
+        which must have the same name and package, and which is never the
+        dependency itself when the manifest is the workspace manifest.
+    */
+    pub fn find_for(&self, doc: &Document, dep: &Dependency) -> Option<&Dependency> {
+        let is_workspace_manifest = doc
+            .uri()
+            .to_file_path()
+            .is_ok_and(|path| path == self.workspace_manifest);
+        if is_workspace_manifest && self.dependencies.contains(dep) {
+            return None;
+        }
+        self.dependencies.iter().find(|inherited| {
+            inherited.name().unquoted() == dep.name().unquoted()
+                && inherited.package_name().unquoted() == dep.package_name().unquoted()
+        })
+    }
+}
+
+/**
+    Resolves the dependencies that the given manifest may inherit, by finding
+    the workspace root and the dependencies in its `[workspace.dependencies]`.
+
+    Returns `None` if the manifest is not a file on disk, or if the
+    workspace does not have any dependencies for members to inherit.
+*/
+pub async fn resolve_workspace_dependencies(
+    doc: &Document,
+    documents: &Documents,
+    workspaces: &WorkspaceCache,
+) -> Option<WorkspaceDependencies> {
+    let manifest_dir = manifest_dir(doc)?;
+    let workspace_manifest = workspaces
+        .workspace_dir(documents, &manifest_dir)
+        .await?
+        .join(CARGO_MANIFEST);
+    let manifest = read_manifest(documents, &workspace_manifest).await?;
+    let dependencies = query_cargo_toml_workspace_dependencies(&manifest);
+    if dependencies.is_empty() {
+        return None;
+    }
+    Some(WorkspaceDependencies {
+        workspace_manifest,
+        dependencies,
+    })
+}
+
+/**
+    Creates diagnostics for dependencies of workspace members that use a
+    different version than the one in `[workspace.dependencies]`, with
+    a quick fix to inherit it from the workspace instead.
+
+    Versions are compared as requirements, so `1.3` and `^1.3` are the same.
+    Dependencies that can not simply be switched over, such as ones in tables
+    under their own header, still get the diagnostic, just without a fix.
+*/
+pub fn get_cargo_diagnostics_workspace_versions(
+    doc: &Document,
+    workspace: &WorkspaceDependencies,
+    dependencies: &[Dependency],
+    severity: DiagnosticSeverity,
+) -> Vec<Diagnostic> {
+    dependencies
+        .iter()
+        .filter_map(|dep| {
+            let inherited = workspace.find_for(doc, dep)?;
+            let version = dep.spec()?.contents.version.as_ref()?;
+            let inherited_version = inherited.spec()?.contents.version.as_ref()?;
+            if normalize_version_req(version.unquoted())
+                == normalize_version_req(inherited_version.unquoted())
+            {
+                return None;
+            }
+            let metadata = get_cargo_workspace_version_metadata(doc, dep, inherited);
+            Some(Diagnostic {
+                source: Some(String::from("Cargo")),
+                range: version.range,
+                message: format!(
+                    "`{}` uses version `{}`, but the workspace declares `{}`",
+                    dep.name().unquoted(),
+                    version.unquoted(),
+                    inherited_version.unquoted(),
+                ),
+                severity: Some(severity),
+                data: metadata.map(|value| {
+                    ResolveContext {
+                        uri: doc.uri().clone(),
+                        value,
+                    }
+                    .into()
+                }),
+                ..DiagnosticCode::WorkspaceVersion.diagnostic()
+            })
+        })
+        .collect()
+}
+
+fn normalize_version_req(version: &str) -> String {
+    match VersionReq::parse(version) {
+        Ok(req) => req.to_string(),
+        Err(_) => version.trim().to_string(),
+    }
+}
+
+pub fn get_cargo_hover_inherited(inherited: &InheritedField) -> Result<Option<Hover>> {
+    let name = inherited.field.name.unquoted();
+
+    let mut md = MarkdownBuilder::new();
+    md.h2(format!("`{name}`"));
+    match &inherited.resolved {
+        Some(resolved) => {
+            md.p(format!("Inherited: `{}`", resolved.value.value.unquoted()));
+            if let Ok(uri) = Url::from_file_path(&resolved.workspace_manifest) {
+                md.br();
+                md.a("Workspace manifest", uri.to_string());
+            }
+        }
+        None => md.p("Inherited from the workspace, but not defined there"),
+    }
+
+    Ok(Some(Hover {
+        range: Some(inherited.field.range()),
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: md.build(),
+        }),
+    }))
+}
+
+pub fn get_cargo_diagnostics_inherited(inherited: &[InheritedField]) -> Vec<Diagnostic> {
+    inherited
+        .iter()
+        .filter(|inherited| inherited.resolved.is_none())
+        .map(|inherited| {
+            let name = inherited.field.name.unquoted();
+            Diagnostic {
+                source: Some(String::from("Cargo")),
+                range: inherited.field.range(),
+                message: format!(
+                    "`{name}` is inherited from the workspace, \
+                    but `workspace.package.{name}` is not defined"
+                ),
+                severity: Some(DiagnosticSeverity::ERROR),
+                ..DiagnosticCode::MissingInherited.diagnostic()
+            }
+        })
+        .collect()
+}
+
+/**
+    Creates diagnostics for path dependencies that point to a directory
+    without a `Cargo.toml`, which usually happens after moving folders.
+
+    Paths are normalized lexically like Cargo does it, meaning that `..`
+    after a symlink goes back out of the symlink, and not to the parent
+    of the directory that the symlink points to.
+*/
+pub async fn get_cargo_diagnostics_paths(
+    doc: &Document,
+    dependencies: &[Dependency],
+) -> Vec<Diagnostic> {
+    // Documents that only exist in memory have nothing to resolve paths against
+    let Some(manifest_dir) = manifest_dir(doc) else {
+        return Vec::new();
+    };
+
+    let paths =
+        dependencies
+            .iter()
+            .filter_map(|dep| match dep.spec().map(|s| &s.contents.source) {
+                Some(DependencySource::Path { path }) => Some(path),
+                _ => None,
+            });
+
+    let mut diagnostics = Vec::new();
+    for path in paths {
+        let manifest = resolve_manifest_path(&manifest_dir, path.unquoted(), CARGO_MANIFEST);
+        if is_file(&manifest).await {
+            continue;
+        }
+        // A path straight to a manifest is a common mistake, since Cargo only takes
+        // directories, so that gets its own message instead of a confusing path
+        let target = manifest.parent().unwrap_or(&manifest);
+        let message = if is_file(target).await {
+            format!(
+                "`path` must point at the directory of a crate, not at `{}`",
+                target.display()
+            )
+        } else {
+            format!("No `{CARGO_MANIFEST}` was found at `{}`", target.display())
+        };
+        diagnostics.push(Diagnostic {
+            source: Some(String::from("Cargo")),
+            range: path.range,
+            message,
+            severity: Some(DiagnosticSeverity::ERROR),
+            ..DiagnosticCode::MissingPath.diagnostic()
+        });
+    }
+    diagnostics
+}
+
+/**
+    Gets the `Location` of the manifest of a path dependency, for going
+    to its definition, if the manifest exists in the directory it points at.
+*/
+pub async fn get_cargo_definition_path(doc: &Document, path: &Node<String>) -> Option<Location> {
+    let manifest = resolve_manifest_path(&manifest_dir(doc)?, path.unquoted(), CARGO_MANIFEST);
+    if !is_file(&manifest).await {
+        return None;
+    }
+    Some(Location {
+        uri: Url::from_file_path(manifest).ok()?,
+        range: Range::default(),
+    })
+}
+
+/**
+    Gets the directory of the crate that a path dependency points at.
+*/
+fn crate_dir(manifest_dir: &Path, path: &str) -> PathBuf {
+    let manifest = resolve_manifest_path(manifest_dir, path, CARGO_MANIFEST);
+    manifest.parent().map(Path::to_path_buf).unwrap_or(manifest)
+}
+
+fn manifest_dir(doc: &Document) -> Option<PathBuf> {
+    let path = doc.uri().to_file_path().ok()?;
+    path.parent().map(Path::to_path_buf)
+}
+
+/**
+    Finds the directory of the closest workspace manifest that
+    is either in, or above, the directory of the given manifest.
+
+    Crates that the closest workspace excludes do not belong to any
+    workspace, same as in Cargo, which does not look any further up.
+*/
+async fn find_workspace_dir(documents: &Documents, manifest_dir: &Path) -> Option<PathBuf> {
+    for dir in manifest_dir.ancestors() {
+        let Some(manifest) = read_manifest(documents, &dir.join(CARGO_MANIFEST)).await else {
+            continue;
+        };
+        if !is_workspace_root(&manifest) {
+            continue;
+        }
+        let fields = query_cargo_toml_workspace_fields(&manifest);
+        if dir != manifest_dir && is_excluded(dir, &fields, &normalize_path(manifest_dir)) {
+            return None;
+        }
+        return Some(dir.to_path_buf());
+    }
+    None
+}
+
+/**
+    Checks if a manifest is the root of a workspace. The `[workspace]`
+    table may be empty, so this looks at table headers instead of fields.
+*/
+fn is_workspace_root(manifest: &TreeSitterDocument) -> bool {
+    query_cargo_toml_table_headers(manifest)
+        .iter()
+        .any(|header| header == "workspace" || header.starts_with("workspace."))
+}
+
+/**
+    Checks if a manifest has a `[package]`, which workspace manifests
+    do not need to have, and are then called virtual manifests.
+*/
+fn is_package(manifest: &TreeSitterDocument) -> bool {
+    query_cargo_toml_table_headers(manifest)
+        .iter()
+        .any(|header| header == "package" || header.starts_with("package."))
+}
+
+/**
+    Reads a manifest, preferring the contents of an open document,
+    which may have changes that have not yet been saved to disk.
+*/
+async fn read_manifest(documents: &Documents, path: &Path) -> Option<TreeSitterDocument> {
+    let uri = Url::from_file_path(path).ok()?;
+    let opened = documents.get(&uri).map(|doc| doc.inner().clone());
+    if opened.is_some() {
+        return opened;
+    }
+    let contents = fs::read_to_string(path).await.ok()?;
+    TreeSitterDocument::new_file(path, contents)
+}
+
+async fn is_file(path: &Path) -> bool {
+    fs::metadata(path).await.is_ok_and(|m| m.is_file())
+}
+
+async fn discover_members(documents: &Documents, workspace_dir: &Path) -> Vec<WorkspaceMember> {
+    let Some(workspace) = read_manifest(documents, &workspace_dir.join(CARGO_MANIFEST)).await
+    else {
+        return Vec::new();
+    };
+
+    let fields = query_cargo_toml_workspace_fields(&workspace);
+    let mut members = Vec::new();
+    for dir in member_dirs(workspace_dir, &fields, is_package(&workspace)).await {
+        let Some(manifest) = read_manifest(documents, &dir.join(CARGO_MANIFEST)).await else {
+            continue;
+        };
+        let Some(name) = query_cargo_toml_package_fields(&manifest)
+            .into_iter()
+            .find(|f| f.name.unquoted() == "name")
+        else {
+            continue;
+        };
+        let path_dependencies = query_cargo_toml_dependencies(&manifest)
+            .iter()
+            .filter_map(|dep| match dep.spec().map(|s| &s.contents.source) {
+                Some(DependencySource::Path { path }) => Some(crate_dir(&dir, path.unquoted())),
+                _ => None,
+            })
+            .collect();
+        members.push(WorkspaceMember {
+            name: name.value.unquoted().to_string(),
+            dir,
+            path_dependencies,
+        });
+    }
+    members
+}
+
+/**
+    Gets the directories of all members of a workspace, sorted by path,
+    including the workspace itself if its manifest is also a package.
+*/
+async fn member_dirs(
+    workspace_dir: &Path,
+    fields: &[TableField],
+    is_package: bool,
+) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if is_package {
+        dirs.push(normalize_path(workspace_dir));
+    }
+    for field in fields.iter().filter(|f| f.name.unquoted() == "members") {
+        for path in expand_member_glob(workspace_dir, field.value.unquoted()).await {
+            let path = normalize_path(&path);
+            if !is_excluded(workspace_dir, fields, &path) {
+                dirs.push(path);
+            }
+        }
+    }
+    dirs.sort();
+    dirs.dedup();
+    dirs
+}
+
+/**
+    Checks if a directory is excluded from a workspace, meaning that it is inside
+    of any `exclude` path, and not listed explicitly in `members`, like in Cargo.
+*/
+fn is_excluded(workspace_dir: &Path, fields: &[TableField], dir: &Path) -> bool {
+    let paths = |name: &'static str| {
+        fields
+            .iter()
+            .filter(move |f| f.name.unquoted() == name)
+            .map(|f| normalize_path(&workspace_dir.join(f.value.unquoted())))
+    };
+    let explicit = paths("members").any(|member| member == dir);
+    !explicit && paths("exclude").any(|excluded| dir.starts_with(excluded))
+}
+
+/**
+    Expands a workspace member glob, such as `crates/<name>` using wildcards,
+    relative to the directory of the workspace manifest, returning all matching
+    directories that contain a `Cargo.toml` file, sorted by path.
+
+    Only the `*` and `?` wildcards are supported, which
+    covers the vast majority of workspace member globs.
+*/
+async fn expand_member_glob(manifest_dir: &Path, pattern: &str) -> Vec<PathBuf> {
+    let mut current = vec![manifest_dir.to_path_buf()];
+
+    for component in Path::new(pattern).components() {
+        let segment = match component {
+            Component::Normal(segment) => segment.to_string_lossy(),
+            Component::ParentDir => {
+                current = current.into_iter().map(|dir| dir.join("..")).collect();
+                continue;
+            }
+            _ => continue,
+        };
+
+        if !segment.contains(['*', '?']) {
+            current = current.into_iter().map(|dir| dir.join(&*segment)).collect();
+            continue;
+        }
+
+        let mut next = Vec::new();
+        for dir in current {
+            let Ok(mut entries) = fs::read_dir(&dir).await else {
+                continue;
+            };
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                let name = entry.file_name();
+                let is_dir = entry.file_type().await.is_ok_and(|t| t.is_dir());
+                if is_dir && wildcard_matches(&segment, &name.to_string_lossy()) {
+                    next.push(entry.path());
+                }
+            }
+        }
+        current = next;
+    }
+
+    let mut members = Vec::new();
+    for dir in current {
+        if is_file(&dir.join(CARGO_MANIFEST)).await {
+            members.push(dir);
+        }
+    }
+    members.sort();
+    members
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::parser::query_cargo_toml_inherited_package_fields;
+    use crate::server::testing::{cursor_fixture, edit_of, MockFetcher, TestServer, SERDE_INDEX};
+    use crate::server::DocumentBuilder;
+    use crate::util::FixtureDir;
+
+    use super::*;
+
+    fn member_doc(path: &Path, text: &str) -> Document {
+        DocumentBuilder::new()
+            .with_uri(Url::from_file_path(path).unwrap())
+            .with_text(text)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_discover_members() {
+        let root = FixtureDir::new("workspace-discover");
+        root.write(
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"crates/*\"]\nexclude = [\"crates/skipped\"]\n",
+        );
+        root.write(
+            "crates/app/Cargo.toml",
+            "[package]\nname = \"app\"\n\n[dependencies]\ncore = { path = \"../core\" }\n",
+        );
+        root.write("crates/core/Cargo.toml", "[package]\nname = \"core\"\n");
+        root.write(
+            "crates/skipped/Cargo.toml",
+            "[package]\nname = \"skipped\"\n",
+        );
+        root.write("crates/empty/README.md", "");
+
+        let members = discover_members(&Documents::default(), &root).await;
+        let names = members.iter().map(|m| m.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["app", "core"]);
+        assert_eq!(members[0].dir, root.join("crates").join("app"));
+        assert_eq!(
+            members[0].path_dependencies,
+            [root.join("crates").join("core")]
+        );
+        assert!(members[1].path_dependencies.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_expand_member_glob() {
+        let root = FixtureDir::new("workspace-globs");
+        for member in [
+            "crates/a-1",
+            "crates/a-2",
+            "crates/b-1",
+            "tools/x/cli",
+            "tools/y/cli",
+        ] {
+            root.write(format!("{member}/Cargo.toml"), "[package]\n");
+        }
+        root.write("crates/a-10/Cargo.toml", "[package]\n");
+        root.write("crates/a-3/README.md", "");
+        root.write("crates/a-4", "not a directory");
+        root.write("sibling/Cargo.toml", "[package]\n");
+        let dir = root.join("workspace");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let expand = |pattern: &'static str| {
+            let root = root.to_path_buf();
+            async move {
+                expand_member_glob(&root, pattern)
+                    .await
+                    .into_iter()
+                    .map(|path| path.strip_prefix(&root).unwrap().to_path_buf())
+                    .collect::<Vec<_>>()
+            }
+        };
+        let paths = |paths: &[&str]| paths.iter().map(PathBuf::from).collect::<Vec<_>>();
+
+        // Directories without a manifest, and files, are never members
+        assert_eq!(
+            expand("crates/*").await,
+            paths(&["crates/a-1", "crates/a-10", "crates/a-2", "crates/b-1"])
+        );
+        assert_eq!(
+            expand("crates/a-?").await,
+            paths(&["crates/a-1", "crates/a-2"])
+        );
+        assert_eq!(
+            expand("crates/?-1").await,
+            paths(&["crates/a-1", "crates/b-1"])
+        );
+        assert_eq!(expand("crates/b-1").await, paths(&["crates/b-1"]));
+        assert!(expand("crates/c-*").await.is_empty());
+        assert!(expand("missing/*").await.is_empty());
+
+        // Wildcards may be in any segment, not only the last one
+        assert_eq!(
+            expand("tools/*/cli").await,
+            paths(&["tools/x/cli", "tools/y/cli"])
+        );
+
+        // Members may also be outside of the workspace directory
+        let outside = expand_member_glob(&dir, "../sib*").await;
+        assert_eq!(outside, [dir.join("..").join("sibling")]);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_inherited_fields() {
+        let root = FixtureDir::new("workspace-inherited");
+        root.write(
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"member\"]\n\n[workspace.package]\nversion = \"1.2.3\"\n",
+        );
+        let path = root.write(
+            "member/Cargo.toml",
+            "[package]\nname = \"member\"\nversion.workspace = true\nedition.workspace = true\n",
+        );
+
+        let doc = member_doc(&path, &std::fs::read_to_string(&path).unwrap());
+        let fields = query_cargo_toml_inherited_package_fields(doc.inner());
+        let documents = Documents::default();
+        let inherited =
+            resolve_inherited_fields(&doc, &documents, &WorkspaceCache::default(), &fields).await;
+
+        assert_eq!(inherited.len(), 2);
+        let version = inherited[0].resolved.as_ref().unwrap();
+        assert_eq!(version.value.value.unquoted(), "1.2.3");
+        assert_eq!(version.workspace_manifest, root.join(CARGO_MANIFEST));
+        assert!(inherited[1].resolved.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_workspace_dir_cache() {
+        let root = FixtureDir::new("workspace-roots");
+        let member_dir = root.join("member");
+        root.write("member/Cargo.toml", "[package]\nname = \"member\"\n");
+
+        let documents = Documents::default();
+        let workspaces = WorkspaceCache::default();
+        assert_eq!(
+            workspaces.workspace_dir(&documents, &member_dir).await,
+            None
+        );
+
+        // The missing workspace is remembered until a manifest changes
+        let workspace = root.write("Cargo.toml", "[workspace]\nmembers = [\"member\"]\n");
+        assert_eq!(
+            workspaces.workspace_dir(&documents, &member_dir).await,
+            None
+        );
+
+        let other = root.write("other/Cargo.toml", "[package]\nname = \"other\"\n");
+        workspaces.invalidate(&Url::from_file_path(&other).unwrap());
+        assert_eq!(
+            workspaces.workspace_dir(&documents, &member_dir).await,
+            None
+        );
+
+        workspaces.invalidate(&Url::from_file_path(&workspace).unwrap());
+        assert_eq!(
+            workspaces.workspace_dir(&documents, &member_dir).await,
+            Some(root.to_path_buf())
+        );
+    }
+
+    const WORKSPACE_MANIFEST: &str = "[workspace]\nmembers = [\"app\"]\n\n\
+        [workspace.dependencies]\nserde = { version = \"1.0\", features = [\"derive\"] }\n";
+
+    #[tokio::test]
+    async fn test_cargo_code_action_use_workspace_version() {
+        let server = TestServer::new(MockFetcher::new()).await;
+        let base = FixtureDir::new("workspace-version-action");
+        let root = Url::from_file_path(base.join("Cargo.toml")).unwrap();
+        let root = server.open_uri(root, WORKSPACE_MANIFEST).await;
+
+        let (contents, pos) = cursor_fixture(
+            "[dependencies]\n\
+            serde = { version = \"1.0$0\", features = [\"derive\", \"rc\"], optional = true }\n",
+        );
+        let member = Url::from_file_path(base.join("app").join("Cargo.toml")).unwrap();
+        let member = server.open_uri(member, &contents).await;
+        let actions = server.code_actions(&member, pos).await;
+
+        // Features already enabled by the workspace are not repeated
+        let action = actions
+            .into_iter()
+            .find(|a| a.title == "Use workspace version")
+            .expect("missing workspace action");
+        let changes = action.edit.and_then(|e| e.changes).expect("missing edit");
+        assert_eq!(
+            changes[&member][0].new_text,
+            "{ workspace = true, features = [\"rc\"], optional = true }"
+        );
+
+        // The workspace dependency itself has nothing to inherit from
+        let actions = server.code_actions(&root, Position::new(4, 20)).await;
+        assert!(actions.iter().all(|a| a.title != "Use workspace version"));
+    }
+
+    #[tokio::test]
+    async fn test_cargo_completion_use_workspace_version() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::new(fetcher).await;
+        let base = FixtureDir::new("workspace-version-completion");
+        let root = Url::from_file_path(base.join("Cargo.toml")).unwrap();
+        server.open_uri(root, WORKSPACE_MANIFEST).await;
+
+        let (contents, pos) = cursor_fixture("[dependencies]\nserde = \"1.0$0\"\n");
+        let member = Url::from_file_path(base.join("app").join("Cargo.toml")).unwrap();
+        let member = server.open_uri(member, &contents).await;
+        let items = server.completion(&member, pos).await;
+
+        let item = items.last().expect("missing completions");
+        assert_eq!(item.label, "workspace = true");
+        assert_eq!(edit_of(item).new_text, "{ workspace = true }");
+        assert_eq!(edit_of(item).range.start, Position::new(1, 8));
+    }
+
+    #[tokio::test]
+    async fn test_cargo_diagnostics_workspace_version() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::new(fetcher).await;
+        let base = FixtureDir::new("workspace-version-diagnostics");
+        let root = Url::from_file_path(base.join("Cargo.toml")).unwrap();
+        server.open_uri(root, WORKSPACE_MANIFEST).await;
+
+        let member = Url::from_file_path(base.join("app").join("Cargo.toml")).unwrap();
+        let member = server
+            .open_uri(member, "[dependencies]\nserde = \"1.0.100\"\n")
+            .await;
+        let diagnostics = server.diagnostics(&member).await;
+
+        assert_eq!(diagnostics.len(), 1, "diagnostics: {diagnostics:#?}");
+        assert_eq!(
+            diagnostics[0].message,
+            "`serde` uses version `1.0.100`, but the workspace declares `1.0`"
+        );
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::HINT));
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String(String::from("workspace-version")))
+        );
+
+        // The quick fix is attached to the diagnostic itself
+        let data = diagnostics[0].data.as_ref().expect("missing quick fix");
+        assert_eq!(data["value"]["kind"], json!("UseWorkspaceVersion"));
+
+        // Tables under their own header have no fix, but are still different
+        let other = Url::from_file_path(base.join("lib").join("Cargo.toml")).unwrap();
+        let other = server
+            .open_uri(other, "[dependencies.serde]\nversion = \"1.0.100\"\n")
+            .await;
+        let diagnostics = server.diagnostics(&other).await;
+        assert_eq!(diagnostics.len(), 1, "diagnostics: {diagnostics:#?}");
+        assert_eq!(diagnostics[0].data, None);
+    }
+
+    #[tokio::test]
+    async fn test_cargo_virtual_workspace() {
+        let base = FixtureDir::new("virtual-workspace");
+        for name in ["app", "cli", "old"] {
+            let manifest = format!("[package]\nname = \"{name}\"\nversion.workspace = true\n");
+            base.write(format!("crates/{name}/Cargo.toml"), manifest);
+        }
+        let server = TestServer::new(MockFetcher::new()).await;
+
+        // A virtual manifest, which has no [package] of its own
+        let contents = "[workspace]\n\
+            members = [\"crates/*\"]\n\
+            exclude = [\"crates/old\"]\n\
+            default-members = [\"crates/app\", \"crates/old\", \"crates/gone\"]\n\
+            \n\
+            [workspace.package]\n\
+            version = \"0.2.0\"\n";
+        let root = Url::from_file_path(base.join("Cargo.toml")).unwrap();
+        let root = server.open_uri(root, contents).await;
+        let diagnostics = server.diagnostics(&root).await;
+        let hover = server.hover(&root, Position::new(3, 22)).await;
+
+        let app = Url::from_file_path(base.join("crates/app/Cargo.toml")).unwrap();
+        let app = server
+            .open_uri(app, "[package]\nversion.workspace = true\n")
+            .await;
+        let app_diagnostics = server.diagnostics(&app).await;
+        let old = Url::from_file_path(base.join("crates/old/Cargo.toml")).unwrap();
+        let old = server
+            .open_uri(old, "[package]\nversion.workspace = true\n")
+            .await;
+        let old_diagnostics = server.diagnostics(&old).await;
+
+        // Excluded crates can not be default members, and missing ones match nothing
+        let messages = diagnostics
+            .iter()
+            .map(|d| d.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "Default member `crates/old` is not a member of the workspace",
+                "Default member `crates/gone` does not match any crates",
+            ]
+        );
+        let hover = hover.expect("missing hover");
+        assert!(
+            hover.contains("Builds by default 1 workspace crate"),
+            "{hover}"
+        );
+
+        // Members inherit from the workspace, while excluded crates do not belong to it
+        assert!(app_diagnostics.is_empty(), "{app_diagnostics:#?}");
+        assert_eq!(old_diagnostics.len(), 1, "{old_diagnostics:#?}");
+        assert_eq!(
+            old_diagnostics[0].code,
+            Some(NumberOrString::String(String::from("missing-inherited")))
+        );
+    }
+}
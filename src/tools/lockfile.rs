@@ -0,0 +1,361 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use dashmap::DashMap;
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use tokio::fs;
+use tower_lsp::lsp_types::Url;
+use tracing::{debug, trace};
+
+use crate::util::LspUriExt;
+
+use super::ToolName;
+
+/**
+    A package that has been resolved to an exact version in a lockfile.
+
+    Dependencies are given using their package name, and their exact
+    version, if the lockfile disambiguates between several versions.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+    pub dependencies: Vec<(String, Option<String>)>,
+}
+
+/**
+    All of the packages in a lockfile, for any of the supported tools.
+*/
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Lockfile {
+    pub packages: Vec<LockedPackage>,
+}
+
+impl Lockfile {
+    /**
+        Parses the contents of a lockfile for the given tool.
+
+        Tools that do not use lockfiles always give an empty lockfile.
+    */
+    pub fn parse(tool: ToolName, contents: &str) -> Result<Self> {
+        match tool {
+            ToolName::Aftman | ToolName::Rokit => Ok(Self::default()),
+            ToolName::Cargo => parse_cargo_lock(contents),
+            ToolName::Npm => parse_package_lock(contents),
+            ToolName::Wally => parse_wally_lock(contents),
+        }
+    }
+
+    /**
+        Finds the locked package with the given name, preferring the
+        greatest version that matches the given version, if any.
+    */
+    pub fn find(&self, name: &str, version: Option<&str>) -> Option<&LockedPackage> {
+        let mut candidates = self
+            .packages
+            .iter()
+            .filter(|package| package.name == name)
+            .collect::<Vec<_>>();
+        let Some(version) = version.map(str::trim).filter(|v| !v.is_empty()) else {
+            return candidates.into_iter().next();
+        };
+
+        if let Some(exact) = candidates.iter().find(|package| package.version == version) {
+            return Some(*exact);
+        }
+
+        // Prefer the greatest version matching the requirement, and fall
+        // back to any version at all for requirements we can't parse
+        let parse = |package: &LockedPackage| package.version.parse::<Version>().ok();
+        candidates.sort_by_key(|package| std::cmp::Reverse(parse(package)));
+        match version.parse::<VersionReq>() {
+            Ok(req) => candidates
+                .iter()
+                .find(|package| parse(package).is_some_and(|v| req.matches(&v)))
+                .or(candidates.first())
+                .copied(),
+            Err(_) => candidates.first().copied(),
+        }
+    }
+}
+
+/**
+    Parsed lockfiles, cached by the uri of each lockfile.
+
+    Hovers and inline values both show locked versions, and are requested
+    all the time while moving around in a manifest, so lockfiles are only
+    read and parsed again after they have been changed on disk.
+*/
+#[derive(Debug, Default, Clone)]
+pub struct LockfileCache {
+    inner: Arc<DashMap<Url, Arc<Lockfile>>>,
+}
+
+impl LockfileCache {
+    /**
+        Gets the lockfile of the manifest at the given uri, if the tool uses
+        lockfiles and one exists, such as a `Cargo.lock` in any of the
+        directories above a `Cargo.toml`, reading it if it is not cached.
+
+        Lockfiles that are missing or fail to parse are not cached, so
+        that they are picked up as soon as they have been written.
+    */
+    pub async fn for_manifest(&self, tool: ToolName, manifest_uri: &Url) -> Option<Arc<Lockfile>> {
+        let lockfile_name = tool.lockfile_name()?;
+        let candidates = tool
+            .relevant_file_uris(manifest_uri)
+            .await
+            .into_iter()
+            .filter(|uri| uri.file_name().as_deref() == Some(lockfile_name));
+
+        // The closest lockfile that exists is the one that is used
+        for uri in candidates {
+            if let Some(lockfile) = self.inner.get(&uri) {
+                return Some(Arc::clone(&lockfile));
+            }
+            let Ok(path) = uri.to_file_path() else {
+                continue;
+            };
+            let Ok(contents) = fs::read_to_string(&path).await else {
+                continue;
+            };
+
+            trace!("Parsing lockfile at '{uri}'");
+            return match Lockfile::parse(tool, &contents) {
+                Ok(lockfile) => {
+                    let lockfile = Arc::new(lockfile);
+                    self.inner.insert(uri, Arc::clone(&lockfile));
+                    Some(lockfile)
+                }
+                Err(e) => {
+                    debug!("Failed to read lockfile at '{uri}' - {e}");
+                    None
+                }
+            };
+        }
+        None
+    }
+
+    pub fn invalidate(&self, uri: &Url) {
+        self.inner.remove(uri);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TomlLockfile<D> {
+    #[serde(default = "Vec::new")]
+    package: Vec<TomlLockedPackage<D>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TomlLockedPackage<D> {
+    name: String,
+    version: String,
+    #[serde(default = "Vec::new")]
+    dependencies: Vec<D>,
+}
+
+/**
+    Parses a `Cargo.lock` file, where dependencies are either just a name,
+    or a name followed by a version, and possibly a source in parentheses.
+*/
+fn parse_cargo_lock(contents: &str) -> Result<Lockfile> {
+    let lockfile: TomlLockfile<String> =
+        toml::from_str(contents).context("Failed to parse Cargo.lock")?;
+    let packages = lockfile
+        .package
+        .into_iter()
+        .map(|package| LockedPackage {
+            name: package.name,
+            version: package.version,
+            dependencies: package
+                .dependencies
+                .iter()
+                .map(|dep| {
+                    let mut parts = dep.split_whitespace();
+                    let name = parts.next().unwrap_or_default().to_string();
+                    (name, parts.next().map(ToString::to_string))
+                })
+                .collect(),
+        })
+        .collect();
+    Ok(Lockfile { packages })
+}
+
+/**
+    Parses a `wally.lock` file, where dependencies are pairs
+    of an alias and a full `scope/name@version` package spec.
+*/
+fn parse_wally_lock(contents: &str) -> Result<Lockfile> {
+    let lockfile: TomlLockfile<(String, String)> =
+        toml::from_str(contents).context("Failed to parse wally.lock")?;
+    let packages = lockfile
+        .package
+        .into_iter()
+        .map(|package| LockedPackage {
+            name: package.name,
+            version: package.version,
+            dependencies: package
+                .dependencies
+                .iter()
+                .map(|(_, spec)| match spec.split_once('@') {
+                    Some((name, version)) => (name.to_string(), Some(version.to_string())),
+                    None => (spec.to_string(), None),
+                })
+                .collect(),
+        })
+        .collect();
+    Ok(Lockfile { packages })
+}
+
+/**
+    Parses a `package-lock.json` file, using the `packages` map from lockfile
+    version 2 and later, where packages are keyed by their install path.
+
+    Dependencies are resolved the same way as in node, by looking for the
+    nearest `node_modules` directory that contains them, starting from the
+    directory of the dependent package and moving up towards the root.
+*/
+fn parse_package_lock(contents: &str) -> Result<Lockfile> {
+    let lockfile: JsonValue =
+        serde_json::from_str(contents).context("Failed to parse package-lock.json")?;
+    let Some(entries) = lockfile.get("packages").and_then(JsonValue::as_object) else {
+        bail!("Unsupported package-lock.json, only lockfile version 2 and later is supported");
+    };
+
+    let versions = entries
+        .iter()
+        .filter_map(|(path, entry)| {
+            let version = entry.get("version")?.as_str()?;
+            Some((path.as_str(), version))
+        })
+        .collect::<BTreeMap<_, _>>();
+    let resolve = |from: &str, name: &str| {
+        let mut dir = from;
+        loop {
+            let candidate = if dir.is_empty() {
+                format!("node_modules/{name}")
+            } else {
+                format!("{dir}/node_modules/{name}")
+            };
+            if let Some(version) = versions.get(candidate.as_str()) {
+                return Some(version.to_string());
+            }
+            if dir.is_empty() {
+                return None;
+            }
+            dir = dir
+                .rsplit_once("/node_modules/")
+                .map_or("", |(parent, _)| parent);
+        }
+    };
+
+    let mut packages = Vec::new();
+    for (path, entry) in entries {
+        // The root package has an empty path, and is the manifest itself
+        let Some((_, name)) = path.rsplit_once("node_modules/") else {
+            continue;
+        };
+        let Some(version) = versions.get(path.as_str()) else {
+            continue;
+        };
+        let dependencies = ["dependencies", "optionalDependencies", "peerDependencies"]
+            .into_iter()
+            .filter_map(|field| entry.get(field).and_then(JsonValue::as_object))
+            .flat_map(|deps| deps.keys())
+            .filter_map(|dep| resolve(path, dep).map(|version| (dep.clone(), Some(version))))
+            .collect();
+        packages.push(LockedPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            dependencies,
+        });
+    }
+    Ok(Lockfile { packages })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cargo_lock() {
+        let contents = r#"
+        version = 3
+
+        [[package]]
+        name = "app"
+        version = "0.1.0"
+        dependencies = ["serde", "syn 2.0.0"]
+
+        [[package]]
+        name = "syn"
+        version = "1.0.0"
+
+        [[package]]
+        name = "syn"
+        version = "2.0.0"
+        source = "registry+https://github.com/rust-lang/crates.io-index"
+        "#;
+
+        let lockfile = Lockfile::parse(ToolName::Cargo, contents).unwrap();
+        let app = lockfile.find("app", None).unwrap();
+        assert_eq!(
+            app.dependencies,
+            vec![
+                (String::from("serde"), None),
+                (String::from("syn"), Some(String::from("2.0.0"))),
+            ]
+        );
+        assert_eq!(lockfile.find("syn", Some("1")).unwrap().version, "1.0.0");
+        assert_eq!(lockfile.find("syn", None).unwrap().version, "1.0.0");
+    }
+
+    #[test]
+    fn test_wally_lock() {
+        let contents = r#"
+        registry = "test"
+
+        [[package]]
+        name = "roblox/roact"
+        version = "1.4.4"
+        dependencies = [["Promise", "evaera/promise@4.0.0"]]
+        "#;
+
+        let lockfile = Lockfile::parse(ToolName::Wally, contents).unwrap();
+        let roact = lockfile.find("roblox/roact", Some("1.4.0")).unwrap();
+        assert_eq!(
+            roact.dependencies,
+            vec![(String::from("evaera/promise"), Some(String::from("4.0.0")))]
+        );
+    }
+
+    #[test]
+    fn test_package_lock_nested() {
+        let contents = r#"{
+            "lockfileVersion": 3,
+            "packages": {
+                "": { "dependencies": { "a": "^1.0.0" } },
+                "node_modules/a": { "version": "1.0.0", "dependencies": { "b": "^2.0.0", "c": "*" } },
+                "node_modules/a/node_modules/b": { "version": "2.0.0" },
+                "node_modules/b": { "version": "1.0.0" },
+                "node_modules/c": { "version": "3.0.0" }
+            }
+        }"#;
+
+        let lockfile = Lockfile::parse(ToolName::Npm, contents).unwrap();
+        let a = lockfile.find("a", Some("^1.0.0")).unwrap();
+        assert_eq!(
+            a.dependencies,
+            vec![
+                (String::from("b"), Some(String::from("2.0.0"))),
+                (String::from("c"), Some(String::from("3.0.0"))),
+            ]
+        );
+        assert_eq!(lockfile.find("b", Some("^1")).unwrap().version, "1.0.0");
+    }
+}
@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{Range, TextDocumentIdentifier};
+
+use crate::parser::Target;
+
+/**
+    Parameters for the custom `tooling/manifestInfo` request.
+*/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestInfoParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+/**
+    Information about a manifest, returned from the custom `tooling/manifestInfo` request.
+
+    Contains things that are not directly related to dependencies,
+    but that may still be useful for clients to know about.
+*/
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestInfo {
+    pub targets: Vec<ManifestTarget>,
+}
+
+/**
+    A build target in a manifest, such as a `[[bin]]` in a `Cargo.toml`.
+*/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestTarget {
+    pub kind: String,
+    pub name: Option<String>,
+    pub path: Option<String>,
+    pub range: Range,
+}
+
+impl From<&Target> for ManifestTarget {
+    fn from(target: &Target) -> Self {
+        Self {
+            kind: target.kind.contents.as_str().to_string(),
+            name: target.name.as_ref().map(|n| n.unquoted().to_string()),
+            path: target.path.as_ref().map(|p| p.unquoted().to_string()),
+            range: target.range,
+        }
+    }
+}
@@ -1,18 +1,28 @@
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::Client;
-use tracing::warn;
+use tracing::{debug, warn};
 
 use crate::clients::*;
 use crate::server::*;
 
 // Tools modules
 
+mod analyze;
+mod lockfile;
+mod manifest_info;
 mod name;
+mod positions;
+mod refresh;
 mod shared;
 mod tool;
 
-use name::*;
+pub use analyze::*;
+pub use lockfile::*;
+pub use manifest_info::*;
+pub use name::*;
+use positions::*;
+pub use refresh::*;
 use shared::*;
 pub use tool::*;
 
@@ -32,6 +42,10 @@ use wally::*;
 
 #[derive(Debug, Clone)]
 pub struct Tools {
+    enabled: Vec<ToolName>,
+    config: SharedConfig,
+    documents: Documents,
+    limited: LimitedDocuments,
     cargo: Cargo,
     npm: Npm,
     rokit: Rokit,
@@ -39,28 +53,98 @@ pub struct Tools {
 }
 
 impl Tools {
-    pub fn new(client: Client, clients: Clients, documents: Documents) -> Self {
+    pub fn new(
+        client: Client,
+        clients: Clients,
+        config: SharedConfig,
+        documents: Documents,
+        enabled: Vec<ToolName>,
+    ) -> Self {
+        let limited = LimitedDocuments::default();
         Self {
-            cargo: Cargo::new(client.clone(), clients.clone(), documents.clone()),
-            npm: Npm::new(client.clone(), clients.clone(), documents.clone()),
-            rokit: Rokit::new(client.clone(), clients.clone(), documents.clone()),
-            wally: Wally::new(client.clone(), clients.clone(), documents.clone()),
+            enabled,
+            config: config.clone(),
+            documents: documents.clone(),
+            limited: limited.clone(),
+            cargo: Cargo::new(
+                client.clone(),
+                clients.clone(),
+                config.clone(),
+                documents.clone(),
+                limited.clone(),
+            ),
+            npm: Npm::new(
+                client.clone(),
+                clients.clone(),
+                config.clone(),
+                documents.clone(),
+                limited.clone(),
+            ),
+            rokit: Rokit::new(
+                client.clone(),
+                clients.clone(),
+                config.clone(),
+                documents.clone(),
+                limited.clone(),
+            ),
+            wally: Wally::new(
+                client.clone(),
+                clients.clone(),
+                config.clone(),
+                documents.clone(),
+                limited.clone(),
+            ),
         }
     }
 
-    pub fn file_globs() -> Vec<&'static str> {
-        ToolName::all().into_iter().map(|t| t.file_glob()).collect()
+    /**
+        Returns the tools that are enabled, in the same order as `ToolName::all`.
+    */
+    pub fn enabled(&self) -> impl Iterator<Item = ToolName> + '_ {
+        ToolName::all().into_iter().filter(|t| self.is_enabled(*t))
     }
 
-    pub fn relevant_file_uris(uri: &Url) -> Vec<Url> {
-        ToolName::all()
-            .into_iter()
-            .flat_map(|t| t.relevant_file_uris(uri))
-            .collect()
+    pub fn is_enabled(&self, tool: ToolName) -> bool {
+        self.enabled.contains(&tool)
+    }
+
+    pub fn file_globs(&self) -> Vec<&'static str> {
+        self.enabled().map(|t| t.file_glob()).collect()
+    }
+
+    /**
+        Returns globs for files that are read from disk and should be watched,
+        meaning lockfiles, and config files that manifests are read with.
+    */
+    pub fn watched_file_globs(&self) -> Vec<String> {
+        let lockfiles = self
+            .enabled()
+            .filter_map(|t| t.lockfile_name())
+            .map(|name| format!("**/{name}"));
+        let configs = self
+            .enabled()
+            .flat_map(|t| t.config_globs().iter().map(ToString::to_string));
+        lockfiles.chain(configs).collect()
+    }
+
+    pub async fn relevant_file_uris(&self, uri: &Url) -> Vec<Url> {
+        let mut uris = Vec::new();
+        for tool in self.enabled() {
+            uris.extend(tool.relevant_file_uris(uri).await);
+        }
+        uris
+    }
+
+    fn features(&self) -> FeaturesConfig {
+        self.config.get().features
     }
 
     fn tool_for_uri(&self, uri: &Url) -> Option<&dyn Tool> {
-        match ToolName::from_uri(uri) {
+        // Modelines in open documents take precedence over file names
+        let tool_override = self.documents.get(uri).and_then(|d| d.tool_override());
+        match tool_override.map_or_else(|| ToolName::from_uri(uri), Ok) {
+            // Files of disabled tools are treated just like unsupported files
+            Ok(name) if !self.is_enabled(name) => None,
             Ok(ToolName::Aftman) => Some(&self.rokit),
             Ok(ToolName::Cargo) => Some(&self.cargo),
             Ok(ToolName::Npm) => Some(&self.npm),
@@ -72,28 +156,101 @@ impl Tools {
             }
         }
     }
+
+    /**
+        Gets the tool for the given uri, same as `tool_for_uri`, after
+        checking its document against the limits, see `LimitedDocuments`.
+    */
+    fn limited_tool_for_uri(&self, uri: &Url) -> Option<&dyn Tool> {
+        let tool = self.tool_for_uri(uri)?;
+        let size = self.documents.get(uri).map_or(0, |doc| doc.as_str().len());
+        let limits = self.config.get().limits;
+        if self
+            .limited
+            .update(&limits, uri, size, || tool.dependency_count(uri))
+        {
+            debug!("Document at '{uri}' is over the limits, nothing will be fetched for it");
+        }
+        Some(tool)
+    }
+
+    fn to_parser<T: Convert>(&self, uri: &Url, value: T) -> T {
+        Positions::new(&self.documents, Direction::ToParser).convert(uri, value)
+    }
+
+    fn to_client<T: Convert>(&self, uri: &Url, value: T) -> T {
+        Positions::new(&self.documents, Direction::ToClient).convert(uri, value)
+    }
 }
 
 #[tower_lsp::async_trait]
 impl Tool for Tools {
-    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
-        match self.tool_for_uri(&params.text_document_position_params.text_document.uri) {
-            Some(tool) => tool.hover(params).await,
+    async fn hover(&self, mut params: HoverParams) -> Result<Option<Hover>> {
+        if !self.features().hover {
+            return Ok(None);
+        }
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .clone();
+        params.text_document_position_params =
+            self.to_parser(&uri, params.text_document_position_params);
+        let hover = match self.limited_tool_for_uri(&uri) {
+            Some(tool) => self.to_client(&uri, tool.hover(params).await?),
+            None => None,
+        };
+        // NOTE: Tools always build markdown hovers, which are only
+        // stripped down here, for clients that prefer plain text
+        if self.config.hover_plaintext() {
+            Ok(hover.map(into_plaintext_hover))
+        } else {
+            Ok(hover)
+        }
+    }
+
+    async fn goto_definition(
+        &self,
+        mut params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        if !self.features().definition {
+            return Ok(None);
+        }
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .clone();
+        params.text_document_position_params =
+            self.to_parser(&uri, params.text_document_position_params);
+        match self.tool_for_uri(&uri) {
+            Some(tool) => Ok(self.to_client(&uri, tool.goto_definition(params).await?)),
             None => Ok(None),
         }
     }
 
-    async fn completion(&self, params: CompletionParams) -> Result<CompletionResponse> {
-        match self.tool_for_uri(&params.text_document_position.text_document.uri) {
-            Some(tool) => tool.completion(params).await,
+    async fn completion(&self, mut params: CompletionParams) -> Result<CompletionResponse> {
+        if !self.features().completion {
+            return Ok(CompletionResponse::Array(Vec::new()));
+        }
+        let uri = params.text_document_position.text_document.uri.clone();
+        params.text_document_position = self.to_parser(&uri, params.text_document_position);
+        match self.limited_tool_for_uri(&uri) {
+            Some(tool) => Ok(self.to_client(&uri, tool.completion(params).await?)),
             None => Ok(CompletionResponse::Array(Vec::new())),
         }
     }
 
     async fn completion_resolve(&self, item: CompletionItem) -> Result<CompletionItem> {
+        if !self.features().completion {
+            return Ok(item);
+        }
         match item.data.as_ref().map(ResolveContextPartial::try_from) {
             Some(Ok(context)) => match self.tool_for_uri(&context.uri) {
-                Some(tool) => tool.completion_resolve(item).await,
+                Some(tool) => {
+                    let item = self.to_parser(&context.uri, item);
+                    Ok(self.to_client(&context.uri, tool.completion_resolve(item).await?))
+                }
                 None => Ok(item),
             },
             _ => Ok(item),
@@ -101,26 +258,253 @@ impl Tool for Tools {
     }
 
     async fn diagnostics(&self, params: DocumentDiagnosticParams) -> Result<Vec<Diagnostic>> {
-        match self.tool_for_uri(&params.text_document.uri) {
-            Some(tool) => tool.diagnostics(params).await,
+        if !self.features().diagnostics {
+            return Ok(Vec::new());
+        }
+        let uri = params.text_document.uri.clone();
+        match self.limited_tool_for_uri(&uri) {
+            Some(tool) => Ok(self.to_client(&uri, tool.diagnostics(params).await?)),
             None => Ok(Vec::new()),
         }
     }
 
     async fn code_action(&self, params: CodeActionParams) -> Result<Vec<CodeActionOrCommand>> {
-        match self.tool_for_uri(&params.text_document.uri) {
-            Some(tool) => tool.code_action(params).await,
-            None => Ok(Vec::new()),
+        if !self.features().code_action {
+            return Ok(Vec::new());
         }
+        let uri = params.text_document.uri.clone();
+        let params = self.to_parser(&uri, params);
+        let mut actions = match self.tool_for_uri(&uri) {
+            Some(tool) => self.to_client(&uri, tool.code_action(params).await?),
+            None => return Ok(Vec::new()),
+        };
+
+        // NOTE: Package managers are run by the client, which not every client
+        // can do, and there is nothing for them to update without a lockfile
+        let lockfile_tool = match ToolName::from_uri(&uri) {
+            Ok(tool) if self.config.package_manager_commands() => {
+                tool.lockfile_uri(&uri).await.map(|_| tool)
+            }
+            _ => None,
+        };
+        retain_package_manager_commands(&mut actions, |command| {
+            command.manifest == uri && Some(command.tool.tool_name()) == lockfile_tool
+        });
+        Ok(actions)
     }
 
     async fn code_action_resolve(&self, action: CodeAction) -> Result<CodeAction> {
+        if !self.features().code_action {
+            return Ok(action);
+        }
         match action.data.as_ref().map(ResolveContextPartial::try_from) {
             Some(Ok(context)) => match self.tool_for_uri(&context.uri) {
-                Some(tool) => tool.code_action_resolve(action).await,
+                Some(tool) => {
+                    let action = self.to_parser(&context.uri, action);
+                    Ok(self.to_client(&context.uri, tool.code_action_resolve(action).await?))
+                }
                 None => Ok(action),
             },
             _ => Ok(action),
         }
     }
+
+    fn file_changed(&self, uri: &Url) {
+        // Changes to one manifest may affect the cached state of other
+        // manifests, so every tool is notified, not only the owning one
+        self.cargo.file_changed(uri);
+        self.npm.file_changed(uri);
+        self.rokit.file_changed(uri);
+        self.wally.file_changed(uri);
+    }
+
+    async fn document_highlight(
+        &self,
+        mut params: DocumentHighlightParams,
+    ) -> Result<Vec<DocumentHighlight>> {
+        if !self.features().document_highlight {
+            return Ok(Vec::new());
+        }
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .clone();
+        params.text_document_position_params =
+            self.to_parser(&uri, params.text_document_position_params);
+        match self.tool_for_uri(&uri) {
+            Some(tool) => Ok(self.to_client(&uri, tool.document_highlight(params).await?)),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        if !self.features().rename {
+            return Ok(None);
+        }
+        let uri = params.text_document.uri.clone();
+        let params = self.to_parser(&uri, params);
+        match self.tool_for_uri(&uri) {
+            Some(tool) => Ok(self.to_client(&uri, tool.prepare_rename(params).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn rename(&self, mut params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        if !self.features().rename {
+            return Ok(None);
+        }
+        let uri = params.text_document_position.text_document.uri.clone();
+        params.text_document_position = self.to_parser(&uri, params.text_document_position);
+        match self.tool_for_uri(&uri) {
+            Some(tool) => Ok(self.to_client(&uri, tool.rename(params).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn document_symbols(&self, params: DocumentSymbolParams) -> Result<Vec<DocumentSymbol>> {
+        if !self.features().document_symbol {
+            return Ok(Vec::new());
+        }
+        let uri = params.text_document.uri.clone();
+        match self.tool_for_uri(&uri) {
+            Some(tool) => Ok(self.to_client(&uri, tool.document_symbols(params).await?)),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    async fn inlay_hints(&self, params: InlayHintParams) -> Result<Vec<InlayHint>> {
+        if !self.features().inlay_hint {
+            return Ok(Vec::new());
+        }
+        let uri = params.text_document.uri.clone();
+        let params = self.to_parser(&uri, params);
+        match self.tool_for_uri(&uri) {
+            Some(tool) => Ok(self.to_client(&uri, tool.inlay_hints(params).await?)),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    async fn inline_values(&self, params: InlineValueParams) -> Result<Vec<InlineValue>> {
+        if !self.features().inline_value {
+            return Ok(Vec::new());
+        }
+        let uri = params.text_document.uri.clone();
+        let params = self.to_parser(&uri, params);
+        match self.tool_for_uri(&uri) {
+            Some(tool) => Ok(self.to_client(&uri, tool.inline_values(params).await?)),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    async fn linked_editing_range(
+        &self,
+        mut params: LinkedEditingRangeParams,
+    ) -> Result<Option<LinkedEditingRanges>> {
+        if !self.features().linked_editing_range {
+            return Ok(None);
+        }
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .clone();
+        params.text_document_position_params =
+            self.to_parser(&uri, params.text_document_position_params);
+        match self.tool_for_uri(&uri) {
+            Some(tool) => Ok(self.to_client(&uri, tool.linked_editing_range(params).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn will_save_wait_until(
+        &self,
+        params: WillSaveTextDocumentParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        if !self.config.get().sort_on_save {
+            return Ok(None);
+        }
+        let uri = params.text_document.uri.clone();
+        match self.tool_for_uri(&uri) {
+            Some(tool) => Ok(self.to_client(&uri, tool.will_save_wait_until(params).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn manifest_info(&self, params: ManifestInfoParams) -> Result<Option<ManifestInfo>> {
+        let uri = params.text_document.uri.clone();
+        match self.tool_for_uri(&uri) {
+            Some(tool) => Ok(self.to_client(&uri, tool.manifest_info(params).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn analyze(&self, params: AnalyzeParams) -> Result<Option<AnalyzeResult>> {
+        let uri = params.text_document.uri.clone();
+        match self.limited_tool_for_uri(&uri) {
+            Some(tool) => Ok(self.to_client(&uri, tool.analyze(params).await?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::testing::{cursor_fixture, MockFetcher, TestServer, SERDE_INDEX};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_modeline_tool_override() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::new(fetcher).await;
+
+        // The file name is not recognized, but the modeline is
+        let (contents, pos) =
+            cursor_fixture("# tooling-lsp: tool=cargo\n[dependencies]\nserde = \"1.0.1$0\"\n");
+        let uri = server.open("dependencies.txt", &contents).await;
+        let items = server.completion(&uri, pos).await;
+        assert_eq!(items.first().map(|i| i.label.as_str()), Some("1.0.150"));
+
+        // A modeline also takes precedence over a recognized file name
+        let (contents, pos) =
+            cursor_fixture("# tooling-lsp: tool=rokit\n[dependencies]\nserde = \"1.0.1$0\"\n");
+        let uri = server.open("Cargo.toml", &contents).await;
+        assert!(server.completion(&uri, pos).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_disabled_tools() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::with_tools(fetcher.clone(), vec![ToolName::Rokit]).await;
+        assert_eq!(server.tools().file_globs(), vec!["**/rokit.toml"]);
+        assert!(server.tools().watched_file_globs().is_empty());
+
+        // Files of disabled tools are ignored, just like unsupported files
+        let uri = server
+            .open("Cargo.toml", "[dependencies]\nserde = \"1.0.0\"\n")
+            .await;
+        assert!(server.hover(&uri, Position::new(1, 1)).await.is_none());
+        assert!(server.diagnostics(&uri).await.is_empty());
+        assert!(server
+            .code_actions(&uri, Position::new(1, 1))
+            .await
+            .is_empty());
+        assert!(fetcher.requested().is_empty());
+
+        // Enabled tools work as usual
+        let uri = server
+            .open(
+                "rokit.toml",
+                "[tools]\nrojo = \"rojo-rbx/rojo@7.4.1\"\nlune = \"lune-org/lune@0.8.9\"",
+            )
+            .await;
+        let actions = server.code_actions(&uri, Position::new(1, 0)).await;
+        assert!(actions
+            .iter()
+            .any(|a| a.title == "Sort tools alphabetically"));
+    }
 }
@@ -1,10 +1,18 @@
 use std::str::FromStr;
 
+use tokio::fs;
 use tower_lsp::lsp_types::*;
+use tracing::warn;
 
 use crate::util::LspUriExt;
 
-#[derive(Debug, Clone, Copy)]
+/**
+    The prefix of a modeline on the first line of a manifest, such as
+    `# tooling-lsp: tool=wally`, which forces the tool for that manifest.
+*/
+const MODELINE_PREFIX: &str = "# tooling-lsp: tool=";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ToolName {
     Aftman,
     Cargo,
@@ -21,6 +29,25 @@ impl ToolName {
         }
     }
 
+    /**
+        Parses a modeline such as `# tooling-lsp: tool=wally` from the first
+        line of a manifest, which takes precedence over its file name.
+
+        The syntax is strict, so that it is never triggered by accident - the
+        modeline must be the very first line, spelled exactly like above, and
+        name a tool with a TOML manifest, since JSON does not allow comments.
+    */
+    pub fn from_modeline(contents: &str) -> Option<Self> {
+        let first_line = contents.lines().next()?.trim_end();
+        match first_line.strip_prefix(MODELINE_PREFIX)? {
+            "aftman" => Some(Self::Aftman),
+            "cargo" => Some(Self::Cargo),
+            "rokit" => Some(Self::Rokit),
+            "wally" => Some(Self::Wally),
+            _ => None,
+        }
+    }
+
     pub fn all() -> Vec<Self> {
         vec![
             Self::Aftman,
@@ -41,43 +68,122 @@ impl ToolName {
         }
     }
 
-    pub fn relevant_file_uris(&self, uri: &Url) -> Vec<Url> {
+    /**
+        Returns the file name of the lockfile for the tool, if it uses lockfiles.
+    */
+    pub fn lockfile_name(&self) -> Option<&'static str> {
+        match self {
+            Self::Aftman | Self::Rokit => None,
+            Self::Cargo => Some("Cargo.lock"),
+            Self::Npm => Some("package-lock.json"),
+            Self::Wally => Some("wally.lock"),
+        }
+    }
+
+    /**
+        Returns globs for config files of the tool that change how its
+        manifests are read, such as where registries and vendored crates are.
+    */
+    pub fn config_globs(&self) -> &'static [&'static str] {
+        match self {
+            Self::Cargo => &["**/.cargo/config.toml", "**/.cargo/config"],
+            _ => &[],
+        }
+    }
+
+    /**
+        Returns the uri of the lockfile for the manifest at the
+        given uri, if the tool uses lockfiles and one exists.
+    */
+    pub async fn lockfile_uri(&self, manifest_uri: &Url) -> Option<Url> {
+        let lockfile_name = self.lockfile_name()?;
+        for uri in self.relevant_file_uris(manifest_uri).await {
+            if uri.file_name().as_deref() != Some(lockfile_name) {
+                continue;
+            }
+            let Ok(path) = uri.to_file_path() else {
+                continue;
+            };
+            if fs::try_exists(&path).await.unwrap_or(false) {
+                return Some(uri);
+            }
+        }
+        None
+    }
+
+    /**
+        Returns uris for any other files that are relevant to the file at the
+        given uri, such as lockfiles for manifests and manifests for lockfiles.
+
+        Never panics, any uris or paths that can't be converted are skipped,
+        and documents that only exist in memory never have any relevant files.
+    */
+    pub async fn relevant_file_uris(&self, uri: &Url) -> Vec<Url> {
+        if uri.is_in_memory() {
+            return Vec::new();
+        }
         match self {
             Self::Aftman => Vec::new(),
             Self::Cargo => match uri.file_name().as_deref() {
-                Some("Cargo.lock") => vec![uri.with_file_name("Cargo.toml").unwrap()],
-                Some("Cargo.toml") => {
-                    let mut lockfiles = Vec::new();
-                    let mut current_dir = uri.to_file_path().unwrap();
-                    loop {
-                        current_dir.pop();
-                        let lockfile = current_dir.join("Cargo.lock");
-                        if lockfile.exists() {
-                            lockfiles.push(Url::from_file_path(lockfile).unwrap());
-                        }
-                        if !current_dir.pop() {
-                            break;
-                        }
-                    }
-                    lockfiles
-                }
+                Some("Cargo.lock") => sibling_file_uri(uri, "Cargo.toml"),
+                Some("Cargo.toml") => ancestor_file_uris(uri, "Cargo.lock").await,
                 _ => Vec::new(),
             },
             Self::Npm => match uri.file_name().as_deref() {
-                Some("package.json") => vec![uri.with_file_name("package-lock.json").unwrap()],
-                Some("package-lock.json") => vec![uri.with_file_name("package.json").unwrap()],
+                Some("package.json") => sibling_file_uri(uri, "package-lock.json"),
+                Some("package-lock.json") => sibling_file_uri(uri, "package.json"),
                 _ => Vec::new(),
             },
             Self::Rokit => Vec::new(),
             Self::Wally => match uri.file_name().as_deref() {
-                Some("wally.toml") => vec![uri.with_file_name("wally.lock").unwrap()],
-                Some("wally.lock") => vec![uri.with_file_name("wally.toml").unwrap()],
+                Some("wally.toml") => sibling_file_uri(uri, "wally.lock"),
+                Some("wally.lock") => sibling_file_uri(uri, "wally.toml"),
                 _ => Vec::new(),
             },
         }
     }
 }
 
+fn sibling_file_uri(uri: &Url, file_name: &str) -> Vec<Url> {
+    match uri.with_file_name(file_name) {
+        Some(sibling) => vec![sibling],
+        None => {
+            warn!("Failed to create uri for '{file_name}' next to '{uri}'");
+            Vec::new()
+        }
+    }
+}
+
+async fn ancestor_file_uris(uri: &Url, file_name: &str) -> Vec<Url> {
+    let Ok(path) = uri.to_file_path() else {
+        warn!("Failed to convert uri '{uri}' to a file path");
+        return Vec::new();
+    };
+
+    let mut uris = Vec::new();
+    let mut canonical_paths = Vec::new();
+    for dir in path.ancestors().skip(1) {
+        // Files that don't exist can't be canonicalized, which skips them too
+        let file_path = dir.join(file_name);
+        let Ok(canonical_path) = fs::canonicalize(&file_path).await else {
+            continue;
+        };
+
+        // NOTE: Symlinks are resolved only to never add the same file twice,
+        // the uri itself must keep the path as given, since that is the one
+        // that clients use for the documents that they send to us
+        if canonical_paths.contains(&canonical_path) {
+            continue;
+        }
+        canonical_paths.push(canonical_path);
+        match Url::from_file_path(&file_path) {
+            Ok(file_uri) => uris.push(file_uri),
+            Err(()) => warn!("Failed to convert path '{}' to a uri", file_path.display()),
+        }
+    }
+    uris
+}
+
 impl FromStr for ToolName {
     type Err = &'static str;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -91,3 +197,113 @@ impl FromStr for ToolName {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::util::FixtureDir;
+
+    use super::*;
+
+    async fn relevant_file_uris(uri: &Url) -> Vec<Url> {
+        let mut uris = Vec::new();
+        for tool in ToolName::all() {
+            uris.extend(tool.relevant_file_uris(uri).await);
+        }
+        uris
+    }
+
+    #[test]
+    fn test_modeline() {
+        let parse = ToolName::from_modeline;
+        assert_eq!(
+            parse("# tooling-lsp: tool=wally\n[dependencies]"),
+            Some(ToolName::Wally)
+        );
+        assert_eq!(
+            parse("# tooling-lsp: tool=cargo\r\n"),
+            Some(ToolName::Cargo)
+        );
+
+        // Anything but the exact syntax on the first line is ignored
+        assert_eq!(parse("# tooling-lsp: tool=npm"), None);
+        assert_eq!(parse("# tooling-lsp: tool=Wally"), None);
+        assert_eq!(parse("#tooling-lsp: tool=wally"), None);
+        assert_eq!(parse("# tooling-lsp: tool = wally"), None);
+        assert_eq!(parse("  # tooling-lsp: tool=wally"), None);
+        assert_eq!(parse("\n# tooling-lsp: tool=wally"), None);
+        assert_eq!(parse("# tooling-lsp: tool=wally.toml"), None);
+    }
+
+    #[tokio::test]
+    async fn test_untitled_uri() {
+        let uri = Url::parse("untitled:Untitled-1").unwrap();
+        assert!(ToolName::from_uri(&uri).is_err());
+        assert!(relevant_file_uris(&uri).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_untitled_uri_with_file_name() {
+        let uri = Url::parse("untitled:/drafts/Cargo.toml").unwrap();
+        assert!(matches!(ToolName::from_uri(&uri), Ok(ToolName::Cargo)));
+        assert!(relevant_file_uris(&uri).await.is_empty());
+        assert!(ToolName::Cargo.lockfile_uri(&uri).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_non_file_uri() {
+        let uri = Url::parse("https://example.com/project/Cargo.toml").unwrap();
+        assert!(relevant_file_uris(&uri).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unusual_characters() {
+        let uri = Url::from_file_path(
+            std::env::temp_dir()
+                .join("tooling lsp %20 ✓ #1")
+                .join("package.json"),
+        )
+        .unwrap();
+
+        let relevant = relevant_file_uris(&uri).await;
+        assert_eq!(relevant.len(), 1);
+        assert_eq!(
+            relevant[0].file_name().as_deref(),
+            Some("package-lock.json")
+        );
+        assert_eq!(
+            relevant[0].to_file_path().unwrap().parent(),
+            uri.to_file_path().unwrap().parent()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cargo_lockfiles() {
+        let root = FixtureDir::new("cargo-lockfiles");
+        root.write("Cargo.lock", "");
+
+        let uri =
+            Url::from_file_path(root.join("crates").join("member").join("Cargo.toml")).unwrap();
+        let relevant = relevant_file_uris(&uri).await;
+
+        assert_eq!(
+            relevant,
+            vec![Url::from_file_path(root.join("Cargo.lock")).unwrap()]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cargo_lockfiles_symlinked() {
+        let root = FixtureDir::new("cargo-lockfiles-symlinked");
+        root.write("Cargo.lock", "");
+        std::os::unix::fs::symlink(&*root, root.join("linked")).unwrap();
+
+        // Both the link and the root have the same lockfile, which is only
+        // listed once, with the path that the manifest was opened with
+        let uri = Url::from_file_path(root.join("linked").join("Cargo.toml")).unwrap();
+        let relevant = relevant_file_uris(&uri).await;
+        let expected = Url::from_file_path(root.join("linked").join("Cargo.lock")).unwrap();
+        assert_eq!(relevant, vec![expected.clone()]);
+        assert_eq!(ToolName::Cargo.lockfile_uri(&uri).await, Some(expected));
+    }
+}
@@ -5,6 +5,10 @@ use crate::clients::*;
 use crate::parser::Dependency;
 use crate::server::*;
 
+use super::super::shared::{
+    sort_alphabetically, version_segment_range, with_sort_texts, with_version_variants,
+    CompletionMetadata, PlainVersion,
+};
 use super::constants::top_npm_packages_prefixed;
 use super::Versioned;
 
@@ -12,6 +16,7 @@ const MAXIMUM_PACKAGES_SHOWN: usize = 64;
 
 pub async fn get_npm_completions_name(
     _clients: &Clients,
+    resolve: CompletionResolveSupport,
     document: &Document,
     dep: &Dependency,
 ) -> Result<CompletionResponse> {
@@ -31,10 +36,46 @@ pub async fn get_npm_completions_name(
                 document.create_edit(dep.name().range, package.name.to_string()),
             )),
             detail: None,
+            data: (resolve.detail || resolve.documentation).then(|| {
+                CompletionMetadata::NpmPackage {
+                    name: package.name.to_string(),
+                }
+                .into_data(document.uri())
+            }),
             ..Default::default()
         })
         .collect::<Vec<_>>();
-    Ok(CompletionResponse::Array(items))
+    Ok(CompletionResponse::Array(sort_alphabetically(items)))
+}
+
+/**
+    Resolves the detail and documentation for a package name completion
+    item, which requires fetching the package metadata from the registry.
+*/
+pub async fn get_npm_completion_resolve(
+    clients: &Clients,
+    resolve: CompletionResolveSupport,
+    mut item: CompletionItem,
+    name: &str,
+) -> Result<CompletionItem> {
+    let Ok(meta) = clients.npm.get_registry_metadata(name).await else {
+        return Ok(item);
+    };
+
+    let version = meta.current_version;
+    if resolve.detail {
+        item.detail = Some(format!("Latest version {}", version.version));
+    }
+    if resolve.documentation {
+        if let Some(desc) = version.description {
+            item.documentation = Some(Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: desc,
+            }));
+        }
+    }
+
+    Ok(item)
 }
 
 pub async fn get_npm_completions_version(
@@ -56,19 +97,23 @@ pub async fn get_npm_completions_version(
         .extract_completion_versions(metadata.versions.into_values())
         .into_iter()
         .take(MAXIMUM_PACKAGES_SHOWN)
-        .enumerate()
-        .map(|(index, potential_version)| CompletionItem {
+        .map(|potential_version| CompletionItem {
             label: potential_version.item_version_raw.to_string(),
             kind: Some(CompletionItemKind::VALUE),
-            sort_text: Some(format!("{:0>5}", index)),
-            text_edit: Some(CompletionTextEdit::Edit(document.create_substring_edit(
-                version.range.start.line,
-                potential_version.this_version_raw,
+            text_edit: Some(CompletionTextEdit::Edit(document.create_edit(
+                version_segment_range(version),
                 potential_version.item_version_raw,
             ))),
             ..Default::default()
         })
         .collect::<Vec<_>>();
 
-    Ok(CompletionResponse::Array(valid_vec))
+    let valid_vec = with_version_variants(
+        document,
+        version.unquoted_range(),
+        version.unquoted(),
+        PlainVersion::Exact,
+        valid_vec,
+    );
+    Ok(CompletionResponse::Array(with_sort_texts(valid_vec)))
 }
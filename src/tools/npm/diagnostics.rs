@@ -5,18 +5,19 @@ use crate::parser::Dependency;
 use crate::util::{VersionReqExt, Versioned};
 
 use super::super::shared::*;
+use super::super::ToolName;
 use super::{Clients, Document};
 
 pub async fn get_npm_diagnostics(
     clients: &Clients,
     doc: &Document,
     dep: &Dependency,
-) -> Result<Vec<Diagnostic>> {
+) -> Result<DependencyDiagnostics> {
     let Some(dep_version) = dep.spec().and_then(|s| s.contents.version.as_ref()) else {
-        return Ok(Vec::new());
+        return Ok(Vec::new().into());
     };
     let Ok(version_req) = dep.parse_version_req() else {
-        return Ok(Vec::new());
+        return Ok(Vec::new().into());
     };
     let version = version_req.minimum_version();
 
@@ -37,10 +38,11 @@ pub async fn get_npm_diagnostics(
                         dep.name().unquoted()
                     ),
                     severity: Some(DiagnosticSeverity::ERROR),
-                    ..Default::default()
-                }]);
+                    ..DiagnosticCode::UnknownPackage.diagnostic()
+                }]
+                .into());
             } else {
-                return Ok(Vec::new());
+                return Ok(DependencyDiagnostics::incomplete(Vec::new()));
             }
         }
     };
@@ -59,15 +61,16 @@ pub async fn get_npm_diagnostics(
                 dep.name().unquoted()
             ),
             severity: Some(DiagnosticSeverity::ERROR),
-            ..Default::default()
-        }]);
+            ..DiagnosticCode::UnknownVersion.diagnostic()
+        }]
+        .into());
     }
 
     // Everything is OK - but we may be able to suggest new versions...
     // ... try to find the latest non-prerelease version
     let Some(latest_version) = version.extract_latest_version(meta.versions.values().cloned())
     else {
-        return Ok(Vec::new());
+        return Ok(Vec::new().into());
     };
 
     if !latest_version.is_semver_compatible {
@@ -79,6 +82,11 @@ pub async fn get_npm_diagnostics(
             source_text: dep_version.quoted().to_string(),
             version_current: version.to_string(),
             version_latest: latest_version_string.to_string(),
+            package_manager: PackageManagerCommand::for_package(
+                ToolName::Npm,
+                doc.uri(),
+                dep.name().unquoted(),
+            ),
         };
 
         return Ok(vec![Diagnostic {
@@ -97,9 +105,10 @@ pub async fn get_npm_diagnostics(
                 }
                 .into(),
             ),
-            ..Default::default()
-        }]);
+            ..DiagnosticCode::Outdated.diagnostic()
+        }]
+        .into());
     }
 
-    Ok(Vec::new())
+    Ok(Vec::new().into())
 }
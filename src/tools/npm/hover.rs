@@ -3,8 +3,10 @@ use tower_lsp::lsp_types::*;
 use tracing::trace;
 
 use crate::{
+    clients::npm::models::RegistryMetadataVersion,
     parser::Dependency,
-    tools::MarkdownBuilder,
+    server::VersionSource,
+    tools::{HoverSections, LockfileCache, MarkdownBuilder, ToolName},
     util::{VersionReqExt, Versioned},
 };
 
@@ -12,55 +14,88 @@ use super::{Clients, Document};
 
 pub async fn get_npm_hover(
     clients: &Clients,
-    _doc: &Document,
+    lockfiles: &LockfileCache,
+    doc: &Document,
     dep: &Dependency,
+    source: VersionSource,
 ) -> Result<Option<Hover>> {
-    let Ok(version_req) = dep.parse_version_req() else {
-        return Ok(None);
-    };
-
     let dependency_name = dep.name().unquoted();
-    let dependency_version = version_req.minimum_version();
+    let version_req = dep.parse_version_req().ok();
 
-    // Add basic hover information with version and name
+    // Add basic hover information with version and name, falling back
+    // to the version as written for tags and other non-semver versions
+    let dependency_version = match &version_req {
+        Some(req) => req.minimum_version().to_string(),
+        None => dep.raw_version_string(),
+    };
     trace!("Hovering: {dependency_name} version {dependency_version}");
-    let mut md = MarkdownBuilder::new();
-    md.h2(dependency_name);
-    md.version(dependency_version);
 
     // Try to fetch additional information from the index - description, links
     trace!("Fetching package data from npm");
-    if let Ok(meta) = clients.npm.get_registry_metadata(dependency_name).await {
-        if let Some(desc) = meta.current_version.description.as_ref() {
-            md.br();
-            md.p(desc);
-        }
-
-        // Ignore homepage or docs if it's the same as the repo
-        let mut page = meta.current_version.homepage.as_deref();
-        let repo = meta.current_version.repository.as_ref();
-        if page.is_some_and(|p| {
-            repo.is_some_and(|r| r.url().is_some_and(|u| u.eq_ignore_ascii_case(p)))
-        }) {
-            page = None;
-        }
+    let meta = clients
+        .npm
+        .get_registry_metadata(dependency_name)
+        .await
+        .ok();
+    let lockfile = match source.uses_lockfile() {
+        true => lockfiles.for_manifest(ToolName::Npm, doc.uri()).await,
+        false => None,
+    };
+    let locked = lockfile.and_then(|lockfile| {
+        let raw = dep.raw_version_string();
+        let package = lockfile.find(dependency_name, Some(&raw))?;
+        Some(package.version.clone())
+    });
+    let resolved = version_req.as_ref().filter(|_| source.uses_registry());
+    let resolved = resolved.and_then(|req| {
+        meta.as_ref()?
+            .versions
+            .values()
+            .filter_map(|v| v.parse_version().ok())
+            .filter(|v| req.matches(v))
+            .max()
+    });
 
-        // Add links to repo and homepage
+    let mut hover = HoverSections::new();
+    hover.section(|md| md.h2(dependency_name));
+    hover.section(|md| {
+        md.version(dependency_version);
         md.br();
-        md.h3("Links");
-        if let Some(repo) = repo.and_then(|r| r.url()) {
-            md.a("Repository", repo);
-        }
-        if let Some(page) = page {
-            md.a("Homepage", page);
+        if let Some(locked) = &locked {
+            md.locked(locked);
         }
+        md.requirement(&dep.raw_version_string(), resolved.as_ref());
+    });
+    if let Some(meta) = &meta {
+        hover.section(|md| {
+            if let Some(desc) = meta.current_version.description.as_ref() {
+                md.p(desc);
+            }
+        });
+        hover.push(links_section(&meta.current_version));
+    }
+
+    Ok(Some(hover.into_hover(dep.range())))
+}
+
+fn links_section(version: &RegistryMetadataVersion) -> MarkdownBuilder {
+    // Ignore homepage or docs if it's the same as the repo
+    let mut page = version.homepage.as_deref();
+    let repo = version.repository.as_ref();
+    if page
+        .is_some_and(|p| repo.is_some_and(|r| r.url().is_some_and(|u| u.eq_ignore_ascii_case(p))))
+    {
+        page = None;
     }
 
-    Ok(Some(Hover {
-        range: Some(dep.range()),
-        contents: HoverContents::Markup(MarkupContent {
-            kind: MarkupKind::Markdown,
-            value: md.build(),
-        }),
-    }))
+    // Add links to repo and homepage
+    let mut md = MarkdownBuilder::new();
+    md.h3("Links");
+    if let Some(repo) = repo.and_then(|r| r.url()) {
+        md.a("Repository", repo);
+    }
+    if let Some(page) = page {
+        md.a("Homepage", page);
+    }
+    md
 }
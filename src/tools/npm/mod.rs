@@ -1,4 +1,3 @@
-use futures::future::try_join_all;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::Client;
@@ -24,28 +23,35 @@ use hover::*;
 pub struct Npm {
     _client: Client,
     clients: Clients,
+    config: SharedConfig,
     documents: Documents,
+    limited: LimitedDocuments,
+    lockfiles: LockfileCache,
 }
 
 impl Npm {
-    pub(super) fn new(client: Client, clients: Clients, documents: Documents) -> Self {
+    pub(super) fn new(
+        client: Client,
+        clients: Clients,
+        config: SharedConfig,
+        documents: Documents,
+        limited: LimitedDocuments,
+    ) -> Self {
         Self {
             _client: client,
             clients,
+            config,
             documents,
+            limited,
+            lockfiles: LockfileCache::default(),
         }
     }
 
     fn get_document(&self, uri: &Url) -> Option<Document> {
-        if uri
-            .file_name()
-            .as_deref()
-            .is_some_and(|f| f.eq_ignore_ascii_case("package.json"))
-        {
-            self.documents.get(uri).map(|r| r.clone())
-        } else {
-            None
-        }
+        self.documents
+            .get(uri)
+            .filter(|doc| doc.is_for_tool(ToolName::Npm, &["package.json"]))
+            .map(|r| r.clone())
     }
 }
 
@@ -64,9 +70,14 @@ impl Tool for Npm {
             return Ok(None);
         };
 
+        if self.limited.contains(&uri) {
+            return Ok(None);
+        }
+
         // Fetch some extra info and return the hover
         debug!("Hovering: {found:?}");
-        get_npm_hover(&self.clients, &doc, found).await
+        let source = self.config.get().hover.version_source;
+        get_npm_hover(&self.clients, &self.lockfiles, &doc, found, source).await
     }
 
     async fn completion(&self, params: CompletionParams) -> Result<CompletionResponse> {
@@ -82,10 +93,15 @@ impl Tool for Npm {
             return Ok(CompletionResponse::Array(Vec::new()));
         };
 
+        if self.limited.contains(&uri) {
+            return Ok(CompletionResponse::Array(Vec::new()));
+        }
+
         // Check what we're completing - name or version
         if found.name().contains(pos) {
             debug!("Completing name: {found:?}");
-            return get_npm_completions_name(&self.clients, &doc, found).await;
+            let resolve = self.config.completion_resolve();
+            return get_npm_completions_name(&self.clients, resolve, &doc, found).await;
         } else if let Some(s) = found.spec().filter(|s| s.contains(pos)) {
             if s.contents.version.as_ref().is_some_and(|v| v.contains(pos)) {
                 debug!("Completing version: {found:?}");
@@ -96,6 +112,17 @@ impl Tool for Npm {
         Ok(CompletionResponse::Array(Vec::new()))
     }
 
+    async fn completion_resolve(&self, item: CompletionItem) -> Result<CompletionItem> {
+        match CompletionMetadata::from_item(&item) {
+            Some(CompletionMetadata::NpmPackage { name }) => {
+                debug!("Resolving completion: {name}");
+                let resolve = self.config.completion_resolve();
+                get_npm_completion_resolve(&self.clients, resolve, item, &name).await
+            }
+            _ => Ok(item),
+        }
+    }
+
     async fn diagnostics(&self, params: DocumentDiagnosticParams) -> Result<Vec<Diagnostic>> {
         let uri = params.text_document.uri;
         let Some(doc) = self.get_document(&uri) else {
@@ -108,16 +135,23 @@ impl Tool for Npm {
             return Ok(Vec::new());
         }
 
+        if self.limited.contains(&uri) {
+            let limits = self.config.get().limits;
+            return Ok(vec![exceeds_limits_diagnostic(&limits, "NPM")]);
+        }
+
         // Fetch all diagnostics concurrently
         debug!("Fetching npm diagnostics for dependencies");
-        let results = try_join_all(
-            dependencies
-                .iter()
-                .map(|dep| get_npm_diagnostics(&self.clients, &doc, dep)),
+        cached_dependency_diagnostics(
+            &doc,
+            self.config.get().diagnostics.merge,
+            dependencies.iter().map(|dep| {
+                let key = DependencyKey::new(&doc, dep.kind(), dep.range());
+                let fut = get_npm_diagnostics(&self.clients, &doc, dep);
+                (key, dep.range(), fut)
+            }),
         )
-        .await?;
-
-        Ok(results.into_iter().flatten().collect())
+        .await
     }
 
     async fn code_action(&self, params: CodeActionParams) -> Result<Vec<CodeActionOrCommand>> {
@@ -128,9 +162,65 @@ impl Tool for Npm {
                 .as_ref()
                 .map(ResolveContext::<CodeActionMetadata>::try_from)
             {
-                actions.push(action.into_inner().into_code_action(diag.clone()))
+                actions.extend(action.into_inner().into_code_actions(Some(diag.clone())))
+            }
+        }
+
+        // Only offered when no specific kinds were asked for, see `into_code_action`
+        if params.context.only.is_none() {
+            if let Some(doc) = self.get_document(&params.text_document.uri) {
+                let dependencies = query_package_json_dependencies(doc.inner());
+                if let Some(found) = Dependency::find_at_pos(&dependencies, params.range.start) {
+                    let dependency = DependencyId::Npm {
+                        name: found.name().unquoted().to_string(),
+                    };
+                    actions.push(
+                        RefreshDependencyParams::new(doc.uri(), dependency).into_code_action(),
+                    );
+                }
             }
         }
+
         Ok(actions)
     }
+
+    fn dependency_count(&self, uri: &Url) -> usize {
+        self.get_document(uri)
+            .map_or(0, |doc| query_package_json_dependencies(doc.inner()).len())
+    }
+
+    fn file_changed(&self, uri: &Url) {
+        self.lockfiles.invalidate(uri);
+
+        // A changed lockfile usually means that dependencies were just
+        // updated, so this is a good time to pick up any new versions
+        if uri
+            .file_name()
+            .as_deref()
+            .is_some_and(|f| f.eq_ignore_ascii_case("package-lock.json"))
+        {
+            debug!("Lockfile changed, invalidating cached npm versions");
+            self.clients.npm.invalidate_versions();
+        }
+    }
+
+    async fn inline_values(&self, params: InlineValueParams) -> Result<Vec<InlineValue>> {
+        let Some(doc) = self.get_document(&params.text_document.uri) else {
+            return Ok(Vec::new());
+        };
+        let Some(lockfile) = self.lockfiles.for_manifest(ToolName::Npm, doc.uri()).await else {
+            return Ok(Vec::new());
+        };
+
+        let dependencies = query_package_json_dependencies(doc.inner());
+        let values = dependencies.iter().map(|dep| LockedInlineValue {
+            range: dep.range(),
+            name: dep.name().unquoted(),
+            version: dep
+                .spec()
+                .and_then(|s| s.contents.version.as_ref())
+                .map(|v| v.unquoted()),
+        });
+        Ok(get_locked_inline_values(&lockfile, values, params.range))
+    }
 }
@@ -0,0 +1,376 @@
+use tower_lsp::lsp_types::*;
+
+use crate::server::Documents;
+
+use super::{AnalyzeResult, ManifestInfo};
+
+/**
+    Which way positions are converted, see `Positions`.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    ToParser,
+    ToClient,
+}
+
+/**
+    Converts positions between the units used by clients and the parser.
+
+    Clients count columns in UTF-16 code units, while tree-sitter, and every
+    tool along with it, counts bytes. Tools only ever work with parser units,
+    and everything that goes in or out of them is converted here instead.
+
+    Positions in documents that the server does not know about are left
+    as they are, since there is no text to convert them with.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct Positions<'a> {
+    documents: &'a Documents,
+    direction: Direction,
+}
+
+impl<'a> Positions<'a> {
+    pub fn new(documents: &'a Documents, direction: Direction) -> Self {
+        Self {
+            documents,
+            direction,
+        }
+    }
+
+    pub fn convert<T: Convert>(self, uri: &Url, mut value: T) -> T {
+        value.convert(uri, self);
+        value
+    }
+
+    fn position(self, uri: &Url, position: &mut Position) {
+        let Some(doc) = self.documents.get(uri) else {
+            return;
+        };
+        *position = match self.direction {
+            Direction::ToParser => doc.lsp_position_to_parser(*position),
+            Direction::ToClient => doc.lsp_position_from_parser(*position),
+        };
+    }
+}
+
+/**
+    Anything that contains positions within the document at `uri`.
+
+    Values that point into other documents, such as locations,
+    use their own uri instead of the one that is passed in.
+*/
+pub trait Convert {
+    fn convert(&mut self, uri: &Url, positions: Positions);
+}
+
+impl<T: Convert> Convert for Option<T> {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        if let Some(value) = self {
+            value.convert(uri, positions);
+        }
+    }
+}
+
+impl<T: Convert> Convert for Vec<T> {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        for value in self {
+            value.convert(uri, positions);
+        }
+    }
+}
+
+impl<A: Convert, B: Convert> Convert for OneOf<A, B> {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        match self {
+            OneOf::Left(a) => a.convert(uri, positions),
+            OneOf::Right(b) => b.convert(uri, positions),
+        }
+    }
+}
+
+impl Convert for Position {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        positions.position(uri, self);
+    }
+}
+
+impl Convert for Range {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        self.start.convert(uri, positions);
+        self.end.convert(uri, positions);
+    }
+}
+
+impl Convert for Location {
+    fn convert(&mut self, _: &Url, positions: Positions) {
+        self.range.convert(&self.uri, positions);
+    }
+}
+
+impl Convert for LocationLink {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        self.origin_selection_range.convert(uri, positions);
+        self.target_range.convert(&self.target_uri, positions);
+        self.target_selection_range
+            .convert(&self.target_uri, positions);
+    }
+}
+
+impl Convert for TextEdit {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        self.range.convert(uri, positions);
+    }
+}
+
+impl Convert for AnnotatedTextEdit {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        self.text_edit.convert(uri, positions);
+    }
+}
+
+impl Convert for TextDocumentEdit {
+    fn convert(&mut self, _: &Url, positions: Positions) {
+        self.edits.convert(&self.text_document.uri, positions);
+    }
+}
+
+impl Convert for WorkspaceEdit {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        if let Some(changes) = &mut self.changes {
+            for (edit_uri, edits) in changes {
+                edits.convert(edit_uri, positions);
+            }
+        }
+        match &mut self.document_changes {
+            Some(DocumentChanges::Edits(edits)) => edits.convert(uri, positions),
+            Some(DocumentChanges::Operations(operations)) => {
+                for operation in operations {
+                    if let DocumentChangeOperation::Edit(edit) = operation {
+                        edit.convert(uri, positions);
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+impl Convert for Diagnostic {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        self.range.convert(uri, positions);
+        if let Some(related) = &mut self.related_information {
+            for info in related {
+                info.location.convert(uri, positions);
+            }
+        }
+    }
+}
+
+impl Convert for CodeAction {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        self.diagnostics.convert(uri, positions);
+        self.edit.convert(uri, positions);
+    }
+}
+
+impl Convert for CodeActionOrCommand {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        if let CodeActionOrCommand::CodeAction(action) = self {
+            action.convert(uri, positions);
+        }
+    }
+}
+
+impl Convert for CompletionTextEdit {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        match self {
+            CompletionTextEdit::Edit(edit) => edit.convert(uri, positions),
+            CompletionTextEdit::InsertAndReplace(edit) => {
+                edit.insert.convert(uri, positions);
+                edit.replace.convert(uri, positions);
+            }
+        }
+    }
+}
+
+impl Convert for CompletionItem {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        self.text_edit.convert(uri, positions);
+        self.additional_text_edits.convert(uri, positions);
+    }
+}
+
+impl Convert for CompletionResponse {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        match self {
+            CompletionResponse::Array(items) => items.convert(uri, positions),
+            CompletionResponse::List(list) => list.items.convert(uri, positions),
+        }
+    }
+}
+
+impl Convert for Hover {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        self.range.convert(uri, positions);
+    }
+}
+
+impl Convert for GotoDefinitionResponse {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        match self {
+            GotoDefinitionResponse::Scalar(location) => location.convert(uri, positions),
+            GotoDefinitionResponse::Array(locations) => locations.convert(uri, positions),
+            GotoDefinitionResponse::Link(links) => links.convert(uri, positions),
+        }
+    }
+}
+
+impl Convert for DocumentHighlight {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        self.range.convert(uri, positions);
+    }
+}
+
+impl Convert for DocumentSymbol {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        self.range.convert(uri, positions);
+        self.selection_range.convert(uri, positions);
+        self.children.convert(uri, positions);
+    }
+}
+
+impl Convert for PrepareRenameResponse {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        match self {
+            PrepareRenameResponse::Range(range)
+            | PrepareRenameResponse::RangeWithPlaceholder { range, .. } => {
+                range.convert(uri, positions);
+            }
+            PrepareRenameResponse::DefaultBehavior { .. } => {}
+        }
+    }
+}
+
+impl Convert for InlayHint {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        self.position.convert(uri, positions);
+        self.text_edits.convert(uri, positions);
+        if let InlayHintLabel::LabelParts(parts) = &mut self.label {
+            for part in parts {
+                part.location.convert(uri, positions);
+            }
+        }
+    }
+}
+
+impl Convert for InlineValue {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        match self {
+            InlineValue::Text(value) => value.range.convert(uri, positions),
+            InlineValue::VariableLookup(value) => value.range.convert(uri, positions),
+            InlineValue::EvaluatableExpression(value) => value.range.convert(uri, positions),
+        }
+    }
+}
+
+impl Convert for LinkedEditingRanges {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        self.ranges.convert(uri, positions);
+    }
+}
+
+impl Convert for ManifestInfo {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        for target in &mut self.targets {
+            target.range.convert(uri, positions);
+        }
+    }
+}
+
+impl Convert for AnalyzeResult {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        let lists = [&mut self.outdated, &mut self.deprecated, &mut self.unknown];
+        for dependency in lists.into_iter().flatten() {
+            dependency.range.convert(uri, positions);
+        }
+    }
+}
+
+// Request parameters, only the positions that tools read are converted
+
+impl Convert for TextDocumentPositionParams {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        self.position.convert(uri, positions);
+    }
+}
+
+impl Convert for CodeActionParams {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        self.range.convert(uri, positions);
+        self.context.diagnostics.convert(uri, positions);
+    }
+}
+
+impl Convert for InlayHintParams {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        self.range.convert(uri, positions);
+    }
+}
+
+impl Convert for InlineValueParams {
+    fn convert(&mut self, uri: &Url, positions: Positions) {
+        self.range.convert(uri, positions);
+        self.context.stopped_location.convert(uri, positions);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::DocumentBuilder;
+
+    use super::*;
+
+    fn documents(uri: &Url, text: &str) -> Documents {
+        let documents = Documents::default();
+        let doc = DocumentBuilder::new()
+            .with_uri(uri.clone())
+            .with_text(text)
+            .build();
+        documents.insert(uri.clone(), doc);
+        documents
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let uri = Url::parse("file:///Cargo.toml").unwrap();
+        let documents = documents(&uri, "[dependencies]\n\"ö🦀\" = \"1\"\n");
+        let edit = TextEdit::new(
+            Range::new(Position::new(1, 7), Position::new(1, 12)),
+            String::from("\"2\""),
+        );
+
+        let client = Positions::new(&documents, Direction::ToClient).convert(&uri, edit.clone());
+        assert_eq!(
+            client.range,
+            Range::new(Position::new(1, 4), Position::new(1, 9))
+        );
+        let parser = Positions::new(&documents, Direction::ToParser).convert(&uri, client);
+        assert_eq!(parser, edit);
+    }
+
+    #[test]
+    fn test_unknown_documents() {
+        let uri = Url::parse("file:///Cargo.toml").unwrap();
+        let other = Url::parse("file:///other/Cargo.toml").unwrap();
+        let documents = documents(&uri, "\"ö\" = \"1\"\n");
+        let location = Location::new(
+            other.clone(),
+            Range::new(Position::new(0, 4), Position::new(0, 5)),
+        );
+
+        // Locations use their own uri, not the one of the request
+        let converted =
+            Positions::new(&documents, Direction::ToClient).convert(&uri, location.clone());
+        assert_eq!(converted, location);
+    }
+}
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{CodeAction, CodeActionOrCommand, Command, TextDocumentIdentifier, Url};
+
+use crate::clients::DependencyId;
+
+pub const REFRESH_DEPENDENCY_COMMAND: &str = "tooling/refreshDependency";
+
+/**
+    Arguments for the `tooling/refreshDependency` command, which clears cached
+    data for a single dependency, and refreshes diagnostics for its document.
+*/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshDependencyParams {
+    pub text_document: TextDocumentIdentifier,
+    pub dependency: DependencyId,
+}
+
+impl RefreshDependencyParams {
+    pub fn new(uri: &Url, dependency: DependencyId) -> Self {
+        Self {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            dependency,
+        }
+    }
+
+    /**
+        Creates a code action that runs the `tooling/refreshDependency` command.
+
+        The action has no kind, since it does not fit any of the
+        standard ones, so it should only be offered to clients that did
+        not ask for specific kinds of code actions, such as quick fixes.
+    */
+    pub fn into_code_action(self) -> CodeActionOrCommand {
+        let title = String::from("Refresh version info");
+        let arguments = serde_json::to_value(&self).ok().map(|value| vec![value]);
+        CodeActionOrCommand::CodeAction(CodeAction {
+            title: title.clone(),
+            command: Some(Command {
+                title,
+                command: REFRESH_DEPENDENCY_COMMAND.to_string(),
+                arguments,
+            }),
+            ..Default::default()
+        })
+    }
+}
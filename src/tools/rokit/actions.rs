@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::ops::Range as Span;
+
+use tower_lsp::lsp_types::*;
+
+use crate::parser::SimpleDependency;
+use crate::server::Document;
+
+/**
+    A tool entry in the `[tools]` table, as the whole lines that it spans,
+    including any comments directly above it and at the end of its last line.
+*/
+struct ToolLines<'a> {
+    tool: &'a SimpleDependency,
+    span: Span<usize>,
+}
+
+/**
+    Gets a code action that sorts all tools alphabetically by name, if the
+    given range is within the tools, and if they are not already sorted.
+*/
+pub fn get_rokit_sort_action(
+    doc: &Document,
+    tools: &[SimpleDependency],
+    range: Range,
+) -> Option<CodeActionOrCommand> {
+    let edit = get_rokit_sort_edit(doc, tools)?;
+    if range.end < edit.range.start || range.start > edit.range.end {
+        return None;
+    }
+
+    let mut change_map = HashMap::new();
+    change_map.insert(doc.uri().clone(), vec![edit]);
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: String::from("Sort tools alphabetically"),
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(WorkspaceEdit {
+            changes: Some(change_map),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }))
+}
+
+/**
+    Gets the edit that sorts all tools alphabetically by name, spanning
+    from the first tool to the last, if they are not already sorted.
+
+    Entries are moved as whole lines, together with their comments,
+    while any blank lines between entries are kept where they were.
+*/
+pub fn get_rokit_sort_edit(doc: &Document, tools: &[SimpleDependency]) -> Option<TextEdit> {
+    let text = doc.as_str();
+    let line_starts = line_starts(text);
+    let line_span = |line: u32| {
+        let start = line_starts
+            .get(line as usize)
+            .copied()
+            .unwrap_or(text.len());
+        let end = line_starts
+            .get(line as usize + 1)
+            .copied()
+            .unwrap_or(text.len());
+        start..end
+    };
+
+    // Tools are already in document order, see `SimpleDependency::sort_vec`
+    let mut entries = Vec::<ToolLines>::new();
+    for tool in tools {
+        let start_line = tool.name.range.start.line;
+        let end_line = tool.spec.range.end.line;
+        let previous_end = entries.last().map(|e| e.span.end);
+        if previous_end.is_some_and(|end| end > line_span(start_line).start) {
+            // Multiple tools on the same line, such as in an inline
+            // table, can't be moved around as whole lines
+            return None;
+        }
+
+        // Comments directly above a tool belong to it
+        let mut start = line_span(start_line).start;
+        for line in (0..start_line).rev() {
+            let span = line_span(line);
+            if previous_end.is_some_and(|end| span.start < end)
+                || !text[span.clone()].trim_start().starts_with('#')
+            {
+                break;
+            }
+            start = span.start;
+        }
+        entries.push(ToolLines {
+            tool,
+            span: start..line_span(end_line).end,
+        });
+    }
+
+    let first = entries.first()?.span.start;
+    let last = entries.last()?.span.end;
+
+    let mut sorted = entries.iter().collect::<Vec<_>>();
+    sorted.sort_by_cached_key(|e| {
+        let name = e.tool.name.unquoted();
+        (name.to_ascii_lowercase(), name.to_string())
+    });
+    if sorted.iter().zip(&entries).all(|(a, b)| a.span == b.span) {
+        return None;
+    }
+
+    // Entries are placed into the slots of the original entries, one by one,
+    // with the text between them left as it was, and every entry is ended
+    // with a newline, since the last line in the file may not have one
+    let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut new_text = String::new();
+    for (index, entry) in sorted.iter().enumerate() {
+        new_text.push_str(&text[entry.span.clone()]);
+        if !new_text.ends_with('\n') {
+            new_text.push_str(newline);
+        }
+        if let Some(next) = entries.get(index + 1) {
+            new_text.push_str(&text[entries[index].span.end..next.span.start]);
+        }
+    }
+    if !text[first..last].ends_with('\n') {
+        new_text.truncate(new_text.trim_end_matches(['\r', '\n']).len());
+    }
+
+    Some(doc.create_edit(doc.range_from_span(first..last), new_text))
+}
+
+fn line_starts(text: &str) -> Vec<usize> {
+    let mut starts = vec![0];
+    starts.extend(
+        text.match_indices('\n')
+            .map(|(index, _)| index + 1)
+            .filter(|&index| index < text.len()),
+    );
+    starts
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::testing::{MockFetcher, TestServer};
+    use crate::server::Config;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rokit_sort_tools() {
+        let server = TestServer::new(MockFetcher::new()).await;
+
+        let contents = "[tools]\n\
+            # Formatter\n\
+            stylua = \"JohnnyMorganz/StyLua@0.20.0\" # pinned\n\
+            \n\
+            rojo = \"rojo-rbx/rojo@7.4.1\"\n\
+            Lune = \"lune-org/lune@0.8.9\"";
+        let uri = server.open("rokit.toml", contents).await;
+        let actions = server.code_actions(&uri, Position::new(4, 0)).await;
+        let sort = actions
+            .iter()
+            .find(|a| a.title == "Sort tools alphabetically")
+            .expect("missing sort action");
+
+        // Comments move with their tools, and blank lines are kept in place
+        let edits = &sort.edit.as_ref().unwrap().changes.as_ref().unwrap()[&uri];
+        assert_eq!(
+            edits[0].new_text,
+            "Lune = \"lune-org/lune@0.8.9\"\n\
+            \n\
+            rojo = \"rojo-rbx/rojo@7.4.1\"\n\
+            # Formatter\n\
+            stylua = \"JohnnyMorganz/StyLua@0.20.0\" # pinned"
+        );
+        assert_eq!(
+            edits[0].range,
+            Range::new(Position::new(1, 0), Position::new(5, 28))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rokit_sort_tools_sorted() {
+        let server = TestServer::new(MockFetcher::new()).await;
+
+        let contents =
+            "[tools]\nrojo = \"rojo-rbx/rojo@7.4.1\"\nstylua = \"JohnnyMorganz/StyLua@0.20.0\"\n";
+        let uri = server.open("rokit.toml", contents).await;
+        let actions = server.code_actions(&uri, Position::new(1, 0)).await;
+        assert!(actions
+            .iter()
+            .all(|a| a.title != "Sort tools alphabetically"));
+    }
+
+    #[tokio::test]
+    async fn test_rokit_sort_on_save() {
+        let server = TestServer::new(MockFetcher::new()).await;
+
+        let contents = "[tools]\n\
+            # Formatter\n\
+            stylua = \"JohnnyMorganz/StyLua@0.20.0\"\n\
+            rojo = \"rojo-rbx/rojo@7.4.1\"\n";
+        let uri = server.open("rokit.toml", contents).await;
+
+        // Sorting on save is opt-in
+        let reason = TextDocumentSaveReason::MANUAL;
+        assert!(server.will_save(&uri, reason).await.is_empty());
+
+        let config = server.config().get();
+        server.config().set(Config {
+            sort_on_save: true,
+            ..config
+        });
+        let edits = server.will_save(&uri, reason).await;
+        assert_eq!(edits.len(), 1);
+        assert_eq!(
+            edits[0].new_text,
+            "rojo = \"rojo-rbx/rojo@7.4.1\"\n# Formatter\nstylua = \"JohnnyMorganz/StyLua@0.20.0\"\n"
+        );
+
+        // Saves that the editor makes on its own never reorder anything
+        let reason = TextDocumentSaveReason::AFTER_DELAY;
+        assert!(server.will_save(&uri, reason).await.is_empty());
+    }
+}
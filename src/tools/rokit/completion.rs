@@ -2,9 +2,12 @@ use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 
 use crate::clients::*;
-use crate::parser::SimpleDependency;
+use crate::parser::{Node, SimpleDependency};
 use crate::server::*;
 
+use super::super::shared::{
+    format_count, sort_alphabetically, sort_by_relevance, with_sort_texts, CompletionMetadata,
+};
 use super::constants::{top_rokit_tool_authors_prefixed, top_rokit_tool_names_prefixed};
 use super::Versioned;
 
@@ -30,11 +33,13 @@ pub async fn get_rokit_completions_spec_author(
             ..Default::default()
         })
         .collect::<Vec<_>>();
-    Ok(CompletionResponse::Array(items))
+    Ok(CompletionResponse::Array(sort_alphabetically(items)))
 }
 
 pub async fn get_rokit_completions_spec_name(
-    _clients: &Clients,
+    clients: &Clients,
+    config: &Config,
+    resolve: CompletionResolveSupport,
     document: &Document,
     dep: &SimpleDependency,
 ) -> Result<CompletionResponse> {
@@ -45,24 +50,104 @@ pub async fn get_rokit_completions_spec_name(
         return Ok(CompletionResponse::Array(Vec::new()));
     };
 
-    let items =
+    // Repositories of the typed owner are the best source of names, and a
+    // missing owner has no tools at all - if GitHub can not be reached,
+    // the well-known tools of the owner are still better than nothing
+    let well_known = || {
         top_rokit_tool_names_prefixed(author.unquoted(), name.unquoted(), MAXIMUM_TOOLS_SHOWN)
             .into_iter()
-            .map(|item| CompletionItem {
-                label: item.name.to_string(),
-                kind: Some(CompletionItemKind::ENUM_MEMBER),
-                text_edit: Some(CompletionTextEdit::Edit(
-                    document.create_edit(name.range, item.name.to_string()),
-                )),
-                commit_characters: Some(vec![String::from("@")]),
-                ..Default::default()
-            })
-            .collect::<Vec<_>>();
+            .map(|item| (item.name.to_string(), None))
+            .collect::<Vec<_>>()
+    };
+
+    // NOTE: Listing repositories takes a few requests per owner, and mostly
+    // gives stars to rank by, so it is skipped if that ranking is turned off
+    if !config.completion.sort_by_popularity {
+        let items = well_known().into_iter().map(|(tool_name, _)| {
+            name_completion(resolve, document, author, name, tool_name, None)
+        });
+        return Ok(CompletionResponse::Array(sort_alphabetically(items)));
+    }
+
+    let prefix = name.unquoted().to_ascii_lowercase();
+    let tools = match clients
+        .rokit()
+        .get_owner_repositories(author.unquoted())
+        .await
+    {
+        Ok(repos) => repos
+            .into_iter()
+            .filter(|repo| repo.name.to_ascii_lowercase().starts_with(&prefix))
+            .map(|repo| (repo.name, Some(repo.stargazers_count)))
+            .collect::<Vec<_>>(),
+        Err(e) if e.is_not_found_error() => Vec::new(),
+        Err(_) => well_known(),
+    };
+
+    let items = tools.into_iter().map(|(tool_name, tool_stars)| {
+        let completion = name_completion(resolve, document, author, name, tool_name, tool_stars);
+        (tool_stars.unwrap_or_default(), completion)
+    });
+
+    let mut items = sort_by_relevance(items);
+    items.truncate(MAXIMUM_TOOLS_SHOWN);
     Ok(CompletionResponse::Array(items))
 }
 
+fn name_completion(
+    resolve: CompletionResolveSupport,
+    document: &Document,
+    author: &Node<String>,
+    name: &Node<String>,
+    tool_name: String,
+    tool_stars: Option<u64>,
+) -> CompletionItem {
+    CompletionItem {
+        label: tool_name.clone(),
+        kind: Some(CompletionItemKind::ENUM_MEMBER),
+        text_edit: Some(CompletionTextEdit::Edit(
+            document.create_edit(name.range, tool_name.clone()),
+        )),
+        commit_characters: Some(vec![String::from("@")]),
+        detail: tool_stars.map(|s| format!("{} stars", format_count(s))),
+        data: resolve.documentation.then(|| {
+            CompletionMetadata::RokitTool {
+                author: author.unquoted().to_string(),
+                name: tool_name,
+            }
+            .into_data(document.uri())
+        }),
+        ..Default::default()
+    }
+}
+
+/**
+    Resolves the documentation for a tool name completion item,
+    which requires fetching the repository metrics from GitHub.
+*/
+pub async fn get_rokit_completion_resolve(
+    clients: &Clients,
+    mut item: CompletionItem,
+    author: &str,
+    name: &str,
+) -> Result<CompletionItem> {
+    let Ok(metrics) = clients.rokit().get_repository_metrics(author, name).await else {
+        return Ok(item);
+    };
+
+    if let Some(desc) = metrics.description {
+        item.documentation = Some(Documentation::MarkupContent(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: desc,
+        }));
+    }
+
+    Ok(item)
+}
+
 pub async fn get_rokit_completions_spec_version(
     clients: &Clients,
+    config: &Config,
     document: &Document,
     dep: &SimpleDependency,
 ) -> Result<CompletionResponse> {
@@ -72,12 +157,16 @@ pub async fn get_rokit_completions_spec_version(
     let Some(name) = dep.name.as_ref() else {
         return Ok(CompletionResponse::Array(Vec::new()));
     };
-    let Some(version) = dep.version.as_ref() else {
-        return Ok(CompletionResponse::Array(Vec::new()));
+
+    // Specs without any version, such as "owner/repo", get the version
+    // inserted right after the repo name, together with the `@` delimiter
+    let (version_range, version_text, prefix) = match dep.version.as_ref() {
+        Some(version) => (version.range, version.unquoted(), ""),
+        None => (Range::new(name.range.end, name.range.end), "", "@"),
     };
 
     let metadatas = match clients
-        .github
+        .rokit()
         .get_repository_releases(author.unquoted(), name.unquoted())
         .await
     {
@@ -85,22 +174,136 @@ pub async fn get_rokit_completions_spec_version(
         Ok(m) => m,
     };
 
-    let valid_vec = version
-        .unquoted()
-        .extract_completion_versions(metadatas.into_iter())
+    // Releases can be marked as prereleases on GitHub regardless of their
+    // tag, and those are left out unless prereleases are wanted
+    let releases = metadatas
+        .into_iter()
+        .filter(|release| config.rokit.include_prereleases || !release.prerelease);
+
+    let potential_versions = version_text
+        .extract_completion_versions(releases)
         .into_iter()
         .take(MAXIMUM_TOOLS_SHOWN)
-        .enumerate()
-        .map(|(index, potential_version)| CompletionItem {
+        .collect::<Vec<_>>();
+
+    // Rokit always requires a version, so instead of letting the user
+    // remove it, we offer an entry at the top that pins the latest stable
+    // version - the version range is a zero-length range right after
+    // the `@` when nothing has been typed yet, so only that slot is filled
+    let latest = potential_versions
+        .iter()
+        .find(|v| v.item_version.as_ref().is_some_and(|v| v.pre.is_empty()))
+        .map(|latest| CompletionItem {
+            label: format!("latest (pins {})", latest.item_version_raw),
+            kind: Some(CompletionItemKind::CONSTANT),
+            detail: Some(String::from("The latest stable release")),
+            filter_text: Some(latest.item_version_raw.to_string()),
+            preselect: Some(true),
+            text_edit: Some(CompletionTextEdit::Edit(document.create_edit(
+                version_range,
+                format!("{prefix}{}", latest.item_version_raw),
+            ))),
+            ..Default::default()
+        });
+
+    let valid_vec = potential_versions
+        .into_iter()
+        .map(|potential_version| CompletionItem {
             label: potential_version.item_version_raw.to_string(),
             kind: Some(CompletionItemKind::VALUE),
-            sort_text: Some(format!("{:0>5}", index)),
-            text_edit: Some(CompletionTextEdit::Edit(
-                document.create_edit(version.range, potential_version.item_version_raw),
-            )),
+            text_edit: Some(CompletionTextEdit::Edit(document.create_edit(
+                version_range,
+                format!("{prefix}{}", potential_version.item_version_raw),
+            ))),
             ..Default::default()
-        })
-        .collect::<Vec<_>>();
+        });
+
+    Ok(CompletionResponse::Array(with_sort_texts(
+        latest.into_iter().chain(valid_vec),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::testing::{cursor_fixture, edit_of, MockFetcher, TestServer};
+    use crate::server::{CompletionConfig, Config};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rokit_completion_owner_repositories() {
+        let repos = r#"[
+            {"name": "rojo", "full_name": "rojo-rbx/rojo", "description": null, "stargazers_count": 900},
+            {"name": "rbx-dom", "full_name": "rojo-rbx/rbx-dom", "description": null, "stargazers_count": 100},
+            {"name": "Rojo-vscode", "full_name": "rojo-rbx/Rojo-vscode", "description": null, "stargazers_count": 50}
+        ]"#;
+        let fetcher = MockFetcher::new().with_response("/users/rojo-rbx/repos", repos);
+        let server = TestServer::new(fetcher.clone()).await;
+
+        // Only repositories of the owner that start with the typed name are suggested
+        let (contents, pos) = cursor_fixture("[tools]\nrojo = \"rojo-rbx/ro$0@7.4.1\"\n");
+        let uri = server.open("rokit.toml", &contents).await;
+        let items = server.completion(&uri, pos).await;
+        let labels = items.iter().map(|i| i.label.as_str()).collect::<Vec<_>>();
+        assert_eq!(labels, vec!["rojo", "Rojo-vscode"]);
+        assert_eq!(items[0].detail.as_deref(), Some("900 stars"));
+
+        // A page that is not full means there are no more pages to fetch
+        let pages = fetcher
+            .requested()
+            .into_iter()
+            .filter(|url| url.contains("/users/rojo-rbx/repos"))
+            .collect::<Vec<_>>();
+        assert_eq!(pages.len(), 1);
+        assert!(pages[0].contains("page=1"));
+
+        // Owners that do not exist have no repositories at all
+        let (contents, pos) = cursor_fixture("[tools]\nrojo = \"nobody-here/ro$0@7.4.1\"\n");
+        let uri = server.open("missing/rokit.toml", &contents).await;
+        assert!(server.completion(&uri, pos).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rokit_completion_owner_repositories_unsorted() {
+        let fetcher = MockFetcher::new();
+        let server = TestServer::new(fetcher.clone()).await;
+        let config = server.config().get();
+        server.config().set(Config {
+            completion: CompletionConfig {
+                sort_by_popularity: false,
+                ..config.completion.clone()
+            },
+            ..config
+        });
+
+        // Without ranking by stars, only the well-known tools are suggested
+        let (contents, pos) = cursor_fixture("[tools]\nrojo = \"rojo-rbx/ro$0@7.4.1\"\n");
+        let uri = server.open("rokit.toml", &contents).await;
+        let items = server.completion(&uri, pos).await;
+        assert!(items.iter().any(|i| i.label == "rojo"));
+        assert!(items.iter().all(|i| i.detail.is_none()));
+        assert!(fetcher
+            .requested()
+            .iter()
+            .all(|url| !url.contains("/users/rojo-rbx/repos")));
+    }
+
+    #[tokio::test]
+    async fn test_rokit_completion_multi_byte() {
+        let repos = r#"[
+            {"name": "rojo", "full_name": "rojo-rbx/rojo", "description": null, "stargazers_count": 900}
+        ]"#;
+        let fetcher = MockFetcher::new().with_response("/users/rojo-rbx/repos", repos);
+        let server = TestServer::new(fetcher).await;
 
-    Ok(CompletionResponse::Array(valid_vec))
+        // The "ö" before the spec is two bytes, but only a single column for clients
+        let (contents, pos) = cursor_fixture("[tools]\n\"röjo\" = \"rojo-rbx/ro$0@7.4.1\"\n");
+        let uri = server.open("rokit.toml", &contents).await;
+        let items = server.completion(&uri, pos).await;
+        assert_eq!(items[0].label, "rojo");
+        assert_eq!(
+            edit_of(&items[0]).range,
+            Range::new(Position::new(1, 19), Position::new(1, 21))
+        );
+    }
 }
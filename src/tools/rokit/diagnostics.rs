@@ -1,13 +1,16 @@
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 
-use crate::parser::SimpleDependency;
+use crate::clients::github::models::RepositoryRelease;
+use crate::parser::{MisplacedTool, ParsedSpecFull, SimpleDependency};
+use crate::server::Config;
 use crate::util::Versioned;
 
 use super::super::shared::*;
+use super::platforms::{platform_assets, Platform};
 use super::{Clients, Document, LspUriExt};
 
-fn diag_source_for_doc(doc: &Document) -> String {
+pub fn diag_source_for_doc(doc: &Document) -> String {
     if doc
         .uri()
         .file_name()
@@ -21,9 +24,10 @@ fn diag_source_for_doc(doc: &Document) -> String {
 
 pub async fn get_rokit_diagnostics(
     clients: &Clients,
+    config: &Config,
     doc: &Document,
     tool: &SimpleDependency,
-) -> Result<Vec<Diagnostic>> {
+) -> Result<DependencyDiagnostics> {
     let parsed = tool.parsed_spec();
 
     // Check for any missing fields
@@ -34,11 +38,23 @@ pub async fn get_rokit_diagnostics(
         .as_ref()
         .is_none_or(|v| v.unquoted().is_empty());
     let missing_diag = if missing_author {
-        Some("Missing tool author")
+        Some(String::from("Missing tool author"))
     } else if missing_name {
-        Some("Missing tool name")
+        Some(String::from("Missing tool name"))
+    } else if parsed.version.is_none() {
+        // Older aftman manifests may have specs such as "owner/repo",
+        // which are valid to parse, but never resolve to any release
+        Some(format!(
+            "No version specified for `{}/{}`, expected `{0}/{1}@version`",
+            parsed.author.unquoted(),
+            parsed
+                .name
+                .as_ref()
+                .map(|n| n.unquoted())
+                .unwrap_or_default(),
+        ))
     } else if missing_version {
-        Some("Missing tool version")
+        Some(String::from("Missing tool version"))
     } else {
         None
     };
@@ -48,24 +64,25 @@ pub async fn get_rokit_diagnostics(
         return Ok(vec![Diagnostic {
             source: Some(diag_source_for_doc(doc)),
             range: tool.spec.range,
-            message: diag.to_string(),
+            message: diag,
             severity: Some(DiagnosticSeverity::WARNING), // Most likely during typing, don't emit a hard error
-            ..Default::default()
-        }]);
+            ..DiagnosticCode::MissingField.diagnostic()
+        }]
+        .into());
     }
 
     // Fetch releases and make sure there is at least one
     let parsed = parsed.into_full().expect("nothing was missing");
     let parsed_version = parsed.version.unquoted().trim_start_matches('v');
     let releases = match clients
-        .github
+        .rokit()
         .get_repository_releases(parsed.author.unquoted(), parsed.name.unquoted())
         .await
     {
         Ok(v) => v,
         Err(e) => {
             if e.is_not_found_error() {
-                return Ok(vec![Diagnostic {
+                let diag = Diagnostic {
                     source: Some(diag_source_for_doc(doc)),
                     range: parsed.range(),
                     message: format!(
@@ -74,10 +91,27 @@ pub async fn get_rokit_diagnostics(
                         parsed.name.unquoted(),
                     ),
                     severity: Some(DiagnosticSeverity::ERROR),
-                    ..Default::default()
-                }]);
+                    ..DiagnosticCode::UnknownPackage.diagnostic()
+                };
+
+                // Suggest other repositories by the same author, if any are close
+                let Ok(repos) = clients
+                    .rokit()
+                    .get_owner_repositories(parsed.author.unquoted())
+                    .await
+                else {
+                    return Ok(vec![diag].into());
+                };
+                return Ok(vec![with_suggestions(
+                    diag,
+                    doc.uri(),
+                    parsed.name.range,
+                    parsed.name.unquoted(),
+                    repos.into_iter().map(|repo| repo.name),
+                )]
+                .into());
             } else {
-                return Ok(Vec::new());
+                return Ok(DependencyDiagnostics::incomplete(Vec::new()));
             }
         }
     };
@@ -91,17 +125,68 @@ pub async fn get_rokit_diagnostics(
                 parsed.name.unquoted(),
             ),
             severity: Some(DiagnosticSeverity::ERROR),
-            ..Default::default()
-        }]);
+            ..DiagnosticCode::UnknownVersion.diagnostic()
+        }]
+        .into());
+    }
+
+    // Tags such as `latest` or `main` may point to different releases over time,
+    // so suggest pinning to the release that such a tag currently resolves to
+    if parsed_version.parse_version().is_err() {
+        if !config.diagnostics.moving_tags {
+            return Ok(Vec::new().into());
+        }
+
+        let mut diag = Diagnostic {
+            source: Some(diag_source_for_doc(doc)),
+            range: parsed.version.range,
+            message: format!(
+                "`{parsed_version}` is not a version, and tools pinned to it are not reproducible"
+            ),
+            severity: Some(DiagnosticSeverity::WARNING),
+            ..DiagnosticCode::MovingTag.diagnostic()
+        };
+
+        // A release with the same tag is what the tag resolves to, otherwise
+        // the tag is most likely a branch or `latest`, so use the latest release
+        let tagged = releases.iter().find(|release| {
+            release.tag_name.eq_ignore_ascii_case(parsed_version)
+                && release
+                    .name
+                    .as_deref()
+                    .is_some_and(|n| n.trim_start_matches('v').parse_version().is_ok())
+        });
+        let pinned = match tagged {
+            Some(release) => release.name.clone(),
+            None => releases
+                .iter()
+                .filter(|release| !release.draft && !release.prerelease)
+                .filter_map(|release| Some((release, release.parse_version().ok()?)))
+                .max_by(|(_, a), (_, b)| a.cmp(b))
+                .map(|(release, _)| release.tag_name.clone()),
+        };
+        if let Some(pinned) = pinned {
+            let pinned = pinned.trim_start_matches('v').to_string();
+            let metadata = CodeActionMetadata::PinVersion {
+                edit_range: parsed.version.range,
+                source_uri: doc.uri().clone(),
+                source_text: parsed.version.quoted().to_string(),
+                version_current: parsed_version.to_string(),
+                version_pinned: pinned,
+            };
+            diag.data = Some(
+                ResolveContext {
+                    uri: doc.uri().clone(),
+                    value: metadata,
+                }
+                .into(),
+            );
+        }
+        return Ok(vec![diag].into());
     }
 
     // Check if the exact version specified exists as a release
-    if !releases.iter().any(|release| {
-        release
-            .tag_name
-            .trim_start_matches('v')
-            .eq_ignore_ascii_case(parsed_version)
-    }) {
+    let Some(release) = find_release(&releases, parsed_version) else {
         return Ok(vec![Diagnostic {
             source: Some(diag_source_for_doc(doc)),
             range: parsed.range(),
@@ -111,14 +196,23 @@ pub async fn get_rokit_diagnostics(
                 parsed.name.unquoted(),
             ),
             severity: Some(DiagnosticSeverity::ERROR),
-            ..Default::default()
-        }]);
-    }
+            ..DiagnosticCode::UnknownVersion.diagnostic()
+        }]
+        .into());
+    };
+    let mut diagnostics = Vec::new();
+    diagnostics.extend(get_rokit_diagnostics_platforms(doc, &parsed, release));
+    let pinned_tag = release.tag_name.clone();
 
     // Everything is OK - but we may be able to suggest new versions...
-    // ... try to find the latest non-prerelease version
-    let Some(latest_version) = parsed_version.extract_latest_version(releases) else {
-        return Ok(Vec::new());
+    // ... try to find the latest non-prerelease version, where releases that
+    // are marked as prereleases on GitHub count as prereleases too, unless
+    // the tool is already pinned to one of them
+    let candidates = releases.into_iter().filter(|release| {
+        config.rokit.include_prereleases || !release.prerelease || release.tag_name == pinned_tag
+    });
+    let Some(latest_version) = parsed_version.extract_latest_version(candidates) else {
+        return Ok(diagnostics.into());
     };
 
     if !latest_version.is_exactly_compatible {
@@ -130,9 +224,10 @@ pub async fn get_rokit_diagnostics(
             source_text: parsed.version.quoted().to_string(),
             version_current: parsed_version.to_string(),
             version_latest: latest_version_string.to_string(),
+            package_manager: None,
         };
 
-        return Ok(vec![Diagnostic {
+        diagnostics.push(Diagnostic {
             source: Some(diag_source_for_doc(doc)),
             range: parsed.range(),
             message: format!(
@@ -149,9 +244,119 @@ pub async fn get_rokit_diagnostics(
                 }
                 .into(),
             ),
-            ..Default::default()
-        }]);
+            ..DiagnosticCode::Outdated.diagnostic()
+        });
     }
 
-    Ok(Vec::new())
+    Ok(diagnostics.into())
+}
+
+/**
+    Finds the release for the given version, ignoring any `v` prefix in its tag.
+*/
+pub fn find_release<'a>(
+    releases: &'a [RepositoryRelease],
+    version: &str,
+) -> Option<&'a RepositoryRelease> {
+    releases.iter().find(|release| {
+        release
+            .tag_name
+            .trim_start_matches('v')
+            .eq_ignore_ascii_case(version.trim_start_matches('v'))
+    })
+}
+
+/**
+    Checks which of the most common platforms a release of a tool has binaries
+    for, flagging any platforms that it can't be installed on, which is a warning
+    if the current platform is one of them, and otherwise only informational.
+
+    Releases where none of the assets could be classified are not flagged,
+    since their binaries may just be named in some unusual way.
+*/
+fn get_rokit_diagnostics_platforms(
+    doc: &Document,
+    parsed: &ParsedSpecFull,
+    release: &RepositoryRelease,
+) -> Option<Diagnostic> {
+    let tool = format!("{}/{}", parsed.author.unquoted(), parsed.name.unquoted());
+    let version = parsed.version.unquoted().trim_start_matches('v');
+    if release.assets.is_empty() {
+        return Some(Diagnostic {
+            source: Some(diag_source_for_doc(doc)),
+            range: parsed.version.range,
+            message: format!(
+                "Version `{version}` of `{tool}` has no release assets, and can not be installed"
+            ),
+            severity: Some(DiagnosticSeverity::WARNING),
+            ..DiagnosticCode::MissingAssets.diagnostic()
+        });
+    }
+
+    let available = platform_assets(&release.assets);
+    if available.is_empty() {
+        return None;
+    }
+    let missing = Platform::COMMON
+        .into_iter()
+        .filter(|platform| !available.contains_key(platform))
+        .collect::<Vec<_>>();
+    if missing.is_empty() {
+        return None;
+    }
+
+    let is_current_missing = Platform::current().is_some_and(|current| missing.contains(&current));
+    let severity = if is_current_missing {
+        DiagnosticSeverity::WARNING
+    } else {
+        DiagnosticSeverity::INFORMATION
+    };
+    Some(Diagnostic {
+        source: Some(diag_source_for_doc(doc)),
+        range: parsed.version.range,
+        message: format!(
+            "Version `{version}` of `{tool}` has no binaries for {}.\
+            \nBinaries are available for {}",
+            platform_names(missing),
+            platform_names(available.into_keys()),
+        ),
+        severity: Some(severity),
+        ..DiagnosticCode::MissingPlatforms.diagnostic()
+    })
+}
+
+fn platform_names(platforms: impl IntoIterator<Item = Platform>) -> String {
+    platforms
+        .into_iter()
+        .map(Platform::name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn get_rokit_diagnostics_misplaced(doc: &Document, tools: &[MisplacedTool]) -> Vec<Diagnostic> {
+    tools
+        .iter()
+        .map(|tool| {
+            let message = match tool.table.as_ref() {
+                Some(table) => format!(
+                    "The tool `{}` is in the `[{}]` table and will not be recognized.\
+                    \nTools must be placed in the `[tools]` table",
+                    tool.name.unquoted(),
+                    table.unquoted(),
+                ),
+                None => format!(
+                    "The tool `{}` is at the top level and will not be recognized.\
+                    \nTools must be placed in the `[tools]` table",
+                    tool.name.unquoted(),
+                ),
+            };
+            Diagnostic {
+                source: Some(diag_source_for_doc(doc)),
+                range: tool.name.range,
+                message,
+                severity: Some(DiagnosticSeverity::WARNING),
+                ..DiagnosticCode::MisplacedTool.diagnostic()
+            }
+        })
+        .collect()
 }
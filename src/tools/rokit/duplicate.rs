@@ -0,0 +1,263 @@
+use tokio::fs;
+use tower_lsp::lsp_types::*;
+
+use crate::parser::{query_rokit_toml_dependencies, SimpleDependency, TreeSitterDocument};
+use crate::server::{Document, Documents};
+
+use super::super::shared::*;
+use super::diagnostics::diag_source_for_doc;
+use super::LspUriExt;
+
+const AFTMAN_MANIFEST: &str = "aftman.toml";
+const ROKIT_MANIFEST: &str = "rokit.toml";
+
+/**
+    The manifest of the other tool in the same directory, meaning
+    a `rokit.toml` next to an `aftman.toml`, or the other way around.
+*/
+pub struct SiblingManifest {
+    pub uri: Url,
+    pub doc: TreeSitterDocument,
+}
+
+/**
+    Finds the sibling manifest of the given manifest, if one exists, preferring
+    the contents of an open document, which may not have been saved yet.
+*/
+pub async fn find_sibling_manifest(
+    doc: &Document,
+    documents: &Documents,
+) -> Option<SiblingManifest> {
+    let file_name = doc.uri().file_name()?;
+    let sibling_name = if file_name.eq_ignore_ascii_case(AFTMAN_MANIFEST) {
+        ROKIT_MANIFEST
+    } else if file_name.eq_ignore_ascii_case(ROKIT_MANIFEST) {
+        AFTMAN_MANIFEST
+    } else {
+        return None;
+    };
+
+    // NOTE: Documents that only exist in memory never have
+    // any siblings, and this is never created for them
+    let uri = doc.uri().with_file_name(sibling_name)?;
+    let opened = documents.get(&uri).map(|open| open.inner().clone());
+    if let Some(doc) = opened {
+        return Some(SiblingManifest { doc, uri });
+    }
+    let path = uri.to_file_path().ok()?;
+    let contents = fs::read_to_string(&path).await.ok()?;
+    Some(SiblingManifest {
+        doc: TreeSitterDocument::new_file(&path, contents)?,
+        uri,
+    })
+}
+
+/**
+    Creates an informational diagnostic for a manifest that has a sibling manifest,
+    which is usually a migration from Aftman to Rokit that was never finished.
+
+    If `aftman.toml` has any tools that are not in `rokit.toml`, a quick fix
+    adds them to `rokit.toml`, after which `aftman.toml` can be removed.
+*/
+pub fn get_rokit_diagnostics_duplicate_manifest(
+    doc: &Document,
+    sibling: &SiblingManifest,
+) -> Diagnostic {
+    let (aftman, rokit, rokit_uri) = if sibling.uri.file_name().as_deref() == Some(ROKIT_MANIFEST) {
+        (doc.inner(), &sibling.doc, &sibling.uri)
+    } else {
+        (&sibling.doc, doc.inner(), doc.uri())
+    };
+
+    let aftman_tools = query_rokit_toml_dependencies(aftman);
+    let data = missing_tools_edit(rokit, &aftman_tools).map(|(edit_range, new_text, count)| {
+        let metadata = CodeActionMetadata::AddMissingTools {
+            edit_range,
+            source_uri: rokit_uri.clone(),
+            new_text,
+            count,
+        };
+        ResolveContext {
+            uri: doc.uri().clone(),
+            value: metadata,
+        }
+        .into()
+    });
+
+    let first_line = doc.as_str().lines().next().unwrap_or_default();
+    Diagnostic {
+        source: Some(diag_source_for_doc(doc)),
+        range: doc.range_from_span(0..first_line.len()),
+        message: format!(
+            "Both `{AFTMAN_MANIFEST}` and `{ROKIT_MANIFEST}` exist in this directory, \
+            which is usually an unfinished migration to Rokit.\
+            \nOnce all tools are in `{ROKIT_MANIFEST}`, `{AFTMAN_MANIFEST}` can be removed"
+        ),
+        severity: Some(DiagnosticSeverity::INFORMATION),
+        related_information: Some(vec![DiagnosticRelatedInformation {
+            location: Location {
+                uri: sibling.uri.clone(),
+                range: Range::default(),
+            },
+            message: String::from("The other manifest"),
+        }]),
+        data,
+        ..DiagnosticCode::DuplicateManifest.diagnostic()
+    }
+}
+
+/**
+    Creates the insertion of all Aftman tools that are missing from a Rokit manifest,
+    returning its range, its text, and how many tools it adds, if any are missing.
+
+    Tools are added after the last tool in the `[tools]` table, or right
+    after the table header if it has no tools, or in a new table at
+    the end of the manifest if there is no `[tools]` table at all.
+*/
+fn missing_tools_edit(
+    rokit: &TreeSitterDocument,
+    aftman_tools: &[SimpleDependency],
+) -> Option<(Range, String, usize)> {
+    let rokit_tools = query_rokit_toml_dependencies(rokit);
+    let missing = aftman_tools
+        .iter()
+        .filter(|tool| {
+            rokit_tools
+                .iter()
+                .all(|t| t.name.unquoted() != tool.name.unquoted())
+        })
+        .map(|tool| format!("{} = {}", tool.name.quoted(), tool.spec.quoted()))
+        .collect::<Vec<_>>();
+    if missing.is_empty() {
+        return None;
+    }
+
+    let contents = rokit.contents();
+    let lines = contents.split('\n').collect::<Vec<_>>();
+    let line_end = |line: usize| Position {
+        line: line as u32,
+        character: lines[line].trim_end_matches('\r').len() as u32,
+    };
+
+    let (position, new_text) = if let Some(last) = rokit_tools.last() {
+        (last.spec.range.end, format!("\n{}", missing.join("\n")))
+    } else if let Some(header) = lines.iter().position(|l| l.trim() == "[tools]") {
+        (line_end(header), format!("\n{}", missing.join("\n")))
+    } else {
+        let separator = match contents {
+            "" => "",
+            c if c.ends_with('\n') => "\n",
+            _ => "\n\n",
+        };
+        (
+            line_end(lines.len() - 1),
+            format!("{separator}[tools]\n{}\n", missing.join("\n")),
+        )
+    };
+    Some((Range::new(position, position), new_text, missing.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::server::testing::{MockFetcher, TestServer};
+    use crate::util::FixtureDir;
+
+    use super::*;
+
+    fn edit(rokit: &str, aftman: &str) -> Option<(Range, String, usize)> {
+        let rokit = TreeSitterDocument::new_file(Path::new(ROKIT_MANIFEST), rokit).unwrap();
+        let aftman = TreeSitterDocument::new_file(Path::new(AFTMAN_MANIFEST), aftman).unwrap();
+        missing_tools_edit(&rokit, &query_rokit_toml_dependencies(&aftman))
+    }
+
+    fn at(line: u32, character: u32) -> Range {
+        let position = Position::new(line, character);
+        Range::new(position, position)
+    }
+
+    const AFTMAN: &str = "[tools]\n\
+        rojo = \"rojo-rbx/rojo@7.4.1\"\n\
+        stylua = \"JohnnyMorganz/StyLua@0.20.0\"\n";
+
+    #[test]
+    fn test_missing_tools_after_last_tool() {
+        let rokit = "[tools]\nrojo = \"rojo-rbx/rojo@7.4.4\"\n";
+        assert_eq!(
+            edit(rokit, AFTMAN),
+            Some((
+                at(1, 28),
+                String::from("\nstylua = \"JohnnyMorganz/StyLua@0.20.0\""),
+                1
+            ))
+        );
+    }
+
+    #[test]
+    fn test_missing_tools_empty_table() {
+        assert_eq!(
+            edit("# Tools\n[tools]\n", AFTMAN),
+            Some((
+                at(1, 7),
+                String::from(
+                    "\nrojo = \"rojo-rbx/rojo@7.4.1\"\
+                    \nstylua = \"JohnnyMorganz/StyLua@0.20.0\""
+                ),
+                2
+            ))
+        );
+    }
+
+    #[test]
+    fn test_missing_tools_no_table() {
+        let (range, text, count) = edit("", AFTMAN).unwrap();
+        assert_eq!(range, at(0, 0));
+        assert!(text.starts_with("[tools]\nrojo = "));
+        assert_eq!(count, 2);
+
+        let (range, text, _) = edit("# Tools", AFTMAN).unwrap();
+        assert_eq!(range, at(0, 7));
+        assert!(text.starts_with("\n\n[tools]\n"));
+    }
+
+    #[test]
+    fn test_missing_tools_none_missing() {
+        let rokit =
+            "[tools]\nstylua = \"JohnnyMorganz/StyLua@2.0.0\"\nrojo = \"rojo-rbx/rojo@7.4.4\"\n";
+        assert_eq!(edit(rokit, AFTMAN), None);
+    }
+
+    #[tokio::test]
+    async fn test_rokit_diagnostics_duplicate_manifest() {
+        let server = TestServer::new(MockFetcher::new()).await;
+        let duplicate = |diags: Vec<Diagnostic>| {
+            diags.into_iter().find(|d| {
+                d.code == Some(NumberOrString::String(String::from("duplicate-manifest")))
+            })
+        };
+        let aftman = "[tools]\nrojo = \"rojo-rbx/rojo@7.4.1\"\n";
+
+        // A single manifest is never a duplicate
+        let base = FixtureDir::new("rokit-single-manifest");
+        let uri = Url::from_file_path(base.join("aftman.toml")).unwrap();
+        let uri = server.open_uri(uri, aftman).await;
+        assert!(duplicate(server.diagnostics(&uri).await).is_none());
+
+        // Both manifests get an informational diagnostic, and the
+        // quick fix always adds the missing tools to rokit.toml
+        let base = FixtureDir::new("rokit-duplicate-manifest");
+        let rokit_uri = Url::from_file_path(base.join("rokit.toml")).unwrap();
+        let rokit_uri = server.open_uri(rokit_uri, "[tools]\n").await;
+        let aftman_uri = Url::from_file_path(base.join("aftman.toml")).unwrap();
+        let aftman_uri = server.open_uri(aftman_uri, aftman).await;
+        for uri in [&aftman_uri, &rokit_uri] {
+            let diag = duplicate(server.diagnostics(uri).await).expect("missing diagnostic");
+            assert_eq!(diag.severity, Some(DiagnosticSeverity::INFORMATION));
+            let value = &diag.data.as_ref().unwrap()["value"];
+            assert_eq!(value["kind"], "AddMissingTools");
+            assert_eq!(value["source_uri"], rokit_uri.as_str());
+            assert_eq!(value["new_text"], "\nrojo = \"rojo-rbx/rojo@7.4.1\"");
+        }
+    }
+}
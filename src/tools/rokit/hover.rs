@@ -2,9 +2,17 @@ use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tracing::trace;
 
-use crate::{parser::SimpleDependency, tools::MarkdownBuilder};
+use crate::{
+    parser::{ParsedSpecFull, SimpleDependency},
+    tools::HoverSections,
+    util::Versioned,
+};
 
-use super::{Clients, Document};
+use super::platforms::{platform_assets, Platform};
+use super::{find_release, Clients, Document};
+
+const SERVER_VERSION: &str = env!("CARGO_PKG_VERSION");
+const SERVER_REPOSITORY: &str = env!("CARGO_PKG_REPOSITORY");
 
 pub async fn get_rokit_hover(
     clients: &Clients,
@@ -21,49 +29,125 @@ pub async fn get_rokit_hover(
         spec.name.unquoted(),
         spec.version.unquoted()
     );
-    let mut md = MarkdownBuilder::new();
-    md.h2(spec.name.unquoted());
-    md.version(spec.version.unquoted());
+    let mut hover = HoverSections::new();
+    hover.section(|md| md.h2(spec.name.unquoted()));
+    hover.section(|md| md.version(spec.version.unquoted()));
 
     // Try to fetch additional information from the index - description, links
     trace!("Fetching repository metrics from GitHub");
     if let Ok(repository) = clients
-        .github
+        .rokit()
         .get_repository_metrics(spec.author.unquoted(), spec.name.unquoted())
         .await
     {
         // Add description, if available
         if let Some(desc) = &repository.description {
-            md.br();
-            md.p(desc);
+            hover.section(|md| md.p(desc));
         }
     }
 
+    // Add a short timeline of the most recent releases, these
+    // are usually already cached from fetching diagnostics
+    let releases = clients
+        .rokit()
+        .get_repository_releases(spec.author.unquoted(), spec.name.unquoted())
+        .await
+        .ok();
+    if let Some(releases) = &releases {
+        // Show which platforms the used release has binaries for, if any are known
+        if let Some(release) = find_release(releases, spec.version.unquoted()) {
+            let platforms = platform_assets(&release.assets)
+                .into_keys()
+                .map(Platform::name)
+                .collect::<Vec<_>>();
+            if !platforms.is_empty() {
+                hover.section(|md| md.p(format!("Binaries for {}", platforms.join(", "))));
+            }
+        }
+
+        let mut published = releases
+            .iter()
+            .filter(|r| !r.draft && r.published_at.is_some())
+            .collect::<Vec<_>>();
+        published.sort_by(|a, b| b.published_at.cmp(&a.published_at));
+        hover.section(|md| {
+            md.timeline(
+                published
+                    .iter()
+                    .map(|r| (&r.tag_name, r.published_at.as_deref().unwrap_or_default())),
+            );
+        });
+    }
+
+    // Add a note if the tool is this language server, and if it is outdated
+    if is_this_language_server(&spec) {
+        hover.section(|md| {
+            md.p(format!(
+                "This is the language server providing this hover, running version `{SERVER_VERSION}`"
+            ));
+            if let Some(releases) = releases {
+                if let Some(latest) = SERVER_VERSION.extract_latest_version(releases) {
+                    if latest.item_version > latest.this_version {
+                        md.p(format!(
+                            "A newer version `{}` is available, see the [release notes]({SERVER_REPOSITORY}/releases/tag/{})",
+                            latest.item_version, latest.item.tag_name
+                        ));
+                    }
+                }
+            }
+        });
+    }
+
     // Add link to the repository and latest release
-    md.br();
-    md.h3("Links");
-    md.a(
-        "Repository",
-        format!(
-            "https://github.com/{}/{}",
-            spec.author.unquoted(),
-            spec.name.unquoted()
-        ),
-    );
-    md.a(
-        "Latest Release",
-        format!(
-            "https://github.com/{}/{}/releases/latest",
-            spec.author.unquoted(),
-            spec.name.unquoted()
-        ),
-    );
+    let web_url = clients.rokit().web_url();
+    hover.section(|md| {
+        md.h3("Links");
+        md.a(
+            "Repository",
+            format!(
+                "{web_url}/{}/{}",
+                spec.author.unquoted(),
+                spec.name.unquoted()
+            ),
+        );
+        md.a(
+            "Latest Release",
+            format!(
+                "{web_url}/{}/{}/releases/latest",
+                spec.author.unquoted(),
+                spec.name.unquoted()
+            ),
+        );
+    });
+
+    Ok(Some(hover.into_hover(tool.range())))
+}
+
+fn is_this_language_server(spec: &ParsedSpecFull) -> bool {
+    SERVER_REPOSITORY
+        .trim_start_matches("https://github.com/")
+        .split_once('/')
+        .is_some_and(|(author, name)| {
+            author.eq_ignore_ascii_case(spec.author.unquoted())
+                && name.eq_ignore_ascii_case(spec.name.unquoted())
+        })
+}
 
-    Ok(Some(Hover {
-        range: Some(tool.range()),
-        contents: HoverContents::Markup(MarkupContent {
-            kind: MarkupKind::Markdown,
-            value: md.build(),
-        }),
-    }))
+#[cfg(test)]
+mod tests {
+    use crate::server::testing::{cursor_fixture, MockFetcher, TestServer};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rokit_hover_version() {
+        let server = TestServer::new(MockFetcher::new()).await;
+        let (contents, pos) = cursor_fixture("[tools]\nrojo = \"rojo-rbx/ro$0jo@7.4.1\"\n");
+        let uri = server.open("rokit.toml", &contents).await;
+
+        // Tools are pinned to exact versions, which are only shown once
+        let hover = server.hover(&uri, pos).await.expect("missing hover");
+        assert!(hover.contains("Version **7.4.1**"));
+        assert!(!hover.contains("`7.4.1`"));
+    }
 }
@@ -0,0 +1,87 @@
+use tower_lsp::lsp_types::*;
+
+use crate::parser::SimpleDependency;
+
+/**
+    Gets the ranges of the alias of a tool and the repository name in its
+    spec, if the position is within either of them and they currently match,
+    such as `rojo` in `rojo = "rojo-rbx/rojo@7.4.1"`, so that editing
+    one of them also edits the other and they stay in sync.
+*/
+pub fn get_rokit_linked_editing_ranges(
+    tools: &[SimpleDependency],
+    pos: Position,
+) -> Option<LinkedEditingRanges> {
+    let tool = SimpleDependency::find_at_pos(tools, pos)?;
+    let name = tool.parsed_spec().name?;
+    if name.unquoted().is_empty() || name.unquoted() != tool.name.unquoted() {
+        return None;
+    }
+
+    let alias_range = tool.name.unquoted_range();
+    if !name.contains(pos) && !(alias_range.start <= pos && pos <= alias_range.end) {
+        return None;
+    }
+
+    Some(LinkedEditingRanges {
+        ranges: vec![alias_range, name.range],
+        word_pattern: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::LanguageServer;
+
+    use crate::server::testing::{MockFetcher, TestServer};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rokit_linked_editing_range() {
+        let server = TestServer::new(MockFetcher::new()).await;
+        let contents = "[tools]\n\
+            rojo = \"rojo-rbx/rojo@7.4.1\"\n\
+            \"wally\" = \"UpliftGames/wally@0.3.2\"\n\
+            lune = \"lune-org/lune-cli@0.8.9\"\n";
+        let uri = server.open("rokit.toml", contents).await;
+        let server = &server;
+        let linked = |position: Position| {
+            let params = LinkedEditingRangeParams {
+                text_document_position_params: TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier { uri: uri.clone() },
+                    position,
+                },
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            };
+            async move {
+                server
+                    .server()
+                    .linked_editing_range(params)
+                    .await
+                    .expect("linked editing range failed")
+                    .map(|linked| linked.ranges)
+            }
+        };
+        let range = |line: u32, start: u32, end: u32| {
+            Range::new(Position::new(line, start), Position::new(line, end))
+        };
+
+        // Both the alias and the repository name are linked, from either one
+        let expected = Some(vec![range(1, 0, 4), range(1, 17, 21)]);
+        assert_eq!(linked(Position::new(1, 2)).await, expected);
+        assert_eq!(linked(Position::new(1, 19)).await, expected);
+
+        // Quoted aliases are linked without their quotes
+        assert_eq!(
+            linked(Position::new(2, 3)).await,
+            Some(vec![range(2, 1, 6), range(2, 23, 28)])
+        );
+
+        // The author and version are never linked, and
+        // neither are aliases that differ from the name
+        assert_eq!(linked(Position::new(1, 10)).await, None);
+        assert_eq!(linked(Position::new(1, 24)).await, None);
+        assert_eq!(linked(Position::new(3, 2)).await, None);
+    }
+}
@@ -1,54 +1,119 @@
-use futures::future::try_join_all;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::Client;
 use tracing::debug;
 
-use crate::parser::query_rokit_toml_dependencies;
-use crate::parser::SimpleDependency;
+use crate::parser::{
+    query_rokit_toml_dependencies, query_rokit_toml_has_tools_table,
+    query_rokit_toml_misplaced_tools,
+};
+use crate::parser::{DependencyKind, SimpleDependency};
 use crate::server::*;
 use crate::util::*;
 
 use super::*;
 
+mod actions;
 mod completion;
 mod constants;
 mod diagnostics;
+mod duplicate;
 mod hover;
+mod linked;
+mod platforms;
 
+use actions::*;
 use completion::*;
 use diagnostics::*;
+use duplicate::*;
 use hover::*;
+use linked::*;
 
 #[derive(Debug, Clone)]
 pub struct Rokit {
     _client: Client,
     clients: Clients,
+    config: SharedConfig,
     documents: Documents,
+    limited: LimitedDocuments,
 }
 
 impl Rokit {
-    pub(super) fn new(client: Client, clients: Clients, documents: Documents) -> Self {
+    pub(super) fn new(
+        client: Client,
+        clients: Clients,
+        config: SharedConfig,
+        documents: Documents,
+        limited: LimitedDocuments,
+    ) -> Self {
         Self {
             _client: client,
             clients,
+            config,
             documents,
+            limited,
         }
     }
 
     fn get_document(&self, uri: &Url) -> Option<Document> {
-        if uri.file_name().as_deref().is_some_and(|f| {
-            f.eq_ignore_ascii_case("rokit.toml") || f.eq_ignore_ascii_case("aftman.toml")
-        }) {
-            self.documents.get(uri).map(|r| r.clone())
-        } else {
-            None
+        self.documents
+            .get(uri)
+            .filter(|doc| {
+                doc.is_for_tool(ToolName::Rokit, &["rokit.toml"])
+                    || doc.is_for_tool(ToolName::Aftman, &["aftman.toml"])
+            })
+            .map(|r| r.clone())
+    }
+
+    /**
+        Completes the author, name, or version of a tool,
+        depending on which part of its spec the cursor is in.
+    */
+    async fn complete_spec(
+        &self,
+        config: &Config,
+        doc: &Document,
+        found: &SimpleDependency,
+        pos: Position,
+    ) -> Result<CompletionResponse> {
+        // Check what we're completing, see `range_contains` for the boundaries
+        let parsed = found.parsed_spec();
+        if parsed.version.as_ref().is_some_and(|v| v.contains(pos)) {
+            debug!("Completing version: {found:?}");
+            return get_rokit_completions_spec_version(&self.clients, config, doc, found).await;
+        } else if parsed.name.is_some_and(|n| n.contains(pos)) {
+            debug!("Completing name: {found:?}");
+            let resolve = self.config.completion_resolve();
+            return get_rokit_completions_spec_name(&self.clients, config, resolve, doc, found)
+                .await;
+        } else if parsed.version.is_none()
+            && parsed
+                .name
+                .as_ref()
+                .is_some_and(|n| !n.unquoted().is_empty() && pos > n.range.end)
+        {
+            // Specs from older aftman manifests may not have a version at all, such
+            // as "owner/repo", so with the cursor past the repo we offer to add one
+            debug!("Completing missing version: {found:?}");
+            return get_rokit_completions_spec_version(&self.clients, config, doc, found).await;
+        } else if parsed.author.contains(pos)
+            || (parsed.author.unquoted().is_empty() && found.spec.contains(pos))
+        {
+            debug!("Completing author: {found:?}");
+            return get_rokit_completions_spec_author(&self.clients, doc, found).await;
         }
+
+        Ok(CompletionResponse::Array(Vec::new()))
     }
 }
 
 #[tower_lsp::async_trait]
 impl Tool for Rokit {
+    fn dependency_count(&self, uri: &Url) -> usize {
+        self.get_document(uri)
+            .map_or(0, |doc| query_rokit_toml_dependencies(doc.inner()).len())
+    }
+
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
         let uri = params.text_document_position_params.text_document.uri;
         let pos = params.text_document_position_params.position;
@@ -62,6 +127,10 @@ impl Tool for Rokit {
             return Ok(None);
         };
 
+        if self.limited.contains(&uri) {
+            return Ok(None);
+        }
+
         // Fetch some extra info and return the hover
         debug!("Hovering: {found:?}");
         get_rokit_hover(&self.clients, &doc, found).await
@@ -74,28 +143,51 @@ impl Tool for Rokit {
             return Ok(CompletionResponse::Array(Vec::new()));
         };
 
-        // Find the dependency that is being completed
+        // Find the dependency that is being completed - in a manifest without a [tools]
+        // table, the tool being typed is not found, so we complete it anyway, and add
+        // the table along with whichever completion is accepted
         let dependencies = query_rokit_toml_dependencies(doc.inner());
-        let Some(found) = SimpleDependency::find_at_pos(&dependencies, pos) else {
-            return Ok(CompletionResponse::Array(Vec::new()));
+        let (found, header_edit) = match SimpleDependency::find_at_pos(&dependencies, pos) {
+            Some(found) => (found.clone(), None),
+            None => {
+                let has_table = query_rokit_toml_has_tools_table(doc.inner());
+                let Some((entry, spec)) = find_unsectioned_entry(&doc, pos)
+                    .filter(|_| !has_table)
+                    .and_then(|e| e.value.clone().map(|spec| (e, spec)))
+                    .filter(|(_, spec)| spec.contains(pos))
+                else {
+                    return Ok(CompletionResponse::Array(Vec::new()));
+                };
+                let edit = section_header_edit(&doc, entry.key.range.start.line, "tools");
+                let found = SimpleDependency {
+                    kind: DependencyKind::Default,
+                    name: entry.key,
+                    spec,
+                };
+                (found, Some(edit))
+            }
         };
 
-        // Check what we're completing - author, name, or version
-        let parsed = found.parsed_spec();
-        if parsed.version.as_ref().is_some_and(|v| v.contains(pos)) {
-            debug!("Completing version: {found:?}");
-            return get_rokit_completions_spec_version(&self.clients, &doc, found).await;
-        } else if parsed.name.is_some_and(|n| n.contains(pos)) {
-            debug!("Completing name: {found:?}");
-            return get_rokit_completions_spec_name(&self.clients, &doc, found).await;
-        } else if parsed.author.contains(pos)
-            || (parsed.author.unquoted().is_empty() && found.spec.contains(pos))
-        {
-            debug!("Completing author: {found:?}");
-            return get_rokit_completions_spec_author(&self.clients, &doc, found).await;
+        let config = self.config.get();
+        if self.limited.contains(&uri) {
+            return Ok(CompletionResponse::Array(Vec::new()));
         }
 
-        Ok(CompletionResponse::Array(Vec::new()))
+        let response = self.complete_spec(&config, &doc, &found, pos).await?;
+        Ok(match header_edit {
+            Some(edit) => with_additional_edit(response, edit),
+            None => response,
+        })
+    }
+
+    async fn completion_resolve(&self, item: CompletionItem) -> Result<CompletionItem> {
+        match CompletionMetadata::from_item(&item) {
+            Some(CompletionMetadata::RokitTool { author, name }) => {
+                debug!("Resolving completion: {author}/{name}");
+                get_rokit_completion_resolve(&self.clients, item, &author, &name).await
+            }
+            _ => Ok(item),
+        }
     }
 
     async fn diagnostics(&self, params: DocumentDiagnosticParams) -> Result<Vec<Diagnostic>> {
@@ -104,22 +196,53 @@ impl Tool for Rokit {
             return Ok(Vec::new());
         };
 
+        // Check for any tools that will not be recognized, and for
+        // an aftman.toml and a rokit.toml in the same directory
+        let misplaced = query_rokit_toml_misplaced_tools(doc.inner());
+        let mut manifest_diags = get_rokit_diagnostics_misplaced(&doc, &misplaced);
+        if let Some(sibling) = find_sibling_manifest(&doc, &self.documents).await {
+            manifest_diags.push(get_rokit_diagnostics_duplicate_manifest(&doc, &sibling));
+        }
+
         // Find all dependencies
         let dependencies = query_rokit_toml_dependencies(doc.inner());
         if dependencies.is_empty() {
-            return Ok(Vec::new());
+            return Ok(manifest_diags);
+        }
+
+        // ... and for any tools that are not permitted by policy
+        let config = self.config.get();
+        manifest_diags.extend(dependencies.iter().filter_map(|tool| {
+            let parsed = tool.parsed_spec();
+            let name = format!("{}/{}", parsed.author.unquoted(), parsed.name?.unquoted());
+            get_policy_diagnostic(
+                &config,
+                ToolName::Rokit,
+                &name,
+                tool.name.range,
+                diag_source_for_doc(&doc),
+            )
+        }));
+
+        if self.limited.contains(&uri) {
+            let limited = exceeds_limits_diagnostic(&config.limits, diag_source_for_doc(&doc));
+            return Ok(manifest_diags.into_iter().chain([limited]).collect());
         }
 
         // Fetch all diagnostics concurrently
         debug!("Fetching rokit diagnostics for dependencies");
-        let results = try_join_all(
-            dependencies
-                .iter()
-                .map(|tool| get_rokit_diagnostics(&self.clients, &doc, tool)),
+        let results = cached_dependency_diagnostics(
+            &doc,
+            config.diagnostics.merge,
+            dependencies.iter().map(|tool| {
+                let key = DependencyKey::new(&doc, tool.kind, tool.range());
+                let fut = get_rokit_diagnostics(&self.clients, &config, &doc, tool);
+                (key, tool.range(), fut)
+            }),
         )
         .await?;
 
-        Ok(results.into_iter().flatten().collect())
+        Ok(results.into_iter().chain(manifest_diags).collect())
     }
 
     async fn code_action(&self, params: CodeActionParams) -> Result<Vec<CodeActionOrCommand>> {
@@ -130,9 +253,155 @@ impl Tool for Rokit {
                 .as_ref()
                 .map(ResolveContext::<CodeActionMetadata>::try_from)
             {
-                actions.push(action.into_inner().into_code_action(diag.clone()))
+                actions.extend(action.into_inner().into_code_actions(Some(diag.clone())))
             }
         }
+
+        // Sorting is available anywhere within the tools, unless
+        // the client only asked for other kinds of code actions
+        let wants_refactors = params.context.only.as_ref().is_none_or(|only| {
+            only.iter().any(|kind| {
+                CodeActionKind::REFACTOR_REWRITE
+                    .as_str()
+                    .starts_with(kind.as_str())
+            })
+        });
+        if wants_refactors {
+            if let Some(doc) = self.get_document(&params.text_document.uri) {
+                let tools = query_rokit_toml_dependencies(doc.inner());
+                actions.extend(get_rokit_sort_action(&doc, &tools, params.range));
+            }
+        }
+
+        // Releases of a single tool can be refreshed, but only when
+        // no specific kinds were asked for, see `into_code_action`
+        if params.context.only.is_none() {
+            if let Some(doc) = self.get_document(&params.text_document.uri) {
+                let tools = query_rokit_toml_dependencies(doc.inner());
+                let found = SimpleDependency::find_at_pos(&tools, params.range.start);
+                if let Some(spec) = found.and_then(|f| f.parsed_spec().into_full()) {
+                    let dependency = DependencyId::Github {
+                        owner: spec.author.unquoted().to_string(),
+                        repository: spec.name.unquoted().to_string(),
+                    };
+                    actions.push(
+                        RefreshDependencyParams::new(doc.uri(), dependency).into_code_action(),
+                    );
+                }
+            }
+        }
+
         Ok(actions)
     }
+
+    async fn linked_editing_range(
+        &self,
+        params: LinkedEditingRangeParams,
+    ) -> Result<Option<LinkedEditingRanges>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let pos = params.text_document_position_params.position;
+        let Some(doc) = self.get_document(&uri) else {
+            return Ok(None);
+        };
+
+        let tools = query_rokit_toml_dependencies(doc.inner());
+        Ok(get_rokit_linked_editing_ranges(&tools, pos))
+    }
+
+    async fn will_save_wait_until(
+        &self,
+        params: WillSaveTextDocumentParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        // Saves that happen on their own, such as after a delay,
+        // could move tools around while they are being typed
+        if params.reason != TextDocumentSaveReason::MANUAL {
+            return Ok(None);
+        }
+        let Some(doc) = self.get_document(&params.text_document.uri) else {
+            return Ok(None);
+        };
+
+        let tools = query_rokit_toml_dependencies(doc.inner());
+        Ok(get_rokit_sort_edit(&doc, &tools).map(|edit| vec![edit]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::testing::{cursor_fixture, MockFetcher, TestServer};
+    use crate::server::{Config, RokitConfig};
+
+    use super::*;
+
+    const ROJO_RELEASES: &str = r#"[
+        {"tag_name": "v7.5.0", "name": "7.5.0", "body": null, "draft": false, "prerelease": true,
+         "created_at": null, "published_at": null, "assets": []},
+        {"tag_name": "v7.4.4", "name": "7.4.4", "body": null, "draft": false, "prerelease": false,
+         "created_at": null, "published_at": null, "assets": []},
+        {"tag_name": "v7.4.1", "name": "7.4.1", "body": null, "draft": false, "prerelease": false,
+         "created_at": null, "published_at": null, "assets": []}
+    ]"#;
+
+    #[tokio::test]
+    async fn test_rokit_github_prereleases() {
+        let fetcher =
+            MockFetcher::new().with_response("/repos/rojo-rbx/rojo/releases", ROJO_RELEASES);
+        let server = TestServer::new(fetcher.clone()).await;
+
+        // Releases marked as prereleases on GitHub are hidden, even with stable tags
+        let (contents, pos) = cursor_fixture("[tools]\nrojo = \"rojo-rbx/rojo@7.$0\"\n");
+        let uri = server.open("rokit.toml", &contents).await;
+        let items = server.completion(&uri, pos).await;
+        let labels = items.iter().map(|i| i.label.as_str()).collect::<Vec<_>>();
+        assert_eq!(labels, vec!["latest (pins 7.4.4)", "7.4.4", "7.4.1"]);
+
+        let is_outdated =
+            |d: &Diagnostic| d.code == Some(NumberOrString::String(String::from("outdated")));
+        let uri = server
+            .open(
+                "pinned/rokit.toml",
+                "[tools]\nrojo = \"rojo-rbx/rojo@7.4.4\"\n",
+            )
+            .await;
+        assert!(!server.diagnostics(&uri).await.iter().any(is_outdated));
+
+        // Tools already pinned to such a prerelease are not told to downgrade
+        let uri = server
+            .open(
+                "prerelease/rokit.toml",
+                "[tools]\nrojo = \"rojo-rbx/rojo@7.5.0\"\n",
+            )
+            .await;
+        assert!(!server.diagnostics(&uri).await.iter().any(is_outdated));
+
+        // Both are included again when prereleases are wanted
+        let server = TestServer::new(fetcher).await;
+        let config = server.config().get();
+        server.config().set(Config {
+            rokit: RokitConfig {
+                include_prereleases: true,
+                ..config.rokit.clone()
+            },
+            ..config
+        });
+        let uri = server.open("rokit.toml", &contents).await;
+        let items = server.completion(&uri, pos).await;
+        assert_eq!(
+            items.first().map(|i| i.label.as_str()),
+            Some("latest (pins 7.5.0)")
+        );
+
+        let uri = server
+            .open(
+                "pinned/rokit.toml",
+                "[tools]\nrojo = \"rojo-rbx/rojo@7.4.4\"\n",
+            )
+            .await;
+        let diagnostics = server.diagnostics(&uri).await;
+        let outdated = diagnostics
+            .iter()
+            .find(|d| is_outdated(d))
+            .expect("missing outdated diagnostic");
+        assert!(outdated.message.contains("`7.5.0`"));
+    }
 }
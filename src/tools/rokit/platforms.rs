@@ -0,0 +1,220 @@
+use std::collections::BTreeMap;
+
+use crate::clients::github::models::RepositoryReleaseAsset;
+
+/**
+    Extensions of release assets that are never binaries of the tool itself,
+    such as checksums and signatures, and that are always ignored.
+*/
+const IGNORED_EXTENSIONS: [&str; 9] = [
+    ".sha256", ".sha512", ".md5", ".sig", ".asc", ".pem", ".txt", ".json", ".sbom",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PlatformOs {
+    Windows,
+    Macos,
+    Linux,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PlatformArch {
+    X86,
+    X86_64,
+    Arm,
+    Aarch64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Platform {
+    pub os: PlatformOs,
+    pub arch: PlatformArch,
+}
+
+impl Platform {
+    /**
+        The platforms that tools are usually expected to be installable on.
+    */
+    pub const COMMON: [Self; 4] = [
+        Self::new(PlatformOs::Windows, PlatformArch::X86_64),
+        Self::new(PlatformOs::Macos, PlatformArch::X86_64),
+        Self::new(PlatformOs::Macos, PlatformArch::Aarch64),
+        Self::new(PlatformOs::Linux, PlatformArch::X86_64),
+    ];
+
+    pub const fn new(os: PlatformOs, arch: PlatformArch) -> Self {
+        Self { os, arch }
+    }
+
+    /**
+        Gets the platform that the language server is currently running on,
+        which is most likely also the platform that tools are installed on.
+    */
+    pub fn current() -> Option<Self> {
+        let os = match std::env::consts::OS {
+            "windows" => PlatformOs::Windows,
+            "macos" => PlatformOs::Macos,
+            "linux" => PlatformOs::Linux,
+            _ => return None,
+        };
+        let arch = match std::env::consts::ARCH {
+            "x86" => PlatformArch::X86,
+            "x86_64" => PlatformArch::X86_64,
+            "arm" => PlatformArch::Arm,
+            "aarch64" => PlatformArch::Aarch64,
+            _ => return None,
+        };
+        Some(Self::new(os, arch))
+    }
+
+    /**
+        Gets a short, human readable name for the platform,
+        such as `Linux` or `macOS (ARM)`, for use in messages.
+    */
+    pub fn name(self) -> String {
+        let os = match self.os {
+            PlatformOs::Windows => "Windows",
+            PlatformOs::Macos => "macOS",
+            PlatformOs::Linux => "Linux",
+        };
+        match (self.os, self.arch) {
+            (PlatformOs::Macos, PlatformArch::X86_64) => format!("{os} (Intel)"),
+            (PlatformOs::Macos, PlatformArch::Aarch64) => format!("{os} (ARM)"),
+            (_, PlatformArch::X86_64) => os.to_string(),
+            (_, PlatformArch::X86) => format!("{os} (x86)"),
+            (_, PlatformArch::Arm) => format!("{os} (ARM, 32-bit)"),
+            (_, PlatformArch::Aarch64) => format!("{os} (ARM)"),
+        }
+    }
+}
+
+/**
+    Classifies the name of a release asset, such as `tool-x86_64-linux.zip`
+    or `tool-aarch64-apple-darwin.tar.gz`, into the platforms it is for.
+
+    Target triples and the usual conventions for operating systems and
+    architectures are recognized, and assets that don't mention any
+    architecture are assumed to be for x86_64, which is how most older
+    tools are named. Universal macOS assets are for both architectures.
+
+    Returns an empty list if the asset is not for any known platform.
+*/
+pub fn classify_asset_name(name: &str) -> Vec<Platform> {
+    let name = name.to_ascii_lowercase();
+    if IGNORED_EXTENSIONS.iter().any(|ext| name.ends_with(ext)) {
+        return Vec::new();
+    }
+
+    // Split into words, keeping architectures such as x86_64 intact
+    let normalized = name.replace("x86_64", "x64").replace("x86-64", "x64");
+    let words = normalized
+        .split(['-', '_', '.', ' '])
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>();
+    let has = |candidates: &[&str]| words.iter().any(|w| candidates.contains(w));
+
+    let os =
+        if has(&["windows", "win", "win32", "win64", "msvc", "mingw"]) || name.ends_with(".exe") {
+            PlatformOs::Windows
+        } else if has(&["macos", "darwin", "osx", "mac", "apple"]) {
+            PlatformOs::Macos
+        } else if has(&["linux", "ubuntu", "musl"]) {
+            PlatformOs::Linux
+        } else {
+            return Vec::new();
+        };
+
+    if os == PlatformOs::Macos && has(&["universal", "universal2"]) {
+        return vec![
+            Platform::new(os, PlatformArch::X86_64),
+            Platform::new(os, PlatformArch::Aarch64),
+        ];
+    }
+
+    // Assets without any architecture are assumed to be for x86_64
+    let arch = if has(&["aarch64", "arm64"]) {
+        PlatformArch::Aarch64
+    } else if has(&["armv7", "armv7l", "armhf", "arm"]) {
+        PlatformArch::Arm
+    } else if has(&["x64", "amd64", "win64"]) {
+        PlatformArch::X86_64
+    } else if has(&["i686", "i386", "x86", "win32"]) {
+        PlatformArch::X86
+    } else {
+        PlatformArch::X86_64
+    };
+    vec![Platform::new(os, arch)]
+}
+
+/**
+    Builds a map from platforms to the assets of a release that they can be
+    installed from, using the first matching asset for each platform.
+*/
+pub fn platform_assets(
+    assets: &[RepositoryReleaseAsset],
+) -> BTreeMap<Platform, &RepositoryReleaseAsset> {
+    let mut map = BTreeMap::new();
+    for asset in assets {
+        for platform in classify_asset_name(&asset.name) {
+            map.entry(platform).or_insert(asset);
+        }
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(name: &str) -> Vec<(PlatformOs, PlatformArch)> {
+        classify_asset_name(name)
+            .into_iter()
+            .map(|p| (p.os, p.arch))
+            .collect()
+    }
+
+    #[test]
+    fn test_classify_conventions() {
+        use PlatformArch::*;
+        use PlatformOs::*;
+        assert_eq!(classify("tool-x86_64-linux.zip"), vec![(Linux, X86_64)]);
+        assert_eq!(classify("tool-linux-aarch64.zip"), vec![(Linux, Aarch64)]);
+        assert_eq!(classify("tool-windows.zip"), vec![(Windows, X86_64)]);
+        assert_eq!(classify("tool-win32.zip"), vec![(Windows, X86)]);
+        assert_eq!(classify("tool-macos-arm64.zip"), vec![(Macos, Aarch64)]);
+        assert_eq!(classify("tool-darwin-amd64.tar.gz"), vec![(Macos, X86_64)]);
+        assert_eq!(
+            classify("tool-macos-universal.zip"),
+            vec![(Macos, X86_64), (Macos, Aarch64)]
+        );
+    }
+
+    #[test]
+    fn test_classify_target_triples() {
+        use PlatformArch::*;
+        use PlatformOs::*;
+        assert_eq!(
+            classify("tool-aarch64-apple-darwin.tar.gz"),
+            vec![(Macos, Aarch64)]
+        );
+        assert_eq!(
+            classify("tool-x86_64-pc-windows-msvc.zip"),
+            vec![(Windows, X86_64)]
+        );
+        assert_eq!(
+            classify("tool-armv7-unknown-linux-gnueabihf.zip"),
+            vec![(Linux, Arm)]
+        );
+    }
+
+    #[test]
+    fn test_classify_ignored() {
+        assert!(classify("tool-linux.zip.sha256").is_empty());
+        assert!(classify("source.tar.gz").is_empty());
+        // "win" is a part of "darwin", but not a word in it
+        assert_eq!(
+            classify_asset_name("tool-darwin.zip")[0].os,
+            PlatformOs::Macos
+        );
+    }
+}
@@ -4,6 +4,11 @@ use serde::{Deserialize, Serialize};
 
 use tower_lsp::lsp_types::*;
 
+use crate::util::LspUriExt;
+
+use super::super::ToolName;
+use super::ResolveContext;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "kind")]
 pub enum CodeActionMetadata {
@@ -13,11 +18,127 @@ pub enum CodeActionMetadata {
         source_text: String,
         version_current: String,
         version_latest: String,
+        #[serde(default)]
+        package_manager: Option<PackageManagerCommand>,
+    },
+    ExpandVersion {
+        edit_range: Range,
+        source_uri: Url,
+        source_text: String,
+        version_current: String,
+        version_expanded: String,
+    },
+    PinVersion {
+        edit_range: Range,
+        source_uri: Url,
+        source_text: String,
+        version_current: String,
+        version_pinned: String,
+    },
+    PinLatestVersion {
+        edit_range: Range,
+        source_uri: Url,
+        source_text: String,
+        version_current: String,
+        package: String,
+    },
+    DidYouMean {
+        edit_range: Range,
+        source_uri: Url,
+        suggestions: Vec<String>,
+    },
+    ConvertToTable {
+        edit_range: Range,
+        source_uri: Url,
+        version: String,
+    },
+    ConvertToShorthand {
+        edit_range: Range,
+        source_uri: Url,
+        version: String,
+    },
+    UseWorkspaceVersion {
+        edit_range: Range,
+        source_uri: Url,
+        #[serde(default)]
+        features: Vec<String>,
+        #[serde(default)]
+        optional: bool,
+    },
+    AddMissingTools {
+        edit_range: Range,
+        source_uri: Url,
+        new_text: String,
+        count: usize,
     },
 }
 
 impl CodeActionMetadata {
-    pub fn into_code_action(self, diag: Diagnostic) -> CodeActionOrCommand {
+    /**
+        Gets the range that this code action edits, if the edit is in the document at the given uri.
+    */
+    pub fn source_edit_range_mut(&mut self, uri: &Url) -> Option<&mut Range> {
+        let (edit_range, source_uri) = match self {
+            Self::LatestVersion {
+                edit_range,
+                source_uri,
+                ..
+            }
+            | Self::ExpandVersion {
+                edit_range,
+                source_uri,
+                ..
+            }
+            | Self::PinVersion {
+                edit_range,
+                source_uri,
+                ..
+            }
+            | Self::PinLatestVersion {
+                edit_range,
+                source_uri,
+                ..
+            }
+            | Self::DidYouMean {
+                edit_range,
+                source_uri,
+                ..
+            }
+            | Self::ConvertToTable {
+                edit_range,
+                source_uri,
+                ..
+            }
+            | Self::ConvertToShorthand {
+                edit_range,
+                source_uri,
+                ..
+            }
+            | Self::UseWorkspaceVersion {
+                edit_range,
+                source_uri,
+                ..
+            }
+            | Self::AddMissingTools {
+                edit_range,
+                source_uri,
+                ..
+            } => (edit_range, source_uri),
+        };
+        (source_uri == uri).then_some(edit_range)
+    }
+
+    /**
+        Creates code actions, fixing the given diagnostic if there is one -
+        refactors such as `ConvertToTable` and `ConvertToShorthand`
+        are available without diagnostics.
+
+        Actions that need a network request to compute their edit, such as
+        `PinLatestVersion`, are created without one, and with this metadata
+        as their data, to be completed later using `into_resolved_code_action`.
+    */
+    pub fn into_code_actions(self, diag: Option<Diagnostic>) -> Vec<CodeActionOrCommand> {
+        let diags = diag.map(|diag| vec![diag]);
         match self {
             Self::LatestVersion {
                 edit_range,
@@ -25,7 +146,7 @@ impl CodeActionMetadata {
                 source_text,
                 version_current,
                 version_latest,
-                ..
+                package_manager,
             } => {
                 let mut change_map = HashMap::new();
                 change_map.insert(
@@ -35,18 +156,503 @@ impl CodeActionMetadata {
                         new_text: source_text.replace(&version_current, &version_latest),
                     }],
                 );
-                CodeActionOrCommand::CodeAction(CodeAction {
+
+                // The manifest edit always applies, and the package manager
+                // may then update the lockfile after it has been applied
+                let command = package_manager.map(PackageManagerCommand::into_command);
+
+                vec![CodeActionOrCommand::CodeAction(CodeAction {
                     title: String::from("Update to latest version"),
                     kind: Some(CodeActionKind::QUICKFIX),
                     edit: Some(WorkspaceEdit {
                         changes: Some(change_map),
                         ..Default::default()
                     }),
-                    diagnostics: Some(vec![diag]),
+                    command,
+                    diagnostics: diags,
+                    is_preferred: Some(true),
+                    ..Default::default()
+                })]
+            }
+            Self::ExpandVersion {
+                edit_range,
+                source_uri,
+                source_text,
+                version_current,
+                version_expanded,
+            } => {
+                // Offer both the plain and the explicit caret form, which are
+                // equivalent in Cargo, with the plain form being preferred
+                [version_expanded.clone(), format!("^{version_expanded}")]
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, version_new)| {
+                        let mut change_map = HashMap::new();
+                        change_map.insert(
+                            source_uri.clone(),
+                            vec![TextEdit {
+                                range: edit_range,
+                                new_text: source_text.replace(&version_current, &version_new),
+                            }],
+                        );
+                        CodeActionOrCommand::CodeAction(CodeAction {
+                            title: format!("Expand to `{version_new}`"),
+                            kind: Some(CodeActionKind::QUICKFIX),
+                            edit: Some(WorkspaceEdit {
+                                changes: Some(change_map),
+                                ..Default::default()
+                            }),
+                            diagnostics: diags.clone(),
+                            is_preferred: Some(index == 0),
+                            ..Default::default()
+                        })
+                    })
+                    .collect()
+            }
+            Self::PinVersion {
+                edit_range,
+                source_uri,
+                source_text,
+                version_current,
+                version_pinned,
+            } => {
+                let mut change_map = HashMap::new();
+                change_map.insert(
+                    source_uri,
+                    vec![TextEdit {
+                        range: edit_range,
+                        new_text: source_text.replace(&version_current, &version_pinned),
+                    }],
+                );
+                vec![CodeActionOrCommand::CodeAction(CodeAction {
+                    title: format!("Pin to `{version_pinned}`"),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(change_map),
+                        ..Default::default()
+                    }),
+                    diagnostics: diags,
                     is_preferred: Some(true),
                     ..Default::default()
+                })]
+            }
+            Self::PinLatestVersion { ref source_uri, .. } => {
+                let data = ResolveContext {
+                    uri: source_uri.clone(),
+                    value: self.clone(),
+                };
+                vec![CodeActionOrCommand::CodeAction(CodeAction {
+                    title: String::from("Pin to latest version"),
+                    kind: Some(CodeActionKind::REFACTOR_REWRITE),
+                    diagnostics: diags,
+                    data: Some(data.into()),
+                    ..Default::default()
+                })]
+            }
+            Self::DidYouMean {
+                edit_range,
+                source_uri,
+                suggestions,
+            } => suggestions
+                .into_iter()
+                .enumerate()
+                .map(|(index, suggestion)| {
+                    let mut change_map = HashMap::new();
+                    change_map.insert(
+                        source_uri.clone(),
+                        vec![TextEdit {
+                            range: edit_range,
+                            new_text: suggestion.clone(),
+                        }],
+                    );
+                    CodeActionOrCommand::CodeAction(CodeAction {
+                        title: format!("Did you mean `{suggestion}`?"),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        edit: Some(WorkspaceEdit {
+                            changes: Some(change_map),
+                            ..Default::default()
+                        }),
+                        diagnostics: diags.clone(),
+                        is_preferred: Some(index == 0),
+                        ..Default::default()
+                    })
                 })
+                .collect(),
+            Self::ConvertToTable {
+                edit_range,
+                source_uri,
+                version,
+            } => {
+                let mut change_map = HashMap::new();
+                change_map.insert(
+                    source_uri,
+                    vec![TextEdit {
+                        range: edit_range,
+                        new_text: format!("{{ version = {version} }}"),
+                    }],
+                );
+                vec![CodeActionOrCommand::CodeAction(CodeAction {
+                    title: String::from("Convert to inline table"),
+                    kind: Some(CodeActionKind::REFACTOR_REWRITE),
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(change_map),
+                        ..Default::default()
+                    }),
+                    diagnostics: diags,
+                    ..Default::default()
+                })]
+            }
+            Self::ConvertToShorthand {
+                edit_range,
+                source_uri,
+                version,
+            } => {
+                let mut change_map = HashMap::new();
+                change_map.insert(
+                    source_uri,
+                    vec![TextEdit {
+                        range: edit_range,
+                        new_text: version,
+                    }],
+                );
+                vec![CodeActionOrCommand::CodeAction(CodeAction {
+                    title: String::from("Simplify to shorthand"),
+                    kind: Some(CodeActionKind::REFACTOR_REWRITE),
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(change_map),
+                        ..Default::default()
+                    }),
+                    diagnostics: diags,
+                    ..Default::default()
+                })]
+            }
+            Self::UseWorkspaceVersion {
+                edit_range,
+                source_uri,
+                features,
+                optional,
+            } => {
+                let mut change_map = HashMap::new();
+                change_map.insert(
+                    source_uri,
+                    vec![TextEdit {
+                        range: edit_range,
+                        new_text: workspace_dependency_text(&features, optional),
+                    }],
+                );
+                // Also offered as a refactor, for dependencies that use the same version
+                let kind = match diags {
+                    Some(_) => CodeActionKind::QUICKFIX,
+                    None => CodeActionKind::REFACTOR_REWRITE,
+                };
+                vec![CodeActionOrCommand::CodeAction(CodeAction {
+                    title: String::from("Use workspace version"),
+                    kind: Some(kind),
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(change_map),
+                        ..Default::default()
+                    }),
+                    diagnostics: diags,
+                    ..Default::default()
+                })]
+            }
+            Self::AddMissingTools {
+                edit_range,
+                source_uri,
+                new_text,
+                count,
+            } => {
+                let file_name = source_uri.file_name().unwrap_or_default();
+                let mut change_map = HashMap::new();
+                change_map.insert(
+                    source_uri,
+                    vec![TextEdit {
+                        range: edit_range,
+                        new_text,
+                    }],
+                );
+                vec![CodeActionOrCommand::CodeAction(CodeAction {
+                    title: match count {
+                        1 => format!("Add 1 missing tool to `{file_name}`"),
+                        _ => format!("Add {count} missing tools to `{file_name}`"),
+                    },
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(change_map),
+                        ..Default::default()
+                    }),
+                    diagnostics: diags,
+                    is_preferred: Some(true),
+                    ..Default::default()
+                })]
+            }
+        }
+    }
+
+    /**
+        Completes a code action that was created without an edit, using
+        the latest version that was fetched for it, or returns it as-is
+        if it never needed resolving or no latest version was found.
+    */
+    pub fn into_resolved_code_action(
+        self,
+        action: CodeAction,
+        version_latest: Option<&str>,
+    ) -> CodeAction {
+        let Some(version_latest) = version_latest else {
+            return action;
+        };
+        match self {
+            Self::PinLatestVersion {
+                edit_range,
+                source_uri,
+                source_text,
+                version_current,
+                ..
+            } => {
+                let mut change_map = HashMap::new();
+                change_map.insert(
+                    source_uri,
+                    vec![TextEdit {
+                        range: edit_range,
+                        new_text: source_text
+                            .replace(&version_current, &format!("={version_latest}")),
+                    }],
+                );
+                CodeAction {
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(change_map),
+                        ..Default::default()
+                    }),
+                    ..action
+                }
+            }
+            _ => action,
+        }
+    }
+}
+
+/**
+    Creates the inline table for a dependency that inherits from the workspace,
+    such as `{ workspace = true, features = ["derive"] }` - only `features` and
+    `optional` may be given together with `workspace = true`, since
+    everything else comes from the workspace dependency.
+*/
+pub fn workspace_dependency_text(features: &[String], optional: bool) -> String {
+    let mut text = String::from("{ workspace = true");
+    if !features.is_empty() {
+        text.push_str(&format!(", features = [{}]", features.join(", ")));
+    }
+    if optional {
+        text.push_str(", optional = true");
+    }
+    text.push_str(" }");
+    text
+}
+
+/**
+    A package manager that the client may be asked to run.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PackageManager {
+    Cargo,
+    Npm,
+    Wally,
+}
+
+impl PackageManager {
+    pub fn tool_name(self) -> ToolName {
+        match self {
+            Self::Cargo => ToolName::Cargo,
+            Self::Npm => ToolName::Npm,
+            Self::Wally => ToolName::Wally,
+        }
+    }
+}
+
+/**
+    What the package manager should do, which is
+    either updating a single package, or installing.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum PackageManagerAction {
+    Update { package: String },
+    Install,
+}
+
+/**
+    A package manager command for the client to run, such as `cargo update`.
+
+    Only the tool, the action, and the manifest are sent, never a program or
+    arguments, and clients build the actual command line from those themselves,
+    running it in the directory of the manifest.
+*/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageManagerCommand {
+    pub tool: PackageManager,
+    pub action: PackageManagerAction,
+    pub manifest: Url,
+}
+
+impl PackageManagerCommand {
+    pub const COMMAND: &'static str = "tooling-language-server.runPackageManager";
+
+    /**
+        Creates a command that updates the lockfile for the given package
+        in the manifest at the given uri, if the tool uses lockfiles.
+    */
+    pub fn for_package(tool: ToolName, manifest_uri: &Url, package: &str) -> Option<Self> {
+        let (tool, action) = match tool {
+            ToolName::Aftman | ToolName::Rokit => return None,
+            ToolName::Cargo => (
+                PackageManager::Cargo,
+                PackageManagerAction::Update {
+                    package: package.to_string(),
+                },
+            ),
+            ToolName::Npm => (PackageManager::Npm, PackageManagerAction::Install),
+            ToolName::Wally => (PackageManager::Wally, PackageManagerAction::Install),
+        };
+        Some(Self {
+            tool,
+            action,
+            manifest: manifest_uri.clone(),
+        })
+    }
+
+    fn from_command(command: &Command) -> Option<Self> {
+        if command.command != Self::COMMAND {
+            return None;
+        }
+        let argument = command.arguments.as_ref()?.first()?;
+        serde_json::from_value(argument.clone()).ok()
+    }
+
+    /**
+        The command line that clients are expected to run, only used as a title.
+    */
+    pub fn command_line(&self) -> String {
+        match (&self.tool, &self.action) {
+            (PackageManager::Cargo, PackageManagerAction::Update { package }) => {
+                format!("cargo update --package {package}")
+            }
+            (PackageManager::Cargo, PackageManagerAction::Install) => String::from("cargo fetch"),
+            (PackageManager::Npm, _) => String::from("npm install"),
+            (PackageManager::Wally, _) => String::from("wally install"),
+        }
+    }
+
+    pub fn into_command(self) -> Command {
+        Command {
+            title: format!("Run `{}`", self.command_line()),
+            command: Self::COMMAND.to_string(),
+            arguments: Some(vec![
+                serde_json::to_value(self).expect("command is always serializable")
+            ]),
+        }
+    }
+}
+
+/**
+    Removes package manager commands from the given code actions, unless `keep`
+    returns `true` for them, leaving the edits of the code actions as they are.
+*/
+pub fn retain_package_manager_commands(
+    actions: &mut Vec<CodeActionOrCommand>,
+    mut keep: impl FnMut(&PackageManagerCommand) -> bool,
+) {
+    actions.retain(|action| match action {
+        CodeActionOrCommand::Command(command) => {
+            PackageManagerCommand::from_command(command).is_none_or(|c| keep(&c))
+        }
+        CodeActionOrCommand::CodeAction(_) => true,
+    });
+    for action in actions {
+        if let CodeActionOrCommand::CodeAction(action) = action {
+            let command = action.command.as_ref();
+            if command
+                .and_then(PackageManagerCommand::from_command)
+                .is_some_and(|c| !keep(&c))
+            {
+                action.command = None;
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspace_dependency_text() {
+        assert_eq!(
+            workspace_dependency_text(&[], false),
+            "{ workspace = true }"
+        );
+        assert_eq!(
+            workspace_dependency_text(&[String::from("\"derive\"")], true),
+            "{ workspace = true, features = [\"derive\"], optional = true }"
+        );
+    }
+
+    #[test]
+    fn test_package_manager_command() {
+        let manifest = Url::parse("file:///project/Cargo.toml").unwrap();
+        let command = PackageManagerCommand {
+            tool: PackageManager::Cargo,
+            action: PackageManagerAction::Update {
+                package: String::from("serde"),
+            },
+            manifest: manifest.clone(),
+        };
+        assert_eq!(command.command_line(), "cargo update --package serde");
+
+        let value = serde_json::to_value(command.into_command().arguments.unwrap()).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!([{
+                "tool": "cargo",
+                "action": { "kind": "update", "package": "serde" },
+                "manifest": "file:///project/Cargo.toml",
+            }])
+        );
+    }
+
+    #[test]
+    fn test_retain_package_manager_commands() {
+        let manifest = Url::parse("file:///project/Cargo.toml").unwrap();
+        let metadata = CodeActionMetadata::LatestVersion {
+            edit_range: Range::default(),
+            source_uri: manifest.clone(),
+            source_text: String::from("\"1.0\""),
+            version_current: String::from("1.0"),
+            version_latest: String::from("2.0"),
+            package_manager: PackageManagerCommand::for_package(
+                ToolName::Cargo,
+                &manifest,
+                "serde",
+            ),
+        };
+        let actions = metadata.into_code_actions(None);
+        let command = |actions: &[CodeActionOrCommand]| match &actions[0] {
+            CodeActionOrCommand::CodeAction(action) => action.command.clone(),
+            CodeActionOrCommand::Command(_) => panic!("expected a code action"),
+        };
+
+        let mut kept = actions.clone();
+        retain_package_manager_commands(&mut kept, |c| c.tool == PackageManager::Cargo);
+        assert_eq!(
+            command(&kept).unwrap().command,
+            PackageManagerCommand::COMMAND
+        );
+
+        // The manifest edit still applies without the command
+        let mut removed = actions;
+        retain_package_manager_commands(&mut removed, |_| false);
+        assert_eq!(command(&removed), None);
+        assert!(matches!(&removed[0], CodeActionOrCommand::CodeAction(a) if a.edit.is_some()));
+    }
+}
@@ -0,0 +1,443 @@
+use futures::future::try_join_all;
+use futures::Future;
+use tower_lsp::jsonrpc::{Error, Result};
+use tower_lsp::lsp_types::*;
+use tracing::debug;
+
+use crate::server::{CachedDiagnostics, DependencyKey, Document};
+
+use super::{CodeActionMetadata, ResolveContext};
+
+/**
+    The most diagnostics shown for the same range of a single dependency,
+    after merging, so that a single line never has too many squiggles.
+*/
+const MAXIMUM_DIAGNOSTICS_PER_RANGE: usize = 3;
+
+/**
+    Diagnostics computed for a single dependency, and whether they are complete.
+
+    Diagnostics are incomplete if some data for them could not be fetched,
+    such as after a timeout, and those are shown as they are, but never cached,
+    so that they are computed again the next time diagnostics are requested.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct DependencyDiagnostics {
+    pub diagnostics: Vec<Diagnostic>,
+    pub complete: bool,
+}
+
+impl DependencyDiagnostics {
+    pub fn incomplete(diagnostics: Vec<Diagnostic>) -> Self {
+        Self {
+            diagnostics,
+            complete: false,
+        }
+    }
+}
+
+impl From<Vec<Diagnostic>> for DependencyDiagnostics {
+    fn from(diagnostics: Vec<Diagnostic>) -> Self {
+        Self {
+            diagnostics,
+            complete: true,
+        }
+    }
+}
+
+/**
+    Computes diagnostics for all of the given dependencies, reusing diagnostics
+    from the last computation for any dependency whose key has not changed.
+
+    Dependencies are given as tuples of their key, their current range, and a
+    future that computes their diagnostics, which only runs if nothing was cached.
+    Diagnostics that are incomplete are never cached, see `DependencyDiagnostics`.
+
+    Reused diagnostics are moved to wherever the dependency is currently located,
+    so the result is always identical to computing all of the diagnostics again.
+
+    If `merge` is enabled, the diagnostics of each dependency are merged
+    and ordered by severity, see `merge_diagnostics` for details.
+*/
+pub async fn cached_dependency_diagnostics<F>(
+    doc: &Document,
+    merge: bool,
+    dependencies: impl IntoIterator<Item = (DependencyKey, Range, F)>,
+) -> Result<Vec<Diagnostic>>
+where
+    F: Future<Output = Result<DependencyDiagnostics>>,
+{
+    let cache = doc.diagnostics_cache();
+
+    let mut entries = Vec::new();
+    let mut pending = Vec::new();
+    for (index, (key, range, fut)) in dependencies.into_iter().enumerate() {
+        match cache.get(&key) {
+            Some(cached) => {
+                let diagnostics = relocate_diagnostics(doc.uri(), &cached, range);
+                entries.push((index, key, CachedDiagnostics::new(range, diagnostics), true));
+            }
+            None => pending.push(async move {
+                let computed = fut.await?;
+                let cached = CachedDiagnostics::new(range, computed.diagnostics);
+                Ok::<_, Error>((index, key, cached, computed.complete))
+            }),
+        }
+    }
+
+    debug!(
+        "Reusing diagnostics for {} dependencies, computing {}",
+        entries.len(),
+        pending.len()
+    );
+    entries.extend(try_join_all(pending).await?);
+    entries.sort_by_key(|(index, _, _, _)| *index);
+
+    // Diagnostics are cached as they were computed, and merged only when
+    // returned, so that changing the config never needs them to be recomputed
+    let diagnostics = entries
+        .iter()
+        .flat_map(|(_, _, cached, _)| {
+            let diagnostics = cached.diagnostics.clone();
+            if merge {
+                merge_diagnostics(diagnostics)
+            } else {
+                diagnostics
+            }
+        })
+        .collect();
+    let complete = entries.iter().all(|(_, _, _, complete)| *complete);
+    cache.replace(
+        entries
+            .into_iter()
+            .filter(|(_, _, _, complete)| *complete)
+            .map(|(_, key, cached, _)| (key, cached)),
+        complete,
+    );
+
+    Ok(diagnostics)
+}
+
+/**
+    Merges diagnostics for a single dependency that are on the exact same range,
+    and orders all of them by severity, most severe first, while keeping the
+    order that they were computed in for diagnostics with the same severity.
+
+    Diagnostics without any code action data are merged into the most severe
+    diagnostic on the same range, with their messages appended to its message,
+    and their tags added to its tags, so that a deprecated dependency is still
+    shown as deprecated. The merged diagnostic keeps the code of the most
+    severe one, or takes the code of the first one that has a code, if any.
+    Diagnostics with code action data are kept as they are, since their quick
+    fixes would otherwise be lost, but at most `MAXIMUM_DIAGNOSTICS_PER_RANGE`
+    diagnostics are kept for the same range, dropping the least severe ones.
+*/
+pub fn merge_diagnostics(mut diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    diagnostics.sort_by_key(|diag| severity_rank(diag.severity));
+
+    let mut merged = Vec::<Diagnostic>::new();
+    for diag in diagnostics {
+        let same_range = merged.iter().filter(|d| d.range == diag.range).count();
+        if same_range == 0 {
+            merged.push(diag);
+        } else if diag.data.is_none() {
+            let primary = merged
+                .iter_mut()
+                .find(|d| d.range == diag.range)
+                .expect("a diagnostic with the same range exists");
+            primary.message.push_str("\n\n");
+            primary.message.push_str(&diag.message);
+            if primary.code.is_none() {
+                primary.code = diag.code;
+                primary.code_description = diag.code_description;
+            }
+            for tag in diag.tags.into_iter().flatten() {
+                let tags = primary.tags.get_or_insert_with(Vec::new);
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+        } else if same_range < MAXIMUM_DIAGNOSTICS_PER_RANGE {
+            merged.push(diag);
+        }
+    }
+    merged
+}
+
+/**
+    Ranks severities with the most severe first, treating a missing
+    severity as an error, which is what most clients show it as.
+*/
+fn severity_rank(severity: Option<DiagnosticSeverity>) -> u8 {
+    match severity {
+        None | Some(DiagnosticSeverity::ERROR) => 0,
+        Some(DiagnosticSeverity::WARNING) => 1,
+        Some(DiagnosticSeverity::INFORMATION) => 2,
+        Some(DiagnosticSeverity::HINT) => 3,
+        Some(_) => 4,
+    }
+}
+
+/**
+    Moves cached diagnostics from the range where they were computed to a new
+    range, including any ranges in related information and code action data.
+
+    The source text of the dependency is the same in both ranges, so
+    every position only moves by the same number of lines, and positions
+    on the first line of the dependency also move by the same number of
+    characters, since only the text before the dependency may differ there.
+*/
+fn relocate_diagnostics(uri: &Url, cached: &CachedDiagnostics, range: Range) -> Vec<Diagnostic> {
+    let from = cached.range.start;
+    let line_delta = i64::from(range.start.line) - i64::from(from.line);
+    let character_delta = i64::from(range.start.character) - i64::from(from.character);
+    let shift = |value: u32, delta: i64| u32::try_from(i64::from(value) + delta).unwrap_or(0);
+    let relocate = |pos: Position| {
+        let line = shift(pos.line, line_delta);
+        if pos.line == from.line {
+            Position::new(line, shift(pos.character, character_delta))
+        } else {
+            Position::new(line, pos.character)
+        }
+    };
+    let relocate_range = |range: Range| Range::new(relocate(range.start), relocate(range.end));
+
+    cached
+        .diagnostics
+        .iter()
+        .cloned()
+        .map(|mut diag| {
+            diag.range = relocate_range(diag.range);
+            if let Some(related) = diag.related_information.as_mut() {
+                for info in related.iter_mut().filter(|info| info.location.uri == *uri) {
+                    info.location.range = relocate_range(info.location.range);
+                }
+            }
+            let context = diag
+                .data
+                .as_ref()
+                .map(ResolveContext::<CodeActionMetadata>::try_from);
+            if let Some(Ok(mut context)) = context {
+                if let Some(edit_range) = context.value.source_edit_range_mut(uri) {
+                    *edit_range = relocate_range(*edit_range);
+                }
+                diag.data = Some(context.into());
+            }
+            diag
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::parser::DependencyKind;
+    use crate::server::DocumentBuilder;
+
+    use super::*;
+
+    fn diagnostic(range: Range) -> Diagnostic {
+        Diagnostic {
+            range,
+            message: String::from("message"),
+            ..Default::default()
+        }
+    }
+
+    fn range(start: (u32, u32), end: (u32, u32)) -> Range {
+        Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1))
+    }
+
+    fn severe(range: Range, severity: DiagnosticSeverity, message: &str) -> Diagnostic {
+        Diagnostic {
+            range,
+            severity: Some(severity),
+            message: message.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn with_data(mut diag: Diagnostic) -> Diagnostic {
+        diag.data = Some(serde_json::json!({ "kind": "test" }));
+        diag
+    }
+
+    #[test]
+    fn test_merge_same_range() {
+        let dep = range((1, 0), (1, 12));
+        let merged = merge_diagnostics(vec![
+            severe(dep, DiagnosticSeverity::INFORMATION, "outdated"),
+            severe(dep, DiagnosticSeverity::WARNING, "deprecated"),
+        ]);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(merged[0].message, "deprecated\n\noutdated");
+    }
+
+    #[test]
+    fn test_merge_tags_and_codes() {
+        let dep = range((1, 0), (1, 12));
+        let code = |code: &str| Some(NumberOrString::String(code.to_string()));
+        let merged = merge_diagnostics(vec![
+            Diagnostic {
+                code: code("outdated"),
+                ..severe(dep, DiagnosticSeverity::INFORMATION, "outdated")
+            },
+            Diagnostic {
+                tags: Some(vec![DiagnosticTag::DEPRECATED]),
+                ..severe(dep, DiagnosticSeverity::WARNING, "deprecated")
+            },
+            Diagnostic {
+                code: code("yanked"),
+                tags: Some(vec![DiagnosticTag::DEPRECATED]),
+                ..severe(dep, DiagnosticSeverity::HINT, "yanked")
+            },
+        ]);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].tags, Some(vec![DiagnosticTag::DEPRECATED]));
+        assert_eq!(merged[0].code, code("outdated"));
+    }
+
+    #[test]
+    fn test_merge_overlapping_ranges() {
+        // Overlapping but different ranges are never merged, only ordered
+        let dep = range((1, 0), (1, 12));
+        let version = range((1, 8), (1, 12));
+        let merged = merge_diagnostics(vec![
+            severe(dep, DiagnosticSeverity::INFORMATION, "outdated"),
+            severe(version, DiagnosticSeverity::ERROR, "yanked"),
+        ]);
+        let messages = merged
+            .iter()
+            .map(|d| d.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(messages, vec!["yanked", "outdated"]);
+    }
+
+    #[test]
+    fn test_merge_keeps_code_actions() {
+        let dep = range((1, 0), (1, 12));
+        let merged = merge_diagnostics(vec![
+            with_data(severe(dep, DiagnosticSeverity::HINT, "first")),
+            with_data(severe(dep, DiagnosticSeverity::INFORMATION, "second")),
+            with_data(severe(dep, DiagnosticSeverity::WARNING, "third")),
+            with_data(severe(dep, DiagnosticSeverity::ERROR, "fourth")),
+        ]);
+        let messages = merged
+            .iter()
+            .map(|d| d.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(messages, vec!["fourth", "third", "second"]);
+        assert!(merged.iter().all(|d| d.data.is_some()));
+    }
+
+    #[test]
+    fn test_relocate_lines() {
+        let uri = Url::parse("file:///Cargo.toml").unwrap();
+        let cached = CachedDiagnostics::new(
+            range((4, 0), (6, 1)),
+            vec![
+                diagnostic(range((4, 6), (4, 13))),
+                diagnostic(range((5, 4), (5, 9))),
+            ],
+        );
+        let relocated = relocate_diagnostics(&uri, &cached, range((2, 0), (4, 1)));
+        assert_eq!(relocated[0].range, range((2, 6), (2, 13)));
+        assert_eq!(relocated[1].range, range((3, 4), (3, 9)));
+    }
+
+    #[test]
+    fn test_relocate_characters() {
+        let uri = Url::parse("file:///Cargo.toml").unwrap();
+        let cached = CachedDiagnostics::new(
+            range((1, 4), (2, 2)),
+            vec![diagnostic(range((1, 10), (2, 1)))],
+        );
+        let relocated = relocate_diagnostics(&uri, &cached, range((3, 0), (4, 2)));
+        assert_eq!(relocated[0].range, range((3, 6), (4, 1)));
+    }
+
+    #[test]
+    fn test_relocate_code_action_data() {
+        let uri = Url::parse("file:///Cargo.toml").unwrap();
+        let mut diag = diagnostic(range((1, 0), (1, 5)));
+        diag.data = Some(
+            ResolveContext {
+                uri: uri.clone(),
+                value: CodeActionMetadata::DidYouMean {
+                    edit_range: range((1, 0), (1, 5)),
+                    source_uri: uri.clone(),
+                    suggestions: vec![String::from("serde")],
+                },
+            }
+            .into(),
+        );
+        let cached = CachedDiagnostics::new(range((1, 0), (1, 12)), vec![diag]);
+        let relocated = relocate_diagnostics(&uri, &cached, range((8, 0), (8, 12)));
+
+        let data = relocated[0].data.as_ref().unwrap();
+        let context = ResolveContext::<CodeActionMetadata>::try_from(data).unwrap();
+        let CodeActionMetadata::DidYouMean { edit_range, .. } = context.value else {
+            panic!("unexpected code action metadata");
+        };
+        assert_eq!(edit_range, range((8, 0), (8, 5)));
+    }
+
+    async fn compute_counted(
+        doc: &Document,
+        computed: &AtomicUsize,
+        result: DependencyDiagnostics,
+    ) -> Vec<Diagnostic> {
+        let dep = range((0, 0), (0, 15));
+        let key = DependencyKey::new(doc, DependencyKind::Default, dep);
+        let fut = async {
+            computed.fetch_add(1, Ordering::SeqCst);
+            Ok(result)
+        };
+        cached_dependency_diagnostics(doc, false, [(key, dep, fut)])
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cached_hit_and_miss() {
+        let doc = DocumentBuilder::new()
+            .with_uri(Url::parse("file:///Cargo.toml").unwrap())
+            .with_text("serde = \"1.0.0\"")
+            .build();
+        let computed = AtomicUsize::new(0);
+        let result = DependencyDiagnostics::from(vec![diagnostic(range((0, 8), (0, 15)))]);
+
+        let first = compute_counted(&doc, &computed, result.clone()).await;
+        let second = compute_counted(&doc, &computed, result.clone()).await;
+        assert_eq!(first, second);
+        assert_eq!(computed.load(Ordering::SeqCst), 1);
+
+        doc.diagnostics_cache().clear();
+        compute_counted(&doc, &computed, result).await;
+        assert_eq!(computed.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cached_skips_incomplete() {
+        let doc = DocumentBuilder::new()
+            .with_uri(Url::parse("file:///Cargo.toml").unwrap())
+            .with_text("serde = \"1.0.0\"")
+            .build();
+        let computed = AtomicUsize::new(0);
+
+        // Diagnostics from a failed fetch are shown, but computed again next time
+        let failed = DependencyDiagnostics::incomplete(Vec::new());
+        assert!(compute_counted(&doc, &computed, failed.clone())
+            .await
+            .is_empty());
+        compute_counted(&doc, &computed, failed).await;
+        assert_eq!(computed.load(Ordering::SeqCst), 2);
+
+        let complete = DependencyDiagnostics::from(vec![diagnostic(range((0, 8), (0, 15)))]);
+        compute_counted(&doc, &computed, complete.clone()).await;
+        compute_counted(&doc, &computed, complete).await;
+        assert_eq!(computed.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use tower_lsp::lsp_types::*;
+
+use super::ResolveContext;
+
+/**
+    Metadata for lazily resolving fields of a completion item,
+    stored in the `data` field of the item until it is resolved.
+*/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum CompletionMetadata {
+    CargoCrate {
+        name: String,
+    },
+    NpmPackage {
+        name: String,
+    },
+    RokitTool {
+        author: String,
+        name: String,
+    },
+    WallyPackage {
+        index_url: String,
+        scope: String,
+        name: String,
+    },
+}
+
+impl CompletionMetadata {
+    pub fn into_data(self, uri: &Url) -> JsonValue {
+        ResolveContext {
+            uri: uri.clone(),
+            value: self,
+        }
+        .into()
+    }
+
+    pub fn from_item(item: &CompletionItem) -> Option<Self> {
+        let context = ResolveContext::<Self>::try_from(item.data.as_ref()?).ok()?;
+        Some(context.into_inner())
+    }
+}
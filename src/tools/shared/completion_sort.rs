@@ -0,0 +1,111 @@
+use std::cmp::Reverse;
+
+use tower_lsp::lsp_types::*;
+
+/**
+    Assigns an explicit sort text to each item, in the order given.
+
+    Clients sort completion items by their sort text, falling back to the
+    label, so this makes sure that the order we pick is always the one shown,
+    and never depends on the order that items were fetched or iterated in.
+*/
+pub fn with_sort_texts(items: impl IntoIterator<Item = CompletionItem>) -> Vec<CompletionItem> {
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| CompletionItem {
+            sort_text: Some(format!("{index:0>5}")),
+            ..item
+        })
+        .collect()
+}
+
+/**
+    Sorts items by relevance, with the most relevant items first, and then
+    alphabetically by label for any items with the same relevance.
+
+    Labels are compared case-insensitively first, and then case-sensitively,
+    so that the order is fully deterministic even for labels like `Foo` / `foo`.
+*/
+pub fn sort_by_relevance(
+    items: impl IntoIterator<Item = (u64, CompletionItem)>,
+) -> Vec<CompletionItem> {
+    let mut items = items.into_iter().collect::<Vec<_>>();
+    items.sort_by_cached_key(|(relevance, item)| {
+        (
+            Reverse(*relevance),
+            item.label.to_ascii_lowercase(),
+            item.label.clone(),
+        )
+    });
+    with_sort_texts(items.into_iter().map(|(_, item)| item))
+}
+
+/**
+    Sorts items alphabetically by label, see `sort_by_relevance` for details.
+*/
+pub fn sort_alphabetically(items: impl IntoIterator<Item = CompletionItem>) -> Vec<CompletionItem> {
+    sort_by_relevance(items.into_iter().map(|item| (0, item)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(label: &str) -> CompletionItem {
+        CompletionItem {
+            label: label.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn labels_and_sort_texts(items: &[CompletionItem]) -> Vec<(&str, &str)> {
+        items
+            .iter()
+            .map(|i| (i.label.as_str(), i.sort_text.as_deref().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_with_sort_texts() {
+        let items = with_sort_texts([item("2.0.0"), item("1.10.0"), item("1.9.0")]);
+        assert_eq!(
+            labels_and_sort_texts(&items),
+            vec![("2.0.0", "00000"), ("1.10.0", "00001"), ("1.9.0", "00002")]
+        );
+    }
+
+    #[test]
+    fn test_sort_alphabetically() {
+        let items =
+            sort_alphabetically([item("serde"), item("Serde"), item("anyhow"), item("tokio")]);
+        assert_eq!(
+            labels_and_sort_texts(&items),
+            vec![
+                ("anyhow", "00000"),
+                ("Serde", "00001"),
+                ("serde", "00002"),
+                ("tokio", "00003"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sort_by_relevance() {
+        let items = sort_by_relevance([
+            (10, item("rand")),
+            (500, item("tokio")),
+            (10, item("anyhow")),
+            (9000, item("serde")),
+        ]);
+        assert_eq!(
+            labels_and_sort_texts(&items),
+            vec![
+                ("serde", "00000"),
+                ("tokio", "00001"),
+                ("anyhow", "00002"),
+                ("rand", "00003"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use tower_lsp::lsp_types::*;
+
+use crate::parser::Node;
+use crate::server::Document;
+
+/**
+    The operators that a version requirement may already start with,
+    in which case no variants with other operators should be offered.
+*/
+const VERSION_OPERATORS: [char; 6] = ['^', '~', '=', '>', '<', '*'];
+
+/**
+    Gets the range of the version to replace when completing a version requirement,
+    which is the first version in it, after any operator, such as `1.2` in `^1.2`.
+
+    Only that version is replaced, so that operators and any other comparators,
+    such as the `<2` in `>=1.2 <2`, are kept exactly as they were typed.
+*/
+pub fn version_segment_range(version: &Node<String>) -> Range {
+    let unquoted = version.unquoted();
+    let rest = unquoted.trim_start_matches(['^', '~', '=', '>', '<', ' ']);
+    let offset = unquoted.len() - rest.len();
+    let len = rest
+        .find(|c: char| c.is_whitespace() || c == ',' || c == '|')
+        .unwrap_or(rest.len());
+    version.unquoted_subrange(offset, len)
+}
+
+/**
+    What a plain version without any operator, such as `1.2.3`, means for a tool.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlainVersion {
+    /** Any compatible version, like in Cargo and Wally, which pin using `=1.2.3` */
+    Caret,
+    /** Only that exact version, like in npm, which allows compatible versions using `^1.2.3` */
+    Exact,
+}
+
+impl PlainVersion {
+    fn detail(self) -> &'static str {
+        match self {
+            Self::Caret => "caret",
+            Self::Exact => "exact",
+        }
+    }
+
+    fn other(self) -> (&'static str, Self) {
+        match self {
+            Self::Caret => ("=", Self::Exact),
+            Self::Exact => ("^", Self::Caret),
+        }
+    }
+}
+
+/**
+    Adds a variant with an explicit operator right after each version completion
+    item, such as `=1.2.3` after `1.2.3` for Cargo, so that both compatible and
+    exact requirements can be picked directly, with details saying which is which.
+
+    Items are left as they are if the current version already starts with an
+    operator, such as `^1.2`, since the user has then already picked one.
+
+    The given range should be that of the full, unquoted version requirement.
+*/
+pub fn with_version_variants(
+    document: &Document,
+    range: Range,
+    current: &str,
+    plain: PlainVersion,
+    items: Vec<CompletionItem>,
+) -> Vec<CompletionItem> {
+    if current.trim_start().starts_with(VERSION_OPERATORS) {
+        return items;
+    }
+
+    let (operator, variant) = plain.other();
+    items
+        .into_iter()
+        .flat_map(|item| {
+            let label = format!("{operator}{}", item.label);
+            let variant_item = CompletionItem {
+                label: label.clone(),
+                detail: Some(variant.detail().to_string()),
+                filter_text: Some(item.label.clone()),
+                text_edit: Some(CompletionTextEdit::Edit(document.create_edit(range, label))),
+                ..item.clone()
+            };
+            let plain_item = CompletionItem {
+                detail: Some(plain.detail().to_string()),
+                ..item
+            };
+            [plain_item, variant_item]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version_node(version: &str) -> Node<String> {
+        let quoted = format!("\"{version}\"");
+        let range = Range::new(
+            Position::new(0, 10),
+            Position::new(0, 10 + quoted.len() as u32),
+        );
+        Node::new_raw(range, quoted)
+    }
+
+    fn segment(version: &str) -> (u32, u32) {
+        let range = version_segment_range(&version_node(version));
+        (range.start.character, range.end.character)
+    }
+
+    #[test]
+    fn test_version_segment_plain() {
+        assert_eq!(segment("1.0.1"), (11, 16));
+        assert_eq!(segment(""), (11, 11));
+        assert_eq!(segment("*"), (11, 12));
+    }
+
+    #[test]
+    fn test_version_segment_operators() {
+        assert_eq!(segment("^1.0"), (12, 15));
+        assert_eq!(segment(">= 1.0"), (14, 17));
+        assert_eq!(segment(">=1.2, <2"), (13, 16));
+        assert_eq!(segment("1.2 || 2"), (11, 14));
+    }
+}
@@ -0,0 +1,190 @@
+use tower_lsp::lsp_types::*;
+
+/**
+    The page documenting every diagnostic, where each code has its own heading.
+*/
+const DIAGNOSTICS_DOCS_URL: &str = concat!(
+    env!("CARGO_PKG_REPOSITORY"),
+    "/blob/main/docs/diagnostics.md"
+);
+
+/**
+    A stable, machine-readable code for each kind of diagnostic, which is
+    the same across all tools for diagnostics that mean the same thing.
+
+    Codes are never renamed, since clients and configs may refer to them.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagnosticCode {
+    MissingField,
+    UnknownPackage,
+    UnknownVersion,
+    Outdated,
+    PartialVersion,
+    Deprecated,
+    UnknownFeature,
+    UnusedOptional,
+    DuplicateTable,
+    ConflictingKeys,
+    UnmatchedMember,
+    MissingInherited,
+    MissingPath,
+    RustVersion,
+    MovingTag,
+    MissingAssets,
+    MissingPlatforms,
+    MisplacedTool,
+    ExceedsLimits,
+    WorkspaceVersion,
+    DuplicateManifest,
+    RegistryAuth,
+    DeniedDependency,
+}
+
+impl DiagnosticCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::MissingField => "missing-field",
+            Self::UnknownPackage => "unknown-package",
+            Self::UnknownVersion => "unknown-version",
+            Self::Outdated => "outdated",
+            Self::PartialVersion => "partial-version",
+            Self::Deprecated => "deprecated",
+            Self::UnknownFeature => "unknown-feature",
+            Self::UnusedOptional => "unused-optional",
+            Self::DuplicateTable => "duplicate-table",
+            Self::ConflictingKeys => "conflicting-keys",
+            Self::UnmatchedMember => "unmatched-member",
+            Self::MissingInherited => "missing-inherited",
+            Self::MissingPath => "missing-path",
+            Self::RustVersion => "rust-version",
+            Self::MovingTag => "moving-tag",
+            Self::MissingAssets => "missing-assets",
+            Self::MissingPlatforms => "missing-platforms",
+            Self::MisplacedTool => "misplaced-tool",
+            Self::ExceedsLimits => "exceeds-limits",
+            Self::WorkspaceVersion => "workspace-version",
+            Self::DuplicateManifest => "duplicate-manifest",
+            Self::RegistryAuth => "registry-auth",
+            Self::DeniedDependency => "denied-dependency",
+        }
+    }
+
+    /**
+        Gets the url of the documentation for this code, which
+        editors show as a clickable link next to the diagnostic.
+    */
+    pub fn description(self) -> Option<CodeDescription> {
+        let href = Url::parse(&format!("{DIAGNOSTICS_DOCS_URL}#{}", self.as_str())).ok()?;
+        Some(CodeDescription { href })
+    }
+
+    /**
+        Creates an otherwise empty diagnostic with this code and its description,
+        meant to be used as the base of a diagnostic, with `..code.diagnostic()`.
+    */
+    pub fn diagnostic(self) -> Diagnostic {
+        Diagnostic {
+            code: Some(NumberOrString::String(self.as_str().to_string())),
+            code_description: self.description(),
+            ..Default::default()
+        }
+    }
+}
+
+impl std::fmt::Display for DiagnosticCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::server::testing::{MockFetcher, TestServer, SERDE_INDEX};
+
+    use super::*;
+
+    const DIAGNOSTICS_DOCS: &str = include_str!("../../../docs/diagnostics.md");
+
+    // NOTE: Every code must be listed here, to be checked
+    const ALL_CODES: [DiagnosticCode; 23] = [
+        DiagnosticCode::MissingField,
+        DiagnosticCode::UnknownPackage,
+        DiagnosticCode::UnknownVersion,
+        DiagnosticCode::Outdated,
+        DiagnosticCode::PartialVersion,
+        DiagnosticCode::Deprecated,
+        DiagnosticCode::UnknownFeature,
+        DiagnosticCode::UnusedOptional,
+        DiagnosticCode::DuplicateTable,
+        DiagnosticCode::ConflictingKeys,
+        DiagnosticCode::UnmatchedMember,
+        DiagnosticCode::MissingInherited,
+        DiagnosticCode::MissingPath,
+        DiagnosticCode::RustVersion,
+        DiagnosticCode::MovingTag,
+        DiagnosticCode::MissingAssets,
+        DiagnosticCode::MissingPlatforms,
+        DiagnosticCode::MisplacedTool,
+        DiagnosticCode::ExceedsLimits,
+        DiagnosticCode::WorkspaceVersion,
+        DiagnosticCode::DuplicateManifest,
+        DiagnosticCode::RegistryAuth,
+        DiagnosticCode::DeniedDependency,
+    ];
+
+    #[test]
+    fn test_codes_unique() {
+        let codes = ALL_CODES.iter().map(|c| c.as_str()).collect::<HashSet<_>>();
+        assert_eq!(codes.len(), ALL_CODES.len());
+    }
+
+    #[test]
+    fn test_codes_documented() {
+        for code in ALL_CODES {
+            let heading = format!("\n## `{code}`\n");
+            assert!(
+                DIAGNOSTICS_DOCS.contains(&heading),
+                "missing docs for diagnostic code `{code}`"
+            );
+            assert!(code.description().is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_diagnostic_codes() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::new(fetcher).await;
+
+        let contents = "[dependencies]\n\
+            serde = \"0.9\"\n\
+            missing = \"1.0\"\n\
+            local = { path = \"../local\", git = \"https://github.com/foo/local\" }\n\
+            \n\
+            [dependencies]\n";
+        let uri = server.open("Cargo.toml", contents).await;
+        let diagnostics = server.diagnostics(&uri).await;
+
+        // Every diagnostic has a code, and a link to the docs for it
+        let codes = diagnostics
+            .iter()
+            .map(|diag| match (&diag.code, &diag.code_description) {
+                (Some(NumberOrString::String(code)), Some(description)) => {
+                    assert!(description.href.as_str().ends_with(&format!("#{code}")));
+                    code.as_str()
+                }
+                _ => panic!("diagnostic without a code: {diag:#?}"),
+            })
+            .collect::<Vec<_>>();
+        for code in [
+            "outdated",
+            "unknown-package",
+            "conflicting-keys",
+            "duplicate-table",
+        ] {
+            assert!(codes.contains(&code), "missing `{code}` in {codes:?}");
+        }
+    }
+}
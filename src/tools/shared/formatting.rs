@@ -0,0 +1,171 @@
+/**
+    Formats a count in a compact, human-readable way.
+
+    For example, `1234` becomes `1.2K` and `5678901` becomes `5.7M`.
+*/
+pub fn format_count(count: u64) -> String {
+    if count < 1_000 {
+        return count.to_string();
+    }
+
+    // Move on to a larger unit when rounding would reach a thousand,
+    // so that `999999` becomes `1.0M` and not `1000K`
+    let mut value = count as f64 / 1_000.0;
+    let mut suffix = "K";
+    for larger in ["M", "B"] {
+        if value < 999.5 {
+            break;
+        }
+        value /= 1_000.0;
+        suffix = larger;
+    }
+
+    if value >= 99.95 {
+        format!("{value:.0}{suffix}")
+    } else {
+        format!("{value:.1}{suffix}")
+    }
+}
+
+/**
+    Parses an RFC 3339 timestamp, such as `2024-01-15T10:20:30.123Z`,
+    into the number of seconds since the unix epoch.
+
+    Fractional seconds and timezone offsets are ignored, since this
+    is only meant to be used for relative, human-readable dates.
+*/
+pub fn parse_timestamp(timestamp: &str) -> Option<i64> {
+    let (date, time) = timestamp.split_once(['T', ' '])?;
+
+    let mut date_parts = date.splitn(3, '-').map(str::parse::<i64>);
+    let year = date_parts.next()?.ok()?;
+    let month = date_parts.next()?.ok()?;
+    let day = date_parts.next()?.ok()?;
+
+    let mut time_parts = time.get(..8)?.splitn(3, ':').map(str::parse::<i64>);
+    let hours = time_parts.next()?.ok()?;
+    let minutes = time_parts.next()?.ok()?;
+    let seconds = time_parts.next()?.ok()?;
+
+    // Days since the epoch for a date in the proleptic gregorian calendar, see:
+    // https://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    Some(days * 86_400 + hours * 3_600 + minutes * 60 + seconds)
+}
+
+/**
+    Formats a timestamp relative to the current time, in a compact way.
+
+    For example, a timestamp from three days ago becomes `3d ago`.
+*/
+pub fn format_time_ago(timestamp: &str) -> Option<String> {
+    let then = parse_timestamp(timestamp)?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs() as i64;
+    Some(format_duration_ago(now.saturating_sub(then).max(0) as u64))
+}
+
+fn format_duration_ago(seconds: u64) -> String {
+    const UNITS: &[(u64, &str)] = &[
+        (365 * 86_400, "y"),
+        (30 * 86_400, "mo"),
+        (86_400, "d"),
+        (3_600, "h"),
+        (60, "m"),
+    ];
+    for &(size, suffix) in UNITS {
+        if seconds >= size {
+            return format!("{}{suffix} ago", seconds / size);
+        }
+    }
+    String::from("just now")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1_000), "1.0K");
+        assert_eq!(format_count(1_234), "1.2K");
+        assert_eq!(format_count(99_960), "100K");
+        assert_eq!(format_count(999_499), "999K");
+        assert_eq!(format_count(999_500), "1.0M");
+        assert_eq!(format_count(999_999), "1.0M");
+        assert_eq!(format_count(1_000_000), "1.0M");
+        assert_eq!(format_count(5_678_901), "5.7M");
+        assert_eq!(format_count(1_000_000_000), "1.0B");
+        assert_eq!(format_count(u64::MAX), "18446744074B");
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(parse_timestamp("1969-12-31T23:59:59Z"), Some(-1));
+        assert_eq!(parse_timestamp("2024-01-01T00:00:00Z"), Some(1_704_067_200));
+        assert_eq!(
+            parse_timestamp("2024-02-29T12:30:15.123456+00:00"),
+            Some(1_709_209_815)
+        );
+        assert_eq!(parse_timestamp("2000-03-01 00:00:00"), Some(951_868_800));
+        assert_eq!(parse_timestamp("2024-01-01"), None);
+        assert_eq!(parse_timestamp("2024-01-01T00:00"), None);
+        assert_eq!(parse_timestamp("not a timestamp"), None);
+    }
+
+    #[test]
+    fn test_parse_timestamp_boundaries() {
+        let day = 86_400;
+        let diff = |a: &str, b: &str| parse_timestamp(b).unwrap() - parse_timestamp(a).unwrap();
+
+        // Ends of months and years, with and without leap days
+        assert_eq!(diff("2023-12-31T23:59:59Z", "2024-01-01T00:00:00Z"), 1);
+        assert_eq!(diff("2023-01-31T00:00:00Z", "2023-02-01T00:00:00Z"), day);
+        assert_eq!(diff("2023-02-28T00:00:00Z", "2023-03-01T00:00:00Z"), day);
+        assert_eq!(
+            diff("2024-02-28T00:00:00Z", "2024-03-01T00:00:00Z"),
+            2 * day
+        );
+        assert_eq!(diff("2100-02-28T00:00:00Z", "2100-03-01T00:00:00Z"), day);
+        assert_eq!(
+            diff("2000-02-28T00:00:00Z", "2000-03-01T00:00:00Z"),
+            2 * day
+        );
+        assert_eq!(
+            diff("2023-01-01T00:00:00Z", "2024-01-01T00:00:00Z"),
+            365 * day
+        );
+        assert_eq!(
+            diff("2024-01-01T00:00:00Z", "2025-01-01T00:00:00Z"),
+            366 * day
+        );
+    }
+
+    #[test]
+    fn test_format_duration_ago() {
+        let day = 86_400;
+        assert_eq!(format_duration_ago(0), "just now");
+        assert_eq!(format_duration_ago(59), "just now");
+        assert_eq!(format_duration_ago(60), "1m ago");
+        assert_eq!(format_duration_ago(3_599), "59m ago");
+        assert_eq!(format_duration_ago(3_600), "1h ago");
+        assert_eq!(format_duration_ago(day - 1), "23h ago");
+        assert_eq!(format_duration_ago(day), "1d ago");
+        assert_eq!(format_duration_ago(30 * day - 1), "29d ago");
+        assert_eq!(format_duration_ago(30 * day), "1mo ago");
+        assert_eq!(format_duration_ago(365 * day - 1), "12mo ago");
+        assert_eq!(format_duration_ago(365 * day), "1y ago");
+        assert_eq!(format_duration_ago(3 * 365 * day), "3y ago");
+    }
+}
@@ -0,0 +1,83 @@
+use tower_lsp::lsp_types::*;
+
+use super::MarkdownBuilder;
+
+/**
+    A hover made out of separate sections, such as the version of a dependency,
+    its description, and its recent releases, which are each built on their own
+    and then joined together, with a blank line between each of them.
+
+    Sections without any content are skipped, so that a section may always be
+    added, even if the information for it turned out to be unavailable.
+*/
+#[derive(Default)]
+pub struct HoverSections {
+    sections: Vec<MarkdownBuilder>,
+}
+
+impl HoverSections {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, section: MarkdownBuilder) {
+        if !section.is_empty() {
+            self.sections.push(section);
+        }
+    }
+
+    pub fn section(&mut self, f: impl FnOnce(&mut MarkdownBuilder)) {
+        let mut md = MarkdownBuilder::new();
+        f(&mut md);
+        self.push(md);
+    }
+
+    pub fn build(self) -> String {
+        let mut md = MarkdownBuilder::new();
+        for (index, section) in self.sections.into_iter().enumerate() {
+            if index > 0 {
+                md.br();
+            }
+            md.append(section);
+        }
+        md.build()
+    }
+
+    /**
+        Builds all sections into a markdown hover, for the given range.
+    */
+    pub fn into_hover(self, range: Range) -> Hover {
+        Hover {
+            range: Some(range),
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: self.build(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sections_joined() {
+        let mut hover = HoverSections::new();
+        hover.section(|md| md.h2("serde"));
+        hover.section(|_| {});
+        hover.section(|md| {
+            md.version("1.0");
+            md.p("Has a build script");
+        });
+        assert_eq!(
+            hover.build(),
+            "## serde\n\nVersion **1.0**\nHas a build script\n"
+        );
+    }
+
+    #[test]
+    fn test_sections_empty() {
+        assert_eq!(HoverSections::new().build(), "\n");
+    }
+}
@@ -0,0 +1,90 @@
+use tower_lsp::lsp_types::*;
+
+use super::super::Lockfile;
+
+/**
+    A dependency to show the locked version of, with its range,
+    its package name, and the version that it was written with.
+*/
+pub struct LockedInlineValue<'a> {
+    pub range: Range,
+    pub name: &'a str,
+    pub version: Option<&'a str>,
+}
+
+/**
+    Creates inline values with the version that each dependency in the given
+    range is locked to, the same version that hovers show as `locked`.
+
+    Dependencies that are not in the lockfile are skipped, such as ones
+    that were added after the lockfile was last updated.
+*/
+pub fn get_locked_inline_values<'a>(
+    lockfile: &Lockfile,
+    dependencies: impl IntoIterator<Item = LockedInlineValue<'a>>,
+    range: Range,
+) -> Vec<InlineValue> {
+    dependencies
+        .into_iter()
+        .filter(|dep| dep.range.start.line <= range.end.line)
+        .filter(|dep| dep.range.end.line >= range.start.line)
+        .filter_map(|dep| {
+            let package = lockfile.find(dep.name, dep.version)?;
+            Some(InlineValue::Text(InlineValueText {
+                range: dep.range,
+                text: format!("locked: {}", package.version),
+            }))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tools::LockedPackage;
+
+    use super::*;
+
+    fn line(line: u32) -> Range {
+        Range::new(Position::new(line, 0), Position::new(line, 10))
+    }
+
+    #[test]
+    fn test_locked_inline_values() {
+        let lockfile = Lockfile {
+            packages: ["1.0.100", "2.0.3"]
+                .into_iter()
+                .map(|version| LockedPackage {
+                    name: String::from("serde"),
+                    version: String::from(version),
+                    dependencies: Vec::new(),
+                })
+                .collect(),
+        };
+        let deps = [
+            LockedInlineValue {
+                range: line(1),
+                name: "serde",
+                version: Some("1"),
+            },
+            LockedInlineValue {
+                range: line(2),
+                name: "tokio",
+                version: Some("1"),
+            },
+            LockedInlineValue {
+                range: line(8),
+                name: "serde",
+                version: Some("2"),
+            },
+        ];
+        let values =
+            get_locked_inline_values(&lockfile, deps, Range::new(line(0).start, line(4).end));
+        assert_eq!(
+            values,
+            vec![InlineValue::Text(InlineValueText {
+                range: line(1),
+                text: String::from("locked: 1.0.100"),
+            })]
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use dashmap::DashSet;
+use tower_lsp::lsp_types::*;
+
+use crate::server::LimitsConfig;
+
+use super::DiagnosticCode;
+
+/**
+    The documents that are above any of the configured limits.
+
+    Large manifests are usually generated or vendored, and fetching data for
+    all of their dependencies can make the server unresponsive, so tools
+    check this before making any requests, and then only do what they can
+    without the network. Everything else, such as local diagnostics,
+    completions of keys, and hovers of local crates, works as usual.
+
+    Kept up to date by `Tools` before each request that may fetch anything,
+    meaning hovers, completions, diagnostics, and analysis.
+*/
+#[derive(Debug, Default, Clone)]
+pub struct LimitedDocuments {
+    inner: Arc<DashSet<Url>>,
+}
+
+impl LimitedDocuments {
+    pub fn contains(&self, uri: &Url) -> bool {
+        self.inner.contains(uri)
+    }
+
+    /**
+        Checks the document at the given uri against the limits, counting
+        its dependencies only if it is not already too large by itself.
+    */
+    pub fn update(
+        &self,
+        limits: &LimitsConfig,
+        uri: &Url,
+        document_size: usize,
+        dependency_count: impl FnOnce() -> usize,
+    ) -> bool {
+        let limited = document_size > limits.max_document_size
+            || dependency_count() > limits.max_dependencies;
+        if limited {
+            self.inner.insert(uri.clone());
+        } else {
+            self.inner.remove(uri);
+        }
+        limited
+    }
+}
+
+/**
+    Creates the single informational diagnostic that explains why
+    nothing was fetched for the dependencies in a large document.
+*/
+pub fn exceeds_limits_diagnostic(limits: &LimitsConfig, source: impl Into<String>) -> Diagnostic {
+    Diagnostic {
+        source: Some(source.into()),
+        range: Range::default(),
+        message: format!(
+            "This manifest is larger than {} bytes or has more than {} dependencies, \
+            so nothing is fetched for its dependencies.\
+            \nThe limits can be changed using the `limits` initialization option",
+            limits.max_document_size, limits.max_dependencies,
+        ),
+        severity: Some(DiagnosticSeverity::INFORMATION),
+        ..DiagnosticCode::ExceedsLimits.diagnostic()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limited_documents() {
+        let limits = LimitsConfig {
+            max_document_size: 10,
+            max_dependencies: 2,
+        };
+        let uri = Url::parse("file:///Cargo.toml").unwrap();
+        let limited = LimitedDocuments::default();
+
+        assert!(!limited.update(&limits, &uri, 10, || 2));
+        assert!(!limited.contains(&uri));
+
+        assert!(limited.update(&limits, &uri, 10, || 3));
+        assert!(limited.contains(&uri));
+
+        // Dependencies are never counted for documents that are too large
+        assert!(limited.update(&limits, &uri, 11, || unreachable!()));
+
+        assert!(!limited.update(&limits, &uri, 0, || 0));
+        assert!(!limited.contains(&uri));
+    }
+}
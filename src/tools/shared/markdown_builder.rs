@@ -1,5 +1,13 @@
 #![allow(dead_code)]
 
+use semver::Version;
+
+use crate::util::Versioned;
+
+use super::format_time_ago;
+
+const MAXIMUM_TIMELINE_ENTRIES: usize = 5;
+
 pub struct MarkdownBuilder {
     lines: Vec<String>,
 }
@@ -37,6 +45,72 @@ impl MarkdownBuilder {
         self.p(format!("Version **{}**", version.to_string()));
     }
 
+    /**
+        Adds the newest published version that matches the version requirement,
+        together with the normalized requirement, as inline code, such as
+        `latest matching: 1.4.2 · requirement: ^1.4`, so that they are easy to
+        copy, or the raw string if the requirement can not be parsed.
+
+        The matching version is only included if it is known. It is not
+        necessarily the version that is used, which is the locked version.
+    */
+    pub fn requirement(&mut self, raw: &str, matching: Option<&Version>) {
+        match raw.parse_version_req() {
+            Ok(req) => match matching {
+                Some(matching) => self.p(format!(
+                    "latest matching: `{matching}` · requirement: `{req}`"
+                )),
+                None => self.p(format!("requirement: `{req}`")),
+            },
+            Err(_) => self.p(format!("unparsed: `{raw}`")),
+        }
+    }
+
+    /**
+        Adds the version locked in a lockfile as inline code, such as `locked: 1.4.0`.
+    */
+    pub fn locked(&mut self, version: &str) {
+        self.p(format!("locked: `{version}`"));
+    }
+
+    /**
+        Adds a compact timeline of versions, such as `1.4.0 · 3d ago, 1.3.2 · 2mo ago`.
+
+        Entries are expected to be sorted from newest to oldest, and any
+        entries without a parseable timestamp are shown without a date.
+        Only the first few entries are shown, to keep hovers compact.
+    */
+    pub fn timeline<V, T>(&mut self, entries: impl IntoIterator<Item = (V, T)>)
+    where
+        V: AsRef<str>,
+        T: AsRef<str>,
+    {
+        let formatted = entries
+            .into_iter()
+            .take(MAXIMUM_TIMELINE_ENTRIES)
+            .map(
+                |(version, timestamp)| match format_time_ago(timestamp.as_ref()) {
+                    Some(ago) => format!("`{}` · {ago}", version.as_ref()),
+                    None => format!("`{}`", version.as_ref()),
+                },
+            )
+            .collect::<Vec<_>>();
+        if !formatted.is_empty() {
+            self.p(format!("Recent releases: {}", formatted.join(", ")));
+        }
+    }
+
+    /**
+        Adds all lines of another builder to the end of this one.
+    */
+    pub fn append(&mut self, other: MarkdownBuilder) {
+        self.lines.extend(other.lines);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
     pub fn extend_last(&mut self, suffix: impl AsRef<str>) {
         let last = self.lines.last_mut().unwrap();
         last.push_str(suffix.as_ref());
@@ -47,3 +121,31 @@ impl MarkdownBuilder {
         self.lines.join("\n")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn built(f: impl FnOnce(&mut MarkdownBuilder)) -> String {
+        let mut md = MarkdownBuilder::new();
+        f(&mut md);
+        md.build()
+    }
+
+    #[test]
+    fn test_requirement() {
+        let matching = Version::new(1, 4, 2);
+        assert_eq!(
+            built(|md| md.requirement("1.4", Some(&matching))),
+            "latest matching: `1.4.2` · requirement: `^1.4`\n"
+        );
+        assert_eq!(
+            built(|md| md.requirement(">= 1.2, <2", None)),
+            "requirement: `>=1.2, <2`\n"
+        );
+        assert_eq!(
+            built(|md| md.requirement("latest", None)),
+            "unparsed: `latest`\n"
+        );
+    }
+}
@@ -0,0 +1,307 @@
+use tower_lsp::lsp_types::*;
+
+use crate::parser::Node;
+use crate::server::Document;
+
+/**
+    An entry that is being typed on a line of its own, such as `serde`
+    or `rojo = "rojo-rbx/rojo"`, found by looking at the text of the line,
+    since an entry that is still incomplete may not parse as a pair at all.
+
+    Contains the key, the quoted value if there is one,
+    and the header of the table that the line is in, if any.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsectionedEntry {
+    pub key: Node<String>,
+    pub value: Option<Node<String>>,
+    pub table: Option<String>,
+}
+
+/**
+    Finds the entry being typed on the line of the given position, if the
+    line is a bare key, or a key with a string value that has both quotes.
+*/
+pub fn find_unsectioned_entry(doc: &Document, pos: Position) -> Option<UnsectionedEntry> {
+    let text = doc.as_str();
+    let line_start = doc.position_to_offset(Position::new(pos.line, 0));
+    let line = text[line_start..].split('\n').next()?;
+    let line = line.strip_suffix('\r').unwrap_or(line);
+
+    let key_start = line.len() - line.trim_start().len();
+    let key_len = line[key_start..]
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        .unwrap_or(line.len() - key_start);
+    if key_len == 0 {
+        return None;
+    }
+    let key_end = key_start + key_len;
+    let node_at = |start: usize, end: usize| {
+        let range = doc.range_from_span((line_start + start)..(line_start + end));
+        Node::new_raw(range, line[start..end].to_string())
+    };
+    let key = node_at(key_start, key_end);
+
+    let rest = line[key_end..].trim();
+    let value = if rest.is_empty() {
+        None
+    } else {
+        let raw = rest.strip_prefix('=')?.trim();
+        let quote = raw.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+        if raw.len() < 2 || !raw.ends_with(quote) || raw[1..raw.len() - 1].contains(quote) {
+            return None;
+        }
+        let value_start = line.trim_end().len() - raw.len();
+        Some(node_at(value_start, value_start + raw.len()))
+    };
+
+    // The table is the closest header above, since the line itself may not parse
+    let table = text[..line_start]
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|l| l.starts_with('['))
+        .map(|l| {
+            l.trim_start_matches('[')
+                .split(']')
+                .next()
+                .unwrap_or_default()
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect()
+        });
+
+    Some(UnsectionedEntry { key, value, table })
+}
+
+/**
+    Creates an edit that inserts the given table header right above
+    the given line, separated from any text before it by an empty line.
+*/
+pub fn section_header_edit(doc: &Document, line: u32, header: &str) -> TextEdit {
+    if line == 0 {
+        return TextEdit {
+            range: Range::default(),
+            new_text: format!("[{header}]\n"),
+        };
+    }
+
+    let above = doc.clamp_position(Position::new(line - 1, u32::MAX));
+    let blank_above = doc
+        .as_str()
+        .split('\n')
+        .nth(above.line as usize)
+        .is_some_and(|l| l.trim().is_empty());
+    let separator = if blank_above { "\n" } else { "\n\n" };
+    TextEdit {
+        range: Range::new(above, above),
+        new_text: format!("{separator}[{header}]"),
+    }
+}
+
+/**
+    Adds the given edit to every item of a completion response,
+    to be applied together with whichever item is accepted.
+*/
+pub fn with_additional_edit(response: CompletionResponse, edit: TextEdit) -> CompletionResponse {
+    let add = |items: Vec<CompletionItem>| {
+        items
+            .into_iter()
+            .map(|mut item| {
+                item.additional_text_edits
+                    .get_or_insert_with(Vec::new)
+                    .push(edit.clone());
+                item
+            })
+            .collect::<Vec<_>>()
+    };
+    match response {
+        CompletionResponse::Array(items) => CompletionResponse::Array(add(items)),
+        CompletionResponse::List(list) => CompletionResponse::List(CompletionList {
+            items: add(list.items),
+            ..list
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::testing::{cursor_fixture, MockFetcher, TestServer};
+    use crate::server::{Config, DocumentBuilder, LimitsConfig};
+
+    use super::*;
+
+    fn doc(text: &str) -> Document {
+        DocumentBuilder::new()
+            .with_uri(Url::parse("file:///Cargo.toml").unwrap())
+            .with_text(text)
+            .build()
+    }
+
+    #[test]
+    fn test_find_unsectioned_entry() {
+        let d = doc("[package]\nname = \"foo\"\n\n  serde\n");
+        let entry = find_unsectioned_entry(&d, Position::new(3, 4)).unwrap();
+        assert_eq!(entry.key.unquoted(), "serde");
+        assert_eq!(
+            entry.key.range,
+            Range::new(Position::new(3, 2), Position::new(3, 7))
+        );
+        assert_eq!(entry.value, None);
+        assert_eq!(entry.table.as_deref(), Some("package"));
+
+        let d = doc("rojo = \"rojo-rbx/ro\"");
+        let entry = find_unsectioned_entry(&d, Position::new(0, 15)).unwrap();
+        let value = entry.value.unwrap();
+        assert_eq!(value.unquoted(), "rojo-rbx/ro");
+        assert_eq!(
+            value.range,
+            Range::new(Position::new(0, 7), Position::new(0, 20))
+        );
+        assert_eq!(entry.table, None);
+    }
+
+    #[test]
+    fn test_find_unsectioned_entry_none() {
+        for text in [
+            "",
+            "[package]",
+            "a = 1",
+            "a = \"b",
+            "a = { b = \"c\" }",
+            "a b",
+        ] {
+            assert_eq!(
+                find_unsectioned_entry(&doc(text), Position::new(0, 1)),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn test_section_header_edit() {
+        let d = doc("[package]\nname = \"foo\"\nserde");
+        let edit = section_header_edit(&d, 2, "dependencies");
+        assert_eq!(edit.range.start, Position::new(1, 12));
+        assert_eq!(edit.new_text, "\n\n[dependencies]");
+
+        let d = doc("[package]\n\nserde");
+        let edit = section_header_edit(&d, 2, "dependencies");
+        assert_eq!(edit.range.start, Position::new(1, 0));
+        assert_eq!(edit.new_text, "\n[dependencies]");
+
+        let edit = section_header_edit(&doc("rojo = \"\""), 0, "tools");
+        assert_eq!(edit.range, Range::default());
+        assert_eq!(edit.new_text, "[tools]\n");
+    }
+
+    #[tokio::test]
+    async fn test_completion_missing_section() {
+        let server = TestServer::new(MockFetcher::new()).await;
+
+        // Crate names typed below [package] also add the missing [dependencies] table
+        let (contents, pos) = cursor_fixture("[package]\nname = \"foo\"\n\nserde_j$0\n");
+        let uri = server.open("Cargo.toml", &contents).await;
+        let items = server.completion(&uri, pos).await;
+        assert!(items.iter().any(|i| i.label == "serde_json"));
+        for item in &items {
+            let edits = item.additional_text_edits.as_ref().unwrap();
+            assert_eq!(edits.len(), 1);
+            assert_eq!(edits[0].range.start, Position::new(2, 0));
+            assert_eq!(edits[0].new_text, "\n[dependencies]");
+        }
+
+        // ... but not if the table exists, or the line is in another dependency table
+        let (contents, pos) =
+            cursor_fixture("[package]\nname = \"foo\"\nserde_j$0\n\n[dependencies]\n");
+        let uri = server.open("existing/Cargo.toml", &contents).await;
+        assert!(server.completion(&uri, pos).await.is_empty());
+        let (contents, pos) = cursor_fixture("[dev-dependencies]\nserde_j$0\n");
+        let uri = server.open("dev/Cargo.toml", &contents).await;
+        assert!(server
+            .completion(&uri, pos)
+            .await
+            .iter()
+            .all(|i| i.additional_text_edits.is_none()));
+
+        // Tools typed in an empty Rokit manifest add the [tools] table
+        let repos = r#"[
+            {"name": "rojo", "full_name": "rojo-rbx/rojo", "description": null, "stargazers_count": 900}
+        ]"#;
+        let fetcher = MockFetcher::new().with_response("/users/rojo-rbx/repos", repos);
+        let server = TestServer::new(fetcher).await;
+        let (contents, pos) = cursor_fixture("rojo = \"rojo-rbx/ro$0\"\n");
+        let uri = server.open("rokit.toml", &contents).await;
+        let items = server.completion(&uri, pos).await;
+        assert_eq!(items.len(), 1);
+        let edits = items[0].additional_text_edits.as_ref().unwrap();
+        assert_eq!(edits[0].range, Range::default());
+        assert_eq!(edits[0].new_text, "[tools]\n");
+    }
+
+    #[tokio::test]
+    async fn test_completion_missing_section_wally_realm() {
+        let tree =
+            |sha: &str, nodes: &str| format!(r#"{{"sha": "{sha}", "url": "", "tree": [{nodes}]}}"#);
+        let fetcher = MockFetcher::new()
+            .with_response(
+                "/contents/config.json",
+                r#"{"api": "https://api.wally.run"}"#,
+            )
+            .with_response(
+                "/git/trees/main",
+                tree(
+                    "main",
+                    r#"{"sha": "scope", "url": "", "type": "tree", "path": "roblox"}"#,
+                ),
+            )
+            .with_response(
+                "/git/trees/scope",
+                tree(
+                    "scope",
+                    r#"{"sha": "a", "url": "", "type": "blob", "path": "roact"}"#,
+                ),
+            );
+        let server = TestServer::new(fetcher).await;
+
+        // Packages of the server realm put their first dependency in [server-dependencies]
+        let (contents, pos) = cursor_fixture(
+            "[package]\nname = \"a/b\"\nrealm = \"server\"\n\nRoact = \"roblox/ro$0\"\n",
+        );
+        let uri = server.open("wally.toml", &contents).await;
+        let items = server.completion(&uri, pos).await;
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].label, "roact");
+        let edits = items[0].additional_text_edits.as_ref().unwrap();
+        assert_eq!(edits[0].range.start, Position::new(3, 0));
+        assert_eq!(edits[0].new_text, "\n[server-dependencies]");
+
+        // ... and package fields are never completed as dependencies
+        let (contents, pos) = cursor_fixture("[package]\nname = \"a/b\"\nregistry = \"ro$0\"\n");
+        let uri = server.open("fields/wally.toml", &contents).await;
+        assert!(server.completion(&uri, pos).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_completion_missing_section_package_fields() {
+        let server = TestServer::new(MockFetcher::new()).await;
+
+        // Fields of [package] look like crate names, but never get completed as them
+        let (contents, pos) = cursor_fixture("[package]\nname = \"foo\"\nedition$0\n");
+        let uri = server.open("Cargo.toml", &contents).await;
+        assert!(server.completion(&uri, pos).await.is_empty());
+
+        // The same goes for manifests that are over the limits
+        let config = server.config().get();
+        server.config().set(Config {
+            limits: LimitsConfig {
+                max_document_size: 8,
+                ..config.limits
+            },
+            ..config
+        });
+        let (contents, pos) = cursor_fixture("[package]\nname = \"foo\"\n\nserde_j$0\n");
+        let uri = server.open("limited/Cargo.toml", &contents).await;
+        assert!(server.completion(&uri, pos).await.is_empty());
+    }
+}
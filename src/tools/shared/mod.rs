@@ -1,11 +1,41 @@
 mod actions;
+mod cached_diagnostics;
 mod completion_map;
+mod completion_metadata;
+mod completion_sort;
+mod completion_versions;
+mod diagnostic_codes;
+mod formatting;
+mod hover_sections;
+mod inline_values;
+mod limits;
 mod markdown_builder;
+mod missing_section;
+mod paths;
+mod plaintext;
+mod policy;
 mod resolve_context;
 mod strsim;
+mod suggestions;
+mod wildcards;
 
 pub use actions::*;
+pub use cached_diagnostics::*;
 pub use completion_map::*;
+pub use completion_metadata::*;
+pub use completion_sort::*;
+pub use completion_versions::*;
+pub use diagnostic_codes::*;
+pub use formatting::*;
+pub use hover_sections::*;
+pub use inline_values::*;
+pub use limits::*;
 pub use markdown_builder::*;
+pub use missing_section::*;
+pub use paths::*;
+pub use plaintext::*;
+pub use policy::*;
 pub use resolve_context::*;
 pub use strsim::*;
+pub use suggestions::*;
+pub use wildcards::*;
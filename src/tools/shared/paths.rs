@@ -0,0 +1,68 @@
+use std::path::{Component, Path, PathBuf};
+
+/**
+    Lexically normalizes a path, removing any `.` and `..` components,
+    so that paths to the same crate can be compared without touching
+    the file system, which canonicalizing the path would require.
+*/
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/**
+    Resolves the `path` of a local dependency, relative to the directory of
+    the manifest that declares it, into the path of the dependency's manifest.
+
+    The path always points at a directory, and the manifest file of the tool,
+    such as `Cargo.toml`, is appended to it. Cargo does not accept paths to the
+    manifest file itself, and fails to read `../foo/Cargo.toml/Cargo.toml`
+    for a path such as `../foo/Cargo.toml`, so this does the same.
+*/
+pub fn resolve_manifest_path(manifest_dir: &Path, path: &str, manifest_name: &str) -> PathBuf {
+    normalize_path(&manifest_dir.join(path)).join(manifest_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(
+            normalize_path(Path::new("/a/b/./../c/d/..")),
+            PathBuf::from("/a/c")
+        );
+        // Parent directories never go above the root
+        assert_eq!(normalize_path(Path::new("/a/../../b")), PathBuf::from("/b"));
+        assert_eq!(normalize_path(Path::new("/a/b/")), PathBuf::from("/a/b"));
+    }
+
+    #[test]
+    fn test_resolve_manifest_path() {
+        let dir = Path::new("/nonexistent/crate");
+        assert_eq!(
+            resolve_manifest_path(dir, "../foo", "Cargo.toml"),
+            PathBuf::from("/nonexistent/foo/Cargo.toml")
+        );
+        assert_eq!(
+            resolve_manifest_path(dir, "./packages/bar/", "wally.toml"),
+            PathBuf::from("/nonexistent/crate/packages/bar/wally.toml")
+        );
+
+        // Paths to the manifest itself are directories that don't exist
+        assert_eq!(
+            resolve_manifest_path(dir, "../foo/Cargo.toml", "Cargo.toml"),
+            PathBuf::from("/nonexistent/foo/Cargo.toml/Cargo.toml")
+        );
+    }
+}
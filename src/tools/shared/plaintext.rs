@@ -0,0 +1,141 @@
+use tower_lsp::lsp_types::*;
+
+/**
+    Converts a markdown hover into a plain text hover, for clients that
+    can not render markdown, and would otherwise show it as raw markup.
+*/
+pub fn into_plaintext_hover(hover: Hover) -> Hover {
+    let contents = match hover.contents {
+        HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }) => HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::PlainText,
+            value: markdown_to_plaintext(&value),
+        }),
+        other => other,
+    };
+    Hover { contents, ..hover }
+}
+
+/**
+    Strips the markdown that hovers are made out of down to plain text.
+
+    Headings become plain lines, emphasis and inline code lose their markers,
+    code fences are removed while keeping their contents, and links are written
+    out as `text (url)`, so that they can still be followed by copying them.
+*/
+pub fn markdown_to_plaintext(markdown: &str) -> String {
+    markdown
+        .split('\n')
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .map(|line| {
+            let heading = line.trim_start_matches('#');
+            let line = if heading.len() < line.len() && heading.starts_with(' ') {
+                heading.trim_start()
+            } else {
+                line
+            };
+            strip_inline(line)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn strip_inline(line: &str) -> String {
+    let mut stripped = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find('[') {
+        let after = &rest[start + 1..];
+        let link = after.find("](").and_then(|mid| {
+            let end = after[mid + 2..].find(')')?;
+            Some((
+                &after[..mid],
+                &after[mid + 2..mid + 2 + end],
+                mid + 2 + end + 1,
+            ))
+        });
+        let Some((text, url, len)) = link else {
+            stripped.push_str(&rest[..=start]);
+            rest = after;
+            continue;
+        };
+        stripped.push_str(&rest[..start]);
+        if text == url {
+            stripped.push_str(url);
+        } else {
+            stripped.push_str(&format!("{text} ({url})"));
+        }
+        rest = &after[len..];
+    }
+    stripped.push_str(rest);
+    stripped.replace("**", "").replace('`', "")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::testing::{cursor_fixture, MockFetcher, TestServer, SERDE_INDEX};
+
+    use super::*;
+
+    #[test]
+    fn test_plaintext_headings_and_emphasis() {
+        assert_eq!(
+            markdown_to_plaintext("## serde\n\nVersion **1.0**\nrequirement: `^1.0`\n"),
+            "serde\n\nVersion 1.0\nrequirement: ^1.0\n"
+        );
+        // Only actual headings lose their markers
+        assert_eq!(markdown_to_plaintext("#[derive]"), "#[derive]");
+    }
+
+    #[test]
+    fn test_plaintext_links() {
+        assert_eq!(
+            markdown_to_plaintext("- [Documentation](https://docs.rs/serde)"),
+            "- Documentation (https://docs.rs/serde)"
+        );
+        assert_eq!(
+            markdown_to_plaintext("[https://serde.rs](https://serde.rs) and [not a link]"),
+            "https://serde.rs and [not a link]"
+        );
+    }
+
+    #[test]
+    fn test_plaintext_code_fences() {
+        assert_eq!(
+            markdown_to_plaintext("```toml\nserde = \"1.0\"\n```"),
+            "serde = \"1.0\""
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hover_plaintext() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let capabilities = ClientCapabilities {
+            text_document: Some(TextDocumentClientCapabilities {
+                hover: Some(HoverClientCapabilities {
+                    content_format: Some(vec![MarkupKind::PlainText, MarkupKind::Markdown]),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let server = TestServer::with_capabilities(fetcher, capabilities).await;
+
+        let (contents, pos) = cursor_fixture("[dependencies]\nser$0de = \"1.0\"\n");
+        let uri = server.open("Cargo.toml", &contents).await;
+        let hover = server
+            .hover_response(&uri, pos)
+            .await
+            .expect("missing hover");
+        let HoverContents::Markup(markup) = hover.contents else {
+            panic!("unexpected hover contents: {:?}", hover.contents);
+        };
+        assert_eq!(markup.kind, MarkupKind::PlainText);
+        assert!(markup.value.starts_with("serde\n"));
+        assert!(markup.value.contains("Version 1.0"));
+        assert!(!markup.value.contains("## "));
+        assert!(!markup.value.contains(['*', '`']));
+    }
+}
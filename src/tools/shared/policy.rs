@@ -0,0 +1,207 @@
+use tower_lsp::lsp_types::*;
+
+use crate::server::Config;
+use crate::tools::ToolName;
+
+use super::{wildcard_matches, DiagnosticCode};
+
+/**
+    Creates an error diagnostic for a dependency that is not permitted by the
+    `denied_dependencies` or `allowed_dependencies` in the config, if any,
+    where only the allowed dependencies of the given tool are ever used.
+
+    Names are matched case-insensitively, and a dependency that is denied
+    is never permitted, even if it is also allowed. Only the name is ever
+    checked, so this needs no network requests, and is always up to date.
+*/
+pub fn get_policy_diagnostic(
+    config: &Config,
+    tool: ToolName,
+    name: &str,
+    range: Range,
+    source: impl Into<String>,
+) -> Option<Diagnostic> {
+    let matches =
+        |pattern: &str| wildcard_matches(&pattern.to_ascii_lowercase(), &name.to_ascii_lowercase());
+
+    let denied = config
+        .denied_dependencies
+        .iter()
+        .find(|rule| matches(rule.name()));
+    let allowed = match tool {
+        ToolName::Cargo => config.allowed_dependencies.cargo.as_ref(),
+        ToolName::Aftman | ToolName::Rokit => config.allowed_dependencies.rokit.as_ref(),
+        ToolName::Wally => config.allowed_dependencies.wally.as_ref(),
+        ToolName::Npm => None,
+    };
+    let is_allowed = allowed.is_none_or(|allowed| allowed.iter().any(|rule| matches(rule.name())));
+
+    let message = match denied {
+        Some(rule) => match rule.message() {
+            Some(message) => format!("`{name}` is not permitted by policy.\n{message}"),
+            None => format!("`{name}` is not permitted by policy"),
+        },
+        None if !is_allowed => {
+            format!("`{name}` is not permitted by policy, since it is not an allowed dependency")
+        }
+        None => return None,
+    };
+
+    Some(Diagnostic {
+        source: Some(source.into()),
+        range,
+        message,
+        severity: Some(DiagnosticSeverity::ERROR),
+        ..DiagnosticCode::DeniedDependency.diagnostic()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::testing::{MockFetcher, TestServer, SERDE_INDEX};
+    use crate::server::{AllowedDependencies, Config, DependencyRule};
+
+    use super::*;
+
+    fn message(config: &Config, name: &str) -> Option<String> {
+        get_policy_diagnostic(config, ToolName::Cargo, name, Range::default(), "Cargo")
+            .map(|d| d.message)
+    }
+
+    #[test]
+    fn test_policy_denied() {
+        let config = Config {
+            denied_dependencies: vec![
+                DependencyRule::Name(String::from("openssl*")),
+                DependencyRule::WithMessage {
+                    name: String::from("Roblox/Roact"),
+                    message: Some(String::from("Use react-lua instead")),
+                },
+            ],
+            ..Config::default()
+        };
+        assert_eq!(
+            message(&config, "openssl-sys").as_deref(),
+            Some("`openssl-sys` is not permitted by policy")
+        );
+        assert_eq!(
+            message(&config, "roblox/roact").as_deref(),
+            Some("`roblox/roact` is not permitted by policy.\nUse react-lua instead")
+        );
+        assert_eq!(message(&config, "serde"), None);
+    }
+
+    #[test]
+    fn test_policy_allowed() {
+        let config = Config {
+            allowed_dependencies: AllowedDependencies {
+                cargo: Some(vec![DependencyRule::Name(String::from("serde*"))]),
+                ..AllowedDependencies::default()
+            },
+            denied_dependencies: vec![DependencyRule::Name(String::from("serde_yaml"))],
+            ..Config::default()
+        };
+        assert_eq!(message(&config, "serde_json"), None);
+        assert!(message(&config, "tokio").is_some());
+
+        // Denied dependencies are never permitted, even if they are allowed
+        assert_eq!(
+            message(&config, "serde_yaml").as_deref(),
+            Some("`serde_yaml` is not permitted by policy")
+        );
+
+        // Allowed crates don't say anything about packages in other ecosystems
+        let wally =
+            get_policy_diagnostic(&config, ToolName::Wally, "tokio", Range::default(), "Wally");
+        assert_eq!(wally, None);
+    }
+
+    #[tokio::test]
+    async fn test_denied_dependencies() {
+        let fetcher = MockFetcher::new().with_response("/se/rd/serde", SERDE_INDEX);
+        let server = TestServer::new(fetcher).await;
+        let config = server.config().get();
+        server.config().set(Config {
+            denied_dependencies: vec![DependencyRule::WithMessage {
+                name: String::from("openssl*"),
+                message: Some(String::from("Use rustls instead")),
+            }],
+            ..config
+        });
+
+        // Renamed crates are checked using the name of the crate itself
+        let contents = "[dependencies]\n\
+            serde = \"1.0\"\n\
+            tls = { package = \"openssl\", version = \"0.10\" }\n";
+        let uri = server.open("Cargo.toml", contents).await;
+        let denied = server
+            .diagnostics(&uri)
+            .await
+            .into_iter()
+            .filter(|d| d.code == Some(NumberOrString::String(String::from("denied-dependency"))))
+            .collect::<Vec<_>>();
+        assert_eq!(denied.len(), 1);
+        assert_eq!(denied[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(
+            denied[0].message,
+            "`openssl` is not permitted by policy.\nUse rustls instead"
+        );
+        assert_eq!(
+            denied[0].range,
+            Range::new(Position::new(2, 18), Position::new(2, 27))
+        );
+
+        // Tools are checked using their full `author/name`
+        let contents = "[tools]\nrojo = \"rojo-rbx/rojo@7.4.1\"\n";
+        let uri = server.open("rokit.toml", contents).await;
+        let config = server.config().get();
+        server.config().set(Config {
+            allowed_dependencies: AllowedDependencies {
+                rokit: Some(vec![DependencyRule::Name(String::from("lune-org/*"))]),
+                ..AllowedDependencies::default()
+            },
+            ..config
+        });
+        let diagnostics = server.diagnostics(&uri).await;
+        assert!(diagnostics.iter().any(|d| d
+            .message
+            .starts_with("`rojo-rbx/rojo` is not permitted by policy")));
+    }
+
+    #[tokio::test]
+    async fn test_allowed_dependencies_per_ecosystem() {
+        let server = TestServer::new(MockFetcher::new()).await;
+        let config = server.config().get();
+        server.config().set(Config {
+            allowed_dependencies: AllowedDependencies {
+                cargo: Some(vec![DependencyRule::Name(String::from("serde*"))]),
+                ..AllowedDependencies::default()
+            },
+            ..config
+        });
+        let denied = |diagnostics: Vec<Diagnostic>| {
+            diagnostics
+                .into_iter()
+                .filter(|d| {
+                    d.code == Some(NumberOrString::String(String::from("denied-dependency")))
+                })
+                .map(|d| d.message)
+                .collect::<Vec<_>>()
+        };
+
+        let contents = "[dependencies]\nserde = \"1.0\"\ntokio = \"1\"\n";
+        let uri = server.open("Cargo.toml", contents).await;
+        assert_eq!(
+            denied(server.diagnostics(&uri).await),
+            vec!["`tokio` is not permitted by policy, since it is not an allowed dependency"]
+        );
+
+        // Allowed crates must not flag packages and tools, which have no list
+        let contents = "[dependencies]\nRoact = \"roblox/roact@1.4.4\"\n";
+        let uri = server.open("wally.toml", contents).await;
+        assert!(denied(server.diagnostics(&uri).await).is_empty());
+        let contents = "[tools]\nrojo = \"rojo-rbx/rojo@7.4.1\"\n";
+        let uri = server.open("rokit.toml", contents).await;
+        assert!(denied(server.diagnostics(&uri).await).is_empty());
+    }
+}
@@ -22,6 +22,37 @@ where
     }
 }
 
+/**
+    Finds the options that are closest to `current`, within a small
+    edit distance, sorted by how close they are and limited to `limit`.
+
+    Options are compared case-insensitively, and exact matches are
+    never included, since those are not useful as suggestions.
+*/
+pub fn closest_matches<S1, S2, I>(current: S1, options: I, limit: usize) -> Vec<String>
+where
+    S1: Into<String>,
+    S2: Into<String>,
+    I: IntoIterator<Item = S2>,
+{
+    let current: String = current.into().to_ascii_lowercase();
+    let max_distance = (current.chars().count() / 3).clamp(1, 3);
+
+    let mut matches = options
+        .into_iter()
+        .map(|s| s.into())
+        .filter_map(|s: String| {
+            let distance = strsim::levenshtein(&current, &s.to_ascii_lowercase());
+            (distance > 0 && distance <= max_distance).then_some((distance, s))
+        })
+        .collect::<Vec<_>>();
+
+    matches.sort();
+    matches.dedup_by(|(_, a), (_, b)| a.eq_ignore_ascii_case(b));
+    matches.truncate(limit);
+    matches.into_iter().map(|(_, s)| s).collect()
+}
+
 pub fn filter_starts_with<S1, S2>(s: S1, prefix: S2) -> bool
 where
     S1: Into<String>,
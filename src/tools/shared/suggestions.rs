@@ -0,0 +1,46 @@
+use tower_lsp::lsp_types::*;
+
+use super::{closest_matches, CodeActionMetadata, ResolveContext};
+
+/**
+    The maximum number of "did you mean" suggestions to offer.
+*/
+pub const MAXIMUM_SUGGESTIONS: usize = 3;
+
+/**
+    Adds "did you mean" suggestions to a diagnostic for something that does not
+    exist, picking the closest matches from the given options, if there are any.
+
+    The best suggestion is mentioned in the message, and all suggestions are
+    attached as quick fixes which replace the text at the given edit range.
+*/
+pub fn with_suggestions<S, I>(
+    mut diag: Diagnostic,
+    uri: &Url,
+    edit_range: Range,
+    current: &str,
+    options: I,
+) -> Diagnostic
+where
+    S: Into<String>,
+    I: IntoIterator<Item = S>,
+{
+    let suggestions = closest_matches(current, options, MAXIMUM_SUGGESTIONS);
+    let Some(best) = suggestions.first() else {
+        return diag;
+    };
+
+    diag.message.push_str(&format!("\nDid you mean `{best}`?"));
+    diag.data = Some(
+        ResolveContext {
+            uri: uri.clone(),
+            value: CodeActionMetadata::DidYouMean {
+                edit_range,
+                source_uri: uri.clone(),
+                suggestions,
+            },
+        }
+        .into(),
+    );
+    diag
+}
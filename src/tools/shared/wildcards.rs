@@ -0,0 +1,46 @@
+/**
+    Checks if the text matches a pattern where `*` matches any
+    number of characters, and `?` matches any single character.
+*/
+pub fn wildcard_matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+
+    // Classic dynamic programming approach, where each entry in
+    // the row is if the pattern so far matches the text up to it
+    let mut row = vec![false; text.len() + 1];
+    row[0] = true;
+    for p in pattern {
+        let mut next = vec![false; text.len() + 1];
+        if p == '*' {
+            next[0] = row[0];
+        }
+        for (i, t) in text.iter().enumerate() {
+            next[i + 1] = match p {
+                '*' => row[i + 1] || next[i],
+                '?' => row[i],
+                _ => row[i] && p == *t,
+            };
+        }
+        row = next;
+    }
+    row[text.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcard_matches() {
+        assert!(wildcard_matches("crates/*", "crates/foo"));
+        assert!(wildcard_matches("crates/*", "crates/"));
+        assert!(wildcard_matches("*", ""));
+        assert!(!wildcard_matches("crates/*", "other/foo"));
+        assert!(!wildcard_matches("crates/foo", "crates/foo/bar"));
+        assert!(wildcard_matches("openssl*", "openssl"));
+        assert!(wildcard_matches("?ibc", "libc"));
+        assert!(!wildcard_matches("?ibc", "ibc"));
+        assert!(!wildcard_matches("serde", "serde_json"));
+    }
+}
@@ -1,6 +1,8 @@
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 
+use super::{AnalyzeParams, AnalyzeResult, ManifestInfo, ManifestInfoParams};
+
 #[tower_lsp::async_trait]
 pub trait Tool: Send + Sync {
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
@@ -8,6 +10,18 @@ pub trait Tool: Send + Sync {
         Ok(None)
     }
 
+    /**
+        Gets the location that a dependency points at, such as
+        the manifest of a local dependency, for going to it.
+    */
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        let _params = params;
+        Ok(None)
+    }
+
     async fn completion(&self, params: CompletionParams) -> Result<CompletionResponse> {
         let _params = params;
         Ok(CompletionResponse::Array(vec![]))
@@ -30,4 +44,104 @@ pub trait Tool: Send + Sync {
     async fn code_action_resolve(&self, action: CodeAction) -> Result<CodeAction> {
         Ok(action)
     }
+
+    /**
+        Returns the number of dependencies in the document at the given
+        uri, which is checked against the configured limits by `Tools`.
+    */
+    fn dependency_count(&self, uri: &Url) -> usize {
+        let _uri = uri;
+        0
+    }
+
+    /**
+        Called when a file has been changed, created, renamed, or deleted,
+        so that tools can invalidate anything they have cached about it.
+    */
+    fn file_changed(&self, uri: &Url) {
+        let _uri = uri;
+    }
+
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Vec<DocumentHighlight>> {
+        let _params = params;
+        Ok(vec![])
+    }
+
+    async fn document_symbols(&self, params: DocumentSymbolParams) -> Result<Vec<DocumentSymbol>> {
+        let _params = params;
+        Ok(vec![])
+    }
+
+    /**
+        Checks if the token at a position can be renamed, returning its range
+        and current name, or `None` if renaming it is not supported.
+    */
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        let _params = params;
+        Ok(None)
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let _params = params;
+        Ok(None)
+    }
+
+    async fn inlay_hints(&self, params: InlayHintParams) -> Result<Vec<InlayHint>> {
+        let _params = params;
+        Ok(vec![])
+    }
+
+    /**
+        Gets inline values for the given range, such as the versions
+        that dependencies are locked to, for clients that show those
+        instead of, or in addition to, inlay hints.
+    */
+    async fn inline_values(&self, params: InlineValueParams) -> Result<Vec<InlineValue>> {
+        let _params = params;
+        Ok(vec![])
+    }
+
+    /**
+        Gets ranges that should always contain the same text, and
+        which editors may then edit together, like a single range.
+    */
+    async fn linked_editing_range(
+        &self,
+        params: LinkedEditingRangeParams,
+    ) -> Result<Option<LinkedEditingRanges>> {
+        let _params = params;
+        Ok(None)
+    }
+
+    /**
+        Gets edits to apply right before a document is saved, which the editor
+        waits for before saving, so this must never fetch anything.
+    */
+    async fn will_save_wait_until(
+        &self,
+        params: WillSaveTextDocumentParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let _params = params;
+        Ok(None)
+    }
+
+    async fn manifest_info(&self, params: ManifestInfoParams) -> Result<Option<ManifestInfo>> {
+        let _params = params;
+        Ok(None)
+    }
+
+    /**
+        Summarizes the problems with the dependencies of a manifest,
+        using the same diagnostics as the `diagnostics` method.
+    */
+    async fn analyze(&self, params: AnalyzeParams) -> Result<Option<AnalyzeResult>> {
+        let _params = params;
+        Ok(None)
+    }
 }
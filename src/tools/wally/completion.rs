@@ -1,8 +1,10 @@
+use futures::future::join_all;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 
+use crate::clients::wally::models::MetadataRealm;
 use crate::clients::*;
-use crate::parser::SimpleDependency;
+use crate::parser::{DependencyKind, SimpleDependency};
 use crate::server::*;
 
 use super::shared::*;
@@ -27,7 +29,6 @@ pub async fn get_wally_completions_spec_author(
     let items = package_scopes
         .into_iter()
         .filter(|package| filter_starts_with(package.as_str(), author.unquoted()))
-        .take(MAXIMUM_PACKAGES_SHOWN)
         .map(|package| CompletionItem {
             label: package.to_string(),
             kind: Some(CompletionItemKind::ENUM),
@@ -36,17 +37,23 @@ pub async fn get_wally_completions_spec_author(
             )),
             commit_characters: Some(vec![String::from("/")]),
             ..Default::default()
-        })
-        .collect::<Vec<_>>();
+        });
+
+    // Sort before truncating, since scopes are not fetched in any particular order
+    let mut items = sort_alphabetically(items);
+    items.truncate(MAXIMUM_PACKAGES_SHOWN);
     Ok(CompletionResponse::Array(items))
 }
 
 pub async fn get_wally_completions_spec_name(
     clients: &Clients,
+    resolve: CompletionResolveSupport,
     document: &Document,
     index_url: &str,
     dep: &SimpleDependency,
+    filter_by_realm: bool,
 ) -> Result<CompletionResponse> {
+    let kind = dep.kind;
     let dep = dep.parsed_spec();
     let author = &dep.author;
 
@@ -66,7 +73,6 @@ pub async fn get_wally_completions_spec_name(
     let items = package_names
         .into_iter()
         .filter(|package| filter_starts_with(package.as_str(), name.unquoted()))
-        .take(MAXIMUM_PACKAGES_SHOWN)
         .map(|package| CompletionItem {
             label: package.to_string(),
             kind: Some(CompletionItemKind::ENUM_MEMBER),
@@ -75,9 +81,96 @@ pub async fn get_wally_completions_spec_name(
             )),
             commit_characters: Some(vec![String::from("@")]),
             ..Default::default()
-        })
-        .collect::<Vec<_>>();
-    Ok(CompletionResponse::Array(items))
+        });
+
+    // Sort before truncating, since packages are not fetched in any particular order
+    let mut items = sort_alphabetically(items);
+    items.truncate(MAXIMUM_PACKAGES_SHOWN);
+
+    // Rank packages with a realm that is compatible with the current section first,
+    // or leave them out entirely if configured to, and show the realm of each one
+    let Some(section_realm) = section_realm(kind) else {
+        return Ok(CompletionResponse::Array(items));
+    };
+
+    // NOTE: Finding the realm of a package takes a request to GitHub, which
+    // is far too many to make while typing, so only realms that were already
+    // fetched are used here, and the rest are fetched when an item is resolved
+    let realms = join_all(items.iter().map(|item| async {
+        let metadatas = clients
+            .wally
+            .get_cached_index_metadatas(index_url, author.unquoted(), &item.label)
+            .await?;
+        metadatas.first().map(|metadata| metadata.package.realm)
+    }))
+    .await;
+
+    let items = items.into_iter().zip(realms).filter_map(|(item, realm)| {
+        // Packages with an unknown realm are never filtered out
+        let compatible =
+            realm.is_none_or(|realm| section_realm.get_suggested_realm(realm).is_none());
+        if filter_by_realm && !compatible {
+            return None;
+        }
+        let data = (realm.is_none() && resolve.detail).then(|| {
+            CompletionMetadata::WallyPackage {
+                index_url: index_url.to_string(),
+                scope: author.unquoted().to_string(),
+                name: item.label.clone(),
+            }
+            .into_data(document.uri())
+        });
+        let item = CompletionItem {
+            detail: realm.map(realm_detail),
+            data,
+            ..item
+        };
+        Some((u64::from(compatible), item))
+    });
+
+    Ok(CompletionResponse::Array(sort_by_relevance(items)))
+}
+
+/**
+    Resolves the realm for a package name completion item
+    that was not known yet when the completion was made.
+*/
+pub async fn get_wally_completion_resolve(
+    clients: &Clients,
+    mut item: CompletionItem,
+    index_url: &str,
+    scope: &str,
+    name: &str,
+) -> Result<CompletionItem> {
+    let Ok(metadatas) = clients
+        .wally
+        .get_index_metadatas(index_url, scope, name)
+        .await
+    else {
+        return Ok(item);
+    };
+
+    if let Some(metadata) = metadatas.first() {
+        item.detail = Some(realm_detail(metadata.package.realm));
+    }
+
+    Ok(item)
+}
+
+fn realm_detail(realm: MetadataRealm) -> String {
+    format!("Realm: {}", realm.name())
+}
+
+/**
+    Returns the realm that packages in a dependency section are used in.
+*/
+pub fn section_realm(kind: DependencyKind) -> Option<MetadataRealm> {
+    match kind {
+        DependencyKind::Default => Some(MetadataRealm::Shared),
+        DependencyKind::Server => Some(MetadataRealm::Server),
+        DependencyKind::Dev => Some(MetadataRealm::Dev),
+        _ => None,
+    }
 }
 
 pub async fn get_wally_completions_spec_version(
@@ -110,11 +203,9 @@ pub async fn get_wally_completions_spec_version(
         .extract_completion_versions(metadatas.into_iter())
         .into_iter()
         .take(MAXIMUM_PACKAGES_SHOWN)
-        .enumerate()
-        .map(|(index, potential_version)| CompletionItem {
+        .map(|potential_version| CompletionItem {
             label: potential_version.item_version_raw.to_string(),
             kind: Some(CompletionItemKind::VALUE),
-            sort_text: Some(format!("{:0>5}", index)),
             text_edit: Some(CompletionTextEdit::Edit(
                 document.create_edit(version.range, potential_version.item_version_raw),
             )),
@@ -122,5 +213,12 @@ pub async fn get_wally_completions_spec_version(
         })
         .collect::<Vec<_>>();
 
-    Ok(CompletionResponse::Array(valid_vec))
+    let valid_vec = with_version_variants(
+        document,
+        version.range,
+        version.unquoted(),
+        PlainVersion::Caret,
+        valid_vec,
+    );
+    Ok(CompletionResponse::Array(with_sort_texts(valid_vec)))
 }
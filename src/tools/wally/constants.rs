@@ -1 +1,20 @@
 pub const WALLY_DEFAULT_REGISTRY: &str = "https://github.com/UpliftGames/wally-index";
+
+/**
+    Fields of the `[package]` table, which are never dependencies,
+    even though some such as `name` look like one.
+*/
+pub const WALLY_PACKAGE_FIELDS: &[&str] = &[
+    "name",
+    "version",
+    "registry",
+    "realm",
+    "description",
+    "license",
+    "authors",
+    "include",
+    "exclude",
+    "private",
+    "homepage",
+    "repository",
+];
@@ -2,10 +2,11 @@ use semver::VersionReq;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 
-use crate::parser::SimpleDependency;
+use crate::parser::{ParsedSpecFull, SimpleDependency};
 use crate::util::Versioned;
 
 use super::super::shared::*;
+use super::super::ToolName;
 use super::{Clients, Document, VersionReqExt};
 
 pub async fn get_wally_diagnostics(
@@ -13,7 +14,7 @@ pub async fn get_wally_diagnostics(
     doc: &Document,
     index_url: &str,
     tool: &SimpleDependency,
-) -> Result<Vec<Diagnostic>> {
+) -> Result<DependencyDiagnostics> {
     let parsed = tool.parsed_spec();
 
     // Check for any missing fields
@@ -40,14 +41,15 @@ pub async fn get_wally_diagnostics(
             range: tool.spec.range,
             message: diag.to_string(),
             severity: Some(DiagnosticSeverity::WARNING), // Most likely during typing, don't emit a hard error
-            ..Default::default()
-        }]);
+            ..DiagnosticCode::MissingField.diagnostic()
+        }]
+        .into());
     }
 
     // Fetch versions and make sure there is at least one
     let parsed = parsed.into_full().expect("nothing was missing");
     let Ok(parsed_version_req) = VersionReq::parse(parsed.version.unquoted()) else {
-        return Ok(Vec::new());
+        return Ok(Vec::new().into());
     };
     let parsed_version = parsed_version_req.minimum_version();
 
@@ -59,19 +61,12 @@ pub async fn get_wally_diagnostics(
         Ok(v) => v,
         Err(e) => {
             if e.is_not_found_error() {
-                return Ok(vec![Diagnostic {
-                    source: Some(String::from("Wally")),
-                    range: parsed.range(),
-                    message: format!(
-                        "No package exists with the name `{}/{}`",
-                        parsed.author.unquoted(),
-                        parsed.name.unquoted(),
-                    ),
-                    severity: Some(DiagnosticSeverity::ERROR),
-                    ..Default::default()
-                }]);
+                return Ok(vec![
+                    get_wally_diagnostic_not_found(clients, doc, index_url, &parsed).await,
+                ]
+                .into());
             } else {
-                return Ok(Vec::new());
+                return Ok(DependencyDiagnostics::incomplete(Vec::new()));
             }
         }
     };
@@ -85,8 +80,9 @@ pub async fn get_wally_diagnostics(
                 parsed.name.unquoted(),
             ),
             severity: Some(DiagnosticSeverity::ERROR),
-            ..Default::default()
-        }]);
+            ..DiagnosticCode::UnknownVersion.diagnostic()
+        }]
+        .into());
     }
 
     // Check if any version meeting the one specified exists
@@ -106,14 +102,15 @@ pub async fn get_wally_diagnostics(
                 parsed.name.unquoted(),
             ),
             severity: Some(DiagnosticSeverity::ERROR),
-            ..Default::default()
-        }]);
+            ..DiagnosticCode::UnknownVersion.diagnostic()
+        }]
+        .into());
     }
 
     // Everything is OK - but we may be able to suggest new versions...
     // ... try to find the latest non-prerelease version
     let Some(latest_version) = parsed_version.extract_latest_version(metadatas) else {
-        return Ok(Vec::new());
+        return Ok(Vec::new().into());
     };
 
     if !latest_version.is_semver_compatible {
@@ -125,6 +122,11 @@ pub async fn get_wally_diagnostics(
             source_text: parsed.version.quoted().to_string(),
             version_current: parsed_version.to_string(),
             version_latest: latest_version_string.to_string(),
+            package_manager: PackageManagerCommand::for_package(
+                ToolName::Wally,
+                doc.uri(),
+                &format!("{}/{}", parsed.author.unquoted(), parsed.name.unquoted()),
+            ),
         };
 
         return Ok(vec![Diagnostic {
@@ -144,9 +146,58 @@ pub async fn get_wally_diagnostics(
                 }
                 .into(),
             ),
-            ..Default::default()
-        }]);
+            ..DiagnosticCode::Outdated.diagnostic()
+        }]
+        .into());
     }
 
-    Ok(Vec::new())
+    Ok(Vec::new().into())
+}
+
+/**
+    Creates a diagnostic for a package that does not exist in the index.
+
+    Checks if the scope or only the package name is wrong, so that the
+    error can be placed on the exact segment that is wrong, together
+    with suggestions for close matches from the index, if any.
+*/
+async fn get_wally_diagnostic_not_found(
+    clients: &Clients,
+    doc: &Document,
+    index_url: &str,
+    parsed: &ParsedSpecFull,
+) -> Diagnostic {
+    let author = parsed.author.unquoted();
+    let name = parsed.name.unquoted();
+
+    let fallback = Diagnostic {
+        source: Some(String::from("Wally")),
+        range: parsed.range(),
+        message: format!("No package exists with the name `{author}/{name}`"),
+        severity: Some(DiagnosticSeverity::ERROR),
+        ..DiagnosticCode::UnknownPackage.diagnostic()
+    };
+
+    let Ok(scopes) = clients.wally.get_index_scopes(index_url).await else {
+        return fallback;
+    };
+
+    if scopes.iter().any(|s| s.eq_ignore_ascii_case(author)) {
+        let Ok(packages) = clients.wally.get_index_packages(index_url, author).await else {
+            return fallback;
+        };
+        let diag = Diagnostic {
+            range: parsed.name.range,
+            message: format!("No package named `{name}` exists in the scope `{author}`"),
+            ..fallback
+        };
+        with_suggestions(diag, doc.uri(), parsed.name.range, name, packages)
+    } else {
+        let diag = Diagnostic {
+            range: parsed.author.range,
+            message: format!("No scope exists with the name `{author}`"),
+            ..fallback
+        };
+        with_suggestions(diag, doc.uri(), parsed.author.range, author, scopes)
+    }
 }
@@ -0,0 +1,33 @@
+use tower_lsp::lsp_types::*;
+
+use crate::parser::{DependencyKind, Node};
+
+use super::section_realm;
+
+/**
+    Creates inlay hints after each dependency section header,
+    showing which realm the packages in that section target.
+*/
+pub fn get_wally_inlay_hints_realms(
+    sections: &[Node<DependencyKind>],
+    range: Range,
+) -> Vec<InlayHint> {
+    sections
+        .iter()
+        .filter(|section| section.range.start.line <= range.end.line)
+        .filter(|section| section.range.end.line >= range.start.line)
+        .filter_map(|section| {
+            let realm = section_realm(section.contents)?;
+            Some(InlayHint {
+                position: section.range.end,
+                label: InlayHintLabel::String(format!("{} realm", realm.name())),
+                kind: None,
+                text_edits: None,
+                tooltip: None,
+                padding_left: Some(true),
+                padding_right: None,
+                data: None,
+            })
+        })
+        .collect()
+}
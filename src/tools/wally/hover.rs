@@ -1,19 +1,37 @@
+use std::collections::HashSet;
+
+use futures::future::join_all;
+use semver::VersionReq;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tracing::trace;
 
 use crate::{
+    clients::wally::models::Metadata,
     parser::SimpleDependency,
-    tools::{wally::WALLY_DEFAULT_REGISTRY, MarkdownBuilder},
+    server::VersionSource,
+    tools::{
+        wally::WALLY_DEFAULT_REGISTRY, HoverSections, LockfileCache, MarkdownBuilder, ToolName,
+    },
+    util::Versioned,
 };
 
 use super::{Clients, Document};
 
+/*
+    Every package in the dependency tree needs its own request
+    to the index, which goes through the GitHub API and its rate
+    limits, so only this many packages are ever looked up
+*/
+const MAX_TREE_LOOKUPS: usize = 32;
+
 pub async fn get_wally_hover(
     clients: &Clients,
-    _doc: &Document,
+    lockfiles: &LockfileCache,
+    doc: &Document,
     index_url: &str,
     tool: &SimpleDependency,
+    source: VersionSource,
 ) -> Result<Option<Hover>> {
     let Some(spec) = tool.parsed_spec().into_full() else {
         return Ok(None);
@@ -25,68 +43,229 @@ pub async fn get_wally_hover(
         spec.name.unquoted(),
         spec.version.unquoted()
     );
-    let mut md = MarkdownBuilder::new();
+    let mut hover = HoverSections::new();
 
     // Try to fetch additional information from the index - description, links
     trace!("Fetching index metadatas from Wally API");
-    if let Ok(mut metadatas) = clients
+    let Ok(mut metadatas) = clients
         .wally
         .get_index_metadatas(index_url, spec.author.unquoted(), spec.name.unquoted())
         .await
+    else {
+        hover.section(|md| md.h2(spec.name.unquoted()));
+        hover.section(|md| md.version(spec.version.unquoted()));
+        return Ok(Some(hover.into_hover(tool.range())));
+    };
+
+    // Prefer the newest version matching the spec, falling back to the latest
+    let matching = newest_matching_metadata(&metadatas, spec.version.unquoted()).cloned();
+    let resolved = matching
+        .as_ref()
+        .filter(|_| source.uses_registry())
+        .and_then(|m| m.parse_version().ok());
+    let lockfile = match source.uses_lockfile() {
+        true => lockfiles.for_manifest(ToolName::Wally, doc.uri()).await,
+        false => None,
+    };
+    let locked = lockfile.and_then(|lockfile| {
+        let name = format!("{}/{}", spec.author.unquoted(), spec.name.unquoted());
+        let package = lockfile.find(&name.to_ascii_lowercase(), Some(spec.version.unquoted()))?;
+        Some(package.version.clone())
+    });
+    metadatas.reverse(); // Latest last, so we can pop
+    let Some(metadata) = matching.or_else(|| metadatas.pop()) else {
+        hover.section(|md| md.h2(spec.name.unquoted()));
+        hover.section(|md| md.version(spec.version.unquoted()));
+        return Ok(Some(hover.into_hover(tool.range())));
+    };
+
+    hover.section(|md| md.h2(&metadata.package.name));
+    hover.section(|md| {
+        md.version(spec.version.unquoted());
+        md.br();
+        if let Some(locked) = &locked {
+            md.locked(locked);
+        }
+        md.requirement(spec.version.unquoted(), resolved.as_ref());
+    });
+
+    // Add description, if available
+    if let Some(desc) = &metadata.package.description {
+        hover.section(|md| md.p(desc));
+    }
+
+    // Add realm and dependency counts, to show the weight of the package
+    let direct = package_dependencies(&metadata).len();
+    let tree = if direct > 0 {
+        Some(get_dependency_tree_size(clients, index_url, &metadata).await)
+    } else {
+        None
+    };
+    hover.section(|md| {
+        md.p(format!("Realm: `{}`", metadata.package.realm.name()));
+        match tree {
+            Some(tree) => md.p(format!(
+                "{direct} direct dependenc{}, {}{} in total",
+                if direct == 1 { "y" } else { "ies" },
+                if tree.complete { "" } else { "at least " },
+                tree.count,
+            )),
+            None => md.p("No dependencies"),
+        }
+    });
+    hover.push(links_section(&metadata, spec.version.unquoted()));
+
+    Ok(Some(hover.into_hover(tool.range())))
+}
+
+fn links_section(metadata: &Metadata, version: &str) -> MarkdownBuilder {
+    let wally_run = metadata
+        .package
+        .registry
+        .eq_ignore_ascii_case(WALLY_DEFAULT_REGISTRY)
+        .then(|| {
+            format!(
+                "https://wally.run/package/{}?version={version}",
+                metadata.package.name,
+            )
+        });
+
+    let mut md = MarkdownBuilder::new();
+    if wally_run.is_none()
+        && metadata.package.homepage.is_none()
+        && metadata.package.repository.is_none()
     {
-        metadatas.reverse(); // Latest last, so we can pop
-        if let Some(metadata) = metadatas.pop() {
-            md.h2(&metadata.package.name);
-            md.version(spec.version.unquoted());
-
-            // Add description, if available
-            if let Some(desc) = &metadata.package.description {
-                md.br();
-                md.p(desc);
-            }
+        return md;
+    }
+    md.h3("Links");
+    if let Some(homepage) = &metadata.package.homepage {
+        md.a("Homepage", homepage);
+    }
+    if let Some(repository) = &metadata.package.repository {
+        md.a("Repository", repository);
+    }
+    if let Some(wally) = wally_run {
+        md.a("Wally", wally);
+    }
+    md
+}
+
+/**
+    The number of unique packages in the dependency tree of a package,
+    not including the package itself, and if the tree was fully explored.
+*/
+struct DependencyTreeSize {
+    count: usize,
+    complete: bool,
+}
+
+/**
+    Explores the dependency tree of a package, level by level, using the
+    newest version matching each dependency, to count unique packages.
+
+    Dev dependencies are not included, since they are never installed
+    for packages that are used as dependencies. Packages that could not
+    be found in the index, or while offline, are still counted, but their
+    dependencies are not, which makes the tree incomplete.
+*/
+async fn get_dependency_tree_size(
+    clients: &Clients,
+    index_url: &str,
+    root: &Metadata,
+) -> DependencyTreeSize {
+    let mut seen = HashSet::new();
+    let mut complete = true;
+    let mut level = package_dependencies(root);
 
-            // Add links, if available
-            let wally_run = metadata
-                .package
-                .registry
-                .eq_ignore_ascii_case(WALLY_DEFAULT_REGISTRY)
-                .then(|| {
-                    format!(
-                        "https://wally.run/package/{}?version={}",
-                        metadata.package.name,
-                        spec.version.unquoted()
-                    )
-                });
-            if wally_run.is_some()
-                || metadata.package.homepage.is_some()
-                || metadata.package.repository.is_some()
-            {
-                md.br();
-                md.h3("Links");
-                if let Some(homepage) = metadata.package.homepage {
-                    md.a("Homepage", homepage);
-                }
-                if let Some(repository) = metadata.package.repository {
-                    md.a("Repository", repository);
-                }
-                if let Some(wally) = wally_run {
-                    md.a("Wally", wally);
-                }
+    let mut lookups = 0;
+    while !level.is_empty() {
+        let mut pending = Vec::new();
+        for (scope, name, version) in level.drain(..) {
+            if !seen.insert(format!("{scope}/{name}")) {
+                continue;
+            }
+            if lookups >= MAX_TREE_LOOKUPS {
+                complete = false;
+                continue;
             }
-        } else {
-            md.h2(spec.name.unquoted());
-            md.version(spec.version.unquoted());
+            lookups += 1;
+            pending.push(async move {
+                let metadatas = clients
+                    .wally
+                    .get_index_metadatas(index_url, &scope, &name)
+                    .await
+                    .ok()?;
+                newest_matching_metadata(&metadatas, &version).cloned()
+            });
         }
-    } else {
-        md.h2(spec.name.unquoted());
-        md.version(spec.version.unquoted());
+        for metadata in join_all(pending).await {
+            match metadata {
+                Some(metadata) => level.extend(package_dependencies(&metadata)),
+                None => complete = false,
+            }
+        }
+    }
+
+    DependencyTreeSize {
+        count: seen.len(),
+        complete,
     }
+}
+
+/**
+    Returns the scope, name, and version requirement
+    of all non-dev dependencies of a package.
+*/
+fn package_dependencies(metadata: &Metadata) -> Vec<(String, String, String)> {
+    let deps = &metadata.dependencies;
+    deps.shared
+        .values()
+        .chain(deps.server.values())
+        .filter_map(|spec| parse_dependency_spec(spec))
+        .map(|(scope, name, version)| {
+            (
+                scope.to_ascii_lowercase(),
+                name.to_ascii_lowercase(),
+                version.to_string(),
+            )
+        })
+        .collect()
+}
 
-    Ok(Some(Hover {
-        range: Some(tool.range()),
-        contents: HoverContents::Markup(MarkupContent {
-            kind: MarkupKind::Markdown,
-            value: md.build(),
-        }),
-    }))
+/**
+    Parses a dependency spec from the index, such as `scope/name@^1.2.3`.
+*/
+fn parse_dependency_spec(spec: &str) -> Option<(&str, &str, &str)> {
+    let (package, version) = spec.split_once('@')?;
+    let (scope, name) = package.split_once('/')?;
+    Some((scope.trim(), name.trim(), version.trim()))
+}
+
+fn newest_matching_metadata<'a>(metas: &'a [Metadata], version: &str) -> Option<&'a Metadata> {
+    let req = VersionReq::parse(version).ok()?;
+    metas
+        .iter()
+        .filter_map(|meta| Some((meta, meta.parse_version().ok()?)))
+        .filter(|(_, v)| req.matches(v))
+        .max_by(|(_, a), (_, b)| a.cmp(b))
+        .map(|(meta, _)| meta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dependency_spec() {
+        assert_eq!(
+            parse_dependency_spec("evaera/promise@^4.0.0"),
+            Some(("evaera", "promise", "^4.0.0"))
+        );
+        assert_eq!(
+            parse_dependency_spec("roblox/roact@1.4.4"),
+            Some(("roblox", "roact", "1.4.4"))
+        );
+        assert_eq!(parse_dependency_spec("roblox/roact"), None);
+        assert_eq!(parse_dependency_spec("roact@1.4.4"), None);
+    }
 }
@@ -1,11 +1,10 @@
-use futures::future::try_join_all;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::Client;
 use tracing::debug;
 
-use crate::parser::query_wally_toml_dependencies;
-use crate::parser::SimpleDependency;
+use crate::parser::{query_wally_toml_dependencies, query_wally_toml_sections};
+use crate::parser::{DependencyKind, SimpleDependency};
 use crate::server::*;
 use crate::util::*;
 
@@ -14,39 +13,87 @@ use super::*;
 mod completion;
 mod constants;
 mod diagnostics;
+mod hints;
 mod hover;
 
 use completion::*;
 use constants::*;
 use diagnostics::*;
+use hints::*;
 use hover::*;
 
 #[derive(Debug, Clone)]
 pub struct Wally {
     _client: Client,
     clients: Clients,
+    config: SharedConfig,
     documents: Documents,
+    limited: LimitedDocuments,
+    lockfiles: LockfileCache,
 }
 
 impl Wally {
-    pub(super) fn new(client: Client, clients: Clients, documents: Documents) -> Self {
+    pub(super) fn new(
+        client: Client,
+        clients: Clients,
+        config: SharedConfig,
+        documents: Documents,
+        limited: LimitedDocuments,
+    ) -> Self {
         Self {
             _client: client,
             clients,
+            config,
             documents,
+            limited,
+            lockfiles: LockfileCache::default(),
         }
     }
 
     fn get_document(&self, uri: &Url) -> Option<Document> {
-        if uri
-            .file_name()
-            .as_deref()
-            .is_some_and(|f| f.eq_ignore_ascii_case("wally.toml"))
+        self.documents
+            .get(uri)
+            .filter(|doc| doc.is_for_tool(ToolName::Wally, &["wally.toml"]))
+            .map(|r| r.clone())
+    }
+
+    /**
+        Completes the scope, name, or version of a package,
+        depending on which part of its spec the cursor is in.
+    */
+    async fn complete_spec(
+        &self,
+        doc: &Document,
+        index_url: &str,
+        found: &SimpleDependency,
+        pos: Position,
+    ) -> Result<CompletionResponse> {
+        // Check if we're completing the author, name, or version of the spec
+        let parsed = found.parsed_spec();
+        if parsed.version.as_ref().is_some_and(|v| v.contains(pos)) {
+            debug!("Completing version: {found:?}");
+            return get_wally_completions_spec_version(&self.clients, doc, index_url, found).await;
+        } else if parsed.name.is_some_and(|n| n.contains(pos)) {
+            debug!("Completing name: {found:?}");
+            let filter_by_realm = self.config.get().completion.filter_by_realm;
+            let resolve = self.config.completion_resolve();
+            return get_wally_completions_spec_name(
+                &self.clients,
+                resolve,
+                doc,
+                index_url,
+                found,
+                filter_by_realm,
+            )
+            .await;
+        } else if parsed.author.contains(pos)
+            || (parsed.author.unquoted().is_empty() && found.spec.contains(pos))
         {
-            self.documents.get(uri).map(|r| r.clone())
-        } else {
-            None
+            debug!("Completing author: {found:?}");
+            return get_wally_completions_spec_author(&self.clients, doc, index_url, found).await;
         }
+
+        Ok(CompletionResponse::Array(Vec::new()))
     }
 }
 
@@ -67,9 +114,22 @@ impl Tool for Wally {
             return Ok(None);
         };
 
+        if self.limited.contains(&uri) {
+            return Ok(None);
+        }
+
         // Fetch some extra info and return the hover
         debug!("Hovering: {found:?}");
-        get_wally_hover(&self.clients, &doc, index_url, found).await
+        let source = self.config.get().hover.version_source;
+        get_wally_hover(
+            &self.clients,
+            &self.lockfiles,
+            &doc,
+            index_url,
+            found,
+            source,
+        )
+        .await
     }
 
     async fn completion(&self, params: CompletionParams) -> Result<CompletionResponse> {
@@ -81,28 +141,58 @@ impl Tool for Wally {
 
         let index_url = extract_wally_index_url(doc.as_str());
 
-        // Find the dependency that is being completed
+        // Find the dependency that is being completed - in a manifest without any
+        // dependency sections, the one being typed is not found, so we complete it
+        // anyway, and add the section for the realm of the package along with it
         let dependencies = query_wally_toml_dependencies(doc.inner());
-        let Some(found) = SimpleDependency::find_at_pos(&dependencies, pos) else {
-            return Ok(CompletionResponse::Array(Vec::new()));
+        let (found, header_edit) = match SimpleDependency::find_at_pos(&dependencies, pos) {
+            Some(found) => (found.clone(), None),
+            None => {
+                let has_sections = !query_wally_toml_sections(doc.inner()).is_empty();
+                let Some((entry, spec)) = find_unsectioned_entry(&doc, pos)
+                    .filter(|e| !has_sections && !WALLY_PACKAGE_FIELDS.contains(&e.key.unquoted()))
+                    .and_then(|e| e.value.clone().map(|spec| (e, spec)))
+                    .filter(|(_, spec)| spec.contains(pos))
+                else {
+                    return Ok(CompletionResponse::Array(Vec::new()));
+                };
+                let (kind, header) = match extract_wally_realm(doc.as_str()) {
+                    Some("server") => (DependencyKind::Server, "server-dependencies"),
+                    _ => (DependencyKind::Default, "dependencies"),
+                };
+                let edit = section_header_edit(&doc, entry.key.range.start.line, header);
+                let found = SimpleDependency {
+                    kind,
+                    name: entry.key,
+                    spec,
+                };
+                (found, Some(edit))
+            }
         };
 
-        // Check what we're completing - author, name, or version
-        let parsed = found.parsed_spec();
-        if parsed.version.as_ref().is_some_and(|v| v.contains(pos)) {
-            debug!("Completing version: {found:?}");
-            return get_wally_completions_spec_version(&self.clients, &doc, index_url, found).await;
-        } else if parsed.name.is_some_and(|n| n.contains(pos)) {
-            debug!("Completing name: {found:?}");
-            return get_wally_completions_spec_name(&self.clients, &doc, index_url, found).await;
-        } else if parsed.author.contains(pos)
-            || (parsed.author.unquoted().is_empty() && found.spec.contains(pos))
-        {
-            debug!("Completing author: {found:?}");
-            return get_wally_completions_spec_author(&self.clients, &doc, index_url, found).await;
+        if self.limited.contains(&uri) {
+            return Ok(CompletionResponse::Array(Vec::new()));
         }
 
-        Ok(CompletionResponse::Array(Vec::new()))
+        let response = self.complete_spec(&doc, index_url, &found, pos).await?;
+        Ok(match header_edit {
+            Some(edit) => with_additional_edit(response, edit),
+            None => response,
+        })
+    }
+
+    async fn completion_resolve(&self, item: CompletionItem) -> Result<CompletionItem> {
+        match CompletionMetadata::from_item(&item) {
+            Some(CompletionMetadata::WallyPackage {
+                index_url,
+                scope,
+                name,
+            }) => {
+                debug!("Resolving completion: {scope}/{name}");
+                get_wally_completion_resolve(&self.clients, item, &index_url, &scope, &name).await
+            }
+            _ => Ok(item),
+        }
     }
 
     async fn diagnostics(&self, params: DocumentDiagnosticParams) -> Result<Vec<Diagnostic>> {
@@ -119,16 +209,37 @@ impl Tool for Wally {
             return Ok(Vec::new());
         }
 
+        // Packages that are not permitted by policy are checked without the network
+        let config = self.config.get();
+        let policy = dependencies
+            .iter()
+            .filter_map(|dep| {
+                let parsed = dep.parsed_spec();
+                let name = format!("{}/{}", parsed.author.unquoted(), parsed.name?.unquoted());
+                get_policy_diagnostic(&config, ToolName::Wally, &name, dep.name.range, "Wally")
+            })
+            .collect::<Vec<_>>();
+
+        if self.limited.contains(&uri) {
+            let limited = exceeds_limits_diagnostic(&config.limits, "Wally");
+            return Ok(policy.into_iter().chain([limited]).collect());
+        }
+
         // Fetch all diagnostics concurrently
         debug!("Fetching wally diagnostics for dependencies");
-        let results = try_join_all(
-            dependencies
-                .iter()
-                .map(|tool| get_wally_diagnostics(&self.clients, &doc, index_url, tool)),
+        // The index url is not a part of any dependency, but affects all of them
+        let results = cached_dependency_diagnostics(
+            &doc,
+            config.diagnostics.merge,
+            dependencies.iter().map(|tool| {
+                let key = DependencyKey::new(&doc, tool.kind, tool.range()).with_context(index_url);
+                let fut = get_wally_diagnostics(&self.clients, &doc, index_url, tool);
+                (key, tool.range(), fut)
+            }),
         )
         .await?;
 
-        Ok(results.into_iter().flatten().collect())
+        Ok(results.into_iter().chain(policy).collect())
     }
 
     async fn code_action(&self, params: CodeActionParams) -> Result<Vec<CodeActionOrCommand>> {
@@ -139,11 +250,95 @@ impl Tool for Wally {
                 .as_ref()
                 .map(ResolveContext::<CodeActionMetadata>::try_from)
             {
-                actions.push(action.into_inner().into_code_action(diag.clone()))
+                actions.extend(action.into_inner().into_code_actions(Some(diag.clone())))
+            }
+        }
+
+        // Only offered when no specific kinds were asked for, see `into_code_action`
+        if params.context.only.is_none() {
+            if let Some(doc) = self.get_document(&params.text_document.uri) {
+                let dependencies = query_wally_toml_dependencies(doc.inner());
+                let found = SimpleDependency::find_at_pos(&dependencies, params.range.start);
+                if let Some(spec) = found.and_then(|f| f.parsed_spec().into_full()) {
+                    let dependency = DependencyId::Wally {
+                        index_url: extract_wally_index_url(doc.as_str()).to_string(),
+                        scope: spec.author.unquoted().to_string(),
+                        name: spec.name.unquoted().to_string(),
+                    };
+                    actions.push(
+                        RefreshDependencyParams::new(doc.uri(), dependency).into_code_action(),
+                    );
+                }
             }
         }
+
         Ok(actions)
     }
+
+    fn dependency_count(&self, uri: &Url) -> usize {
+        self.get_document(uri)
+            .map_or(0, |doc| query_wally_toml_dependencies(doc.inner()).len())
+    }
+
+    fn file_changed(&self, uri: &Url) {
+        self.lockfiles.invalidate(uri);
+
+        // A changed lockfile usually means that dependencies were just
+        // updated, so this is a good time to pick up any new versions
+        if uri
+            .file_name()
+            .as_deref()
+            .is_some_and(|f| f.eq_ignore_ascii_case("wally.lock"))
+        {
+            debug!("Lockfile changed, invalidating cached Wally versions");
+            self.clients.wally.invalidate_versions();
+        }
+    }
+
+    async fn inlay_hints(&self, params: InlayHintParams) -> Result<Vec<InlayHint>> {
+        let Some(doc) = self.get_document(&params.text_document.uri) else {
+            return Ok(Vec::new());
+        };
+
+        let mut hints = Vec::new();
+        if self.config.get().inlay_hints.wally_realms {
+            let sections = query_wally_toml_sections(doc.inner());
+            hints.extend(get_wally_inlay_hints_realms(&sections, params.range));
+        }
+        Ok(hints)
+    }
+
+    async fn inline_values(&self, params: InlineValueParams) -> Result<Vec<InlineValue>> {
+        let Some(doc) = self.get_document(&params.text_document.uri) else {
+            return Ok(Vec::new());
+        };
+        let Some(lockfile) = self
+            .lockfiles
+            .for_manifest(ToolName::Wally, doc.uri())
+            .await
+        else {
+            return Ok(Vec::new());
+        };
+
+        // Packages are locked under their full, lowercased name, same as in hovers
+        let dependencies = query_wally_toml_dependencies(doc.inner())
+            .into_iter()
+            .filter_map(|dep| {
+                let spec = dep.parsed_spec();
+                let name = format!("{}/{}", spec.author.unquoted(), spec.name?.unquoted());
+                let version = spec.version.map(|v| v.unquoted().to_string());
+                Some((dep.range(), name.to_ascii_lowercase(), version))
+            })
+            .collect::<Vec<_>>();
+        let values = dependencies
+            .iter()
+            .map(|(range, name, version)| LockedInlineValue {
+                range: *range,
+                name,
+                version: version.as_deref(),
+            });
+        Ok(get_locked_inline_values(&lockfile, values, params.range))
+    }
 }
 
 fn extract_wally_index_url(doc_contents: &str) -> &str {
@@ -162,3 +357,15 @@ fn extract_wally_index_url(doc_contents: &str) -> &str {
         })
         .unwrap_or(WALLY_DEFAULT_REGISTRY)
 }
+
+/**
+    Finds the realm of the package itself, which decides the section that
+    its first dependency goes in, when the manifest has no sections yet.
+*/
+fn extract_wally_realm(doc_contents: &str) -> Option<&str> {
+    doc_contents.lines().find_map(|line| {
+        line.split_once('=')
+            .filter(|(key, _)| key.trim() == "realm")
+            .map(|(_, value)| value.trim().trim_matches(['\'', '\"']))
+    })
+}
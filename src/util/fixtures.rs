@@ -0,0 +1,59 @@
+use std::env::temp_dir;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/**
+    A directory on disk for the files of a single test, which
+    is removed when dropped, even if the test panics halfway.
+
+    All fixture directories are in the same parent directory, and
+    any leftovers from an earlier run are removed before creating it.
+*/
+#[derive(Debug)]
+pub struct FixtureDir {
+    path: PathBuf,
+}
+
+impl FixtureDir {
+    pub fn new(name: &str) -> Self {
+        let path = temp_dir()
+            .join("tooling-language-server-fixtures")
+            .join(name);
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).expect("failed to create fixture directory");
+
+        // Temp dirs may themselves be behind symlinks, which are resolved
+        // so that tests can compare against canonical paths where needed
+        let path = path
+            .canonicalize()
+            .expect("failed to canonicalize fixture directory");
+        Self { path }
+    }
+
+    /**
+        Writes a file at the given path relative to the
+        fixture directory, creating any parent directories.
+    */
+    pub fn write(&self, relative: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> PathBuf {
+        let path = self.path.join(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("failed to create fixture directory");
+        }
+        fs::write(&path, contents).expect("failed to write fixture file");
+        path
+    }
+}
+
+impl Deref for FixtureDir {
+    type Target = Path;
+    fn deref(&self) -> &Self::Target {
+        &self.path
+    }
+}
+
+impl Drop for FixtureDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+/**
+    A local usage metric that can be counted.
+
+    Metrics are never sent anywhere, they are only kept
+    in memory and can be inspected using `tooling/status`.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Hover,
+    Completion,
+    Diagnostics,
+    CacheHit,
+    CacheMiss,
+}
+
+impl Metric {
+    const COUNT: usize = 5;
+}
+
+#[derive(Debug, Default)]
+struct MetricsInner {
+    enabled: AtomicBool,
+    counters: [AtomicU64; Metric::COUNT],
+}
+
+/**
+    Counters for local usage metrics, shared by all clones.
+
+    Recording is a no-op until metrics have been enabled, which only
+    happens when the server was started with the `--metrics` flag.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    inner: Arc<MetricsInner>,
+}
+
+impl Metrics {
+    /**
+        Enables recording of metrics for all clones of these metrics.
+    */
+    pub fn enable(&self) {
+        self.inner.enabled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.inner.enabled.load(Ordering::Relaxed)
+    }
+
+    /**
+        Records a single occurrence of the given metric, if enabled.
+    */
+    pub fn record(&self, metric: Metric) {
+        if self.is_enabled() {
+            self.inner.counters[metric as usize].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn count(&self, metric: Metric) -> u64 {
+        self.inner.counters[metric as usize].load(Ordering::Relaxed)
+    }
+
+    /**
+        Takes a snapshot of the current metrics, or returns
+        `None` if metrics have not been enabled.
+    */
+    pub fn snapshot(&self) -> Option<MetricsSnapshot> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let cache_hits = self.count(Metric::CacheHit);
+        let cache_misses = self.count(Metric::CacheMiss);
+        let cache_lookups = cache_hits + cache_misses;
+        Some(MetricsSnapshot {
+            hovers: self.count(Metric::Hover),
+            completions: self.count(Metric::Completion),
+            diagnostics: self.count(Metric::Diagnostics),
+            cache_hits,
+            cache_misses,
+            cache_hit_ratio: (cache_lookups > 0).then(|| cache_hits as f64 / cache_lookups as f64),
+        })
+    }
+}
+
+/**
+    A snapshot of all recorded metrics.
+
+    The cache hit ratio is `None` until something has been looked up in a cache.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsSnapshot {
+    pub hovers: u64,
+    pub completions: u64,
+    pub diagnostics: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub cache_hit_ratio: Option<f64>,
+}
+
+impl fmt::Display for MetricsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} hovers, {} completions, {} diagnostics runs, {} cache hits, {} cache misses",
+            self.hovers, self.completions, self.diagnostics, self.cache_hits, self.cache_misses
+        )?;
+        if let Some(ratio) = self.cache_hit_ratio {
+            write!(f, " ({:.1}% hit ratio)", ratio * 100.0)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_disabled() {
+        let metrics = Metrics::default();
+        metrics.record(Metric::Hover);
+        assert_eq!(metrics.snapshot(), None);
+
+        // Anything recorded before enabling is not counted
+        metrics.enable();
+        assert_eq!(metrics.snapshot().unwrap().hovers, 0);
+    }
+
+    #[test]
+    fn test_metrics_counters() {
+        let metrics = Metrics::default();
+        let other = Metrics::default();
+        metrics.enable();
+        other.enable();
+
+        let clone = metrics.clone();
+        metrics.record(Metric::Hover);
+        clone.record(Metric::Hover);
+        clone.record(Metric::Completion);
+        metrics.record(Metric::CacheMiss);
+        for _ in 0..3 {
+            metrics.record(Metric::CacheHit);
+        }
+
+        let snapshot = metrics.snapshot().unwrap();
+        assert_eq!(
+            snapshot,
+            MetricsSnapshot {
+                hovers: 2,
+                completions: 1,
+                diagnostics: 0,
+                cache_hits: 3,
+                cache_misses: 1,
+                cache_hit_ratio: Some(0.75),
+            }
+        );
+        assert_eq!(
+            snapshot.to_string(),
+            "2 hovers, 1 completions, 0 diagnostics runs, 3 cache hits, 1 cache misses (75.0% hit ratio)"
+        );
+
+        // Separate metrics, such as those of another server, are never shared
+        assert_eq!(other.snapshot().unwrap().hovers, 0);
+        assert_eq!(other.snapshot().unwrap().cache_hit_ratio, None);
+    }
+}
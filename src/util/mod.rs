@@ -1,7 +1,16 @@
+#[cfg(test)]
+mod fixtures;
+mod metrics;
+mod paths;
 mod requests;
 mod uri;
 mod versions;
 
 pub use requests::*;
-pub use uri::*;
 pub use versions::*;
+
+#[cfg(test)]
+pub(crate) use fixtures::*;
+pub(crate) use metrics::*;
+pub(crate) use paths::*;
+pub(crate) use uri::*;
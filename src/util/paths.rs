@@ -0,0 +1,23 @@
+use std::env;
+use std::path::PathBuf;
+
+/**
+    Returns the home directory of the current user, if it can be found.
+*/
+pub fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/**
+    Returns the Cargo home directory, respecting the `CARGO_HOME`
+    environment variable, and otherwise using `~/.cargo`.
+*/
+pub fn cargo_home_dir() -> Option<PathBuf> {
+    match env::var_os("CARGO_HOME").filter(|home| !home.is_empty()) {
+        Some(home) => Some(PathBuf::from(home)),
+        None => home_dir().map(|home| home.join(".cargo")),
+    }
+}
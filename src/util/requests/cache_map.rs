@@ -6,9 +6,48 @@ use futures::Future;
 use moka::future::Cache;
 use tracing::trace;
 
+use crate::util::{Metric, Metrics};
+
 type CacheMap<T> = Cache<String, T>;
+
+/**
+    How many entries a session-lived cache map may hold before the least
+    recently used ones are evicted, which is larger than usual since
+    nothing ever expires from these on its own.
+*/
+const SESSION_CAPACITY: u64 = 1024;
+
+/**
+    How many seconds a failed request stays cached, which is short so
+    that a single timeout or server error is retried soon after, but
+    still long enough to not retry on every keystroke.
+*/
+const FAILURE_SECONDS_TO_LIVE: u64 = 30;
 type Semaphores = Arc<DashMap<String, Arc<Semaphore>>>;
 
+/**
+    A cached value that may be a failure, such as the result of a request.
+
+    Failures are kept separately from other values, and only for a few
+    seconds, in every cache map, no matter how long other values live.
+*/
+pub trait CacheOutcome {
+    fn is_failure(&self) -> bool;
+}
+
+impl<T, E> CacheOutcome for Result<T, E> {
+    fn is_failure(&self) -> bool {
+        self.is_err()
+    }
+}
+
+fn failures_map<T: Clone + Send + Sync + 'static>() -> CacheMap<T> {
+    Cache::builder()
+        .max_capacity(64)
+        .time_to_live(Duration::from_secs(FAILURE_SECONDS_TO_LIVE))
+        .build()
+}
+
 /**
     Generic cache map for web requests.
 
@@ -17,7 +56,9 @@ type Semaphores = Arc<DashMap<String, Arc<Semaphore>>>;
 #[derive(Debug, Clone)]
 pub struct RequestCacheMap<T: Clone + Send + Sync + 'static> {
     map: CacheMap<T>,
+    failures: CacheMap<T>,
     sems: Semaphores,
+    metrics: Metrics,
 }
 
 impl<T: Clone + Send + Sync + 'static> RequestCacheMap<T> {
@@ -37,10 +78,37 @@ impl<T: Clone + Send + Sync + 'static> RequestCacheMap<T> {
             .build();
         RequestCacheMap {
             map,
+            failures: failures_map(),
             sems: Arc::new(DashMap::new()),
+            metrics: Metrics::default(),
         }
     }
 
+    /**
+        Creates a new cache map where cached data never expires on its own,
+        and is kept for the whole session until explicitly invalidated.
+
+        Used for data that users see change directly, such as versions
+        shown in completions, which should stay the same while editing.
+    */
+    pub fn for_session() -> Self {
+        let map = Cache::builder().max_capacity(SESSION_CAPACITY).build();
+        RequestCacheMap {
+            map,
+            failures: failures_map(),
+            sems: Arc::new(DashMap::new()),
+            metrics: Metrics::default(),
+        }
+    }
+
+    /**
+        Records cache hits and misses of this cache map in the given metrics.
+    */
+    pub fn with_metrics(mut self, metrics: &Metrics) -> Self {
+        self.metrics = metrics.clone();
+        self
+    }
+
     /**
         Invalidates the cache map.
 
@@ -49,6 +117,28 @@ impl<T: Clone + Send + Sync + 'static> RequestCacheMap<T> {
     */
     pub fn invalidate(&self) {
         self.map.invalidate_all();
+        self.failures.invalidate_all();
+    }
+
+    /**
+        Invalidates a single key in the cache map.
+
+        Any new request using `with_caching` with the same
+        key will fetch new data, while other keys stay cached.
+    */
+    pub async fn invalidate_key(&self, key: &str) {
+        self.map.invalidate(key).await;
+        self.failures.invalidate(key).await;
+    }
+
+    /**
+        Gets the value cached for the given key, if any, without fetching anything.
+    */
+    pub async fn get_cached(&self, key: &str) -> Option<T> {
+        match self.map.get(key).await {
+            Some(cached) => Some(cached),
+            None => self.failures.get(key).await,
+        }
     }
 
     /**
@@ -59,6 +149,8 @@ impl<T: Clone + Send + Sync + 'static> RequestCacheMap<T> {
         provided cache key. There is no guarantee that the future runs, since the result
         may have already been cached, so make sure it does not have any side effects.
 
+        Failed results are only cached for a few seconds, see [`CacheOutcome`].
+
         ### Example usage
 
         ```rust
@@ -86,12 +178,14 @@ impl<T: Clone + Send + Sync + 'static> RequestCacheMap<T> {
     pub async fn with_caching<F>(&self, key: impl Into<String>, f: F) -> T
     where
         F: Future<Output = T>,
+        T: CacheOutcome,
     {
         let key = key.into();
 
         // Return cached value right away if possible
-        if let Some(cached) = self.map.get(&key).await {
+        if let Some(cached) = self.get_cached(&key).await {
             trace!("Cache hit (1): {key}");
+            self.metrics.record(Metric::CacheHit);
             return cached.clone();
         }
 
@@ -105,15 +199,106 @@ impl<T: Clone + Send + Sync + 'static> RequestCacheMap<T> {
         let _guard = sem.acquire_arc().await;
 
         // We have permission, but the cache may have been updated, check again
-        if let Some(cached) = self.map.get(&key).await {
+        if let Some(cached) = self.get_cached(&key).await {
             trace!("Cache hit (2): {key}");
+            self.metrics.record(Metric::CacheHit);
             return cached.clone();
         }
 
         // Not cached, and we have permission, so perform the request
         trace!("Performing cached request: {key}");
+        self.metrics.record(Metric::CacheMiss);
         let result = f.await;
-        self.map.insert(key.clone(), result.clone()).await;
+        if result.is_failure() {
+            self.failures.insert(key.clone(), result.clone()).await;
+        } else {
+            self.map.insert(key.clone(), result.clone()).await;
+        }
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_session_cache_invalidation() {
+        let cache_map = RequestCacheMap::<Result<usize, ()>>::for_session();
+        let runs = AtomicUsize::new(0);
+        let fetch = || async { Ok(runs.fetch_add(1, Ordering::SeqCst)) };
+
+        assert_eq!(cache_map.with_caching("key", fetch()).await, Ok(0));
+        assert_eq!(cache_map.with_caching("key", fetch()).await, Ok(0));
+        assert_eq!(cache_map.with_caching("other", fetch()).await, Ok(1));
+
+        cache_map.invalidate();
+        assert_eq!(cache_map.with_caching("key", fetch()).await, Ok(2));
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_session_cache_invalidation_key() {
+        let cache_map = RequestCacheMap::<Result<usize, ()>>::for_session();
+        let runs = AtomicUsize::new(0);
+        let fetch = || async { Ok(runs.fetch_add(1, Ordering::SeqCst)) };
+
+        assert_eq!(cache_map.with_caching("key", fetch()).await, Ok(0));
+        assert_eq!(cache_map.with_caching("other", fetch()).await, Ok(1));
+
+        cache_map.invalidate_key("key").await;
+        assert_eq!(cache_map.with_caching("key", fetch()).await, Ok(2));
+        assert_eq!(cache_map.with_caching("other", fetch()).await, Ok(1));
+    }
+
+    #[tokio::test]
+    async fn test_failures_not_kept() {
+        let cache_map = RequestCacheMap::<Result<usize, usize>>::for_session();
+        let runs = AtomicUsize::new(0);
+        let fail = || async { Err(runs.fetch_add(1, Ordering::SeqCst)) };
+
+        // Failures are cached briefly, separately from successful values
+        assert_eq!(cache_map.with_caching("key", fail()).await, Err(0));
+        assert_eq!(cache_map.with_caching("key", fail()).await, Err(0));
+        cache_map.failures.invalidate_all();
+        assert_eq!(cache_map.with_caching("key", fail()).await, Err(1));
+        assert!(cache_map.map.get("key").await.is_none());
+
+        // ... and are cleared together with everything else
+        cache_map.invalidate_key("key").await;
+        let ok = async { Ok(5) };
+        assert_eq!(cache_map.with_caching("key", ok).await, Ok(5));
+        assert_eq!(cache_map.with_caching("key", fail()).await, Ok(5));
+    }
+
+    #[tokio::test]
+    async fn test_cache_metrics() {
+        let metrics = Metrics::default();
+        metrics.enable();
+        let cache_map = RequestCacheMap::<Result<usize, ()>>::for_session().with_metrics(&metrics);
+
+        cache_map
+            .with_caching("key", async { Ok(1) })
+            .await
+            .unwrap();
+        cache_map
+            .with_caching("key", async { Ok(2) })
+            .await
+            .unwrap();
+        cache_map
+            .with_caching("key", async { Ok(3) })
+            .await
+            .unwrap();
+        cache_map
+            .with_caching("other", async { Ok(4) })
+            .await
+            .unwrap();
+
+        let snapshot = metrics.snapshot().unwrap();
+        assert_eq!(snapshot.cache_hits, 2);
+        assert_eq!(snapshot.cache_misses, 2);
+        assert_eq!(snapshot.cache_hit_ratio, Some(0.5));
+    }
+}
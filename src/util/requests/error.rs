@@ -72,6 +72,30 @@ impl RequestError {
         }
     }
 
+    /**
+        Checks if the server responded that the request needs credentials,
+        or that the credentials which were sent are not valid.
+    */
+    pub fn is_auth_error(&self) -> bool {
+        if let RequestError::Response(e) = self {
+            e.status == StatusCode::UNAUTHORIZED || e.status == StatusCode::FORBIDDEN
+        } else {
+            false
+        }
+    }
+
+    /**
+        Checks if the request never got a response, or if the
+        server responded that it is currently unavailable.
+    */
+    pub fn is_unreachable_error(&self) -> bool {
+        match self {
+            RequestError::Client(_) => true,
+            RequestError::Response(e) => e.status.is_server_error(),
+            _ => false,
+        }
+    }
+
     pub fn is_rate_limit_error(&self) -> bool {
         if let RequestError::Response(e) = self {
             if e.status == StatusCode::TOO_MANY_REQUESTS {
@@ -0,0 +1,49 @@
+use std::{fmt, sync::Arc};
+
+use futures::future::BoxFuture;
+
+use super::{Request, RequestResult};
+
+/**
+    Something that can respond to requests, instead of sending them over the network.
+*/
+pub trait Fetch: Send + Sync + 'static {
+    fn fetch(&self, request: Request) -> BoxFuture<'static, RequestResult<Vec<u8>>>;
+}
+
+/**
+    Sends requests for clients - over the network by default, or using
+    a custom handler, such as one that gives canned responses in tests.
+
+    This fetcher is cheaply cloneable, and all clones share the same handler.
+*/
+#[derive(Clone, Default)]
+pub struct Fetcher {
+    handler: Option<Arc<dyn Fetch>>,
+}
+
+impl Fetcher {
+    /**
+        Creates a fetcher that sends all requests to the given handler.
+    */
+    pub fn new(handler: impl Fetch) -> Self {
+        Self {
+            handler: Some(Arc::new(handler)),
+        }
+    }
+
+    pub async fn send(&self, request: Request) -> RequestResult<Vec<u8>> {
+        match &self.handler {
+            Some(handler) => handler.fetch(request).await,
+            None => request.send().await,
+        }
+    }
+}
+
+impl fmt::Debug for Fetcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Fetcher")
+            .field("custom", &self.handler.is_some())
+            .finish()
+    }
+}
@@ -0,0 +1,61 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, RwLock,
+};
+
+use async_semaphore::{Semaphore, SemaphoreGuardArc};
+use tracing::debug;
+
+pub const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 8;
+
+/**
+    Limits how many web requests may run concurrently.
+
+    This limiter is cheaply cloneable, and all clones share the same limit.
+    Permits are released as soon as the guard returned from `acquire` is
+    dropped, which includes when a request future gets cancelled.
+*/
+#[derive(Debug, Clone)]
+pub struct RequestLimiter {
+    limit: Arc<AtomicUsize>,
+    semaphore: Arc<RwLock<Arc<Semaphore>>>,
+}
+
+impl RequestLimiter {
+    pub fn new(limit: usize) -> Self {
+        let limit = limit.max(1);
+        Self {
+            limit: Arc::new(AtomicUsize::new(limit)),
+            semaphore: Arc::new(RwLock::new(Arc::new(Semaphore::new(limit)))),
+        }
+    }
+
+    /**
+        Sets a new limit for concurrent requests.
+
+        Requests that are already running will keep their permits,
+        meaning the new limit fully applies once they have finished.
+    */
+    pub fn set_limit(&self, limit: usize) {
+        let limit = limit.max(1);
+        if self.limit.swap(limit, Ordering::SeqCst) != limit {
+            debug!("Setting request concurrency limit to {limit}");
+            *self.semaphore.write().expect("limiter lock was poisoned") =
+                Arc::new(Semaphore::new(limit));
+        }
+    }
+
+    /**
+        Waits for permission to perform a request.
+    */
+    pub async fn acquire(&self) -> SemaphoreGuardArc {
+        let semaphore = Arc::clone(&self.semaphore.read().expect("limiter lock was poisoned"));
+        semaphore.acquire_arc().await
+    }
+}
+
+impl Default for RequestLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CONCURRENT_REQUESTS)
+    }
+}
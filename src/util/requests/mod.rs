@@ -1,7 +1,11 @@
 mod cache_map;
 mod error;
+mod fetcher;
+mod limiter;
 mod request;
 
 pub use cache_map::*;
 pub use error::*;
+pub use fetcher::*;
+pub use limiter::*;
 pub use request::*;
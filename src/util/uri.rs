@@ -1,4 +1,5 @@
 use std::ffi::OsStr;
+use std::path::Path;
 
 use tower_lsp::lsp_types::Url;
 
@@ -8,8 +9,15 @@ where
 {
     fn file_name(&self) -> Option<String>;
     fn with_file_name(&self, file_name: impl AsRef<OsStr>) -> Option<Self>;
+    fn is_in_memory(&self) -> bool;
 }
 
+/**
+    Schemes used by clients for documents that only exist in memory,
+    such as new files that have not been saved anywhere yet.
+*/
+const IN_MEMORY_SCHEMES: [&str; 1] = ["untitled"];
+
 impl LspUriExt for Url {
     fn file_name(&self) -> Option<String> {
         if let Ok(file_path) = self.to_file_path() {
@@ -17,6 +25,13 @@ impl LspUriExt for Url {
                 return file_name.to_str().map(ToString::to_string);
             }
         }
+        // In-memory documents have no file path, but usually still end with a
+        // file name, such as `untitled:/drafts/Cargo.toml`, if one was picked
+        if self.is_in_memory() {
+            if let Some(file_name) = Path::new(self.path()).file_name() {
+                return file_name.to_str().map(ToString::to_string);
+            }
+        }
         None
     }
 
@@ -27,4 +42,8 @@ impl LspUriExt for Url {
         }
         None
     }
+
+    fn is_in_memory(&self) -> bool {
+        IN_MEMORY_SCHEMES.contains(&self.scheme())
+    }
 }
@@ -1,3 +1,5 @@
+use std::cmp::Ordering;
+
 use semver::{Error, Op, Version, VersionReq};
 
 /**
@@ -124,6 +126,37 @@ fn possible_versions_for_req(req: &VersionReq) -> Vec<Version> {
         .collect()
 }
 
+/**
+    Compares two versions by their precedence, as defined by semver, where build
+    metadata is ignored - `1.2.3+build.5` is neither older nor newer than `1.2.3`.
+
+    Note that the `Ord` implementation of `Version` is not the same, since it
+    also orders by build metadata, to stay consistent with its `Eq` implementation.
+*/
+pub fn cmp_precedence(a: &Version, b: &Version) -> Ordering {
+    (a.major, a.minor, a.patch, &a.pre).cmp(&(b.major, b.minor, b.patch, &b.pre))
+}
+
+/**
+    Orders versions for completions, by precedence and then by the raw version
+    string, so that the order is deterministic even for versions that compare as
+    equal. Versions that could not be parsed are only ordered by their raw strings.
+
+    This is also used to pick which versions end up being shown in completions,
+    before any of them are fully parsed, so both must always use this same order.
+*/
+pub fn cmp_completion_versions(
+    a: Option<&Version>,
+    a_raw: &str,
+    b: Option<&Version>,
+    b_raw: &str,
+) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => cmp_precedence(a, b).then_with(|| a_raw.cmp(b_raw)),
+        _ => a_raw.cmp(b_raw),
+    }
+}
+
 fn trim_version_specifiers(s: String) -> String {
     s.trim_start_matches('^')
         .trim_start_matches('>')
@@ -133,6 +166,55 @@ fn trim_version_specifiers(s: String) -> String {
         .to_string()
 }
 
+/**
+    Gets the first version in a version requirement as it was typed,
+    without any operators, such as `1.2` for both `^1.2` and `>= 1.2, <2`.
+*/
+fn partial_version(s: &str) -> &str {
+    let rest = s.trim().trim_start_matches(['^', '~', '=', '>', '<', ' ']);
+    let len = rest
+        .find(|c: char| c.is_whitespace() || c == ',' || c == '|')
+        .unwrap_or(rest.len());
+    &rest[..len]
+}
+
+/**
+    Checks if a version matches a partially typed version, component by
+    component - every component must be equal, except for the last one,
+    which only needs to be a prefix, since it may still be getting typed.
+
+    This means that `1.` matches `1.0.0` and `1.9.9`, but not `10.0.0`,
+    and that `1.2` matches both `1.2.3` and `1.20.0`. Partial versions
+    with pre-release or build metadata are matched as plain prefixes.
+*/
+fn matches_partial_version(partial: &str, version: &str) -> bool {
+    let partial = partial.trim_end_matches(['*', 'x', 'X']);
+    if partial.is_empty() {
+        return true;
+    }
+    if partial.contains(['-', '+']) {
+        return version.starts_with(partial);
+    }
+
+    let core = version.split(['-', '+']).next().unwrap_or(version);
+    let mut typed = partial.split('.').peekable();
+    let mut components = core.split('.');
+    while let Some(typed_component) = typed.next() {
+        let Some(component) = components.next() else {
+            return false;
+        };
+        let matches = if typed.peek().is_some() {
+            component == typed_component
+        } else {
+            component.starts_with(typed_component)
+        };
+        if !matches {
+            return false;
+        }
+    }
+    true
+}
+
 /**
     The latest found version from a comparison.
 
@@ -207,12 +289,12 @@ pub trait Versioned {
             })
             .collect::<Vec<_>>();
 
-        other_versions.sort_by_key(|(_, v)| v.clone());
+        // Build metadata is only used to break ties, so that the
+        // same latest version is always picked out of equal ones
+        other_versions.sort_by(|(_, a), (_, b)| cmp_precedence(a, b).then_with(|| a.cmp(b)));
 
         other_versions.pop().map(|(item, item_version)| {
-            let is_exactly_compatible = item_version
-                .to_string()
-                .eq_ignore_ascii_case(&this_version.to_string());
+            let is_exactly_compatible = cmp_precedence(&item_version, &this_version).is_eq();
             LatestVersion {
                 is_semver_compatible: is_exactly_compatible
                     || this_version_req.is_some_and(|req| req.matches(&item_version)),
@@ -224,6 +306,14 @@ pub trait Versioned {
         })
     }
 
+    /**
+        Checks if the given version would be offered as a completion for this
+        version, using the same check as `extract_completion_versions` does.
+    */
+    fn matches_completion_version(&self, version: &str) -> bool {
+        matches_partial_version(partial_version(&self.raw_version_string()), version)
+    }
+
     fn extract_completion_versions<I, V>(&self, potential_versions: I) -> Vec<CompletionVersion<V>>
     where
         I: IntoIterator<Item = V>,
@@ -234,33 +324,22 @@ pub trait Versioned {
             Err(_) => trim_version_specifiers(self.raw_version_string()), // Tries to still remove prefixes, less correct
         };
 
+        // Filter using the version exactly as typed, since a partial version
+        // such as `1.` or `1.2` is not the same as its minimum version `1.0.0`
+        let raw = self.raw_version_string();
+        let partial = partial_version(&raw);
         let mut potential_versions = potential_versions
             .into_iter()
-            .filter_map(|item| {
-                if this_version_raw.is_empty() {
-                    return Some(item);
-                }
-
-                let item_version = item.raw_version_string();
-                if this_version_raw.len() <= item_version.len()
-                    && item_version.starts_with(&this_version_raw)
-                {
-                    Some(item)
-                } else {
-                    None
-                }
-            })
+            .filter(|item| matches_partial_version(partial, &item.raw_version_string()))
             .collect::<Vec<_>>();
 
-        potential_versions.sort_unstable_by(|a, b| {
-            if let Ok(v_a) = a.parse_version() {
-                if let Ok(v_b) = b.parse_version() {
-                    return v_a.cmp(&v_b);
-                }
-            }
-            let s_a = a.raw_version_string();
-            let s_b = b.raw_version_string();
-            s_a.cmp(&s_b)
+        potential_versions.sort_by(|a, b| {
+            cmp_completion_versions(
+                a.parse_version().ok().as_ref(),
+                &a.raw_version_string(),
+                b.parse_version().ok().as_ref(),
+                &b.raw_version_string(),
+            )
         });
 
         potential_versions.reverse(); // Latest versions first
@@ -301,3 +380,87 @@ impl Versioned for &str {
         self.to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_version() {
+        assert_eq!(partial_version("1."), "1.");
+        assert_eq!(partial_version("^1.2"), "1.2");
+        assert_eq!(partial_version(">= 1.2, <2"), "1.2");
+        assert_eq!(partial_version(""), "");
+    }
+
+    #[test]
+    fn test_matches_partial_version() {
+        assert!(matches_partial_version("1.", "1.0.0"));
+        assert!(matches_partial_version("1.", "1.9.9"));
+        assert!(!matches_partial_version("1.", "10.0.0"));
+        assert!(matches_partial_version("1.2.", "1.2.10"));
+        assert!(!matches_partial_version("1.2.", "1.20.0"));
+        assert!(matches_partial_version("1.2", "1.20.0"));
+        assert!(matches_partial_version("1", "10.0.0"));
+        assert!(matches_partial_version("1.*", "1.4.0"));
+        assert!(matches_partial_version("1.0.0-al", "1.0.0-alpha.1"));
+        assert!(!matches_partial_version("1.0.0-al", "1.0.0"));
+        assert!(matches_partial_version("", "0.1.0"));
+    }
+
+    #[test]
+    fn test_cmp_precedence() {
+        let v = |s: &str| Version::parse(s).unwrap();
+        assert!(cmp_precedence(&v("1.2.3"), &v("1.2.3+x")).is_eq());
+        assert!(cmp_precedence(&v("1.2.3-rc.1"), &v("1.2.3")).is_lt());
+        assert!(cmp_precedence(&v("1.2.3-rc.1"), &v("1.2.3-rc.2")).is_lt());
+        assert!(cmp_precedence(&v("1.2.3+x"), &v("1.2.4-rc.1")).is_lt());
+        assert!(cmp_precedence(&v("1.2.4-rc.1"), &v("1.2.4")).is_lt());
+    }
+
+    #[test]
+    fn test_latest_version_build_metadata() {
+        // Only build metadata differs, so the current version is not outdated
+        let latest = "1.2.3"
+            .extract_latest_version(["1.2.3+x", "1.2.2"])
+            .unwrap();
+        assert!(latest.is_exactly_compatible);
+        assert_eq!(latest.item_version.to_string(), "1.2.3+x");
+
+        let latest = "1.2.3+x".extract_latest_version(["1.2.3"]).unwrap();
+        assert!(latest.is_exactly_compatible);
+    }
+
+    #[test]
+    fn test_latest_version_prerelease() {
+        let versions = ["1.2.3", "1.2.3+x", "1.2.3-rc.1", "1.2.4-rc.1"];
+
+        // Prereleases of other versions are never the latest version
+        let latest = "1.2.3".extract_latest_version(versions).unwrap();
+        assert_eq!(latest.item_version.to_string(), "1.2.3+x");
+        assert!(latest.is_exactly_compatible);
+
+        // The release is newer than any of its own prereleases
+        let latest = "1.2.3-rc.1".extract_latest_version(versions).unwrap();
+        assert_eq!(latest.item_version.to_string(), "1.2.3+x");
+        assert!(!latest.is_exactly_compatible);
+
+        let latest = "1.2.4-rc.1".extract_latest_version(versions).unwrap();
+        assert_eq!(latest.item_version.to_string(), "1.2.4-rc.1");
+        assert!(latest.is_exactly_compatible);
+    }
+
+    #[test]
+    fn test_completion_versions_order() {
+        let versions = ["1.2.3+x", "1.2.4-rc.1", "1.2.3-rc.1", "1.2.3"];
+        let ordered = ""
+            .extract_completion_versions(versions)
+            .into_iter()
+            .map(|v| v.item_version_raw)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ordered,
+            vec!["1.2.4-rc.1", "1.2.3+x", "1.2.3", "1.2.3-rc.1"]
+        );
+    }
+}